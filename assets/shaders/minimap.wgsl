@@ -0,0 +1,21 @@
+#import bevy_ui::ui_vertex_output::UiVertexOutput
+
+@group(1) @binding(0) var texture: texture_2d<f32>;
+@group(1) @binding(1) var texture_sampler: sampler;
+@group(1) @binding(2) var<uniform> rim_color: vec4<f32>;
+
+// fraction of the radius that's rim
+const RIM_WIDTH: f32 = 0.04;
+
+@fragment
+fn fragment(in: UiVertexOutput) -> @location(0) vec4<f32> {
+    // `0.0` in the middle, `1.0` at the edge
+    let r = distance(in.uv, vec2<f32>(0.5, 0.5)) * 2.0;
+    if r > 1.0 {
+        discard;
+    }
+    if r > 1.0 - RIM_WIDTH {
+        return rim_color;
+    }
+    return textureSample(texture, texture_sampler, in.uv);
+}
@@ -28,6 +28,36 @@ var sketch_texture_array: texture_2d_array<f32>;
 @group(2) @binding(103)
 var sketch_texture_array_sampler: sampler;
 
+#ifdef DISSOLVE
+@group(2) @binding(105)
+var<uniform> dissolve: f32;
+
+@group(2) @binding(106)
+var<uniform> dissolve_edge_color: vec4<f32>;
+
+// how much of the noise range glows before it's cut off
+const DISSOLVE_EDGE: f32 = 0.05;
+// noise cells across the uv
+const DISSOLVE_SCALE: f32 = 12.0;
+
+fn dissolve_hash(p: vec2<f32>) -> f32 {
+    return fract(sin(dot(p, vec2<f32>(127.1, 311.7))) * 43758.5453);
+}
+
+// smoothed value noise in [0, 1]
+fn dissolve_noise(uv: vec2<f32>) -> f32 {
+    let p = uv * DISSOLVE_SCALE;
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(dissolve_hash(i), dissolve_hash(i + vec2<f32>(1.0, 0.0)), u.x),
+        mix(dissolve_hash(i + vec2<f32>(0.0, 1.0)), dissolve_hash(i + vec2<f32>(1.0, 1.0)), u.x),
+        u.y,
+    );
+}
+#endif
+
 @fragment
 fn fragment(
     in: VertexOutput,
@@ -41,6 +71,14 @@ fn fragment(
     }
 #endif
 
+    // cull anything under the dissolve threshold
+#ifdef DISSOLVE
+    let noise = dissolve_noise(in.uv);
+    if (noise < dissolve) {
+        discard;
+    }
+#endif
+
     // generate a PbrInput struct from the StandardMaterial bindings
     var pbr_input = pbr_input_from_standard_material(in, is_front);
 
@@ -67,6 +105,13 @@ fn fragment(
     // apply in-shader post processing (fog, alpha-premultiply, and also tonemapping, debanding if the camera is non-hdr)
     // note this does not include fullscreen postprocessing effects like bloom.
     out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+
+    // glow right at the edge of the dissolve
+#ifdef DISSOLVE
+    if (dissolve > 0.0 && noise < dissolve + DISSOLVE_EDGE) {
+        out.color = vec4<f32>(dissolve_edge_color.rgb, out.color.a);
+    }
+#endif
 #endif
 
     return out;
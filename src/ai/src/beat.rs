@@ -0,0 +1,132 @@
+//! Keeping time with music.
+
+use bevy::prelude::*;
+use grin_physics::PhysicsTime;
+use grin_time::scaling::TimeScale;
+
+pub struct BeatPlugin;
+
+impl Plugin for BeatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<BeatEvent>()
+            .add_systems(Update, tick_beat_clocks);
+    }
+}
+
+/// Counts the beats of whatever's playing on this entity.
+///
+/// It follows the `AudioSink` or `SpatialAudioSink` on the same entity, so it slows down, speeds
+/// up and pauses along with what you can actually hear. Without one it goes by `PhysicsTime` and
+/// `TimeScale`. The sink only shows up once the track loads, so `position` starts over then.
+#[derive(Component, Debug, Clone)]
+pub struct BeatClock {
+    pub bpm: f32,
+    /// Seconds into the track where the first beat is.
+    pub offset: f32,
+    /// The first beat of every bar is the downbeat.
+    pub beats_per_bar: u32,
+    /// Seconds of the track that have played.
+    pub position: f32,
+}
+
+impl BeatClock {
+    /// In 4/4.
+    pub fn new(bpm: f32, offset: f32) -> Self {
+        Self {
+            bpm,
+            offset,
+            beats_per_bar: 4,
+            position: 0.0,
+        }
+    }
+
+    pub fn seconds_per_beat(&self) -> f32 {
+        60.0 / self.bpm
+    }
+
+    /// Beats since the first one. Negative before `offset`.
+    pub fn beats(&self) -> f32 {
+        (self.position - self.offset) / self.seconds_per_beat()
+    }
+
+    pub fn is_downbeat(&self, beat_index: u64) -> bool {
+        beat_index % self.beats_per_bar.max(1) as u64 == 0
+    }
+
+    /// Moves `seconds` further into the track. Returns the beats that went by.
+    pub fn advance(&mut self, seconds: f32) -> std::ops::Range<u64> {
+        let before = self.beats();
+        self.position += seconds;
+        let after = self.beats();
+        before.ceil().max(0.0) as u64..after.ceil().max(0.0) as u64
+    }
+}
+
+/// Sent by a `BeatClock` on every beat.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeatEvent {
+    pub entity: Entity,
+    pub beat_index: u64,
+    pub is_downbeat: bool,
+}
+
+pub fn tick_beat_clocks(
+    real_time: Res<Time<Real>>,
+    physics_time: Res<PhysicsTime>,
+    mut clock_query: Query<(
+        Entity,
+        &mut BeatClock,
+        Option<Ref<AudioSink>>,
+        Option<Ref<SpatialAudioSink>>,
+        Option<&TimeScale>,
+    )>,
+    mut beat_events: EventWriter<BeatEvent>,
+) {
+    for (entity, mut clock, sink, spatial_sink, time_scale) in clock_query.iter_mut() {
+        // the track starts when the sink shows up, not when the clock does
+        if sink.as_ref().is_some_and(Ref::is_added)
+            || spatial_sink.as_ref().is_some_and(Ref::is_added)
+        {
+            clock.position = 0.0;
+        }
+
+        let sink = sink
+            .as_deref()
+            .map(|s| s as &dyn AudioSinkPlayback)
+            .or(spatial_sink.as_deref().map(|s| s as &dyn AudioSinkPlayback));
+        let seconds = match sink {
+            Some(sink) if sink.is_paused() => 0.0,
+            Some(sink) => real_time.delta_seconds() * sink.speed(),
+            None => physics_time.0.delta_seconds() * time_scale.map_or(1.0, f32::from),
+        };
+
+        for beat_index in clock.advance(seconds) {
+            beat_events.send(BeatEvent {
+                entity,
+                beat_index,
+                is_downbeat: clock.is_downbeat(beat_index),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beats() {
+        let mut clock = BeatClock::new(120.0, 0.25);
+        assert!(clock.advance(0.2).is_empty(), "Beat before the offset.");
+        assert_eq!(clock.advance(0.1), 0..1, "Missed the first beat.");
+        assert_eq!(
+            clock.advance(2.0),
+            1..5,
+            "Wrong beats after a couple seconds."
+        );
+        assert!(
+            clock.is_downbeat(0) && clock.is_downbeat(4) && !clock.is_downbeat(3),
+            "Wrong downbeats."
+        );
+    }
+}
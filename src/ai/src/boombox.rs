@@ -1,6 +1,9 @@
-use std::f32::consts::PI;
+use std::{
+    f32::consts::{FRAC_PI_2, PI},
+    time::Duration,
+};
 
-use bevy::prelude::*;
+use bevy::{ecs::entity::EntityHashSet, pbr::NotShadowCaster, prelude::*};
 use bevy_asset_loader::prelude::*;
 use bevy_enum_filter::prelude::*;
 use bevy_landmass::Agent;
@@ -8,27 +11,40 @@ use bevy_rapier3d::prelude::*;
 use grin_asset::AssetLoadState;
 use grin_character::PlayerCharacter;
 use grin_damage::{
-    health::Dead,
+    explosion::Falloff,
+    health::{DamageBuffer, Dead},
     hit::{Damage, DamageVariant},
-    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor},
+    hitbox::Hitbox,
+    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor, ProjectilePool},
+};
+use grin_map::{obstacle::NavObstacle, MapData};
+use grin_physics::{
+    CollisionGroupExt, CollisionGroupsExt, ForceEasing, PhysicsTime, TimedForce, TimedForceStack,
 };
-use grin_map::MapData;
-use grin_physics::ForceTimer;
 use grin_rig::humanoid::{Humanoid, HumanoidBundle, HumanoidDominantHand, HUMANOID_RADIUS};
-use grin_time::Rewind;
+use grin_time::{
+    scaling::{TimeScale, TimeScaleImmune},
+    Rewind,
+};
 use grin_util::{
     distr,
-    event::Spawnable,
+    event::{SpawnAppExt, Spawnable},
     query::gltf_path_search,
+    rng::GrinRng,
     vectors::{self, Vec3Ext},
 };
 
 use super::{
+    beat::{tick_beat_clocks, BeatClock, BeatEvent},
     bt::{BehaviorIteration, BehaviorSet, Brain, Verdict},
     configure_humanoid_physics,
     dummy::{dummy_ai_filters, DummyAi, ShotCooldown},
-    movement::{match_desired_velocity, propagate_attack_target_to_agent_target},
-    protective_cooldown, set_closest_attack_target, AiSet, EnemyAgentBundle,
+    movement::{
+        match_desired_velocity, patrol, propagate_attack_target_to_agent_target, PatrolRoute,
+    },
+    protective_cooldown, set_closest_attack_target,
+    telegraph::{tick_telegraphs, Telegraph, TelegraphFinishedEvent, TelegraphStyle},
+    AiSet, EnemyAgentBundle,
 };
 
 pub struct BoomBoxPlugin;
@@ -36,10 +52,20 @@ pub struct BoomBoxPlugin;
 impl Plugin for BoomBoxPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<BoomBoxSpawnEvent>()
+            .register_spawnable::<BoomBox>("boombox")
             .configure_loading_state(
                 LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<BoomBoxAssets>(),
             )
             .add_systems(Update, spawn.in_set(AiSet::Spawn))
+            .add_systems(
+                Update,
+                (
+                    telegraph_pulses::<BoomBox>.after(tick_beat_clocks),
+                    fire::<BoomBox>.after(tick_telegraphs),
+                    expand_shockwaves,
+                    (wind_down_music::<BoomBox>, tick_music_wind_downs).chain(),
+                ),
+            )
             .add_systems(
                 PreUpdate,
                 (load, configure_humanoid_physics::<BoomBox>).in_set(AiSet::Load),
@@ -51,7 +77,8 @@ impl Plugin for BoomBoxPlugin {
                     propagate_attack_target_to_agent_target::<BoomBox, Enum!(DummyAi::Target)>,
                     protective_cooldown::<BoomBox, Enum!(DummyAi::FireCheck), ShotCooldown>,
                     match_desired_velocity::<BoomBox, Enum!(DummyAi::Chase)>,
-                    fire::<BoomBox, Enum!(DummyAi::Fire)>,
+                    patrol::<BoomBox, Enum!(DummyAi::Patrol)>,
+                    queue_pulses::<BoomBox, Enum!(DummyAi::Fire)>,
                 )
                     .in_set(BehaviorSet::Act),
             );
@@ -69,11 +96,23 @@ pub struct BoomBoxAssets {
     pub idle_lt: Handle<AnimationClip>,
     #[asset(key = "anim.boombox.right")]
     pub idle_rt: Handle<AnimationClip>,
+    #[asset(key = "music.boombox")]
+    pub music: Handle<AudioSource>,
 }
 
 #[derive(Event, Clone, Default)]
 pub struct BoomBoxSpawnEvent {
     pub transform: Transform,
+    pub patrol: Option<PatrolRoute>,
+}
+
+impl From<Transform> for BoomBoxSpawnEvent {
+    fn from(transform: Transform) -> Self {
+        Self {
+            transform,
+            ..Default::default()
+        }
+    }
 }
 
 impl Spawnable for BoomBox {
@@ -85,15 +124,21 @@ pub fn spawn(
     map_data: Res<MapData>,
     mut events: EventReader<BoomBoxSpawnEvent>,
     assets: Res<BoomBoxAssets>,
+    mut rng: ResMut<GrinRng>,
 ) {
-    for BoomBoxSpawnEvent { transform } in events.read() {
-        commands.spawn((
+    for BoomBoxSpawnEvent { transform, patrol } in events.read() {
+        let mut e_boombox_commands = commands.spawn((
             BoomBox,
             ShotCooldown::default(),
+            BeatClock::new(BOOMBOX_BPM, 0.0),
+            AudioBundle {
+                source: assets.music.clone(),
+                settings: PlaybackSettings::LOOP.with_spatial(true),
+            },
             HumanoidBundle {
                 rig: assets.rig.clone(),
                 spatial: SpatialBundle::from_transform(transform.clone()),
-                ..Default::default()
+                ..HumanoidBundle::random(&mut rng)
             },
             EnemyAgentBundle::<DummyAi> {
                 agent: Agent {
@@ -103,6 +148,9 @@ pub fn spawn(
                 ..EnemyAgentBundle::from_archipelago(map_data.archipelago)
             },
         ));
+        if let Some(patrol) = patrol {
+            e_boombox_commands.insert(patrol.clone());
+        }
     }
 }
 
@@ -150,6 +198,29 @@ pub fn load(
     }
 }
 
+/// Tempo of `BoomBoxAssets::music`. The loop is one bar of 4/4 at this tempo.
+pub const BOOMBOX_BPM: f32 = 120.0;
+
+pub const PULSE_TELEGRAPH_COLOR: Color = Color::RED;
+
+/// Shockwaves go out this far.
+pub const SHOCKWAVE_RADIUS: f32 = 8.0;
+/// Units per second.
+pub const SHOCKWAVE_SPEED: f32 = 10.0;
+/// Thickness of the ring.
+pub const SHOCKWAVE_WIDTH: f32 = 0.5;
+/// Things more than this far above or below the ring don't get hit. So, it can be jumped.
+pub const SHOCKWAVE_HEIGHT: f32 = 0.5;
+pub const SHOCKWAVE_DAMAGE: f32 = 15.0;
+pub const SHOCKWAVE_COLOR: Color = Color::rgba(1.0, 0.0, 0.0, 0.5);
+/// `NavObstacle::cost` of a `Shockwave`. Agents would rather not walk into one.
+pub const SHOCKWAVE_NAV_COST: f32 = 4.0;
+
+/// Seconds that the music takes to die down after the boombox does.
+pub const MUSIC_WIND_DOWN: f32 = 1.5;
+/// Playback speed at the end of `MUSIC_WIND_DOWN`.
+pub const MUSIC_WIND_DOWN_SPEED: f32 = 0.2;
+
 pub const BULLET_SIZE: f32 = 0.5;
 pub const END_SPEED: f32 = 5.0;
 pub const DRAG_DURATION: f32 = 0.5;
@@ -173,41 +244,289 @@ pub fn create_bullet(source: Entity, transform: Transform) -> impl Bundle {
             mass_properties: ColliderMassProperties::Mass(1.0),
             ..ProjectileBundle::enemy_default()
         },
-        ExternalForce {
-            force: transform.forward() * DRAG,
-            ..Default::default()
-        },
-        ForceTimer::from_seconds(DRAG_DURATION),
+        ExternalForce::default(),
+        TimedForceStack(vec![TimedForce::new(
+            transform.forward() * DRAG,
+            Duration::from_secs_f32(DRAG_DURATION),
+            ForceEasing::default(),
+        )]),
     )
 }
 
-pub fn fire<T: Component, A: Component>(
+/// Waits for the bar before the next downbeat to start the pulse telegraph. See `telegraph_pulses`.
+#[derive(Component, Debug, Default)]
+#[component(storage = "SparseSet")]
+pub struct PulseQueued;
+
+pub fn queue_pulses<T: Component, A: Component>(
     mut commands: Commands,
     mut agent_query: Query<
-        (Entity, &mut Brain, &Humanoid),
+        (Entity, &mut Brain, Has<Telegraph>),
         (With<T>, With<A>, Without<Rewind>, Without<Dead>),
     >,
+) {
+    for (e_agent, mut brain, winding_up) in agent_query.iter_mut() {
+        if !winding_up {
+            commands.entity(e_agent).insert(PulseQueued);
+        }
+        brain.write_verdict(Verdict::Success);
+    }
+}
+
+/// Starts the telegraph for a `PulseQueued` agent one beat before a downbeat. It lasts one beat,
+/// so `fire` lands right on the downbeat.
+pub fn telegraph_pulses<T: Component>(
+    mut commands: Commands,
+    agent_query: Query<&BeatClock, (With<T>, With<PulseQueued>, Without<Rewind>, Without<Dead>)>,
+    mut beat_events: EventReader<BeatEvent>,
+) {
+    for &BeatEvent {
+        entity: e_agent,
+        beat_index,
+        ..
+    } in beat_events.read()
+    {
+        let Ok(clock) = agent_query.get(e_agent) else {
+            continue;
+        };
+        if !clock.is_downbeat(beat_index + 1) {
+            continue;
+        }
+
+        commands
+            .entity(e_agent)
+            .remove::<PulseQueued>()
+            .insert(Telegraph::new(
+                clock.seconds_per_beat(),
+                TelegraphStyle::Tint {
+                    color: PULSE_TELEGRAPH_COLOR,
+                },
+            ));
+    }
+}
+
+/// Fires the bullet ring and a `Shockwave` once the pulse telegraph is over.
+pub fn fire<T: Component>(
+    mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    agent_query: Query<&Humanoid, With<T>>,
     transform_query: Query<&GlobalTransform>,
+    mut telegraph_events: EventReader<TelegraphFinishedEvent>,
 ) {
-    for (e_agent, mut brain, humanoid) in agent_query.iter_mut() {
+    for &TelegraphFinishedEvent { entity: e_agent } in telegraph_events.read() {
+        let Ok(humanoid) = agent_query.get(e_agent) else {
+            continue;
+        };
+
+        let ground =
+            Transform::from_translation(transform_query.get(e_agent).unwrap().translation());
+        commands
+            .spawn((
+                Shockwave::new(Damage {
+                    ty: DamageVariant::Ballistic,
+                    value: SHOCKWAVE_DAMAGE,
+                    source: Some(e_agent),
+                }),
+                CollisionGroups::from_group_default(Group::ENEMY_PROJECTILE),
+                NavObstacle {
+                    shape: Collider::ball(SHOCKWAVE_RADIUS),
+                    cost: Some(SHOCKWAVE_NAV_COST),
+                },
+                SpatialBundle {
+                    transform: ground,
+                    global_transform: ground.into(),
+                    ..Default::default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    ShockwaveRing,
+                    NotShadowCaster,
+                    PbrBundle {
+                        mesh: meshes.add(Circle::new(1.0)),
+                        material: materials.add(StandardMaterial {
+                            base_color: SHOCKWAVE_COLOR,
+                            unlit: true,
+                            alpha_mode: AlphaMode::Blend,
+                            ..Default::default()
+                        }),
+                        // `Circle` faces +Z
+                        transform: Transform::from_rotation(Quat::from_rotation_x(-FRAC_PI_2))
+                            .with_scale(Vec3::ZERO),
+                        ..Default::default()
+                    },
+                ));
+            });
+
         let origin = transform_query.get(humanoid.dominant_hand()).unwrap();
         let transform = Transform::from_translation(origin.translation());
 
-        commands.spawn_batch(
-            vectors::circle(
-                origin.forward().xz_flat().normalize_or_zero(),
-                Vec3::Y,
-                16,
-                &distr::linear,
-            )
-            .map(move |dir| {
-                create_bullet(
-                    e_agent,
-                    transform.looking_to(dir, dir.any_orthogonal_vector()),
-                )
-            }),
+        for dir in vectors::circle(
+            origin.forward().xz_flat().normalize_or_zero(),
+            Vec3::Y,
+            16,
+            &distr::linear,
+        ) {
+            pool.acquire(&mut commands).insert(create_bullet(
+                e_agent,
+                transform.looking_to(dir, dir.any_orthogonal_vector()),
+            ));
+        }
+    }
+}
+
+/// A ring that spreads out along the ground, hitting everything it passes over once.
+#[derive(Component, Debug, Clone)]
+pub struct Shockwave {
+    pub damage: Damage,
+    pub falloff: Falloff,
+    pub max_radius: f32,
+    /// Units per second.
+    pub speed: f32,
+    /// Current radius.
+    pub radius: f32,
+    /// `Hitbox` targets that have been hit.
+    pub hits: EntityHashSet,
+}
+
+impl Shockwave {
+    pub fn new(damage: Damage) -> Self {
+        Self {
+            damage,
+            falloff: Falloff::Linear,
+            max_radius: SHOCKWAVE_RADIUS,
+            speed: SHOCKWAVE_SPEED,
+            radius: 0.0,
+            hits: EntityHashSet::default(),
+        }
+    }
+}
+
+/// The visible part of a `Shockwave`. It's scaled to the radius.
+#[derive(Component, Debug, Default)]
+pub struct ShockwaveRing;
+
+/// Grows `Shockwave`s and pushes their damage into anything the ring passed this frame,
+/// filtered by the shockwave's `CollisionGroups`.
+pub fn expand_shockwaves(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    rapier_context: Res<RapierContext>,
+    mut shockwave_query: Query<
+        (
+            Entity,
+            &mut Shockwave,
+            &GlobalTransform,
+            Option<&CollisionGroups>,
+            Option<&TimeScale>,
+            Option<&Children>,
+        ),
+        Without<Rewind>,
+    >,
+    hitbox_query: Query<&Hitbox>,
+    transform_query: Query<&GlobalTransform>,
+    mut hit_query: Query<&mut DamageBuffer>,
+    mut ring_query: Query<&mut Transform, With<ShockwaveRing>>,
+) {
+    for (e_shockwave, mut shockwave, g_transform, groups, time_scale, children) in
+        shockwave_query.iter_mut()
+    {
+        let r0 = shockwave.radius;
+        shockwave.radius = (shockwave.radius
+            + shockwave.speed * time.0.delta_seconds() * time_scale.map_or(1.0, f32::from))
+        .min(shockwave.max_radius);
+        let r1 = shockwave.radius;
+        let origin = g_transform.translation();
+
+        let mut targets = Vec::new();
+        rapier_context.intersections_with_shape(
+            origin,
+            Quat::IDENTITY,
+            &Collider::ball(r1),
+            QueryFilter::new().groups(groups.copied().unwrap_or_default()),
+            |e_hit| {
+                targets.push(hitbox_query.get(e_hit).map_or(e_hit, |h| h.target));
+                true
+            },
         );
 
-        brain.write_verdict(Verdict::Success);
+        for e_target in targets {
+            if shockwave.hits.contains(&e_target) {
+                continue;
+            }
+            let Ok(g_target_transform) = transform_query.get(e_target) else {
+                continue;
+            };
+            let offset = g_target_transform.translation() - origin;
+            let distance = offset.xz().length();
+            // the ring hasn't gotten there yet, or it's already gone by
+            if distance > r1 || distance < r0 - SHOCKWAVE_WIDTH {
+                continue;
+            }
+            // jumped over it
+            if offset.y.abs() > SHOCKWAVE_HEIGHT {
+                continue;
+            }
+
+            shockwave.hits.insert(e_target);
+            let Ok(mut damage_buf) = hit_query.get_mut(e_target) else {
+                continue;
+            };
+            let damage = Damage {
+                value: shockwave.damage.value
+                    * shockwave.falloff.scale(distance, shockwave.max_radius),
+                ..shockwave.damage
+            };
+            trace!(msg="Shockwave hit.", receiver=?e_target, dmg=?damage);
+            damage_buf.0.push(damage);
+        }
+
+        for &e_ring in children.into_iter().flatten() {
+            if let Ok(mut transform) = ring_query.get_mut(e_ring) {
+                transform.scale = Vec3::splat(r1);
+            }
+        }
+
+        if r1 >= shockwave.max_radius {
+            commands.entity(e_shockwave).despawn_recursive();
+        }
+    }
+}
+
+/// Slows the music on a dying `T` down to a stop.
+#[derive(Component, Debug, Clone)]
+pub struct MusicWindDown(pub Timer);
+
+/// `TimeScaleImmune` keeps `scale_audio` from touching the speed while it winds down.
+pub fn wind_down_music<T: Component>(
+    mut commands: Commands,
+    agent_query: Query<Entity, (With<T>, Added<Dead>, With<SpatialAudioSink>)>,
+) {
+    for e_agent in agent_query.iter() {
+        commands.entity(e_agent).insert((
+            TimeScaleImmune,
+            MusicWindDown(Timer::from_seconds(MUSIC_WIND_DOWN, TimerMode::Once)),
+        ));
+    }
+}
+
+pub fn tick_music_wind_downs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut sink_query: Query<(Entity, &mut MusicWindDown, &SpatialAudioSink)>,
+) {
+    for (e_agent, mut wind_down, sink) in sink_query.iter_mut() {
+        wind_down.0.tick(time.delta());
+        if wind_down.0.finished() {
+            sink.stop();
+            commands.entity(e_agent).remove::<MusicWindDown>();
+            continue;
+        }
+
+        let t = wind_down.0.fraction();
+        sink.set_speed(1.0 + (MUSIC_WIND_DOWN_SPEED - 1.0) * t);
+        sink.set_volume(1.0 - t);
     }
 }
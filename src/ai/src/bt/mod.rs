@@ -4,8 +4,10 @@ use std::{marker::PhantomData, mem};
 
 use bevy::{ecs::schedule::ScheduleLabel, prelude::*};
 use bevy_enum_filter::prelude::*;
+use grin_damage::status::{Disoriented, Stunned};
+use grin_physics::PhysicsTime;
 
-use self::tree::{BehaviorOutput, BehaviorTree, OutVerdict};
+use self::tree::{BehaviorOutput, BehaviorTree, OutVerdict, TreeState};
 
 use super::AiSet;
 
@@ -106,7 +108,15 @@ impl Plugin for MasterBehaviorPlugin {
             (BehaviorSet::Act, BehaviorSet::Think).chain(),
         )
         .add_systems(Update, ai_schedule_runner.in_set(AiSet::RunTrees))
-        .add_systems(PreBehaviorIteration, init_behavior_update);
+        .add_systems(PreBehaviorIteration, init_behavior_update)
+        .add_systems(
+            BehaviorIteration,
+            (fail_stunned, fail_disoriented)
+                .after(BehaviorSet::Act)
+                .before(BehaviorSet::Think),
+        )
+        // `Cooldown` decorators
+        .init_resource::<PhysicsTime>();
     }
 }
 
@@ -170,6 +180,8 @@ pub struct Brain {
     visiting_node: usize,
     verdict: Verdict,
     changed: bool,
+    /// This agent's decorator memory.
+    tree_state: TreeState,
 }
 
 impl Brain {
@@ -217,27 +229,48 @@ pub fn init_behavior_update(mut commands: Commands, mut agent_query: Query<Entit
     }
 }
 
+/// `Stunned` agents fail whatever they're doing, no matter what the action wrote.
+pub fn fail_stunned(mut agent_query: Query<&mut Brain, (With<ActiveTree>, With<Stunned>)>) {
+    for mut brain in agent_query.iter_mut() {
+        brain.write_verdict(Verdict::Failure);
+    }
+}
+
+/// `Disoriented` agents don't know what they're doing either.
+pub fn fail_disoriented(mut agent_query: Query<&mut Brain, (With<ActiveTree>, With<Disoriented>)>) {
+    for mut brain in agent_query.iter_mut() {
+        brain.write_verdict(Verdict::Failure);
+    }
+}
+
 /// Updates all behavior trees until the next task/root node.
 pub fn behavior_update<A: Action>(
     mut commands: Commands,
+    time: Res<PhysicsTime>,
     ai: Res<AiModel<A>>,
     mut agent_query: Query<(Entity, &mut Brain, &mut A), With<ActiveTree>>,
 ) {
     for (e_agent, mut brain, mut action) in agent_query.iter_mut() {
+        let brain = &mut *brain;
+        brain.tree_state.now = time.0.elapsed();
+
         if brain.visiting_node == 0 {
             // there's no action so the tree needs to be restarted. call `run_root`.
-            let BehaviorOutput::Task {
-                node,
-                action: new_action,
-            } = ai.bt.run_root()
-            else {
-                error!("Behavior tree finished without task.");
-                continue;
-            };
-
-            debug!("Running action {:?}.", new_action);
-            brain.visiting_node = node;
-            *action = new_action;
+            match ai.bt.run_root(&mut brain.tree_state) {
+                BehaviorOutput::Task {
+                    node,
+                    action: new_action,
+                } => {
+                    debug!("Running action {:?}.", new_action);
+                    brain.visiting_node = node;
+                    *action = new_action;
+                }
+                // e.g. everything is on cooldown. try again next frame
+                BehaviorOutput::Complete { .. } => {
+                    *action = A::no_op();
+                    commands.entity(e_agent).remove::<ActiveTree>();
+                }
+            }
         } else {
             if !brain.pop_changed() {
                 warn!("{:?} was not handled. Make sure to handle this with `Brain::write_status`. Terminating tree...", action);
@@ -249,10 +282,11 @@ pub fn behavior_update<A: Action>(
 
             debug!("--> {:?}", brain.verdict());
             match brain.verdict() {
-                Verdict::Success | Verdict::Failure => match ai
-                    .bt
-                    .run_leaf(brain.visiting_node, brain.verdict().try_into().unwrap())
-                {
+                Verdict::Success | Verdict::Failure => match ai.bt.run_leaf(
+                    brain.visiting_node,
+                    brain.verdict().try_into().unwrap(),
+                    &mut brain.tree_state,
+                ) {
                     // don't deactivate the tree for further iteration steps
                     BehaviorOutput::Task {
                         node,
@@ -281,7 +315,12 @@ pub fn behavior_update<A: Action>(
 
 #[cfg(test)]
 mod tests {
-    use crate::{bt, bt::tree::CompositeNode};
+    use std::time::Duration;
+
+    use crate::{
+        bt,
+        bt::tree::{CompositeNode, DecoratorNode},
+    };
 
     use super::*;
 
@@ -346,6 +385,128 @@ mod tests {
         assert_eq!(action, &MockTask::no_op());
     }
 
+    #[derive(Resource)]
+    struct MockRuns {
+        a: u32,
+        /// `MockTask::A` fails after succeeding this many times.
+        a_successes: u32,
+    }
+
+    fn count_runs_system(
+        mut agent_query: Query<(&MockTask, &mut Brain)>,
+        mut runs: ResMut<MockRuns>,
+    ) {
+        for (action, mut brain) in agent_query.iter_mut() {
+            match action {
+                MockTask::A => {
+                    runs.a += 1;
+                    brain.write_verdict(match runs.a <= runs.a_successes {
+                        true => Verdict::Success,
+                        false => Verdict::Failure,
+                    });
+                }
+                MockTask::B => brain.write_verdict(Verdict::Failure),
+                _ => (),
+            }
+        }
+    }
+
+    fn decorator_app(bt: BehaviorTree<MockTask>, a_successes: u32) -> App {
+        let mut app = App::default();
+        app.add_plugins((MasterBehaviorPlugin, BehaviorPlugin::<MockTask>::default()))
+            .insert_resource(AiModel { bt })
+            .insert_resource(MockRuns { a: 0, a_successes })
+            .add_systems(
+                BehaviorIteration,
+                count_runs_system.in_set(BehaviorSet::Act),
+            );
+        app.world.spawn(BrainBundle::<MockTask>::default());
+        app
+    }
+
+    fn a_runs(app: &App) -> u32 {
+        app.world.resource::<MockRuns>().a
+    }
+
+    #[test]
+    fn invert() {
+        let mut app = decorator_app(
+            bt! {
+                Composite(CompositeNode::Sequence) {
+                    Decorator(DecoratorNode::Invert) {
+                        Leaf(MockTask::B),
+                    },
+                    Leaf(MockTask::A),
+                },
+            },
+            u32::MAX,
+        );
+
+        app.update();
+        assert_eq!(a_runs(&app), 1, "Failure wasn't inverted.");
+    }
+
+    #[test]
+    fn repeat() {
+        let mut app = decorator_app(
+            bt! {
+                Decorator(DecoratorNode::Repeat(3)) {
+                    Leaf(MockTask::A),
+                },
+            },
+            4,
+        );
+
+        app.update();
+        assert_eq!(a_runs(&app), 3, "Didn't repeat the right number of times.");
+
+        app.update();
+        assert_eq!(a_runs(&app), 5, "Didn't stop repeating on failure.");
+    }
+
+    #[test]
+    fn repeat_until_failure() {
+        let mut app = decorator_app(
+            bt! {
+                Composite(CompositeNode::Sequence) {
+                    Decorator(DecoratorNode::RepeatUntilFailure) {
+                        Leaf(MockTask::A),
+                    },
+                    Leaf(MockTask::B),
+                },
+            },
+            2,
+        );
+
+        app.update();
+        assert_eq!(a_runs(&app), 3, "Didn't repeat until failure.");
+    }
+
+    #[test]
+    fn cooldown() {
+        let mut app = decorator_app(
+            bt! {
+                Decorator(DecoratorNode::Cooldown(Duration::from_secs(1))) {
+                    Leaf(MockTask::A),
+                },
+            },
+            u32::MAX,
+        );
+
+        app.update();
+        assert_eq!(a_runs(&app), 1);
+
+        app.update();
+        assert_eq!(a_runs(&app), 1, "Ran during the cooldown.");
+
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_secs(1));
+        app.update();
+        assert_eq!(a_runs(&app), 2, "Didn't run after the cooldown.");
+    }
+
     #[test]
     fn kill_unhandled_trees() {
         let mut app = App::default();
@@ -1,5 +1,7 @@
 //! Behavior tree (no bevy stuff here).
 
+use std::{collections::HashMap, time::Duration};
+
 /// `Verdict`, without `Running`. For tree traversal.
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum OutVerdict {
@@ -36,7 +38,7 @@ impl<A> Default for Node<A> {
 
 impl<A: Clone> Node<A> {
     /// Traverse down the behavior tree. Returns on a leaf node.
-    fn down(&self) -> NodeTraversal<A> {
+    fn down(&self, id: usize, state: &mut TreeState) -> NodeTraversal<A> {
         match self {
             Node::Root(node) => NodeTraversal::Down { node: *node },
             Node::Composite(composite) => {
@@ -44,8 +46,26 @@ impl<A: Clone> Node<A> {
                 let node = composite.children[0];
                 NodeTraversal::Down { node }
             }
-            Node::Decorator(decorator) => NodeTraversal::Down {
-                node: decorator.child,
+            Node::Decorator(decorator) => match decorator.kind {
+                DecoratorNode::Repeat(0) => NodeTraversal::Up {
+                    node: decorator.parent,
+                    verdict: OutVerdict::Success,
+                },
+                DecoratorNode::Repeat(..) => {
+                    state.repeats.insert(id, 0);
+                    NodeTraversal::Down {
+                        node: decorator.child,
+                    }
+                }
+                DecoratorNode::Cooldown(duration) if state.cooling_down(id, duration) => {
+                    NodeTraversal::Up {
+                        node: decorator.parent,
+                        verdict: OutVerdict::Failure,
+                    }
+                }
+                _ => NodeTraversal::Down {
+                    node: decorator.child,
+                },
             },
             Node::Leaf(Leaf { action, .. }) => NodeTraversal::Task {
                 action: action.clone(),
@@ -54,7 +74,13 @@ impl<A: Clone> Node<A> {
     }
 
     /// Traverse up the behavior tree. Returns on a leaf node or the root node if the tree is finished.
-    fn up(&self, source: usize, verdict: OutVerdict) -> NodeTraversal<A> {
+    fn up(
+        &self,
+        id: usize,
+        source: usize,
+        verdict: OutVerdict,
+        state: &mut TreeState,
+    ) -> NodeTraversal<A> {
         match self {
             Node::Root(..) => NodeTraversal::Finish { verdict },
             Node::Composite(composite) => {
@@ -90,6 +116,37 @@ impl<A: Clone> Node<A> {
                         OutVerdict::Failure => OutVerdict::Success,
                     },
                 },
+                DecoratorNode::Repeat(n) => {
+                    let count = state.repeats.entry(id).or_default();
+                    *count += 1;
+                    match verdict {
+                        OutVerdict::Success if *count < n => NodeTraversal::Down {
+                            node: decorator.child,
+                        },
+                        _ => NodeTraversal::Up {
+                            node: decorator.parent,
+                            verdict,
+                        },
+                    }
+                }
+                DecoratorNode::RepeatUntilFailure => match verdict {
+                    OutVerdict::Success => NodeTraversal::Down {
+                        node: decorator.child,
+                    },
+                    OutVerdict::Failure => NodeTraversal::Up {
+                        node: decorator.parent,
+                        verdict: OutVerdict::Success,
+                    },
+                },
+                DecoratorNode::Cooldown(..) => {
+                    if verdict == OutVerdict::Success {
+                        state.cooldowns.insert(id, state.now);
+                    }
+                    NodeTraversal::Up {
+                        node: decorator.parent,
+                        verdict,
+                    }
+                }
             },
             Node::Leaf(leaf) => NodeTraversal::Up {
                 node: leaf.parent,
@@ -143,6 +200,32 @@ impl Decorator {
 pub enum DecoratorNode {
     /// Turns `Verdict::Success` into `Verdict::Failure` and vice versa.
     Invert,
+    /// Runs the child this many times. Stops early with `Verdict::Failure` if it fails.
+    Repeat(u32),
+    /// Runs the child until it fails, then succeeds.
+    RepeatUntilFailure,
+    /// Fails without visiting the child until this long after it last succeeded.
+    Cooldown(Duration),
+}
+
+/// Memory for decorators that need it, like `Repeat` and `Cooldown`. One per agent, since the
+/// `BehaviorTree` is shared.
+#[derive(Debug, Default, Clone)]
+pub struct TreeState {
+    /// Current time. `Cooldown`s are measured against this.
+    pub now: Duration,
+    /// Times each `Repeat` node's child has finished.
+    pub repeats: HashMap<usize, u32>,
+    /// When each `Cooldown` node's child last succeeded.
+    pub cooldowns: HashMap<usize, Duration>,
+}
+
+impl TreeState {
+    fn cooling_down(&self, node: usize, duration: Duration) -> bool {
+        self.cooldowns
+            .get(&node)
+            .is_some_and(|&t| self.now.saturating_sub(t) < duration)
+    }
 }
 
 pub struct Leaf<A> {
@@ -165,6 +248,8 @@ impl<A> Leaf<A> {
 /// It will only return the appropriate task or root node that it reaches first.
 /// This property allows the tree to be shared by multiple agents.
 /// It's up to the user to continue running the tree from a task node after it reaches one.
+///
+/// Decorators that need to remember things keep it in a `TreeState` owned by the agent.
 pub struct BehaviorTree<A> {
     pub graph: Vec<Node<A>>,
 }
@@ -191,18 +276,22 @@ pub enum BehaviorOutput<A> {
 
 impl<A: Clone> BehaviorTree<A> {
     /// Run a node traversal.
-    fn traverse_at(&self, mut traversal: NodeTraversal<A>) -> BehaviorOutput<A> {
+    fn traverse_at(
+        &self,
+        mut traversal: NodeTraversal<A>,
+        state: &mut TreeState,
+    ) -> BehaviorOutput<A> {
         let mut visiting_node = usize::MAX;
         loop {
             traversal = match traversal {
                 NodeTraversal::Down { node } => {
                     visiting_node = node;
-                    self.graph[node].down()
+                    self.graph[node].down(node, state)
                 }
                 NodeTraversal::Up { node, verdict } => {
                     let source = visiting_node;
                     visiting_node = node;
-                    self.graph[node].up(source, verdict)
+                    self.graph[node].up(node, source, verdict, state)
                 }
                 // I dunno why they get formatted like this, but OK
                 NodeTraversal::Task { action } => {
@@ -217,22 +306,24 @@ impl<A: Clone> BehaviorTree<A> {
     }
 
     /// Run from the top of the tree.
-    pub fn run_root(&self) -> BehaviorOutput<A> {
-        self.traverse_at(NodeTraversal::Down { node: 0 })
+    pub fn run_root(&self, state: &mut TreeState) -> BehaviorOutput<A> {
+        self.traverse_at(NodeTraversal::Down { node: 0 }, state)
     }
 
     /// Run from a particular node that outputted a particular verdict.
-    pub fn run_leaf(&self, input_node: usize, verdict: OutVerdict) -> BehaviorOutput<A> {
-        match verdict {
-            OutVerdict::Success => self.traverse_at(NodeTraversal::Up {
-                node: input_node,
-                verdict: OutVerdict::Success,
-            }),
-            OutVerdict::Failure => self.traverse_at(NodeTraversal::Up {
+    pub fn run_leaf(
+        &self,
+        input_node: usize,
+        verdict: OutVerdict,
+        state: &mut TreeState,
+    ) -> BehaviorOutput<A> {
+        self.traverse_at(
+            NodeTraversal::Up {
                 node: input_node,
-                verdict: OutVerdict::Failure,
-            }),
-        }
+                verdict,
+            },
+            state,
+        )
     }
 
     /// Add a node. Returns the node's id.
@@ -417,11 +508,12 @@ mod tests {
                 Leaf(MockTask::B),
             },
         };
+        let mut state = TreeState::default();
 
         let [_root, _composite, task_a, task_b] = nodes::<4>();
 
         assert_eq!(
-            bt.run_root(),
+            bt.run_root(&mut state),
             BehaviorOutput::Task {
                 node: task_a,
                 action: MockTask::A,
@@ -430,7 +522,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_a, OutVerdict::Success),
+            bt.run_leaf(task_a, OutVerdict::Success, &mut state),
             BehaviorOutput::Task {
                 node: task_b,
                 action: MockTask::B,
@@ -439,7 +531,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_b, OutVerdict::Success),
+            bt.run_leaf(task_b, OutVerdict::Success, &mut state),
             BehaviorOutput::Complete {
                 verdict: OutVerdict::Success
             },
@@ -447,7 +539,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_root(),
+            bt.run_root(&mut state),
             BehaviorOutput::Task {
                 node: task_a,
                 action: MockTask::A,
@@ -456,7 +548,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_a, OutVerdict::Failure),
+            bt.run_leaf(task_a, OutVerdict::Failure, &mut state),
             BehaviorOutput::Complete {
                 verdict: OutVerdict::Failure
             },
@@ -472,11 +564,12 @@ mod tests {
                 Leaf(MockTask::B),
             },
         };
+        let mut state = TreeState::default();
 
         let [_root, _composite, task_a, task_b] = nodes::<4>();
 
         assert_eq!(
-            bt.run_root(),
+            bt.run_root(&mut state),
             BehaviorOutput::Task {
                 node: task_a,
                 action: MockTask::A,
@@ -485,7 +578,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_a, OutVerdict::Failure),
+            bt.run_leaf(task_a, OutVerdict::Failure, &mut state),
             BehaviorOutput::Task {
                 node: task_b,
                 action: MockTask::B,
@@ -494,7 +587,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_b, OutVerdict::Failure),
+            bt.run_leaf(task_b, OutVerdict::Failure, &mut state),
             BehaviorOutput::Complete {
                 verdict: OutVerdict::Failure
             },
@@ -502,7 +595,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_root(),
+            bt.run_root(&mut state),
             BehaviorOutput::Task {
                 node: task_a,
                 action: MockTask::A,
@@ -511,7 +604,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_a, OutVerdict::Success),
+            bt.run_leaf(task_a, OutVerdict::Success, &mut state),
             BehaviorOutput::Complete {
                 verdict: OutVerdict::Success
             },
@@ -526,10 +619,11 @@ mod tests {
                 Leaf(MockTask::A),
             },
         };
+        let mut state = TreeState::default();
         let [_root, _decorator, task_a] = nodes::<3>();
 
         assert_eq!(
-            bt.run_root(),
+            bt.run_root(&mut state),
             BehaviorOutput::Task {
                 node: task_a,
                 action: MockTask::A,
@@ -538,7 +632,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_a, OutVerdict::Success),
+            bt.run_leaf(task_a, OutVerdict::Success, &mut state),
             BehaviorOutput::Complete {
                 verdict: OutVerdict::Failure
             },
@@ -546,7 +640,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_root(),
+            bt.run_root(&mut state),
             BehaviorOutput::Task {
                 node: task_a,
                 action: MockTask::A,
@@ -555,7 +649,7 @@ mod tests {
         );
 
         assert_eq!(
-            bt.run_leaf(task_a, OutVerdict::Failure),
+            bt.run_leaf(task_a, OutVerdict::Failure, &mut state),
             BehaviorOutput::Complete {
                 verdict: OutVerdict::Success
             },
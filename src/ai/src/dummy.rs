@@ -3,32 +3,35 @@ use bevy_asset_loader::prelude::*;
 use bevy_enum_filter::prelude::*;
 use bevy_landmass::Agent;
 use bevy_rapier3d::prelude::*;
-use grin_asset::AssetLoadState;
+use grin_asset::{animation::IDLE_STATE, AssetLoadState};
 use grin_character::PlayerCharacter;
 use grin_damage::{
     health::Dead,
     hit::{Damage, DamageVariant},
-    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor},
+    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor, ProjectilePool},
 };
 use grin_derive::Cooldown;
 use grin_map::MapData;
 use grin_rig::{
     humanoid::{Humanoid, HumanoidBundle, HUMANOID_RADIUS},
-    Idle,
+    PlayAnimationState,
 };
 use grin_time::Rewind;
-use grin_util::vectors::Vec3Ext;
+use grin_util::{rng::GrinRng, vectors::Vec3Ext};
 
 use super::{
     bt::{
         tree::CompositeNode, AiModel, BehaviorIteration, BehaviorSet, Brain, EnumBehaviorPlugin,
         Verdict,
     },
-    movement::{match_desired_velocity, propagate_attack_target_to_agent_target, AttackTarget},
+    movement::{
+        match_desired_velocity, patrol, propagate_attack_target_to_agent_target, AttackTarget,
+    },
     protective_cooldown, set_closest_attack_target, EnemyAgentBundle,
 };
 use crate::{
     bt,
+    encounter::EncounterAppExt,
     enemy_identifier_filters::Dummy,
     spawn::{ai_spawner, enemy_spawner, indicators::SpawnIndicatorEffect, EnemySpawnPlugin},
     AiSet, EnemyIdentifier,
@@ -49,36 +52,42 @@ impl Plugin for DummyPlugin {
             EnemySpawnPlugin::<Dummy>::default(),
             EnumBehaviorPlugin::<DummyAi>::default(),
         ))
+        .register_encounter_enemy::<Dummy>("dummy")
         .insert_resource(AiModel {
             bt: bt! {
-                Composite(CompositeNode::Sequence) {
-                    Leaf(DummyAi::Track),
-                    Leaf(DummyAi::Target),
-                    Composite(CompositeNode::Selector) {
-                        Composite(CompositeNode::Sequence) {
-                            Leaf(DummyAi::FireCheck),
-                            Leaf(DummyAi::Fire),
+                Composite(CompositeNode::Selector) {
+                    Composite(CompositeNode::Sequence) {
+                        Leaf(DummyAi::Track),
+                        Leaf(DummyAi::Target),
+                        Composite(CompositeNode::Selector) {
+                            Composite(CompositeNode::Sequence) {
+                                Leaf(DummyAi::FireCheck),
+                                Leaf(DummyAi::Fire),
+                            },
+                            Leaf(DummyAi::Chase),
                         },
-                        Leaf(DummyAi::Chase),
                     },
+                    Leaf(DummyAi::Patrol),
                 },
             },
         })
         .add_systems(
             PreUpdate,
             (
-                enemy_spawner::<Dummy, _, _, _>(|assets: Res<DummyAssets>| {
-                    (
-                        EnemyIdentifier::Dummy,
-                        SpawnIndicatorEffect::Neon,
-                        HumanoidBundle {
-                            rig: assets.rig.clone(),
-                            ..Default::default()
-                        },
-                    )
-                })
+                enemy_spawner::<Dummy, _, _, _>(
+                    |assets: Res<DummyAssets>, mut rng: ResMut<GrinRng>| {
+                        (
+                            EnemyIdentifier::Dummy,
+                            SpawnIndicatorEffect::Neon,
+                            HumanoidBundle {
+                                rig: assets.rig.clone(),
+                                ..HumanoidBundle::random(&mut rng)
+                            },
+                        )
+                    },
+                )
                 .in_set(AiSet::Spawn),
-                ai_spawner::<Dummy, _, _, _>(|assets: Res<DummyAssets>, map_data: Res<MapData>| {
+                ai_spawner::<Dummy, _, _, _>(|map_data: Res<MapData>| {
                     (
                         EnemyAgentBundle::<DummyAi> {
                             agent: Agent {
@@ -88,9 +97,8 @@ impl Plugin for DummyPlugin {
                             ..EnemyAgentBundle::from_archipelago(map_data.archipelago)
                         },
                         ShotCooldown::default(),
-                        Idle {
-                            clip: assets.idle.clone(),
-                        },
+                        // out of the spawn pose
+                        PlayAnimationState(IDLE_STATE),
                     )
                 })
                 .in_set(AiSet::Spawn),
@@ -103,6 +111,7 @@ impl Plugin for DummyPlugin {
                 propagate_attack_target_to_agent_target::<Dummy, Enum!(DummyAi::Target)>,
                 protective_cooldown::<Dummy, Enum!(DummyAi::FireCheck), ShotCooldown>,
                 match_desired_velocity::<Dummy, Enum!(DummyAi::Chase)>,
+                patrol::<Dummy, Enum!(DummyAi::Patrol)>,
                 fire::<Dummy, Enum!(DummyAi::Fire)>,
             )
                 .in_set(BehaviorSet::Act),
@@ -114,8 +123,6 @@ impl Plugin for DummyPlugin {
 pub struct DummyAssets {
     #[asset(key = "rig.dummy")]
     pub rig: Handle<Scene>,
-    #[asset(key = "anim.idle")]
-    pub idle: Handle<AnimationClip>,
 }
 
 #[derive(Component, EnumFilter, Clone, Copy, Debug, Default)]
@@ -127,10 +134,12 @@ pub enum DummyAi {
     FireCheck,
     Fire,
     Chase,
+    Patrol,
 }
 
 pub fn fire<T: Component, A: Component>(
     mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
     mut agent_query: Query<
         (Entity, &mut Brain, &Humanoid, &AttackTarget),
         (With<T>, With<A>, Without<Rewind>, Without<Dead>),
@@ -144,7 +153,7 @@ pub fn fire<T: Component, A: Component>(
         );
         let bullet_transform = Transform::from_translation(origin.translation())
             .looking_at(target.translation().with_y(origin.translation().y), Vec3::Y);
-        commands.spawn((
+        pool.acquire(&mut commands).insert((
             BulletProjectile,
             ProjectileBundle {
                 color: ProjectileColor::Red,
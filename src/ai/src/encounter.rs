@@ -0,0 +1,490 @@
+//! Fights made of waves of enemies.
+
+use bevy::{prelude::*, reflect::TypePath, utils::HashMap};
+use bevy_common_assets::ron::RonAssetPlugin;
+use grin_character::PlayerCharacter;
+use grin_damage::health::Dead;
+use grin_map::{
+    marker::{SpawnPoint, TriggerEnteredEvent},
+    MapLoadState,
+};
+use grin_physics::PhysicsTime;
+use grin_util::event::SpawnAppExt;
+use serde::Deserialize;
+
+use crate::spawn::EnemySpawn;
+
+pub struct EncounterPlugin;
+
+impl Plugin for EncounterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<Wave>::new(&["wave.ron"]))
+            .init_resource::<EncounterDirector>()
+            .init_resource::<EnemyRegistry>()
+            .init_resource::<EncounterTriggers>()
+            .add_event::<WaveStartedEvent>()
+            .add_event::<EncounterCompleteEvent>()
+            .add_systems(
+                Update,
+                (start_encounters_on_trigger, run_encounter)
+                    .chain()
+                    .run_if(in_state(MapLoadState::Success)),
+            );
+    }
+}
+
+/// Some enemies that spawn together. Loaded from `*.wave.ron`.
+#[derive(Asset, TypePath, Deserialize, Clone, Debug)]
+pub struct Wave {
+    pub groups: Vec<SpawnGroup>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct SpawnGroup {
+    /// Name in the `EnemyRegistry`.
+    pub enemy_type: String,
+    pub count: u32,
+    /// Enemies go to these in order, wrapping around if there's more enemies than points.
+    ///
+    /// Leave it out to use the map's `Spawn.Enemy.<Type>` points instead.
+    #[serde(default)]
+    pub spawn_points: Vec<Vec3>,
+    /// Seconds after the wave starts.
+    #[serde(default)]
+    pub delay: f32,
+}
+
+/// Marks agents spawned by the `EncounterDirector`. The next wave starts when they're all `Dead`.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct EncounterMember;
+
+pub type EnemySpawnFn = Box<dyn Fn(&mut Commands, Transform) + Send + Sync>;
+
+/// Whether an enemy is of some registered type.
+pub type EnemyMatchFn = fn(&EntityRef) -> bool;
+
+/// Maps `SpawnGroup::enemy_type` to whatever spawns it.
+///
+/// Spawned agents need `EncounterMember`, or the wave ends before they're dead.
+#[derive(Resource, Default)]
+pub struct EnemyRegistry {
+    pub spawn_fns: HashMap<String, EnemySpawnFn>,
+    /// For going back from an enemy to its name, e.g. for saving.
+    pub match_fns: HashMap<String, EnemyMatchFn>,
+}
+
+impl EnemyRegistry {
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        spawn_fn: impl Fn(&mut Commands, Transform) + Send + Sync + 'static,
+    ) {
+        self.spawn_fns.insert(name.into(), Box::new(spawn_fn));
+    }
+
+    /// Anything with `T` is called `name`.
+    pub fn identify<T: Component>(&mut self, name: impl Into<String>) {
+        self.match_fns
+            .insert(name.into(), |entity: &EntityRef| entity.contains::<T>());
+    }
+
+    /// Name of the registered type that `entity` is.
+    pub fn enemy_type(&self, entity: &EntityRef) -> Option<&str> {
+        self.match_fns
+            .iter()
+            .find(|(_, match_fn)| match_fn(entity))
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Spawns enemies through `EnemySpawn<T>`.
+    pub fn register<T: Component>(&mut self, name: impl Into<String>) {
+        let name = name.into();
+        self.identify::<T>(name.clone());
+        self.insert(name, |commands, transform| {
+            commands.add(move |world: &mut World| {
+                world.send_event(EnemySpawn::<T> {
+                    transform,
+                    encounter: true,
+                    ..Default::default()
+                });
+            });
+        });
+    }
+}
+
+pub trait EncounterAppExt {
+    /// Lets waves and `SpawnByNameEvent` spawn `T` by `name`. Needs `EnemySpawnPlugin<T>`.
+    fn register_encounter_enemy<T: Component>(&mut self, name: impl Into<String>) -> &mut Self;
+}
+
+impl EncounterAppExt for App {
+    fn register_encounter_enemy<T: Component>(&mut self, name: impl Into<String>) -> &mut Self {
+        let name = name.into();
+        self.world
+            .get_resource_or_insert_with(EnemyRegistry::default)
+            .register::<T>(name.clone());
+        self.register_spawn::<EnemySpawn<T>>(name)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PendingSpawn {
+    enemy_type: String,
+    point: Transform,
+    delay: f32,
+}
+
+#[derive(Debug)]
+struct Encounter {
+    waves: Vec<Handle<Wave>>,
+    /// Index into `waves`, once one has started.
+    wave: Option<usize>,
+    /// Seconds since the wave started.
+    elapsed: f32,
+    pending: Vec<PendingSpawn>,
+}
+
+/// Runs one encounter at a time.
+#[derive(Resource, Default, Debug)]
+pub struct EncounterDirector {
+    /// Most `EncounterMember`s alive at once. The rest trickle in as they die.
+    pub max_alive: Option<usize>,
+    active: Option<Encounter>,
+}
+
+impl EncounterDirector {
+    /// Replaces whatever's running.
+    pub fn start(&mut self, waves: Vec<Handle<Wave>>) {
+        self.active = Some(Encounter {
+            waves,
+            wave: None,
+            elapsed: 0.0,
+            pending: Vec::new(),
+        });
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_some()
+    }
+
+    /// Index of the current wave.
+    pub fn wave(&self) -> Option<usize> {
+        self.active.as_ref().and_then(|e| e.wave)
+    }
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct WaveStartedEvent {
+    /// Index of the wave.
+    pub wave: usize,
+    /// How many waves there are.
+    pub total: usize,
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub struct EncounterCompleteEvent;
+
+/// Encounters that start when the player walks into the `TriggerVolume` with the same id.
+/// Each one only goes once.
+#[derive(Resource, Default, Debug)]
+pub struct EncounterTriggers(pub HashMap<String, Vec<Handle<Wave>>>);
+
+/// Waits for whatever's running to finish first.
+pub fn start_encounters_on_trigger(
+    mut director: ResMut<EncounterDirector>,
+    mut triggers: ResMut<EncounterTriggers>,
+    player_query: Query<(), With<PlayerCharacter>>,
+    mut trigger_events: EventReader<TriggerEnteredEvent>,
+) {
+    for TriggerEnteredEvent { id, entity } in trigger_events.read() {
+        if director.is_active() || !player_query.contains(*entity) {
+            continue;
+        }
+        if let Some(waves) = triggers.0.remove(id) {
+            info!("Encounter `{}` triggered.", id);
+            director.start(waves);
+        }
+    }
+}
+
+pub fn run_encounter(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    waves: Res<Assets<Wave>>,
+    registry: Res<EnemyRegistry>,
+    mut director: ResMut<EncounterDirector>,
+    spawn_point_query: Query<(&Name, &SpawnPoint)>,
+    member_query: Query<(), (With<EncounterMember>, Without<Dead>)>,
+    mut wave_events: EventWriter<WaveStartedEvent>,
+    mut complete_events: EventWriter<EncounterCompleteEvent>,
+) {
+    let max_alive = director.max_alive.unwrap_or(usize::MAX);
+    let Some(encounter) = director.active.as_mut() else {
+        return;
+    };
+
+    // spawn events are read in `PreUpdate`, so last frame's spawns are already here
+    let mut alive = member_query.iter().count();
+
+    if encounter.pending.is_empty() && alive == 0 {
+        let next = encounter.wave.map_or(0, |i| i + 1);
+        if next >= encounter.waves.len() {
+            info!("Encounter complete.");
+            director.active = None;
+            complete_events.send(EncounterCompleteEvent);
+            return;
+        }
+
+        // try again when it's loaded
+        let Some(wave) = waves.get(&encounter.waves[next]) else {
+            return;
+        };
+
+        encounter.pending = wave
+            .groups
+            .iter()
+            .flat_map(|group| {
+                let points = match group.spawn_points.is_empty() {
+                    true => SpawnPoint::enemies(spawn_point_query.iter(), &group.enemy_type),
+                    false => group
+                        .spawn_points
+                        .iter()
+                        .map(|p| Transform::from_translation(*p))
+                        .collect(),
+                };
+                if points.is_empty() {
+                    warn!(
+                        "Nowhere to spawn `{}`. Add `Spawn.Enemy.<Type>` points to the map.",
+                        group.enemy_type
+                    );
+                }
+                (0..group.count as usize).map(move |i| PendingSpawn {
+                    enemy_type: group.enemy_type.clone(),
+                    point: match points.len() {
+                        0 => Transform::default(),
+                        n => points[i % n],
+                    },
+                    delay: group.delay,
+                })
+            })
+            .collect();
+        encounter.wave = Some(next);
+        encounter.elapsed = 0.0;
+
+        info!("Wave {} started.", next);
+        wave_events.send(WaveStartedEvent {
+            wave: next,
+            total: encounter.waves.len(),
+        });
+    }
+
+    encounter.elapsed += time.0.delta_seconds();
+
+    let elapsed = encounter.elapsed;
+    encounter.pending.retain(|spawn| {
+        if spawn.delay > elapsed || alive >= max_alive {
+            return true;
+        }
+        match registry.spawn_fns.get(&spawn.enemy_type) {
+            Some(spawn_fn) => {
+                spawn_fn(&mut commands, spawn.point);
+                alive += 1;
+            }
+            None => warn!("Unknown enemy type `{}` in wave.", spawn.enemy_type),
+        }
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use grin_map::marker::SpawnPointKind;
+
+    use super::*;
+
+    fn advance(app: &mut App) {
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_secs_f32(1.0));
+        app.update();
+    }
+
+    fn members(app: &mut App) -> Vec<Entity> {
+        app.world
+            .query_filtered::<Entity, (With<EncounterMember>, Without<Dead>)>()
+            .iter(&app.world)
+            .collect()
+    }
+
+    fn kill_all(app: &mut App) {
+        for e in members(app) {
+            app.world.entity_mut(e).insert(Dead);
+        }
+    }
+
+    fn count_events<E: Event>(app: &App) -> usize {
+        let events = app.world.resource::<Events<E>>();
+        events.get_reader().read(events).count()
+    }
+
+    #[test]
+    fn waves_and_cap() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Wave>()
+            .init_resource::<PhysicsTime>()
+            .init_resource::<EnemyRegistry>()
+            .insert_resource(EncounterDirector {
+                max_alive: Some(2),
+                ..Default::default()
+            })
+            .add_event::<WaveStartedEvent>()
+            .add_event::<EncounterCompleteEvent>()
+            .add_systems(Update, run_encounter);
+
+        app.world
+            .resource_mut::<EnemyRegistry>()
+            .insert("mock", |commands, transform| {
+                commands.spawn((EncounterMember, transform));
+            });
+
+        let group = |count, delay| SpawnGroup {
+            enemy_type: "mock".into(),
+            count,
+            spawn_points: vec![Vec3::ZERO, Vec3::X],
+            delay,
+        };
+        let mut assets = app.world.resource_mut::<Assets<Wave>>();
+        let waves = vec![
+            assets.add(Wave {
+                groups: vec![group(3, 0.0)],
+            }),
+            assets.add(Wave {
+                groups: vec![group(1, 2.5)],
+            }),
+        ];
+        app.world.resource_mut::<EncounterDirector>().start(waves);
+
+        advance(&mut app);
+        assert_eq!(members(&mut app).len(), 2, "Didn't respect the cap.");
+        assert_eq!(
+            count_events::<WaveStartedEvent>(&app),
+            1,
+            "Didn't announce the wave."
+        );
+
+        // one slot frees up
+        let e_dead = members(&mut app)[0];
+        app.world.entity_mut(e_dead).insert(Dead);
+        advance(&mut app);
+        assert_eq!(
+            members(&mut app).len(),
+            2,
+            "Didn't trickle in the rest of the wave."
+        );
+
+        kill_all(&mut app);
+        advance(&mut app);
+        assert_eq!(
+            app.world.resource::<EncounterDirector>().wave(),
+            Some(1),
+            "Didn't start the next wave."
+        );
+        assert!(members(&mut app).is_empty(), "Ignored the spawn delay.");
+
+        advance(&mut app);
+        advance(&mut app);
+        assert_eq!(members(&mut app).len(), 1, "Didn't spawn after the delay.");
+
+        kill_all(&mut app);
+        advance(&mut app);
+        assert!(
+            !app.world.resource::<EncounterDirector>().is_active(),
+            "Encounter didn't end."
+        );
+        assert_eq!(
+            count_events::<EncounterCompleteEvent>(&app),
+            1,
+            "Didn't announce the end of the encounter."
+        );
+    }
+
+    #[test]
+    fn triggered_encounter() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Wave>()
+            .init_resource::<PhysicsTime>()
+            .init_resource::<EnemyRegistry>()
+            .init_resource::<EncounterDirector>()
+            .init_resource::<EncounterTriggers>()
+            .add_event::<TriggerEnteredEvent>()
+            .add_event::<WaveStartedEvent>()
+            .add_event::<EncounterCompleteEvent>()
+            .add_systems(Update, (start_encounters_on_trigger, run_encounter).chain());
+
+        app.world
+            .resource_mut::<EnemyRegistry>()
+            .insert("mock", |commands, transform| {
+                commands.spawn((EncounterMember, transform));
+            });
+        let wave = app.world.resource_mut::<Assets<Wave>>().add(Wave {
+            groups: vec![SpawnGroup {
+                enemy_type: "mock".into(),
+                count: 2,
+                spawn_points: Vec::new(),
+                delay: 0.0,
+            }],
+        });
+        app.world
+            .resource_mut::<EncounterTriggers>()
+            .0
+            .insert("Encounter1".into(), vec![wave]);
+        for (name, z) in [("Spawn.Enemy.Mock.02", 2.0), ("Spawn.Enemy.Mock.01", 1.0)] {
+            app.world.spawn((
+                Name::new(name),
+                SpawnPoint {
+                    kind: SpawnPointKind::Enemy("mock".into()),
+                    transform: Transform::from_xyz(0.0, 0.0, z),
+                },
+            ));
+        }
+
+        let e_player = app.world.spawn(PlayerCharacter).id();
+        let e_other = app.world.spawn_empty().id();
+        let trigger = |app: &mut App, entity| {
+            app.world.send_event(TriggerEnteredEvent {
+                id: "Encounter1".into(),
+                entity,
+            });
+            advance(app);
+        };
+
+        trigger(&mut app, e_other);
+        assert!(
+            !app.world.resource::<EncounterDirector>().is_active(),
+            "Something other than the player triggered the encounter."
+        );
+
+        trigger(&mut app, e_player);
+        let mut spawned = app
+            .world
+            .query_filtered::<&Transform, With<EncounterMember>>()
+            .iter(&app.world)
+            .map(|t| t.translation.z)
+            .collect::<Vec<_>>();
+        spawned.sort_by(f32::total_cmp);
+        assert_eq!(
+            spawned,
+            vec![1.0, 2.0],
+            "Enemies didn't spawn at the map's spawn points."
+        );
+        assert!(
+            app.world.resource::<EncounterTriggers>().0.is_empty(),
+            "Trigger can go off again."
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! A dummy with an SMG in each hand. It circles you and takes turns shooting with them.
+
+use bevy::prelude::*;
+use bevy_enum_filter::prelude::*;
+use bevy_landmass::Agent;
+use grin_asset::animation::IDLE_STATE;
+use grin_character::PlayerCharacter;
+use grin_derive::Cooldown;
+use grin_item::{
+    equip::{dual_wield_slot, Equipped},
+    library::smg::Smg,
+    spawn::ItemSpawnEvent,
+};
+use grin_map::MapData;
+use grin_rig::{
+    humanoid::{Humanoid, HumanoidBundle, HUMANOID_RADIUS},
+    PlayAnimationState,
+};
+use grin_util::rng::GrinRng;
+
+use super::{
+    aim_at_attack_target, alternate_dual_fire,
+    bt::{
+        tree::CompositeNode, AiModel, BehaviorIteration, BehaviorSet, EnumBehaviorPlugin,
+        PreBehaviorIteration,
+    },
+    cease_fire,
+    dummy::DummyAssets,
+    movement::{patrol, strafe, Strafe},
+    protective_cooldown, set_closest_attack_target, AlternateHands, EnemyAgentBundle,
+};
+use crate::{
+    bt,
+    encounter::EncounterAppExt,
+    enemy_identifier_filters::Gunslinger,
+    spawn::{
+        ai_spawner, enemy_spawner, indicators::SpawnIndicatorEffect, EnemySpawnPlugin,
+        SpawnCompleted,
+    },
+    AiSet, EnemyIdentifier,
+};
+
+/// Time between shots. Every shot is from the other hand.
+#[derive(Component, Cooldown)]
+#[cooldown(duration = 0.3)]
+pub struct DualShotCooldown(pub Timer);
+
+pub struct GunslingerPlugin;
+
+impl Plugin for GunslingerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            EnemySpawnPlugin::<Gunslinger>::default(),
+            EnumBehaviorPlugin::<GunslingerAi>::default(),
+        ))
+        .register_encounter_enemy::<Gunslinger>("gunslinger")
+        .insert_resource(AiModel {
+            bt: bt! {
+                Composite(CompositeNode::Selector) {
+                    Composite(CompositeNode::Sequence) {
+                        Leaf(GunslingerAi::Track),
+                        Leaf(GunslingerAi::Aim),
+                        Composite(CompositeNode::Selector) {
+                            Composite(CompositeNode::Sequence) {
+                                Leaf(GunslingerAi::FireCheck),
+                                Leaf(GunslingerAi::Fire),
+                            },
+                            Leaf(GunslingerAi::Strafe),
+                        },
+                    },
+                    Leaf(GunslingerAi::Patrol),
+                },
+            },
+        })
+        .add_systems(
+            PreUpdate,
+            (
+                enemy_spawner::<Gunslinger, _, _, _>(
+                    |assets: Res<DummyAssets>, mut rng: ResMut<GrinRng>| {
+                        (
+                            EnemyIdentifier::Gunslinger,
+                            SpawnIndicatorEffect::Neon,
+                            HumanoidBundle {
+                                rig: assets.rig.clone(),
+                                ..HumanoidBundle::random(&mut rng)
+                            },
+                        )
+                    },
+                )
+                .in_set(AiSet::Spawn),
+                ai_spawner::<Gunslinger, _, _, _>(|map_data: Res<MapData>| {
+                    (
+                        EnemyAgentBundle::<GunslingerAi> {
+                            agent: Agent {
+                                radius: HUMANOID_RADIUS,
+                                max_velocity: 2.5,
+                            },
+                            ..EnemyAgentBundle::from_archipelago(map_data.archipelago)
+                        },
+                        DualShotCooldown::default(),
+                        AlternateHands::default(),
+                        Strafe::new(6.0, 3.0),
+                        // out of the spawn pose
+                        PlayAnimationState(IDLE_STATE),
+                    )
+                })
+                .in_set(AiSet::Spawn),
+                spawn_smgs.in_set(AiSet::Spawn),
+            ),
+        )
+        .add_systems(PreBehaviorIteration, cease_fire::<Gunslinger>)
+        .add_systems(
+            BehaviorIteration,
+            (
+                set_closest_attack_target::<
+                    Gunslinger,
+                    Enum!(GunslingerAi::Track),
+                    PlayerCharacter,
+                >,
+                aim_at_attack_target::<Gunslinger, Enum!(GunslingerAi::Aim)>,
+                protective_cooldown::<
+                    Gunslinger,
+                    Enum!(GunslingerAi::FireCheck),
+                    DualShotCooldown,
+                >,
+                alternate_dual_fire::<Gunslinger, Enum!(GunslingerAi::Fire)>,
+                strafe::<Gunslinger, Enum!(GunslingerAi::Strafe)>,
+                patrol::<Gunslinger, Enum!(GunslingerAi::Patrol)>,
+            )
+                .in_set(BehaviorSet::Act),
+        );
+    }
+}
+
+#[derive(Component, EnumFilter, Clone, Copy, Debug, Default)]
+pub enum GunslingerAi {
+    #[default]
+    Empty,
+    Track,
+    Aim,
+    FireCheck,
+    Fire,
+    Strafe,
+    Patrol,
+}
+
+/// Waiting on the first SMG to get equipped before getting the second one, so that it goes in
+/// the off hand.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct EmptyOffHand;
+
+/// One SMG for each hand. Enemies don't start with anything `Equipped`, so this gives them
+/// empty hands first.
+pub fn spawn_smgs(
+    mut commands: Commands,
+    agent_query: Query<(Entity, &Humanoid, Option<&Equipped>), With<EmptyOffHand>>,
+    smg_query: Query<(), With<Smg>>,
+    mut spawn_events: EventReader<SpawnCompleted<Gunslinger>>,
+    mut item_events: EventWriter<ItemSpawnEvent<Smg>>,
+) {
+    for SpawnCompleted { entity, .. } in spawn_events.read() {
+        item_events.send(ItemSpawnEvent {
+            parent_entity: Some(*entity),
+            ..Default::default()
+        });
+        let equipped = Equipped::empty(&mut commands);
+        commands.entity(*entity).insert((equipped, EmptyOffHand));
+    }
+
+    for (e_agent, humanoid, equipped) in agent_query.iter() {
+        let slot = dual_wield_slot(humanoid, equipped, &smg_query);
+        if slot.is_off_hand(humanoid.dominant_hand_type) {
+            item_events.send(ItemSpawnEvent {
+                parent_entity: Some(e_agent),
+                ..Default::default()
+            });
+            commands.entity(e_agent).remove::<EmptyOffHand>();
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! Health bars over enemies, and highlighting whatever the player's aiming at.
+
+use bevy::{prelude::*, ui::TargetCamera};
+use bevy_rapier3d::prelude::*;
+use grin_damage::{
+    faction::Faction,
+    health::{Dead, Health},
+    hitbox::Hitbox,
+};
+use grin_input::camera::{CameraAlignment, LookInfo, PlayerCamera};
+use grin_physics::CollisionGroupExt;
+use grin_render::outline::{OutlineOverride, OutlineOverrideCommandsExt, AIM_HIGHLIGHT_PRIORITY};
+use grin_rig::humanoid::{Humanoid, Shattered};
+
+/// Seconds that a health bar sticks around after its enemy gets hit.
+pub const ENEMY_HEALTH_BAR_TIMEOUT: f32 = 3.0;
+
+/// Seconds at the end of `ENEMY_HEALTH_BAR_TIMEOUT` that the health bar spends fading out.
+pub const ENEMY_HEALTH_BAR_FADE: f32 = 0.5;
+
+/// How far above the head the health bar goes.
+pub const ENEMY_HEALTH_BAR_OFFSET: f32 = 0.75;
+
+/// Pixels.
+pub const ENEMY_HEALTH_BAR_WIDTH: f32 = 60.0;
+
+/// Nothing further than this gets highlighted.
+pub const AIM_HIGHLIGHT_DISTANCE: f32 = 64.0;
+
+pub const AIM_HIGHLIGHT_COLOR: Color = Color::rgb(1.0, 0.8, 0.0);
+
+const BAR_BACKGROUND_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+const BAR_FILL_COLOR: Color = Color::rgb(0.8, 0.1, 0.1);
+
+pub struct EnemyHudPlugin;
+
+impl Plugin for EnemyHudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AimHighlight>().add_systems(
+            Update,
+            (
+                (
+                    spawn_enemy_health_bars,
+                    update_enemy_health_bars,
+                    despawn_enemy_health_bars,
+                )
+                    .chain(),
+                highlight_aim_target.run_if(resource_exists::<LookInfo>),
+            ),
+        );
+    }
+}
+
+/// Floats over `target` for a bit whenever it loses health.
+#[derive(Component, Debug)]
+pub struct EnemyHealthBar {
+    pub target: Entity,
+    /// Highest health seen, since `Health` doesn't have a max.
+    pub max: f32,
+    /// Health as of last frame.
+    pub last: f32,
+    pub timer: Timer,
+}
+
+impl EnemyHealthBar {
+    pub fn new(target: Entity, health: f32) -> Self {
+        let mut timer = Timer::from_seconds(ENEMY_HEALTH_BAR_TIMEOUT, TimerMode::Once);
+        timer.tick(timer.duration());
+        Self {
+            target,
+            max: health,
+            last: health,
+            timer,
+        }
+    }
+
+    /// `1.0` until it starts fading out.
+    pub fn alpha(&self) -> f32 {
+        (self.timer.remaining_secs() / ENEMY_HEALTH_BAR_FADE).min(1.0)
+    }
+}
+
+#[derive(Component)]
+pub struct EnemyHealthBarFill;
+
+/// The enemy whose outline is highlighted.
+#[derive(Resource, Debug, Default)]
+pub struct AimHighlight(pub Option<Entity>);
+
+pub fn spawn_enemy_health_bars(
+    mut commands: Commands,
+    enemy_query: Query<(Entity, &Faction, &Health), Added<Faction>>,
+) {
+    for (e_enemy, faction, health) in enemy_query.iter() {
+        if *faction != Faction::Enemy {
+            continue;
+        }
+
+        commands
+            .spawn((
+                EnemyHealthBar::new(e_enemy, health.0),
+                NodeBundle {
+                    style: Style {
+                        display: Display::None,
+                        position_type: PositionType::Absolute,
+                        width: Val::Px(ENEMY_HEALTH_BAR_WIDTH),
+                        height: Val::Px(6.0),
+                        ..Default::default()
+                    },
+                    background_color: BAR_BACKGROUND_COLOR.into(),
+                    ..Default::default()
+                },
+            ))
+            .with_children(|parent| {
+                parent.spawn((
+                    EnemyHealthBarFill,
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            ..Default::default()
+                        },
+                        background_color: BAR_FILL_COLOR.into(),
+                        ..Default::default()
+                    },
+                ));
+            });
+    }
+}
+
+/// Resets the timeout when health goes down, and moves the bar over the enemy's head.
+///
+/// Bars only draw on the `PlayerCamera`, so they stay out of the GoPro.
+pub fn update_enemy_health_bars(
+    mut commands: Commands,
+    time: Res<Time>,
+    camera_query: Query<(Entity, &Camera, &GlobalTransform), With<PlayerCamera>>,
+    enemy_query: Query<(&Health, &GlobalTransform, Option<&Humanoid>)>,
+    head_query: Query<&GlobalTransform>,
+    mut bar_query: Query<
+        (
+            Entity,
+            &mut EnemyHealthBar,
+            &mut Style,
+            &mut BackgroundColor,
+            &Children,
+            Option<&TargetCamera>,
+        ),
+        Without<EnemyHealthBarFill>,
+    >,
+    mut fill_query: Query<(&mut Style, &mut BackgroundColor), With<EnemyHealthBarFill>>,
+) {
+    let camera = camera_query.get_single().ok();
+
+    for (e_bar, mut bar, mut style, mut color, children, target_camera) in bar_query.iter_mut() {
+        let Ok((health, g_transform, humanoid)) = enemy_query.get(bar.target) else {
+            continue;
+        };
+
+        if health.0 < bar.last {
+            bar.timer.reset();
+        } else {
+            bar.timer.tick(time.delta());
+        }
+        bar.last = health.0;
+        bar.max = bar.max.max(health.0);
+
+        let anchor = humanoid
+            .and_then(|h| head_query.get(h.head).ok())
+            .unwrap_or(g_transform)
+            .translation()
+            + Vec3::Y * ENEMY_HEALTH_BAR_OFFSET;
+        let position = camera.filter(|_| !bar.timer.finished()).and_then(
+            |(e_camera, camera, g_camera_transform)| {
+                camera
+                    .world_to_viewport(g_camera_transform, anchor)
+                    .map(|position| (e_camera, position))
+            },
+        );
+
+        let Some((e_camera, position)) = position else {
+            if style.display != Display::None {
+                style.display = Display::None;
+            }
+            continue;
+        };
+
+        if target_camera.map(|t| t.0) != Some(e_camera) {
+            commands.entity(e_bar).insert(TargetCamera(e_camera));
+        }
+
+        style.display = Display::Flex;
+        style.left = Val::Px(position.x - ENEMY_HEALTH_BAR_WIDTH / 2.0);
+        style.top = Val::Px(position.y);
+
+        let alpha = bar.alpha();
+        color.0 = BAR_BACKGROUND_COLOR.with_a(BAR_BACKGROUND_COLOR.a() * alpha);
+
+        let fill = match bar.max > 0.0 {
+            true => (health.0 / bar.max).clamp(0.0, 1.0),
+            false => 0.0,
+        };
+        for &e_fill in children.iter() {
+            if let Ok((mut fill_style, mut fill_color)) = fill_query.get_mut(e_fill) {
+                fill_style.width = Val::Percent(fill * 100.0);
+                fill_color.0 = BAR_FILL_COLOR.with_a(alpha);
+            }
+        }
+    }
+}
+
+/// Gets rid of bars whose enemy is gone or `Shattered`.
+pub fn despawn_enemy_health_bars(
+    mut commands: Commands,
+    enemy_query: Query<(), (With<Health>, Without<Shattered>)>,
+    bar_query: Query<(Entity, &EnemyHealthBar)>,
+) {
+    for (e_bar, bar) in bar_query.iter() {
+        if !enemy_query.contains(bar.target) {
+            commands.entity(e_bar).despawn_recursive();
+        }
+    }
+}
+
+/// Highlights the outline of the enemy under the crosshair, or under the mouse for
+/// `CameraAlignment::FortyFive`. The old one goes back to normal.
+pub fn highlight_aim_target(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    look_info: Res<LookInfo>,
+    camera_query: Query<&PlayerCamera>,
+    enemy_query: Query<&Faction, Without<Dead>>,
+    hitbox_query: Query<&Hitbox>,
+    parent_query: Query<&Parent>,
+    mut highlight: ResMut<AimHighlight>,
+) {
+    let ray = camera_query
+        .get_single()
+        .ok()
+        .and_then(|camera| match camera.alignment {
+            CameraAlignment::FortyFive => look_info.mouse_ray,
+            CameraAlignment::Shooter { .. } => look_info.viewport_ray,
+        });
+
+    let target = ray
+        .and_then(|ray| {
+            rapier_context.cast_ray(
+                ray.origin,
+                *ray.direction,
+                AIM_HIGHLIGHT_DISTANCE,
+                true,
+                QueryFilter::new().groups(CollisionGroups::new(
+                    Group::all(),
+                    Group::MAP | Group::ENEMY,
+                )),
+            )
+        })
+        .and_then(|(e_hit, _)| {
+            let e_hit = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+            std::iter::once(e_hit)
+                .chain(parent_query.iter_ancestors(e_hit))
+                .find(|&e| enemy_query.get(e).is_ok_and(|f| *f == Faction::Enemy))
+        });
+
+    if highlight.0 == target {
+        return;
+    }
+    if let Some(mut e_old) = highlight.0.and_then(|e| commands.get_entity(e)) {
+        e_old.clear_outline_override("aim_highlight");
+    }
+    if let Some(e_target) = target {
+        commands.entity(e_target).set_outline_override(
+            "aim_highlight",
+            OutlineOverride::color(AIM_HIGHLIGHT_COLOR, AIM_HIGHLIGHT_PRIORITY),
+        );
+    }
+    highlight.0 = target;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use super::*;
+
+    fn bar(app: &mut App) -> Option<(f32, bool)> {
+        app.world
+            .query::<&EnemyHealthBar>()
+            .get_single(&app.world)
+            .ok()
+            .map(|bar| (bar.last, bar.timer.finished()))
+    }
+
+    #[test]
+    fn enemy_health_bars() {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .add_plugins(EnemyHudPlugin);
+
+        let e_enemy = app
+            .world
+            .spawn((Faction::Enemy, Health(100.0), TransformBundle::default()))
+            .id();
+        app.world
+            .spawn((Faction::Player, Health(100.0), TransformBundle::default()));
+        app.update();
+        assert_eq!(
+            app.world
+                .query::<&EnemyHealthBar>()
+                .iter(&app.world)
+                .count(),
+            1,
+            "Wrong number of health bars."
+        );
+        assert_eq!(bar(&mut app), Some((100.0, true)), "Undamaged bar is up.");
+
+        app.world.get_mut::<Health>(e_enemy).unwrap().0 = 60.0;
+        app.update();
+        assert_eq!(bar(&mut app), Some((60.0, false)), "Damaged bar isn't up.");
+
+        for _ in 0..(ENEMY_HEALTH_BAR_TIMEOUT * 10.0) as usize + 1 {
+            app.update();
+        }
+        assert_eq!(bar(&mut app), Some((60.0, true)), "Bar didn't time out.");
+
+        app.world.entity_mut(e_enemy).insert(Shattered);
+        app.update();
+        assert_eq!(bar(&mut app), None, "Bar outlived its enemy.");
+    }
+}
@@ -1,9 +1,19 @@
+pub mod beat;
 pub mod boombox;
 pub mod bt;
 pub mod dummy;
+pub mod encounter;
+pub mod gunslinger;
+pub mod hud;
+pub mod loot;
+pub mod minimap;
 pub mod movement;
+pub mod perception;
 pub mod screamer;
 pub mod spawn;
+pub mod squad;
+pub mod targeting;
+pub mod telegraph;
 
 use bevy::{app::PluginGroupBuilder, prelude::*};
 use bevy_enum_filter::prelude::*;
@@ -13,21 +23,44 @@ use bevy_landmass::{
 };
 use bevy_mod_inverse_kinematics::InverseKinematicsPlugin;
 use bevy_rapier3d::prelude::*;
-use grin_damage::health::{DamageBuffer, Dead, Health, Resist};
+use grin_character::PlayerCharacter;
+use grin_damage::{
+    faction::Faction,
+    health::{DamageBuffer, Dead, DeathEvent, Health, LastDamagedBy, Resist},
+};
 use grin_derive::TypedEvents;
-use grin_map::MapLoadState;
-use grin_physics::{CollisionGroupExt, CollisionGroupsExt, PhysicsTime};
-use grin_rig::humanoid::{Humanoid, HumanoidPartType};
+use grin_item::{
+    equip::{Equipped, EquippedTo},
+    mechanics::firing::{Active, Reloading, Target},
+    plugin::Weapon,
+};
+use grin_map::{MapLoadState, NavMeshDebugging};
+use grin_physics::{platform::Carriable, CollisionGroupExt, CollisionGroupsExt, PhysicsTime};
+use grin_render::capture::KillCamEvent;
+use grin_rig::humanoid::{Humanoid, HumanoidDamageScales, HumanoidPartType};
 use grin_time::{scaling::RawVelocity, Rewind};
 use grin_util::event::Spawnable;
 use spawn::MasterSpawnPlugin;
 
 use self::{
+    beat::BeatPlugin,
     boombox::BoomBoxPlugin,
     bt::{Action, Brain, MasterBehaviorPlugin, Verdict},
     dummy::DummyPlugin,
-    movement::{update_biped_procedural_walk_cycle, AttackTarget, PathBehavior},
+    encounter::EncounterPlugin,
+    gunslinger::GunslingerPlugin,
+    hud::EnemyHudPlugin,
+    loot::{LootPlugin, LootTable},
+    minimap::MinimapPlugin,
+    movement::{
+        avoid_nav_obstacles, draw_patrol_routes, init_patrols, keep_out_of_water, separate_agents,
+        suspend_patrols, update_biped_procedural_walk_cycle, AttackTarget, PathBehavior,
+        Separation,
+    },
+    perception::{Awareness, Perception, PerceptionPlugin},
     screamer::ScreamerPlugin,
+    squad::SquadPlugin,
+    telegraph::TelegraphPlugin,
 };
 pub use enemy_identifier_filters::*;
 
@@ -70,7 +103,22 @@ impl Plugin for MasterAiPlugin {
                 LandmassPlugin,
                 InverseKinematicsPlugin,
             ))
-            .add_systems(Update, (update_biped_procedural_walk_cycle,));
+            .add_systems(
+                Update,
+                (
+                    update_biped_procedural_walk_cycle,
+                    (init_patrols, suspend_patrols)
+                        .chain()
+                        .before(AiSet::RunTrees),
+                    (separate_agents, avoid_nav_obstacles, keep_out_of_water)
+                        .chain()
+                        .after(AiSet::RunTrees)
+                        .before(LandmassSystemSet::SyncValues),
+                    draw_patrol_routes.run_if(resource_exists::<NavMeshDebugging>),
+                    kill_cam_on_enemy_death,
+                    arm_enemy_items,
+                ),
+            );
     }
 }
 
@@ -81,24 +129,73 @@ impl PluginGroup for AiPlugins {
         PluginGroupBuilder::start::<Self>()
             .add(MasterAiPlugin)
             .add(MasterSpawnPlugin)
+            .add(EncounterPlugin)
+            .add(PerceptionPlugin::<PlayerCharacter>::default())
+            .add(SquadPlugin)
+            .add(TelegraphPlugin)
+            .add(EnemyHudPlugin)
+            .add(LootPlugin)
+            .add(MinimapPlugin)
+            .add(BeatPlugin)
             .add(BoomBoxPlugin)
             .add(DummyPlugin)
+            .add(GunslingerPlugin)
             .add(ScreamerPlugin)
     }
 }
 
+/// How far above the killer the kill cam goes.
+pub const KILL_CAM_HEIGHT: f32 = 2.0;
+
+/// Watches enemies die from their killer's point of view, if the kill cam is on.
+pub fn kill_cam_on_enemy_death(
+    enemy_query: Query<(), With<EnemyIdentifier>>,
+    transform_query: Query<&GlobalTransform>,
+    mut death_events: EventReader<DeathEvent>,
+    mut kill_cam_events: EventWriter<KillCamEvent>,
+) {
+    for &DeathEvent { entity, killer } in death_events.read() {
+        let Some(g_killer_transform) = killer.and_then(|e| transform_query.get(e).ok()) else {
+            continue;
+        };
+        if enemy_query.contains(entity) {
+            kill_cam_events.send(KillCamEvent {
+                victim: entity,
+                from: g_killer_transform.translation() + Vec3::Y * KILL_CAM_HEIGHT,
+            });
+        }
+    }
+}
+
+/// Agents with this go after whoever hit them last instead of the closest target, if they can.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PreferAttacker;
+
+/// Agents with `Awareness` only consider targets they're `Alert` to.
 pub fn set_closest_attack_target<T: Component, A: Component, E: Component>(
     mut commands: Commands,
     mut agent_query: Query<
-        (Entity, &mut Brain, &GlobalTransform),
+        (
+            Entity,
+            &mut Brain,
+            &GlobalTransform,
+            Option<&LastDamagedBy>,
+            Has<PreferAttacker>,
+            Option<&Awareness>,
+        ),
         (With<T>, With<A>, Without<Rewind>, Without<Dead>),
     >,
     target_query: Query<(Entity, &GlobalTransform), With<E>>,
 ) {
-    for (e_agent, mut brain, src_transform) in agent_query.iter_mut() {
+    for (e_agent, mut brain, src_transform, last_damaged_by, prefer_attacker, awareness) in
+        agent_query.iter_mut()
+    {
         let mut new_target = None;
         let mut target_distance = f32::MAX;
         for (e_target, dst_transform) in target_query.iter() {
+            if awareness.is_some_and(|a| !a.is_alert_to(e_target)) {
+                continue;
+            }
             let distance = src_transform
                 .translation()
                 .distance(dst_transform.translation());
@@ -108,6 +205,12 @@ pub fn set_closest_attack_target<T: Component, A: Component, E: Component>(
             }
         }
 
+        if let Some(LastDamagedBy(e_attacker)) = last_damaged_by.filter(|_| prefer_attacker) {
+            if target_query.contains(*e_attacker) {
+                new_target = Some(AttackTarget(*e_attacker));
+            }
+        }
+
         if let Some(t) = new_target {
             commands.entity(e_agent).insert(t);
             brain.write_verdict(Verdict::Success);
@@ -120,16 +223,22 @@ pub fn set_closest_attack_target<T: Component, A: Component, E: Component>(
 
 pub fn configure_humanoid_physics<T: Component>(
     mut commands: Commands,
-    humanoid_query: Query<(Entity, &Humanoid), (Added<Humanoid>, With<T>)>,
+    humanoid_query: Query<
+        (Entity, &Humanoid, Option<&HumanoidDamageScales>),
+        (Added<Humanoid>, With<T>),
+    >,
 ) {
-    for (e_humanoid, humanoid) in humanoid_query.iter() {
+    for (e_humanoid, humanoid, scales) in humanoid_query.iter() {
         commands
             .entity(e_humanoid)
             .insert(RigidBody::KinematicVelocityBased);
 
-        for e_part in humanoid.parts(HumanoidPartType::HITBOX) {
-            commands.entity(e_part).insert((
+        let scales = scales.copied().unwrap_or_default();
+        for part in HumanoidPartType::HITBOX {
+            let scale = scales.get(&part);
+            commands.entity(humanoid.part(part)).insert((
                 DamageBuffer::default(),
+                scale,
                 CollisionGroups::from_group_default(Group::ENEMY),
             ));
         }
@@ -177,6 +286,7 @@ pub struct EnemyAgentBundle<A: Action> {
     pub health: Health,
     pub resist: Resist,
     pub damage_buffer: DamageBuffer,
+    pub faction: Faction,
     pub collision_groups: CollisionGroups,
     pub brain: Brain,
     pub action: A,
@@ -189,6 +299,11 @@ pub struct EnemyAgentBundle<A: Action> {
     pub rapier_velocity: Velocity,
     pub raw_velocity: RawVelocity,
     pub rapier_body: RigidBody,
+    pub perception: Perception,
+    pub awareness: Awareness,
+    pub separation: Separation,
+    pub carriable: Carriable,
+    pub loot_table: LootTable,
 }
 
 impl<A: Action> EnemyAgentBundle<A> {
@@ -197,6 +312,7 @@ impl<A: Action> EnemyAgentBundle<A> {
             health: Health::default(),
             resist: Resist::default(),
             damage_buffer: DamageBuffer::default(),
+            faction: Faction::Enemy,
             collision_groups: CollisionGroups::from_group_default(Group::ENEMY),
             brain: Brain::default(),
             action: A::no_op(),
@@ -212,6 +328,11 @@ impl<A: Action> EnemyAgentBundle<A> {
             rapier_velocity: Velocity::default(),
             raw_velocity: RawVelocity::default(),
             rapier_body: RigidBody::KinematicVelocityBased,
+            perception: Perception::default(),
+            awareness: Awareness::default(),
+            separation: Separation::default(),
+            carriable: Carriable,
+            loot_table: LootTable::default(),
         }
     }
 }
@@ -255,8 +376,122 @@ pub fn blocking_cooldown<T: Component, A: Component, C: Cooldown>(
     cooldown_win_lose(&time, &mut agent_query, Verdict::Success, Verdict::Running);
 }
 
+/// Writes `Verdict::Failure` while any of the agent's items are `Reloading`,
+/// `Verdict::Success` otherwise.
+pub fn protective_reload<T: Component, A: Component>(
+    mut agent_query: Query<(Entity, &mut Brain), (With<T>, With<A>)>,
+    children_query: Query<&Children>,
+    reload_query: Query<(), With<Reloading>>,
+) {
+    for (e_agent, mut brain) in agent_query.iter_mut() {
+        let reloading = children_query
+            .iter_descendants(e_agent)
+            .any(|e| reload_query.contains(e));
+        brain.write_verdict(match reloading {
+            true => Verdict::Failure,
+            false => Verdict::Success,
+        });
+    }
+}
+
+/// Which hand a dual wielding agent fires next.
+#[derive(Component, Copy, Clone, Debug, Default)]
+pub struct AlternateHands {
+    pub off_hand: bool,
+}
+
+/// Makes one hand's weapon `Active` and the other's not, switching hands every time it runs.
+/// Agents without an off hand weapon always use the dominant hand. Writes `Verdict::Success`.
+pub fn alternate_dual_fire<T: Component, A: Component>(
+    mut commands: Commands,
+    mut agent_query: Query<
+        (&mut Brain, &Humanoid, &Equipped, &mut AlternateHands),
+        (With<T>, With<A>, Without<Rewind>, Without<Dead>),
+    >,
+    weapon_query: Query<(), With<Weapon>>,
+) {
+    for (mut brain, humanoid, equipped, mut alternate) in agent_query.iter_mut() {
+        let (e_main, e_off) = equipped.by_dominance(humanoid.dominant_hand_type);
+        let dual = e_main != e_off && weapon_query.contains(e_off);
+        let (e_fire, e_rest) = match alternate.off_hand && dual {
+            true => (e_off, e_main),
+            false => (e_main, e_off),
+        };
+
+        if weapon_query.contains(e_fire) {
+            commands.entity(e_fire).insert(Active);
+        }
+        if dual {
+            commands.entity(e_rest).remove::<Active>();
+            alternate.off_hand = !alternate.off_hand;
+        }
+
+        brain.write_verdict(Verdict::Success);
+    }
+}
+
+/// Points the `Target` of the agent's items at its `AttackTarget`. Writes `Verdict::Failure`
+/// without an `AttackTarget` or anything `Equipped`, `Verdict::Success` otherwise.
+pub fn aim_at_attack_target<T: Component, A: Component>(
+    mut agent_query: Query<
+        (&mut Brain, Option<&Equipped>, Option<&AttackTarget>),
+        (With<T>, With<A>, Without<Rewind>, Without<Dead>),
+    >,
+    g_transform_query: Query<&GlobalTransform>,
+    mut item_query: Query<(&mut Target, &GlobalTransform)>,
+) {
+    for (mut brain, equipped, attack_target) in agent_query.iter_mut() {
+        let (Some(equipped), Some(g_target_transform)) = (
+            equipped,
+            attack_target.and_then(|AttackTarget(e)| g_transform_query.get(*e).ok()),
+        ) else {
+            brain.write_verdict(Verdict::Failure);
+            continue;
+        };
+
+        for e_item in [equipped.left, equipped.right] {
+            if let Ok((mut target, g_item_transform)) = item_query.get_mut(e_item) {
+                *target = Target::from_pair(
+                    g_item_transform.translation(),
+                    g_target_transform.translation(),
+                );
+            }
+        }
+        brain.write_verdict(Verdict::Success);
+    }
+}
+
+/// Takes `Active` off of the agent's items every frame, so that whatever leaf made them `Active`
+/// only gets one shot out of them. Goes in `PreBehaviorIteration`.
+pub fn cease_fire<T: Component>(
+    mut commands: Commands,
+    agent_query: Query<&Equipped, With<T>>,
+    active_query: Query<(), With<Active>>,
+) {
+    for equipped in agent_query.iter() {
+        for e_item in [equipped.left, equipped.right] {
+            if active_query.contains(e_item) {
+                commands.entity(e_item).remove::<Active>();
+            }
+        }
+    }
+}
+
+/// Items equipped to enemies shoot enemy projectiles.
+pub fn arm_enemy_items(
+    faction_query: Query<&Faction>,
+    mut item_query: Query<(&EquippedTo, &mut CollisionGroups), Changed<EquippedTo>>,
+) {
+    for (EquippedTo { target }, mut groups) in item_query.iter_mut() {
+        if matches!(faction_query.get(*target), Ok(Faction::Enemy)) {
+            *groups = CollisionGroups::from_group_default(Group::ENEMY_PROJECTILE);
+        }
+    }
+}
+
 #[derive(Component, Copy, Clone, Debug, EnumFilter, TypedEvents, Default)]
 pub enum EnemyIdentifier {
     #[default]
     Dummy,
+    Gunslinger,
 }
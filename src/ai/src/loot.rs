@@ -0,0 +1,485 @@
+//! Stuff that pops out of enemies when they die, and flies to the player when they get close.
+
+use std::{f32::consts::TAU, time::Duration};
+
+use bevy::prelude::*;
+use bevy_asset_loader::prelude::*;
+use bevy_rapier3d::prelude::*;
+use bevy_tweening::{
+    lens::{TransformPositionLens, TransformRotateYLens},
+    Animator, EaseFunction, EaseMethod, RepeatCount, RepeatStrategy, Tracks, Tween,
+};
+use grin_asset::AssetLoadState;
+use grin_character::PlayerCharacter;
+use grin_damage::health::{DeathEvent, Health};
+use grin_item::{
+    equip::Equipped,
+    inventory::Inventory,
+    mechanics::firing::{Ammo, AmmoChangedEvent},
+};
+use grin_physics::{CollisionGroupExt, CollisionGroupsExt};
+use grin_render::sketched::{NoOutline, SketchMaterial};
+use grin_time::{CommandsExt, Parked, Rewind, RewindableDespawn};
+use grin_util::rng::{GrinRng, RNG_LOOT};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_loading_state(
+            LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<LootAssets>(),
+        )
+        .init_resource::<GrinRng>()
+        .init_resource::<Score>()
+        .add_event::<LootCollectedEvent>()
+        // in case `AmmoPlugin` isn't around
+        .add_event::<AmmoChangedEvent>()
+        .add_systems(
+            Update,
+            (
+                drop_loot,
+                init_pickup_models.run_if(in_state(AssetLoadState::Success)),
+                (attract_pickups, collect_pickups).chain(),
+                expire_pickups,
+                repark_consumed_pickups,
+            ),
+        );
+    }
+}
+
+/// Collider radius.
+pub const PICKUP_RADIUS: f32 = 0.15;
+
+/// How fast pickups pop out of whatever dropped them.
+pub const PICKUP_POP_SPEED: f32 = 4.0;
+
+/// Seconds before a pickup goes away on its own.
+pub const PICKUP_LIFETIME: f32 = 20.0;
+
+/// Pickups closer than this to the player start flying at them.
+pub const PICKUP_MAGNET_RADIUS: f32 = 4.0;
+
+pub const PICKUP_MAGNET_ACCELERATION: f32 = 40.0;
+
+pub const PICKUP_MAGNET_MAX_SPEED: f32 = 12.0;
+
+/// Pickups go for this high above the player's feet.
+pub const PICKUP_MAGNET_HEIGHT: f32 = 1.0;
+
+/// Pickups this close to where they're headed get picked up.
+pub const PICKUP_COLLECT_RADIUS: f32 = 0.75;
+
+/// How far the model bobs up and down.
+pub const PICKUP_BOB_HEIGHT: f32 = 0.15;
+
+#[derive(Resource, AssetCollection)]
+pub struct LootAssets {
+    #[asset(key = "mesh.sphere_100cm")]
+    pub orb: Handle<Mesh>,
+    #[asset(key = "mat.green_unlit")]
+    pub health: Handle<SketchMaterial>,
+    #[asset(key = "mat.yellow_unlit")]
+    pub ammo: Handle<SketchMaterial>,
+    #[asset(key = "mat.blue_unlit")]
+    pub score: Handle<SketchMaterial>,
+}
+
+/// Points from score orbs.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Score(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Loot {
+    Health(f32),
+    /// Goes into the reserve of every weapon with a limited one.
+    Ammo(u32),
+    Score(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LootEntry {
+    /// `None` drops nothing.
+    pub loot: Option<Loot>,
+    pub weight: f32,
+}
+
+/// Rolled `rolls` times when this entity dies.
+#[derive(Component, Debug, Clone)]
+pub struct LootTable {
+    pub entries: Vec<LootEntry>,
+    pub rolls: u32,
+}
+
+impl Default for LootTable {
+    fn default() -> Self {
+        Self::new(1)
+            .with_nothing(2.0)
+            .with(Loot::Health(15.0), 1.0)
+            .with(Loot::Ammo(10), 1.0)
+            .with(Loot::Score(10), 2.0)
+    }
+}
+
+impl LootTable {
+    pub fn new(rolls: u32) -> Self {
+        Self {
+            entries: Vec::new(),
+            rolls,
+        }
+    }
+
+    pub fn with(mut self, loot: Loot, weight: f32) -> Self {
+        self.entries.push(LootEntry {
+            loot: Some(loot),
+            weight,
+        });
+        self
+    }
+
+    pub fn with_nothing(mut self, weight: f32) -> Self {
+        self.entries.push(LootEntry { loot: None, weight });
+        self
+    }
+
+    /// Everything that dropped. Empty if the weights don't add up to anything.
+    pub fn roll(&self, rng: &mut impl Rng) -> Vec<Loot> {
+        let Ok(index) = WeightedIndex::new(self.entries.iter().map(|e| e.weight)) else {
+            return Vec::new();
+        };
+        (0..self.rolls)
+            .filter_map(|_| self.entries[index.sample(rng)].loot)
+            .collect()
+    }
+}
+
+/// Something on the ground that does `loot` to the player when they touch it.
+#[derive(Component, Debug, Clone)]
+pub struct Pickup {
+    pub loot: Loot,
+    pub lifetime: Timer,
+}
+
+/// The pickup's been used. It stays this way if it gets rewound, so it can't be used twice.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Consumed;
+
+/// The bobbing, spinning thing under a `Pickup`.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct PickupModel;
+
+#[derive(Event, Debug, Clone, Copy)]
+pub struct LootCollectedEvent {
+    pub pickup: Entity,
+    pub collector: Entity,
+    pub loot: Loot,
+}
+
+/// Rolls the `LootTable` of anything that dies, and pops the pickups out of it.
+pub fn drop_loot(
+    mut commands: Commands,
+    mut rng: ResMut<GrinRng>,
+    loot_query: Query<(&LootTable, &GlobalTransform)>,
+    mut death_events: EventReader<DeathEvent>,
+) {
+    for &DeathEvent { entity, .. } in death_events.read() {
+        let Ok((loot_table, g_transform)) = loot_query.get(entity) else {
+            continue;
+        };
+
+        let rng = rng.fork(RNG_LOOT);
+        let transform = Transform::from_translation(g_transform.translation());
+        for loot in loot_table.roll(rng) {
+            let direction = Vec3::new(rng.gen_range(-1.0..1.0), 1.0, rng.gen_range(-1.0..1.0));
+            commands.spawn((
+                Pickup {
+                    loot,
+                    lifetime: Timer::from_seconds(PICKUP_LIFETIME, TimerMode::Once),
+                },
+                SpatialBundle {
+                    transform,
+                    global_transform: transform.into(),
+                    ..Default::default()
+                },
+                RigidBody::Dynamic,
+                Collider::ball(PICKUP_RADIUS),
+                CollisionGroups::from_group_default(Group::DEBRIS),
+                Velocity::linear(direction.normalize() * PICKUP_POP_SPEED),
+                RewindableDespawn,
+            ));
+        }
+    }
+}
+
+pub fn init_pickup_models(
+    mut commands: Commands,
+    assets: Res<LootAssets>,
+    pickup_query: Query<(Entity, &Pickup), Added<Pickup>>,
+) {
+    for (e_pickup, pickup) in pickup_query.iter() {
+        let material = match pickup.loot {
+            Loot::Health(..) => assets.health.clone(),
+            Loot::Ammo(..) => assets.ammo.clone(),
+            Loot::Score(..) => assets.score.clone(),
+        };
+        let bob = Tween::new(
+            EaseFunction::SineInOut,
+            Duration::from_millis(800),
+            TransformPositionLens {
+                start: Vec3::ZERO,
+                end: Vec3::Y * PICKUP_BOB_HEIGHT,
+            },
+        )
+        .with_repeat_count(RepeatCount::Infinite)
+        .with_repeat_strategy(RepeatStrategy::MirroredRepeat);
+        let spin = Tween::new(
+            EaseMethod::Linear,
+            Duration::from_secs(2),
+            TransformRotateYLens {
+                start: 0.0,
+                end: TAU,
+            },
+        )
+        .with_repeat_count(RepeatCount::Infinite);
+
+        commands.entity(e_pickup).with_children(|parent| {
+            parent.spawn((
+                PickupModel,
+                MaterialMeshBundle {
+                    mesh: assets.orb.clone(),
+                    material,
+                    transform: Transform::from_scale(Vec3::splat(PICKUP_RADIUS * 2.0)),
+                    ..Default::default()
+                },
+                Animator::new(Tracks::new([bob, spin])),
+                NoOutline,
+            ));
+        });
+    }
+}
+
+/// Pulls pickups in range towards the player.
+pub fn attract_pickups(
+    time: Res<Time>,
+    player_query: Query<&GlobalTransform, With<PlayerCharacter>>,
+    mut pickup_query: Query<
+        (&GlobalTransform, &mut Velocity),
+        (With<Pickup>, Without<Consumed>, Without<Parked>),
+    >,
+) {
+    let Ok(g_player_transform) = player_query.get_single() else {
+        return;
+    };
+    let target = g_player_transform.translation() + Vec3::Y * PICKUP_MAGNET_HEIGHT;
+
+    for (g_transform, mut velocity) in pickup_query.iter_mut() {
+        let offset = target - g_transform.translation();
+        if offset.length() > PICKUP_MAGNET_RADIUS {
+            continue;
+        }
+        velocity.linvel = (velocity.linvel
+            + offset.normalize_or_zero() * PICKUP_MAGNET_ACCELERATION * time.delta_seconds())
+        .clamp_length_max(PICKUP_MAGNET_MAX_SPEED);
+    }
+}
+
+/// Applies pickups that reached the player, then despawns them.
+pub fn collect_pickups(
+    mut commands: Commands,
+    mut score: ResMut<Score>,
+    mut player_query: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            Option<&mut Health>,
+            Option<&Equipped>,
+            Option<&Inventory>,
+        ),
+        With<PlayerCharacter>,
+    >,
+    pickup_query: Query<(Entity, &Pickup, &GlobalTransform), (Without<Consumed>, Without<Parked>)>,
+    mut ammo_query: Query<&mut Ammo>,
+    mut collected_events: EventWriter<LootCollectedEvent>,
+    mut ammo_events: EventWriter<AmmoChangedEvent>,
+) {
+    let Ok((e_player, g_player_transform, mut health, equipped, inventory)) =
+        player_query.get_single_mut()
+    else {
+        return;
+    };
+    let target = g_player_transform.translation() + Vec3::Y * PICKUP_MAGNET_HEIGHT;
+
+    for (e_pickup, pickup, g_transform) in pickup_query.iter() {
+        if g_transform.translation().distance(target) > PICKUP_COLLECT_RADIUS {
+            continue;
+        }
+
+        match pickup.loot {
+            Loot::Health(amount) => {
+                if let Some(health) = health.as_mut() {
+                    health.0 += amount;
+                }
+            }
+            Loot::Ammo(amount) => {
+                let mut items = equipped
+                    .into_iter()
+                    .flat_map(|e| [e.left, e.right])
+                    .chain(inventory.into_iter().flat_map(|i| i.items.iter().copied()))
+                    .collect::<Vec<_>>();
+                items.sort();
+                items.dedup();
+                for e_item in items {
+                    let Ok(mut ammo) = ammo_query.get_mut(e_item) else {
+                        continue;
+                    };
+                    if let Some(reserve) = ammo.reserve.as_mut() {
+                        *reserve += amount;
+                        ammo_events.send(AmmoChangedEvent {
+                            entity: e_item,
+                            ammo: *ammo,
+                        });
+                    }
+                }
+            }
+            Loot::Score(amount) => score.0 += amount,
+        }
+
+        commands.entity(e_pickup).insert(Consumed).time_despawn();
+        collected_events.send(LootCollectedEvent {
+            pickup: e_pickup,
+            collector: e_player,
+            loot: pickup.loot,
+        });
+    }
+}
+
+pub fn expire_pickups(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pickup_query: Query<(Entity, &mut Pickup), Without<Parked>>,
+) {
+    for (e_pickup, mut pickup) in pickup_query.iter_mut() {
+        if pickup.lifetime.tick(time.delta()).just_finished() {
+            commands.entity(e_pickup).time_despawn();
+        }
+    }
+}
+
+/// Rewinding brings back pickups that were already used. They can't be picked up again, and
+/// they go away again once the rewind's over.
+pub fn repark_consumed_pickups(
+    mut commands: Commands,
+    pickup_query: Query<Entity, (With<Consumed>, Without<Parked>, Without<Rewind>)>,
+) {
+    for e_pickup in pickup_query.iter() {
+        commands.entity(e_pickup).time_despawn();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pickups(app: &mut App) -> Vec<Entity> {
+        app.world
+            .query_filtered::<Entity, With<Pickup>>()
+            .iter(&app.world)
+            .collect()
+    }
+
+    #[test]
+    fn loot_rolls() {
+        let table = LootTable::new(8)
+            .with(Loot::Score(1), 1.0)
+            .with_nothing(0.0);
+        assert_eq!(
+            table.roll(GrinRng::new(0).fork(RNG_LOOT)),
+            vec![Loot::Score(1); 8],
+            "Rolled something with no weight."
+        );
+        assert!(
+            LootTable::new(8)
+                .with_nothing(0.0)
+                .roll(GrinRng::new(0).fork(RNG_LOOT))
+                .is_empty(),
+            "Rolled an empty table."
+        );
+    }
+
+    #[test]
+    fn collect_once() {
+        let mut app = App::new();
+        app.insert_resource(GrinRng::new(0))
+            .init_resource::<Time>()
+            .init_resource::<Score>()
+            .add_event::<DeathEvent>()
+            .add_event::<LootCollectedEvent>()
+            .add_event::<AmmoChangedEvent>()
+            .add_systems(
+                Update,
+                (
+                    drop_loot,
+                    collect_pickups,
+                    repark_consumed_pickups.after(collect_pickups),
+                ),
+            );
+
+        let transform = Transform::from_xyz(0.0, 0.0, 4.0);
+        let e_enemy = app
+            .world
+            .spawn((
+                LootTable::new(2).with(Loot::Health(25.0), 1.0),
+                TransformBundle {
+                    local: transform,
+                    global: transform.into(),
+                },
+            ))
+            .id();
+        app.world.send_event(DeathEvent {
+            entity: e_enemy,
+            killer: None,
+        });
+        app.update();
+        assert_eq!(pickups(&mut app).len(), 2, "Wrong number of drops.");
+
+        // standing right where they dropped
+        let transform = Transform::from_xyz(0.0, -PICKUP_MAGNET_HEIGHT, 4.0);
+        let e_player = app
+            .world
+            .spawn((
+                PlayerCharacter,
+                Health(50.0),
+                TransformBundle {
+                    local: transform,
+                    global: transform.into(),
+                },
+            ))
+            .id();
+        app.update();
+        assert_eq!(
+            app.world.get::<Health>(e_player).unwrap().0,
+            100.0,
+            "Didn't heal from the pickups."
+        );
+
+        // as if they got rewound to before they were picked up
+        for e_pickup in pickups(&mut app) {
+            app.world.entity_mut(e_pickup).remove::<Parked>();
+        }
+        app.update();
+        assert_eq!(
+            app.world.get::<Health>(e_player).unwrap().0,
+            100.0,
+            "Rewound pickups healed again."
+        );
+        for e_pickup in pickups(&mut app) {
+            assert!(
+                app.world.get::<Parked>(e_pickup).is_some(),
+                "Rewound pickup didn't go away again."
+            );
+        }
+    }
+}
@@ -0,0 +1,592 @@
+//! Overhead map in the corner, showing the player and enemies.
+
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        camera::{ClearColorConfig, ScalingMode},
+        mesh::{Indices, PrimitiveTopology},
+        render_asset::RenderAssetUsages,
+        render_resource::{AsBindGroup, ShaderRef},
+        view::RenderLayers,
+    },
+};
+use grin_character::PlayerCharacter;
+use grin_damage::{faction::Faction, health::Dead};
+use grin_map::NavMeshGeometry;
+use grin_render::{
+    gopro::{create_gopro, create_image_target, GoProSettings},
+    sketched::{NoOutline, SketchMaterial, SketchMaterialInfo},
+    RenderLayer,
+};
+
+use crate::perception::Awareness;
+
+/// How far above the player the minimap camera floats.
+pub const MINIMAP_CAMERA_HEIGHT: f32 = 64.0;
+
+/// How big markers are, as a fraction of `MinimapSettings::zoom`.
+pub const MINIMAP_MARKER_SCALE: f32 = 0.08;
+
+/// Markers go this far above what they're marking, so they draw over the map outline.
+pub const MINIMAP_MARKER_HEIGHT: f32 = 1.0;
+
+/// `MinimapSettings::zoom` stays in here.
+pub const MINIMAP_ZOOM_RANGE: (f32, f32) = (8.0, 96.0);
+
+/// Each press of +/- zooms by this much.
+pub const MINIMAP_ZOOM_STEP: f32 = 1.25;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(UiMaterialPlugin::<MinimapMaterial>::default())
+            .init_resource::<MinimapSettings>()
+            .add_systems(Startup, init_minimap_assets)
+            .add_systems(
+                Update,
+                (
+                    (spawn_minimap, init_minimap_cameras, despawn_minimap).chain(),
+                    zoom_minimap,
+                    (spawn_minimap_markers, update_minimap_markers).chain(),
+                    spawn_minimap_outline.run_if(resource_exists_and_changed::<NavMeshGeometry>),
+                ),
+            );
+    }
+}
+
+/// Which enemies show up on the minimap.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MinimapReveal {
+    /// Only ones that are `Awareness::Alert`.
+    #[default]
+    Alerted,
+    Always,
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct MinimapSettings {
+    pub reveal: MinimapReveal,
+    /// World units from the middle of the minimap to the edge.
+    pub zoom: f32,
+    /// Pixels across.
+    pub size: u32,
+}
+
+impl Default for MinimapSettings {
+    fn default() -> Self {
+        Self {
+            reveal: MinimapReveal::default(),
+            zoom: 24.0,
+            size: 200,
+        }
+    }
+}
+
+/// Shows a `GoPro` image cropped to a circle.
+#[derive(Asset, TypePath, AsBindGroup, Clone, Debug)]
+pub struct MinimapMaterial {
+    #[texture(0)]
+    #[sampler(1)]
+    pub image: Handle<Image>,
+    #[uniform(2)]
+    pub rim_color: Color,
+}
+
+impl UiMaterial for MinimapMaterial {
+    fn fragment_shader() -> ShaderRef {
+        "shaders/minimap.wgsl".into()
+    }
+}
+
+#[derive(Resource)]
+pub struct MinimapAssets {
+    pub arrow: Handle<Mesh>,
+    pub dot: Handle<Mesh>,
+    pub player: Handle<SketchMaterial>,
+    pub unaware: Handle<SketchMaterial>,
+    pub suspicious: Handle<SketchMaterial>,
+    pub alert: Handle<SketchMaterial>,
+    pub outline: Handle<SketchMaterial>,
+}
+
+impl MinimapAssets {
+    pub fn awareness_material(&self, awareness: &Awareness) -> &Handle<SketchMaterial> {
+        match awareness {
+            Awareness::Unaware => &self.unaware,
+            Awareness::Suspicious { .. } => &self.suspicious,
+            Awareness::Alert { .. } => &self.alert,
+        }
+    }
+}
+
+/// Follows the player around, with the minimap camera on it.
+#[derive(Component)]
+pub struct MinimapAnchor;
+
+#[derive(Component)]
+pub struct MinimapCamera;
+
+/// The UI node.
+#[derive(Component)]
+pub struct Minimap;
+
+/// Shows where `target` is on the minimap.
+#[derive(Component, Debug)]
+pub struct MinimapMarker {
+    pub target: Entity,
+}
+
+/// Map geometry on the minimap, made from the navmesh.
+#[derive(Component)]
+pub struct MinimapOutline;
+
+fn minimap_layer() -> RenderLayers {
+    RenderLayers::layer(RenderLayer::MINIMAP as u8)
+}
+
+/// Lies flat, facing up, so the minimap camera can see it.
+fn flat_mesh(mesh: impl Into<Mesh>) -> Mesh {
+    mesh.into().rotated_by(Quat::from_rotation_x(-FRAC_PI_2))
+}
+
+fn marker_material(color: Color) -> SketchMaterial {
+    SketchMaterial {
+        base: StandardMaterial {
+            base_color: color,
+            unlit: true,
+            ..Default::default()
+        },
+        extension: SketchMaterialInfo::default(),
+    }
+}
+
+pub fn init_minimap_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SketchMaterial>>,
+) {
+    commands.insert_resource(MinimapAssets {
+        // pointing forward
+        arrow: meshes.add(flat_mesh(Triangle2d::new(
+            Vec2::new(0.0, 0.5),
+            Vec2::new(-0.35, -0.5),
+            Vec2::new(0.35, -0.5),
+        ))),
+        dot: meshes.add(flat_mesh(Circle::new(0.35))),
+        player: materials.add(marker_material(Color::rgb(0.2, 0.8, 1.0))),
+        unaware: materials.add(marker_material(Color::rgb(0.8, 0.8, 0.8))),
+        suspicious: materials.add(marker_material(Color::rgb(1.0, 0.8, 0.0))),
+        alert: materials.add(marker_material(Color::rgb(1.0, 0.1, 0.1))),
+        outline: materials.add({
+            // it's whichever way the navmesh is wound
+            let mut material = marker_material(Color::rgb(0.25, 0.25, 0.3));
+            material.base.cull_mode = None;
+            material.base.double_sided = true;
+            material
+        }),
+    });
+}
+
+pub fn spawn_minimap(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut minimap_materials: ResMut<Assets<MinimapMaterial>>,
+    settings: Res<MinimapSettings>,
+    player_query: Query<(), Added<PlayerCharacter>>,
+    minimap_query: Query<(), With<MinimapAnchor>>,
+) {
+    if player_query.is_empty() || !minimap_query.is_empty() {
+        return;
+    }
+
+    let e_anchor = commands
+        .spawn((MinimapAnchor, TransformBundle::default()))
+        .id();
+
+    let gopro_settings = GoProSettings {
+        entity: e_anchor,
+        // north is up
+        transform: Transform::from_xyz(0.0, MINIMAP_CAMERA_HEIGHT, 0.0)
+            .looking_to(Vec3::NEG_Y, Vec3::NEG_Z),
+        size: UVec2::splat(settings.size),
+        render_layers: minimap_layer(),
+        exclusive_layer: false,
+    };
+    let image = create_image_target(&mut images, gopro_settings.size);
+    commands
+        .spawn((
+            MinimapCamera,
+            create_gopro(image.clone_weak(), gopro_settings),
+        ))
+        .insert(Projection::Orthographic(OrthographicProjection {
+            scaling_mode: ScalingMode::FixedVertical(settings.zoom * 2.0),
+            far: MINIMAP_CAMERA_HEIGHT * 2.0,
+            ..Default::default()
+        }))
+        .set_parent(e_anchor);
+
+    commands.spawn((
+        Minimap,
+        MaterialNodeBundle {
+            material: minimap_materials.add(MinimapMaterial {
+                image,
+                rim_color: Color::rgba(1.0, 1.0, 1.0, 0.8),
+            }),
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(12.0),
+                right: Val::Px(12.0),
+                width: Val::Px(settings.size as f32),
+                height: Val::Px(settings.size as f32),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    ));
+}
+
+/// `GoPro`s clear to the normal background, which looks weird in the corner.
+pub fn init_minimap_cameras(mut camera_query: Query<&mut Camera, Added<MinimapCamera>>) {
+    for mut camera in camera_query.iter_mut() {
+        camera.clear_color = ClearColorConfig::Custom(Color::rgba(0.0, 0.0, 0.0, 0.6));
+    }
+}
+
+/// Throws out the minimap when there's no player, like when it's getting respawned.
+/// Otherwise, keeps it over the player.
+pub fn despawn_minimap(
+    mut commands: Commands,
+    player_query: Query<&GlobalTransform, With<PlayerCharacter>>,
+    mut anchor_query: Query<(Entity, &mut Transform), With<MinimapAnchor>>,
+    minimap_query: Query<Entity, With<Minimap>>,
+) {
+    let Ok(g_player_transform) = player_query.get_single() else {
+        for entity in anchor_query
+            .iter()
+            .map(|(e, _)| e)
+            .chain(minimap_query.iter())
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    };
+
+    for (_, mut transform) in anchor_query.iter_mut() {
+        transform.translation = g_player_transform.translation();
+    }
+}
+
+/// Zooms in with +, out with -.
+pub fn zoom_minimap(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut settings: ResMut<MinimapSettings>,
+    mut camera_query: Query<&mut Projection, With<MinimapCamera>>,
+) {
+    let (min, max) = MINIMAP_ZOOM_RANGE;
+    if keys.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        settings.zoom = (settings.zoom / MINIMAP_ZOOM_STEP).clamp(min, max);
+    }
+    if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        settings.zoom = (settings.zoom * MINIMAP_ZOOM_STEP).clamp(min, max);
+    }
+
+    if !settings.is_changed() {
+        return;
+    }
+    for mut projection in camera_query.iter_mut() {
+        if let Projection::Orthographic(ref mut ortho) = *projection {
+            ortho.scaling_mode = ScalingMode::FixedVertical(settings.zoom * 2.0);
+        }
+    }
+}
+
+fn marker_bundle(mesh: &Handle<Mesh>, material: &Handle<SketchMaterial>) -> impl Bundle {
+    (
+        MaterialMeshBundle {
+            mesh: mesh.clone(),
+            material: material.clone(),
+            ..Default::default()
+        },
+        minimap_layer(),
+        NoOutline,
+        NotShadowCaster,
+    )
+}
+
+pub fn spawn_minimap_markers(
+    mut commands: Commands,
+    assets: Option<Res<MinimapAssets>>,
+    player_query: Query<Entity, Added<PlayerCharacter>>,
+    enemy_query: Query<(Entity, &Faction, &Awareness), Added<Awareness>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+
+    for e_player in player_query.iter() {
+        commands.spawn((
+            MinimapMarker { target: e_player },
+            marker_bundle(&assets.arrow, &assets.player),
+        ));
+    }
+
+    for (e_enemy, faction, awareness) in enemy_query.iter() {
+        if *faction != Faction::Enemy {
+            continue;
+        }
+        commands.spawn((
+            MinimapMarker { target: e_enemy },
+            marker_bundle(&assets.dot, assets.awareness_material(awareness)),
+        ));
+    }
+}
+
+/// Moves markers to what they're marking, and throws them out when it's gone or dead.
+pub fn update_minimap_markers(
+    mut commands: Commands,
+    assets: Option<Res<MinimapAssets>>,
+    settings: Res<MinimapSettings>,
+    target_query: Query<(&GlobalTransform, Option<&Awareness>), Without<Dead>>,
+    mut marker_query: Query<(
+        Entity,
+        &MinimapMarker,
+        &mut Transform,
+        &mut Visibility,
+        &mut Handle<SketchMaterial>,
+    )>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    let scale = Vec3::splat(settings.zoom * MINIMAP_MARKER_SCALE);
+
+    for (e_marker, marker, mut transform, mut visibility, mut material) in marker_query.iter_mut() {
+        let Ok((g_target_transform, awareness)) = target_query.get(marker.target) else {
+            commands.entity(e_marker).despawn_recursive();
+            continue;
+        };
+
+        let target_transform = g_target_transform.compute_transform();
+        let (yaw, _, _) = target_transform.rotation.to_euler(EulerRot::YXZ);
+        *transform = Transform {
+            translation: target_transform.translation + Vec3::Y * MINIMAP_MARKER_HEIGHT,
+            rotation: Quat::from_rotation_y(yaw),
+            scale,
+        };
+
+        let Some(awareness) = awareness else {
+            continue;
+        };
+        let revealed = match settings.reveal {
+            MinimapReveal::Always => true,
+            MinimapReveal::Alerted => matches!(awareness, Awareness::Alert { .. }),
+        };
+        let new_visibility = match revealed {
+            true => Visibility::Inherited,
+            false => Visibility::Hidden,
+        };
+        if *visibility != new_visibility {
+            *visibility = new_visibility;
+        }
+
+        let awareness_material = assets.awareness_material(awareness);
+        if *material != *awareness_material {
+            *material = awareness_material.clone();
+        }
+    }
+}
+
+/// Flattens the walkable parts of the navmesh into a mesh for the minimap.
+pub fn spawn_minimap_outline(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    assets: Option<Res<MinimapAssets>>,
+    navmesh: Res<NavMeshGeometry>,
+    outline_query: Query<Entity, With<MinimapOutline>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    for e_outline in outline_query.iter() {
+        commands.entity(e_outline).despawn_recursive();
+    }
+
+    let vertices = &navmesh.0.vertices;
+    let indices = navmesh
+        .0
+        .polygons
+        .iter()
+        .filter(|poly| poly.len() >= 3)
+        // fan out from the first vertex
+        .flat_map(|poly| (1..poly.len() - 1).map(move |i| [poly[0], poly[i + 1], poly[i]]))
+        .flatten()
+        .map(|i| i as u32)
+        .collect::<Vec<_>>();
+
+    let mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices.clone())
+    .with_inserted_attribute(Mesh::ATTRIBUTE_NORMAL, vec![Vec3::Y; vertices.len()])
+    .with_inserted_attribute(Mesh::ATTRIBUTE_UV_0, vec![Vec2::ZERO; vertices.len()])
+    .with_inserted_indices(Indices::U32(indices));
+
+    commands.spawn((
+        MinimapOutline,
+        marker_bundle(&meshes.add(mesh), &assets.outline),
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{ecs::entity::EntityHashSet, render::mesh::MeshPlugin};
+
+    use super::*;
+
+    fn enemy(app: &mut App, translation: Vec3) -> Entity {
+        app.world
+            .spawn((
+                Faction::Enemy,
+                Awareness::Unaware,
+                GlobalTransform::from_translation(translation),
+            ))
+            .id()
+    }
+
+    fn marker(app: &mut App, target: Entity) -> Option<(Entity, Transform, Visibility)> {
+        app.world
+            .query::<(Entity, &MinimapMarker, &Transform, &Visibility)>()
+            .iter(&app.world)
+            .find(|(_, marker, _, _)| marker.target == target)
+            .map(|(entity, _, transform, visibility)| (entity, *transform, *visibility))
+    }
+
+    #[test]
+    fn minimap_markers() {
+        let mut app = App::new();
+        app.add_plugins((AssetPlugin::default(), MeshPlugin))
+            .init_asset::<SketchMaterial>()
+            .init_resource::<MinimapSettings>()
+            .add_systems(Startup, init_minimap_assets)
+            .add_systems(
+                Update,
+                (spawn_minimap_markers, update_minimap_markers).chain(),
+            );
+
+        let e_player = app
+            .world
+            .spawn((
+                PlayerCharacter,
+                GlobalTransform::from(
+                    Transform::from_xyz(1.0, 0.0, 2.0).with_rotation(Quat::from_rotation_y(1.0)),
+                ),
+            ))
+            .id();
+        let e_enemy = enemy(&mut app, Vec3::new(5.0, 0.0, 5.0));
+        app.update();
+        app.update();
+
+        let (_, player_marker, _) = marker(&mut app, e_player).expect("No player marker.");
+        assert!(
+            player_marker
+                .translation
+                .abs_diff_eq(Vec3::new(1.0, MINIMAP_MARKER_HEIGHT, 2.0), 1e-5),
+            "Player marker isn't over the player."
+        );
+        assert!(
+            player_marker
+                .rotation
+                .angle_between(Quat::from_rotation_y(1.0))
+                < 1e-3,
+            "Player marker isn't facing where the player is."
+        );
+        assert_eq!(
+            marker(&mut app, e_enemy).map(|(_, _, v)| v),
+            Some(Visibility::Hidden),
+            "Unaware enemy was revealed."
+        );
+
+        *app.world.get_mut::<Awareness>(e_enemy).unwrap() = Awareness::Alert {
+            targets: EntityHashSet::from_iter([e_player]),
+            last_seen: Vec3::ZERO,
+        };
+        app.update();
+        let (e_enemy_marker, _, visibility) = marker(&mut app, e_enemy).unwrap();
+        assert_eq!(
+            visibility,
+            Visibility::Inherited,
+            "Alert enemy wasn't revealed."
+        );
+        assert_eq!(
+            app.world.get::<Handle<SketchMaterial>>(e_enemy_marker),
+            Some(&app.world.resource::<MinimapAssets>().alert),
+            "Marker isn't colored by awareness."
+        );
+
+        let e_hidden = enemy(&mut app, Vec3::ZERO);
+        app.world.resource_mut::<MinimapSettings>().reveal = MinimapReveal::Always;
+        app.update();
+        app.update();
+        assert_eq!(
+            marker(&mut app, e_hidden).map(|(_, _, v)| v),
+            Some(Visibility::Inherited),
+            "Enemy wasn't always revealed."
+        );
+
+        app.world.entity_mut(e_enemy).insert(Dead);
+        app.update();
+        assert!(
+            app.world.get_entity(e_enemy_marker).is_none(),
+            "Dead enemy's marker wasn't despawned."
+        );
+    }
+
+    #[test]
+    fn minimap_zoom() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<MinimapSettings>()
+            .add_systems(Update, zoom_minimap);
+
+        let e_camera = app
+            .world
+            .spawn((MinimapCamera, Projection::Orthographic(default())))
+            .id();
+        let zoom = MinimapSettings::default().zoom;
+
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Equal);
+        app.update();
+        assert_eq!(
+            app.world.resource::<MinimapSettings>().zoom,
+            zoom / MINIMAP_ZOOM_STEP,
+            "Didn't zoom in."
+        );
+        let Projection::Orthographic(ortho) = app.world.get::<Projection>(e_camera).unwrap() else {
+            unreachable!();
+        };
+        assert!(
+            matches!(ortho.scaling_mode, ScalingMode::FixedVertical(h) if h == zoom / MINIMAP_ZOOM_STEP * 2.0),
+            "Camera didn't zoom."
+        );
+
+        for _ in 0..32 {
+            let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+            keys.clear();
+            keys.release_all();
+            keys.press(KeyCode::Minus);
+            app.update();
+        }
+        assert_eq!(
+            app.world.resource::<MinimapSettings>().zoom,
+            MINIMAP_ZOOM_RANGE.1,
+            "Zoom went past the max."
+        );
+    }
+}
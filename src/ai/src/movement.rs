@@ -1,19 +1,57 @@
-use bevy::{math::cubic_splines::CubicCurve, prelude::*};
+use bevy::{ecs::entity::EntityHashSet, math::cubic_splines::CubicCurve, prelude::*};
 use bevy_landmass::{Agent, AgentDesiredVelocity, AgentTarget, AgentVelocity};
 use bevy_rapier3d::prelude::*;
-use grin_damage::health::Dead;
-use grin_physics::PhysicsTime;
+use grin_damage::{health::Dead, hitbox::Hitbox, status::Slowed};
+use grin_item::{
+    equip::Equipped,
+    mechanics::melee::{MeleeSwing, Swinging},
+};
+use grin_map::{
+    obstacle::{NavObstacle, NavObstacles},
+    NavMeshDebugging, NavMeshGeometry,
+};
+use grin_physics::{
+    volume::{volumes_at, MovementVolume, MovementVolumeKind, MOVEMENT_VOLUME_PROBE_HEIGHT},
+    CollisionGroupExt, PhysicsTime,
+};
+use grin_rig::{
+    footstep::{surface_at, Foot, FootstepEvent, SurfaceKind},
+    humanoid::{HUMANOID_HEIGHT, HUMANOID_RADIUS},
+};
 use grin_time::{
     scaling::{RawVelocity, TimeScale},
     Rewind,
 };
-use grin_util::{numbers::MulStack, vectors::Vec3Ext};
+use grin_util::{
+    numbers::MulStack,
+    rng::{GrinRng, RNG_PATROL},
+    vectors::Vec3Ext,
+};
+use rand::Rng;
 
-use super::bt::{Brain, Verdict};
+use super::{
+    bt::{Brain, Verdict},
+    perception::Awareness,
+};
 
 /// Proportional constant for the angular velocity P controller.
 pub const AGENT_ANGULAR_VELOCITY_P: f32 = 1.0;
 
+/// How close an agent needs to get to a waypoint to move on to the next one.
+pub const PATROL_ARRIVAL_RADIUS: f32 = 1.0;
+
+/// `PatrolRoute::wait_time` is randomly scaled by up to this much either way.
+pub const PATROL_WAIT_JITTER: f32 = 0.25;
+
+/// How far ahead of itself a strafing agent aims along the circle.
+pub const STRAFE_LEAD: f32 = 2.0;
+
+/// Height of the ray used to check whether a spot is behind cover. About chest height.
+pub const COVER_HEIGHT: f32 = 1.0;
+
+/// How close an agent needs to get to its cover.
+pub const COVER_ARRIVAL_RADIUS: f32 = 0.5;
+
 #[derive(Bundle, Default)]
 pub struct MovementBundle {
     pub path_behavior: PathBehavior,
@@ -67,6 +105,7 @@ pub fn propagate_attack_target_to_agent_target<T: Component, A: Component>(
             &Transform,
             &AttackTarget,
             &PathBehavior,
+            Option<&Slowed>,
         ),
         (With<T>, With<A>, Without<Rewind>, Without<Dead>),
     >,
@@ -81,14 +120,16 @@ pub fn propagate_attack_target_to_agent_target<T: Component, A: Component>(
         transform,
         AttackTarget(e_target),
         path_behavior,
+        slowed,
     ) in agent_query.iter_mut()
     {
+        let speed = slowed.map_or(1.0, |s| s.0);
         let target = transform_query.get(*e_target).unwrap();
 
         let direction = (target.translation - transform.translation).xz_flat();
         *agent_target = match *path_behavior {
             PathBehavior::Beeline { velocity } => {
-                agent.max_velocity = velocity;
+                agent.max_velocity = velocity * speed;
                 AgentTarget::Entity(*e_target)
             }
             PathBehavior::Strafe {
@@ -99,7 +140,7 @@ pub fn propagate_attack_target_to_agent_target<T: Component, A: Component>(
                     CircularVelocity::Linear(v) => v / direction.length(),
                     CircularVelocity::Angular(v) => v,
                 };
-                agent.max_velocity = radial_velocity.hypot(angular);
+                agent.max_velocity = radial_velocity.hypot(angular) * speed;
 
                 let mut new_transform = transform.clone();
                 new_transform.translation += direction.normalize() * radial_velocity * dt;
@@ -115,19 +156,706 @@ pub fn propagate_attack_target_to_agent_target<T: Component, A: Component>(
             }
         };
 
-        let angle_diff = Quat::from_rotation_arc(
-            transform.forward().xz_flat().normalize(),
-            direction.normalize(),
-        );
-        let (axis, mut angle_diff) = angle_diff.to_axis_angle();
-        // we do a little trolling
-        if axis == Vec3::NEG_Y {
-            angle_diff *= -1.0;
+        raw_velocity.0.angvel = turn_towards(transform, direction);
+
+        brain.write_verdict(Verdict::Success);
+    }
+}
+
+/// Angular velocity to face `direction`.
+fn turn_towards(transform: &Transform, direction: Vec3) -> Vec3 {
+    let angle_diff = Quat::from_rotation_arc(
+        transform.forward().xz_flat().normalize(),
+        direction.normalize(),
+    );
+    let (axis, mut angle_diff) = angle_diff.to_axis_angle();
+    // we do a little trolling
+    if axis == Vec3::NEG_Y {
+        angle_diff *= -1.0;
+    }
+
+    Vec3::Y * AGENT_ANGULAR_VELOCITY_P * angle_diff
+}
+
+/// What happens after the last waypoint of a `PatrolRoute`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PatrolMode {
+    /// Back to the first one.
+    #[default]
+    Loop,
+    /// Back the way it came.
+    PingPong,
+    /// Stays there.
+    Once,
+}
+
+/// Where to walk while there's no `AttackTarget`. Needs `patrol::<T, A>` in the behavior tree.
+#[derive(Component, Clone, Debug, Default)]
+pub struct PatrolRoute {
+    pub waypoints: Vec<Vec3>,
+    pub mode: PatrolMode,
+    /// Seconds to idle at each waypoint.
+    pub wait_time: f32,
+}
+
+impl PatrolRoute {
+    /// Index of the closest waypoint to `point`.
+    pub fn nearest(&self, point: Vec3) -> Option<usize> {
+        self.waypoints
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.distance(point).total_cmp(&b.distance(point)))
+            .map(|(i, _)| i)
+    }
+}
+
+/// Progress along a `PatrolRoute`. Added automatically.
+#[derive(Component, Clone, Debug, Default)]
+pub struct PatrolState {
+    /// Waypoint it's walking to.
+    pub index: usize,
+    /// Walking back along a `PatrolMode::PingPong` route.
+    pub reversed: bool,
+    /// Seconds left to idle at the last waypoint.
+    pub wait: f32,
+    /// Reached the end of a `PatrolMode::Once` route.
+    pub finished: bool,
+    /// Got distracted. It goes back to the nearest waypoint once it calms down.
+    pub suspended: bool,
+}
+
+impl PatrolState {
+    /// Moves on to the next waypoint.
+    pub fn advance(&mut self, route: &PatrolRoute) {
+        let Some(last) = route.waypoints.len().checked_sub(1) else {
+            return;
+        };
+        match route.mode {
+            PatrolMode::Loop => self.index = (self.index + 1) % (last + 1),
+            PatrolMode::PingPong if last == 0 => (),
+            PatrolMode::PingPong => {
+                if self.index == last {
+                    self.reversed = true;
+                } else if self.index == 0 {
+                    self.reversed = false;
+                }
+                self.index = match self.reversed {
+                    true => self.index - 1,
+                    false => self.index + 1,
+                };
+            }
+            PatrolMode::Once if self.index >= last => self.finished = true,
+            PatrolMode::Once => self.index += 1,
         }
+    }
+}
+
+pub fn init_patrols(
+    mut commands: Commands,
+    agent_query: Query<Entity, (With<PatrolRoute>, Without<PatrolState>)>,
+) {
+    for e_agent in agent_query.iter() {
+        commands.entity(e_agent).insert(PatrolState::default());
+    }
+}
 
-        raw_velocity.0.angvel = Vec3::Y * AGENT_ANGULAR_VELOCITY_P * angle_diff;
+/// Suspends patrols while there's an `AttackTarget` or the agent isn't `Unaware`.
+pub fn suspend_patrols(
+    mut agent_query: Query<(&mut PatrolState, Has<AttackTarget>, Option<&Awareness>)>,
+) {
+    for (mut state, attacking, awareness) in agent_query.iter_mut() {
+        if attacking || !matches!(awareness, None | Some(Awareness::Unaware)) {
+            state.suspended = true;
+        }
+    }
+}
 
+/// Walks the `PatrolRoute`. Writes `Verdict::Failure` without one, `Verdict::Success` otherwise.
+///
+/// Stands still while `Suspicious`.
+pub fn patrol<T: Component, A: Component>(
+    time: Res<PhysicsTime>,
+    mut agent_query: Query<
+        (
+            &mut Brain,
+            &mut AgentTarget,
+            &mut RawVelocity,
+            &mut AgentVelocity,
+            &AgentDesiredVelocity,
+            &Transform,
+            Option<&PatrolRoute>,
+            Option<&mut PatrolState>,
+            Option<&Awareness>,
+            Option<&TimeScale>,
+        ),
+        (With<T>, With<A>, Without<Rewind>, Without<Dead>),
+    >,
+    mut rng: ResMut<GrinRng>,
+) {
+    let rng = rng.fork(RNG_PATROL);
+    for (
+        mut brain,
+        mut agent_target,
+        mut raw_velocity,
+        mut agent_velocity,
+        desired_velocity,
+        transform,
+        route,
+        state,
+        awareness,
+        time_scale,
+    ) in agent_query.iter_mut()
+    {
+        let (Some(route), Some(mut state)) = (route, state) else {
+            brain.write_verdict(Verdict::Failure);
+            continue;
+        };
         brain.write_verdict(Verdict::Success);
+
+        let waypoint = match route.waypoints.get(state.index) {
+            _ if state.finished => None,
+            _ if matches!(awareness, Some(Awareness::Suspicious { .. })) => None,
+            _ if state.suspended => {
+                // walk back instead of picking up where it left off
+                state.suspended = false;
+                state.wait = 0.0;
+                state.index = route.nearest(transform.translation).unwrap_or_default();
+                route.waypoints.get(state.index)
+            }
+            _ if state.wait > 0.0 => {
+                state.wait -= time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+                None
+            }
+            waypoint => waypoint,
+        };
+
+        let Some(&waypoint) = waypoint else {
+            *agent_target = AgentTarget::None;
+            raw_velocity.0.linvel = Vec3::ZERO;
+            raw_velocity.0.angvel = Vec3::ZERO;
+            agent_velocity.0 = Vec3::ZERO;
+            continue;
+        };
+
+        let direction = (waypoint - transform.translation).xz_flat();
+        if direction.length() <= PATROL_ARRIVAL_RADIUS {
+            state.wait = route.wait_time
+                * rng.gen_range(1.0 - PATROL_WAIT_JITTER..=1.0 + PATROL_WAIT_JITTER);
+            state.advance(route);
+            continue;
+        }
+
+        *agent_target = AgentTarget::Point(waypoint);
+        raw_velocity.0.linvel = desired_velocity.velocity();
+        raw_velocity.0.angvel = turn_towards(transform, direction);
+        agent_velocity.0 = raw_velocity.0.linvel;
+    }
+}
+
+/// Draws `PatrolRoute`s along with the navmesh.
+pub fn draw_patrol_routes(
+    mut gizmos: Gizmos,
+    debugging: Res<NavMeshDebugging>,
+    agent_query: Query<(&PatrolRoute, Option<&PatrolState>)>,
+) {
+    for (route, state) in agent_query.iter() {
+        let points = route.waypoints.iter().copied();
+        match route.mode {
+            PatrolMode::Loop => {
+                gizmos.linestrip(points.chain(route.waypoints.first().copied()), debugging.0)
+            }
+            _ => gizmos.linestrip(points, debugging.0),
+        }
+        for (i, &waypoint) in route.waypoints.iter().enumerate() {
+            let radius = match state.is_some_and(|s| s.index == i) {
+                true => PATROL_ARRIVAL_RADIUS,
+                false => PATROL_ARRIVAL_RADIUS / 2.0,
+            };
+            gizmos.sphere(waypoint, Quat::IDENTITY, radius, debugging.0);
+        }
+    }
+}
+
+/// Circles the `AttackTarget` at `radius`, switching directions every so often.
+/// Needs `strafe::<T, A>` in the behavior tree.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Strafe {
+    /// Preferred distance from the target.
+    pub radius: f32,
+    /// Seconds between switching directions.
+    pub direction_flip_interval: f32,
+    /// Going clockwise.
+    pub clockwise: bool,
+    /// Seconds since it last switched.
+    pub elapsed: f32,
+}
+
+impl Strafe {
+    pub fn new(radius: f32, direction_flip_interval: f32) -> Self {
+        Self {
+            radius,
+            direction_flip_interval,
+            clockwise: false,
+            elapsed: 0.0,
+        }
+    }
+}
+
+/// Strafes around the `AttackTarget` while facing it. Writes `Verdict::Failure` without a
+/// `Strafe` or `AttackTarget`, `Verdict::Success` otherwise.
+pub fn strafe<T: Component, A: Component>(
+    time: Res<PhysicsTime>,
+    mut agent_query: Query<
+        (
+            &mut Brain,
+            &mut AgentTarget,
+            &mut RawVelocity,
+            &mut AgentVelocity,
+            &AgentDesiredVelocity,
+            &Transform,
+            Option<&mut Strafe>,
+            Option<&AttackTarget>,
+            Option<&TimeScale>,
+        ),
+        (With<T>, With<A>, Without<Rewind>, Without<Dead>),
+    >,
+    target_query: Query<&GlobalTransform>,
+) {
+    for (
+        mut brain,
+        mut agent_target,
+        mut raw_velocity,
+        mut agent_velocity,
+        desired_velocity,
+        transform,
+        strafe,
+        attack_target,
+        time_scale,
+    ) in agent_query.iter_mut()
+    {
+        let (Some(mut strafe), Some(Ok(g_target_transform))) =
+            (strafe, attack_target.map(|t| target_query.get(t.0)))
+        else {
+            brain.write_verdict(Verdict::Failure);
+            continue;
+        };
+
+        strafe.elapsed += time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        if strafe.elapsed >= strafe.direction_flip_interval {
+            strafe.elapsed = 0.0;
+            strafe.clockwise = !strafe.clockwise;
+        }
+
+        let target = g_target_transform.translation();
+        let direction = (target - transform.translation).xz_flat();
+        // the point on the circle closest to the agent, then a little further along
+        let outward = -direction.normalize_or_zero();
+        let tangent = match strafe.clockwise {
+            true => outward.cross(Vec3::Y),
+            false => Vec3::Y.cross(outward),
+        };
+        let goal = target + outward * strafe.radius + tangent * STRAFE_LEAD;
+
+        *agent_target = AgentTarget::Point(goal.with_y(transform.translation.y));
+        raw_velocity.0.linvel = desired_velocity.velocity();
+        raw_velocity.0.angvel = turn_towards(transform, direction);
+        agent_velocity.0 = raw_velocity.0.linvel;
+
+        brain.write_verdict(Verdict::Success);
+    }
+}
+
+/// Hides from the `AttackTarget` behind map geometry. Needs `seek_cover::<T, A>` in the
+/// behavior tree.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct SeekCover {
+    /// Furthest it'll look for cover.
+    pub max_search_dist: f32,
+    /// Where it's headed, if it found somewhere.
+    pub cover: Option<Vec3>,
+}
+
+impl SeekCover {
+    pub fn new(max_search_dist: f32) -> Self {
+        Self {
+            max_search_dist,
+            cover: None,
+        }
+    }
+}
+
+/// Closest of `candidates` within `max_search_dist` of `origin` that can't see `target`.
+pub fn find_cover(
+    rapier_context: &RapierContext,
+    candidates: impl IntoIterator<Item = Vec3>,
+    origin: Vec3,
+    target: Vec3,
+    max_search_dist: f32,
+) -> Option<Vec3> {
+    candidates
+        .into_iter()
+        .filter(|point| point.distance(origin) <= max_search_dist)
+        .filter(|&point| {
+            let eye = point + Vec3::Y * COVER_HEIGHT;
+            let offset = target - eye;
+            let distance = offset.length();
+            distance > f32::EPSILON
+                && rapier_context
+                    .cast_ray(
+                        eye,
+                        offset / distance,
+                        distance,
+                        true,
+                        QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+                    )
+                    .is_some()
+        })
+        .min_by(|a, b| a.distance(origin).total_cmp(&b.distance(origin)))
+}
+
+/// Paths to the closest spot on the navmesh the `AttackTarget` can't see.
+///
+/// Writes `Verdict::Running` on the way, `Verdict::Success` once it's there, and
+/// `Verdict::Failure` if there's nowhere to hide.
+pub fn seek_cover<T: Component, A: Component>(
+    rapier_context: Res<RapierContext>,
+    navmesh: Option<Res<NavMeshGeometry>>,
+    mut agent_query: Query<
+        (
+            &mut Brain,
+            &mut AgentTarget,
+            &mut RawVelocity,
+            &mut AgentVelocity,
+            &AgentDesiredVelocity,
+            &Transform,
+            Option<&mut SeekCover>,
+            Option<&AttackTarget>,
+        ),
+        (With<T>, With<A>, Without<Rewind>, Without<Dead>),
+    >,
+    target_query: Query<&GlobalTransform>,
+) {
+    for (
+        mut brain,
+        mut agent_target,
+        mut raw_velocity,
+        mut agent_velocity,
+        desired_velocity,
+        transform,
+        seek_cover,
+        attack_target,
+    ) in agent_query.iter_mut()
+    {
+        let (Some(mut seek_cover), Some(Ok(g_target_transform))) =
+            (seek_cover, attack_target.map(|t| target_query.get(t.0)))
+        else {
+            brain.write_verdict(Verdict::Failure);
+            continue;
+        };
+        let target = g_target_transform.translation();
+
+        if seek_cover.cover.is_none() {
+            seek_cover.cover = navmesh.as_ref().and_then(|navmesh| {
+                find_cover(
+                    &rapier_context,
+                    navmesh
+                        .polygon_centers()
+                        .map(|p| p.with_y(transform.translation.y)),
+                    transform.translation,
+                    target,
+                    seek_cover.max_search_dist,
+                )
+            });
+        }
+
+        let Some(cover) = seek_cover.cover else {
+            brain.write_verdict(Verdict::Failure);
+            continue;
+        };
+
+        let direction = (cover - transform.translation).xz_flat();
+        if direction.length() <= COVER_ARRIVAL_RADIUS {
+            // look again next time, the target has probably moved
+            seek_cover.cover = None;
+            *agent_target = AgentTarget::None;
+            raw_velocity.0.linvel = Vec3::ZERO;
+            raw_velocity.0.angvel =
+                turn_towards(transform, (target - transform.translation).xz_flat());
+            agent_velocity.0 = Vec3::ZERO;
+            brain.write_verdict(Verdict::Success);
+            continue;
+        }
+
+        *agent_target = AgentTarget::Point(cover);
+        raw_velocity.0.linvel = desired_velocity.velocity();
+        raw_velocity.0.angvel = turn_towards(transform, direction);
+        agent_velocity.0 = raw_velocity.0.linvel;
+        brain.write_verdict(Verdict::Running);
+    }
+}
+
+/// Pushes agents away from each other so they don't clump up.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Separation {
+    /// Other agents inside this get pushed away.
+    pub radius: f32,
+    /// Velocity of the push when they're right on top of each other.
+    pub strength: f32,
+}
+
+impl Default for Separation {
+    fn default() -> Self {
+        Self {
+            radius: 1.5,
+            strength: 2.0,
+        }
+    }
+}
+
+/// Adds the `Separation` push on top of whatever the behavior tree decided.
+///
+/// Doesn't push agents into the map, or anyone in the middle of a melee swing.
+pub fn separate_agents(
+    time: Res<PhysicsTime>,
+    rapier_context: Res<RapierContext>,
+    mut agent_query: Query<
+        (
+            Entity,
+            &Separation,
+            &GlobalTransform,
+            &mut RawVelocity,
+            Option<&mut AgentVelocity>,
+            Option<&Equipped>,
+            Option<&TimeScale>,
+        ),
+        (Without<Rewind>, Without<Dead>),
+    >,
+    transform_query: Query<&GlobalTransform>,
+    hitbox_query: Query<&Hitbox>,
+    melee_query: Query<(), Or<(With<MeleeSwing>, With<Swinging>)>>,
+) {
+    let push_shape = Collider::ball(HUMANOID_RADIUS);
+    for (
+        e_agent,
+        separation,
+        g_transform,
+        mut raw_velocity,
+        agent_velocity,
+        equipped,
+        time_scale,
+    ) in agent_query.iter_mut()
+    {
+        let dt = time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        if dt <= 0.0 {
+            continue;
+        }
+
+        // it needs to actually reach whoever it's hitting
+        if equipped.is_some_and(|e| melee_query.contains(e.left) || melee_query.contains(e.right)) {
+            continue;
+        }
+
+        let center = g_transform.translation() + Vec3::Y * HUMANOID_HEIGHT / 2.0;
+
+        let mut neighbors = EntityHashSet::default();
+        rapier_context.intersections_with_shape(
+            center,
+            Quat::IDENTITY,
+            &Collider::ball(separation.radius),
+            QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::ENEMY)),
+            |e_hit| {
+                let e_other = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+                if e_other != e_agent {
+                    neighbors.insert(e_other);
+                }
+                true
+            },
+        );
+
+        let mut push = Vec3::ZERO;
+        for e_other in neighbors {
+            let Ok(g_other_transform) = transform_query.get(e_other) else {
+                continue;
+            };
+            let offset = (g_transform.translation() - g_other_transform.translation()).xz_flat();
+            let distance = offset.length();
+            if distance >= separation.radius {
+                continue;
+            }
+            // stacked right on top of each other, so just pick a way to go
+            let direction = match distance > f32::EPSILON {
+                true => offset / distance,
+                false => match e_agent < e_other {
+                    true => Vec3::X,
+                    false => Vec3::NEG_X,
+                },
+            };
+            push += direction * (1.0 - distance / separation.radius);
+        }
+        push *= separation.strength;
+
+        if push == Vec3::ZERO {
+            continue;
+        }
+
+        // stop at walls
+        if let Some((_, hit)) = rapier_context.cast_shape(
+            center,
+            Quat::IDENTITY,
+            push,
+            &push_shape,
+            ShapeCastOptions::with_max_time_of_impact(dt),
+            QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+        ) {
+            push *= hit.time_of_impact / dt;
+        }
+
+        raw_velocity.0.linvel += push;
+        if let Some(mut agent_velocity) = agent_velocity {
+            agent_velocity.0 = raw_velocity.0.linvel;
+        }
+    }
+}
+
+/// How far away agents start steering around a `NavObstacle`.
+pub const NAV_OBSTACLE_LOOKAHEAD: f32 = 3.0;
+
+/// How hard agents turn away from a `NavObstacle` right in front of them.
+pub const NAV_OBSTACLE_STEERING: f32 = 2.0;
+
+/// Steers agents around `NavObstacle`s, since they aren't in the navmesh.
+///
+/// Only turns, so they don't slow down. Obstacles behind them are ignored.
+pub fn avoid_nav_obstacles(
+    obstacles: Res<NavObstacles>,
+    obstacle_query: Query<(&NavObstacle, &GlobalTransform)>,
+    mut agent_query: Query<
+        (
+            &GlobalTransform,
+            &mut RawVelocity,
+            Option<&mut AgentVelocity>,
+        ),
+        (With<Agent>, Without<Rewind>, Without<Dead>),
+    >,
+) {
+    if obstacles.0.is_empty() {
+        return;
+    }
+
+    for (g_transform, mut raw_velocity, agent_velocity) in agent_query.iter_mut() {
+        let velocity = raw_velocity.0.linvel.xz_flat();
+        let speed = velocity.length();
+        if speed <= f32::EPSILON {
+            continue;
+        }
+        let heading = velocity / speed;
+        let position = g_transform.translation();
+
+        let mut steering = Vec3::ZERO;
+        for (obstacle, g_obstacle_transform) in obstacle_query.iter_many(obstacles.0.iter()) {
+            let (_, rotation, translation) = g_obstacle_transform.to_scale_rotation_translation();
+            // only cares about the floor plan
+            let point = Vec3::new(position.x, translation.y, position.z);
+            let projection = obstacle
+                .shape
+                .project_point(translation, rotation, point, true);
+
+            let (away, distance) = match projection.is_inside {
+                true => ((point - translation).xz_flat().normalize_or_zero(), 0.0),
+                false => {
+                    let offset = (point - projection.point).xz_flat();
+                    (offset.normalize_or_zero(), offset.length())
+                }
+            };
+            let clearance = distance - HUMANOID_RADIUS;
+            if away == Vec3::ZERO || clearance >= NAV_OBSTACLE_LOOKAHEAD || away.dot(heading) >= 0.0
+            {
+                continue;
+            }
+
+            // whichever way around is closer to where it's already going
+            let tangent = match away.cross(Vec3::Y).dot(heading) >= 0.0 {
+                true => away.cross(Vec3::Y),
+                false => Vec3::Y.cross(away),
+            };
+            let weight = 1.0 - clearance.max(0.0) / NAV_OBSTACLE_LOOKAHEAD;
+            steering += (away + tangent) * weight * obstacle.avoidance();
+        }
+
+        if steering == Vec3::ZERO {
+            continue;
+        }
+
+        let direction = (heading + steering * NAV_OBSTACLE_STEERING).normalize_or_zero();
+        raw_velocity.0.linvel = direction * speed + Vec3::Y * raw_velocity.0.linvel.y;
+        if let Some(mut agent_velocity) = agent_velocity {
+            agent_velocity.0 = raw_velocity.0.linvel;
+        }
+    }
+}
+
+/// Seconds ahead that agents check for water.
+pub const AGENT_WATER_LOOKAHEAD: f32 = 0.5;
+
+/// Lets agents go in the water.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Amphibious;
+
+fn in_water(
+    rapier_context: &RapierContext,
+    volume_query: &Query<&MovementVolume>,
+    feet: Vec3,
+) -> bool {
+    volumes_at(
+        rapier_context,
+        volume_query,
+        feet + Vec3::Y * MOVEMENT_VOLUME_PROBE_HEIGHT,
+    )
+    .iter()
+    .any(|v| matches!(v.kind, MovementVolumeKind::Water { .. }))
+}
+
+/// Stops agents that aren't `Amphibious` from heading into water.
+///
+/// Targets in the water are dropped, and agents stop at the edge instead of walking in.
+/// Agents that somehow ended up in the water anyways are allowed to walk out.
+pub fn keep_out_of_water(
+    rapier_context: Res<RapierContext>,
+    volume_query: Query<&MovementVolume>,
+    mut agent_query: Query<
+        (
+            &GlobalTransform,
+            &mut RawVelocity,
+            Option<&mut AgentVelocity>,
+            Option<&mut AgentTarget>,
+        ),
+        (
+            With<Agent>,
+            Without<Amphibious>,
+            Without<Rewind>,
+            Without<Dead>,
+        ),
+    >,
+) {
+    for (g_transform, mut raw_velocity, agent_velocity, target) in agent_query.iter_mut() {
+        if let Some(mut target) = target {
+            if let AgentTarget::Point(point) = *target {
+                if in_water(&rapier_context, &volume_query, point) {
+                    *target = AgentTarget::None;
+                }
+            }
+        }
+
+        let feet = g_transform.translation();
+        let ahead = feet + raw_velocity.0.linvel * AGENT_WATER_LOOKAHEAD;
+        if in_water(&rapier_context, &volume_query, ahead)
+            && !in_water(&rapier_context, &volume_query, feet)
+        {
+            raw_velocity.0.linvel = Vec3::ZERO;
+            if let Some(mut agent_velocity) = agent_velocity {
+                agent_velocity.0 = Vec3::ZERO;
+            }
+        }
     }
 }
 
@@ -194,8 +922,6 @@ pub struct IkProcs {
     pub step_duration: f32,
     /// Y displacement at peak of IK step.
     pub step_height: f32,
-    /// Sound when the step lands.
-    pub audio: Option<Handle<AudioSource>>,
     /// Which proc should step next.
     pub active_proc: usize,
 }
@@ -221,16 +947,17 @@ impl IkProcs {
             .is_none()
     }
 
-    /// Updates all active procs.
+    /// Updates all active procs. Returns the index and translation of any procs that landed.
     pub fn step_all(
         &mut self,
         dt: f32,
-        commands: &mut Commands,
         transform_query: &mut Query<&mut Transform>,
-    ) {
-        for proc in self.procs.iter_mut() {
-            proc.step(dt, commands, transform_query, self.audio.as_ref());
-        }
+    ) -> Vec<(usize, Vec3)> {
+        self.procs
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, proc)| proc.step(dt, transform_query).map(|t| (i, t)))
+            .collect()
     }
 }
 
@@ -287,35 +1014,18 @@ impl IkProc {
         });
     }
 
-    /// Updates the step if active.
-    pub fn step(
-        &mut self,
-        dt: f32,
-        commands: &mut Commands,
-        transform_query: &mut Query<&mut Transform>,
-        audio: Option<&Handle<AudioSource>>,
-    ) {
-        let Some(step_state) = &mut self.step_state else {
-            return;
-        };
+    /// Updates the step if active. Returns where it landed if it finished.
+    pub fn step(&mut self, dt: f32, transform_query: &mut Query<&mut Transform>) -> Option<Vec3> {
+        let step_state = self.step_state.as_mut()?;
 
         let mut target_transform = transform_query.get_mut(self.target).unwrap();
         *target_transform = step_state.step(dt);
 
         if step_state.done() {
             self.step_state = None;
-
-            // play sound
-            if let Some(audio) = audio {
-                commands.spawn((
-                    AudioBundle {
-                        source: audio.clone(),
-                        settings: PlaybackSettings::DESPAWN,
-                        ..Default::default()
-                    },
-                    TransformBundle::from_transform(target_transform.clone()),
-                ));
-            };
+            Some(target_transform.translation)
+        } else {
+            None
         }
     }
 }
@@ -349,20 +1059,41 @@ impl StepState {
     }
 }
 
+/// Also sends `FootstepEvent`s when steps land. Procs are assumed to go left, then right.
 pub fn update_biped_procedural_walk_cycle(
-    mut commands: Commands,
     time: Res<PhysicsTime>,
-    mut agent_query: Query<(&mut IkProcs, &TimeScale)>,
+    rapier_context: Res<RapierContext>,
+    mut agent_query: Query<(Entity, &mut IkProcs, &TimeScale, Has<Dead>, Has<Rewind>)>,
     mut transform_query: Query<&mut Transform>,
     g_transform_query: Query<&GlobalTransform>,
     velocity_query: Query<&Velocity>,
+    surface_query: Query<&SurfaceKind>,
+    mut footstep_events: EventWriter<FootstepEvent>,
 ) {
-    for (mut ik_procs, time_scale) in agent_query.iter_mut() {
+    for (e_agent, mut ik_procs, time_scale, dead, rewinding) in agent_query.iter_mut() {
         // update active `IkProc`s
         // note: this works for multiple steps at a time, although really only one should
         // be active at a time for bipeds
         let dt = (time.0.delta_seconds() * f32::from(time_scale)) / ik_procs.step_duration;
-        ik_procs.step_all(dt, &mut commands, &mut transform_query);
+        let landed = ik_procs.step_all(dt, &mut transform_query);
+
+        if !dead && !rewinding {
+            let speed = velocity_query
+                .get(e_agent)
+                .map_or(0.0, |v| v.linvel.length());
+            for (i, translation) in landed {
+                footstep_events.send(FootstepEvent {
+                    entity: e_agent,
+                    foot: match i % 2 {
+                        0 => Foot::Left,
+                        _ => Foot::Right,
+                    },
+                    translation,
+                    speed,
+                    surface: surface_at(&rapier_context, &surface_query, translation),
+                });
+            }
+        }
 
         if !ik_procs.stepping() && !ik_procs.all_in_range(&g_transform_query) {
             // copy these cause borrow checker
@@ -385,3 +1116,223 @@ pub fn update_biped_procedural_walk_cycle(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::MeshPlugin, scene::ScenePlugin, time::TimePlugin};
+    use grin_map::obstacle::NavObstaclePlugin;
+    use grin_physics::CollisionGroupsExt;
+
+    use super::*;
+
+    fn walk(route: &PatrolRoute, steps: usize) -> Vec<usize> {
+        let mut state = PatrolState::default();
+        (0..steps)
+            .map(|_| {
+                state.advance(route);
+                state.index
+            })
+            .collect()
+    }
+
+    #[test]
+    fn patrol_modes() {
+        let mut route = PatrolRoute {
+            waypoints: vec![Vec3::ZERO, Vec3::X * 4.0, Vec3::X * 8.0],
+            ..Default::default()
+        };
+        assert_eq!(
+            walk(&route, 4),
+            vec![1, 2, 0, 1],
+            "Loop didn't wrap around."
+        );
+
+        route.mode = PatrolMode::PingPong;
+        assert_eq!(
+            walk(&route, 5),
+            vec![1, 2, 1, 0, 1],
+            "PingPong didn't turn around."
+        );
+
+        route.mode = PatrolMode::Once;
+        let mut state = PatrolState::default();
+        for _ in 0..3 {
+            state.advance(&route);
+        }
+        assert!(
+            state.finished && state.index == 2,
+            "Once didn't stop at the end."
+        );
+
+        assert_eq!(
+            route.nearest(Vec3::new(7.0, 0.0, 1.0)),
+            Some(2),
+            "Didn't find the nearest waypoint."
+        );
+    }
+
+    #[test]
+    fn separation() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .init_resource::<PhysicsTime>()
+            .add_systems(PostUpdate, separate_agents.after(PhysicsSet::Writeback));
+
+        let spawn_agent = |app: &mut App, x: f32| {
+            app.world
+                .spawn((
+                    Separation::default(),
+                    RawVelocity::default(),
+                    Collider::ball(HUMANOID_RADIUS),
+                    CollisionGroups::from_group_default(Group::ENEMY),
+                    TransformBundle::from_transform(Transform::from_xyz(x, 0.0, 0.0)),
+                ))
+                .id()
+        };
+        let e_left = spawn_agent(&mut app, -0.5);
+        let e_right = spawn_agent(&mut app, 0.5);
+        let e_walled = spawn_agent(&mut app, 10.0);
+        spawn_agent(&mut app, 10.5);
+
+        // up against `e_walled`
+        app.world.spawn((
+            Collider::cuboid(0.1, 4.0, 4.0),
+            CollisionGroups::new(Group::MAP, Group::all()),
+            TransformBundle::from_transform(Transform::from_xyz(9.45, 0.0, 0.0)),
+        ));
+
+        // colliders get added to the physics world here
+        app.update();
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(std::time::Duration::from_secs_f32(0.1));
+        app.update();
+
+        let linvel = |app: &App, e| app.world.get::<RawVelocity>(e).unwrap().0.linvel;
+        assert!(
+            linvel(&app, e_left).x < 0.0 && linvel(&app, e_right).x > 0.0,
+            "Agents weren't pushed apart."
+        );
+        assert!(
+            linvel(&app, e_walled).x > -0.1,
+            "Agent was pushed into a wall."
+        );
+    }
+
+    #[test]
+    fn cover_selection() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default());
+
+        // wall between the target at -Z and anything past +Z 5
+        app.world.spawn((
+            Collider::cuboid(2.0, 2.0, 0.1),
+            CollisionGroups::new(Group::MAP, Group::all()),
+            TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, 5.0)),
+        ));
+
+        // colliders get added to the physics world here
+        app.update();
+        app.update();
+
+        let rapier_context = app.world.resource::<RapierContext>();
+        let target = Vec3::ZERO;
+        let candidates = [
+            Vec3::new(0.0, 0.0, 3.0),
+            Vec3::new(0.0, 0.0, 8.0),
+            Vec3::new(0.0, 0.0, 6.0),
+            Vec3::new(0.0, 0.0, 30.0),
+        ];
+        let origin = Vec3::new(0.0, 0.0, 10.0);
+
+        assert_eq!(
+            find_cover(rapier_context, candidates, origin, target, 5.0),
+            Some(Vec3::new(0.0, 0.0, 8.0)),
+            "Didn't pick the closest covered spot in range."
+        );
+        assert_eq!(
+            find_cover(
+                rapier_context,
+                [Vec3::new(0.0, 0.0, 3.0)],
+                origin,
+                target,
+                50.0
+            ),
+            None,
+            "Hid in plain sight."
+        );
+    }
+
+    #[test]
+    fn obstacle_avoidance() {
+        let mut app = App::new();
+        app.add_plugins(NavObstaclePlugin)
+            .add_systems(Update, avoid_nav_obstacles);
+
+        let target = Vec3::new(0.0, 0.0, 20.0);
+        let e_agent = app
+            .world
+            .spawn((
+                Agent {
+                    radius: HUMANOID_RADIUS,
+                    max_velocity: 4.0,
+                },
+                RawVelocity::default(),
+                TransformBundle::default(),
+            ))
+            .id();
+
+        // stands in for the navmesh, which goes straight there
+        let dt = 0.05;
+        // no transform propagation here, so it's moved by hand
+        let step = |app: &mut App| {
+            let position = app
+                .world
+                .get::<GlobalTransform>(e_agent)
+                .unwrap()
+                .translation();
+            app.world.get_mut::<RawVelocity>(e_agent).unwrap().0.linvel =
+                (target - position).normalize_or_zero() * 4.0;
+            app.update();
+            let linvel = app.world.get::<RawVelocity>(e_agent).unwrap().0.linvel;
+            let position = position + linvel * dt;
+            *app.world.get_mut::<GlobalTransform>(e_agent).unwrap() =
+                GlobalTransform::from_translation(position);
+            position
+        };
+
+        for _ in 0..10 {
+            step(&mut app);
+        }
+        let obstacle = Vec3::new(0.0, 0.0, 10.0);
+        app.world.spawn((
+            NavObstacle::solid(Collider::cylinder(1.0, 1.0)),
+            GlobalTransform::from_translation(obstacle),
+        ));
+
+        let mut deviation = 0.0_f32;
+        let mut closest = f32::INFINITY;
+        let mut position = Vec3::ZERO;
+        for _ in 0..200 {
+            position = step(&mut app);
+            deviation = deviation.max(position.x.abs());
+            closest = closest.min((position - obstacle).xz_flat().length());
+            if position.distance(target) < 0.25 {
+                break;
+            }
+        }
+
+        assert!(deviation > 1.0, "Agent went straight through the obstacle.");
+        assert!(
+            closest > 1.0 + HUMANOID_RADIUS,
+            "Agent walked into the obstacle."
+        );
+        assert!(
+            position.distance(target) < 0.25,
+            "Agent didn't make it to the target."
+        );
+    }
+}
@@ -0,0 +1,331 @@
+//! What AI can see and hear, so they aren't omniscient.
+
+use std::{marker::PhantomData, mem};
+
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use bevy_rapier3d::prelude::*;
+use grin_damage::health::Dead;
+use grin_item::mechanics::fx::MuzzleFlashEvent;
+use grin_physics::{CollisionGroupExt, PhysicsTime};
+use grin_rig::humanoid::Humanoid;
+use grin_time::{scaling::TimeScale, Rewind};
+
+use crate::{
+    bt::{Brain, Verdict},
+    AiSet,
+};
+
+/// Updates `Awareness` of targets with `E`.
+pub struct PerceptionPlugin<E: Component> {
+    pub phantom_data: PhantomData<E>,
+}
+
+impl<E: Component> Default for PerceptionPlugin<E> {
+    fn default() -> Self {
+        Self {
+            phantom_data: PhantomData::default(),
+        }
+    }
+}
+
+impl<E: Component> Plugin for PerceptionPlugin<E> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (hear_gunshots::<E>, perceive_targets::<E>)
+                .chain()
+                .before(AiSet::RunTrees),
+        );
+    }
+}
+
+/// Seconds to stay `Suspicious` before going back to `Unaware`.
+pub const SUSPICION_DURATION: f32 = 5.0;
+
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Perception {
+    pub sight_range: f32,
+    /// Total width of the view cone.
+    pub fov_degrees: f32,
+    /// How close gunshots need to be to be heard.
+    pub hearing_range: f32,
+}
+
+impl Default for Perception {
+    fn default() -> Self {
+        Self {
+            sight_range: 24.0,
+            fov_degrees: 120.0,
+            hearing_range: 16.0,
+        }
+    }
+}
+
+impl Perception {
+    /// Whether `point` is in range, in the view cone, and not behind the map.
+    pub fn can_see(
+        &self,
+        rapier_context: &RapierContext,
+        eye: Vec3,
+        facing: Vec3,
+        point: Vec3,
+    ) -> bool {
+        let offset = point - eye;
+        let distance = offset.length();
+        if distance > self.sight_range {
+            return false;
+        }
+        if distance <= f32::EPSILON {
+            return true;
+        }
+
+        let dir = offset / distance;
+        if facing.angle_between(dir).to_degrees() > self.fov_degrees / 2.0 {
+            return false;
+        }
+
+        rapier_context
+            .cast_ray(
+                eye,
+                dir,
+                distance,
+                true,
+                QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+            )
+            .is_none()
+    }
+}
+
+/// What an agent knows about its targets. Updated by `perceive_targets`.
+#[derive(Component, Clone, Debug, Default)]
+pub enum Awareness {
+    #[default]
+    Unaware,
+    /// Saw or heard something at `last_seen`. Goes back to `Unaware` when the timer runs out.
+    Suspicious { last_seen: Vec3, timer: Timer },
+    /// Can see `targets` right now.
+    Alert {
+        targets: EntityHashSet,
+        last_seen: Vec3,
+    },
+}
+
+impl Awareness {
+    pub fn suspicious(last_seen: Vec3) -> Self {
+        Self::Suspicious {
+            last_seen,
+            timer: Timer::from_seconds(SUSPICION_DURATION, TimerMode::Once),
+        }
+    }
+
+    pub fn is_alert_to(&self, e_target: Entity) -> bool {
+        match self {
+            Self::Alert { targets, .. } => targets.contains(&e_target),
+            _ => false,
+        }
+    }
+}
+
+/// Where to look from. The head for humanoids.
+fn eye_position(
+    g_transform: &GlobalTransform,
+    humanoid: Option<&Humanoid>,
+    transform_query: &Query<&GlobalTransform>,
+) -> Vec3 {
+    humanoid
+        .and_then(|h| transform_query.get(h.head).ok())
+        .unwrap_or(g_transform)
+        .translation()
+}
+
+pub fn perceive_targets<E: Component>(
+    time: Res<PhysicsTime>,
+    rapier_context: Res<RapierContext>,
+    mut agent_query: Query<
+        (
+            &Perception,
+            &mut Awareness,
+            &GlobalTransform,
+            Option<&Humanoid>,
+            Option<&TimeScale>,
+        ),
+        (Without<Rewind>, Without<Dead>),
+    >,
+    target_query: Query<(Entity, &GlobalTransform, Option<&Humanoid>), With<E>>,
+    transform_query: Query<&GlobalTransform>,
+) {
+    for (perception, mut awareness, g_transform, humanoid, time_scale) in agent_query.iter_mut() {
+        let eye = eye_position(g_transform, humanoid, &transform_query);
+        let facing = g_transform.forward();
+
+        let mut targets = EntityHashSet::default();
+        let mut last_seen = None;
+        for (e_target, g_target_transform, target_humanoid) in target_query.iter() {
+            let point = eye_position(g_target_transform, target_humanoid, &transform_query);
+            if perception.can_see(&rapier_context, eye, facing, point) {
+                targets.insert(e_target);
+                last_seen = Some(point);
+            }
+        }
+
+        if let Some(last_seen) = last_seen {
+            *awareness = Awareness::Alert { targets, last_seen };
+            continue;
+        }
+
+        let delta = time.0.delta().mul_f32(time_scale.map_or(1.0, f32::from));
+        *awareness = match mem::take(&mut *awareness) {
+            Awareness::Alert { last_seen, .. } => Awareness::suspicious(last_seen),
+            Awareness::Suspicious {
+                last_seen,
+                mut timer,
+            } => match timer.tick(delta).finished() {
+                true => Awareness::Unaware,
+                false => Awareness::Suspicious { last_seen, timer },
+            },
+            Awareness::Unaware => Awareness::Unaware,
+        };
+    }
+}
+
+/// Gunshots from `E` make `Unaware` agents in hearing range `Suspicious` of where it came from.
+pub fn hear_gunshots<E: Component>(
+    mut agent_query: Query<
+        (&Perception, &mut Awareness, &GlobalTransform),
+        (Without<Rewind>, Without<Dead>),
+    >,
+    source_query: Query<&GlobalTransform>,
+    parent_query: Query<&Parent>,
+    shooter_query: Query<(), With<E>>,
+    mut flash_events: EventReader<MuzzleFlashEvent>,
+) {
+    for &MuzzleFlashEvent(e_source) in flash_events.read() {
+        if !parent_query
+            .iter_ancestors(e_source)
+            .any(|e| shooter_query.contains(e))
+        {
+            continue;
+        }
+        let Ok(g_source_transform) = source_query.get(e_source) else {
+            continue;
+        };
+        let sound = g_source_transform.translation();
+
+        for (perception, mut awareness, g_transform) in agent_query.iter_mut() {
+            if matches!(*awareness, Awareness::Unaware)
+                && g_transform.translation().distance(sound) <= perception.hearing_range
+            {
+                *awareness = Awareness::suspicious(sound);
+            }
+        }
+    }
+}
+
+/// Writes `Verdict::Failure` while `Unaware` and `Verdict::Success` otherwise, for behavior trees
+/// to branch on.
+pub fn check_awareness<T: Component, A: Component>(
+    mut agent_query: Query<
+        (&mut Brain, &Awareness),
+        (With<T>, With<A>, Without<Rewind>, Without<Dead>),
+    >,
+) {
+    for (mut brain, awareness) in agent_query.iter_mut() {
+        brain.write_verdict(match awareness {
+            Awareness::Unaware => Verdict::Failure,
+            _ => Verdict::Success,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::MeshPlugin, scene::ScenePlugin, time::TimePlugin};
+
+    use super::*;
+
+    #[derive(Component)]
+    struct MockTarget;
+
+    fn transform_bundle(transform: Transform) -> TransformBundle {
+        TransformBundle {
+            local: transform,
+            global: transform.into(),
+        }
+    }
+
+    #[test]
+    fn line_of_sight() {
+        let mut app = App::new();
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1.0,
+                substeps: 1,
+            },
+            ..Default::default()
+        })
+        .add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .init_resource::<PhysicsTime>()
+        .add_event::<MuzzleFlashEvent>()
+        .add_plugins(PerceptionPlugin::<MockTarget>::default());
+
+        // facing -Z
+        let e_agent = app
+            .world
+            .spawn((
+                Perception::default(),
+                Awareness::default(),
+                transform_bundle(Transform::default()),
+            ))
+            .id();
+        let e_target = app
+            .world
+            .spawn((
+                MockTarget,
+                transform_bundle(Transform::from_xyz(0.0, 0.0, -10.0)),
+            ))
+            .id();
+        let e_wall = app
+            .world
+            .spawn((
+                Collider::cuboid(2.0, 2.0, 0.1),
+                CollisionGroups::new(Group::MAP, Group::all()),
+                transform_bundle(Transform::from_xyz(0.0, 0.0, -5.0)),
+            ))
+            .id();
+
+        // colliders get added to the physics world here
+        app.update();
+        app.update();
+        assert!(
+            matches!(
+                app.world.get::<Awareness>(e_agent).unwrap(),
+                Awareness::Unaware
+            ),
+            "Saw through a wall."
+        );
+
+        app.world.despawn(e_wall);
+        app.update();
+        app.update();
+        assert!(
+            app.world
+                .get::<Awareness>(e_agent)
+                .unwrap()
+                .is_alert_to(e_target),
+            "Didn't see the target."
+        );
+
+        app.world
+            .entity_mut(e_target)
+            .insert(transform_bundle(Transform::from_xyz(0.0, 0.0, 10.0)));
+        app.update();
+        assert!(
+            matches!(
+                app.world.get::<Awareness>(e_agent).unwrap(),
+                Awareness::Suspicious { .. }
+            ),
+            "Saw something behind it."
+        );
+    }
+}
@@ -3,7 +3,7 @@
 
 use std::time::Duration;
 
-use bevy::prelude::*;
+use bevy::{audio::Volume, prelude::*};
 use bevy_asset_loader::prelude::*;
 use bevy_enum_filter::prelude::*;
 use bevy_landmass::Agent;
@@ -13,11 +13,18 @@ use grin_asset::AssetLoadState;
 use grin_character::PlayerCharacter;
 use grin_damage::{
     hit::{Damage, DamageVariant},
-    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor},
+    hitbox::Hitbox,
+    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor, ProjectilePool},
+    status::{ApplyStatusEvent, Disoriented},
 };
 use grin_derive::Cooldown;
 use grin_map::MapData;
-use grin_util::{event::Spawnable, query::gltf_path_search, vectors::Vec3Ext};
+use grin_rig::footstep::FootstepSound;
+use grin_util::{
+    event::{SpawnAppExt, Spawnable},
+    query::gltf_path_search,
+    vectors::Vec3Ext,
+};
 use itertools::Itertools;
 
 use super::{
@@ -27,25 +34,54 @@ use super::{
         Verdict,
     },
     movement::{
-        match_desired_velocity, propagate_attack_target_to_agent_target, zero_velocity,
-        AttackTarget, IkProc, IkProcs,
+        match_desired_velocity, patrol, propagate_attack_target_to_agent_target, zero_velocity,
+        AttackTarget, IkProc, IkProcs, PatrolRoute,
+    },
+    protective_cooldown, set_closest_attack_target,
+    telegraph::{
+        await_telegraph, tick_telegraphs, Telegraph, TelegraphFinishedEvent, TelegraphStyle,
     },
-    protective_cooldown, set_closest_attack_target, AiSet, EnemyAgentBundle,
+    AiSet, EnemyAgentBundle,
 };
 use crate::bt;
 
+/// How long the bass cannon takes to charge.
+pub const BASS_CANNON_WINDUP: f32 = 1.0;
+
 #[derive(Component, Cooldown)]
 #[cooldown(duration = 2.0)]
 pub struct BassCannonCooldown(pub Timer);
 
-#[derive(Component, Cooldown)]
-#[cooldown(duration = 1.0)]
-pub struct BassCannonAim(pub Timer);
-
 #[derive(Component, Cooldown)]
 #[cooldown(duration = 40.0 / 60.0)]
 pub struct BassCannonSelfStun(pub Timer);
 
+/// How long the scream takes to charge.
+pub const SCREAM_WINDUP: f32 = 0.8;
+
+/// How far the scream reaches.
+pub const SCREAM_RANGE: f32 = 10.0;
+
+/// Angle between the middle of the scream cone and its edge, in radians.
+pub const SCREAM_HALF_ANGLE: f32 = 0.6;
+
+/// How long the scream leaves you `Disoriented`.
+pub const SCREAM_DISORIENT_SECS: f32 = 3.0;
+
+/// It's loud.
+pub const SCREAM_VOLUME: f32 = 2.0;
+
+/// Volume is multiplied by this if the map is in between the scream and the listener.
+pub const SCREAM_OCCLUDED_VOLUME: f32 = 0.35;
+
+#[derive(Component, Cooldown)]
+#[cooldown(duration = 8.0)]
+pub struct ScreamCooldown(pub Timer);
+
+/// The `Telegraph` that's going right now is for a scream, not the bass cannon.
+#[derive(Component)]
+pub struct ScreamCharging;
+
 pub struct ScreamerPlugin;
 
 impl Plugin for ScreamerPlugin {
@@ -55,43 +91,61 @@ impl Plugin for ScreamerPlugin {
             LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<ScreamerAssets>(),
         )
             .add_plugins(EnumBehaviorPlugin::<ScreamerAi>::default())
+            .register_spawnable::<Screamer>("screamer")
             .insert_resource(AiModel {
                 bt: bt! {
-                    Composite(CompositeNode::Sequence) {
-                        Leaf(ScreamerAi::Track),
-                        Leaf(ScreamerAi::Target),
-                        Composite(CompositeNode::Selector) {
-                            Composite(CompositeNode::Sequence) {
-                                Leaf(ScreamerAi::BassCooldownCheck),
-                                Leaf(ScreamerAi::EndChase),
+                    Composite(CompositeNode::Selector) {
+                        Composite(CompositeNode::Sequence) {
+                            Leaf(ScreamerAi::Track),
+                            Leaf(ScreamerAi::Target),
+                            Composite(CompositeNode::Selector) {
                                 Composite(CompositeNode::Sequence) {
-                                    Leaf(ScreamerAi::AimBegin),
-                                    Leaf(ScreamerAi::AimCheck),
+                                    Leaf(ScreamerAi::ScreamRangeCheck),
+                                    Leaf(ScreamerAi::ScreamCooldownCheck),
+                                    Leaf(ScreamerAi::EndChase),
+                                    Composite(CompositeNode::Sequence) {
+                                        Leaf(ScreamerAi::ScreamBegin),
+                                        Leaf(ScreamerAi::ScreamCheck),
+                                    },
+                                    Leaf(ScreamerAi::SetIdle),
                                 },
-                                Leaf(ScreamerAi::BassCannon),
-                                Leaf(ScreamerAi::BassCannonSelfStun),
-                                Leaf(ScreamerAi::SetIdle),
+                                Composite(CompositeNode::Sequence) {
+                                    Leaf(ScreamerAi::BassCooldownCheck),
+                                    Leaf(ScreamerAi::EndChase),
+                                    Composite(CompositeNode::Sequence) {
+                                        Leaf(ScreamerAi::AimBegin),
+                                        Leaf(ScreamerAi::AimCheck),
+                                    },
+                                    Leaf(ScreamerAi::BassCannonSelfStun),
+                                    Leaf(ScreamerAi::SetIdle),
+                                },
+                                Leaf(ScreamerAi::Chase),
                             },
-                            Leaf(ScreamerAi::Chase),
                         },
+                        Leaf(ScreamerAi::Patrol),
                     },
                 },
             })
             .add_systems(Update, spawn.in_set(AiSet::Spawn))
+            .add_systems(Update, (bass_cannon, scream).after(tick_telegraphs))
             .add_systems(PreUpdate, load.in_set(AiSet::Load))
             .add_systems(
                 BehaviorIteration,
                 (
                     protective_cooldown::<Screamer, Enum!(ScreamerAi::BassCooldownCheck), BassCannonCooldown>,
-                    blocking_cooldown::<Screamer, Enum!(ScreamerAi::AimCheck), BassCannonAim>,
+                    await_telegraph::<Screamer, Enum!(ScreamerAi::AimCheck)>,
+                    protective_cooldown::<Screamer, Enum!(ScreamerAi::ScreamCooldownCheck), ScreamCooldown>,
+                    await_telegraph::<Screamer, Enum!(ScreamerAi::ScreamCheck)>,
                     blocking_cooldown::<Screamer, Enum!(ScreamerAi::BassCannonSelfStun), BassCannonSelfStun>,
                     set_closest_attack_target::<Screamer, Enum!(ScreamerAi::Track), PlayerCharacter>,
                     propagate_attack_target_to_agent_target::<Screamer, Enum!(ScreamerAi::Target)>,
                     match_desired_velocity::<Screamer, Enum!(ScreamerAi::Chase)>,
                     zero_velocity::<Screamer, Enum!(ScreamerAi::EndChase)>,
+                    patrol::<Screamer, Enum!(ScreamerAi::Patrol)>,
                     set_idle::<Enum!(ScreamerAi::SetIdle)>,
                     aim_begin::<Enum!(ScreamerAi::AimBegin)>,
-                    bass_cannon::<Enum!(ScreamerAi::BassCannon)>,
+                    scream_range_check::<Enum!(ScreamerAi::ScreamRangeCheck)>,
+                    scream_begin::<Enum!(ScreamerAi::ScreamBegin)>,
                 )
                     .in_set(BehaviorSet::Act),
             );
@@ -108,6 +162,16 @@ impl Spawnable for Screamer {
 #[derive(Event, Clone, Default)]
 pub struct ScreamerSpawnEvent {
     pub transform: Transform,
+    pub patrol: Option<PatrolRoute>,
+}
+
+impl From<Transform> for ScreamerSpawnEvent {
+    fn from(transform: Transform) -> Self {
+        Self {
+            transform,
+            ..Default::default()
+        }
+    }
 }
 
 #[derive(Component)]
@@ -126,9 +190,13 @@ pub enum ScreamerAi {
     BassCooldownCheck,
     AimBegin,
     AimCheck,
-    BassCannon,
     BassCannonSelfStun,
+    ScreamRangeCheck,
+    ScreamCooldownCheck,
+    ScreamBegin,
+    ScreamCheck,
     SetIdle,
+    Patrol,
 }
 
 #[derive(Resource, AssetCollection)]
@@ -149,6 +217,8 @@ pub struct ScreamerAssets {
     pub stomp: Handle<AudioSource>,
     #[asset(key = "sfx.bass_cannon")]
     pub bass_sfx: Handle<AudioSource>,
+    #[asset(key = "sfx.scream")]
+    pub scream_sfx: Handle<AudioSource>,
 }
 
 pub fn spawn(
@@ -156,8 +226,8 @@ pub fn spawn(
     assets: Res<ScreamerAssets>,
     mut events: EventReader<ScreamerSpawnEvent>,
 ) {
-    for ScreamerSpawnEvent { transform } in events.read() {
-        commands.spawn((
+    for ScreamerSpawnEvent { transform, patrol } in events.read() {
+        let mut e_screamer_commands = commands.spawn((
             Screamer,
             SceneBundle {
                 scene: assets.skeleton.clone(),
@@ -165,6 +235,9 @@ pub fn spawn(
                 ..Default::default()
             },
         ));
+        if let Some(patrol) = patrol {
+            e_screamer_commands.insert(patrol.clone());
+        }
     }
 }
 
@@ -288,50 +361,62 @@ pub fn load(
                 armature: e_armature,
             },
             BassCannonCooldown::default(),
-            BassCannonAim::default(),
             BassCannonSelfStun::default(),
+            ScreamCooldown::default(),
             IkProcs {
                 procs,
                 scare_distance: 1.0,
                 step_duration: 0.1,
                 step_height: 0.5,
-                audio: Some(assets.stomp.clone()),
                 active_proc: 0,
             },
+            FootstepSound(assets.stomp.clone()),
         ));
     }
 }
 
 pub fn aim_begin<T: Component>(
+    mut commands: Commands,
     assets: Res<ScreamerAssets>,
-    mut agent_query: Query<(&mut Brain, &ScreamerParts), (With<Screamer>, With<T>)>,
+    mut agent_query: Query<(Entity, &mut Brain, &ScreamerParts), (With<Screamer>, With<T>)>,
     mut animator_query: Query<&mut AnimationPlayer>,
 ) {
-    for (mut brain, parts) in agent_query.iter_mut() {
+    for (e_screamer, mut brain, parts) in agent_query.iter_mut() {
         let mut animator = animator_query.get_mut(parts.armature).unwrap();
         animator.play_with_transition(assets.bass_ready.clone(), Duration::from_secs_f32(0.2));
+        commands
+            .entity(e_screamer)
+            .remove::<ScreamCharging>()
+            .insert(Telegraph::new(
+                BASS_CANNON_WINDUP,
+                TelegraphStyle::Tint { color: Color::RED },
+            ));
         brain.write_verdict(Verdict::Success);
     }
 }
 
-pub fn bass_cannon<T: Component>(
+/// Fires once the `aim_begin` wind-up is over.
+pub fn bass_cannon(
     mut commands: Commands,
+    mut pool: ResMut<ProjectilePool>,
     assets: Res<ScreamerAssets>,
-    mut agent_query: Query<
-        (Entity, &mut Brain, &ScreamerParts, &AttackTarget),
-        (With<Screamer>, With<T>),
-    >,
+    agent_query: Query<(&ScreamerParts, &AttackTarget), (With<Screamer>, Without<ScreamCharging>)>,
     mut animator_query: Query<&mut AnimationPlayer>,
     g_transform_query: Query<&GlobalTransform>,
+    mut telegraph_events: EventReader<TelegraphFinishedEvent>,
 ) {
-    for (e_screamer, mut brain, parts, AttackTarget(e_target)) in agent_query.iter_mut() {
+    for &TelegraphFinishedEvent { entity: e_screamer } in telegraph_events.read() {
+        let Ok((parts, AttackTarget(e_target))) = agent_query.get(e_screamer) else {
+            continue;
+        };
+        let Ok(target) = g_transform_query.get(*e_target) else {
+            continue;
+        };
+
         let mut animator = animator_query.get_mut(parts.armature).unwrap();
         animator.play(assets.bass.clone());
 
-        let (origin, target) = (
-            g_transform_query.get(parts.armature).unwrap(),
-            g_transform_query.get(*e_target).unwrap(),
-        );
+        let origin = g_transform_query.get(parts.armature).unwrap();
         let bullet_transform = Transform::from_translation(origin.translation())
             .looking_at(target.translation().with_y(origin.translation().y), Vec3::Y);
 
@@ -341,7 +426,7 @@ pub fn bass_cannon<T: Component>(
             ..Default::default()
         });
 
-        commands.spawn((
+        pool.acquire(&mut commands).insert((
             BulletProjectile,
             ProjectileBundle {
                 color: ProjectileColor::Red,
@@ -356,8 +441,6 @@ pub fn bass_cannon<T: Component>(
                 ..ProjectileBundle::enemy_default()
             },
         ));
-
-        brain.write_verdict(Verdict::Success);
     }
 }
 
@@ -372,3 +455,180 @@ pub fn set_idle<T: Component>(
         brain.write_verdict(Verdict::Success);
     }
 }
+
+/// Whether `point` is in the scream cone in front of `origin`.
+pub fn in_scream_cone(origin: &GlobalTransform, point: Vec3) -> bool {
+    let offset = point - origin.translation();
+    let distance = offset.length();
+    distance <= SCREAM_RANGE
+        && (distance == 0.0 || origin.forward().angle_between(offset) <= SCREAM_HALF_ANGLE)
+}
+
+/// Writes `Verdict::Success` if the `AttackTarget` is close enough to scream at.
+pub fn scream_range_check<T: Component>(
+    mut agent_query: Query<
+        (&mut Brain, &GlobalTransform, &AttackTarget),
+        (With<Screamer>, With<T>),
+    >,
+    g_transform_query: Query<&GlobalTransform>,
+) {
+    for (mut brain, g_transform, AttackTarget(e_target)) in agent_query.iter_mut() {
+        let in_range = g_transform_query.get(*e_target).is_ok_and(|target| {
+            g_transform.translation().distance(target.translation()) <= SCREAM_RANGE
+        });
+        brain.write_verdict(match in_range {
+            true => Verdict::Success,
+            false => Verdict::Failure,
+        });
+    }
+}
+
+/// Turns to the `AttackTarget` and starts charging the scream.
+pub fn scream_begin<T: Component>(
+    mut commands: Commands,
+    assets: Res<ScreamerAssets>,
+    mut agent_query: Query<
+        (
+            Entity,
+            &mut Brain,
+            &mut Transform,
+            &ScreamerParts,
+            &AttackTarget,
+        ),
+        (With<Screamer>, With<T>),
+    >,
+    mut animator_query: Query<&mut AnimationPlayer>,
+    g_transform_query: Query<&GlobalTransform>,
+) {
+    for (e_screamer, mut brain, mut transform, parts, AttackTarget(e_target)) in
+        agent_query.iter_mut()
+    {
+        if let Ok(target) = g_transform_query.get(*e_target) {
+            let target = target.translation().with_y(transform.translation.y);
+            if target != transform.translation {
+                transform.look_at(target, Vec3::Y);
+            }
+        }
+
+        let mut animator = animator_query.get_mut(parts.armature).unwrap();
+        animator.play_with_transition(assets.bass_ready.clone(), Duration::from_secs_f32(0.2));
+        commands.entity(e_screamer).insert((
+            ScreamCharging,
+            Telegraph::new(
+                SCREAM_WINDUP,
+                TelegraphStyle::Outline {
+                    max_scale: 1.5,
+                    rate: 6.0,
+                },
+            ),
+        ));
+        brain.write_verdict(Verdict::Success);
+    }
+}
+
+/// Screams once the `scream_begin` wind-up is over. Everything in the cone is `Disoriented`.
+pub fn scream(
+    mut commands: Commands,
+    assets: Res<ScreamerAssets>,
+    rapier_context: Res<RapierContext>,
+    agent_query: Query<(&ScreamerParts, &GlobalTransform), (With<Screamer>, With<ScreamCharging>)>,
+    listener_query: Query<&GlobalTransform, With<SpatialListener>>,
+    hitbox_query: Query<&Hitbox>,
+    g_transform_query: Query<&GlobalTransform>,
+    mut animator_query: Query<&mut AnimationPlayer>,
+    mut telegraph_events: EventReader<TelegraphFinishedEvent>,
+    mut status_events: EventWriter<ApplyStatusEvent<Disoriented>>,
+) {
+    for &TelegraphFinishedEvent { entity: e_screamer } in telegraph_events.read() {
+        let Ok((parts, g_transform)) = agent_query.get(e_screamer) else {
+            continue;
+        };
+        commands.entity(e_screamer).remove::<ScreamCharging>();
+
+        let mut animator = animator_query.get_mut(parts.armature).unwrap();
+        animator.play(assets.scream.clone());
+
+        let origin = g_transform.translation();
+        let occluded = listener_query.get_single().is_ok_and(|listener| {
+            let offset = listener.translation() - origin;
+            rapier_context
+                .cast_ray(
+                    origin,
+                    offset.normalize_or_zero(),
+                    offset.length(),
+                    true,
+                    QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+                )
+                .is_some()
+        });
+        let volume = match occluded {
+            true => SCREAM_VOLUME * SCREAM_OCCLUDED_VOLUME,
+            false => SCREAM_VOLUME,
+        };
+        commands.spawn((
+            AudioBundle {
+                source: assets.scream_sfx.clone(),
+                settings: PlaybackSettings::DESPAWN
+                    .with_spatial(true)
+                    .with_volume(Volume::new(volume)),
+            },
+            TransformBundle::from_transform(Transform::from_translation(origin)),
+        ));
+
+        // each `HitboxManager` should only be disoriented once
+        let mut targets = Vec::new();
+        rapier_context.intersections_with_shape(
+            origin,
+            Quat::IDENTITY,
+            &Collider::ball(SCREAM_RANGE),
+            QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::PLAYER)),
+            |e_hit| {
+                let e_target = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+                if e_target != e_screamer && !targets.contains(&e_target) {
+                    targets.push(e_target);
+                }
+                true
+            },
+        );
+
+        for e_target in targets {
+            let Ok(target) = g_transform_query.get(e_target) else {
+                continue;
+            };
+            if !in_scream_cone(g_transform, target.translation()) {
+                continue;
+            }
+            status_events.send(ApplyStatusEvent::new(
+                e_target,
+                Disoriented::default(),
+                Duration::from_secs_f32(SCREAM_DISORIENT_SECS),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scream_cone() {
+        let origin = GlobalTransform::from(Transform::from_xyz(1.0, 0.0, 1.0));
+        assert!(
+            in_scream_cone(&origin, Vec3::new(1.0, 0.0, -4.0)),
+            "Missed straight ahead."
+        );
+        assert!(
+            !in_scream_cone(&origin, Vec3::new(1.0, 0.0, 6.0)),
+            "Hit something behind."
+        );
+        assert!(
+            !in_scream_cone(&origin, Vec3::new(6.0, 0.0, 0.0)),
+            "Hit something off to the side."
+        );
+        assert!(
+            !in_scream_cone(&origin, Vec3::new(1.0, 0.0, -1.0 - SCREAM_RANGE)),
+            "Hit something out of range."
+        );
+    }
+}
@@ -1,11 +1,12 @@
 use std::{marker::PhantomData, time::Duration};
 
 use bevy::{ecs::system::SystemParam, prelude::*};
-use bevy_asset_loader::prelude::*;
 use bevy_tweening::AnimationSystem;
 use grin_asset::AssetLoadState;
 use grin_physics::PhysicsTime;
 
+use crate::{encounter::EncounterMember, movement::PatrolRoute, squad::SquadId};
+
 /// Enemy spawning systems.
 ///
 /// All states occur before `AnimationUpdate`.
@@ -60,10 +61,6 @@ impl<T: Component> Plugin for EnemySpawnPlugin<T> {
             .add_event::<SpawnBegan<T>>()
             .add_event::<SpawnStageReached<T>>()
             .add_event::<SpawnCompleted<T>>()
-            .configure_loading_state(
-                LoadingStateConfig::new(AssetLoadState::Loading)
-                    .load_collection::<indicators::SpawnIndicatorAssets>(),
-            )
             .add_systems(PreUpdate, init_spawn_events::<T>.in_set(SpawnSet::Spawn))
             .add_systems(
                 Update,
@@ -124,6 +121,12 @@ pub struct SpawningBundle {
 #[derive(Event, Clone)]
 pub struct EnemySpawn<T> {
     pub transform: Transform,
+    /// Inserted on the agent if there is one.
+    pub squad: Option<SquadId>,
+    /// Inserted on the agent if there is one.
+    pub patrol: Option<PatrolRoute>,
+    /// Spawned by the `EncounterDirector`.
+    pub encounter: bool,
     pub phantom_data: PhantomData<T>,
 }
 
@@ -131,11 +134,23 @@ impl<T> Default for EnemySpawn<T> {
     fn default() -> Self {
         Self {
             transform: Transform::default(),
+            squad: None,
+            patrol: None,
+            encounter: false,
             phantom_data: PhantomData,
         }
     }
 }
 
+impl<T> From<Transform> for EnemySpawn<T> {
+    fn from(transform: Transform) -> Self {
+        Self {
+            transform,
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Event)]
 pub struct SpawnBegan<T> {
     pub entity: Entity,
@@ -228,7 +243,6 @@ pub fn reinitialize_spawn_timers<T: Component>(
 // this module is a bit ugly. but really, what is life if not ugly? AMEN (REAL...)
 pub mod indicators {
     use bevy::{ecs::system::EntityCommands, prelude::*};
-    use bevy_asset_loader::prelude::*;
     use bevy_tweening::{Animator, EaseFunction, EaseMethod, Tween};
     use grin_damage::hitbox::{GltfHitboxAutoGenTarget, HitboxManager, Hitboxes, Hurtboxes};
     use grin_render::{
@@ -236,16 +250,10 @@ pub mod indicators {
         tint::{TintCompletedEvent, TintEffect, TintEmissiveLens},
         EffectFlags, TweenCompletedEvent,
     };
-    use grin_rig::Idle;
+    use grin_rig::PlayAnimationState;
 
     use super::{SpawnStage, SpawnStageReached};
 
-    #[derive(Resource, AssetCollection)]
-    pub struct SpawnIndicatorAssets {
-        #[asset(key = "anim.rock")]
-        pub rock_animation: Handle<AnimationClip>,
-    }
-
     #[derive(Component, Copy, Clone, Debug, Default)]
     pub enum SpawnIndicatorEffect {
         #[default]
@@ -254,18 +262,12 @@ pub mod indicators {
 
     pub const NEON_EMISSIVE_SCALE: f32 = 2000.0;
 
-    pub fn neon_effect(
-        stage: &SpawnStage,
-        commands: &mut EntityCommands,
-        assets: &SpawnIndicatorAssets,
-    ) {
+    pub fn neon_effect(stage: &SpawnStage, commands: &mut EntityCommands) {
         match stage {
             SpawnStage::Indicate => {
                 commands.insert((
                     // pose
-                    Idle {
-                        clip: assets.rock_animation.clone(),
-                    },
+                    PlayAnimationState("rock"),
                     // fill
                     FillEffect::default(),
                     Animator::new(
@@ -327,16 +329,13 @@ pub mod indicators {
 
     pub fn transition_indicator_states<T: Component>(
         mut commands: Commands,
-        assets: Res<SpawnIndicatorAssets>,
         effect_query: Query<&SpawnIndicatorEffect>,
         mut events: EventReader<SpawnStageReached<T>>,
     ) {
         for SpawnStageReached { entity, stage, .. } in events.read() {
             let effect = effect_query.get(*entity).copied().unwrap_or_default();
             match effect {
-                SpawnIndicatorEffect::Neon => {
-                    neon_effect(stage, &mut commands.entity(*entity), &assets)
-                }
+                SpawnIndicatorEffect::Neon => neon_effect(stage, &mut commands.entity(*entity)),
             }
         }
     }
@@ -371,7 +370,14 @@ where
     F: SystemParamFunction<Marker, In = (), Out = B>,
 {
     move |In(spawn_fn_in), mut spawn_events, mut params| {
-        for EnemySpawn { transform, .. } in spawn_events.read() {
+        for EnemySpawn {
+            transform,
+            squad,
+            patrol,
+            encounter,
+            ..
+        } in spawn_events.read()
+        {
             let bundle = spawn_fn.run(spawn_fn_in, params.p0());
 
             let EnemySpawnerParams {
@@ -379,10 +385,18 @@ where
                 mut indicator_events,
             } = params.p1();
 
-            let e_agent = commands
-                .spawn(bundle)
-                .insert(TransformBundle::from_transform(*transform))
-                .id();
+            let mut e_agent_commands = commands.spawn(bundle);
+            e_agent_commands.insert(TransformBundle::from_transform(*transform));
+            if let Some(squad) = squad {
+                e_agent_commands.insert(*squad);
+            }
+            if let Some(patrol) = patrol {
+                e_agent_commands.insert(patrol.clone());
+            }
+            if *encounter {
+                e_agent_commands.insert(EncounterMember);
+            }
+            let e_agent = e_agent_commands.id();
 
             indicator_events.send(SpawnBegan {
                 entity: e_agent,
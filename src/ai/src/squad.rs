@@ -0,0 +1,219 @@
+//! Agents that look out for each other.
+
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use grin_character::PlayerCharacter;
+use grin_damage::health::{Dead, DeathEvent};
+use grin_time::Rewind;
+
+use crate::{
+    perception::{perceive_targets, Awareness},
+    AiSet,
+};
+
+pub struct SquadPlugin;
+
+impl Plugin for SquadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AlertPropagationEvent>().add_systems(
+            Update,
+            (announce_alerts, announce_deaths, propagate_alerts)
+                .chain()
+                .after(perceive_targets::<PlayerCharacter>)
+                .before(AiSet::RunTrees),
+        );
+    }
+}
+
+/// How close squad members need to be to each other to pass on alerts, without `RadioLinked`.
+pub const ALERT_PROPAGATION_RADIUS: f32 = 16.0;
+
+/// Agents in the same squad pass on what they know.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SquadId(pub u32);
+
+/// Hears squad alerts from any distance.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct RadioLinked;
+
+/// Sent when a squad member becomes `Alert` or gets killed.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct AlertPropagationEvent {
+    /// The agent that raised the alert.
+    pub source: Entity,
+    pub squad: SquadId,
+    /// Where the alert came from.
+    pub origin: Vec3,
+    /// Last known position of the target.
+    pub last_seen: Vec3,
+}
+
+/// Sends `AlertPropagationEvent` when a squad member becomes `Alert`.
+pub fn announce_alerts(
+    mut alerted: Local<EntityHashSet>,
+    agent_query: Query<(Entity, &SquadId, &Awareness, &GlobalTransform), Without<Dead>>,
+    mut propagation_events: EventWriter<AlertPropagationEvent>,
+) {
+    let mut now_alerted = EntityHashSet::default();
+    for (e_agent, &squad, awareness, g_transform) in agent_query.iter() {
+        let &Awareness::Alert { last_seen, .. } = awareness else {
+            continue;
+        };
+        now_alerted.insert(e_agent);
+        if !alerted.contains(&e_agent) {
+            propagation_events.send(AlertPropagationEvent {
+                source: e_agent,
+                squad,
+                origin: g_transform.translation(),
+                last_seen,
+            });
+        }
+    }
+    *alerted = now_alerted;
+}
+
+/// Sends `AlertPropagationEvent` when a squad member is killed by something.
+pub fn announce_deaths(
+    agent_query: Query<(&SquadId, &GlobalTransform)>,
+    transform_query: Query<&GlobalTransform>,
+    mut death_events: EventReader<DeathEvent>,
+    mut propagation_events: EventWriter<AlertPropagationEvent>,
+) {
+    for &DeathEvent { entity, killer } in death_events.read() {
+        let Some(e_killer) = killer else {
+            continue;
+        };
+        let Ok((&squad, g_transform)) = agent_query.get(entity) else {
+            continue;
+        };
+        let Ok(g_killer_transform) = transform_query.get(e_killer) else {
+            continue;
+        };
+        propagation_events.send(AlertPropagationEvent {
+            source: entity,
+            squad,
+            origin: g_transform.translation(),
+            last_seen: g_killer_transform.translation(),
+        });
+    }
+}
+
+/// Makes squad members in range at least `Suspicious` of the last known position.
+pub fn propagate_alerts(
+    mut agent_query: Query<
+        (
+            Entity,
+            &SquadId,
+            &mut Awareness,
+            &GlobalTransform,
+            Has<RadioLinked>,
+        ),
+        (Without<Rewind>, Without<Dead>),
+    >,
+    mut propagation_events: EventReader<AlertPropagationEvent>,
+) {
+    for event in propagation_events.read() {
+        for (e_agent, &squad, mut awareness, g_transform, radio) in agent_query.iter_mut() {
+            if e_agent == event.source || squad != event.squad {
+                continue;
+            }
+            if !radio && g_transform.translation().distance(event.origin) > ALERT_PROPAGATION_RADIUS
+            {
+                continue;
+            }
+            if !matches!(*awareness, Awareness::Alert { .. }) {
+                *awareness = Awareness::suspicious(event.last_seen);
+                debug!(
+                    msg="Squad alert propagated.",
+                    from=?event.source,
+                    to=?e_agent,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spawn_member(app: &mut App, x: f32) -> Entity {
+        let transform = Transform::from_xyz(x, 0.0, 0.0);
+        app.world
+            .spawn((
+                SquadId(0),
+                Awareness::default(),
+                TransformBundle {
+                    local: transform,
+                    global: transform.into(),
+                },
+            ))
+            .id()
+    }
+
+    #[test]
+    fn alert_on_death() {
+        let mut app = App::new();
+        app.add_event::<DeathEvent>()
+            .add_event::<AlertPropagationEvent>()
+            .add_systems(
+                Update,
+                (announce_alerts, announce_deaths, propagate_alerts).chain(),
+            );
+
+        let e_first = spawn_member(&mut app, 0.0);
+        let e_second = spawn_member(&mut app, 2.0);
+        let e_far = spawn_member(&mut app, 2.0 * ALERT_PROPAGATION_RADIUS);
+        let e_other_squad = spawn_member(&mut app, 2.0);
+        app.world.entity_mut(e_other_squad).insert(SquadId(1));
+        let e_killer = app
+            .world
+            .spawn(TransformBundle::from_transform(Transform::from_xyz(
+                0.0, 0.0, -8.0,
+            )))
+            .id();
+
+        app.world.send_event(DeathEvent {
+            entity: e_first,
+            killer: Some(e_killer),
+        });
+        app.update();
+
+        let Awareness::Suspicious { last_seen, .. } = app.world.get::<Awareness>(e_second).unwrap()
+        else {
+            panic!("Squad member didn't hear about the death.");
+        };
+        assert_eq!(
+            *last_seen,
+            Vec3::new(0.0, 0.0, -8.0),
+            "Wasn't suspicious of the killer."
+        );
+        assert!(
+            matches!(
+                app.world.get::<Awareness>(e_far).unwrap(),
+                Awareness::Unaware
+            ),
+            "Alert went past the propagation radius."
+        );
+        assert!(
+            matches!(
+                app.world.get::<Awareness>(e_other_squad).unwrap(),
+                Awareness::Unaware
+            ),
+            "Alert went to another squad."
+        );
+
+        app.world.entity_mut(e_far).insert(RadioLinked);
+        app.world.send_event(DeathEvent {
+            entity: e_second,
+            killer: Some(e_killer),
+        });
+        app.update();
+        assert!(
+            matches!(
+                app.world.get::<Awareness>(e_far).unwrap(),
+                Awareness::Suspicious { .. }
+            ),
+            "`RadioLinked` member didn't hear the alert."
+        );
+    }
+}
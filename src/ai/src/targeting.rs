@@ -0,0 +1,215 @@
+//! Picking who to attack by more than just distance.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use grin_damage::health::{Dead, Health, LastDamagedBy};
+use grin_physics::CollisionGroupExt;
+use grin_time::Rewind;
+
+use crate::{
+    bt::{Brain, Verdict},
+    movement::AttackTarget,
+    perception::Awareness,
+};
+
+/// Weights for `set_scored_attack_target`. Higher scores win.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct TargetScoringPolicy {
+    /// Subtracted per meter away.
+    pub distance_weight: f32,
+    /// Added for whoever hit the agent last.
+    pub damage_weight: f32,
+    /// Added for targets that aren't behind the map.
+    pub line_of_sight_bonus: f32,
+    /// Subtracted per point of `Health`. Positive goes after weaker targets.
+    pub health_weight: f32,
+    /// How much a new target needs to beat the current one by to switch. Stops flip-flopping.
+    pub switch_margin: f32,
+}
+
+impl Default for TargetScoringPolicy {
+    fn default() -> Self {
+        Self {
+            distance_weight: 1.0,
+            damage_weight: 20.0,
+            line_of_sight_bonus: 10.0,
+            health_weight: 0.0,
+            switch_margin: 5.0,
+        }
+    }
+}
+
+impl TargetScoringPolicy {
+    pub fn score(
+        &self,
+        distance: f32,
+        damaged_by: bool,
+        line_of_sight: bool,
+        health: Option<f32>,
+    ) -> f32 {
+        let mut score = -self.distance_weight * distance;
+        if damaged_by {
+            score += self.damage_weight;
+        }
+        if line_of_sight {
+            score += self.line_of_sight_bonus;
+        }
+        score - self.health_weight * health.unwrap_or_default()
+    }
+}
+
+/// Like `set_closest_attack_target`, but picks the best `TargetScoringPolicy` score.
+///
+/// Agents with `Awareness` only consider targets they're `Alert` to.
+pub fn set_scored_attack_target<T: Component, A: Component, E: Component>(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    mut agent_query: Query<
+        (
+            Entity,
+            &mut Brain,
+            &TargetScoringPolicy,
+            &GlobalTransform,
+            Option<&AttackTarget>,
+            Option<&LastDamagedBy>,
+            Option<&Awareness>,
+        ),
+        (With<T>, With<A>, Without<Rewind>, Without<Dead>),
+    >,
+    target_query: Query<(Entity, &GlobalTransform, Option<&Health>), With<E>>,
+) {
+    for (e_agent, mut brain, policy, g_transform, current, last_damaged_by, awareness) in
+        agent_query.iter_mut()
+    {
+        let origin = g_transform.translation();
+        let mut best = None::<(Entity, f32)>;
+        let mut current_score = None;
+
+        for (e_target, g_target_transform, health) in target_query.iter() {
+            if awareness.is_some_and(|a| !a.is_alert_to(e_target)) {
+                continue;
+            }
+
+            let offset = g_target_transform.translation() - origin;
+            let distance = offset.length();
+            let line_of_sight = distance <= f32::EPSILON
+                || rapier_context
+                    .cast_ray(
+                        origin,
+                        offset / distance,
+                        distance,
+                        true,
+                        QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+                    )
+                    .is_none();
+            let score = policy.score(
+                distance,
+                last_damaged_by.is_some_and(|l| l.0 == e_target),
+                line_of_sight,
+                health.map(|h| h.0),
+            );
+
+            if current.is_some_and(|c| c.0 == e_target) {
+                current_score = Some(score);
+            }
+            if best.map_or(true, |(_, s)| score > s) {
+                best = Some((e_target, score));
+            }
+        }
+
+        // only switch if it's worth it
+        let new_target = match (current, current_score, best) {
+            (Some(&current), Some(current_score), Some((_, best_score)))
+                if best_score < current_score + policy.switch_margin =>
+            {
+                Some(current)
+            }
+            (.., best) => best.map(|(e, _)| AttackTarget(e)),
+        };
+
+        if let Some(t) = new_target {
+            commands.entity(e_agent).insert(t);
+            brain.write_verdict(Verdict::Success);
+        } else {
+            commands.entity(e_agent).remove::<AttackTarget>();
+            brain.write_verdict(Verdict::Failure);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component)]
+    struct MockAgent;
+
+    #[derive(Component)]
+    struct MockTarget;
+
+    fn spawn_at(app: &mut App, x: f32, bundle: impl Bundle) -> Entity {
+        let transform = Transform::from_xyz(x, 0.0, 0.0);
+        app.world
+            .spawn((
+                bundle,
+                TransformBundle {
+                    local: transform,
+                    global: transform.into(),
+                },
+            ))
+            .id()
+    }
+
+    fn attack_target(app: &App, e_agent: Entity) -> Option<Entity> {
+        app.world.get::<AttackTarget>(e_agent).map(|t| t.0)
+    }
+
+    #[test]
+    fn attacker_wins() {
+        let mut app = App::new();
+        app.init_resource::<RapierContext>().add_systems(
+            Update,
+            set_scored_attack_target::<MockAgent, MockAgent, MockTarget>,
+        );
+
+        let e_agent = spawn_at(
+            &mut app,
+            0.0,
+            (MockAgent, Brain::default(), TargetScoringPolicy::default()),
+        );
+        let e_idle = spawn_at(&mut app, 2.0, MockTarget);
+        let e_shooter = spawn_at(&mut app, -10.0, MockTarget);
+
+        app.update();
+        assert_eq!(
+            attack_target(&app, e_agent),
+            Some(e_idle),
+            "Didn't go for the closest target."
+        );
+
+        app.world
+            .entity_mut(e_agent)
+            .insert(LastDamagedBy(e_shooter));
+        app.update();
+        assert_eq!(
+            attack_target(&app, e_agent),
+            Some(e_shooter),
+            "Turned its back on the target shooting it."
+        );
+
+        // the attacker stops counting, but the closer one doesn't beat it by enough
+        app.world
+            .entity_mut(e_agent)
+            .remove::<LastDamagedBy>()
+            .insert(TargetScoringPolicy {
+                switch_margin: 10.0,
+                ..Default::default()
+            });
+        app.update();
+        assert_eq!(
+            attack_target(&app, e_agent),
+            Some(e_shooter),
+            "Switched targets without beating the margin."
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Giving the player a fair warning before an attack lands.
+
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use grin_damage::{
+    health::Dead,
+    status::{Stagger, Stunned},
+};
+use grin_physics::PhysicsTime;
+use grin_render::{
+    outline::{OutlineOverride, OutlineOverrideCommandsExt, TELEGRAPH_PRIORITY},
+    sketched::GlobalMeshOutline,
+    tint::{set_tint_color, TintCompletedEvent, TintEffect},
+    EffectFlags,
+};
+use grin_time::{scaling::TimeScale, Rewind};
+
+use crate::{
+    bt::{Brain, Verdict},
+    AiSet,
+};
+
+pub struct TelegraphPlugin;
+
+impl Plugin for TelegraphPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TelegraphFinishedEvent>().add_systems(
+            Update,
+            (init_telegraphs, tick_telegraphs)
+                .chain()
+                .before(AiSet::RunTrees)
+                .before(set_tint_color),
+        );
+    }
+}
+
+/// Lifts decals off the ground, so they don't z-fight.
+const DECAL_OFFSET: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug)]
+pub enum TelegraphStyle {
+    /// Ramps the emissive up to `color` over the wind-up.
+    Tint { color: Color },
+    /// Pulses the outline up to `max_scale` times its normal width, `rate` times per second.
+    Outline { max_scale: f32, rate: f32 },
+    /// A circle under the agent that grows to `radius`. For AoE attacks.
+    GroundDecal { radius: f32, color: Color },
+}
+
+/// Wind-up before an attack. Sends `TelegraphFinishedEvent` when it's over, which is when the
+/// attack should actually happen.
+///
+/// Gets cancelled if the agent is killed, stunned or staggered.
+#[derive(Component, Clone, Debug)]
+#[component(storage = "SparseSet")]
+pub struct Telegraph {
+    /// Seconds.
+    pub duration: f32,
+    pub style: TelegraphStyle,
+    /// Seconds so far.
+    pub elapsed: f32,
+}
+
+impl Telegraph {
+    pub fn new(duration: f32, style: TelegraphStyle) -> Self {
+        Self {
+            duration,
+            style,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        match self.duration > 0.0 {
+            true => (self.elapsed / self.duration).clamp(0.0, 1.0),
+            false => 1.0,
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Sent when a `Telegraph` runs out without being cancelled.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct TelegraphFinishedEvent {
+    pub entity: Entity,
+}
+
+/// Spawned under the agent for `TelegraphStyle::GroundDecal`.
+#[derive(Component, Debug)]
+pub struct TelegraphDecal;
+
+pub fn init_telegraphs(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    agent_query: Query<(Entity, &Telegraph), Added<Telegraph>>,
+) {
+    for (e_agent, telegraph) in agent_query.iter() {
+        match telegraph.style {
+            TelegraphStyle::Tint { .. } => {
+                commands.entity(e_agent).insert(TintEffect {
+                    emissive: Some(Color::BLACK),
+                    // reverted by `tick_telegraphs`
+                    flags: EffectFlags::empty(),
+                    ..Default::default()
+                });
+            }
+            TelegraphStyle::Outline { .. } => (),
+            TelegraphStyle::GroundDecal { radius, color } => {
+                let e_decal = commands
+                    .spawn((
+                        TelegraphDecal,
+                        NotShadowCaster,
+                        PbrBundle {
+                            mesh: meshes.add(Circle::new(radius)),
+                            material: materials.add(StandardMaterial {
+                                base_color: color,
+                                unlit: true,
+                                alpha_mode: AlphaMode::Blend,
+                                ..Default::default()
+                            }),
+                            // `Circle` faces +Z
+                            transform: Transform::from_xyz(0.0, DECAL_OFFSET, 0.0)
+                                .with_rotation(Quat::from_rotation_x(-FRAC_PI_2))
+                                .with_scale(Vec3::ZERO),
+                            ..Default::default()
+                        },
+                    ))
+                    .id();
+                commands.entity(e_agent).add_child(e_decal);
+            }
+        }
+    }
+}
+
+pub fn tick_telegraphs(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    outline: Res<GlobalMeshOutline>,
+    mut agent_query: Query<
+        (
+            Entity,
+            &mut Telegraph,
+            Option<&mut TintEffect>,
+            Option<&Children>,
+            Option<&TimeScale>,
+        ),
+        Without<Rewind>,
+    >,
+    cancel_query: Query<(), Or<(With<Dead>, With<Stunned>, With<Stagger>)>>,
+    mut decal_query: Query<&mut Transform, With<TelegraphDecal>>,
+    mut tint_events: EventWriter<TintCompletedEvent>,
+    mut finished_events: EventWriter<TelegraphFinishedEvent>,
+) {
+    for (e_agent, mut telegraph, tint, children, time_scale) in agent_query.iter_mut() {
+        let decals = children
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|&e| decal_query.contains(e))
+            .collect::<Vec<_>>();
+
+        let cancelled = cancel_query.contains(e_agent);
+        if !cancelled {
+            telegraph.elapsed += time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        }
+
+        if cancelled || telegraph.finished() {
+            commands.entity(e_agent).remove::<Telegraph>();
+
+            // back to normal
+            if let Some(mut tint) = tint {
+                tint.flags = EffectFlags::DESPAWN | EffectFlags::REZERO;
+                tint_events.send(TintCompletedEvent(e_agent));
+            }
+            commands.entity(e_agent).clear_outline_override("telegraph");
+            for e_decal in decals {
+                commands.entity(e_decal).despawn_recursive();
+            }
+
+            if !cancelled {
+                finished_events.send(TelegraphFinishedEvent { entity: e_agent });
+            } else {
+                debug!(msg="Telegraph cancelled.", e_agent=?e_agent);
+            }
+            continue;
+        }
+
+        let progress = telegraph.progress();
+        match telegraph.style {
+            TelegraphStyle::Tint { color } => {
+                if let Some(mut tint) = tint {
+                    tint.emissive = Some(color.as_rgba_linear() * progress);
+                }
+            }
+            TelegraphStyle::Outline { max_scale, rate } => {
+                let pulse = 0.5 - 0.5 * (telegraph.elapsed * rate * TAU).cos();
+                let width = outline.standard.outline.width * (1.0 + (max_scale - 1.0) * pulse);
+                commands.entity(e_agent).set_outline_override(
+                    "telegraph",
+                    OutlineOverride::width(width, TELEGRAPH_PRIORITY),
+                );
+            }
+            TelegraphStyle::GroundDecal { .. } => {
+                for e_decal in decals {
+                    if let Ok(mut transform) = decal_query.get_mut(e_decal) {
+                        transform.scale = Vec3::splat(progress);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Writes `Verdict::Running` while the agent has a `Telegraph`, `Verdict::Success` otherwise.
+pub fn await_telegraph<T: Component, A: Component>(
+    mut agent_query: Query<(&mut Brain, Has<Telegraph>), (With<T>, With<A>)>,
+) {
+    for (mut brain, winding_up) in agent_query.iter_mut() {
+        brain.write_verdict(match winding_up {
+            true => Verdict::Running,
+            false => Verdict::Success,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn advance(app: &mut App, secs: f32) {
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_secs_f32(secs));
+        app.update();
+    }
+
+    fn finished(app: &App) -> Vec<Entity> {
+        let events = app.world.resource::<Events<TelegraphFinishedEvent>>();
+        events
+            .get_reader()
+            .read(events)
+            .map(|ev| ev.entity)
+            .collect()
+    }
+
+    #[test]
+    fn wind_up_and_cancel() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_resource::<PhysicsTime>()
+            .init_resource::<GlobalMeshOutline>()
+            .add_event::<TintCompletedEvent>()
+            .add_event::<TelegraphFinishedEvent>()
+            .add_systems(Update, (init_telegraphs, tick_telegraphs).chain());
+
+        let style = TelegraphStyle::Tint { color: Color::RED };
+        let e_agent = app.world.spawn(Telegraph::new(1.0, style)).id();
+        let e_cancelled = app.world.spawn(Telegraph::new(1.0, style)).id();
+
+        advance(&mut app, 0.5);
+        assert!(
+            app.world.get::<TintEffect>(e_agent).is_some(),
+            "Telegraph didn't tint the agent."
+        );
+        assert!(finished(&app).is_empty(), "Telegraph finished early.");
+
+        app.world.entity_mut(e_cancelled).insert(Dead);
+        advance(&mut app, 0.6);
+        assert_eq!(
+            finished(&app),
+            vec![e_agent],
+            "Telegraph didn't finish, or a cancelled one did."
+        );
+        for e in [e_agent, e_cancelled] {
+            assert!(
+                app.world.get::<Telegraph>(e).is_none(),
+                "Telegraph wasn't removed."
+            );
+            assert!(
+                app.world
+                    .get::<TintEffect>(e)
+                    .is_some_and(|t| t.flags.contains(EffectFlags::REZERO)),
+                "Tint wasn't reverted."
+            );
+        }
+
+        let tint_events = app.world.resource::<Events<TintCompletedEvent>>();
+        assert_eq!(
+            tint_events.get_reader().read(tint_events).count(),
+            2,
+            "Tint wasn't completed."
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Logical animation states, like "idle" or "aim_ranged_single_r", mapped onto clips.
+//!
+//! Each rig or kit gets one of these in its asset file, so new poses don't need code.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, reflect::TypePath, utils::HashMap};
+use serde::Deserialize;
+
+/// Everything falls back to this.
+pub const IDLE_STATE: &str = "idle";
+
+/// Blend seconds for transitions that aren't in the graph.
+pub const DEFAULT_BLEND_SECS: f32 = 0.1;
+
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct AnimationGraphAsset {
+    pub states: HashMap<String, AnimationGraphState>,
+    /// Blend seconds by `(from, to)`.
+    pub blends: HashMap<(String, String), f32>,
+    pub default_blend: f32,
+}
+
+impl Default for AnimationGraphAsset {
+    fn default() -> Self {
+        Self {
+            states: HashMap::default(),
+            blends: HashMap::default(),
+            default_blend: DEFAULT_BLEND_SECS,
+        }
+    }
+}
+
+impl AnimationGraphAsset {
+    /// How long to blend going from `from` to `to`.
+    pub fn blend(&self, from: Option<&str>, to: &str) -> Duration {
+        let secs = from
+            .and_then(|from| self.blends.get(&(from.to_string(), to.to_string())))
+            .copied()
+            .unwrap_or(self.default_blend);
+        Duration::from_secs_f32(secs)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationGraphState {
+    pub clip: Handle<AnimationClip>,
+    pub repeat: bool,
+}
+
+/// Deserializable `AnimationGraphState`. `item` is the animation's name in the GLTF.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AssetAnimationState {
+    pub item: String,
+    pub repeat: Option<bool>,
+}
+
+/// Deserializable blend between two states.
+#[derive(Debug, Deserialize, Clone)]
+pub struct AssetAnimationBlend {
+    pub from: String,
+    pub to: String,
+    pub secs: f32,
+}
@@ -1,18 +1,30 @@
+pub mod animation;
+pub mod preload;
+pub mod report;
 pub mod texture;
 
+use animation::{
+    AnimationGraphAsset, AnimationGraphState, AssetAnimationBlend, AssetAnimationState,
+};
 use bevy::{
-    asset::LoadState,
-    gltf::Gltf,
+    gltf::{Gltf, GltfMesh, GltfNode},
     prelude::*,
     reflect::TypePath,
-    render::render_resource::{Face, TextureViewDescriptor, TextureViewDimension},
-    utils::{thiserror::Error, HashMap},
+    render::{render_resource::Face, texture::ImageLoaderSettings},
+    utils::{thiserror::Error, HashMap, HashSet},
 };
 use bevy_asset_loader::prelude::*;
 use bevy_common_assets::ron::RonAssetPlugin;
-use grin_render::sketched::{SketchMaterial, SketchMaterialInfo, SketchUiImage};
+use grin_render::{
+    flipbook::{set_layered_view, LayeredTextures},
+    sketched::{SketchMaterial, SketchMaterialInfo, SketchUiImage},
+};
 use itertools::Itertools;
 use iyes_progress::prelude::*;
+use preload::{GltfPreload, GltfPreloadPlugin};
+use report::{
+    is_image_path, load_failed, AssetLoadReport, AssetLoadReportEntry, AssetReportPlugin,
+};
 use serde::Deserialize;
 
 pub const GLTF_PRELOAD_FOLDER: &str = "gltf/";
@@ -23,10 +35,14 @@ impl Plugin for DynamicAssetPlugin {
     fn build(&self, app: &mut App) {
         app.init_state::<AssetLoadState>()
             .init_resource::<FallbackImage>()
-            .init_resource::<GltfPreload>()
+            .init_resource::<LayeredTextures>()
+            .init_resource::<NormalMappedMaterials>()
+            .init_asset::<AnimationGraphAsset>()
             .add_plugins((
                 ProgressPlugin::new(AssetLoadState::Loading).continue_to(AssetLoadState::Success),
                 RonAssetPlugin::<CustomDynamicAssetCollection>::new(&["assets.ron"]),
+                AssetReportPlugin,
+                GltfPreloadPlugin,
             ))
             .add_loading_state(
                 LoadingState::new(AssetLoadState::Loading)
@@ -35,20 +51,23 @@ impl Plugin for DynamicAssetPlugin {
                     .register_dynamic_asset_collection::<CustomDynamicAssetCollection>()
                     .with_dynamic_assets_file::<CustomDynamicAssetCollection>("test.assets.ron"),
             )
-            .add_systems(OnEnter(AssetLoadState::PreLoading), begin_gltf_preload)
-            .add_systems(
-                Update,
-                end_gltf_preload.run_if(in_state(AssetLoadState::PreLoading)),
-            )
+            .add_systems(Update, warn_missing_tangents)
             .add_systems(
                 Update,
-                log_load_progress.run_if(in_state(AssetLoadState::Loading)),
+                log_load_progress.run_if(
+                    in_state(AssetLoadState::PreLoading).or_else(in_state(AssetLoadState::Loading)),
+                ),
             );
     }
 }
 
 pub fn log_load_progress(progress: Option<Res<ProgressCounter>>, mut last_done: Local<u32>) {
-    if let Some(progress) = progress.map(|counter| counter.progress()) {
+    if let Some(counter) = progress {
+        // new counter for each state
+        if counter.is_added() {
+            *last_done = 0;
+        }
+        let progress = counter.progress();
         if progress.done > *last_done {
             *last_done = progress.done;
             debug!("{:?}", progress);
@@ -56,36 +75,38 @@ pub fn log_load_progress(progress: Option<Res<ProgressCounter>>, mut last_done:
     }
 }
 
-/// All GlTF files are loaded EARLY, so that the asset files can specify sub-assets directly
-/// and put them into asset collections.
-///
-/// This step will no longer be needed if bevy ever supports direct loading of named GLTF sub-assets.
+/// Keys of the `SketchMaterial`s that have normal maps.
 #[derive(Resource, Debug, Default)]
-pub struct GltfPreload(pub HashMap<String, Handle<Gltf>>);
-
-pub fn begin_gltf_preload(asset_server: Res<AssetServer>, mut gltf_preload: ResMut<GltfPreload>) {
-    // load everything in the `GLTF_PRELOAD_FOLDER` folder
-    for file in std::fs::read_dir("assets/".to_string() + GLTF_PRELOAD_FOLDER).unwrap() {
-        if let Ok(file) = file {
-            let fname = file.file_name().into_string().unwrap();
-            let fpath = GLTF_PRELOAD_FOLDER.to_string() + fname.as_str();
-            gltf_preload.0.insert(fname, asset_server.load(fpath));
-        }
-    }
-}
+pub struct NormalMappedMaterials(pub HashMap<AssetId<SketchMaterial>, String>);
 
-pub fn end_gltf_preload(
-    asset_server: Res<AssetServer>,
-    gltf_preload: Res<GltfPreload>,
-    mut next_state: ResMut<NextState<AssetLoadState>>,
+/// Normal maps don't work without tangents, and nothing says so otherwise.
+pub fn warn_missing_tangents(
+    normal_mapped: Res<NormalMappedMaterials>,
+    meshes: Res<Assets<Mesh>>,
+    mesh_query: Query<
+        (&Handle<Mesh>, &Handle<SketchMaterial>),
+        Or<(Changed<Handle<Mesh>>, Changed<Handle<SketchMaterial>>)>,
+    >,
+    mut warned: Local<HashSet<(AssetId<Mesh>, AssetId<SketchMaterial>)>>,
 ) {
-    // check for preload completion
-    if gltf_preload
-        .0
-        .values()
-        .all(|h| matches!(asset_server.get_load_state(h), Some(LoadState::Loaded)))
-    {
-        next_state.set(AssetLoadState::Loading);
+    for (h_mesh, h_material) in mesh_query.iter() {
+        let Some(key) = normal_mapped.0.get(&h_material.id()) else {
+            continue;
+        };
+        let Some(mesh) = meshes.get(h_mesh) else {
+            continue;
+        };
+        if mesh.contains_attribute(Mesh::ATTRIBUTE_TANGENT)
+            || !warned.insert((h_mesh.id(), h_material.id()))
+        {
+            continue;
+        }
+        warn!(
+            "`{}` has a normal map, but it's on a mesh without tangents ({:?}). \
+            Use `Mesh::generate_tangents`, or export them with the model.",
+            key,
+            h_mesh.id(),
+        );
     }
 }
 
@@ -98,14 +119,7 @@ impl FromWorld for FallbackImage {
     fn from_world(world: &mut World) -> Self {
         let mut textures = world.resource_mut::<Assets<Image>>();
         let mut tex = Image::default();
-        tex.reinterpret_stacked_2d_as_array(1);
-        tex.texture_view_descriptor = Some(TextureViewDescriptor {
-            label: Some("D2Array Texture View"),
-            dimension: Some(TextureViewDimension::D2Array),
-            format: Some(tex.texture_descriptor.format),
-            array_layer_count: Some(1),
-            ..Default::default()
-        });
+        set_layered_view(&mut tex, 1);
         Self {
             texture: textures.add(tex),
         }
@@ -206,39 +220,130 @@ pub enum CustomDynamicAsset {
     UVSphereMesh {
         radius: f32,
     },
-    // NOTE: will fill in the rest if I ever need to... feeling lazy
+    /// Anything left out is the same as `StandardMaterial::default()`.
+    ///
+    /// Only `base_color_texture` is layered. The other textures go through the regular
+    /// `StandardMaterial` bindings, so they stay D2.
     SketchMaterial {
         base_color: Option<[f32; 4]>,
         base_color_texture: Option<String>,
         perceptual_roughness: Option<f32>,
+        metallic: Option<f32>,
         reflectance: Option<f32>,
         emissive: Option<[f32; 4]>,
+        emissive_texture: Option<String>,
+        /// Needs tangents on the mesh.
+        normal_map_texture: Option<String>,
+        occlusion_texture: Option<String>,
         double_sided: Option<bool>,
         cull_mode: Option<AssetFace>,
         layers: Option<u32>,
         alpha_mode: Option<AssetAlphaMode>,
         unlit: Option<bool>,
+        depth_bias: Option<f32>,
+        fog_enabled: Option<bool>,
+        parallax_depth_scale: Option<f32>,
     },
     SketchUiImage {
         images: Vec<String>,
     },
+    /// Animation states by name. Clips come from `source` like `GltfSubAsset`.
+    AnimationGraph {
+        source: String,
+        states: HashMap<String, AssetAnimationState>,
+        blends: Option<Vec<AssetAnimationBlend>>,
+        default_blend: Option<f32>,
+    },
+}
+
+impl CustomDynamicAsset {
+    /// Every file this asset comes from.
+    pub fn paths(&self) -> Vec<String> {
+        match self {
+            Self::File { path } => vec![path.clone()],
+            Self::GltfSubAsset { source, .. } | Self::AnimationGraph { source, .. } => {
+                vec![GLTF_PRELOAD_FOLDER.to_string() + source]
+            }
+            Self::UVSphereMesh { .. } => vec![],
+            Self::SketchMaterial { .. } => self
+                .textures()
+                .into_iter()
+                .map(|(path, _)| path.clone())
+                .collect_vec(),
+            Self::SketchUiImage { images } => images.clone(),
+        }
+    }
+
+    /// Texture paths of a `SketchMaterial`, and whether they're linear (not sRGB).
+    fn textures(&self) -> Vec<(&String, bool)> {
+        let Self::SketchMaterial {
+            base_color_texture,
+            emissive_texture,
+            normal_map_texture,
+            occlusion_texture,
+            ..
+        } = self
+        else {
+            return vec![];
+        };
+        [
+            (base_color_texture, false),
+            (emissive_texture, false),
+            (normal_map_texture, true),
+            (occlusion_texture, true),
+        ]
+        .into_iter()
+        .filter_map(|(path, linear)| path.as_ref().map(|path| (path, linear)))
+        .collect_vec()
+    }
+
+    /// Something to put in the collection if it can't be built, so the loading state doesn't
+    /// blow up. Only materials are fine to actually use.
+    fn placeholder(&self) -> Option<(UntypedHandle, bool)> {
+        match self {
+            Self::GltfSubAsset { ty, .. } => Some(match ty {
+                GltfSubAssetType::Scene => (Handle::<Scene>::default().untyped(), false),
+                GltfSubAssetType::Animation => {
+                    (Handle::<AnimationClip>::default().untyped(), false)
+                }
+                GltfSubAssetType::Mesh => (Handle::<GltfMesh>::default().untyped(), false),
+                GltfSubAssetType::Material => {
+                    (Handle::<StandardMaterial>::default().untyped(), true)
+                }
+                GltfSubAssetType::Node => (Handle::<GltfNode>::default().untyped(), false),
+            }),
+            Self::SketchMaterial { .. } => {
+                Some((Handle::<SketchMaterial>::default().untyped(), true))
+            }
+            Self::SketchUiImage { .. } => {
+                Some((Handle::<SketchUiImage>::default().untyped(), true))
+            }
+            Self::AnimationGraph { .. } => {
+                Some((Handle::<AnimationGraphAsset>::default().untyped(), false))
+            }
+            Self::File { .. } | Self::UVSphereMesh { .. } => None,
+        }
+    }
 }
 
 impl DynamicAsset for CustomDynamicAsset {
     fn load(&self, asset_server: &AssetServer) -> Vec<UntypedHandle> {
         trace!("{:?}", self);
+        // things that already failed are only still around because of "continue anyway"
+        // they get substituted in `build`
         match self {
-            Self::File { path } => vec![asset_server.load_untyped(path).untyped()],
-            Self::UVSphereMesh { .. } | Self::GltfSubAsset { .. } => vec![],
-            Self::SketchMaterial {
-                base_color_texture, ..
-            } => base_color_texture
-                .as_ref()
-                .map_or_else(Default::default, |path| {
-                    vec![asset_server.load_untyped(path).untyped()]
-                }),
-            Self::SketchUiImage { images } => images
+            // already preloaded
+            Self::GltfSubAsset { .. } | Self::AnimationGraph { .. } => vec![],
+            Self::SketchMaterial { .. } => self
+                .textures()
+                .into_iter()
+                .filter(|(path, _)| !load_failed(asset_server, path))
+                .map(|(path, linear)| load_texture(asset_server, path, linear).untyped())
+                .collect_vec(),
+            _ => self
+                .paths()
                 .iter()
+                .filter(|path| !load_failed(asset_server, path))
                 .map(|path| asset_server.load_untyped(path).untyped())
                 .collect_vec(),
         }
@@ -249,18 +354,18 @@ impl DynamicAsset for CustomDynamicAsset {
         let asset_server = world_cell.resource::<AssetServer>();
 
         match self {
+            Self::File { path } if is_image_path(path) && load_failed(&asset_server, path) => Ok(
+                DynamicAssetType::Single(Handle::<Image>::default().untyped()),
+            ),
             Self::File { path } => Ok(DynamicAssetType::Single(
-                asset_server.get_handle_untyped(path).unwrap(),
+                asset_server
+                    .get_handle_untyped(path)
+                    .ok_or_else(|| anyhow::anyhow!("`{}` was never loaded.", path))?,
             )),
             Self::GltfSubAsset { source, item, ty } => {
                 let gltf_assets = world_cell.resource::<Assets<Gltf>>();
                 let gltf_preload = world_cell.resource::<GltfPreload>();
-                // get the corresponding gltf defined in `source`
-                let gltf_handle = gltf_preload
-                    .0
-                    .get(source)
-                    .ok_or(GltfSubAssetLoadError::SourceNotFound(source.clone()))?;
-                let gltf = gltf_assets.get(gltf_handle).unwrap();
+                let gltf = gltf_preload.get(&gltf_assets, source)?;
                 // AVERAGE RUST PROGRAM
                 Ok(DynamicAssetType::Single(
                     match ty {
@@ -298,13 +403,20 @@ impl DynamicAsset for CustomDynamicAsset {
                 base_color,
                 base_color_texture,
                 perceptual_roughness,
+                metallic,
                 reflectance,
                 emissive,
+                emissive_texture,
+                normal_map_texture,
+                occlusion_texture,
                 double_sided,
                 cull_mode,
                 layers,
                 alpha_mode,
                 unlit,
+                depth_bias,
+                fog_enabled,
+                parallax_depth_scale,
             } => {
                 // the textureview dimension MUST be D2Array
                 // this is a problem because singly layered images
@@ -312,31 +424,27 @@ impl DynamicAsset for CustomDynamicAsset {
                 // which leads to mismatches
                 let mut materials = world_cell.resource_mut::<Assets<SketchMaterial>>();
 
-                let base_color_texture = match base_color_texture {
-                    Some(tex_path) => {
-                        let mut textures = world_cell.resource_mut::<Assets<Image>>();
-
-                        let tex_handle = asset_server.load(tex_path);
-                        let tex = textures
-                            .get_mut(&tex_handle)
-                            .expect("Failed to get StandardMaterial texture.");
+                let mut textures = world_cell.resource_mut::<Assets<Image>>();
+                let tex = base_color_texture.as_ref().and_then(|tex_path| {
+                    let tex_handle = load_texture(&asset_server, tex_path, false);
+                    textures.get_mut(&tex_handle).map(|tex| (tex_handle, tex))
+                });
 
+                let base_color_texture = match tex {
+                    Some((tex_handle, tex)) => {
                         if tex.texture_descriptor.size.depth_or_array_layers == 1 {
                             let layers = layers.unwrap_or(1);
-                            tex.reinterpret_stacked_2d_as_array(layers);
-
-                            // force dimension to D2Array
-                            tex.texture_view_descriptor = Some(TextureViewDescriptor {
-                                label: Some("D2Array Texture View"),
-                                dimension: Some(TextureViewDimension::D2Array),
-                                format: Some(tex.texture_descriptor.format),
-                                array_layer_count: Some(layers),
-                                ..Default::default()
-                            });
+                            set_layered_view(tex, layers);
+                            // so it can be split up again if it gets hot-reloaded
+                            world_cell
+                                .resource_mut::<LayeredTextures>()
+                                .0
+                                .insert(tex_handle.id(), layers);
                         }
 
                         tex_handle
                     }
+                    // also used for textures that failed to load, if continuing anyway
                     // a custom FallbackImage is used
                     // because bevy's is always D2 even if the binding isn't
                     // (this will be fixed in 0.11)
@@ -344,6 +452,13 @@ impl DynamicAsset for CustomDynamicAsset {
                     None => world_cell.resource::<FallbackImage>().texture.clone(),
                 };
 
+                // these ones are optional, so failed ones can just be left out
+                let optional_texture = |path: &Option<String>, linear: bool| {
+                    path.as_ref()
+                        .filter(|path| !load_failed(&asset_server, path))
+                        .map(|path| load_texture(&asset_server, path, linear))
+                };
+
                 let mat_default = StandardMaterial::default();
 
                 Ok(DynamicAssetType::Single(
@@ -355,15 +470,23 @@ impl DynamicAsset for CustomDynamicAsset {
                                 base_color_texture: None,
                                 perceptual_roughness: perceptual_roughness
                                     .unwrap_or(mat_default.perceptual_roughness),
+                                metallic: metallic.unwrap_or(mat_default.metallic),
                                 reflectance: reflectance.unwrap_or(mat_default.reflectance),
                                 emissive: emissive
                                     .map_or(mat_default.emissive, Color::rgba_from_array),
+                                emissive_texture: optional_texture(emissive_texture, false),
+                                normal_map_texture: optional_texture(normal_map_texture, true),
+                                occlusion_texture: optional_texture(occlusion_texture, true),
                                 double_sided: double_sided.unwrap_or(mat_default.double_sided),
                                 cull_mode: cull_mode
                                     .map_or(mat_default.cull_mode, Option::<Face>::from),
                                 alpha_mode: alpha_mode
                                     .map_or(mat_default.alpha_mode, AlphaMode::from),
                                 unlit: unlit.unwrap_or(mat_default.unlit),
+                                depth_bias: depth_bias.unwrap_or(mat_default.depth_bias),
+                                fog_enabled: fog_enabled.unwrap_or(mat_default.fog_enabled),
+                                parallax_depth_scale: parallax_depth_scale
+                                    .unwrap_or(mat_default.parallax_depth_scale),
                                 ..Default::default()
                             },
                             extension: SketchMaterialInfo {
@@ -372,11 +495,46 @@ impl DynamicAsset for CustomDynamicAsset {
                                 base_color_texture: Some(base_color_texture),
                                 fill_enabled: true,
                                 y_cutoff: f32::MAX,
+                                ..Default::default()
                             },
                         })
                         .untyped(),
                 ))
             }
+            Self::AnimationGraph {
+                source,
+                states,
+                blends,
+                default_blend,
+            } => {
+                let gltf_assets = world_cell.resource::<Assets<Gltf>>();
+                let gltf_preload = world_cell.resource::<GltfPreload>();
+                let gltf = gltf_preload.get(&gltf_assets, source)?;
+
+                let mut graph = AnimationGraphAsset::default();
+                for (state, AssetAnimationState { item, repeat }) in states.iter() {
+                    let clip = gltf
+                        .named_animations
+                        .get(item)
+                        .ok_or(GltfSubAssetLoadError::ItemNotFound(item.clone()))?;
+                    graph.states.insert(
+                        state.clone(),
+                        AnimationGraphState {
+                            clip: clip.clone(),
+                            repeat: repeat.unwrap_or(false),
+                        },
+                    );
+                }
+                for AssetAnimationBlend { from, to, secs } in blends.iter().flatten() {
+                    graph.blends.insert((from.clone(), to.clone()), *secs);
+                }
+                if let Some(default_blend) = default_blend {
+                    graph.default_blend = *default_blend;
+                }
+
+                let mut assets = world_cell.resource_mut::<Assets<AnimationGraphAsset>>();
+                Ok(DynamicAssetType::Single(assets.add(graph).untyped()))
+            }
             Self::SketchUiImage { images } => {
                 let mut assets = world_cell.resource_mut::<Assets<SketchUiImage>>();
                 Ok(DynamicAssetType::Single(
@@ -384,7 +542,10 @@ impl DynamicAsset for CustomDynamicAsset {
                         .add(SketchUiImage {
                             images: images
                                 .iter()
-                                .map(|path| asset_server.load(path))
+                                .map(|path| match load_failed(&asset_server, path) {
+                                    true => Handle::default(),
+                                    false => asset_server.load(path),
+                                })
                                 .collect_vec(),
                         })
                         .untyped(),
@@ -394,13 +555,183 @@ impl DynamicAsset for CustomDynamicAsset {
     }
 }
 
+/// Normal maps and occlusion maps are data, so they shouldn't be loaded as sRGB.
+fn load_texture(asset_server: &AssetServer, path: &str, linear: bool) -> Handle<Image> {
+    match linear {
+        true => asset_server.load_with_settings(path.to_string(), |s: &mut ImageLoaderSettings| {
+            s.is_srgb = false
+        }),
+        false => asset_server.load(path.to_string()),
+    }
+}
+
 #[derive(Asset, Deserialize, TypePath)]
 pub struct CustomDynamicAssetCollection(pub HashMap<String, CustomDynamicAsset>);
 
 impl DynamicAssetCollection for CustomDynamicAssetCollection {
     fn register(&self, dynamic_assets: &mut DynamicAssets) {
         for (key, asset) in self.0.iter() {
-            dynamic_assets.register_asset(key, Box::new(asset.clone()));
+            dynamic_assets.register_asset(
+                key,
+                Box::new(ReportedDynamicAsset {
+                    key: key.clone(),
+                    asset: asset.clone(),
+                }),
+            );
+        }
+    }
+}
+
+/// A `CustomDynamicAsset` that puts its build errors in the `AssetLoadReport`,
+/// so they show up under the right key.
+#[derive(Debug, Clone)]
+pub struct ReportedDynamicAsset {
+    pub key: String,
+    pub asset: CustomDynamicAsset,
+}
+
+impl DynamicAsset for ReportedDynamicAsset {
+    fn load(&self, asset_server: &AssetServer) -> Vec<UntypedHandle> {
+        self.asset.load(asset_server)
+    }
+
+    fn build(&self, world: &mut World) -> Result<DynamicAssetType, anyhow::Error> {
+        let built = self.asset.build(world);
+        if let (
+            Ok(DynamicAssetType::Single(handle)),
+            CustomDynamicAsset::SketchMaterial {
+                normal_map_texture: Some(_),
+                ..
+            },
+        ) = (&built, &self.asset)
+        {
+            if let Some(mut normal_mapped) = world.get_resource_mut::<NormalMappedMaterials>() {
+                normal_mapped
+                    .0
+                    .insert(handle.id().typed(), self.key.clone());
+            }
         }
+
+        built.or_else(|error| {
+            let placeholder = self.asset.placeholder();
+            if let Some(mut report) = world.get_resource_mut::<AssetLoadReport>() {
+                report.push(AssetLoadReportEntry {
+                    keys: vec![self.key.clone()],
+                    path: None,
+                    reason: error.to_string(),
+                    substitutable: placeholder.as_ref().is_some_and(|(_, safe)| *safe),
+                });
+            }
+            placeholder
+                .map(|(handle, _)| DynamicAssetType::Single(handle))
+                .ok_or(error)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::ron;
+
+    use super::*;
+
+    fn build(
+        app: &mut App,
+        collection: &CustomDynamicAssetCollection,
+        key: &str,
+    ) -> AssetId<SketchMaterial> {
+        let asset = ReportedDynamicAsset {
+            key: key.to_string(),
+            asset: collection.0[key].clone(),
+        };
+        let Ok(DynamicAssetType::Single(handle)) = asset.build(&mut app.world) else {
+            panic!("`{}` didn't build.", key);
+        };
+        handle.id().typed()
+    }
+
+    fn texture_path(app: &App, texture: &Option<Handle<Image>>) -> Option<String> {
+        let path = app
+            .world
+            .resource::<AssetServer>()
+            .get_path(texture.as_ref()?.id())?;
+        Some(path.to_string())
+    }
+
+    #[test]
+    fn sketch_material_fields() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Image>()
+            .init_asset::<SketchMaterial>()
+            .init_resource::<FallbackImage>()
+            .init_resource::<LayeredTextures>()
+            .init_resource::<NormalMappedMaterials>();
+
+        let collection: CustomDynamicAssetCollection =
+            ron::de::from_str(include_str!("../tests/sketch_material.assets.ron"))
+                .expect("Asset file didn't deserialize.");
+        let id_everything = build(&mut app, &collection, "mat.everything");
+        let id_nothing = build(&mut app, &collection, "mat.nothing");
+
+        let materials = app.world.resource::<Assets<SketchMaterial>>();
+        let everything = &materials.get(id_everything).unwrap().base;
+        assert_eq!(everything.metallic, 0.8, "`metallic` wasn't applied.");
+        assert_eq!(everything.depth_bias, 2.0, "`depth_bias` wasn't applied.");
+        assert!(!everything.fog_enabled, "`fog_enabled` wasn't applied.");
+        assert_eq!(
+            everything.parallax_depth_scale, 0.05,
+            "`parallax_depth_scale` wasn't applied."
+        );
+        assert_eq!(
+            texture_path(&app, &everything.emissive_texture).as_deref(),
+            Some("textures/test/emissive.png"),
+            "`emissive_texture` wasn't loaded."
+        );
+        assert_eq!(
+            texture_path(&app, &everything.normal_map_texture).as_deref(),
+            Some("textures/test/normal.png"),
+            "`normal_map_texture` wasn't loaded."
+        );
+        assert_eq!(
+            texture_path(&app, &everything.occlusion_texture).as_deref(),
+            Some("textures/test/occlusion.png"),
+            "`occlusion_texture` wasn't loaded."
+        );
+
+        let nothing = &materials.get(id_nothing).unwrap().base;
+        let mat_default = StandardMaterial::default();
+        assert_eq!(
+            (
+                nothing.metallic,
+                nothing.depth_bias,
+                nothing.fog_enabled,
+                nothing.parallax_depth_scale,
+            ),
+            (
+                mat_default.metallic,
+                mat_default.depth_bias,
+                mat_default.fog_enabled,
+                mat_default.parallax_depth_scale,
+            ),
+            "Unspecified fields weren't left at the defaults."
+        );
+        assert!(
+            nothing.emissive_texture.is_none()
+                && nothing.normal_map_texture.is_none()
+                && nothing.occlusion_texture.is_none(),
+            "Unspecified textures were loaded."
+        );
+
+        let normal_mapped = &app.world.resource::<NormalMappedMaterials>().0;
+        assert_eq!(
+            normal_mapped.get(&id_everything).map(String::as_str),
+            Some("mat.everything"),
+            "Normal mapped material wasn't keyed."
+        );
+        assert!(
+            !normal_mapped.contains_key(&id_nothing),
+            "Material without a normal map was keyed."
+        );
     }
 }
@@ -0,0 +1,285 @@
+//! All GLTF files are loaded EARLY, so that the asset files can specify sub-assets directly
+//! and put them into asset collections.
+//!
+//! This step will no longer be needed if bevy ever supports direct loading of named GLTF sub-assets.
+
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    asset::{io::AssetSourceId, LoadState},
+    gltf::Gltf,
+    prelude::*,
+    tasks::{block_on, poll_once, IoTaskPool, Task},
+    utils::HashMap,
+};
+use futures_lite::StreamExt;
+use iyes_progress::prelude::*;
+
+use crate::{
+    report::{AssetLoadReport, AssetLoadReportEntry},
+    AssetLoadState, GltfSubAssetLoadError, GLTF_PRELOAD_FOLDER,
+};
+
+const BAR_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+const BAR_FILL_COLOR: Color = Color::WHITE;
+
+pub struct GltfPreloadPlugin;
+
+impl Plugin for GltfPreloadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GltfPreload>()
+            .add_plugins(
+                ProgressPlugin::new(AssetLoadState::PreLoading)
+                    .continue_to(AssetLoadState::Loading),
+            )
+            .add_systems(
+                OnEnter(AssetLoadState::PreLoading),
+                (begin_gltf_preload, spawn_preload_bar),
+            )
+            .add_systems(OnExit(AssetLoadState::PreLoading), despawn_preload_bar)
+            .add_systems(
+                Update,
+                (
+                    (poll_gltf_listing, update_preload_bar).chain(),
+                    track_gltf_preload.track_progress(),
+                )
+                    .run_if(in_state(AssetLoadState::PreLoading)),
+            );
+    }
+}
+
+type GltfListing = Result<Vec<PathBuf>, String>;
+
+#[derive(Resource, Default)]
+pub struct GltfPreload {
+    /// By file name.
+    pub gltfs: HashMap<String, Handle<Gltf>>,
+    /// Reading the folder. `None` once it's done.
+    listing: Option<Task<GltfListing>>,
+}
+
+impl GltfPreload {
+    pub fn listed(&self) -> bool {
+        self.listing.is_none()
+    }
+
+    /// How many GLTFs are done, including the ones that failed.
+    pub fn finished(&self, asset_server: &AssetServer) -> usize {
+        self.gltfs
+            .values()
+            .filter(|h| {
+                matches!(
+                    asset_server.get_load_state(*h),
+                    Some(LoadState::Loaded | LoadState::Failed)
+                )
+            })
+            .count()
+    }
+
+    /// The first GLTF that's still loading, alphabetically.
+    pub fn current(&self, asset_server: &AssetServer) -> Option<&str> {
+        self.gltfs
+            .iter()
+            .filter(|(_, h)| {
+                !matches!(
+                    asset_server.get_load_state(*h),
+                    Some(LoadState::Loaded | LoadState::Failed)
+                )
+            })
+            .map(|(fname, _)| fname.as_str())
+            .min()
+    }
+
+    pub fn clear(&mut self) {
+        self.gltfs.clear();
+        self.listing = None;
+    }
+
+    /// The preloaded GLTF with the file name `source`.
+    pub fn get<'a>(
+        &self,
+        gltf_assets: &'a Assets<Gltf>,
+        source: &str,
+    ) -> Result<&'a Gltf, GltfSubAssetLoadError> {
+        let gltf_handle = self
+            .gltfs
+            .get(source)
+            .ok_or(GltfSubAssetLoadError::SourceNotFound(source.to_string()))?;
+        gltf_assets
+            .get(gltf_handle)
+            .ok_or(GltfSubAssetLoadError::GltfNotFound)
+    }
+}
+
+fn is_gltf(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gltf") || ext.eq_ignore_ascii_case("glb"))
+}
+
+/// Reads the `GLTF_PRELOAD_FOLDER` through the asset source, so it works wherever the assets are.
+pub fn begin_gltf_preload(asset_server: Res<AssetServer>, mut gltf_preload: ResMut<GltfPreload>) {
+    gltf_preload.clear();
+    let asset_server = asset_server.clone();
+    gltf_preload.listing = Some(IoTaskPool::get().spawn(async move {
+        let source = asset_server
+            .get_source(AssetSourceId::Default)
+            .map_err(|e| e.to_string())?;
+        let paths = source
+            .reader()
+            .read_directory(Path::new(GLTF_PRELOAD_FOLDER))
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok::<_, String>(paths.filter(|path| is_gltf(path)).collect().await)
+    }));
+}
+
+pub fn poll_gltf_listing(
+    asset_server: Res<AssetServer>,
+    mut gltf_preload: ResMut<GltfPreload>,
+    mut report: ResMut<AssetLoadReport>,
+) {
+    let Some(task) = gltf_preload.listing.as_mut() else {
+        return;
+    };
+    let Some(listing) = block_on(poll_once(task)) else {
+        return;
+    };
+    gltf_preload.listing = None;
+
+    let paths = match listing {
+        Ok(paths) => paths,
+        Err(e) => {
+            return report.push(AssetLoadReportEntry {
+                keys: Vec::new(),
+                path: Some(GLTF_PRELOAD_FOLDER.to_string()),
+                reason: format!("Couldn't read the GLTF folder: {}", e),
+                substitutable: false,
+            });
+        }
+    };
+    for path in paths {
+        match path.file_name().and_then(|fname| fname.to_str()) {
+            Some(fname) => {
+                let fname = fname.to_string();
+                gltf_preload.gltfs.insert(fname, asset_server.load(path));
+            }
+            None => report.push(AssetLoadReportEntry {
+                keys: Vec::new(),
+                path: Some(path.to_string_lossy().into_owned()),
+                reason: "File name isn't valid unicode.".to_string(),
+                substitutable: false,
+            }),
+        }
+    }
+}
+
+/// Failed GLTFs count as done. They're in the `AssetLoadReport`.
+pub fn track_gltf_preload(
+    asset_server: Res<AssetServer>,
+    gltf_preload: Res<GltfPreload>,
+) -> Progress {
+    // the listing counts too, otherwise it'd be done before it started
+    Progress {
+        done: gltf_preload.listed() as u32 + gltf_preload.finished(&asset_server) as u32,
+        total: 1 + gltf_preload.gltfs.len() as u32,
+    }
+}
+
+#[derive(Component)]
+pub struct PreloadBar;
+
+#[derive(Component)]
+pub struct PreloadBarFill;
+
+#[derive(Component)]
+pub struct PreloadBarText;
+
+pub fn spawn_preload_bar(mut commands: Commands) {
+    // nothing else has a camera up yet
+    commands.spawn((PreloadBar, Camera2dBundle::default()));
+    commands
+        .spawn((
+            PreloadBar,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(8.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                PreloadBarText,
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 24.0,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(400.0),
+                        height: Val::Px(16.0),
+                        ..Default::default()
+                    },
+                    background_color: BAR_COLOR.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        PreloadBarFill,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..Default::default()
+                            },
+                            background_color: BAR_FILL_COLOR.into(),
+                            ..Default::default()
+                        },
+                    ));
+                });
+        });
+}
+
+pub fn despawn_preload_bar(mut commands: Commands, bar_query: Query<Entity, With<PreloadBar>>) {
+    for e_bar in bar_query.iter() {
+        commands.entity(e_bar).despawn_recursive();
+    }
+}
+
+pub fn update_preload_bar(
+    asset_server: Res<AssetServer>,
+    gltf_preload: Res<GltfPreload>,
+    mut fill_query: Query<&mut Style, With<PreloadBarFill>>,
+    mut text_query: Query<&mut Text, With<PreloadBarText>>,
+) {
+    let total = gltf_preload.gltfs.len();
+    let percent = match total {
+        0 => 0.0,
+        _ => gltf_preload.finished(&asset_server) as f32 / total as f32 * 100.0,
+    };
+    let label = match (gltf_preload.listed(), gltf_preload.current(&asset_server)) {
+        (false, _) => format!("Reading {}", GLTF_PRELOAD_FOLDER),
+        (true, Some(fname)) => format!("Loading {} ({:.0}%)", fname, percent),
+        (true, None) => format!("Loaded {} GLTFs", total),
+    };
+
+    for mut style in fill_query.iter_mut() {
+        style.width = Val::Percent(percent);
+    }
+    for mut text in text_query.iter_mut() {
+        text.sections[0].value.clone_from(&label);
+    }
+}
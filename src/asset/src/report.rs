@@ -0,0 +1,380 @@
+//! What went wrong while loading, and a screen for it instead of just sitting in `Failure`.
+
+use bevy::{
+    asset::{AssetPath, LoadState, UntypedAssetLoadFailedEvent},
+    prelude::*,
+};
+
+use crate::{preload::GltfPreload, AssetLoadState, CustomDynamicAssetCollection};
+
+const BUTTON_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+const BUTTON_HOVER_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.5);
+
+/// Extensions that the `FallbackImage` can stand in for.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "bmp", "tga", "ktx2", "dds", "hdr"];
+
+pub struct AssetReportPlugin;
+
+impl Plugin for AssetReportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AssetLoadReport>()
+            .add_systems(OnEnter(AssetLoadState::Failure), spawn_load_failure_menu)
+            .add_systems(OnExit(AssetLoadState::Failure), despawn_load_failure_menu)
+            .add_systems(Update, collect_load_failures)
+            .add_systems(
+                Update,
+                press_load_failure_buttons.run_if(in_state(AssetLoadState::Failure)),
+            )
+            .add_systems(
+                Last,
+                fail_on_reported_errors.run_if(
+                    in_state(AssetLoadState::PreLoading).or_else(in_state(AssetLoadState::Loading)),
+                ),
+            );
+    }
+}
+
+/// Everything that failed since the last retry.
+#[derive(Resource, Debug, Default)]
+pub struct AssetLoadReport {
+    pub errors: Vec<AssetLoadReportEntry>,
+    /// Set by "continue anyway". Failed assets get substituted where they can be,
+    /// instead of going to `AssetLoadState::Failure`.
+    pub continue_anyway: bool,
+}
+
+impl AssetLoadReport {
+    /// Adds the error, unless there's already one for the same path.
+    pub fn push(&mut self, entry: AssetLoadReportEntry) {
+        if entry.path.is_some() && self.errors.iter().any(|e| e.path == entry.path) {
+            return;
+        }
+        warn!("Asset failed to load: {}", entry);
+        self.errors.push(entry);
+    }
+
+    /// Whether everything that failed has a stand-in.
+    pub fn can_continue(&self) -> bool {
+        self.errors.iter().all(|e| e.substitutable)
+    }
+
+    pub fn clear(&mut self) {
+        self.errors.clear();
+        self.continue_anyway = false;
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssetLoadReportEntry {
+    /// Dynamic asset keys that needed it. Empty if it wasn't from an asset collection.
+    pub keys: Vec<String>,
+    pub path: Option<String>,
+    pub reason: String,
+    /// Whether "continue anyway" can put something in its place.
+    pub substitutable: bool,
+}
+
+impl std::fmt::Display for AssetLoadReportEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.keys.is_empty(), &self.path) {
+            (false, _) => write!(f, "{}", self.keys.join(", "))?,
+            (true, Some(path)) => write!(f, "{}", path)?,
+            (true, None) => write!(f, "<unknown>")?,
+        }
+        write!(f, ": {}", self.reason)
+    }
+}
+
+pub fn is_image_path(path: &str) -> bool {
+    AssetPath::parse(path)
+        .get_full_extension()
+        .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// Whether `path` already failed to load. Loading it again would just fail again.
+pub fn load_failed(asset_server: &AssetServer, path: &str) -> bool {
+    asset_server
+        .get_handle_untyped(path)
+        .is_some_and(|h| matches!(asset_server.get_load_state(h.id()), Some(LoadState::Failed)))
+}
+
+/// Keys of every dynamic asset that loads something from `path`.
+pub fn keys_for_path(
+    collections: &Assets<CustomDynamicAssetCollection>,
+    path: &AssetPath,
+) -> Vec<String> {
+    let mut keys = collections
+        .iter()
+        .flat_map(|(_, collection)| collection.0.iter())
+        .filter(|(_, asset)| {
+            asset
+                .paths()
+                .iter()
+                .any(|p| AssetPath::parse(p).path() == path.path())
+        })
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys
+}
+
+pub fn collect_load_failures(
+    mut report: ResMut<AssetLoadReport>,
+    collections: Res<Assets<CustomDynamicAssetCollection>>,
+    mut failure_events: EventReader<UntypedAssetLoadFailedEvent>,
+) {
+    for UntypedAssetLoadFailedEvent { path, error, .. } in failure_events.read() {
+        let path_str = path.path().to_string_lossy().into_owned();
+        report.push(AssetLoadReportEntry {
+            keys: keys_for_path(&collections, path),
+            substitutable: is_image_path(&path_str),
+            path: Some(path_str),
+            reason: error.to_string(),
+        });
+    }
+}
+
+/// `DynamicAsset::build` errors get a placeholder so the loading state doesn't panic,
+/// and failed GLTFs count as preloaded, so this is what actually stops them.
+/// Runs in `Last` so it beats whatever the progress trackers want.
+pub fn fail_on_reported_errors(
+    report: Res<AssetLoadReport>,
+    mut next_state: ResMut<NextState<AssetLoadState>>,
+) {
+    if !report.errors.is_empty() && !report.continue_anyway {
+        next_state.set(AssetLoadState::Failure);
+    }
+}
+
+#[derive(Component)]
+pub struct LoadFailureMenu;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoadFailureButton {
+    Retry,
+    ContinueAnyway,
+}
+
+fn menu_text(value: impl Into<String>, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            color: Color::WHITE,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn spawn_load_failure_menu(mut commands: Commands, report: Res<AssetLoadReport>) {
+    // nothing else has a camera up yet
+    commands.spawn((LoadFailureMenu, Camera2dBundle::default()));
+    commands
+        .spawn((
+            LoadFailureMenu,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(menu_text("FAILED TO LOAD", 64.0));
+            for entry in report.errors.iter() {
+                parent.spawn(menu_text(entry.to_string(), 16.0));
+            }
+
+            let mut buttons = vec![(LoadFailureButton::Retry, "Retry")];
+            if report.can_continue() {
+                buttons.push((LoadFailureButton::ContinueAnyway, "Continue anyway"));
+            }
+            for (button, label) in buttons {
+                parent
+                    .spawn((
+                        button,
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(300.0),
+                                padding: UiRect::all(Val::Px(8.0)),
+                                justify_content: JustifyContent::Center,
+                                ..Default::default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..Default::default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(menu_text(label, 32.0));
+                    });
+            }
+        });
+}
+
+pub fn despawn_load_failure_menu(
+    mut commands: Commands,
+    menu_query: Query<Entity, With<LoadFailureMenu>>,
+) {
+    for e_menu in menu_query.iter() {
+        commands.entity(e_menu).despawn_recursive();
+    }
+}
+
+pub fn press_load_failure_buttons(
+    asset_server: Res<AssetServer>,
+    mut report: ResMut<AssetLoadReport>,
+    collections: Res<Assets<CustomDynamicAssetCollection>>,
+    mut gltf_preload: ResMut<GltfPreload>,
+    mut button_query: Query<
+        (&Interaction, &LoadFailureButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut next_state: ResMut<NextState<AssetLoadState>>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        color.0 = match interaction {
+            Interaction::Hovered => BUTTON_HOVER_COLOR,
+            _ => BUTTON_COLOR,
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            LoadFailureButton::Retry => {
+                retry(&asset_server, &mut report, &collections, &mut gltf_preload);
+                next_state.set(AssetLoadState::PreLoading);
+            }
+            LoadFailureButton::ContinueAnyway => {
+                report.continue_anyway = true;
+                next_state.set(AssetLoadState::Loading);
+            }
+        }
+    }
+}
+
+/// Reloads whatever failed along with the asset collections, in case they got fixed.
+pub fn retry(
+    asset_server: &AssetServer,
+    report: &mut AssetLoadReport,
+    collections: &Assets<CustomDynamicAssetCollection>,
+    gltf_preload: &mut GltfPreload,
+) {
+    for path in report.errors.iter().filter_map(|e| e.path.as_ref()) {
+        asset_server.reload(path.clone());
+    }
+    for (id, _) in collections.iter() {
+        if let Some(path) = asset_server.get_path(id) {
+            asset_server.reload(path.into_owned());
+        }
+    }
+    gltf_preload.clear();
+    report.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::utils::HashMap;
+
+    use crate::CustomDynamicAsset;
+
+    use super::*;
+
+    fn entry(path: &str, substitutable: bool) -> AssetLoadReportEntry {
+        AssetLoadReportEntry {
+            keys: Vec::new(),
+            path: Some(path.into()),
+            reason: "Oops".into(),
+            substitutable,
+        }
+    }
+
+    #[test]
+    fn load_report() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<CustomDynamicAssetCollection>()
+            .init_state::<AssetLoadState>()
+            .init_resource::<GltfPreload>()
+            .add_plugins(AssetReportPlugin);
+
+        app.world
+            .resource_mut::<Assets<CustomDynamicAssetCollection>>()
+            .add(CustomDynamicAssetCollection(HashMap::from([
+                (
+                    "mat.wall".to_string(),
+                    CustomDynamicAsset::SketchMaterial {
+                        base_color: None,
+                        base_color_texture: Some("textures/wall.png".into()),
+                        perceptual_roughness: None,
+                        reflectance: None,
+                        emissive: None,
+                        double_sided: None,
+                        cull_mode: None,
+                        layers: None,
+                        alpha_mode: None,
+                        unlit: None,
+                    },
+                ),
+                (
+                    "mesh.wall".to_string(),
+                    CustomDynamicAsset::File {
+                        path: "meshes/wall.glb#Mesh0/Primitive0".into(),
+                    },
+                ),
+            ])));
+
+        let collections = app.world.resource::<Assets<CustomDynamicAssetCollection>>();
+        assert_eq!(
+            keys_for_path(collections, &AssetPath::parse("textures/wall.png")),
+            vec!["mat.wall".to_string()],
+            "Texture wasn't mapped to its key."
+        );
+        assert_eq!(
+            keys_for_path(collections, &AssetPath::parse("meshes/wall.glb")),
+            vec!["mesh.wall".to_string()],
+            "Labeled path wasn't mapped to its key."
+        );
+
+        let mut report = app.world.resource_mut::<AssetLoadReport>();
+        report.push(entry("textures/wall.png", true));
+        report.push(entry("textures/wall.png", true));
+        assert_eq!(report.errors.len(), 1, "Duplicate error wasn't dropped.");
+        assert!(report.can_continue(), "Image can't be substituted.");
+        report.push(entry("meshes/wall.glb", false));
+        assert!(!report.can_continue(), "Mesh can be substituted.");
+
+        app.world
+            .resource_mut::<NextState<AssetLoadState>>()
+            .set(AssetLoadState::Loading);
+        app.update();
+        app.update();
+        assert_eq!(
+            app.world.resource::<State<AssetLoadState>>().get(),
+            &AssetLoadState::Failure,
+            "Errors didn't fail loading."
+        );
+
+        app.world
+            .resource_scope(|world, mut report: Mut<AssetLoadReport>| {
+                let mut gltf_preload = GltfPreload::default();
+                retry(
+                    world.resource::<AssetServer>(),
+                    &mut report,
+                    world.resource::<Assets<CustomDynamicAssetCollection>>(),
+                    &mut gltf_preload,
+                );
+            });
+        assert!(
+            app.world.resource::<AssetLoadReport>().errors.is_empty(),
+            "Retrying didn't clear the report."
+        );
+    }
+}
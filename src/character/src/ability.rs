@@ -0,0 +1,234 @@
+//! Kit passives and ultimates.
+//!
+//! Each `Character` adds its own systems with `Character::abilities`. They only run while the
+//! player is that kit. Ultimates go off on `UltimateEvent`, once `UltimateCharge` is full.
+
+use bevy::prelude::*;
+use grin_damage::{
+    health::{Dead, Health},
+    hit::{credit_damage_owner, Damage, DamageEvent},
+    hitbox::Hitbox,
+    status::Stunned,
+};
+use grin_input::action::InputAction;
+
+use crate::{Character, PlayerCharacter};
+
+/// `UltimateCharge` per point of damage the player deals. 100 damage for a full charge.
+pub const ULTIMATE_CHARGE_PER_DAMAGE: f32 = 0.01;
+
+pub struct AbilityPlugin;
+
+impl Plugin for AbilityPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<UltimateCharge>()
+            .add_event::<UltimateEvent>()
+            .add_event::<UltimateChargeChangedEvent>()
+            .add_systems(
+                Update,
+                (charge_ultimate_on_damage, send_ultimate_charge_changes).chain(),
+            );
+    }
+}
+
+/// How charged up the player's ultimate is, from `0.0` to `1.0`.
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct UltimateCharge(pub f32);
+
+impl UltimateCharge {
+    pub fn add(&mut self, amount: f32) {
+        self.0 = (self.0 + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn full(&self) -> bool {
+        self.0 >= 1.0
+    }
+}
+
+/// Sent when the player uses their ultimate. The charge is already spent.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UltimateEvent {
+    pub entity: Entity,
+}
+
+/// Sent when `UltimateCharge` changes, for the HUD.
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct UltimateChargeChangedEvent {
+    pub percent: f32,
+}
+
+/// Run condition for kit abilities. The player is a living `T`.
+pub fn player_is<T: Character>(
+    player_query: Query<(), (With<T>, With<PlayerCharacter>, Without<Dead>)>,
+) -> bool {
+    !player_query.is_empty()
+}
+
+/// Who gets credit for a `DamageEvent`, and how much damage it was.
+/// `None` if it was absorbed, or it's damage over time.
+pub fn damage_credit(
+    event: &DamageEvent,
+    damage_query: &Query<&Damage>,
+    parent_query: &Query<&Parent>,
+    owner_query: &Query<(), With<Health>>,
+) -> Option<(Entity, Entity, f32)> {
+    let (damage, e_damage, e_hit) = match event {
+        DamageEvent::Contact {
+            e_damage,
+            e_hit,
+            absorbed: false,
+            ..
+        } => (*damage_query.get(*e_damage).ok()?, Some(*e_damage), *e_hit),
+        DamageEvent::Direct { damage, e_hit } => (*damage, None, *e_hit),
+        _ => return None,
+    };
+    let source = credit_damage_owner(damage.source, e_damage, parent_query, owner_query)?;
+    Some((source, e_hit, damage.value))
+}
+
+/// Damage the player deals to something else charges the ultimate.
+pub fn charge_ultimate_on_damage(
+    mut charge: ResMut<UltimateCharge>,
+    player_query: Query<Entity, (With<PlayerCharacter>, Without<Dead>)>,
+    damage_query: Query<&Damage>,
+    hitbox_query: Query<&Hitbox>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
+    mut damage_events: EventReader<DamageEvent>,
+) {
+    let Ok(e_player) = player_query.get_single() else {
+        damage_events.clear();
+        return;
+    };
+    for event in damage_events.read() {
+        let Some((source, e_hit, value)) =
+            damage_credit(event, &damage_query, &parent_query, &owner_query)
+        else {
+            continue;
+        };
+        let e_target = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+        if source == e_player && e_target != e_player && value > 0.0 {
+            charge.add(value * ULTIMATE_CHARGE_PER_DAMAGE);
+        }
+    }
+}
+
+pub fn send_ultimate_charge_changes(
+    charge: Res<UltimateCharge>,
+    mut charge_events: EventWriter<UltimateChargeChangedEvent>,
+) {
+    if charge.is_changed() {
+        charge_events.send(UltimateChargeChangedEvent {
+            percent: charge.0 * 100.0,
+        });
+    }
+}
+
+/// Spends a full `UltimateCharge` when the ultimate button is pressed.
+pub fn input_ultimate(
+    mut charge: ResMut<UltimateCharge>,
+    player_query: Query<Entity, (With<PlayerCharacter>, Without<Dead>, Without<Stunned>)>,
+    actions: Res<ButtonInput<InputAction>>,
+    mut ultimate_events: EventWriter<UltimateEvent>,
+) {
+    if !actions.just_pressed(InputAction::Ultimate) || !charge.full() {
+        return;
+    }
+    let Ok(e_player) = player_query.get_single() else {
+        return;
+    };
+    charge.0 = 0.0;
+    ultimate_events.send(UltimateEvent { entity: e_player });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(app: &mut App, source: Entity, e_hit: Entity, value: f32) {
+        app.world.send_event(DamageEvent::Direct {
+            damage: Damage {
+                value,
+                source: Some(source),
+                ..Default::default()
+            },
+            e_hit,
+        });
+        app.update();
+    }
+
+    fn charge_percents(app: &mut App) -> Vec<f32> {
+        app.world
+            .resource_mut::<Events<UltimateChargeChangedEvent>>()
+            .drain()
+            .map(|ev| ev.percent)
+            .collect()
+    }
+
+    #[test]
+    fn ultimate_charge() {
+        let mut app = App::new();
+        app.add_event::<DamageEvent>()
+            .init_resource::<ButtonInput<InputAction>>()
+            .add_plugins(AbilityPlugin)
+            .add_systems(Update, input_ultimate);
+
+        let e_player = app.world.spawn((PlayerCharacter, Health(100.0))).id();
+        let e_enemy = app.world.spawn(Health(100.0)).id();
+        app.update();
+        charge_percents(&mut app);
+
+        hit(&mut app, e_enemy, e_player, 50.0);
+        hit(&mut app, e_player, e_player, 50.0);
+        assert_eq!(
+            app.world.resource::<UltimateCharge>().0,
+            0.0,
+            "Charged from damage the player didn't deal to something else."
+        );
+
+        hit(&mut app, e_player, e_enemy, 60.0);
+        assert!(
+            (app.world.resource::<UltimateCharge>().0 - 0.6).abs() < 1E-4,
+            "Didn't charge from the player's damage."
+        );
+        assert_eq!(charge_percents(&mut app).len(), 1, "HUD wasn't told.");
+
+        app.world
+            .resource_mut::<ButtonInput<InputAction>>()
+            .press(InputAction::Ultimate);
+        app.update();
+        assert!(
+            app.world.resource::<Events<UltimateEvent>>().is_empty(),
+            "Ultimate went off before it was charged."
+        );
+        app.world
+            .resource_mut::<ButtonInput<InputAction>>()
+            .reset_all();
+
+        hit(&mut app, e_player, e_enemy, 60.0);
+        assert_eq!(
+            app.world.resource::<UltimateCharge>().0,
+            1.0,
+            "Charge didn't cap."
+        );
+        assert_eq!(charge_percents(&mut app), vec![100.0], "HUD wasn't told.");
+
+        app.world
+            .resource_mut::<ButtonInput<InputAction>>()
+            .press(InputAction::Ultimate);
+        app.update();
+        assert_eq!(
+            app.world.resource::<UltimateCharge>().0,
+            0.0,
+            "Charge wasn't spent."
+        );
+        assert_eq!(
+            app.world
+                .resource_mut::<Events<UltimateEvent>>()
+                .drain()
+                .collect::<Vec<_>>(),
+            vec![UltimateEvent { entity: e_player }],
+            "Ultimate didn't go off."
+        );
+    }
+}
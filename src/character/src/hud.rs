@@ -0,0 +1,502 @@
+//! Health bar and whatever the player's holding.
+
+use bevy::prelude::*;
+use grin_damage::{
+    health::{Dead, Health},
+    hit::DamageEvent,
+    hitbox::Hitbox,
+};
+use grin_item::{
+    equip::{Equipped, ItemIcon},
+    inventory::Inventory,
+    mechanics::firing::Ammo,
+};
+use grin_render::sketched::SketchUiImage;
+
+use crate::{ability::UltimateChargeChangedEvent, PlayerCharacter};
+
+/// Size of the status viewport in the bottom left. The HUD goes to the right of it.
+pub const STATUS_VIEWPORT_SIZE: f32 = 240.0;
+
+/// Space between HUD elements.
+pub const HUD_MARGIN: f32 = 12.0;
+
+/// How quickly the health bar catches up to the actual health. Higher is faster.
+pub const HEALTH_BAR_SMOOTHING: f32 = 8.0;
+
+/// Seconds the health bar flashes for after getting hit.
+pub const HEALTH_BAR_FLASH: f32 = 0.15;
+
+const HEALTH_COLOR: Color = Color::rgb(0.8, 0.1, 0.1);
+const FLASH_COLOR: Color = Color::WHITE;
+const DEAD_COLOR: Color = Color::GRAY;
+const ULTIMATE_COLOR: Color = Color::rgb(0.9, 0.7, 0.1);
+const ULTIMATE_READY_COLOR: Color = Color::rgb(1.0, 0.95, 0.4);
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        // in case `AbilityPlugin` isn't around
+        app.add_event::<UltimateChargeChangedEvent>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(
+                Update,
+                (
+                    show_hud,
+                    flash_health_bar,
+                    update_health_bar,
+                    update_ultimate_bar,
+                    update_death_banner,
+                    update_equipped_item_panel,
+                )
+                    .chain(),
+            );
+    }
+}
+
+#[derive(Component)]
+pub struct HudRoot;
+
+/// The part of the health bar that fills up.
+#[derive(Component, Debug)]
+pub struct HealthBar {
+    /// Health that's shown right now, which trails behind the actual health.
+    pub displayed: f32,
+    /// Highest health seen, since `Health` doesn't have a max.
+    pub max: f32,
+    pub flash: Timer,
+}
+
+impl Default for HealthBar {
+    fn default() -> Self {
+        let mut flash = Timer::from_seconds(HEALTH_BAR_FLASH, TimerMode::Once);
+        flash.tick(flash.duration());
+        Self {
+            displayed: 0.0,
+            max: 0.0,
+            flash,
+        }
+    }
+}
+
+/// The part of the ultimate bar that fills up.
+#[derive(Component)]
+pub struct UltimateBar;
+
+#[derive(Component)]
+pub struct EquippedItemIcon;
+
+#[derive(Component)]
+pub struct AmmoText;
+
+#[derive(Component)]
+pub struct DeathBanner;
+
+pub fn spawn_hud(mut commands: Commands) {
+    commands
+        .spawn((
+            HudRoot,
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(HUD_MARGIN),
+                    left: Val::Px(STATUS_VIEWPORT_SIZE + HUD_MARGIN),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(HUD_MARGIN),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            // equipped item
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        column_gap: Val::Px(HUD_MARGIN),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        EquippedItemIcon,
+                        NodeBundle {
+                            style: Style {
+                                display: Display::None,
+                                width: Val::Px(64.0),
+                                height: Val::Px(64.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                    ));
+                    parent.spawn((
+                        AmmoText,
+                        TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font_size: 32.0,
+                                color: Color::WHITE,
+                                ..Default::default()
+                            },
+                        ),
+                    ));
+                });
+
+            // health bar
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(STATUS_VIEWPORT_SIZE),
+                        height: Val::Px(16.0),
+                        ..Default::default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        HealthBar::default(),
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(100.0),
+                                height: Val::Percent(100.0),
+                                ..Default::default()
+                            },
+                            background_color: HEALTH_COLOR.into(),
+                            ..Default::default()
+                        },
+                    ));
+                });
+
+            // ultimate bar
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(STATUS_VIEWPORT_SIZE),
+                        height: Val::Px(6.0),
+                        ..Default::default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        UltimateBar,
+                        NodeBundle {
+                            style: Style {
+                                width: Val::Percent(0.0),
+                                height: Val::Percent(100.0),
+                                ..Default::default()
+                            },
+                            background_color: ULTIMATE_COLOR.into(),
+                            ..Default::default()
+                        },
+                    ));
+                });
+        });
+
+    commands.spawn((
+        DeathBanner,
+        TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                top: Val::Percent(40.0),
+                width: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            ..TextBundle::from_section(
+                "DEAD",
+                TextStyle {
+                    font_size: 96.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            )
+            .with_text_justify(JustifyText::Center)
+        },
+    ));
+}
+
+/// The HUD only shows up when there's a player.
+pub fn show_hud(
+    player_query: Query<(), With<PlayerCharacter>>,
+    mut root_query: Query<&mut Style, With<HudRoot>>,
+) {
+    let display = match player_query.is_empty() {
+        true => Display::None,
+        false => Display::Flex,
+    };
+    for mut style in root_query.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+}
+
+/// Flashes when the player gets hit.
+pub fn flash_health_bar(
+    time: Res<Time>,
+    player_query: Query<Entity, With<PlayerCharacter>>,
+    hitbox_query: Query<&Hitbox>,
+    mut bar_query: Query<&mut HealthBar>,
+    mut damage_events: EventReader<DamageEvent>,
+) {
+    let e_player = player_query.get_single().ok();
+    let hit = damage_events.read().any(|event| {
+        let e_hit = match event {
+            DamageEvent::Contact {
+                e_hit,
+                absorbed: false,
+                ..
+            }
+            | DamageEvent::Direct { e_hit, .. }
+            | DamageEvent::OverTime { e_hit, .. } => *e_hit,
+            DamageEvent::Contact { absorbed: true, .. } => return false,
+        };
+        let e_hit = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+        Some(e_hit) == e_player
+    });
+
+    for mut bar in bar_query.iter_mut() {
+        if hit {
+            bar.flash.reset();
+        } else {
+            bar.flash.tick(time.delta());
+        }
+    }
+}
+
+/// Eases the health bar towards the player's health.
+pub fn update_health_bar(
+    time: Res<Time>,
+    player_query: Query<(&Health, Has<Dead>), With<PlayerCharacter>>,
+    mut bar_query: Query<(&mut HealthBar, &mut Style, &mut BackgroundColor)>,
+) {
+    let Ok((health, dead)) = player_query.get_single() else {
+        return;
+    };
+
+    for (mut bar, mut style, mut color) in bar_query.iter_mut() {
+        if health.0 > bar.max {
+            // first time seeing the player, or it healed past what it started at
+            bar.max = health.0;
+            bar.displayed = health.0;
+        }
+
+        let t = 1.0 - (-HEALTH_BAR_SMOOTHING * time.delta_seconds()).exp();
+        bar.displayed += (health.0 - bar.displayed) * t;
+
+        let fill = match bar.max > 0.0 {
+            true => (bar.displayed / bar.max).clamp(0.0, 1.0),
+            false => 0.0,
+        };
+        style.width = Val::Percent(fill * 100.0);
+
+        color.0 = match (dead, bar.flash.finished()) {
+            (true, _) => DEAD_COLOR,
+            (false, false) => FLASH_COLOR,
+            (false, true) => HEALTH_COLOR,
+        };
+    }
+}
+
+pub fn update_ultimate_bar(
+    mut bar_query: Query<(&mut Style, &mut BackgroundColor), With<UltimateBar>>,
+    mut charge_events: EventReader<UltimateChargeChangedEvent>,
+) {
+    let Some(UltimateChargeChangedEvent { percent }) = charge_events.read().last() else {
+        return;
+    };
+    for (mut style, mut color) in bar_query.iter_mut() {
+        style.width = Val::Percent(*percent);
+        color.0 = match *percent >= 100.0 {
+            true => ULTIMATE_READY_COLOR,
+            false => ULTIMATE_COLOR,
+        };
+    }
+}
+
+/// Greys out the ammo counter and shows the banner when the player is `Dead`.
+pub fn update_death_banner(
+    player_query: Query<Has<Dead>, With<PlayerCharacter>>,
+    mut banner_query: Query<&mut Style, With<DeathBanner>>,
+    mut text_query: Query<&mut Text, With<AmmoText>>,
+) {
+    let dead = player_query.get_single().unwrap_or(false);
+    let display = match dead {
+        true => Display::Flex,
+        false => Display::None,
+    };
+    for mut style in banner_query.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    let color = match dead {
+        true => DEAD_COLOR,
+        false => Color::WHITE,
+    };
+    for mut text in text_query.iter_mut() {
+        for section in text.sections.iter_mut() {
+            section.style.color = color;
+        }
+    }
+}
+
+/// Shows the icon and ammo of the selected item, or the right hand if there's no `Inventory`.
+pub fn update_equipped_item_panel(
+    mut commands: Commands,
+    player_query: Query<(Option<&Inventory>, Option<&Equipped>), With<PlayerCharacter>>,
+    item_query: Query<(Option<&ItemIcon>, Option<&Ammo>)>,
+    mut icon_query: Query<
+        (Entity, &mut Style, Option<&Handle<SketchUiImage>>),
+        With<EquippedItemIcon>,
+    >,
+    mut text_query: Query<&mut Text, With<AmmoText>>,
+) {
+    let (icon, ammo) = player_query
+        .get_single()
+        .ok()
+        .and_then(|(inventory, equipped)| {
+            inventory
+                .and_then(Inventory::selected_item)
+                .or(equipped.map(|e| e.right))
+        })
+        .and_then(|e_item| item_query.get(e_item).ok())
+        .unwrap_or_default();
+
+    for (e_icon, mut style, current) in icon_query.iter_mut() {
+        match icon {
+            Some(ItemIcon(image)) => {
+                if current != Some(image) {
+                    commands.entity(e_icon).insert(image.clone());
+                }
+                style.display = Display::Flex;
+            }
+            None => {
+                if current.is_some() {
+                    commands
+                        .entity(e_icon)
+                        .remove::<(Handle<SketchUiImage>, UiImage)>();
+                }
+                if style.display != Display::None {
+                    style.display = Display::None;
+                }
+            }
+        }
+    }
+
+    let text = match ammo {
+        // infinite clip, so it doesn't really have ammo
+        Some(ammo) if ammo.clip_size == u32::MAX => String::new(),
+        Some(ammo) => match ammo.reserve {
+            Some(reserve) => format!("{} / {}", ammo.clip, reserve),
+            None => format!("{} / ∞", ammo.clip),
+        },
+        None => String::new(),
+    };
+    for mut ammo_text in text_query.iter_mut() {
+        if ammo_text.sections[0].value != text {
+            ammo_text.sections[0].value = text.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+    use grin_damage::hit::Damage;
+
+    use super::*;
+
+    fn health_bar(app: &mut App) -> (f32, Color) {
+        let (bar, style, color) = app
+            .world
+            .query::<(&HealthBar, &Style, &BackgroundColor)>()
+            .single(&app.world);
+        let Val::Percent(width) = style.width else {
+            panic!("Health bar isn't a percentage.");
+        };
+        assert!(bar.displayed <= bar.max);
+        (width, color.0)
+    }
+
+    fn displayed<T: Component>(app: &mut App) -> bool {
+        app.world
+            .query_filtered::<&Style, With<T>>()
+            .single(&app.world)
+            .display
+            != Display::None
+    }
+
+    #[test]
+    fn hud() {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                50,
+            )))
+            .add_event::<DamageEvent>()
+            .add_plugins(HudPlugin);
+
+        app.update();
+        assert!(
+            !displayed::<HudRoot>(&mut app),
+            "HUD shown without a player."
+        );
+
+        let e_player = app.world.spawn((PlayerCharacter, Health(100.0))).id();
+        app.update();
+        assert!(displayed::<HudRoot>(&mut app), "HUD hidden with a player.");
+        assert_eq!(health_bar(&mut app).0, 100.0, "Health bar isn't full.");
+
+        app.world.get_mut::<Health>(e_player).unwrap().0 = 50.0;
+        app.world.send_event(DamageEvent::Direct {
+            damage: Damage::default(),
+            e_hit: e_player,
+        });
+        app.update();
+        let (width, color) = health_bar(&mut app);
+        assert!(
+            width < 100.0 && width > 50.0,
+            "Health bar didn't ease down. ({})",
+            width
+        );
+        assert_eq!(color, FLASH_COLOR, "Health bar didn't flash.");
+
+        for _ in 0..60 {
+            app.update();
+        }
+        let (width, color) = health_bar(&mut app);
+        assert!((width - 50.0).abs() < 0.1, "Health bar didn't settle.");
+        assert_eq!(color, HEALTH_COLOR, "Health bar is still flashing.");
+
+        app.world
+            .send_event(UltimateChargeChangedEvent { percent: 100.0 });
+        app.update();
+        let (style, color) = app
+            .world
+            .query_filtered::<(&Style, &BackgroundColor), With<UltimateBar>>()
+            .single(&app.world);
+        assert_eq!(style.width, Val::Percent(100.0), "Ultimate bar isn't full.");
+        assert_eq!(
+            color.0, ULTIMATE_READY_COLOR,
+            "Ultimate bar doesn't show it's ready."
+        );
+
+        app.world.entity_mut(e_player).insert(Dead);
+        app.update();
+        assert!(displayed::<DeathBanner>(&mut app), "No death banner.");
+        assert_eq!(health_bar(&mut app).1, DEAD_COLOR, "Health bar isn't grey.");
+    }
+}
@@ -1,15 +1,34 @@
-use bevy::prelude::*;
+use bevy::{
+    ecs::schedule::SystemConfigs,
+    gltf::{GltfMesh, GltfNode},
+    prelude::*,
+};
 use bevy_asset_loader::prelude::*;
+use bevy_rapier3d::prelude::*;
 use grin_asset::AssetLoadState;
-use grin_damage::hitbox::{GltfHitboxAutoGenTarget, HitboxManager, Hurtboxes};
+use grin_damage::{
+    explosion::{Explosion, ExplosionEvent, Falloff},
+    hit::{Damage, DamageEvent, DamageVariant},
+    hitbox::{GltfHitboxAutoGenTarget, Hitbox, HitboxManager, Hurtboxes},
+};
+use grin_map::marker::SpawnPoint;
+use grin_physics::{CollisionGroupExt, CollisionGroupsExt};
 use grin_render::sketched::SketchMaterial;
 use grin_rig::{
-    humanoid::{Humanoid, HumanoidBuild, HumanoidBundle, HumanoidDominantHand},
-    Idle,
+    accessory::{AccessorySpec, HumanoidAccessories},
+    humanoid::{Humanoid, HumanoidBuild, HumanoidBundle, HumanoidDominantHand, HumanoidPartType},
 };
 use grin_util::event::Spawnable;
 
-use crate::{Character, CharacterSet, GenericHumanoidCharacterPlugin, PlayerCharacter};
+use crate::{
+    ability::{UltimateCharge, UltimateEvent},
+    default_player_spawn, Character, CharacterSet, GenericHumanoidCharacterPlugin, PlayerCharacter,
+};
+
+/// Passive. Extra `UltimateCharge` for each melee hit, on top of the damage.
+pub const GRIN_MELEE_HIT_CHARGE: f32 = 0.05;
+pub const GRIN_ULTIMATE_RADIUS: f32 = 6.0;
+pub const GRIN_ULTIMATE_DAMAGE: f32 = 80.0;
 
 pub struct GrinPlugin;
 
@@ -34,8 +53,10 @@ pub struct GrinAssets {
     pub face: Handle<SketchMaterial>,
     #[asset(key = "rig.grin")]
     pub rig: Handle<Scene>,
-    #[asset(key = "anim.idle")]
-    pub idle: Handle<AnimationClip>,
+    #[asset(key = "node.pizza_shades")]
+    pub shades: Handle<GltfNode>,
+    #[asset(key = "mat.shades")]
+    pub shades_material: Handle<SketchMaterial>,
 }
 
 #[derive(Event, Clone, Default)]
@@ -49,6 +70,10 @@ pub struct GrinUninit;
 
 impl Character for Grin {
     type StartItem = grin_item::library::fist::Fist;
+
+    fn abilities() -> SystemConfigs {
+        (charge_ultimate_on_melee, grin_ultimate).into_configs()
+    }
 }
 
 impl Spawnable for Grin {
@@ -58,9 +83,19 @@ impl Spawnable for Grin {
 pub fn spawn(
     mut commands: Commands,
     assets: Res<GrinAssets>,
+    nodes: Res<Assets<GltfNode>>,
+    gltf_meshes: Res<Assets<GltfMesh>>,
+    spawn_point_query: Query<&SpawnPoint>,
     mut events: EventReader<<Grin as Spawnable>::Event>,
 ) {
     for _ in events.read() {
+        let shades = AccessorySpec::from_gltf_node(
+            HumanoidPartType::Head,
+            &assets.shades,
+            assets.shades_material.clone(),
+            &nodes,
+            &gltf_meshes,
+        );
         commands.spawn((
             GrinUninit,
             PlayerCharacter,
@@ -69,14 +104,15 @@ pub fn spawn(
                 face: assets.face.clone().into(),
                 build: HumanoidBuild::Male,
                 dominant_hand: HumanoidDominantHand::Right,
-                spatial: SpatialBundle::from_transform(Transform::from_xyz(0.0, 1E-2, 0.0)),
+                spatial: SpatialBundle::from_transform(
+                    SpawnPoint::player(spawn_point_query.iter())
+                        .unwrap_or_else(default_player_spawn),
+                ),
                 ..Default::default()
             },
-            Idle {
-                clip: assets.idle.clone(),
-            },
             HitboxManager::<Hurtboxes>::default(),
             GltfHitboxAutoGenTarget::Here,
+            HumanoidAccessories(shades.into_iter().collect()),
         ));
     }
 }
@@ -95,6 +131,63 @@ pub fn init_humanoid(
         .remove::<GrinUninit>();
 }
 
+/// Anything Grin is holding counts as melee. Projectiles aren't parented to him.
+pub fn charge_ultimate_on_melee(
+    mut charge: ResMut<UltimateCharge>,
+    grin_query: Query<Entity, With<Grin>>,
+    hitbox_query: Query<&Hitbox>,
+    parent_query: Query<&Parent>,
+    mut damage_events: EventReader<DamageEvent>,
+) {
+    let Ok(e_grin) = grin_query.get_single() else {
+        damage_events.clear();
+        return;
+    };
+    for event in damage_events.read() {
+        let DamageEvent::Contact {
+            e_damage,
+            e_hit,
+            absorbed: false,
+            ..
+        } = event
+        else {
+            continue;
+        };
+        let e_target = hitbox_query.get(*e_hit).map_or(*e_hit, |h| h.target);
+        if e_target != e_grin && parent_query.iter_ancestors(*e_damage).any(|e| e == e_grin) {
+            charge.add(GRIN_MELEE_HIT_CHARGE);
+        }
+    }
+}
+
+/// Blows up everything around him.
+pub fn grin_ultimate(
+    grin_query: Query<&GlobalTransform, With<Grin>>,
+    mut ultimate_events: EventReader<UltimateEvent>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+) {
+    for UltimateEvent { entity } in ultimate_events.read() {
+        let Ok(g_transform) = grin_query.get(*entity) else {
+            continue;
+        };
+        explosion_events.send(ExplosionEvent {
+            explosion: Explosion {
+                radius: GRIN_ULTIMATE_RADIUS,
+                base_damage: Damage {
+                    ty: DamageVariant::Ballistic,
+                    value: GRIN_ULTIMATE_DAMAGE,
+                    source: Some(*entity),
+                },
+                falloff: Falloff::Linear,
+                line_of_sight: true,
+            },
+            origin: g_transform.translation(),
+            groups: CollisionGroups::from_group_default(Group::PLAYER_PROJECTILE),
+            e_damage: Some(*entity),
+        });
+    }
+}
+
 #[derive(Component)]
 #[component(storage = "SparseSet")]
 pub struct ManualFreeze;
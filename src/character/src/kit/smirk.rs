@@ -1,15 +1,28 @@
-use bevy::prelude::*;
+use bevy::{ecs::schedule::SystemConfigs, prelude::*};
 use bevy_asset_loader::prelude::*;
 use grin_asset::AssetLoadState;
-use grin_damage::hitbox::{GltfHitboxAutoGenTarget, HitboxManager, Hurtboxes};
-use grin_render::sketched::SketchMaterial;
-use grin_rig::{
-    humanoid::{HumanoidBuild, HumanoidBundle, HumanoidDominantHand},
-    Idle,
+use grin_damage::{
+    health::{Dead, Invulnerable, Shield},
+    hitbox::{GltfHitboxAutoGenTarget, HitboxManager, Hurtboxes},
 };
+use grin_map::marker::SpawnPoint;
+use grin_physics::PhysicsTime;
+use grin_render::sketched::SketchMaterial;
+use grin_rig::humanoid::{HumanoidBuild, HumanoidBundle, HumanoidDominantHand};
 use grin_util::event::Spawnable;
 
-use crate::{Character, CharacterSet, GenericHumanoidCharacterPlugin, PlayerCharacter};
+use crate::{
+    ability::UltimateEvent, default_player_spawn, Character, CharacterSet,
+    GenericHumanoidCharacterPlugin, PlayerCharacter,
+};
+
+pub const SMIRK_SHIELD: f32 = 50.0;
+pub const SMIRK_SHIELD_RECHARGE_RATE: f32 = 10.0;
+pub const SMIRK_SHIELD_RECHARGE_DELAY: f32 = 3.0;
+/// Passive. Smirk's shield recharges this many times faster than it says.
+pub const SMIRK_SHIELD_RECHARGE_MULTIPLIER: f32 = 2.0;
+/// Seconds of `Invulnerable` from the ultimate.
+pub const SMIRK_ULTIMATE_SECS: f32 = 4.0;
 
 pub struct SmirkPlugin;
 
@@ -34,8 +47,6 @@ pub struct SmirkAssets {
     pub face: Handle<SketchMaterial>,
     #[asset(key = "rig.smirk")]
     pub rig: Handle<Scene>,
-    #[asset(key = "anim.idle")]
-    pub idle: Handle<AnimationClip>,
 }
 
 #[derive(Event, Clone, Default)]
@@ -49,6 +60,10 @@ pub struct SmirkUninit;
 
 impl Character for Smirk {
     type StartItem = grin_item::library::fist::Fist;
+
+    fn abilities() -> SystemConfigs {
+        (recharge_shield_faster, smirk_ultimate).into_configs()
+    }
 }
 
 impl Spawnable for Smirk {
@@ -58,6 +73,7 @@ impl Spawnable for Smirk {
 pub fn spawn(
     mut commands: Commands,
     assets: Res<SmirkAssets>,
+    spawn_point_query: Query<&SpawnPoint>,
     mut events: EventReader<<Smirk as Spawnable>::Event>,
 ) {
     for _ in events.read() {
@@ -69,14 +85,19 @@ pub fn spawn(
                 face: assets.face.clone().into(),
                 build: HumanoidBuild::Male,
                 dominant_hand: HumanoidDominantHand::Right,
-                spatial: SpatialBundle::from_transform(Transform::from_xyz(0.0, 1E-2, 0.0)),
+                spatial: SpatialBundle::from_transform(
+                    SpawnPoint::player(spawn_point_query.iter())
+                        .unwrap_or_else(default_player_spawn),
+                ),
                 ..Default::default()
             },
-            Idle {
-                clip: assets.idle.clone(),
-            },
             HitboxManager::<Hurtboxes>::default(),
             GltfHitboxAutoGenTarget::Here,
+            Shield::new(
+                SMIRK_SHIELD,
+                SMIRK_SHIELD_RECHARGE_RATE,
+                SMIRK_SHIELD_RECHARGE_DELAY,
+            ),
         ));
     }
 }
@@ -91,3 +112,33 @@ pub fn init_humanoid(mut commands: Commands, humanoid_query: Query<Entity, With<
         .insert(Smirk::default())
         .remove::<SmirkUninit>();
 }
+
+/// Tops up whatever `recharge_shields` already did.
+pub fn recharge_shield_faster(
+    time: Res<PhysicsTime>,
+    mut shield_query: Query<&mut Shield, (With<Smirk>, Without<Dead>)>,
+) {
+    for mut shield in shield_query.iter_mut() {
+        if shield.since_damaged >= shield.recharge_delay && shield.current < shield.max {
+            let bonus = shield.recharge_rate * (SMIRK_SHIELD_RECHARGE_MULTIPLIER - 1.0);
+            shield.current = (shield.current + bonus * time.0.delta_seconds()).min(shield.max);
+        }
+    }
+}
+
+/// Refills the shield and goes invulnerable for a bit.
+pub fn smirk_ultimate(
+    mut commands: Commands,
+    mut smirk_query: Query<&mut Shield, With<Smirk>>,
+    mut ultimate_events: EventReader<UltimateEvent>,
+) {
+    for UltimateEvent { entity } in ultimate_events.read() {
+        let Ok(mut shield) = smirk_query.get_mut(*entity) else {
+            continue;
+        };
+        shield.current = shield.max;
+        commands
+            .entity(*entity)
+            .insert(Invulnerable::from_seconds(SMIRK_ULTIMATE_SECS));
+    }
+}
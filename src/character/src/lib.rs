@@ -1,25 +1,67 @@
+pub mod ability;
+pub mod hud;
 pub mod kit;
+pub mod menu;
 
 use std::marker::PhantomData;
 
-use bevy::{app::PluginGroupBuilder, prelude::*, render::view::RenderLayers};
+use bevy::{
+    app::PluginGroupBuilder, ecs::schedule::SystemConfigs, input::mouse::MouseWheel, prelude::*,
+    render::view::RenderLayers,
+};
 use bevy_rapier3d::prelude::*;
 use grin_asset::AssetLoadState;
-use grin_damage::health::{Health, HealthBundle};
-use grin_input::camera::{CameraAlignment, LookInfo, PlayerCamera, PlayerCameraPlugin};
-use grin_item::{equip::Equipped, mechanics::util::InputHandler, spawn::ItemSpawnEvent};
-use grin_physics::{CollisionGroupExt, CollisionGroupsExt, PhysicsTime};
+use grin_damage::{
+    faction::Faction,
+    feedback::DamageNumberEvent,
+    health::{Dead, Health, HealthBundle, Invulnerable},
+    status::{Disoriented, Slowed},
+};
+use grin_dialogue::{ActiveDialogue, DialogueEvent, DialogueMap};
+use grin_input::{
+    action::InputAction,
+    camera::{
+        input_toggle_camera_alignment, CameraAlignment, LookInfo, PlayerCamera, PlayerCameraPlugin,
+    },
+    interact::{
+        InteractEvent, InteractPlugin, InteractSet, Interactable, InteractionKind,
+        InteractionsBlocked, Interactor,
+    },
+};
+use grin_item::{
+    equip::Equipped,
+    inventory::{Inventory, SwitchItemEvent, SwitchTarget},
+    mechanics::util::InputHandler,
+    spawn::ItemSpawnEvent,
+};
+use grin_map::{marker::SpawnPoint, MapLoadState};
+use grin_physics::{
+    platform::{Carriable, DropThrough},
+    volume::{update_movement_modes, LadderDismount, MovementMode},
+    CollisionGroupExt, CollisionGroupsExt, PhysicsTime,
+};
 use grin_render::{
+    feedback::{AddTraumaEvent, Wobble},
     gopro::{add_gopro, GoProSettings},
     RenderLayer,
 };
-use grin_rig::humanoid::{Dash, Humanoid, HumanoidRace, HUMANOID_HEIGHT, HUMANOID_RADIUS};
-use grin_util::{event::Spawnable, vectors::Vec3Ext};
+use grin_rig::{
+    footstep::StrideFootsteps,
+    humanoid::{Dash, Humanoid, HumanoidRace, HUMANOID_HEIGHT},
+};
+use grin_time::{global_rewind_active, RewindExempt};
+use grin_util::{event::Spawnable, state::GameState, vectors::Vec3Ext};
 
+use ability::{input_ultimate, player_is, AbilityPlugin};
+use hud::{HudPlugin, STATUS_VIEWPORT_SIZE};
 use kit::{grin::GrinPlugin, smirk::SmirkPlugin};
+use menu::GameStatePlugin;
 
 pub const CHARACTER_WALKSPEED: f32 = 6.0;
 
+/// Screen shake trauma per point of damage the player takes.
+pub const PLAYER_DAMAGE_TRAUMA: f32 = 0.02;
+
 pub struct MasterCharacterPlugin;
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
@@ -48,6 +90,7 @@ impl Plugin for MasterCharacterPlugin {
                 ),
             )
             .add_systems(PostUpdate, init_character_model.in_set(CharacterSet::Init))
+            .add_systems(OnEnter(MapLoadState::Success), move_player_to_spawn_point)
             .add_systems(
                 Update,
                 apply_deferred
@@ -58,15 +101,41 @@ impl Plugin for MasterCharacterPlugin {
                 Update,
                 set_avatar_load_state_on_humanoid_load.in_set(CharacterSet::Load),
             )
+            // in case `DialoguePlugin` isn't around
+            .add_event::<DialogueEvent>()
+            .add_plugins((InteractPlugin, HudPlugin, GameStatePlugin, AbilityPlugin))
+            .add_systems(
+                Update,
+                (
+                    block_interactions_during_dialogue.before(InteractSet),
+                    say_dialogue_on_interact.after(InteractSet),
+                ),
+            )
             .add_systems(OnEnter(AvatarLoadState::Loaded), insert_status_viewport)
             .add_systems(
                 Update,
                 (
-                    input_walk,
+                    interrupt_dialogue_on_death,
+                    interrupt_dialogue_on_disoriented,
+                    shake_on_player_damage,
+                    wobble_when_disoriented,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    input_walk.after(update_movement_modes),
                     input_dash.before(grin_rig::humanoid::dash),
+                    input_drop_through,
+                    input_ladder_dismount,
+                    input_switch_items,
                     enable_input_for_player_items,
+                    input_ultimate,
+                    input_toggle_camera_alignment,
                 )
-                    .run_if(in_state(AvatarLoadState::Loaded)),
+                    .run_if(in_state(AvatarLoadState::Loaded))
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(not(global_rewind_active)),
             );
     }
 }
@@ -88,7 +157,8 @@ impl<T: Character> Plugin for GenericHumanoidCharacterPlugin<T> {
         app.add_systems(
             OnEnter(AvatarLoadState::Loaded),
             equip_spawn_item_on_humanoid_load::<T>.in_set(CharacterSet::Load),
-        );
+        )
+        .add_systems(Update, T::abilities().run_if(player_is::<T>));
     }
 }
 
@@ -128,9 +198,6 @@ pub fn enable_input_for_player_items(
     }
 }
 
-// TODO: disable for unequip! what's a good way to do this?
-// will probably need to keep track of dropped items in a vec somewhere.
-
 pub struct CharacterPlugins;
 
 impl PluginGroup for CharacterPlugins {
@@ -144,11 +211,33 @@ impl PluginGroup for CharacterPlugins {
 
 pub trait Character: Component + Sized + Spawnable {
     type StartItem: Component;
+
+    /// Passive and ultimate systems. They only run while the player is this kit.
+    fn abilities() -> SystemConfigs;
 }
 
 #[derive(Component, Default)]
 pub struct PlayerCharacter;
 
+/// Where the player spawns if the map doesn't have a `Spawn.Player`.
+pub fn default_player_spawn() -> Transform {
+    Transform::from_xyz(0.0, 1E-2, 0.0)
+}
+
+/// The player usually spawns before the map's done loading, so this puts them on the map's
+/// `Spawn.Player` once it is.
+pub fn move_player_to_spawn_point(
+    mut player_query: Query<&mut Transform, With<PlayerCharacter>>,
+    spawn_point_query: Query<&SpawnPoint>,
+) {
+    let Some(transform) = SpawnPoint::player(spawn_point_query.iter()) else {
+        return;
+    };
+    for mut player_transform in player_query.iter_mut() {
+        *player_transform = transform;
+    }
+}
+
 #[derive(Component, Copy, Clone, Default)]
 pub struct Player;
 
@@ -165,31 +254,27 @@ pub fn init_character_model(
         return;
     };
 
-    // PLACEHOLDERS
-    let left = commands.spawn_empty().id();
-    let right = commands.spawn_empty().id();
-
+    let equipped = Equipped::empty(&mut commands);
     commands.entity(e_humanoid).insert((
         Player,
         HealthBundle {
             health: Health(100.0),
             ..Default::default()
         },
-        Equipped { left, right },
+        Faction::Player,
+        equipped,
+        Inventory::default(),
         RigidBody::KinematicPositionBased,
         Velocity::default(),
+        RewindExempt,
+        Carriable,
+        Interactor,
+        MovementMode::default(),
+        StrideFootsteps::default(),
         CollisionGroups::from_group_default(Group::PLAYER),
         KinematicCharacterController {
             custom_shape: Some((
-                match race {
-                    HumanoidRace::Round => Collider::capsule_y(
-                        HUMANOID_HEIGHT / 2.0 - HUMANOID_RADIUS,
-                        HUMANOID_RADIUS,
-                    ),
-                    HumanoidRace::Square => {
-                        Collider::cuboid(HUMANOID_RADIUS, HUMANOID_HEIGHT / 2.0, HUMANOID_RADIUS)
-                    }
-                },
+                race.controller_shape(),
                 Vec3::Y * HUMANOID_HEIGHT / 2.0,
                 Quat::default(),
             )),
@@ -217,6 +302,10 @@ pub fn init_character_model(
     }
 }
 
+/// Shows the player's face in the bottom left.
+#[derive(Component)]
+pub struct StatusViewport;
+
 pub fn insert_status_viewport(
     mut commands: Commands,
     mut images: ResMut<Assets<Image>>,
@@ -231,55 +320,137 @@ pub fn insert_status_viewport(
             entity: e_avatar,
             transform: Transform::from_translation(Vec3::new(0.0, 2.125, -2.0))
                 .looking_to(Vec3::Z, Vec3::Y),
-            size: UVec2::splat(240),
+            size: UVec2::splat(STATUS_VIEWPORT_SIZE as u32),
             render_layers: RenderLayers::layer(RenderLayer::AVATAR as u8),
+            exclusive_layer: false,
         },
     );
 
-    commands.spawn(ImageBundle {
-        image: image.into(),
-        style: Style {
-            position_type: PositionType::Absolute,
-            bottom: Val::Percent(0.0),
-            left: Val::Percent(0.0),
-            width: Val::Px(240.0),
-            height: Val::Px(240.0),
+    commands.spawn((
+        StatusViewport,
+        ImageBundle {
+            image: image.into(),
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Percent(0.0),
+                left: Val::Percent(0.0),
+                width: Val::Px(STATUS_VIEWPORT_SIZE),
+                height: Val::Px(STATUS_VIEWPORT_SIZE),
+                ..Default::default()
+            },
             ..Default::default()
         },
-        ..Default::default()
-    });
+    ));
+}
+
+/// Dead men tell no tales.
+pub fn interrupt_dialogue_on_death(
+    player_query: Query<(), (With<PlayerCharacter>, Added<Dead>)>,
+    mut events: EventWriter<DialogueEvent>,
+) {
+    if !player_query.is_empty() {
+        events.send(DialogueEvent::Interrupt);
+    }
+}
+
+/// Hard to keep talking with your ears ringing.
+pub fn interrupt_dialogue_on_disoriented(
+    player_query: Query<(), (With<PlayerCharacter>, Added<Disoriented>)>,
+    active: Option<Res<ActiveDialogue>>,
+    mut events: EventWriter<DialogueEvent>,
+) {
+    let talking = active.map_or(false, |active| active.id.is_some());
+    if talking && !player_query.is_empty() {
+        events.send(DialogueEvent::Interrupt);
+    }
+}
+
+/// The camera wobbles while the player is `Disoriented`.
+pub fn wobble_when_disoriented(
+    player_query: Query<(), (With<PlayerCharacter>, With<Disoriented>)>,
+    mut wobble: ResMut<Wobble>,
+) {
+    wobble.target = if player_query.is_empty() { 0.0 } else { 1.0 };
+}
+
+/// Getting hit shakes the screen. Bigger hits shake it harder.
+pub fn shake_on_player_damage(
+    player_query: Query<(), With<PlayerCharacter>>,
+    mut damage_events: EventReader<DamageNumberEvent>,
+    mut trauma_events: EventWriter<AddTraumaEvent>,
+) {
+    for DamageNumberEvent { amount, target, .. } in damage_events.read() {
+        if player_query.contains(*target) {
+            trauma_events.send(AddTraumaEvent(amount * PLAYER_DAMAGE_TRAUMA));
+        }
+    }
 }
 
 pub fn input_walk(
-    input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ButtonInput<InputAction>>,
     camera_query: Query<(&GlobalTransform, &PlayerCamera), Without<PlayerCharacter>>,
     mut character: Query<
-        (&mut KinematicCharacterController, &mut Transform),
+        (
+            &mut KinematicCharacterController,
+            &mut Transform,
+            Option<&Slowed>,
+            Option<&Disoriented>,
+            Option<&MovementMode>,
+        ),
         (With<PlayerCharacter>, Without<Dash>),
     >,
     look_info: Res<LookInfo>,
     time: Res<PhysicsTime>,
 ) {
-    if let Ok((mut char_controller, mut transform)) = character.get_single_mut() {
+    if let Ok((mut char_controller, mut transform, slowed, disoriented, mode)) =
+        character.get_single_mut()
+    {
         let (cam_transform, camera) = camera_query.single();
+        let mode = mode.copied().unwrap_or_default();
 
         let mut movement = Vec3::ZERO;
-        if input.pressed(KeyCode::KeyW) {
-            movement += cam_transform.forward().xz_flat();
-        }
-        if input.pressed(KeyCode::KeyA) {
-            movement += cam_transform.left().xz_flat();
-        }
-        if input.pressed(KeyCode::KeyS) {
-            movement += cam_transform.back().xz_flat();
+        match mode {
+            MovementMode::Climbing { axis } => {
+                // strafing off of a ladder isn't a thing, you have to jump
+                if actions.pressed(InputAction::MoveForward) {
+                    movement += axis;
+                }
+                if actions.pressed(InputAction::MoveBack) {
+                    movement -= axis;
+                }
+            }
+            MovementMode::Walking | MovementMode::Swimming { .. } => {
+                if actions.pressed(InputAction::MoveForward) {
+                    movement += cam_transform.forward().xz_flat();
+                }
+                if actions.pressed(InputAction::MoveLeft) {
+                    movement += cam_transform.left().xz_flat();
+                }
+                if actions.pressed(InputAction::MoveBack) {
+                    movement += cam_transform.back().xz_flat();
+                }
+                if actions.pressed(InputAction::MoveRight) {
+                    movement += cam_transform.right().xz_flat();
+                }
+            }
         }
-        if input.pressed(KeyCode::KeyD) {
-            movement += cam_transform.right().xz_flat();
+        if let MovementMode::Swimming { .. } = mode {
+            if actions.pressed(InputAction::Jump) {
+                movement += Vec3::Y;
+            }
+            if actions.pressed(InputAction::Crouch) {
+                movement -= Vec3::Y;
+            }
         }
 
         char_controller.translation = Some(
             char_controller.translation.unwrap_or_default()
-                + movement.normalize_or_zero() * CHARACTER_WALKSPEED * time.0.delta_seconds(),
+                + movement.normalize_or_zero()
+                    * CHARACTER_WALKSPEED
+                    * mode.speed_scale()
+                    * slowed.map_or(1.0, |s| s.0)
+                    * disoriented.map_or(1.0, |d| d.0)
+                    * time.0.delta_seconds(),
         );
         match camera.alignment {
             CameraAlignment::FortyFive => {
@@ -298,20 +469,126 @@ pub fn input_walk(
     }
 }
 
+/// Says `InteractionKind::Dialogue` through the `DialogueMap`.
+pub fn say_dialogue_on_interact(
+    interactable_query: Query<&Interactable>,
+    dialogue_map: Option<Res<DialogueMap>>,
+    mut interact_events: EventReader<InteractEvent>,
+    mut dialogue_events: EventWriter<DialogueEvent>,
+) {
+    for &InteractEvent { target, .. } in interact_events.read() {
+        let Ok(Interactable {
+            kind: InteractionKind::Dialogue(id),
+            ..
+        }) = interactable_query.get(target)
+        else {
+            continue;
+        };
+
+        match dialogue_map.as_ref().and_then(|m| m.0.get(id)) {
+            Some(dialogue) => {
+                dialogue_events.send(DialogueEvent::Say(dialogue.clone()));
+            }
+            None => warn!("Dialogue \"{}\" doesn't exist.", id),
+        }
+    }
+}
+
+/// Keeps the interaction prompt out of the way of the dialogue window.
+pub fn block_interactions_during_dialogue(
+    active: Option<Res<ActiveDialogue>>,
+    mut blocked: ResMut<InteractionsBlocked>,
+) {
+    let talking = active.is_some_and(|a| a.id.is_some());
+    if blocked.0 != talking {
+        blocked.0 = talking;
+    }
+}
+
+/// Number keys for each inventory slot, in order.
+const SLOT_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+/// Scroll wheel cycles items, number keys pick a slot.
+pub fn input_switch_items(
+    character: Query<Entity, (With<PlayerCharacter>, With<Inventory>)>,
+    input: Res<ButtonInput<KeyCode>>,
+    mut wheel_events: EventReader<MouseWheel>,
+    mut switch_events: EventWriter<SwitchItemEvent>,
+) {
+    let scroll = wheel_events.read().map(|ev| ev.y).sum::<f32>();
+    let Ok(entity) = character.get_single() else {
+        return;
+    };
+
+    let target = if let Some(slot) = SLOT_KEYS.iter().position(|k| input.just_pressed(*k)) {
+        SwitchTarget::Slot(slot)
+    } else if scroll > 0.0 {
+        SwitchTarget::Previous
+    } else if scroll < 0.0 {
+        SwitchTarget::Next
+    } else {
+        return;
+    };
+    switch_events.send(SwitchItemEvent { entity, target });
+}
+
+/// Seconds of invulnerability when starting a dash.
+pub const DASH_IFRAMES: f32 = 0.2;
+
+/// Seconds that a dash lasts underwater.
+pub const SWIM_LUNGE_SECS: f32 = 0.1;
+
 pub fn input_dash(
     mut commands: Commands,
-    character: Query<(Entity, &Velocity), With<PlayerCharacter>>,
-    input: Res<ButtonInput<KeyCode>>,
+    character: Query<
+        (
+            Entity,
+            &Velocity,
+            Option<&Invulnerable>,
+            Option<&MovementMode>,
+        ),
+        With<PlayerCharacter>,
+    >,
+    actions: Res<ButtonInput<InputAction>>,
     mut cooldown: Local<f32>,
     time: Res<Time>,
 ) {
     if *cooldown <= 0.0 {
-        if input.pressed(KeyCode::ShiftLeft) {
-            let (entity, velocity) = character.single();
-            commands.entity(entity).insert(Dash {
-                velocity: velocity.linvel * 2.0,
-                time: 0.2,
-            });
+        if actions.pressed(InputAction::Dash) {
+            let (entity, velocity, invulnerable, mode) = character.single();
+            let dash = match mode.copied().unwrap_or_default() {
+                MovementMode::Walking => Dash {
+                    velocity: velocity.linvel * 2.0,
+                    time: 0.2,
+                },
+                MovementMode::Swimming { drag, .. } => Dash {
+                    velocity: velocity.linvel * 2.0 * (1.0 - drag).max(0.0),
+                    time: SWIM_LUNGE_SECS,
+                },
+                // can't dash on a ladder
+                MovementMode::Climbing { .. } => return,
+            };
+            commands.entity(entity).insert(dash);
+            // don't cut short a longer invulnerability
+            if invulnerable.map_or(true, |i| {
+                i.timer
+                    .as_ref()
+                    .is_some_and(|t| t.remaining_secs() < DASH_IFRAMES)
+            }) {
+                commands
+                    .entity(entity)
+                    .insert(Invulnerable::from_seconds(DASH_IFRAMES));
+            }
         }
         *cooldown = 0.4;
     } else {
@@ -319,6 +596,33 @@ pub fn input_dash(
     }
 }
 
+/// Back+jump drops through `OneWayPlatform`s.
+pub fn input_drop_through(
+    mut commands: Commands,
+    character: Query<Entity, (With<PlayerCharacter>, Without<DropThrough>)>,
+    actions: Res<ButtonInput<InputAction>>,
+) {
+    if actions.pressed(InputAction::MoveBack) && actions.just_pressed(InputAction::Jump) {
+        if let Ok(entity) = character.get_single() {
+            commands.entity(entity).insert(DropThrough::default());
+        }
+    }
+}
+
+/// Jumping gets off of ladders.
+pub fn input_ladder_dismount(
+    mut commands: Commands,
+    character: Query<(Entity, &MovementMode), (With<PlayerCharacter>, Without<LadderDismount>)>,
+    actions: Res<ButtonInput<InputAction>>,
+) {
+    if !actions.just_pressed(InputAction::Jump) {
+        return;
+    }
+    if let Ok((entity, MovementMode::Climbing { .. })) = character.get_single() {
+        commands.entity(entity).insert(LadderDismount::default());
+    }
+}
+
 #[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
 pub enum AvatarLoadState {
     #[default]
@@ -0,0 +1,337 @@
+//! Pausing and restarting.
+
+use bevy::{app::AppExit, prelude::*, window::CursorGrabMode};
+use bevy_rapier3d::prelude::*;
+use grin_damage::health::Dead;
+use grin_input::{
+    action::InputAction,
+    camera::{CameraAlignment, PlayerCamera},
+};
+use grin_util::state::GameState;
+
+use crate::{AvatarLoadState, PlayerCharacter, StatusViewport};
+
+const BUTTON_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.5);
+const BUTTON_HOVER_COLOR: Color = Color::rgba(0.2, 0.2, 0.2, 0.5);
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .add_event::<RespawnPlayerEvent>()
+            .add_systems(Startup, (spawn_pause_menu, spawn_restart_prompt))
+            .add_systems(
+                OnEnter(GameState::Paused),
+                (pause_world, show_menu::<PauseMenu>),
+            )
+            .add_systems(
+                OnExit(GameState::Paused),
+                (resume_world, hide_menu::<PauseMenu>),
+            )
+            .add_systems(OnEnter(GameState::Dead), show_menu::<RestartPrompt>)
+            .add_systems(OnExit(GameState::Dead), hide_menu::<RestartPrompt>)
+            .add_systems(
+                Update,
+                (
+                    input_pause,
+                    enter_dead_state.run_if(in_state(GameState::Playing)),
+                    press_pause_menu_buttons.run_if(in_state(GameState::Paused)),
+                    input_restart.run_if(in_state(GameState::Dead)),
+                ),
+            );
+    }
+}
+
+/// Sent when the player asks to restart. Whatever spawned the player should spawn it again.
+#[derive(Event, Clone, Copy, Debug, Default)]
+pub struct RespawnPlayerEvent;
+
+#[derive(Component)]
+pub struct PauseMenu;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseMenuButton {
+    Resume,
+    Quit,
+}
+
+#[derive(Component)]
+pub struct RestartPrompt;
+
+fn menu_text(value: &str, font_size: f32) -> TextBundle {
+    TextBundle::from_section(
+        value,
+        TextStyle {
+            font_size,
+            color: Color::WHITE,
+            ..Default::default()
+        },
+    )
+}
+
+pub fn spawn_pause_menu(mut commands: Commands) {
+    commands
+        .spawn((
+            PauseMenu,
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(16.0),
+                    ..Default::default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(menu_text("PAUSED", 64.0));
+            for (button, label) in [
+                (PauseMenuButton::Resume, "Resume"),
+                (PauseMenuButton::Quit, "Quit"),
+            ] {
+                parent
+                    .spawn((
+                        button,
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(200.0),
+                                padding: UiRect::all(Val::Px(8.0)),
+                                justify_content: JustifyContent::Center,
+                                ..Default::default()
+                            },
+                            background_color: BUTTON_COLOR.into(),
+                            ..Default::default()
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(menu_text(label, 32.0));
+                    });
+            }
+        });
+}
+
+pub fn spawn_restart_prompt(mut commands: Commands) {
+    commands.spawn((
+        RestartPrompt,
+        TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                top: Val::Percent(55.0),
+                width: Val::Percent(100.0),
+                ..Default::default()
+            },
+            ..menu_text("Press Enter to restart", 32.0).with_text_justify(JustifyText::Center)
+        },
+    ));
+}
+
+pub fn show_menu<T: Component>(mut menu_query: Query<&mut Style, With<T>>) {
+    for mut style in menu_query.iter_mut() {
+        style.display = Display::Flex;
+    }
+}
+
+pub fn hide_menu<T: Component>(mut menu_query: Query<&mut Style, With<T>>) {
+    for mut style in menu_query.iter_mut() {
+        style.display = Display::None;
+    }
+}
+
+/// Toggles between `Playing` and `Paused`. Does nothing while `Dead`.
+pub fn input_pause(
+    actions: Res<ButtonInput<InputAction>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !actions.just_pressed(InputAction::Pause) {
+        return;
+    }
+
+    match state.get() {
+        GameState::Playing => next_state.set(GameState::Paused),
+        GameState::Paused => next_state.set(GameState::Playing),
+        GameState::Dead => (),
+    }
+}
+
+/// Stops physics and virtual time, which covers `PhysicsTime`, `FixedUpdate` and anything
+/// going off of those. Also lets go of the cursor.
+pub fn pause_world(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut time: ResMut<Time<Virtual>>,
+    mut window_query: Query<&mut Window>,
+) {
+    rapier_config.physics_pipeline_active = false;
+    time.pause();
+    for mut window in window_query.iter_mut() {
+        window.cursor.grab_mode = CursorGrabMode::None;
+        window.cursor.visible = true;
+    }
+}
+
+/// Gives the cursor back to the `PlayerCamera`.
+pub fn resume_world(
+    mut rapier_config: ResMut<RapierConfiguration>,
+    mut time: ResMut<Time<Virtual>>,
+    camera_query: Query<&PlayerCamera>,
+    mut window_query: Query<&mut Window>,
+) {
+    rapier_config.physics_pipeline_active = true;
+    time.unpause();
+    let alignment = camera_query
+        .get_single()
+        .map_or(CameraAlignment::SHOOTER, |c| c.alignment);
+    for mut window in window_query.iter_mut() {
+        window.cursor.grab_mode = alignment.cursor_grab_mode();
+        window.cursor.visible = alignment.cursor_visible();
+    }
+}
+
+pub fn press_pause_menu_buttons(
+    mut button_query: Query<
+        (&Interaction, &PauseMenuButton, &mut BackgroundColor),
+        Changed<Interaction>,
+    >,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut exit_events: EventWriter<AppExit>,
+) {
+    for (interaction, button, mut color) in button_query.iter_mut() {
+        color.0 = match interaction {
+            Interaction::Hovered => BUTTON_HOVER_COLOR,
+            _ => BUTTON_COLOR,
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            PauseMenuButton::Resume => next_state.set(GameState::Playing),
+            PauseMenuButton::Quit => {
+                exit_events.send(AppExit);
+            }
+        }
+    }
+}
+
+pub fn enter_dead_state(
+    player_query: Query<(), (With<PlayerCharacter>, With<Dead>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !player_query.is_empty() {
+        next_state.set(GameState::Dead);
+    }
+}
+
+/// Throws out the player, along with its camera and status viewport, and asks for a new one
+/// with `RespawnPlayerEvent`.
+pub fn input_restart(
+    mut commands: Commands,
+    actions: Res<ButtonInput<InputAction>>,
+    player_query: Query<Entity, With<PlayerCharacter>>,
+    camera_query: Query<Entity, With<PlayerCamera>>,
+    viewport_query: Query<Entity, With<StatusViewport>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_avatar_state: ResMut<NextState<AvatarLoadState>>,
+    mut respawn_events: EventWriter<RespawnPlayerEvent>,
+) {
+    if !actions.just_pressed(InputAction::Confirm) {
+        return;
+    }
+
+    for entity in player_query
+        .iter()
+        .chain(camera_query.iter())
+        .chain(viewport_query.iter())
+    {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    next_avatar_state.set(AvatarLoadState::NotLoaded);
+    next_state.set(GameState::Playing);
+    respawn_events.send(RespawnPlayerEvent);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::time::TimePlugin;
+
+    use super::*;
+
+    fn press(app: &mut App, action: InputAction) {
+        let mut actions = app.world.resource_mut::<ButtonInput<InputAction>>();
+        actions.release_all();
+        actions.clear();
+        actions.press(action);
+        // one frame to set the state, one to enter it
+        app.update();
+        app.update();
+    }
+
+    fn state(app: &App) -> GameState {
+        *app.world.resource::<State<GameState>>().get()
+    }
+
+    #[test]
+    fn pause_and_restart() {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(RapierConfiguration::new(1.0))
+            .init_state::<AvatarLoadState>()
+            .init_resource::<ButtonInput<InputAction>>()
+            .add_event::<AppExit>()
+            .add_plugins(GameStatePlugin);
+        app.update();
+
+        press(&mut app, InputAction::Pause);
+        assert_eq!(state(&app), GameState::Paused, "Didn't pause.");
+        assert!(
+            !app.world
+                .resource::<RapierConfiguration>()
+                .physics_pipeline_active,
+            "Physics didn't stop."
+        );
+        assert!(
+            app.world.resource::<Time<Virtual>>().is_paused(),
+            "Time didn't stop."
+        );
+
+        press(&mut app, InputAction::Pause);
+        assert_eq!(state(&app), GameState::Playing, "Didn't unpause.");
+        assert!(
+            app.world
+                .resource::<RapierConfiguration>()
+                .physics_pipeline_active
+                && !app.world.resource::<Time<Virtual>>().is_paused(),
+            "World didn't resume."
+        );
+
+        let e_player = app.world.spawn((PlayerCharacter, Dead)).id();
+        app.update();
+        app.update();
+        assert_eq!(state(&app), GameState::Dead, "Didn't die.");
+
+        press(&mut app, InputAction::Pause);
+        assert_eq!(state(&app), GameState::Dead, "Paused while dead.");
+
+        press(&mut app, InputAction::Confirm);
+        assert_eq!(state(&app), GameState::Playing, "Didn't restart.");
+        assert!(
+            app.world.get_entity(e_player).is_none(),
+            "Old player wasn't despawned."
+        );
+        let respawns = app.world.resource::<Events<RespawnPlayerEvent>>();
+        assert_eq!(
+            respawns.get_reader().read(respawns).count(),
+            1,
+            "Player wasn't respawned."
+        );
+    }
+}
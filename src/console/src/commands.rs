@@ -0,0 +1,312 @@
+//! The commands that come with the console.
+
+use bevy::prelude::*;
+use grin_character::PlayerCharacter;
+use grin_damage::health::Health;
+use grin_dialogue::{DialogueEvent, DialogueMap};
+use grin_item::{library::plugin::ItemIdentifier, spawn::UntypedItemSpawnEvent};
+use grin_time::{scaling::GlobalTimeScale, GlobalRewindEvent};
+use grin_util::event::{SpawnByNameEvent, SpawnRegistry};
+use itertools::Itertools;
+
+use crate::{expect_args, parse_arg, Console, ConsoleCommand, ConsoleCommands, ConsoleError};
+
+fn player(world: &mut World) -> Result<Entity, ConsoleError> {
+    world
+        .query_filtered::<Entity, With<PlayerCharacter>>()
+        .get_single(world)
+        .map_err(|_| ConsoleError::Failed("There's no player.".into()))
+}
+
+pub struct HelpCommand;
+
+impl ConsoleCommand for HelpCommand {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn description(&self) -> &'static str {
+        "Lists the commands."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 0)?;
+        let help = world
+            .resource::<ConsoleCommands>()
+            .iter()
+            .map(|command| {
+                format!(
+                    "{} {} - {}",
+                    command.name(),
+                    command.usage(),
+                    command.description()
+                )
+            })
+            .join("\n");
+        Ok(Some(help))
+    }
+}
+
+pub struct ClearCommand;
+
+impl ConsoleCommand for ClearCommand {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn description(&self) -> &'static str {
+        "Clears the output."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 0)?;
+        world.resource_mut::<Console>().clear();
+        Ok(None)
+    }
+}
+
+/// Goes through the `SpawnRegistry`.
+pub struct SpawnCommand;
+
+impl ConsoleCommand for SpawnCommand {
+    fn name(&self) -> &'static str {
+        "spawn"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<name> <x> <y> <z>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Spawns something by name."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 4)?;
+        let name = args[0];
+        let translation = Vec3::new(
+            parse_arg(args, 1)?,
+            parse_arg(args, 2)?,
+            parse_arg(args, 3)?,
+        );
+
+        let Some(registry) = world.get_resource::<SpawnRegistry>() else {
+            return Err(ConsoleError::Failed("Nothing can be spawned.".into()));
+        };
+        if !registry.spawn_fns.contains_key(name) {
+            return Err(ConsoleError::Failed(format!(
+                "Nothing called `{}` to spawn. Known: {}.",
+                name,
+                registry.names().join(", "),
+            )));
+        }
+
+        world.send_event(SpawnByNameEvent {
+            name: name.to_owned(),
+            transform: Transform::from_translation(translation),
+        });
+        Ok(Some(format!("Spawned {} at {}.", name, translation)))
+    }
+}
+
+/// Equips an item on the player.
+pub struct GiveCommand;
+
+impl ConsoleCommand for GiveCommand {
+    fn name(&self) -> &'static str {
+        "give"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<item>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Gives the player an item."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 1)?;
+        let Some(identifier) = ItemIdentifier::from_name(args[0]) else {
+            return Err(ConsoleError::Failed(format!(
+                "No item called `{}`. Known: {}.",
+                args[0],
+                ItemIdentifier::ALL
+                    .iter()
+                    .map(ItemIdentifier::name)
+                    .join(", "),
+            )));
+        };
+        let e_player = player(world)?;
+
+        world.send_event(UntypedItemSpawnEvent {
+            identifier,
+            parent_entity: Some(e_player),
+            transform: Transform::default(),
+        });
+        Ok(Some(format!("Gave {}.", identifier.name())))
+    }
+}
+
+pub struct SetHealthCommand;
+
+impl ConsoleCommand for SetHealthCommand {
+    fn name(&self) -> &'static str {
+        "sethealth"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<health>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Sets the player's health."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 1)?;
+        let health: f32 = parse_arg(args, 0)?;
+        if health <= 0.0 {
+            return Err(ConsoleError::Failed("Health has to be positive.".into()));
+        }
+        let e_player = player(world)?;
+
+        let Some(mut player_health) = world.get_mut::<Health>(e_player) else {
+            return Err(ConsoleError::Failed("The player has no health.".into()));
+        };
+        player_health.0 = health;
+        Ok(None)
+    }
+}
+
+/// Sets the `GlobalTimeScale`.
+pub struct TimeScaleCommand;
+
+impl ConsoleCommand for TimeScaleCommand {
+    fn name(&self) -> &'static str {
+        "timescale"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<scale>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Speeds up or slows down time."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 1)?;
+        let scale: f32 = parse_arg(args, 0)?;
+        if scale <= 0.0 {
+            return Err(ConsoleError::Failed(
+                "Time scale has to be positive.".into(),
+            ));
+        }
+
+        let Some(mut time_scale) = world.get_resource_mut::<GlobalTimeScale>() else {
+            return Err(ConsoleError::Failed("There's no time scaling.".into()));
+        };
+        time_scale.scale = scale;
+        Ok(None)
+    }
+}
+
+pub struct RewindCommand;
+
+impl ConsoleCommand for RewindCommand {
+    fn name(&self) -> &'static str {
+        "rewind"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<seconds>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Rewinds everything."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 1)?;
+        let seconds: f32 = parse_arg(args, 0)?;
+        if seconds <= 0.0 {
+            return Err(ConsoleError::Failed("Can't rewind into the future.".into()));
+        }
+
+        world.send_event(GlobalRewindEvent { seconds });
+        Ok(None)
+    }
+}
+
+pub struct DialogueCommand;
+
+impl ConsoleCommand for DialogueCommand {
+    fn name(&self) -> &'static str {
+        "dialogue"
+    }
+
+    fn usage(&self) -> &'static str {
+        "<id>"
+    }
+
+    fn description(&self) -> &'static str {
+        "Starts a conversation."
+    }
+
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+        expect_args(args, 1)?;
+        let Some(dialogue_map) = world.get_resource::<DialogueMap>() else {
+            return Err(ConsoleError::Failed("Dialogue isn't loaded.".into()));
+        };
+        let Some(h_dialogue) = dialogue_map.0.get(args[0]).cloned() else {
+            return Err(ConsoleError::Failed(format!(
+                "No dialogue called `{}`.",
+                args[0]
+            )));
+        };
+
+        world.send_event(DialogueEvent::Say(h_dialogue));
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{execute, ConsoleAppExt, ConsoleLineKind};
+
+    use super::*;
+
+    #[test]
+    fn set_health() {
+        let mut app = App::new();
+        app.init_resource::<Console>()
+            .add_console_command(SetHealthCommand);
+
+        execute(&mut app.world, "sethealth 20");
+        assert!(
+            app.world
+                .resource::<Console>()
+                .lines()
+                .last()
+                .map(|line| line.kind)
+                == Some(ConsoleLineKind::Error),
+            "Set health without a player."
+        );
+
+        let e_player = app.world.spawn((PlayerCharacter, Health(100.0))).id();
+        execute(&mut app.world, "sethealth 20");
+        assert_eq!(
+            app.world.get::<Health>(e_player).unwrap().0,
+            20.0,
+            "Didn't set health."
+        );
+
+        execute(&mut app.world, "sethealth -5");
+        assert_eq!(
+            app.world.get::<Health>(e_player).unwrap().0,
+            20.0,
+            "Set health below zero."
+        );
+    }
+}
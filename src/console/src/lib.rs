@@ -0,0 +1,656 @@
+//! Drop-down debug console. Backtick opens it.
+//!
+//! Lines are split on whitespace and the first word picks the `ConsoleCommand`. Other crates can
+//! add their own with `ConsoleAppExt::add_console_command`.
+
+pub mod commands;
+
+use std::{collections::VecDeque, str::FromStr, sync::Arc};
+
+use bevy::{
+    input::{keyboard::KeyboardInput, mouse::MouseWheel, ButtonState},
+    prelude::*,
+    ui::FocusPolicy,
+    utils::HashMap,
+    window::ReceivedCharacter,
+};
+use grin_input::action::{update_input_actions, InputAction};
+use itertools::Itertools;
+
+use commands::{
+    ClearCommand, DialogueCommand, GiveCommand, HelpCommand, RewindCommand, SetHealthCommand,
+    SpawnCommand, TimeScaleCommand,
+};
+
+/// Opens and closes the console.
+pub const CONSOLE_KEY: KeyCode = KeyCode::Backquote;
+
+/// Lines of output kept around. The oldest ones are forgotten past this.
+pub const CONSOLE_CAPACITY: usize = 200;
+
+/// Commands kept around for the up and down arrows.
+pub const CONSOLE_HISTORY_CAPACITY: usize = 50;
+
+/// Pixels scrolled per line of mouse wheel movement.
+pub const CONSOLE_SCROLL_SPEED: f32 = 24.0;
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<ConsoleCommands>()
+            .init_resource::<ConsoleStyle>()
+            .add_systems(Startup, init_console)
+            .add_systems(
+                PreUpdate,
+                (
+                    toggle_console,
+                    type_into_console,
+                    suppress_gameplay_input.run_if(console_open),
+                )
+                    .chain()
+                    .after(update_input_actions),
+            )
+            .add_systems(
+                Update,
+                (run_console_commands, display_console, scroll_console).chain(),
+            )
+            .add_console_command(HelpCommand)
+            .add_console_command(ClearCommand)
+            .add_console_command(SpawnCommand)
+            .add_console_command(GiveCommand)
+            .add_console_command(SetHealthCommand)
+            .add_console_command(TimeScaleCommand)
+            .add_console_command(RewindCommand)
+            .add_console_command(DialogueCommand);
+    }
+}
+
+/// Something that can be typed into the console.
+pub trait ConsoleCommand: Send + Sync + 'static {
+    /// The first word of the line.
+    fn name(&self) -> &'static str;
+
+    /// Arguments, for `help` and for when they're wrong.
+    fn usage(&self) -> &'static str {
+        ""
+    }
+
+    /// What shows up in `help`.
+    fn description(&self) -> &'static str {
+        ""
+    }
+
+    /// Returns what to print, if anything.
+    fn run(&self, world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConsoleError {
+    /// The arguments are wrong. Prints the usage.
+    Usage,
+    /// Prints this.
+    Failed(String),
+}
+
+/// Parses `args[index]`. `ConsoleError::Usage` if it isn't there.
+pub fn parse_arg<T: FromStr>(args: &[&str], index: usize) -> Result<T, ConsoleError> {
+    let arg = args.get(index).ok_or(ConsoleError::Usage)?;
+    arg.parse()
+        .map_err(|_| ConsoleError::Failed(format!("Can't make sense of `{}`.", arg)))
+}
+
+/// `ConsoleError::Usage` unless there's exactly `count` arguments.
+pub fn expect_args(args: &[&str], count: usize) -> Result<(), ConsoleError> {
+    match args.len() == count {
+        true => Ok(()),
+        false => Err(ConsoleError::Usage),
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(pub HashMap<&'static str, Arc<dyn ConsoleCommand>>);
+
+impl ConsoleCommands {
+    pub fn insert(&mut self, command: impl ConsoleCommand) {
+        self.0.insert(command.name(), Arc::new(command));
+    }
+
+    /// Sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn ConsoleCommand> {
+        self.0
+            .iter()
+            .sorted_by_key(|(name, _)| **name)
+            .map(|(_, command)| command.as_ref())
+    }
+}
+
+pub trait ConsoleAppExt {
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self;
+}
+
+impl ConsoleAppExt for App {
+    fn add_console_command(&mut self, command: impl ConsoleCommand) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(ConsoleCommands::default)
+            .insert(command);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConsoleLineKind {
+    /// Something that was typed in.
+    Input,
+    Output,
+    Error,
+}
+
+impl ConsoleLineKind {
+    pub fn color(&self) -> Color {
+        match self {
+            Self::Input => Color::GRAY,
+            Self::Output => Color::WHITE,
+            Self::Error => Color::RED,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsoleLine {
+    pub text: String,
+    pub kind: ConsoleLineKind,
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct Console {
+    pub open: bool,
+    /// What's been typed so far.
+    pub input: String,
+    /// Output, oldest first.
+    lines: VecDeque<ConsoleLine>,
+    /// Commands that were run, oldest first.
+    history: VecDeque<String>,
+    /// Index into `history` while going through it with the arrows.
+    history_index: Option<usize>,
+    /// Submitted lines that haven't run yet.
+    pending: Vec<String>,
+}
+
+impl Console {
+    pub fn lines(&self) -> impl Iterator<Item = &ConsoleLine> {
+        self.lines.iter()
+    }
+
+    pub fn push(&mut self, text: impl Into<String>, kind: ConsoleLineKind) {
+        while self.lines.len() >= CONSOLE_CAPACITY {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(ConsoleLine {
+            text: text.into(),
+            kind,
+        });
+    }
+
+    pub fn print(&mut self, text: impl Into<String>) {
+        self.push(text, ConsoleLineKind::Output);
+    }
+
+    pub fn error(&mut self, text: impl Into<String>) {
+        self.push(text, ConsoleLineKind::Error);
+    }
+
+    pub fn clear(&mut self) {
+        self.lines.clear();
+    }
+
+    /// Queues up `input` to run, and remembers it for the arrows.
+    pub fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input).trim().to_owned();
+        self.history_index = None;
+        if line.is_empty() {
+            return;
+        }
+
+        self.push(format!("> {}", line), ConsoleLineKind::Input);
+        if self.history.back() != Some(&line) {
+            while self.history.len() >= CONSOLE_HISTORY_CAPACITY {
+                self.history.pop_front();
+            }
+            self.history.push_back(line.clone());
+        }
+        self.pending.push(line);
+    }
+
+    /// Up arrow. Goes back a command.
+    pub fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let index = self
+            .history_index
+            .map_or(self.history.len() - 1, |i| i.saturating_sub(1));
+        self.history_index = Some(index);
+        self.input = self.history[index].clone();
+    }
+
+    /// Down arrow. Goes forward a command, then back to a blank line.
+    pub fn history_next(&mut self) {
+        let Some(index) = self.history_index else {
+            return;
+        };
+        if index + 1 < self.history.len() {
+            self.history_index = Some(index + 1);
+            self.input = self.history[index + 1].clone();
+        } else {
+            self.history_index = None;
+            self.input.clear();
+        }
+    }
+}
+
+pub fn console_open(console: Res<Console>) -> bool {
+    console.open
+}
+
+/// Runs `line` right now.
+pub fn execute(world: &mut World, line: &str) {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        return;
+    };
+    let args = words.collect_vec();
+
+    let command = world.resource::<ConsoleCommands>().0.get(name).cloned();
+    let result = match command {
+        Some(command) => command.run(world, &args).map_err(|err| match err {
+            ConsoleError::Usage => format!("Usage: {} {}", name, command.usage()),
+            ConsoleError::Failed(msg) => msg,
+        }),
+        None => Err(format!("No command called `{}`. Try `help`.", name)),
+    };
+
+    let mut console = world.resource_mut::<Console>();
+    match result {
+        Ok(Some(output)) => console.print(output),
+        Ok(None) => (),
+        Err(msg) => console.error(msg),
+    }
+}
+
+#[derive(Resource)]
+pub struct ConsoleStyle(pub TextStyle);
+
+impl FromWorld for ConsoleStyle {
+    fn from_world(world: &mut World) -> Self {
+        let asset_server = world.resource::<AssetServer>();
+        Self(TextStyle {
+            font: asset_server.load("fonts/FiraSans-Regular.ttf"),
+            font_size: 18.0,
+            color: Color::WHITE,
+        })
+    }
+}
+
+#[derive(Component)]
+pub struct ConsoleWindow;
+
+/// Contains the output. Moves up and down when scrolling.
+#[derive(Component, Default)]
+pub struct ConsoleLog {
+    pub scroll: f32,
+}
+
+#[derive(Component)]
+pub struct ConsoleInput;
+
+pub fn init_console(mut commands: Commands) {
+    commands
+        .spawn((
+            ConsoleWindow,
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    top: Val::Percent(0.0),
+                    left: Val::Percent(0.0),
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(40.0),
+                    padding: UiRect::all(Val::Px(8.0)),
+                    row_gap: Val::Px(4.0),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::BLACK.with_a(0.85)),
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Global(1002),
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_grow: 1.0,
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::FlexEnd,
+                        overflow: Overflow::clip_y(),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        ConsoleLog::default(),
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Relative,
+                                flex_direction: FlexDirection::Column,
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                    ));
+                });
+
+            parent.spawn((ConsoleInput, TextBundle::default()));
+        });
+}
+
+/// Escape closes it too, without pausing the game on the way out.
+pub fn toggle_console(
+    mut console: ResMut<Console>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut actions: ResMut<ButtonInput<InputAction>>,
+) {
+    if keys.just_pressed(CONSOLE_KEY) {
+        console.open = !console.open;
+    } else if console.open && keys.just_pressed(KeyCode::Escape) {
+        console.open = false;
+        keys.reset(KeyCode::Escape);
+        actions.reset(InputAction::Pause);
+    }
+}
+
+pub fn type_into_console(
+    mut console: ResMut<Console>,
+    keys: Res<ButtonInput<KeyCode>>,
+    mut char_events: EventReader<ReceivedCharacter>,
+    mut key_events: EventReader<KeyboardInput>,
+) {
+    // the key that opened it comes through too
+    if !console.open || keys.just_pressed(CONSOLE_KEY) {
+        char_events.clear();
+        key_events.clear();
+        return;
+    }
+
+    for ReceivedCharacter { char, .. } in char_events.read() {
+        for c in char.chars() {
+            if !c.is_control() {
+                console.input.push(c);
+            }
+        }
+    }
+
+    for event in key_events.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+        match event.key_code {
+            KeyCode::Enter | KeyCode::NumpadEnter => console.submit(),
+            KeyCode::Backspace => {
+                console.input.pop();
+            }
+            KeyCode::ArrowUp => console.history_prev(),
+            KeyCode::ArrowDown => console.history_next(),
+            _ => (),
+        }
+    }
+}
+
+/// Nothing else gets to see the keyboard or mouse buttons while the console is open.
+pub fn suppress_gameplay_input(
+    mut actions: ResMut<ButtonInput<InputAction>>,
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut mouse_buttons: ResMut<ButtonInput<MouseButton>>,
+) {
+    actions.reset_all();
+    keys.reset_all();
+    mouse_buttons.reset_all();
+}
+
+pub fn run_console_commands(world: &mut World) {
+    let pending = std::mem::take(&mut world.resource_mut::<Console>().pending);
+    for line in pending {
+        execute(world, &line);
+    }
+}
+
+pub fn display_console(
+    mut commands: Commands,
+    console: Res<Console>,
+    style: Res<ConsoleStyle>,
+    mut window_query: Query<&mut Style, With<ConsoleWindow>>,
+    mut log_query: Query<(Entity, &mut ConsoleLog)>,
+    mut input_query: Query<&mut Text, With<ConsoleInput>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+
+    let mut window_style = window_query.single_mut();
+    window_style.display = match console.open {
+        true => Display::Flex,
+        false => Display::None,
+    };
+
+    *input_query.single_mut() =
+        Text::from_section(format!("> {}_", console.input), style.0.clone());
+
+    let (e_log, mut log) = log_query.single_mut();
+    log.scroll = 0.0;
+    commands
+        .entity(e_log)
+        .despawn_descendants()
+        .with_children(|parent| {
+            for ConsoleLine { text, kind } in console.lines() {
+                parent.spawn(TextBundle::from_section(
+                    text.clone(),
+                    TextStyle {
+                        color: kind.color(),
+                        ..style.0.clone()
+                    },
+                ));
+            }
+        });
+}
+
+pub fn scroll_console(
+    console: Res<Console>,
+    mut scroll_events: EventReader<MouseWheel>,
+    parent_query: Query<&Parent>,
+    node_query: Query<&Node>,
+    mut log_query: Query<(Entity, &mut Style, &mut ConsoleLog)>,
+) {
+    if !console.open {
+        scroll_events.clear();
+        return;
+    }
+
+    let (e_log, mut style, mut log) = log_query.single_mut();
+    let (Ok(node), Some(viewport)) = (
+        node_query.get(e_log),
+        parent_query
+            .get(e_log)
+            .ok()
+            .and_then(|parent| node_query.get(parent.get()).ok()),
+    ) else {
+        return;
+    };
+
+    let max_scroll = (node.size().y - viewport.size().y).max(0.0);
+    for MouseWheel { y, .. } in scroll_events.read() {
+        log.scroll = (log.scroll + y * CONSOLE_SCROLL_SPEED).clamp(0.0, max_scroll);
+    }
+    style.bottom = Val::Px(-log.scroll);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MockCommand;
+
+    impl ConsoleCommand for MockCommand {
+        fn name(&self) -> &'static str {
+            "double"
+        }
+
+        fn usage(&self) -> &'static str {
+            "<number>"
+        }
+
+        fn run(&self, _world: &mut World, args: &[&str]) -> Result<Option<String>, ConsoleError> {
+            expect_args(args, 1)?;
+            let n = parse_arg::<i32>(args, 0)?;
+            Ok(Some((n * 2).to_string()))
+        }
+    }
+
+    #[test]
+    fn history() {
+        let mut console = Console::default();
+        for line in ["one", "two", "two", "  "] {
+            console.input = line.into();
+            console.submit();
+        }
+        assert_eq!(
+            console.pending,
+            vec!["one", "two", "two"],
+            "Wrong lines queued."
+        );
+
+        console.history_prev();
+        assert_eq!(console.input, "two", "Up didn't go to the last command.");
+        console.history_prev();
+        assert_eq!(console.input, "one", "Repeats weren't collapsed.");
+        console.history_prev();
+        assert_eq!(console.input, "one", "Went past the oldest command.");
+        console.history_next();
+        console.history_next();
+        assert_eq!(console.input, "", "Down didn't go back to a blank line.");
+    }
+
+    #[test]
+    fn escape_closes() {
+        let mut app = App::new();
+        app.init_resource::<Console>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<InputAction>>()
+            .add_systems(Update, toggle_console);
+
+        app.world.resource_mut::<Console>().open = true;
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Escape);
+        app.world
+            .resource_mut::<ButtonInput<InputAction>>()
+            .press(InputAction::Pause);
+        app.update();
+
+        assert!(
+            !app.world.resource::<Console>().open,
+            "Escape didn't close the console."
+        );
+        assert!(
+            !app.world
+                .resource::<ButtonInput<InputAction>>()
+                .just_pressed(InputAction::Pause),
+            "Escape went through to the pause menu."
+        );
+    }
+
+    #[test]
+    fn type_backtick() {
+        let mut app = App::new();
+        app.init_resource::<Console>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<InputAction>>()
+            .add_event::<ReceivedCharacter>()
+            .add_event::<KeyboardInput>()
+            .add_systems(Update, (toggle_console, type_into_console).chain());
+
+        let type_chars = |app: &mut App, chars: &[&str]| {
+            for c in chars {
+                app.world.send_event(ReceivedCharacter {
+                    window: Entity::PLACEHOLDER,
+                    char: (*c).into(),
+                });
+            }
+        };
+
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(CONSOLE_KEY);
+        type_chars(&mut app, &["`"]);
+        app.update();
+        assert!(
+            app.world.resource::<Console>().open,
+            "Backtick didn't open the console."
+        );
+        assert_eq!(
+            app.world.resource::<Console>().input,
+            "",
+            "The backtick that opened it got typed in."
+        );
+
+        let mut keys = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        keys.release(CONSOLE_KEY);
+        keys.clear();
+        type_chars(&mut app, &["a", "`", "~"]);
+        app.update();
+        assert_eq!(
+            app.world.resource::<Console>().input,
+            "a`~",
+            "Backticks got swallowed."
+        );
+    }
+
+    #[test]
+    fn run_commands() {
+        let mut app = App::new();
+        app.init_resource::<Console>()
+            .add_console_command(MockCommand)
+            .add_systems(Update, run_console_commands);
+
+        for line in ["double 21", "double", "double x", "triple 3"] {
+            let mut console = app.world.resource_mut::<Console>();
+            console.input = line.into();
+            console.submit();
+        }
+        app.update();
+
+        let output = app
+            .world
+            .resource::<Console>()
+            .lines()
+            .filter(|line| line.kind != ConsoleLineKind::Input)
+            .map(|line| (line.text.as_str(), line.kind))
+            .collect_vec();
+        assert_eq!(
+            output,
+            vec![
+                ("42", ConsoleLineKind::Output),
+                ("Usage: double <number>", ConsoleLineKind::Error),
+                ("Can't make sense of `x`.", ConsoleLineKind::Error),
+                (
+                    "No command called `triple`. Try `help`.",
+                    ConsoleLineKind::Error
+                ),
+            ],
+            "Wrong output."
+        );
+    }
+}
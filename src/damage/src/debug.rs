@@ -0,0 +1,427 @@
+//! Wireframes for just the colliders that deal and take damage. The rapier debug renderer draws
+//! everything, which is a lot when all you care about is a melee hitbox.
+//!
+//! Nothing here runs unless `HitboxDebug` is enabled.
+
+use bevy::{prelude::*, ui::TargetCamera};
+use bevy_rapier3d::prelude::*;
+use grin_physics::CollisionGroupExt;
+
+use crate::{
+    explosion::ExplosionEvent,
+    hit::{try_find_deepest_contact_point, ContactDamage, Damage, DamageEvent},
+    hitbox::Hitbox,
+};
+
+pub struct HitboxDebugPlugin;
+
+impl Plugin for HitboxDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitboxDebug>()
+            .init_resource::<RecentExplosions>()
+            // in case `ContactDamagePlugin` and `ExplosionPlugin` aren't around
+            .add_event::<DamageEvent>()
+            .add_event::<ExplosionEvent>()
+            .add_systems(
+                Update,
+                (
+                    toggle_hitbox_debug.run_if(resource_exists::<ButtonInput<KeyCode>>),
+                    (
+                        draw_hitboxes,
+                        draw_explosion_radii,
+                        (spawn_damage_labels, update_damage_labels).chain(),
+                    )
+                        .run_if(hitbox_debug_enabled),
+                    clear_hitbox_debug.run_if(resource_changed::<HitboxDebug>),
+                ),
+            );
+    }
+}
+
+/// Seconds that explosions and damage numbers stay up.
+pub const HITBOX_DEBUG_LINGER: f32 = 1.0;
+
+/// Alpha for shapes that are only around for a moment.
+pub const HITBOX_DEBUG_ALPHA: f32 = 0.4;
+
+/// Alpha for colliders with `ColliderDisabled`.
+pub const HITBOX_DEBUG_DISABLED_ALPHA: f32 = 0.15;
+
+pub const PLAYER_PROJECTILE_DEBUG_COLOR: Color = Color::rgb(0.2, 0.6, 1.0);
+pub const ENEMY_PROJECTILE_DEBUG_COLOR: Color = Color::rgb(1.0, 0.2, 0.2);
+pub const HITBOX_DEBUG_COLOR: Color = Color::rgb(0.2, 1.0, 0.4);
+/// Anything in neither projectile group that isn't a `Hitbox`.
+pub const OTHER_DAMAGE_DEBUG_COLOR: Color = Color::rgb(1.0, 0.9, 0.2);
+pub const EXPLOSION_DEBUG_COLOR: Color = Color::rgb(1.0, 0.5, 0.0);
+
+/// Press `toggle` to draw damage-relevant colliders.
+#[derive(Resource, Debug)]
+pub struct HitboxDebug {
+    pub enabled: bool,
+    pub toggle: KeyCode,
+}
+
+impl Default for HitboxDebug {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            toggle: KeyCode::F3,
+        }
+    }
+}
+
+/// Run condition. Also works without `HitboxDebugPlugin`.
+pub fn hitbox_debug_enabled(debug: Option<Res<HitboxDebug>>) -> bool {
+    debug.is_some_and(|d| d.enabled)
+}
+
+/// Explosions from the last `HITBOX_DEBUG_LINGER` seconds, and how long they have left.
+#[derive(Resource, Debug, Default)]
+pub struct RecentExplosions(pub Vec<(ExplosionEvent, Timer)>);
+
+/// Floating text for a `DamageEvent`, over where it landed.
+#[derive(Component, Debug)]
+pub struct DamageLabel {
+    pub point: Vec3,
+    pub timer: Timer,
+}
+
+/// Picks a color by collision group. Projectiles first, then hitboxes.
+pub fn hitbox_debug_color(groups: Option<&CollisionGroups>, hitbox: bool) -> Color {
+    let memberships = groups.map_or(Group::NONE, |g| g.memberships);
+    if memberships.intersects(Group::PLAYER_PROJECTILE) {
+        PLAYER_PROJECTILE_DEBUG_COLOR
+    } else if memberships.intersects(Group::ENEMY_PROJECTILE) {
+        ENEMY_PROJECTILE_DEBUG_COLOR
+    } else if hitbox {
+        HITBOX_DEBUG_COLOR
+    } else {
+        OTHER_DAMAGE_DEBUG_COLOR
+    }
+}
+
+/// Draws `collider` where `g_transform` puts it, ignoring scale.
+///
+/// Shapes that don't have a wireframe here get their bounding sphere.
+pub fn draw_collider(
+    gizmos: &mut Gizmos,
+    collider: &Collider,
+    g_transform: &GlobalTransform,
+    color: Color,
+) {
+    let (_, rotation, translation) = g_transform.to_scale_rotation_translation();
+    match collider.as_typed_shape() {
+        ColliderView::Ball(ball) => {
+            gizmos.sphere(translation, rotation, ball.radius(), color);
+        }
+        ColliderView::Cuboid(cuboid) => gizmos.cuboid(
+            Transform {
+                translation,
+                rotation,
+                scale: cuboid.half_extents() * 2.0,
+            },
+            color,
+        ),
+        ColliderView::Capsule(capsule) => {
+            let segment = capsule.segment();
+            let (a, b) = (segment.a(), segment.b());
+            let radius = capsule.radius();
+            let (side_0, side_1) = (b - a).normalize_or_zero().any_orthonormal_pair();
+            for offset in [side_0, side_1, -side_0, -side_1] {
+                gizmos.line(
+                    translation + rotation * (a + offset * radius),
+                    translation + rotation * (b + offset * radius),
+                    color,
+                );
+            }
+            for end in [a, b] {
+                gizmos.sphere(translation + rotation * end, rotation, radius, color);
+            }
+        }
+        _ => {
+            let radius = collider.raw.compute_local_bounding_sphere().radius();
+            gizmos.sphere(translation, rotation, radius, color);
+        }
+    }
+}
+
+pub fn toggle_hitbox_debug(keys: Res<ButtonInput<KeyCode>>, mut debug: ResMut<HitboxDebug>) {
+    if keys.just_pressed(debug.toggle) {
+        debug.enabled = !debug.enabled;
+        info!("Hitbox debug: {}", debug.enabled);
+    }
+}
+
+pub fn draw_hitboxes(
+    mut gizmos: Gizmos,
+    collider_query: Query<
+        (
+            &Collider,
+            &GlobalTransform,
+            Option<&CollisionGroups>,
+            Has<Hitbox>,
+            Has<ColliderDisabled>,
+        ),
+        Or<(With<ContactDamage>, With<Damage>, With<Hitbox>)>,
+    >,
+) {
+    for (collider, g_transform, groups, hitbox, disabled) in collider_query.iter() {
+        let color = hitbox_debug_color(groups, hitbox);
+        let color = match disabled {
+            true => color.with_a(HITBOX_DEBUG_DISABLED_ALPHA),
+            false => color,
+        };
+        draw_collider(&mut gizmos, collider, g_transform, color);
+    }
+}
+
+/// Draws explosion radii for a little while after they go off.
+pub fn draw_explosion_radii(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    mut recent: ResMut<RecentExplosions>,
+    mut explosion_events: EventReader<ExplosionEvent>,
+) {
+    for explosion_event in explosion_events.read() {
+        recent.0.push((
+            *explosion_event,
+            Timer::from_seconds(HITBOX_DEBUG_LINGER, TimerMode::Once),
+        ));
+    }
+
+    recent.0.retain_mut(|(explosion_event, timer)| {
+        timer.tick(time.delta());
+        let alpha = HITBOX_DEBUG_ALPHA * timer.fraction_remaining();
+        gizmos.sphere(
+            explosion_event.origin,
+            Quat::IDENTITY,
+            explosion_event.explosion.radius,
+            EXPLOSION_DEBUG_COLOR.with_a(alpha),
+        );
+        !timer.finished()
+    });
+}
+
+fn damage_text(damage: &Damage) -> String {
+    format!("{:.1} {:?}", damage.value, damage.ty)
+}
+
+/// Spawns a `DamageLabel` at the contact point of each `DamageEvent`, or on whatever got hit
+/// if there isn't one.
+pub fn spawn_damage_labels(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    damage_query: Query<&Damage>,
+    transform_query: Query<&GlobalTransform>,
+    mut damage_events: EventReader<DamageEvent>,
+) {
+    for damage_event in damage_events.read() {
+        let (e_hit, text) = match damage_event {
+            DamageEvent::Contact {
+                e_damage,
+                e_hit,
+                absorbed,
+                ..
+            } => {
+                let text = match (absorbed, damage_query.get(*e_damage)) {
+                    (true, _) => "absorbed".to_owned(),
+                    (false, Ok(damage)) => damage_text(damage),
+                    (false, Err(_)) => "contact".to_owned(),
+                };
+                (*e_hit, text)
+            }
+            DamageEvent::Direct { damage, e_hit } => (*e_hit, damage_text(damage)),
+            DamageEvent::OverTime { dot, e_hit } => {
+                (*e_hit, format!("{}/tick", damage_text(&dot.damage)))
+            }
+        };
+
+        let Some(point) =
+            try_find_deepest_contact_point(damage_event, &rapier_context, &transform_query)
+                .ok()
+                .or_else(|| {
+                    transform_query
+                        .get(e_hit)
+                        .ok()
+                        .map(GlobalTransform::translation)
+                })
+        else {
+            continue;
+        };
+
+        commands.spawn((
+            DamageLabel {
+                point,
+                timer: Timer::from_seconds(HITBOX_DEBUG_LINGER, TimerMode::Once),
+            },
+            TextBundle {
+                text: Text::from_section(
+                    text,
+                    TextStyle {
+                        font_size: 16.0,
+                        color: Color::WHITE,
+                        ..Default::default()
+                    },
+                ),
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ));
+    }
+}
+
+/// Moves `DamageLabel`s over their point on the frontmost camera, fades them out and
+/// despawns them.
+pub fn update_damage_labels(
+    mut commands: Commands,
+    time: Res<Time>,
+    camera_query: Query<(Entity, &Camera, &GlobalTransform)>,
+    mut label_query: Query<(
+        Entity,
+        &mut DamageLabel,
+        &mut Style,
+        &mut Text,
+        Option<&TargetCamera>,
+    )>,
+) {
+    let camera = camera_query
+        .iter()
+        .filter(|(_, camera, _)| camera.is_active)
+        .max_by_key(|(_, camera, _)| camera.order);
+
+    for (e_label, mut label, mut style, mut text, target_camera) in label_query.iter_mut() {
+        label.timer.tick(time.delta());
+        if label.timer.finished() {
+            commands.entity(e_label).despawn_recursive();
+            continue;
+        }
+
+        let position = camera.and_then(|(e_camera, camera, g_camera_transform)| {
+            camera
+                .world_to_viewport(g_camera_transform, label.point)
+                .map(|position| (e_camera, position))
+        });
+        let Some((e_camera, position)) = position else {
+            style.display = Display::None;
+            continue;
+        };
+
+        if target_camera.map(|t| t.0) != Some(e_camera) {
+            commands.entity(e_label).insert(TargetCamera(e_camera));
+        }
+
+        style.display = Display::Flex;
+        style.left = Val::Px(position.x);
+        style.top = Val::Px(position.y);
+
+        let alpha = label.timer.fraction_remaining();
+        for section in text.sections.iter_mut() {
+            section.style.color = section.style.color.with_a(alpha);
+        }
+    }
+}
+
+/// Gets rid of anything left over once `HitboxDebug` is turned off.
+pub fn clear_hitbox_debug(
+    mut commands: Commands,
+    debug: Res<HitboxDebug>,
+    mut recent: ResMut<RecentExplosions>,
+    label_query: Query<Entity, With<DamageLabel>>,
+) {
+    if debug.enabled {
+        return;
+    }
+
+    recent.0.clear();
+    for e_label in label_query.iter() {
+        commands.entity(e_label).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use crate::hit::DamageVariant;
+
+    use super::*;
+
+    fn labels(app: &mut App) -> Vec<Vec3> {
+        app.world
+            .query::<&DamageLabel>()
+            .iter(&app.world)
+            .map(|label| label.point)
+            .collect()
+    }
+
+    #[test]
+    fn damage_labels() {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .insert_resource(HitboxDebug {
+                enabled: true,
+                ..Default::default()
+            })
+            .init_resource::<RecentExplosions>()
+            .init_resource::<RapierContext>()
+            .add_event::<DamageEvent>()
+            .add_systems(
+                Update,
+                (
+                    (spawn_damage_labels, update_damage_labels)
+                        .chain()
+                        .run_if(hitbox_debug_enabled),
+                    clear_hitbox_debug.run_if(resource_changed::<HitboxDebug>),
+                ),
+            );
+
+        let transform = Transform::from_xyz(1.0, 2.0, 3.0);
+        let e_hit = app
+            .world
+            .spawn(TransformBundle {
+                local: transform,
+                global: transform.into(),
+            })
+            .id();
+        let mut hit = |app: &mut App| {
+            app.world.send_event(DamageEvent::Direct {
+                damage: Damage {
+                    ty: DamageVariant::Ballistic,
+                    value: 10.0,
+                    source: None,
+                },
+                e_hit,
+            });
+            app.update();
+        };
+
+        hit(&mut app);
+        assert_eq!(
+            labels(&mut app),
+            vec![Vec3::new(1.0, 2.0, 3.0)],
+            "Label isn't on the target."
+        );
+
+        for _ in 0..(HITBOX_DEBUG_LINGER * 10.0) as usize + 1 {
+            app.update();
+        }
+        assert!(labels(&mut app).is_empty(), "Label didn't time out.");
+
+        hit(&mut app);
+        app.world.resource_mut::<HitboxDebug>().enabled = false;
+        app.update();
+        assert!(
+            labels(&mut app).is_empty(),
+            "Label outlived the debug overlay."
+        );
+    }
+}
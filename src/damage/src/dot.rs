@@ -0,0 +1,347 @@
+//! Damage over time, like burning and poison.
+//!
+//! Not to be confused with `dot_region`, which is a damaging area rather than a damaging status.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashMap};
+use grin_physics::PhysicsTime;
+use grin_time::scaling::TimeScale;
+
+use crate::{
+    health::{DamageBuffer, Dead},
+    hit::{Damage, DamageEvent, DamageVariant},
+    hitbox::Hitbox,
+    plugin::DamageSet,
+};
+
+pub struct DamageOverTimePlugin;
+
+impl Plugin for DamageOverTimePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DotStackingPolicy>().add_systems(
+            Update,
+            (
+                apply_damage_over_time,
+                apply_deferred,
+                tick_damage_over_time,
+            )
+                .chain()
+                .in_set(DamageSet::Add),
+        );
+    }
+}
+
+/// Pushes `damage` into the `DamageBuffer` every `tick_interval`, until `remaining` runs out.
+#[derive(Clone, Debug, Default)]
+pub struct DamageOverTime {
+    /// Damage per tick.
+    pub damage: Damage,
+    pub tick_interval: Duration,
+    pub remaining: Duration,
+    /// Time since the last tick.
+    pub elapsed: Duration,
+}
+
+impl DamageOverTime {
+    pub fn new(damage: Damage, tick_interval: Duration, duration: Duration) -> Self {
+        Self {
+            damage,
+            tick_interval,
+            remaining: duration,
+            elapsed: Duration::ZERO,
+        }
+    }
+
+    /// Advances the timer, returning the number of ticks that happened.
+    pub fn tick(&mut self, delta: Duration) -> usize {
+        let delta = delta.min(self.remaining);
+        self.remaining -= delta;
+        self.elapsed += delta;
+
+        if self.tick_interval.is_zero() {
+            return 0;
+        }
+
+        let mut ticks = 0;
+        while self.elapsed >= self.tick_interval {
+            self.elapsed -= self.tick_interval;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    pub fn finished(&self) -> bool {
+        self.remaining.is_zero()
+    }
+}
+
+/// What happens when a `DamageOverTime` is applied to something that already has one of the same
+/// `DamageVariant`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DotStacking {
+    /// The new one replaces the old one. The tick timer carries over, so reapplying can't be used
+    /// to tick faster.
+    #[default]
+    Refresh,
+    /// The new duration is added onto the old one.
+    Extend,
+    /// Both tick separately, up to `max` at once. The oldest is dropped past that.
+    Stack { max: usize },
+}
+
+/// `DotStacking` for each `DamageVariant`.
+#[derive(Resource, Debug, Default)]
+pub struct DotStackingPolicy {
+    /// Used for variants without an override.
+    pub default: DotStacking,
+    pub overrides: HashMap<DamageVariant, DotStacking>,
+}
+
+impl DotStackingPolicy {
+    pub fn get(&self, variant: DamageVariant) -> DotStacking {
+        self.overrides
+            .get(&variant)
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Every `DamageOverTime` affecting this entity. Goes next to the `DamageBuffer`.
+#[derive(Component, Debug, Default)]
+pub struct DamageOverTimeEffects(pub Vec<DamageOverTime>);
+
+impl DamageOverTimeEffects {
+    pub fn apply(&mut self, dot: DamageOverTime, stacking: DotStacking) {
+        let variant = dot.damage.ty;
+        let mut same = self.0.iter_mut().filter(|d| d.damage.ty == variant);
+
+        match stacking {
+            DotStacking::Refresh => match same.next() {
+                Some(old) => {
+                    *old = DamageOverTime {
+                        elapsed: old.elapsed,
+                        ..dot
+                    };
+                }
+                None => self.0.push(dot),
+            },
+            DotStacking::Extend => match same.next() {
+                Some(old) => old.remaining += dot.remaining,
+                None => self.0.push(dot),
+            },
+            DotStacking::Stack { max } => {
+                let count = same.count();
+                if count >= max {
+                    // oldest first, so the first one is the oldest
+                    let Some(i) = self.0.iter().position(|d| d.damage.ty == variant) else {
+                        return;
+                    };
+                    self.0.remove(i);
+                }
+                if max > 0 {
+                    self.0.push(dot);
+                }
+            }
+        }
+    }
+}
+
+/// Applies `DamageEvent::OverTime`.
+pub fn apply_damage_over_time(
+    mut commands: Commands,
+    policy: Res<DotStackingPolicy>,
+    hitbox_query: Query<&Hitbox>,
+    mut dot_query: Query<Option<&mut DamageOverTimeEffects>, With<DamageBuffer>>,
+    mut damage_events: EventReader<DamageEvent>,
+) {
+    // a second event for the same entity in the same frame can't see the inserted component
+    let mut inserted = HashMap::<Entity, DamageOverTimeEffects>::new();
+
+    for damage_event in damage_events.read() {
+        let DamageEvent::OverTime { dot, e_hit } = damage_event else {
+            continue;
+        };
+        let e_target = hitbox_query
+            .get(*e_hit)
+            .map_or(*e_hit, |hitbox| hitbox.target);
+        let stacking = policy.get(dot.damage.ty);
+
+        match dot_query.get_mut(e_target) {
+            Ok(Some(mut effects)) => effects.apply(dot.clone(), stacking),
+            Ok(None) => inserted
+                .entry(e_target)
+                .or_default()
+                .apply(dot.clone(), stacking),
+            Err(e) => {
+                error!(
+                    msg="Damage over time error.",
+                    receiver=?e_target,
+                    err=?e,
+                );
+            }
+        }
+    }
+
+    for (e_target, effects) in inserted {
+        commands.entity(e_target).insert(effects);
+    }
+}
+
+/// Pushes `DamageOverTime` ticks into the `DamageBuffer`.
+pub fn tick_damage_over_time(
+    time: Res<PhysicsTime>,
+    mut dot_query: Query<
+        (
+            &mut DamageOverTimeEffects,
+            &mut DamageBuffer,
+            Option<&TimeScale>,
+        ),
+        Without<Dead>,
+    >,
+) {
+    for (mut effects, mut damage_buf, time_scale) in dot_query.iter_mut() {
+        let delta = time.0.delta().mul_f32(time_scale.map_or(1.0, f32::from));
+
+        for dot in effects.0.iter_mut() {
+            for _ in 0..dot.tick(delta) {
+                damage_buf.0.push(dot.damage);
+            }
+        }
+        effects.0.retain(|dot| !dot.finished());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::health::{
+        apply_damage_buffers, apply_resist, die, DeathEvent, Health, Resist, ShieldBrokenEvent,
+    };
+
+    use super::*;
+
+    fn burn(value: f32, seconds: u64) -> DamageOverTime {
+        DamageOverTime::new(
+            Damage {
+                ty: DamageVariant::Fire,
+                value,
+                source: None,
+            },
+            Duration::from_secs(1),
+            Duration::from_secs(seconds),
+        )
+    }
+
+    fn step(app: &mut App, millis: u64) {
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_millis(millis));
+        app.update();
+    }
+
+    fn health(app: &App, entity: Entity) -> f32 {
+        app.world.get::<Health>(entity).unwrap().0
+    }
+
+    #[test]
+    fn tick_timing() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<ShieldBrokenEvent>()
+            .init_resource::<DotStackingPolicy>()
+            .add_systems(
+                Update,
+                (
+                    apply_damage_over_time,
+                    apply_deferred,
+                    tick_damage_over_time,
+                    apply_resist,
+                    apply_damage_buffers,
+                    die,
+                )
+                    .chain(),
+            );
+
+        let e = app
+            .world
+            .spawn((
+                Health(100.0),
+                Resist(HashMap::from([(DamageVariant::Fire, 0.5)])),
+                DamageBuffer::default(),
+                TimeScale::default(),
+            ))
+            .id();
+        app.world.send_event(DamageEvent::OverTime {
+            dot: burn(20.0, 3),
+            e_hit: e,
+        });
+
+        step(&mut app, 500);
+        assert_eq!(health(&app, e), 100.0, "Ticked early.");
+        step(&mut app, 500);
+        assert_eq!(health(&app, e), 90.0, "Didn't tick on time.");
+        step(&mut app, 1500);
+        assert_eq!(health(&app, e), 80.0, "Ticked more than once per interval.");
+
+        app.world.get_mut::<TimeScale>(e).unwrap().scale_by(0.5);
+        step(&mut app, 500);
+        assert_eq!(health(&app, e), 80.0, "Ignored `TimeScale`.");
+        step(&mut app, 500);
+        assert_eq!(health(&app, e), 70.0, "Ignored `TimeScale`.");
+        assert!(
+            app.world
+                .get::<DamageOverTimeEffects>(e)
+                .unwrap()
+                .0
+                .is_empty(),
+            "Expired `DamageOverTime` wasn't removed."
+        );
+
+        app.world.send_event(DamageEvent::OverTime {
+            dot: burn(100.0, 5),
+            e_hit: e,
+        });
+        step(&mut app, 2000);
+        step(&mut app, 2000);
+        assert_eq!(health(&app, e), 0.0);
+        assert!(app.world.entity(e).contains::<Dead>());
+    }
+
+    #[test]
+    fn stacking() {
+        let mut effects = DamageOverTimeEffects::default();
+        effects.apply(burn(1.0, 3), DotStacking::Refresh);
+        effects.0[0].tick(Duration::from_millis(500));
+        effects.apply(burn(2.0, 3), DotStacking::Refresh);
+        assert_eq!(effects.0.len(), 1);
+        assert_eq!(effects.0[0].damage.value, 2.0);
+        assert_eq!(effects.0[0].remaining, Duration::from_secs(3));
+        assert_eq!(
+            effects.0[0].elapsed,
+            Duration::from_millis(500),
+            "Refreshing reset the tick timer."
+        );
+
+        effects.apply(burn(2.0, 2), DotStacking::Extend);
+        assert_eq!(effects.0.len(), 1);
+        assert_eq!(effects.0[0].remaining, Duration::from_secs(5));
+
+        let mut toxin = burn(1.0, 1);
+        toxin.damage.ty = DamageVariant::Toxin;
+        effects.apply(toxin, DotStacking::Refresh);
+        assert_eq!(effects.0.len(), 2, "Different variants interfered.");
+
+        effects.apply(burn(3.0, 1), DotStacking::Stack { max: 2 });
+        effects.apply(burn(4.0, 1), DotStacking::Stack { max: 2 });
+        let burns = effects
+            .0
+            .iter()
+            .filter(|d| d.damage.ty == DamageVariant::Fire)
+            .map(|d| d.damage.value)
+            .collect::<Vec<_>>();
+        assert_eq!(burns, [3.0, 4.0], "Oldest stack wasn't dropped.");
+    }
+}
@@ -0,0 +1,419 @@
+//! Area of effect damage.
+
+use bevy::{
+    ecs::entity::{EntityHashMap, EntityHashSet},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::*;
+use grin_physics::CollisionGroupExt;
+use grin_render::{
+    decal::{DecalKind, SpawnDecalEvent},
+    feedback::{AddTraumaEvent, ScreenShake},
+};
+
+use crate::{
+    faction::{Faction, FriendlyFirePolicy},
+    health::{DamageBuffer, Health},
+    hit::{
+        credit_damage_owner, push_contact_damage, try_find_deepest_contact_point, Damage,
+        DamageEvent,
+    },
+    hitbox::Hitbox,
+    impact::Impact,
+    plugin::DamageSet,
+};
+
+pub struct ExplosionPlugin;
+
+impl Plugin for ExplosionPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ExplosionEvent>()
+            // in case `FeedbackPlugin` and `DecalPlugin` aren't around
+            .add_event::<AddTraumaEvent>()
+            .add_event::<SpawnDecalEvent>()
+            .add_systems(
+                Update,
+                (
+                    (trigger_explosions, apply_explosions)
+                        .chain()
+                        .before(push_contact_damage)
+                        .in_set(DamageSet::Add),
+                    shake_from_explosions.after(trigger_explosions),
+                    scorch_from_explosions.after(trigger_explosions),
+                ),
+            );
+    }
+}
+
+/// Line of sight raycasts stop this far short of the explosion, so that explosions on the surface
+/// of a wall don't get blocked by the wall itself.
+const LINE_OF_SIGHT_TOLERANCE: f32 = 0.1;
+
+/// Explosions shake the screen out to this many times their radius.
+pub const EXPLOSION_SHAKE_RANGE: f32 = 3.0;
+
+/// Trauma from an explosion right on top of the camera.
+pub const EXPLOSION_TRAUMA: f32 = 0.6;
+
+/// Scorch marks are this many times the explosion radius across.
+pub const EXPLOSION_SCORCH_SCALE: f32 = 1.0;
+
+/// How `Explosion` damage drops off with distance from the center.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Falloff {
+    /// Full damage everywhere in the radius.
+    None,
+    #[default]
+    Linear,
+    Quadratic,
+}
+
+impl Falloff {
+    /// Damage multiplier at `distance` from the center.
+    pub fn scale(&self, distance: f32, radius: f32) -> f32 {
+        if radius <= 0.0 {
+            return 0.0;
+        }
+        let t = 1.0 - (distance / radius).clamp(0.0, 1.0);
+        match self {
+            Falloff::None => 1.0,
+            Falloff::Linear => t,
+            Falloff::Quadratic => t * t,
+        }
+    }
+}
+
+/// Damages everything in `radius` when the adjacent `ContactDamage` hits something.
+///
+/// This is separate from the adjacent `Damage`, which still goes to whatever was hit directly.
+/// Leave it out for explosion damage only.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Explosion {
+    pub radius: f32,
+    /// Damage at the center.
+    pub base_damage: Damage,
+    pub falloff: Falloff,
+    /// Whether the map blocks the explosion.
+    pub line_of_sight: bool,
+}
+
+/// Sent when an `Explosion` goes off. Can also be sent directly, for explosions that don't come
+/// from contact damage.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ExplosionEvent {
+    pub explosion: Explosion,
+    pub origin: Vec3,
+    /// Only colliders that can interact with these groups are damaged.
+    pub groups: CollisionGroups,
+    /// The exploding entity, if there is one. It doesn't get hit by itself.
+    pub e_damage: Option<Entity>,
+}
+
+/// Sends `ExplosionEvent` for `Explosion`s that made contact.
+pub fn trigger_explosions(
+    rapier_context: Res<RapierContext>,
+    explosion_query: Query<(&Explosion, Option<&CollisionGroups>)>,
+    transform_query: Query<&GlobalTransform>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut explosion_events: EventWriter<ExplosionEvent>,
+) {
+    // hitting a few things in the same frame shouldn't make a few explosions
+    let mut exploded = EntityHashSet::default();
+
+    for damage_event in damage_events.read() {
+        let &DamageEvent::Contact { e_damage, .. } = damage_event else {
+            continue;
+        };
+        let Ok((explosion, groups)) = explosion_query.get(e_damage) else {
+            continue;
+        };
+        if !exploded.insert(e_damage) {
+            continue;
+        }
+
+        // sensors don't have contact points, so use the center of the attack instead
+        let Some(origin) =
+            try_find_deepest_contact_point(damage_event, &rapier_context, &transform_query)
+                .ok()
+                .or_else(|| {
+                    transform_query
+                        .get(e_damage)
+                        .ok()
+                        .map(GlobalTransform::translation)
+                })
+        else {
+            continue;
+        };
+
+        explosion_events.send(ExplosionEvent {
+            explosion: *explosion,
+            origin,
+            groups: groups.copied().unwrap_or_default(),
+            e_damage: Some(e_damage),
+        });
+    }
+}
+
+/// Shakes the screen for explosions near the camera.
+pub fn shake_from_explosions(
+    camera_query: Query<&GlobalTransform, With<ScreenShake>>,
+    mut explosion_events: EventReader<ExplosionEvent>,
+    mut trauma_events: EventWriter<AddTraumaEvent>,
+) {
+    for ExplosionEvent {
+        explosion, origin, ..
+    } in explosion_events.read()
+    {
+        let range = explosion.radius * EXPLOSION_SHAKE_RANGE;
+        for g_camera_transform in camera_query.iter() {
+            let distance = g_camera_transform.translation().distance(*origin);
+            let trauma = EXPLOSION_TRAUMA * Falloff::Linear.scale(distance, range);
+            if trauma > 0.0 {
+                trauma_events.send(AddTraumaEvent(trauma));
+            }
+        }
+    }
+}
+
+/// Leaves a scorch mark on the map under explosions, if it's close enough.
+pub fn scorch_from_explosions(
+    rapier_context: Res<RapierContext>,
+    mut explosion_events: EventReader<ExplosionEvent>,
+    mut decal_events: EventWriter<SpawnDecalEvent>,
+) {
+    for ExplosionEvent {
+        explosion, origin, ..
+    } in explosion_events.read()
+    {
+        let Some((e_hit, intersection)) = rapier_context.cast_ray_and_get_normal(
+            *origin,
+            Vec3::NEG_Y,
+            explosion.radius,
+            true,
+            QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+        ) else {
+            continue;
+        };
+
+        decal_events.send(SpawnDecalEvent {
+            position: intersection.point,
+            normal: intersection.normal,
+            kind: DecalKind::Scorch,
+            size: explosion.radius * EXPLOSION_SCORCH_SCALE,
+            target: Some(e_hit),
+        });
+    }
+}
+
+/// Pushes `Explosion` damage into everything in range.
+pub fn apply_explosions(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    hitbox_query: Query<&Hitbox>,
+    transform_query: Query<&GlobalTransform>,
+    mut hit_query: Query<&mut DamageBuffer>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
+    faction_query: Query<&Faction>,
+    policy: Res<FriendlyFirePolicy>,
+    mut explosion_events: EventReader<ExplosionEvent>,
+) {
+    for &ExplosionEvent {
+        explosion,
+        origin,
+        groups,
+        e_damage,
+    } in explosion_events.read()
+    {
+        commands.spawn((
+            Impact::from_burst_radius(explosion.radius),
+            TransformBundle::from_transform(Transform::from_translation(origin)),
+        ));
+
+        let mut damage = explosion.base_damage;
+        damage.source = credit_damage_owner(damage.source, e_damage, &parent_query, &owner_query);
+
+        let mut filter = QueryFilter::new().groups(groups);
+        if let Some(e_damage) = e_damage {
+            filter = filter.exclude_collider(e_damage);
+        }
+
+        // each `HitboxManager` should only be hit once, even if a bunch of its hitboxes are in range
+        let mut targets = EntityHashMap::<Entity>::default();
+        rapier_context.intersections_with_shape(
+            origin,
+            Quat::IDENTITY,
+            &Collider::ball(explosion.radius),
+            filter,
+            |e_hit| {
+                let e_target = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+                targets.entry(e_target).or_insert(e_hit);
+                true
+            },
+        );
+
+        for (e_target, e_hit) in targets {
+            let Ok(g_target_transform) = transform_query.get(e_target) else {
+                continue;
+            };
+            let offset = origin - g_target_transform.translation();
+            let distance = offset.length();
+
+            let Some(multiplier) = policy.multiplier(damage.source, e_target, &faction_query)
+            else {
+                trace!(
+                    msg="Explosion rejected by friendly fire policy.",
+                    receiver=?e_hit,
+                );
+                continue;
+            };
+            let scale = explosion.falloff.scale(distance, explosion.radius) * multiplier;
+            if scale <= 0.0 {
+                continue;
+            }
+
+            if explosion.line_of_sight && distance > LINE_OF_SIGHT_TOLERANCE {
+                let blocked = rapier_context
+                    .cast_ray(
+                        g_target_transform.translation(),
+                        offset / distance,
+                        distance - LINE_OF_SIGHT_TOLERANCE,
+                        true,
+                        QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+                    )
+                    .is_some();
+                if blocked {
+                    trace!(
+                        msg="Explosion blocked by the map.",
+                        receiver=?e_hit,
+                    );
+                    continue;
+                }
+            }
+
+            let Ok(mut damage_buf) = hit_query.get_mut(e_target) else {
+                continue;
+            };
+            let damage = Damage {
+                value: damage.value * scale,
+                ..damage
+            };
+            damage_buf.0.push(damage);
+            info!(
+                msg="Pushed explosion damage.",
+                dealer=?e_damage,
+                receiver=?e_hit,
+                dmg=?damage,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::MeshPlugin, scene::ScenePlugin, time::TimePlugin};
+
+    use crate::hit::DamageVariant;
+
+    use super::*;
+
+    fn explosion_app() -> App {
+        let mut app = App::new();
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1.0,
+                substeps: 1,
+            },
+            ..Default::default()
+        })
+        .add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .init_resource::<FriendlyFirePolicy>()
+        .add_event::<ExplosionEvent>()
+        .add_systems(Update, apply_explosions);
+        app
+    }
+
+    fn spawn_dummy(app: &mut App, x: f32) -> Entity {
+        let transform = Transform::from_xyz(x, 0.0, 0.0);
+        app.world
+            .spawn((
+                Collider::ball(0.5),
+                TransformBundle {
+                    local: transform,
+                    global: transform.into(),
+                },
+                DamageBuffer::default(),
+            ))
+            .id()
+    }
+
+    /// Radius 8, 40 damage at the center, from the origin.
+    fn explode(app: &mut App, source: Option<Entity>) {
+        // colliders get added to the physics world here
+        app.update();
+
+        app.world.send_event(ExplosionEvent {
+            explosion: Explosion {
+                radius: 8.0,
+                base_damage: Damage {
+                    ty: DamageVariant::Fire,
+                    value: 40.0,
+                    source,
+                },
+                falloff: Falloff::Linear,
+                line_of_sight: false,
+            },
+            origin: Vec3::ZERO,
+            groups: CollisionGroups::default(),
+            e_damage: None,
+        });
+        app.update();
+    }
+
+    fn total_damage(app: &App, entity: Entity) -> f32 {
+        app.world
+            .get::<DamageBuffer>(entity)
+            .unwrap()
+            .0
+            .iter()
+            .map(|d| d.value)
+            .sum()
+    }
+
+    #[test]
+    fn falloff() {
+        let mut app = explosion_app();
+        let dummies = [2.0, 4.0, 6.0, 10.0].map(|x| spawn_dummy(&mut app, x));
+        explode(&mut app, None);
+
+        let damage = dummies.map(|e| total_damage(&app, e));
+        assert!(
+            damage[0] > damage[1] && damage[1] > damage[2] && damage[2] > 0.0,
+            "Damage didn't fall off with distance: {:?}.",
+            damage,
+        );
+        assert_eq!(damage[..3], [30.0, 20.0, 10.0]);
+        assert_eq!(damage[3], 0.0, "Damaged something out of range.");
+    }
+
+    #[test]
+    fn self_damage() {
+        let mut app = explosion_app();
+        let e_player = spawn_dummy(&mut app, 2.0);
+        let e_ally = spawn_dummy(&mut app, 2.0);
+        for e in [e_player, e_ally] {
+            app.world
+                .entity_mut(e)
+                .insert((Health(100.0), Faction::Player));
+        }
+        explode(&mut app, Some(e_player));
+
+        assert_eq!(
+            total_damage(&app, e_player),
+            15.0,
+            "Self damage wasn't halved."
+        );
+        assert_eq!(total_damage(&app, e_ally), 0.0, "Explosion hurt an ally.");
+    }
+}
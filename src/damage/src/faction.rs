@@ -0,0 +1,95 @@
+//! Who's allowed to hurt who.
+//!
+//! `CollisionGroups` already keep most attacks from touching allies. This is checked afterwards,
+//! when the damage is applied, for whatever the collision groups let through.
+
+use bevy::{prelude::*, utils::HashMap};
+
+pub struct FactionPlugin;
+
+impl Plugin for FactionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FriendlyFirePolicy>();
+    }
+}
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Faction {
+    Player,
+    Enemy,
+    Neutral,
+    Custom(u32),
+}
+
+/// What happens when damage goes between two factions.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FriendlyFire {
+    Allow,
+    Block,
+    /// The damage is multiplied by this.
+    Scale(f32),
+}
+
+impl FriendlyFire {
+    /// Damage multiplier, or `None` if it's blocked.
+    pub fn multiplier(&self) -> Option<f32> {
+        match self {
+            FriendlyFire::Allow => Some(1.0),
+            FriendlyFire::Block => None,
+            FriendlyFire::Scale(scale) => Some(*scale),
+        }
+    }
+}
+
+#[derive(Resource, Debug)]
+pub struct FriendlyFirePolicy {
+    /// Damage between different entities of the same faction.
+    pub same_faction: FriendlyFire,
+    /// Damage from an entity to itself, e.g. with its own explosion.
+    pub self_damage: FriendlyFire,
+    /// Policies for `(attacker, victim)` pairs. Takes priority over `same_faction`.
+    pub overrides: HashMap<(Faction, Faction), FriendlyFire>,
+}
+
+impl Default for FriendlyFirePolicy {
+    fn default() -> Self {
+        Self {
+            same_faction: FriendlyFire::Block,
+            self_damage: FriendlyFire::Scale(0.5),
+            overrides: HashMap::default(),
+        }
+    }
+}
+
+impl FriendlyFirePolicy {
+    pub fn get(&self, attacker: Faction, victim: Faction) -> FriendlyFire {
+        self.overrides
+            .get(&(attacker, victim))
+            .copied()
+            .unwrap_or(match attacker == victim {
+                true => self.same_faction,
+                false => FriendlyFire::Allow,
+            })
+    }
+
+    /// Damage multiplier for `source` hitting `target`, or `None` if it's blocked.
+    ///
+    /// Anything without a `Faction` can hurt and be hurt by anything.
+    pub fn multiplier(
+        &self,
+        source: Option<Entity>,
+        target: Entity,
+        faction_query: &Query<&Faction>,
+    ) -> Option<f32> {
+        let Some(source) = source else {
+            return Some(1.0);
+        };
+        if source == target {
+            return self.self_damage.multiplier();
+        }
+        match (faction_query.get(source), faction_query.get(target)) {
+            (Ok(attacker), Ok(victim)) => self.get(*attacker, *victim).multiplier(),
+            _ => Some(1.0),
+        }
+    }
+}
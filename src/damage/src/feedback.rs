@@ -0,0 +1,419 @@
+//! Floating damage numbers, and flashing outlines.
+
+use bevy::{
+    prelude::*,
+    render::{camera::RenderTarget, view::RenderLayers},
+    ui::FocusPolicy,
+};
+use grin_render::{
+    outline::{OutlineOverride, OutlineOverrideCommandsExt, DAMAGE_FLASH_PRIORITY},
+    RenderLayer,
+};
+
+use crate::{
+    health::{apply_damage_buffers, apply_resist, DamageBuffer, Dead, Health, Invulnerable},
+    hit::DamageVariant,
+};
+
+pub struct DamageFeedbackPlugin;
+
+impl Plugin for DamageFeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DamageNumberConfig>()
+            .add_event::<DamageNumberEvent>()
+            .add_systems(
+                Update,
+                (
+                    send_damage_numbers
+                        .after(apply_resist)
+                        .before(apply_damage_buffers),
+                    (spawn_damage_numbers, float_damage_numbers)
+                        .chain()
+                        .after(send_damage_numbers),
+                    (flash_damaged_outlines, tick_damage_flashes)
+                        .chain()
+                        .after(send_damage_numbers),
+                ),
+            );
+    }
+}
+
+/// Frames that the outline flashes for after taking damage.
+pub const DAMAGE_FLASH_FRAMES: u32 = 4;
+
+/// Sent for each `Damage` in a `DamageBuffer`, after `Resist`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct DamageNumberEvent {
+    pub amount: f32,
+    pub variant: DamageVariant,
+    pub world_pos: Vec3,
+    /// What took the damage.
+    pub target: Entity,
+}
+
+#[derive(Resource, Debug, Clone)]
+pub struct DamageNumberConfig {
+    /// Seconds before despawning.
+    pub lifetime: f32,
+    /// Pixels per second upwards.
+    pub rise_speed: f32,
+    /// Numbers on the same target within this many seconds are added together.
+    pub merge_window: f32,
+    pub font_size: f32,
+    /// Numbers at least this big are "crits".
+    pub crit_threshold: f32,
+    pub crit_font_size: f32,
+    pub crit_color: Color,
+}
+
+impl Default for DamageNumberConfig {
+    fn default() -> Self {
+        Self {
+            lifetime: 0.7,
+            rise_speed: 48.0,
+            merge_window: 0.2,
+            font_size: 24.0,
+            crit_threshold: 20.0,
+            crit_font_size: 36.0,
+            crit_color: Color::rgb(1.0, 0.9, 0.2),
+        }
+    }
+}
+
+impl DamageNumberConfig {
+    pub fn style(&self, amount: f32, variant: DamageVariant) -> TextStyle {
+        let crit = amount >= self.crit_threshold;
+        TextStyle {
+            font_size: match crit {
+                true => self.crit_font_size,
+                false => self.font_size,
+            },
+            color: match (crit, variant) {
+                (true, _) => self.crit_color,
+                (false, DamageVariant::Ballistic) => Color::WHITE,
+                (false, DamageVariant::Fire) => Color::ORANGE_RED,
+                (false, DamageVariant::Toxin) => Color::LIME_GREEN,
+            },
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+pub struct DamageNumber {
+    pub target: Entity,
+    pub world_pos: Vec3,
+    pub amount: f32,
+    pub variant: DamageVariant,
+    /// Seconds since spawning, or since the last merge.
+    pub age: f32,
+}
+
+fn format_amount(amount: f32) -> String {
+    match amount {
+        a if a < 1.0 => format!("{:.1}", a),
+        a => format!("{:.0}", a),
+    }
+}
+
+/// Sends `DamageNumberEvent`s. Has to run between `apply_resist` and `apply_damage_buffers`.
+pub fn send_damage_numbers(
+    damage_query: Query<
+        (Entity, &DamageBuffer, &GlobalTransform),
+        (With<Health>, Without<Dead>, Without<Invulnerable>),
+    >,
+    mut events: EventWriter<DamageNumberEvent>,
+) {
+    for (entity, damage_buf, g_transform) in damage_query.iter() {
+        for damage in damage_buf.0.iter().filter(|d| d.value > 0.0) {
+            events.send(DamageNumberEvent {
+                amount: damage.value,
+                variant: damage.ty,
+                world_pos: g_transform.translation(),
+                target: entity,
+            });
+        }
+    }
+}
+
+/// The camera that draws `RenderLayer::STANDARD` to the window.
+/// Damage numbers only show up on this one, so they don't end up in the GoPro.
+fn find_standard_camera<'a>(
+    camera_query: &'a Query<(Entity, &Camera, &GlobalTransform, Option<&RenderLayers>)>,
+) -> Option<(Entity, &'a Camera, &'a GlobalTransform)> {
+    let standard = RenderLayers::layer(RenderLayer::STANDARD as u8);
+    camera_query
+        .iter()
+        .filter(|(_, camera, ..)| camera.is_active)
+        .filter(|(_, camera, ..)| matches!(camera.target, RenderTarget::Window(_)))
+        .filter(|(.., layers)| layers.map_or(true, |l| l.intersects(&standard)))
+        .max_by_key(|(_, camera, ..)| camera.order)
+        .map(|(e, camera, g_transform, _)| (e, camera, g_transform))
+}
+
+pub fn spawn_damage_numbers(
+    mut commands: Commands,
+    config: Res<DamageNumberConfig>,
+    camera_query: Query<(Entity, &Camera, &GlobalTransform, Option<&RenderLayers>)>,
+    mut number_query: Query<(&mut DamageNumber, &mut Text)>,
+    mut events: EventReader<DamageNumberEvent>,
+) {
+    let camera = find_standard_camera(&camera_query).map(|(e, ..)| e);
+    // spawned this frame, which the query can't see yet
+    let mut spawned = Vec::<(Entity, DamageNumber)>::new();
+
+    for &DamageNumberEvent {
+        amount,
+        variant,
+        world_pos,
+        target,
+    } in events.read()
+    {
+        let merge_window = config.merge_window;
+        let mergeable = |number: &DamageNumber| {
+            number.target == target && number.variant == variant && number.age < merge_window
+        };
+
+        if let Some((mut number, mut text)) = number_query
+            .iter_mut()
+            .find(|(number, _)| mergeable(number))
+        {
+            number.amount += amount;
+            number.world_pos = world_pos;
+            number.age = 0.0;
+            text.sections[0] = TextSection::new(
+                format_amount(number.amount),
+                config.style(number.amount, variant),
+            );
+            continue;
+        }
+
+        if let Some((_, number)) = spawned.iter_mut().find(|(_, number)| mergeable(number)) {
+            number.amount += amount;
+            continue;
+        }
+
+        let e_number = commands.spawn_empty().id();
+        spawned.push((
+            e_number,
+            DamageNumber {
+                target,
+                world_pos,
+                amount,
+                variant,
+                age: 0.0,
+            },
+        ));
+    }
+
+    for (e_number, number) in spawned {
+        let mut e_number = commands.entity(e_number);
+        e_number.insert((
+            TextBundle {
+                text: Text::from_section(
+                    format_amount(number.amount),
+                    config.style(number.amount, number.variant),
+                ),
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    ..Default::default()
+                },
+                focus_policy: FocusPolicy::Pass,
+                // hidden until it gets positioned
+                visibility: Visibility::Hidden,
+                z_index: ZIndex::Global(500),
+                ..Default::default()
+            },
+            number,
+        ));
+        if let Some(camera) = camera {
+            e_number.insert(TargetCamera(camera));
+        }
+    }
+}
+
+/// Moves damage numbers up, fades them out, and despawns them.
+pub fn float_damage_numbers(
+    mut commands: Commands,
+    time: Res<Time>,
+    config: Res<DamageNumberConfig>,
+    camera_query: Query<(Entity, &Camera, &GlobalTransform, Option<&RenderLayers>)>,
+    mut number_query: Query<(
+        Entity,
+        &mut DamageNumber,
+        &mut Text,
+        &mut Style,
+        &mut Visibility,
+        &Node,
+    )>,
+) {
+    let camera = find_standard_camera(&camera_query);
+
+    for (e_number, mut number, mut text, mut style, mut visibility, node) in number_query.iter_mut()
+    {
+        number.age += time.delta_seconds();
+        if number.age >= config.lifetime {
+            commands.entity(e_number).despawn_recursive();
+            continue;
+        }
+
+        let alpha = 1.0 - number.age / config.lifetime;
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(alpha);
+        }
+
+        let Some(viewport_pos) = camera.and_then(|(_, camera, g_camera_transform)| {
+            camera.world_to_viewport(g_camera_transform, number.world_pos)
+        }) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        // centered on the target, floating upwards
+        let offset = node.size() / 2.0 + Vec2::Y * config.rise_speed * number.age;
+        style.left = Val::Px(viewport_pos.x - offset.x);
+        style.top = Val::Px(viewport_pos.y - offset.y);
+        *visibility = Visibility::Inherited;
+    }
+}
+
+/// Flashes the outline white and red, swapping every frame.
+#[derive(Component, Debug)]
+pub struct DamageFlash {
+    /// Frames so far.
+    pub frames: u32,
+}
+
+pub fn flash_damaged_outlines(mut commands: Commands, mut events: EventReader<DamageNumberEvent>) {
+    for DamageNumberEvent { target, .. } in events.read() {
+        if let Some(mut e) = commands.get_entity(*target) {
+            e.insert(DamageFlash { frames: 0 });
+        }
+    }
+}
+
+pub fn tick_damage_flashes(
+    mut commands: Commands,
+    mut flash_query: Query<(Entity, &mut DamageFlash)>,
+) {
+    for (entity, mut flash) in flash_query.iter_mut() {
+        if flash.frames >= DAMAGE_FLASH_FRAMES {
+            commands
+                .entity(entity)
+                .remove::<DamageFlash>()
+                .clear_outline_override("damage_flash");
+            continue;
+        }
+
+        let color = match flash.frames % 2 {
+            0 => Color::WHITE,
+            _ => Color::RED,
+        };
+        commands.entity(entity).set_outline_override(
+            "damage_flash",
+            OutlineOverride::color(color, DAMAGE_FLASH_PRIORITY),
+        );
+        flash.frames += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grin_render::outline::OutlineOverrides;
+
+    use crate::hit::Damage;
+
+    use super::*;
+
+    #[test]
+    fn merge() {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<DamageNumberConfig>()
+            .add_event::<DamageNumberEvent>()
+            .add_systems(Update, (send_damage_numbers, spawn_damage_numbers).chain());
+
+        let damage = |value| Damage {
+            ty: DamageVariant::Ballistic,
+            value,
+            source: None,
+        };
+        let e_target = app
+            .world
+            .spawn((
+                Health(100.0),
+                DamageBuffer(vec![damage(5.0), damage(5.0)]),
+                GlobalTransform::default(),
+            ))
+            .id();
+        let e_other = app
+            .world
+            .spawn((
+                Health(100.0),
+                DamageBuffer(vec![damage(30.0)]),
+                GlobalTransform::default(),
+            ))
+            .id();
+
+        app.update();
+        app.world.get_mut::<DamageBuffer>(e_target).unwrap().0 = vec![damage(2.0)];
+        app.world
+            .get_mut::<DamageBuffer>(e_other)
+            .unwrap()
+            .0
+            .clear();
+        app.update();
+
+        let mut numbers = app.world.query::<&DamageNumber>();
+        let numbers = numbers.iter(&app.world).collect::<Vec<_>>();
+        assert_eq!(
+            numbers.len(),
+            2,
+            "Numbers on the same target weren't merged."
+        );
+        for number in numbers {
+            match number.target {
+                e if e == e_target => assert_eq!(number.amount, 12.0),
+                e if e == e_other => assert_eq!(number.amount, 30.0),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn damage_flash() {
+        let mut app = App::new();
+        app.add_event::<DamageNumberEvent>().add_systems(
+            Update,
+            (flash_damaged_outlines, tick_damage_flashes).chain(),
+        );
+
+        let e_target = app.world.spawn_empty().id();
+        app.world.send_event(DamageNumberEvent {
+            amount: 1.0,
+            variant: DamageVariant::Ballistic,
+            world_pos: Vec3::ZERO,
+            target: e_target,
+        });
+
+        let flash_color = |app: &App| {
+            app.world
+                .get::<OutlineOverrides>(e_target)
+                .and_then(|o| o.0.get("damage_flash"))
+                .and_then(|o| o.color)
+        };
+
+        app.update();
+        assert_eq!(flash_color(&app), Some(Color::WHITE), "Didn't flash white.");
+        app.update();
+        assert_eq!(flash_color(&app), Some(Color::RED), "Didn't flash red.");
+        for _ in 0..DAMAGE_FLASH_FRAMES - 1 {
+            app.update();
+        }
+        assert_eq!(flash_color(&app), None, "Flash didn't stop.");
+        assert!(
+            app.world.get::<DamageFlash>(e_target).is_none(),
+            "Flash wasn't removed."
+        );
+    }
+}
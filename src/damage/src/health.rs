@@ -1,4 +1,7 @@
+use std::time::Duration;
+
 use bevy::{prelude::*, utils::HashMap};
+use grin_physics::PhysicsTime;
 
 use crate::{
     hit::{Damage, DamageVariant},
@@ -10,15 +13,20 @@ pub struct HealthPlugin;
 
 impl Plugin for HealthPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                propagate_damage_buffers.in_set(DamageSet::Propagate),
-                apply_resist.in_set(DamageSet::Resist),
-                apply_damage_buffers.in_set(DamageSet::Clear),
-                die.in_set(DamageSet::Kill),
-            ),
-        );
+        app.add_event::<DeathEvent>()
+            .add_event::<ShieldBrokenEvent>()
+            .add_event::<CritEvent>()
+            .add_systems(
+                Update,
+                (
+                    propagate_damage_buffers.in_set(DamageSet::Propagate),
+                    apply_resist.in_set(DamageSet::Resist),
+                    tick_invulnerability.before(apply_damage_buffers),
+                    recharge_shields.before(apply_damage_buffers),
+                    apply_damage_buffers.in_set(DamageSet::Clear),
+                    die.in_set(DamageSet::Kill),
+                ),
+            );
     }
 }
 
@@ -38,7 +46,60 @@ impl Default for Health {
 ///
 /// Scales linearly from no resist at `0.0` to full resist at `1.0`. All values default to `0.0`.
 #[derive(Component, Debug, Default)]
-pub struct Resist(HashMap<DamageVariant, f32>);
+pub struct Resist(pub HashMap<DamageVariant, f32>);
+
+/// Takes damage before `Health` does. Whatever it can't take spills over into `Health`.
+#[derive(Component, Debug, Clone)]
+pub struct Shield {
+    pub current: f32,
+    pub max: f32,
+    /// Per second.
+    pub recharge_rate: f32,
+    /// Seconds without taking damage before recharging.
+    pub recharge_delay: f32,
+    /// Seconds since last taking damage.
+    pub since_damaged: f32,
+}
+
+impl Shield {
+    /// Starts out full.
+    pub fn new(max: f32, recharge_rate: f32, recharge_delay: f32) -> Self {
+        Self {
+            current: max,
+            max,
+            recharge_rate,
+            recharge_delay,
+            since_damaged: 0.0,
+        }
+    }
+
+    /// Depletes the shield, with `resist` from `ShieldResist`.
+    /// Returns the part of `value` that didn't get absorbed, before `Resist`.
+    pub fn absorb(&mut self, value: f32, resist: f32) -> f32 {
+        if self.current <= 0.0 || value <= 0.0 {
+            return value;
+        }
+        let scaled = value * (1.0 - resist);
+        if scaled <= self.current {
+            self.current -= scaled;
+            return 0.0;
+        }
+        // `resist` can't be `1.0` here, or `scaled` would have been zero
+        let covered = self.current / (1.0 - resist);
+        self.current = 0.0;
+        value - covered
+    }
+}
+
+/// Like `Resist`, but for `Shield`. `Resist` only applies to what spills over into `Health`.
+#[derive(Component, Debug, Default)]
+pub struct ShieldResist(pub HashMap<DamageVariant, f32>);
+
+/// Sent the frame a `Shield` runs out.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShieldBrokenEvent {
+    pub entity: Entity,
+}
 
 #[derive(Bundle, Default)]
 pub struct HealthBundle {
@@ -53,8 +114,58 @@ pub struct HealthBundle {
 #[derive(Component, Default)]
 pub struct DamageBuffer(pub Vec<Damage>);
 
+/// Damage is thrown out instead of applied while this is around.
+#[derive(Component, Debug, Clone)]
+pub struct Invulnerable {
+    /// Removes this component when finished. `None` lasts until removed manually.
+    pub timer: Option<Timer>,
+    /// Whether contact hits are still sent as `DamageEvent::Contact` with `absorbed` set,
+    /// so effects can still play.
+    pub send_absorbed: bool,
+}
+
+impl Default for Invulnerable {
+    fn default() -> Self {
+        Self {
+            timer: None,
+            send_absorbed: true,
+        }
+    }
+}
+
+impl Invulnerable {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            timer: Some(Timer::new(duration, TimerMode::Once)),
+            ..Default::default()
+        }
+    }
+
+    pub fn from_seconds(duration: f32) -> Self {
+        Self::new(Duration::from_secs_f32(duration))
+    }
+}
+
+/// Removes `Invulnerable` when its timer runs out.
+pub fn tick_invulnerability(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut query: Query<(Entity, &mut Invulnerable)>,
+) {
+    for (entity, mut invulnerable) in query.iter_mut() {
+        let Some(timer) = invulnerable.timer.as_mut() else {
+            continue;
+        };
+        if timer.tick(time.0.delta()).finished() {
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
 /// Applies resistance by scaling `Damage` values.
-pub fn apply_resist(mut query: Query<(&mut DamageBuffer, &Resist)>) {
+///
+/// `Shield`ed entities are skipped, `apply_damage_buffers` handles those.
+pub fn apply_resist(mut query: Query<(&mut DamageBuffer, &Resist), Without<Shield>>) {
     for (mut damage_buf, Resist(resist)) in query.iter_mut() {
         for damage in damage_buf.0.iter_mut() {
             let r = resist.get(&damage.ty).unwrap_or(&0.0);
@@ -63,39 +174,175 @@ pub fn apply_resist(mut query: Query<(&mut DamageBuffer, &Resist)>) {
     }
 }
 
-/// Recursively empties `DamageBuffer`s for entities without a `Health` component
-/// and appends them to the `DamageBuffer` of the `HitboxManager` `Health`.
+/// Multiplies damage going through this entity, or its descendants, e.g. for headshots.
+///
+/// Only the closest one applies.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct PartDamageScale(pub f32);
+
+impl Default for PartDamageScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// Sent when damage goes through a `PartDamageScale` above `1.0`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct CritEvent {
+    /// The entity with `Health`.
+    pub entity: Entity,
+    /// The entity that got hit.
+    pub part: Entity,
+    pub scale: f32,
+    /// After scaling.
+    pub damage: Damage,
+}
+
+/// Empties `DamageBuffer`s for entities without a `Health` component and appends them to the
+/// `DamageBuffer` of the `Hitbox` target, or the closest ancestor with `Health` if it isn't a
+/// `Hitbox`. Applies `PartDamageScale` on the way.
 pub fn propagate_damage_buffers(
     mut health_query: Query<&mut DamageBuffer, With<Health>>,
-    mut hitbox_query: Query<(&Hitbox, &mut DamageBuffer), (Without<Health>, Changed<DamageBuffer>)>,
+    mut hitbox_query: Query<
+        (Entity, Option<&Hitbox>, &mut DamageBuffer),
+        (Without<Health>, Changed<DamageBuffer>),
+    >,
+    parent_query: Query<&Parent>,
+    scale_query: Query<&PartDamageScale>,
+    mut crit_events: EventWriter<CritEvent>,
 ) {
-    for (Hitbox { target: e_health }, mut src_buf) in hitbox_query.iter_mut() {
-        let Ok(mut dst_buf) = health_query.get_mut(*e_health) else {
+    for (e_part, hitbox, mut src_buf) in hitbox_query.iter_mut() {
+        if src_buf.0.is_empty() {
+            continue;
+        }
+
+        let e_health = match hitbox {
+            Some(Hitbox { target }) => Some(*target),
+            None => parent_query
+                .iter_ancestors(e_part)
+                .find(|e| health_query.contains(*e)),
+        };
+        let Some(mut dst_buf) = e_health.and_then(|e| health_query.get_mut(e).ok()) else {
             error!(
                 error="Cannot propagate damage buffer to entity without `Health`.",
                 entity=?e_health,
             );
             continue;
         };
-        dst_buf.0.append(&mut src_buf.0);
+
+        let scale = std::iter::once(e_part)
+            .chain(parent_query.iter_ancestors(e_part))
+            .take_while(|e| Some(*e) != e_health)
+            .find_map(|e| scale_query.get(e).ok())
+            .map_or(1.0, |s| s.0);
+
+        for mut damage in src_buf.0.drain(..) {
+            damage.value *= scale;
+            if scale > 1.0 {
+                crit_events.send(CritEvent {
+                    entity: e_health.unwrap(),
+                    part: e_part,
+                    scale,
+                    damage,
+                });
+            }
+            dst_buf.0.push(damage);
+        }
     }
 }
 
-/// Applies damage values from `DamageBuffer`.
-pub fn apply_damage_buffers(mut query: Query<(&mut Health, &mut DamageBuffer), Without<Dead>>) {
-    for (mut health, mut damage_buf) in query.iter_mut() {
+/// The `Damage.source` of the most recent damage that had one.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastDamagedBy(pub Entity);
+
+/// Sent when something dies.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    /// The `LastDamagedBy` entity.
+    pub killer: Option<Entity>,
+}
+
+/// Applies damage values from `DamageBuffer`, to the `Shield` first if there is one.
+pub fn apply_damage_buffers(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &mut Health,
+            &mut DamageBuffer,
+            Has<Invulnerable>,
+            Option<&mut LastDamagedBy>,
+            Option<(&mut Shield, Option<&ShieldResist>, Option<&Resist>)>,
+        ),
+        Without<Dead>,
+    >,
+    mut shield_events: EventWriter<ShieldBrokenEvent>,
+) {
+    for (entity, mut health, mut damage_buf, invulnerable, mut last_damaged_by, mut shield) in
+        query.iter_mut()
+    {
+        if invulnerable {
+            damage_buf.0.clear();
+            continue;
+        }
+        let mut source = None;
         for damage in damage_buf.0.drain(0..) {
-            health.0 = (health.0 - damage.value).max(0.0);
+            let value = match shield.as_mut() {
+                Some((shield, shield_resist, resist)) => {
+                    let resist_of = |r: Option<&HashMap<DamageVariant, f32>>| {
+                        r.and_then(|r| r.get(&damage.ty)).copied().unwrap_or(0.0)
+                    };
+                    if damage.value > 0.0 {
+                        shield.since_damaged = 0.0;
+                    }
+                    let was_up = shield.current > 0.0;
+                    let spill = shield.absorb(damage.value, resist_of(shield_resist.map(|r| &r.0)));
+                    if was_up && shield.current <= 0.0 {
+                        shield_events.send(ShieldBrokenEvent { entity });
+                    }
+                    spill * (1.0 - resist_of(resist.map(|r| &r.0)))
+                }
+                None => damage.value,
+            };
+            health.0 = (health.0 - value).max(0.0);
             info!("health: {}", health.0);
+            source = damage.source.or(source);
+        }
+        match (source, last_damaged_by.as_deref_mut()) {
+            (Some(source), Some(last_damaged_by)) => last_damaged_by.0 = source,
+            (Some(source), None) => {
+                commands.entity(entity).insert(LastDamagedBy(source));
+            }
+            (None, _) => (),
+        }
+    }
+}
+
+/// Recharges `Shield`s that haven't taken damage in a while.
+pub fn recharge_shields(time: Res<PhysicsTime>, mut query: Query<&mut Shield, Without<Dead>>) {
+    for mut shield in query.iter_mut() {
+        let dt = time.0.delta_seconds();
+        shield.since_damaged += dt;
+        if shield.since_damaged >= shield.recharge_delay && shield.current < shield.max {
+            shield.current = (shield.current + shield.recharge_rate * dt).min(shield.max);
         }
     }
 }
 
 /// Inserts `Dead` component.
-pub fn die(mut commands: Commands, health_query: Query<(Entity, &Health)>) {
-    for (entity, health) in health_query.iter() {
+pub fn die(
+    mut commands: Commands,
+    health_query: Query<(Entity, &Health, Option<&LastDamagedBy>), Without<Dead>>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    for (entity, health, last_damaged_by) in health_query.iter() {
         if health.0 == 0.0 {
             commands.entity(entity).insert(Dead);
+            death_events.send(DeathEvent {
+                entity,
+                killer: last_damaged_by.map(|l| l.0),
+            });
         }
     }
 }
@@ -112,7 +359,8 @@ mod tests {
     #[test]
     fn damage() {
         let mut app = App::new();
-        app.add_systems(Update, apply_damage_buffers);
+        app.add_event::<ShieldBrokenEvent>()
+            .add_systems(Update, apply_damage_buffers);
 
         let damage_dst = app
             .world
@@ -171,7 +419,8 @@ mod tests {
     #[test]
     fn resist() {
         let mut app = App::new();
-        app.add_systems(Update, (apply_resist, apply_damage_buffers).chain());
+        app.add_event::<ShieldBrokenEvent>()
+            .add_systems(Update, (apply_resist, apply_damage_buffers).chain());
 
         let damage_dst = app
             .world
@@ -198,7 +447,8 @@ mod tests {
     #[test]
     fn propagation() {
         let mut app = App::new();
-        app.add_systems(Update, propagate_damage_buffers);
+        app.add_event::<CritEvent>()
+            .add_systems(Update, propagate_damage_buffers);
 
         let child = app.world.spawn(DamageBuffer(vec![Damage::default()])).id();
 
@@ -223,10 +473,64 @@ mod tests {
         );
     }
 
+    #[test]
+    fn invulnerability() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_event::<ShieldBrokenEvent>()
+            .add_systems(
+                Update,
+                (tick_invulnerability, apply_deferred, apply_damage_buffers).chain(),
+            );
+
+        let ballistic = |value| Damage {
+            ty: DamageVariant::Ballistic,
+            value,
+            source: None,
+        };
+        let timed = app
+            .world
+            .spawn((Health(100.0), Invulnerable::from_seconds(1.0)))
+            .id();
+        let forever = app
+            .world
+            .spawn((Health(100.0), Invulnerable::default()))
+            .id();
+
+        for millis in [500, 400, 200, 1000] {
+            app.world
+                .resource_mut::<PhysicsTime>()
+                .0
+                .advance_by(Duration::from_millis(millis));
+            for e in [timed, forever] {
+                app.world
+                    .entity_mut(e)
+                    .insert(DamageBuffer(vec![ballistic(10.0)]));
+            }
+            app.update();
+        }
+
+        assert_eq!(
+            app.world.get::<Health>(timed).unwrap().0,
+            80.0,
+            "Damage wasn't ignored during invulnerability, or wasn't applied after.",
+        );
+        assert!(!app.world.entity(timed).contains::<Invulnerable>());
+        assert_eq!(
+            app.world.get::<Health>(forever).unwrap().0,
+            100.0,
+            "Timer-less invulnerability wore off.",
+        );
+        assert!(
+            app.world.get::<DamageBuffer>(forever).unwrap().0.is_empty(),
+            "`DamageBuffer` wasn't drained.",
+        );
+    }
+
     #[test]
     fn death() {
         let mut app = App::new();
-        app.add_systems(Update, die);
+        app.add_event::<DeathEvent>().add_systems(Update, die);
 
         let e = app.world.spawn(Health(0.0)).id();
 
@@ -234,4 +538,144 @@ mod tests {
 
         assert!(app.world.entity(e).contains::<Dead>());
     }
+
+    fn shield_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_event::<ShieldBrokenEvent>()
+            .add_systems(
+                Update,
+                (apply_resist, recharge_shields, apply_damage_buffers).chain(),
+            );
+        app
+    }
+
+    fn hit(app: &mut App, entity: Entity, millis: u64, value: f32) {
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_millis(millis));
+        app.world
+            .get_mut::<DamageBuffer>(entity)
+            .unwrap()
+            .0
+            .push(Damage {
+                ty: DamageVariant::Ballistic,
+                value,
+                source: None,
+            });
+        app.update();
+    }
+
+    #[test]
+    fn shield_spill() {
+        let mut app = shield_app();
+        let e = app
+            .world
+            .spawn((
+                Health(100.0),
+                DamageBuffer::default(),
+                Shield::new(30.0, 10.0, 1.0),
+                ShieldResist(HashMap::from([(DamageVariant::Ballistic, 0.5)])),
+                Resist(HashMap::from([(DamageVariant::Ballistic, 0.5)])),
+            ))
+            .id();
+
+        // 60 of this gets halved by the shield, the other 20 gets halved by `Resist`
+        hit(&mut app, e, 0, 80.0);
+        assert_eq!(app.world.get::<Shield>(e).unwrap().current, 0.0);
+        assert_eq!(
+            app.world.get::<Health>(e).unwrap().0,
+            90.0,
+            "Damage didn't spill over correctly.",
+        );
+
+        hit(&mut app, e, 0, 10.0);
+        assert_eq!(app.world.get::<Health>(e).unwrap().0, 85.0);
+
+        let events = app.world.resource::<Events<ShieldBrokenEvent>>();
+        assert_eq!(
+            events.get_reader().read(events).count(),
+            1,
+            "`ShieldBrokenEvent` wasn't sent exactly once.",
+        );
+    }
+
+    #[test]
+    fn shield_recharge() {
+        let mut app = shield_app();
+        let e = app
+            .world
+            .spawn((
+                Health(100.0),
+                DamageBuffer::default(),
+                Shield::new(10.0, 10.0, 1.0),
+            ))
+            .id();
+        let shield = |app: &App| app.world.get::<Shield>(e).unwrap().current;
+
+        hit(&mut app, e, 0, 5.0);
+        assert_eq!(shield(&app), 5.0);
+        hit(&mut app, e, 500, 1.0);
+        hit(&mut app, e, 750, 0.0);
+        assert_eq!(
+            shield(&app),
+            4.0,
+            "Getting hit didn't interrupt recharging."
+        );
+        hit(&mut app, e, 500, 0.0);
+        assert_eq!(shield(&app), 9.0, "Didn't recharge after the delay.");
+        hit(&mut app, e, 500, 0.0);
+        assert_eq!(shield(&app), 10.0, "Recharged past the max.");
+        assert_eq!(app.world.get::<Health>(e).unwrap().0, 100.0);
+    }
+
+    #[test]
+    fn part_damage_scale() {
+        let mut app = App::new();
+        app.add_event::<CritEvent>()
+            .add_systems(Update, propagate_damage_buffers);
+
+        let damage = || {
+            DamageBuffer(vec![Damage {
+                ty: DamageVariant::Ballistic,
+                value: 10.0,
+                source: None,
+            }])
+        };
+        let e_mesh = app.world.spawn(damage()).id();
+        let e_head = app
+            .world
+            .spawn((PartDamageScale(2.0), damage()))
+            .add_child(e_mesh)
+            .id();
+        let e_body = app
+            .world
+            .spawn((PartDamageScale(1.0), damage()))
+            .add_child(e_head)
+            .id();
+        let e_root = app
+            .world
+            .spawn((Health(100.0), DamageBuffer::default()))
+            .add_child(e_body)
+            .id();
+
+        app.update();
+
+        let values = app
+            .world
+            .get::<DamageBuffer>(e_root)
+            .unwrap()
+            .0
+            .iter()
+            .map(|d| d.value)
+            .sum::<f32>();
+        assert_eq!(
+            values, 50.0,
+            "`PartDamageScale` wasn't applied exactly once per hit."
+        );
+
+        let events = app.world.resource::<Events<CritEvent>>();
+        assert_eq!(events.get_reader().read(events).count(), 2);
+    }
 }
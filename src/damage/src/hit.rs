@@ -1,13 +1,24 @@
 use std::time::Duration;
 
 use bevy::{
-    ecs::{entity::EntityHashSet, query::QueryEntityError},
+    ecs::{
+        entity::{Entities, EntityHashMap, EntityHashSet},
+        query::QueryEntityError,
+    },
     prelude::*,
 };
 use bevy_rapier3d::prelude::*;
+use grin_physics::PhysicsTime;
 use grin_util::query::distinguish_by_query;
 
-use crate::{health::DamageBuffer, hitbox::Hitbox, plugin::DamageSet};
+use crate::{
+    dot::DamageOverTime,
+    faction::{Faction, FriendlyFirePolicy},
+    health::{DamageBuffer, Health, Invulnerable},
+    hitbox::Hitbox,
+    plugin::DamageSet,
+    projectiles::ProjectileCommandsExt,
+};
 
 pub struct ContactDamagePlugin;
 
@@ -15,7 +26,12 @@ impl Plugin for ContactDamagePlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<DamageEvent>()
             .add_systems(PreUpdate, send_contact_damage_events)
-            .add_systems(Update, push_contact_damage.in_set(DamageSet::Add));
+            .add_systems(
+                Update,
+                (tick_contact_debounces, push_contact_damage)
+                    .chain()
+                    .in_set(DamageSet::Add),
+            );
     }
 }
 /// Damage variant.
@@ -23,6 +39,8 @@ impl Plugin for ContactDamagePlugin {
 pub enum DamageVariant {
     #[default]
     Ballistic,
+    Fire,
+    Toxin,
 }
 
 /// Damage.
@@ -33,6 +51,8 @@ pub struct Damage {
     /// Damage amount.
     pub value: f32,
     /// The `Entity` that dealt the damage.
+    ///
+    /// Contact damage credits this to the closest ancestor with `Health`, i.e. the owner.
     pub source: Option<Entity>,
 }
 
@@ -47,10 +67,11 @@ pub enum ContactDamage {
     #[default]
     FollowThrough,
     /// This entity is despawned after contact damage event is fired.
+    /// `PooledProjectile`s go back to the `ProjectilePool` instead.
     Despawn,
     /// This component is removed after contact damage event is fired.
     Once,
-    /// Contact damage events are disabled for `0`.
+    /// Contact damage is disabled for `0` after hitting, per target. See `ContactDebounce`.
     Debounce(Duration),
 }
 
@@ -65,10 +86,27 @@ pub enum DamageEvent {
         kind: ContactDamage,
         e_damage: Entity,
         e_hit: Entity,
+        /// The target was `Invulnerable`, so no damage gets applied.
+        absorbed: bool,
     },
     /// Direct damage.
     // TODO: system to apply this damage, when it actually ends up getting used.
     Direct { damage: Damage, e_hit: Entity },
+    /// Damage over time. Stacks according to `DotStackingPolicy`.
+    OverTime { dot: DamageOverTime, e_hit: Entity },
+}
+
+/// Targets recently hit by a `ContactDamage::Debounce`, and how long until they can be hit again.
+///
+/// Inserted automatically. Targets are the `Hitbox` target, if the hit entity was a `Hitbox`.
+#[derive(Component, Debug, Default)]
+pub struct ContactDebounce(pub EntityHashMap<Timer>);
+
+impl ContactDebounce {
+    /// Whether `target` can be hit right now.
+    pub fn ready(&self, target: Entity) -> bool {
+        self.0.get(&target).map_or(true, Timer::finished)
+    }
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -107,8 +145,17 @@ impl MacroCollisionFilter {
 }
 
 pub fn send_contact_damage_events(
-    mut damage_query: Query<(&ContactDamage, Option<&mut MacroCollisionFilter>)>,
+    policy: Res<FriendlyFirePolicy>,
+    mut damage_query: Query<(
+        &ContactDamage,
+        Option<&Damage>,
+        Option<&mut MacroCollisionFilter>,
+    )>,
     hitbox_query: Query<&Hitbox>,
+    invulnerable_query: Query<&Invulnerable>,
+    faction_query: Query<&Faction>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
     mut collision_events: EventReader<CollisionEvent>,
     mut damage_events: EventWriter<DamageEvent>,
 ) {
@@ -127,13 +174,38 @@ pub fn send_contact_damage_events(
             continue;
         };
 
-        let (damage_kind, collision_filter) = damage_query.get_mut(e_damage).unwrap();
+        let (damage_kind, damage, collision_filter) = damage_query.get_mut(e_damage).unwrap();
+        let target = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+
+        let invulnerable = invulnerable_query.get(target).ok();
+        if invulnerable.is_some_and(|i| !i.send_absorbed) {
+            trace!(
+                msg="Hit rejected by invulnerability.",
+                dealer=?e_damage,
+                receiver=?e_hit,
+            );
+            continue;
+        }
+
+        let source = credit_damage_owner(
+            damage.and_then(|d| d.source),
+            Some(e_damage),
+            &parent_query,
+            &owner_query,
+        );
+        if policy.multiplier(source, target, &faction_query).is_none() {
+            trace!(
+                msg="Hit rejected by friendly fire policy.",
+                dealer=?e_damage,
+                receiver=?e_hit,
+            );
+            continue;
+        }
 
         if let Some(mut collision_filter) = collision_filter {
-            let Hitbox { target } = hitbox_query.get(e_hit).unwrap();
             if match collision_filter.kind {
-                MacroCollisionFilterKind::Whitelist => collision_filter.cache.insert(*target),
-                MacroCollisionFilterKind::Blacklist => !collision_filter.cache.insert(*target),
+                MacroCollisionFilterKind::Whitelist => collision_filter.cache.insert(target),
+                MacroCollisionFilterKind::Blacklist => !collision_filter.cache.insert(target),
             } {
                 trace!(
                     msg="Hit rejected by MacroCollisionFilter.",
@@ -155,20 +227,35 @@ pub fn send_contact_damage_events(
             kind: *damage_kind,
             e_damage,
             e_hit,
+            absorbed: invulnerable.is_some(),
         });
     }
 }
 
-fn try_push_damage<'a>(
-    e_damage: Entity,
-    e_hit: Entity,
-    damage_query: &'a Query<&Damage>,
-    hit_query: &mut Query<&mut DamageBuffer>,
-) -> Result<&'a Damage, QueryEntityError> {
-    let damage = damage_query.get(e_damage)?;
-    let mut damage_buf = hit_query.get_mut(e_hit)?;
-    damage_buf.0.push(*damage);
-    Ok(damage)
+/// Returns `entity` or its closest ancestor with `Health`, e.g. the owner of an item.
+pub fn find_damage_owner(
+    entity: Entity,
+    parent_query: &Query<&Parent>,
+    owner_query: &Query<(), With<Health>>,
+) -> Option<Entity> {
+    std::iter::once(entity)
+        .chain(parent_query.iter_ancestors(entity))
+        .find(|e| owner_query.contains(*e))
+}
+
+/// Finds who gets credit for `Damage` from `e_damage`.
+///
+/// Credit goes to whoever is holding the weapon, falling back to `source` as is.
+pub fn credit_damage_owner(
+    source: Option<Entity>,
+    e_damage: Option<Entity>,
+    parent_query: &Query<&Parent>,
+    owner_query: &Query<(), With<Health>>,
+) -> Option<Entity> {
+    source
+        .or(e_damage)
+        .and_then(|e| find_damage_owner(e, parent_query, owner_query))
+        .or(source)
 }
 
 pub fn push_contact_damage(
@@ -176,12 +263,22 @@ pub fn push_contact_damage(
     mut hit_query: Query<&mut DamageBuffer>,
     mut damage_events: EventReader<DamageEvent>,
     damage_query: Query<&Damage>,
+    hitbox_query: Query<&Hitbox>,
+    mut debounce_query: Query<&mut ContactDebounce>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
+    faction_query: Query<&Faction>,
+    policy: Res<FriendlyFirePolicy>,
 ) {
+    // debouncers inserted this frame, which the query can't see yet
+    let mut inserted = EntityHashMap::<ContactDebounce>::default();
+
     for damage_event in damage_events.read() {
         let DamageEvent::Contact {
             kind,
             e_damage,
             e_hit,
+            absorbed,
         } = damage_event
         else {
             continue;
@@ -189,19 +286,65 @@ pub fn push_contact_damage(
 
         match kind {
             ContactDamage::Despawn => {
-                commands.get_or_spawn(*e_damage).despawn_recursive();
+                commands.get_or_spawn(*e_damage).release_projectile();
             }
             ContactDamage::Once => {
                 commands.get_or_spawn(*e_damage).remove::<ContactDamage>();
             }
-            ContactDamage::Debounce(_debounce) => {
-                todo!();
+            ContactDamage::Debounce(debounce) => {
+                let target = hitbox_query.get(*e_hit).map_or(*e_hit, |h| h.target);
+                let mut debouncer = debounce_query.get_mut(*e_damage).ok();
+                let debouncer = match debouncer.as_deref_mut() {
+                    Some(debouncer) => debouncer,
+                    None => inserted.entry(*e_damage).or_default(),
+                };
+
+                if !debouncer.ready(target) {
+                    trace!(
+                        msg="Hit rejected by debounce.",
+                        dealer=?e_damage,
+                        receiver=?e_hit,
+                    );
+                    continue;
+                }
+                debouncer
+                    .0
+                    .insert(target, Timer::new(*debounce, TimerMode::Once));
             }
             ContactDamage::FollowThrough => (),
         };
 
-        match try_push_damage(*e_damage, *e_hit, &damage_query, &mut hit_query) {
-            Ok(damage) => {
+        if *absorbed {
+            debug!(
+                msg="Contact damage absorbed.",
+                dealer=?*e_damage,
+                receiver=?*e_hit,
+            );
+            continue;
+        }
+
+        // `Explosion`s don't need `Damage`
+        let Ok(damage) = damage_query.get(*e_damage) else {
+            continue;
+        };
+        let mut damage = *damage;
+        damage.source =
+            credit_damage_owner(damage.source, Some(*e_damage), &parent_query, &owner_query);
+
+        let target = hitbox_query.get(*e_hit).map_or(*e_hit, |h| h.target);
+        let Some(multiplier) = policy.multiplier(damage.source, target, &faction_query) else {
+            trace!(
+                msg="Damage rejected by friendly fire policy.",
+                dealer=?*e_damage,
+                receiver=?*e_hit,
+            );
+            continue;
+        };
+        damage.value *= multiplier;
+
+        match hit_query.get_mut(*e_hit) {
+            Ok(mut damage_buf) => {
+                damage_buf.0.push(damage);
                 info!(
                     msg="Pushed contact damage.",
                     dealer=?*e_damage,
@@ -219,6 +362,71 @@ pub fn push_contact_damage(
             }
         }
     }
+
+    for (e_damage, debouncer) in inserted {
+        commands.get_or_spawn(e_damage).insert(debouncer);
+    }
+}
+
+/// Ticks `ContactDebounce` timers, and forgets targets that expired or don't exist anymore.
+pub fn tick_contact_debounces(
+    time: Res<PhysicsTime>,
+    entities: &Entities,
+    mut debounce_query: Query<&mut ContactDebounce>,
+) {
+    for mut debouncer in debounce_query.iter_mut() {
+        debouncer.0.retain(|target, timer| {
+            !timer.tick(time.0.delta()).finished() && entities.contains(*target)
+        });
+    }
+}
+
+#[derive(Debug)]
+pub enum DamageContactError {
+    EventMismatch(DamageEvent),
+    ItemQueryMismatch(QueryEntityError),
+    NoContactPair(Entity, Entity),
+    NoContact(Entity, Entity),
+}
+
+/// Helper function for finding a collision point.
+pub fn try_find_deepest_contact_point(
+    damage_event: &DamageEvent,
+    rapier_context: &RapierContext,
+    transform_query: &Query<&GlobalTransform>,
+) -> Result<Vec3, DamageContactError> {
+    try_find_deepest_contact(damage_event, rapier_context, transform_query).map(|(p, _)| p)
+}
+
+/// Same as `try_find_deepest_contact_point`, but also gives the normal of the surface that got
+/// hit, pointing out towards the damage.
+pub fn try_find_deepest_contact(
+    damage_event: &DamageEvent,
+    rapier_context: &RapierContext,
+    transform_query: &Query<&GlobalTransform>,
+) -> Result<(Vec3, Vec3), DamageContactError> {
+    let &DamageEvent::Contact {
+        e_damage, e_hit, ..
+    } = damage_event
+    else {
+        return Err(DamageContactError::EventMismatch(damage_event.clone()));
+    };
+    let g_damage_transform = transform_query
+        .get(e_damage)
+        .map_err(DamageContactError::ItemQueryMismatch)?;
+    let contact_pair = rapier_context
+        .contact_pair(e_hit, e_damage)
+        .ok_or(DamageContactError::NoContactPair(e_damage, e_hit))?;
+    let contact = contact_pair
+        .find_deepest_contact()
+        .ok_or(DamageContactError::NoContact(e_damage, e_hit))?;
+    let contact_point = g_damage_transform.transform_point(contact.1.local_p1());
+    // the manifold normal points from the first collider to the second
+    let normal = match contact_pair.collider1() == e_hit {
+        true => contact.0.normal(),
+        false => -contact.0.normal(),
+    };
+    Ok((contact_point, normal))
 }
 
 pub fn clear_macro_collision_filters(
@@ -228,3 +436,181 @@ pub fn clear_macro_collision_filters(
         filter.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        faction::FriendlyFire,
+        health::{apply_damage_buffers, die, DeathEvent, ShieldBrokenEvent},
+    };
+
+    use super::*;
+
+    #[test]
+    fn debounce() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .init_resource::<FriendlyFirePolicy>()
+            .add_event::<DamageEvent>()
+            .add_systems(
+                Update,
+                (tick_contact_debounces, push_contact_damage).chain(),
+            );
+
+        let kind = ContactDamage::Debounce(Duration::from_millis(100));
+        let e_damage = app.world.spawn((kind, Damage::default())).id();
+        let e_hit = app.world.spawn(DamageBuffer::default()).id();
+        let e_other = app.world.spawn(DamageBuffer::default()).id();
+
+        let hit = |app: &mut App, e_hit: Entity, millis: u64| {
+            app.world
+                .resource_mut::<PhysicsTime>()
+                .0
+                .advance_by(Duration::from_millis(millis));
+            app.world.send_event(DamageEvent::Contact {
+                kind,
+                e_damage,
+                e_hit,
+                absorbed: false,
+            });
+            app.update();
+        };
+
+        hit(&mut app, e_hit, 0);
+        hit(&mut app, e_hit, 50);
+        hit(&mut app, e_other, 0);
+        hit(&mut app, e_hit, 60);
+
+        assert_eq!(
+            app.world.get::<DamageBuffer>(e_hit).unwrap().0.len(),
+            2,
+            "Debounce didn't block the second hit, or blocked the third.",
+        );
+        assert_eq!(
+            app.world.get::<DamageBuffer>(e_other).unwrap().0.len(),
+            1,
+            "Debounce blocked a different target.",
+        );
+    }
+
+    #[test]
+    fn kill_credit() {
+        let mut app = App::new();
+        app.init_resource::<FriendlyFirePolicy>()
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_event::<ShieldBrokenEvent>()
+            .add_systems(
+                Update,
+                (
+                    push_contact_damage,
+                    apply_damage_buffers,
+                    apply_deferred,
+                    die,
+                )
+                    .chain(),
+            );
+
+        let e_owner = app.world.spawn(Health(100.0)).id();
+        let e_weapon = app.world.spawn_empty().set_parent(e_owner).id();
+        let e_projectile = app
+            .world
+            .spawn(Damage {
+                ty: DamageVariant::Ballistic,
+                value: 20.0,
+                source: Some(e_weapon),
+            })
+            .id();
+        let e_victim = app
+            .world
+            .spawn((Health(10.0), DamageBuffer::default()))
+            .id();
+
+        app.world.send_event(DamageEvent::Contact {
+            kind: ContactDamage::Despawn,
+            e_damage: e_projectile,
+            e_hit: e_victim,
+            absorbed: false,
+        });
+        app.update();
+
+        let events = app.world.resource::<Events<DeathEvent>>();
+        let deaths = events
+            .get_reader()
+            .read(events)
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            deaths,
+            [DeathEvent {
+                entity: e_victim,
+                killer: Some(e_owner),
+            }],
+            "Kill wasn't credited to the owner.",
+        );
+    }
+
+    #[test]
+    fn friendly_fire() {
+        let mut app = App::new();
+        app.init_resource::<FriendlyFirePolicy>()
+            .add_event::<DamageEvent>()
+            .add_systems(Update, push_contact_damage);
+
+        let e_enemy = app.world.spawn((Health(100.0), Faction::Enemy)).id();
+        let e_projectile = app
+            .world
+            .spawn(Damage {
+                ty: DamageVariant::Ballistic,
+                value: 10.0,
+                source: Some(e_enemy),
+            })
+            .id();
+        let e_other = app
+            .world
+            .spawn((Health(100.0), Faction::Enemy, DamageBuffer::default()))
+            .id();
+        let e_player = app
+            .world
+            .spawn((Health(100.0), Faction::Player, DamageBuffer::default()))
+            .id();
+
+        let hit = |app: &mut App, e_hit: Entity| {
+            app.world.send_event(DamageEvent::Contact {
+                kind: ContactDamage::FollowThrough,
+                e_damage: e_projectile,
+                e_hit,
+                absorbed: false,
+            });
+            app.update();
+            app.world
+                .get_mut::<DamageBuffer>(e_hit)
+                .unwrap()
+                .0
+                .drain(..)
+                .map(|d| d.value)
+                .sum::<f32>()
+        };
+
+        assert_eq!(
+            hit(&mut app, e_player),
+            10.0,
+            "Enemy couldn't hurt the player."
+        );
+        assert_eq!(
+            hit(&mut app, e_other),
+            0.0,
+            "Friendly fire wasn't blocked by default."
+        );
+
+        app.world
+            .resource_mut::<FriendlyFirePolicy>()
+            .overrides
+            .insert((Faction::Enemy, Faction::Enemy), FriendlyFire::Allow);
+        assert_eq!(
+            hit(&mut app, e_other),
+            10.0,
+            "Friendly fire wasn't allowed by the policy."
+        );
+    }
+}
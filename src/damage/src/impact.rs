@@ -8,22 +8,45 @@ use bevy_tweening::{
     Animator, EaseFunction, Tracks, Tween,
 };
 use grin_asset::AssetLoadState;
-use grin_render::sketched::{NoOutline, SketchMaterial};
-use grin_util::event::TweenCompletedEvent;
+use grin_render::{
+    decal::{DecalAssets, DecalKind, SpawnDecalEvent},
+    sketched::{NoOutline, SketchMaterial},
+};
+use grin_util::{
+    event::TweenCompletedEvent,
+    rng::{GrinRng, RNG_FX},
+};
 use rand::prelude::*;
 use rand_distr::UnitSphere;
 
+use crate::{
+    hit::{push_contact_damage, try_find_deepest_contact, DamageEvent},
+    projectiles::BulletProjectile,
+};
+
+/// Bullet holes are this big across.
+pub const BULLET_HOLE_SIZE: f32 = 0.15;
+
 pub struct ImpactPlugin;
 
 impl Plugin for ImpactPlugin {
     fn build(&self, app: &mut App) {
-        app.configure_loading_state(
-            LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<ImpactAssets>(),
-        )
-        .add_systems(
-            Update,
-            init_impact.run_if(in_state(AssetLoadState::Success)),
-        );
+        app.init_resource::<GrinRng>()
+            .configure_loading_state(
+                LoadingStateConfig::new(AssetLoadState::Loading)
+                    .load_collection::<ImpactAssets>()
+                    .load_collection::<DecalAssets>(),
+            )
+            // in case `DecalPlugin` isn't around
+            .add_event::<SpawnDecalEvent>()
+            .add_systems(
+                Update,
+                (
+                    init_impact.run_if(in_state(AssetLoadState::Success)),
+                    // bullets get despawned here
+                    bullet_hole_decals.before(push_contact_damage),
+                ),
+            );
     }
 }
 
@@ -65,9 +88,10 @@ pub fn init_impact(
     mut commands: Commands,
     impact_assets: Res<ImpactAssets>,
     mut impact_query: Query<(Entity, &mut Impact, &GlobalTransform)>,
+    mut grin_rng: ResMut<GrinRng>,
 ) {
     for (e_impact, mut impact, g_transform) in impact_query.iter_mut() {
-        thread_rng().fill(&mut impact.rng_seed);
+        grin_rng.fork(RNG_FX).fill(&mut impact.rng_seed);
         let mut rng = StdRng::from_seed(impact.rng_seed);
 
         let material = impact_assets.white.clone();
@@ -115,3 +139,37 @@ pub fn init_impact(
         commands.entity(e_impact).despawn();
     }
 }
+
+/// Leaves a bullet hole wherever a `BulletProjectile` hits.
+pub fn bullet_hole_decals(
+    rapier_context: Res<RapierContext>,
+    bullet_query: Query<(), With<BulletProjectile>>,
+    transform_query: Query<&GlobalTransform>,
+    mut damage_events: EventReader<DamageEvent>,
+    mut decal_events: EventWriter<SpawnDecalEvent>,
+) {
+    for damage_event in damage_events.read() {
+        let &DamageEvent::Contact {
+            e_damage, e_hit, ..
+        } = damage_event
+        else {
+            continue;
+        };
+        if !bullet_query.contains(e_damage) {
+            continue;
+        }
+
+        let Ok((position, normal)) =
+            try_find_deepest_contact(damage_event, &rapier_context, &transform_query)
+        else {
+            continue;
+        };
+        decal_events.send(SpawnDecalEvent {
+            position,
+            normal,
+            kind: DecalKind::BulletHole,
+            size: BULLET_HOLE_SIZE,
+            target: Some(e_hit),
+        });
+    }
+}
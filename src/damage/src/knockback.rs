@@ -0,0 +1,360 @@
+use std::time::Duration;
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_rapier3d::prelude::*;
+use grin_physics::{ForceEasing, PhysicsTime, TimedForce, TimedForceStack};
+use grin_time::scaling::TimeScale;
+
+use crate::{
+    hit::{
+        push_contact_damage, tick_contact_debounces, try_find_deepest_contact_point, ContactDamage,
+        ContactDebounce, DamageEvent,
+    },
+    hitbox::Hitbox,
+    plugin::DamageSet,
+};
+
+pub struct KnockbackPlugin;
+
+impl Plugin for KnockbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                apply_knockback
+                    .in_set(DamageSet::Add)
+                    .after(tick_contact_debounces)
+                    .before(push_contact_damage),
+                apply_shoves,
+            ),
+        );
+    }
+}
+
+/// How long a `Shove` lasts, in seconds.
+pub const SHOVE_DURATION: f32 = 0.25;
+
+/// Pushes things hit by the adjacent `Damage`.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Knockback {
+    /// For dynamic bodies, this is the impulse.
+    /// For anything else, this is the initial speed of the `Shove`.
+    pub impulse: f32,
+    pub mode: KnockbackMode,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum KnockbackMode {
+    /// From the contact point towards the center of whatever was hit.
+    #[default]
+    Radial,
+    /// Along the velocity of the damaging entity. Good for projectiles.
+    /// Falls back to `Radial` if it isn't moving.
+    Directional,
+}
+
+/// Scales `Knockback` linearly from no resist at `0.0` to full resist at `1.0`.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct KnockbackResist(pub f32);
+
+/// Knockback for things that aren't pushed around by the physics engine.
+/// The adjacent `TimedForceStack` is a velocity instead of a force, so shoves add up too.
+#[derive(Component, Clone, Copy, Debug, Default)]
+#[component(storage = "SparseSet")]
+pub struct Shove;
+
+pub fn apply_knockback(
+    mut commands: Commands,
+    rapier_context: Res<RapierContext>,
+    mut damage_events: EventReader<DamageEvent>,
+    knockback_query: Query<&Knockback>,
+    debounce_query: Query<&ContactDebounce>,
+    hitbox_query: Query<&Hitbox>,
+    transform_query: Query<&GlobalTransform>,
+    velocity_query: Query<&Velocity>,
+    mut target_query: Query<(
+        Option<&RigidBody>,
+        Option<&KnockbackResist>,
+        Option<&mut ExternalImpulse>,
+        Option<&mut TimedForceStack>,
+    )>,
+) {
+    // debounced hits this frame, which `ContactDebounce` doesn't have until `push_contact_damage`
+    let mut debounced = HashSet::<(Entity, Entity)>::default();
+
+    for damage_event in damage_events.read() {
+        let &DamageEvent::Contact {
+            kind,
+            e_damage,
+            e_hit,
+            absorbed: false,
+        } = damage_event
+        else {
+            continue;
+        };
+        let Ok(knockback) = knockback_query.get(e_damage) else {
+            continue;
+        };
+
+        let e_target = hitbox_query
+            .get(e_hit)
+            .map_or(e_hit, |hitbox| hitbox.target);
+        if let ContactDamage::Debounce(..) = kind {
+            let ready = debounce_query
+                .get(e_damage)
+                .map_or(true, |debouncer| debouncer.ready(e_target));
+            if !ready || !debounced.insert((e_damage, e_target)) {
+                continue;
+            }
+        }
+        let Ok((body, resist, impulse, stack)) = target_query.get_mut(e_target) else {
+            continue;
+        };
+        let Ok(g_target_transform) = transform_query.get(e_target) else {
+            continue;
+        };
+
+        let magnitude = knockback.impulse * (1.0 - resist.map_or(0.0, |r| r.0.clamp(0.0, 1.0)));
+        if magnitude <= 0.0 {
+            continue;
+        }
+
+        let direction = match knockback.mode {
+            KnockbackMode::Directional => velocity_query
+                .get(e_damage)
+                .ok()
+                .map(|v| v.linvel.normalize_or_zero())
+                .filter(|d| *d != Vec3::ZERO),
+            KnockbackMode::Radial => None,
+        };
+        let direction = match direction {
+            Some(direction) => direction,
+            None => {
+                // sensors don't have contact points, so use the center of the attack instead
+                let Some(origin) =
+                    try_find_deepest_contact_point(damage_event, &rapier_context, &transform_query)
+                        .ok()
+                        .or_else(|| {
+                            transform_query
+                                .get(e_damage)
+                                .ok()
+                                .map(GlobalTransform::translation)
+                        })
+                else {
+                    continue;
+                };
+                (g_target_transform.translation() - origin).normalize_or_zero()
+            }
+        };
+
+        debug!(
+            msg="Applying knockback.",
+            dealer=?e_damage,
+            receiver=?e_target,
+            magnitude,
+        );
+
+        match body {
+            Some(RigidBody::Dynamic) => match impulse {
+                Some(mut impulse) => impulse.impulse += direction * magnitude,
+                None => {
+                    commands.entity(e_target).insert(ExternalImpulse {
+                        impulse: direction * magnitude,
+                        ..Default::default()
+                    });
+                }
+            },
+            Some(RigidBody::Fixed) => (),
+            _ => {
+                // these are generally on the ground, and should stay there
+                let direction = Vec3::new(direction.x, 0.0, direction.z).normalize_or_zero();
+                let shove = TimedForce::new(
+                    direction * magnitude,
+                    Duration::from_secs_f32(SHOVE_DURATION),
+                    ForceEasing::Linear,
+                );
+                match stack {
+                    Some(mut stack) => stack.push(shove),
+                    None => {
+                        commands
+                            .entity(e_target)
+                            .insert((Shove, TimedForceStack(vec![shove])));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Moves `Shove`d entities, through the `KinematicCharacterController` if there is one.
+pub fn apply_shoves(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut shove_query: Query<
+        (
+            Entity,
+            &TimedForceStack,
+            Option<&TimeScale>,
+            Option<&mut KinematicCharacterController>,
+            &mut Transform,
+        ),
+        With<Shove>,
+    >,
+) {
+    for (e_shove, stack, time_scale, char_controller, mut transform) in shove_query.iter_mut() {
+        if stack.0.is_empty() {
+            commands
+                .entity(e_shove)
+                .remove::<(Shove, TimedForceStack)>();
+            continue;
+        }
+
+        let dt = time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        let offset = stack.sum().force * dt;
+        match char_controller {
+            Some(mut char_controller) => {
+                let mut t = char_controller.translation.unwrap_or_default();
+                t += offset;
+                char_controller.translation = Some(t);
+            }
+            None => transform.translation += offset,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn knockback() {
+        let mut app = App::new();
+        app.init_resource::<RapierContext>()
+            .add_event::<DamageEvent>()
+            .add_systems(Update, apply_knockback);
+
+        let e_damage = app
+            .world
+            .spawn((
+                Knockback {
+                    impulse: 2.0,
+                    mode: KnockbackMode::Radial,
+                },
+                GlobalTransform::default(),
+            ))
+            .id();
+        let e_dynamic = app
+            .world
+            .spawn((RigidBody::Dynamic, GlobalTransform::from_xyz(1.0, 0.0, 0.0)))
+            .id();
+        let e_kinematic = app
+            .world
+            .spawn((
+                RigidBody::KinematicVelocityBased,
+                GlobalTransform::from_xyz(0.0, 0.0, -1.0),
+            ))
+            .id();
+        let e_resist = app
+            .world
+            .spawn((
+                RigidBody::Dynamic,
+                KnockbackResist(1.0),
+                GlobalTransform::from_xyz(1.0, 0.0, 0.0),
+            ))
+            .id();
+
+        for e_hit in [e_dynamic, e_kinematic, e_resist] {
+            app.world.send_event(DamageEvent::Contact {
+                kind: Default::default(),
+                e_damage,
+                e_hit,
+                absorbed: false,
+            });
+        }
+        app.update();
+
+        assert_eq!(
+            app.world.get::<ExternalImpulse>(e_dynamic).unwrap().impulse,
+            Vec3::X * 2.0,
+            "Dynamic body wasn't knocked away from the attack.",
+        );
+        assert!(
+            app.world.get::<Shove>(e_kinematic).is_some(),
+            "Kinematic body wasn't shoved.",
+        );
+        assert_eq!(
+            app.world
+                .get::<TimedForceStack>(e_kinematic)
+                .unwrap()
+                .sum()
+                .force,
+            Vec3::NEG_Z * 2.0,
+            "Kinematic body wasn't shoved along the ground.",
+        );
+        assert!(
+            app.world.get::<ExternalImpulse>(e_resist).is_none(),
+            "`KnockbackResist` was ignored.",
+        );
+    }
+
+    #[test]
+    fn debounced_knockback() {
+        let mut app = App::new();
+        app.init_resource::<RapierContext>()
+            .add_event::<DamageEvent>()
+            .add_systems(Update, apply_knockback);
+
+        let kind = ContactDamage::Debounce(Duration::from_millis(100));
+        let e_damage = app
+            .world
+            .spawn((
+                kind,
+                Knockback {
+                    impulse: 2.0,
+                    mode: KnockbackMode::Radial,
+                },
+                GlobalTransform::default(),
+            ))
+            .id();
+        let e_hit = app
+            .world
+            .spawn((
+                RigidBody::Dynamic,
+                ExternalImpulse::default(),
+                GlobalTransform::from_xyz(1.0, 0.0, 0.0),
+            ))
+            .id();
+
+        let hit = |app: &mut App| {
+            app.world.send_event(DamageEvent::Contact {
+                kind,
+                e_damage,
+                e_hit,
+                absorbed: false,
+            });
+        };
+
+        hit(&mut app);
+        hit(&mut app);
+        app.update();
+        assert_eq!(
+            app.world.get::<ExternalImpulse>(e_hit).unwrap().impulse,
+            Vec3::X * 2.0,
+            "Debounce didn't block the second knockback in the same frame.",
+        );
+
+        let mut debouncer = ContactDebounce::default();
+        debouncer.0.insert(
+            e_hit,
+            Timer::new(Duration::from_millis(100), TimerMode::Once),
+        );
+        app.world.entity_mut(e_damage).insert(debouncer);
+        hit(&mut app);
+        app.update();
+        assert_eq!(
+            app.world.get::<ExternalImpulse>(e_hit).unwrap().impulse,
+            Vec3::X * 2.0,
+            "Debounced hit was knocked back.",
+        );
+    }
+}
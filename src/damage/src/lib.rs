@@ -1,7 +1,15 @@
+pub mod debug;
+pub mod dot;
 pub mod dot_region;
+pub mod explosion;
+pub mod faction;
+pub mod feedback;
 pub mod health;
 pub mod hit;
 pub mod hitbox;
 pub mod impact;
+pub mod knockback;
 pub mod plugin;
 pub mod projectiles;
+pub mod status;
+pub mod zone;
@@ -1,8 +1,11 @@
 use bevy::{app::PluginGroupBuilder, prelude::*};
 
 use crate::{
-    health::HealthPlugin, hit::ContactDamagePlugin, hitbox::GltfHitboxGenerationPlugin,
-    impact::ImpactPlugin, projectiles::ProjectilePlugin,
+    debug::HitboxDebugPlugin, dot::DamageOverTimePlugin, explosion::ExplosionPlugin,
+    faction::FactionPlugin, feedback::DamageFeedbackPlugin, health::HealthPlugin,
+    hit::ContactDamagePlugin, hitbox::GltfHitboxGenerationPlugin, impact::ImpactPlugin,
+    knockback::KnockbackPlugin, projectiles::ProjectilePlugin, status::StatusEffectPlugin,
+    zone::DamageZonePlugin,
 };
 
 /// Health and damage calculations.
@@ -14,8 +17,16 @@ impl PluginGroup for DamagePlugins {
             .add(ProjectilePlugin)
             .add(ContactDamagePlugin)
             .add(HealthPlugin)
+            .add(FactionPlugin)
+            .add(DamageOverTimePlugin)
+            .add(StatusEffectPlugin)
+            .add(ExplosionPlugin)
             .add(ImpactPlugin)
+            .add(KnockbackPlugin)
+            .add(DamageZonePlugin)
+            .add(DamageFeedbackPlugin)
             .add(GltfHitboxGenerationPlugin)
+            .add(HitboxDebugPlugin)
     }
 }
 
@@ -27,6 +38,8 @@ pub enum DamageSet {
     Propagate,
     /// `Resist` is applied in this stage.
     Resist,
+    /// `DamageZone`s are applied in this stage.
+    Zone,
     /// `DamageBuffer`s are cleared in this stage.
     Clear,
     /// Things die in this stage.
@@ -2,15 +2,28 @@
 // there is a 50:50 chance of a projectile physics overhaul coming?
 // I need to see 1) the performance difference and 2) the accuracy of CCD first.
 
-use bevy::prelude::*;
+use bevy::{
+    ecs::system::{EntityCommand, EntityCommands},
+    prelude::*,
+};
 use bevy_asset_loader::prelude::*;
 use bevy_rapier3d::prelude::*;
 use grin_asset::AssetLoadState;
-use grin_physics::{CollisionGroupExt, CollisionGroupsExt};
+use grin_physics::{CollisionGroupExt, CollisionGroupsExt, PhysicsTime};
 use grin_render::sketched::{GlobalMeshOutline, SketchMaterial};
+use grin_time::{scaling::TimeScale, Despawn, Recycle, Rewind, RewindableDespawn};
 use grin_util::{distr, vectors};
 
-use crate::hit::{ContactDamage, Damage};
+use crate::{
+    health::Dead,
+    hit::{ContactDamage, Damage},
+};
+
+/// Default for `ProjectilePool::capacity`.
+pub const PROJECTILE_POOL_SIZE: usize = 256;
+
+/// Default for `ProjectilePool::lifetime`.
+pub const PROJECTILE_LIFETIME: f32 = 5.0;
 
 pub struct ProjectilePlugin;
 
@@ -19,12 +32,16 @@ impl Plugin for ProjectilePlugin {
         app.configure_loading_state(
             LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<ProjectileAssets>(),
         )
+        .init_resource::<ProjectilePool>()
+        .add_systems(Startup, fill_projectile_pool)
         .add_systems(
             Update,
             (
                 spawn_bullet_projectiles.run_if(in_state(AssetLoadState::Success)),
                 curve_trajectories,
-                target_trajectories,
+                home_projectiles,
+                apply_drag,
+                expire_projectiles,
             ),
         );
     }
@@ -139,6 +156,8 @@ pub struct ProjectileBundle {
     pub damage: Damage,
     pub contact_damage: ContactDamage,
     pub ccd: Ccd,
+    /// Rapier's, since projectiles are dynamic.
+    pub interpolation: TransformInterpolation,
     pub mass_properties: ColliderMassProperties,
     pub spatial_constraints: LockedAxes,
     pub visibility: Visibility,
@@ -148,7 +167,36 @@ pub struct ProjectileBundle {
     pub global_transform: GlobalTransform,
 }
 
+/// Makes a projectile fall and slow down instead of flying straight.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct BallisticProfile {
+    pub gravity_scale: f32,
+    /// Fraction of velocity lost per second.
+    pub drag: f32,
+    /// Angular velocity at launch.
+    pub spin: Vec3,
+}
+
+impl Default for BallisticProfile {
+    fn default() -> Self {
+        Self {
+            gravity_scale: 1.0,
+            drag: 0.0,
+            spin: Vec3::ZERO,
+        }
+    }
+}
+
 impl ProjectileBundle {
+    /// Adds a `BallisticProfile`. Projectiles are normally locked to the ground plane, so this
+    /// unlocks them too.
+    pub fn with_ballistics(mut self, profile: BallisticProfile) -> (Self, BallisticProfile) {
+        self.gravity = GravityScale(profile.gravity_scale);
+        self.spatial_constraints = LockedAxes::empty();
+        self.velocity.angvel = profile.spin;
+        (self, profile)
+    }
+
     pub fn player_default() -> Self {
         Self {
             collision_groups: CollisionGroups::from_group_default(Group::PLAYER_PROJECTILE),
@@ -180,6 +228,7 @@ impl Default for ProjectileBundle {
             damage: Damage::default(),
             contact_damage: ContactDamage::default(),
             ccd: Ccd::default(),
+            interpolation: TransformInterpolation::default(),
             color: ProjectileColor::Red,
             mass_properties: ColliderMassProperties::default(),
             spatial_constraints: LockedAxes::TRANSLATION_LOCKED_Y,
@@ -192,19 +241,220 @@ impl Default for ProjectileBundle {
     }
 }
 
-pub fn spawn_bullet_projectiles(
+/// Projectiles that get reused instead of despawned. Taking one out with `acquire` is
+/// basically `spawn`, and putting it back with `release_projectile` is basically `despawn`.
+///
+/// The ones in the pool keep their mesh, collider and rigidbody, but are hidden and disabled.
+#[derive(Resource, Debug)]
+pub struct ProjectilePool {
+    /// How many are kept around. More get spawned if they run out,
+    /// but they're despawned for real on release if the pool's full.
+    pub capacity: usize,
+    /// Seconds before a projectile gets released on its own.
+    pub lifetime: f32,
+    /// Shared by all the bullets.
+    pub mesh: Handle<Mesh>,
+    free: Vec<Entity>,
+}
+
+impl Default for ProjectilePool {
+    fn default() -> Self {
+        Self {
+            capacity: PROJECTILE_POOL_SIZE,
+            lifetime: PROJECTILE_LIFETIME,
+            mesh: Handle::default(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl ProjectilePool {
+    /// Takes a projectile out of the pool, or spawns one if it's empty.
+    /// Insert a `ProjectileBundle` on it, same as a fresh one.
+    pub fn acquire<'a>(&mut self, commands: &'a mut Commands) -> EntityCommands<'a> {
+        let entity = loop {
+            match self.free.pop() {
+                // something else could have despawned it
+                Some(e) if commands.get_entity(e).is_none() => continue,
+                Some(e) => break e,
+                None => break commands.spawn(self.shell()).id(),
+            }
+        };
+
+        let mut e_projectile = commands.entity(entity);
+        e_projectile
+            .remove::<(RigidBodyDisabled, ColliderDisabled)>()
+            .insert((
+                PooledProjectile { free: false },
+                Visibility::Inherited,
+                ProjectileLifetime(self.lifetime),
+            ));
+        e_projectile
+    }
+
+    /// How many are waiting in the pool.
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    fn shell(&self) -> impl Bundle {
+        (
+            PooledProjectile { free: false },
+            ProjectileBundle::default(),
+            self.mesh.clone(),
+        )
+    }
+}
+
+/// Came from the `ProjectilePool`.
+#[derive(Component, Debug)]
+pub struct PooledProjectile {
+    /// In the pool, waiting to be used.
+    pub free: bool,
+}
+
+/// What a pooled projectile keeps between uses. Everything else comes off when it's released.
+type ProjectileShell = (
+    PooledProjectile,
+    (
+        RigidBody,
+        RapierRigidBodyHandle,
+        Velocity,
+        GravityScale,
+        Ccd,
+        LockedAxes,
+    ),
+    (
+        Collider,
+        RapierColliderHandle,
+        CollisionGroups,
+        Sensor,
+        ActiveEvents,
+        ColliderMassProperties,
+    ),
+    (
+        Transform,
+        GlobalTransform,
+        Visibility,
+        InheritedVisibility,
+        ViewVisibility,
+    ),
+    Handle<Mesh>,
+);
+
+/// Seconds before a pooled projectile is released.
+#[derive(Component, Debug)]
+pub struct ProjectileLifetime(pub f32);
+
+/// Puts a `PooledProjectile` back in the `ProjectilePool`. Anything else just gets despawned.
+///
+/// It also leaves the time hierarchy and forgets its history, so a `Rewind` can't drag the next
+/// projectile through this one's past. `RewindableDespawn` ones get parked like `time_despawn`
+/// instead, since they might come back.
+pub struct ReleaseProjectile;
+
+impl EntityCommand for ReleaseProjectile {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let Some(e_projectile) = world.get_entity(entity) else {
+            return;
+        };
+        match e_projectile.get::<PooledProjectile>() {
+            Some(PooledProjectile { free: true }) => return,
+            Some(..) if e_projectile.contains::<RewindableDespawn>() => {
+                Despawn.apply(entity, world);
+                return;
+            }
+            Some(..) => (),
+            None => {
+                world.entity_mut(entity).despawn_recursive();
+                return;
+            }
+        }
+
+        Recycle.apply(entity, world);
+        let pooled = match world.get_resource_mut::<ProjectilePool>() {
+            Some(mut pool) if pool.free.len() < pool.capacity => {
+                pool.free.push(entity);
+                true
+            }
+            _ => false,
+        };
+        if !pooled {
+            world.entity_mut(entity).despawn_recursive();
+            return;
+        }
+
+        let mut e_projectile = world.entity_mut(entity);
+        e_projectile.despawn_descendants();
+        e_projectile.retain::<ProjectileShell>().insert((
+            PooledProjectile { free: true },
+            Visibility::Hidden,
+            RigidBodyDisabled,
+            ColliderDisabled,
+        ));
+    }
+}
+
+pub trait ProjectileCommandsExt {
+    fn release_projectile(&mut self);
+}
+
+impl<'a> ProjectileCommandsExt for EntityCommands<'a> {
+    /// See `ReleaseProjectile`.
+    fn release_projectile(&mut self) {
+        self.add(ReleaseProjectile);
+    }
+}
+
+pub fn fill_projectile_pool(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
+    mut pool: ResMut<ProjectilePool>,
+) {
+    pool.mesh = meshes.add(Mesh::from(Sphere {
+        radius: 0.5,
+        ..Default::default()
+    }));
+    let free = (0..pool.capacity)
+        .map(|_| {
+            commands
+                .spawn(pool.shell())
+                .insert((
+                    PooledProjectile { free: true },
+                    Visibility::Hidden,
+                    RigidBodyDisabled,
+                    ColliderDisabled,
+                ))
+                .id()
+        })
+        .collect();
+    pool.free = free;
+}
+
+// rewinding projectiles can be `TimeChildren` of the weapon, and shouldn't run out while rewinding
+pub fn expire_projectiles(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut query: Query<(Entity, &mut ProjectileLifetime, Option<&TimeScale>), Without<Rewind>>,
+) {
+    for (entity, mut lifetime, time_scale) in query.iter_mut() {
+        lifetime.0 -= time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        if lifetime.0 <= 0.0 {
+            commands.entity(entity).release_projectile();
+        }
+    }
+}
+
+pub fn spawn_bullet_projectiles(
+    mut commands: Commands,
+    pool: Res<ProjectilePool>,
     assets: Res<ProjectileAssets>,
     outline: Res<GlobalMeshOutline>,
     query: Query<(Entity, Option<&ProjectileColor>), Added<BulletProjectile>>,
 ) {
     for (e_projectile, color) in query.iter() {
         commands.get_or_spawn(e_projectile).insert((
-            meshes.add(Mesh::from(Sphere {
-                radius: 0.5,
-                ..Default::default()
-            })),
+            pool.mesh.clone(),
             assets.solid_color(ProjectileColor::White).clone(),
             {
                 let mut outline = outline.standard.clone();
@@ -229,17 +479,318 @@ pub fn curve_trajectories(time: Res<Time>, mut query: Query<(&mut Velocity, &Cur
     }
 }
 
-#[derive(Component)]
-pub struct TargettedTrajectory {
-    pub entity: Entity,
+pub fn apply_drag(
+    time: Res<PhysicsTime>,
+    mut query: Query<(&mut Velocity, &BallisticProfile, Option<&TimeScale>)>,
+) {
+    for (mut velocity, profile, time_scale) in query.iter_mut() {
+        let dt = time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        velocity.linvel *= (-profile.drag * dt).exp();
+    }
+}
+
+/// Pitch, in radians, to launch at to land at `offset` away under `gravity` (magnitude, pointing
+/// down). Takes the lower of the two arcs. `None` if `speed` can't reach it.
+pub fn launch_pitch(offset: Vec3, speed: f32, gravity: f32) -> Option<f32> {
+    let x = offset.xz().length();
+    let y = offset.y;
+    if gravity <= 0.0 || x <= f32::EPSILON {
+        return Some(y.atan2(x));
+    }
+
+    let v2 = speed * speed;
+    let discriminant = v2 * v2 - gravity * (gravity * x * x + 2.0 * y * v2);
+    if discriminant < 0.0 {
+        return None;
+    }
+    Some(((v2 - discriminant.sqrt()) / (gravity * x)).atan())
+}
+
+/// Steers a projectile toward `target`, keeping its speed. Removed once the target is gone or
+/// `Dead`, or after `max_lifetime` seconds, and the projectile keeps going straight.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Homing {
+    pub target: Entity,
+    /// Max turning speed in radians per second.
+    pub turn_rate_rad: f32,
+    /// Seconds.
+    pub max_lifetime: f32,
+    /// Seconds since launch.
+    pub elapsed: f32,
+}
+
+impl Homing {
+    pub fn new(target: Entity, turn_rate_rad: f32, max_lifetime: f32) -> Self {
+        Self {
+            target,
+            turn_rate_rad,
+            max_lifetime,
+            elapsed: 0.0,
+        }
+    }
 }
 
-pub fn target_trajectories(
-    _time: Res<Time>,
-    mut query: Query<(&Transform, &mut Velocity, &TargettedTrajectory)>,
-    _transform_query: Query<&GlobalTransform>,
+/// Returns the closest of `candidates` within `max_angle` radians of `forward` from `origin`.
+///
+/// For picking a `Homing` target at fire time, with `forward` pointing at the item's `Target`.
+pub fn find_homing_target(
+    origin: Vec3,
+    forward: Vec3,
+    max_angle: f32,
+    candidates: impl IntoIterator<Item = (Entity, Vec3)>,
+) -> Option<Entity> {
+    candidates
+        .into_iter()
+        .filter(|(_, pos)| forward.angle_between(*pos - origin) <= max_angle)
+        .min_by(|(_, a), (_, b)| {
+            a.distance_squared(origin)
+                .total_cmp(&b.distance_squared(origin))
+        })
+        .map(|(e, _)| e)
+}
+
+// rewinding projectiles can be `TimeChildren` of the weapon, and shouldn't steer while rewinding
+pub fn home_projectiles(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut query: Query<
+        (
+            Entity,
+            &GlobalTransform,
+            &mut Velocity,
+            &mut Homing,
+            Option<&TimeScale>,
+        ),
+        Without<Rewind>,
+    >,
+    target_query: Query<&GlobalTransform, Without<Dead>>,
 ) {
-    for (_transform, _velocity, _curve) in query.iter_mut() {
-        todo!();
+    for (entity, g_transform, mut velocity, mut homing, time_scale) in query.iter_mut() {
+        let dt = time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        homing.elapsed += dt;
+
+        let Ok(g_target_transform) = target_query.get(homing.target) else {
+            commands.entity(entity).remove::<Homing>();
+            continue;
+        };
+        if homing.elapsed > homing.max_lifetime {
+            commands.entity(entity).remove::<Homing>();
+            continue;
+        }
+
+        let desired = g_target_transform.translation() - g_transform.translation();
+        let linvel = velocity.linvel;
+        let angle = linvel.angle_between(desired);
+        if !angle.is_finite() || angle <= f32::EPSILON {
+            continue;
+        }
+
+        let max_turn = homing.turn_rate_rad * dt;
+        velocity.linvel = if angle <= max_turn {
+            desired.normalize() * linvel.length()
+        } else {
+            // going straight away from it, any direction works
+            let axis = linvel
+                .cross(desired)
+                .try_normalize()
+                .unwrap_or_else(|| linvel.any_orthonormal_vector());
+            Quat::from_axis_angle(axis, max_turn) * linvel
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{f32::consts::FRAC_PI_4, time::Duration};
+
+    use grin_time::{CommandsExt, TimeChildren};
+
+    use super::*;
+
+    #[test]
+    fn pitch() {
+        // max range for speed 10 and gravity 10 is 10, at 45 degrees
+        let pitch = launch_pitch(Vec3::new(10.0, 0.0, 0.0), 10.0, 10.0).unwrap();
+        assert!((pitch - FRAC_PI_4).abs() < 1e-3, "Wrong max range pitch.");
+
+        let pitch = launch_pitch(Vec3::new(0.0, 0.0, -5.0), 10.0, 10.0).unwrap();
+        let (sin, cos) = pitch.sin_cos();
+        // lands where it was aimed: x = v * cos * t, y = v * sin * t - g * t^2 / 2
+        let t = 5.0 / (10.0 * cos);
+        let y = 10.0 * sin * t - 5.0 * t * t;
+        assert!(y.abs() < 1e-3, "Missed the target by {}.", y);
+        assert!(pitch < FRAC_PI_4, "Didn't pick the low arc.");
+
+        assert_eq!(
+            launch_pitch(Vec3::new(11.0, 0.0, 0.0), 10.0, 10.0),
+            None,
+            "Reached a target out of range."
+        );
+    }
+
+    #[test]
+    fn homing() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_systems(Update, home_projectiles);
+
+        let transform = Transform::from_xyz(0.0, 0.0, -10.0);
+        let e_target = app
+            .world
+            .spawn(TransformBundle {
+                local: transform,
+                global: transform.into(),
+            })
+            .id();
+        // 90 degrees off, turning 0.1 radians per tick
+        let e = app
+            .world
+            .spawn((
+                TransformBundle::default(),
+                Velocity::linear(Vec3::X * 10.0),
+                Homing::new(e_target, 1.0, 10.0),
+            ))
+            .id();
+
+        let heading = |app: &App| app.world.get::<Velocity>(e).unwrap().linvel;
+        for _ in 0..15 {
+            app.world
+                .resource_mut::<PhysicsTime>()
+                .0
+                .advance_by(Duration::from_millis(100));
+            app.update();
+        }
+        assert!(
+            heading(&app).angle_between(Vec3::NEG_Z) > 0.01,
+            "Turned faster than the turn rate."
+        );
+
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_millis(100));
+        app.update();
+        assert!(
+            heading(&app).angle_between(Vec3::NEG_Z) < 1e-3,
+            "Didn't converge on the target: {:?}.",
+            heading(&app),
+        );
+        assert!(
+            (heading(&app).length() - 10.0).abs() < 1e-3,
+            "Homing changed the speed."
+        );
+    }
+
+    #[test]
+    fn projectile_pool() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Mesh>()
+            .init_resource::<PhysicsTime>()
+            .insert_resource(ProjectilePool {
+                capacity: 64,
+                lifetime: 1.0,
+                ..Default::default()
+            })
+            .add_systems(Startup, fill_projectile_pool)
+            .add_systems(Update, expire_projectiles);
+
+        let e_gun = app.world.spawn(TimeChildren::default()).id();
+        app.update();
+        let before = app.world.entities().len();
+
+        // 100 a frame, like a really fast SMG
+        app.add_systems(
+            Update,
+            (move |mut commands: Commands,
+                   mut pool: ResMut<ProjectilePool>,
+                   mut fired: Local<u32>| {
+                for _ in 0..100 {
+                    if *fired == 1000 {
+                        return;
+                    }
+                    let mut e_bullet = pool.acquire(&mut commands);
+                    e_bullet.insert((
+                        BulletProjectile,
+                        ProjectileBundle::default(),
+                        Homing::new(e_gun, 1.0, 1.0),
+                    ));
+                    e_bullet.set_time_parent(e_gun);
+                    *fired += 1;
+                }
+            })
+            .before(expire_projectiles),
+        );
+        for _ in 0..10 {
+            app.update();
+        }
+        assert_eq!(
+            app.world
+                .query_filtered::<(), With<BulletProjectile>>()
+                .iter(&app.world)
+                .count(),
+            1000,
+            "Wrong number of bullets."
+        );
+
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_secs(2));
+        app.update();
+        assert_eq!(
+            app.world.entities().len(),
+            before,
+            "Entities weren't returned to the pool."
+        );
+        assert_eq!(
+            app.world.resource::<ProjectilePool>().available(),
+            64,
+            "Pool wasn't refilled."
+        );
+        assert!(
+            app.world
+                .query_filtered::<
+                    (Has<BulletProjectile>, Has<Homing>, Has<RigidBodyDisabled>),
+                    With<PooledProjectile>,
+                >()
+                .iter(&app.world)
+                .all(|(bullet, homing, disabled)| !bullet && !homing && disabled),
+            "Released projectiles weren't reset."
+        );
+        assert!(
+            app.world.get::<TimeChildren>(e_gun).unwrap().0.is_empty(),
+            "Released projectiles are still time children."
+        );
+    }
+
+    #[test]
+    fn drag() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_systems(Update, apply_drag);
+
+        let e = app
+            .world
+            .spawn((
+                Velocity::linear(Vec3::X * 10.0),
+                BallisticProfile {
+                    drag: 0.5,
+                    ..Default::default()
+                },
+            ))
+            .id();
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_secs(2));
+        app.update();
+
+        let speed = app.world.get::<Velocity>(e).unwrap().linvel.x;
+        assert!(
+            (speed - 10.0 * (-1.0f32).exp()).abs() < 1e-4,
+            "Drag didn't slow it down right: {}.",
+            speed
+        );
     }
 }
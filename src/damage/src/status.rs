@@ -1,23 +1,343 @@
-use std::time::Duration;
+//! Temporary effects on characters, like slows and stuns.
+//!
+//! Send `ApplyStatusEvent<T>` and `T` is inserted on the target, next to a `StatusEffect<T>` that
+//! keeps track of how long it has left. Both are removed when it runs out.
 
-use bevy::prelude::*;
-use bevy_asset_loader::prelude::*;
+use std::{marker::PhantomData, time::Duration};
 
-pub trait StatusEffect {
-    pub const NAME: &'static str;
-    pub const DESCRIPTION: &'static str;
+use bevy::{prelude::*, utils::HashMap};
+use bevy_asset_loader::prelude::*;
+use grin_physics::PhysicsTime;
+use grin_time::{scaling::TimeScale, Rewind, RewindComponentPlugin};
+
+use crate::{
+    dot::DamageOverTime,
+    hit::{Damage, DamageEvent},
+    plugin::DamageSet,
+};
+
+/// Name and flavor text for the HUD.
+pub trait StatusInfo {
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
+    /// What happens when it's applied to something that already has it.
+    const STACKING: StatusStacking = StatusStacking::Refresh;
 }
 
 pub trait Perk {
-    pub const NAME: &'static str;
-    pub const DESCRIPTION: &'static str;
+    const NAME: &'static str;
+    const DESCRIPTION: &'static str;
 }
 
 pub struct StatusEffectPlugin;
 
 impl Plugin for StatusEffectPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, apply_stagger);
+        app.add_plugins((
+            StatusPlugin::<Slowed>::default(),
+            StatusPlugin::<Stunned>::default(),
+            StatusPlugin::<Burning>::default(),
+            StatusPlugin::<Disoriented>::default(),
+        ))
+        .add_systems(
+            Update,
+            (
+                ignite_burning
+                    .after(apply_statuses::<Burning>)
+                    .before(DamageSet::Add),
+                apply_stagger,
+            ),
+        );
+    }
+}
+
+/// Applies and times out `T`.
+pub struct StatusPlugin<T: StatusComponent> {
+    pub phantom_data: PhantomData<T>,
+}
+
+impl<T: StatusComponent> Default for StatusPlugin<T> {
+    fn default() -> Self {
+        Self {
+            phantom_data: PhantomData::default(),
+        }
+    }
+}
+
+impl<T: StatusComponent> Plugin for StatusPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatusEvent<T>>()
+            .add_event::<StatusAppliedEvent<T>>()
+            .add_event::<StatusRemovedEvent<T>>()
+            .add_systems(Update, (apply_statuses::<T>, tick_statuses::<T>).chain());
+    }
+}
+
+/// Lets `Rewind` bring back `T` along with its remaining duration. Needs `RewindPlugin`.
+pub struct RewindStatusPlugin<T: StatusComponent> {
+    pub phantom_data: PhantomData<T>,
+}
+
+impl<T: StatusComponent> Default for RewindStatusPlugin<T> {
+    fn default() -> Self {
+        Self {
+            phantom_data: PhantomData::default(),
+        }
+    }
+}
+
+impl<T: StatusComponent> Plugin for RewindStatusPlugin<T> {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            RewindComponentPlugin::<T>::default(),
+            RewindComponentPlugin::<StatusEffect<T>>::default(),
+        ));
+    }
+}
+
+/// Anything that can be applied with `ApplyStatusEvent`.
+pub trait StatusComponent: Component + Clone + StatusInfo {}
+
+impl<T: Component + Clone + StatusInfo> StatusComponent for T {}
+
+/// What happens when a status is applied to something that already has it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StatusStacking {
+    /// The new one replaces the old one.
+    #[default]
+    Refresh,
+    /// Stacks are added up to `max`. The duration is refreshed.
+    Stack { max: u32 },
+    /// The old one stays as is.
+    Ignore,
+}
+
+/// Applies `effect` to `target` for `duration`.
+#[derive(Event, Clone, Debug)]
+pub struct ApplyStatusEvent<T: StatusComponent> {
+    pub target: Entity,
+    pub effect: T,
+    pub duration: Duration,
+    pub stacks: u32,
+}
+
+impl<T: StatusComponent> ApplyStatusEvent<T> {
+    /// One stack.
+    pub fn new(target: Entity, effect: T, duration: Duration) -> Self {
+        Self {
+            target,
+            effect,
+            duration,
+            stacks: 1,
+        }
+    }
+}
+
+/// Sent when an `ApplyStatusEvent<T>` actually does something, i.e. `T::STACKING` didn't ignore it.
+#[derive(Event, Clone, Debug)]
+pub struct StatusAppliedEvent<T: StatusComponent>(pub ApplyStatusEvent<T>);
+
+/// Sent when `T` runs out.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct StatusRemovedEvent<T: StatusComponent> {
+    pub target: Entity,
+    pub phantom_data: PhantomData<T>,
+}
+
+/// Timer for the adjacent `T`.
+#[derive(Component, Clone, Debug)]
+pub struct StatusEffect<T: StatusComponent> {
+    pub remaining: Duration,
+    pub stacks: u32,
+    pub phantom_data: PhantomData<T>,
+}
+
+impl<T: StatusComponent> StatusEffect<T> {
+    pub fn new(duration: Duration, stacks: u32) -> Self {
+        Self {
+            remaining: duration,
+            stacks,
+            phantom_data: PhantomData::default(),
+        }
+    }
+
+    /// Applies it again following `T::STACKING`. Returns whether anything changed.
+    pub fn reapply(&mut self, duration: Duration, stacks: u32) -> bool {
+        match T::STACKING {
+            StatusStacking::Refresh => {
+                self.remaining = duration;
+                self.stacks = stacks;
+            }
+            StatusStacking::Stack { max } => {
+                self.remaining = duration;
+                self.stacks = (self.stacks + stacks).min(max);
+            }
+            StatusStacking::Ignore => return false,
+        }
+        true
+    }
+}
+
+/// Applies `ApplyStatusEvent<T>`.
+pub fn apply_statuses<T: StatusComponent>(
+    mut commands: Commands,
+    mut status_query: Query<(&mut StatusEffect<T>, &mut T)>,
+    mut apply_events: EventReader<ApplyStatusEvent<T>>,
+    mut applied_events: EventWriter<StatusAppliedEvent<T>>,
+) {
+    // a second event for the same entity in the same frame can't see the inserted component
+    let mut inserted = HashMap::<Entity, (StatusEffect<T>, T)>::new();
+
+    for apply_event in apply_events.read() {
+        let ApplyStatusEvent {
+            target,
+            effect,
+            duration,
+            stacks,
+        } = apply_event;
+
+        let applied = if let Ok((mut status, mut old_effect)) = status_query.get_mut(*target) {
+            let applied = status.reapply(*duration, *stacks);
+            if applied {
+                *old_effect = effect.clone();
+            }
+            applied
+        } else if let Some((status, old_effect)) = inserted.get_mut(target) {
+            let applied = status.reapply(*duration, *stacks);
+            if applied {
+                *old_effect = effect.clone();
+            }
+            applied
+        } else {
+            inserted.insert(
+                *target,
+                (StatusEffect::new(*duration, *stacks), effect.clone()),
+            );
+            true
+        };
+        if !applied {
+            continue;
+        }
+
+        debug!(
+            msg="Applied status.",
+            status=T::NAME,
+            receiver=?target,
+        );
+        applied_events.send(StatusAppliedEvent(apply_event.clone()));
+    }
+
+    for (e_target, (status, effect)) in inserted {
+        if let Some(mut e_target_commands) = commands.get_entity(e_target) {
+            e_target_commands.insert((status, effect));
+        }
+    }
+}
+
+/// Counts down `StatusEffect<T>` and removes it when it runs out. Paused while rewinding.
+pub fn tick_statuses<T: StatusComponent>(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut status_query: Query<(Entity, &mut StatusEffect<T>, Option<&TimeScale>), Without<Rewind>>,
+    mut removed_events: EventWriter<StatusRemovedEvent<T>>,
+) {
+    for (entity, mut status, time_scale) in status_query.iter_mut() {
+        let delta = time.0.delta().mul_f32(time_scale.map_or(1.0, f32::from));
+        status.remaining = status.remaining.saturating_sub(delta);
+        if status.remaining.is_zero() {
+            commands.entity(entity).remove::<(T, StatusEffect<T>)>();
+            removed_events.send(StatusRemovedEvent {
+                target: entity,
+                phantom_data: PhantomData::default(),
+            });
+        }
+    }
+}
+
+/// Speed is multiplied by this. Affects `CHARACTER_WALKSPEED` and agent velocity.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Slowed(pub f32);
+
+impl Default for Slowed {
+    fn default() -> Self {
+        Self(0.5)
+    }
+}
+
+impl StatusInfo for Slowed {
+    const NAME: &'static str = "Chilly";
+    const DESCRIPTION: &'static str = r#"
+Soup should do it. Pack some soup next time.
+Everything is slower."#;
+}
+
+/// Can't use items, and AI can't do anything.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Stunned;
+
+impl StatusInfo for Stunned {
+    const NAME: &'static str = "Knocked";
+    const DESCRIPTION: &'static str = r#"
+Heavy stun. If someone else isn't stunned, you're pretty screwed, right now."#;
+    const STACKING: StatusStacking = StatusStacking::Ignore;
+}
+
+/// Movement input is multiplied by this, so anything negative turns it around. AI can't do
+/// anything either.
+#[derive(Component, Clone, Copy, Debug, PartialEq)]
+pub struct Disoriented(pub f32);
+
+impl Default for Disoriented {
+    fn default() -> Self {
+        Self(-1.0)
+    }
+}
+
+impl StatusInfo for Disoriented {
+    const NAME: &'static str = "Rattled";
+    const DESCRIPTION: &'static str = r#"
+Left is right, up is down and your ears are ringing.
+Movement is reversed."#;
+}
+
+/// Seconds between `Burning` damage ticks.
+pub const BURN_TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Deals `damage` every `BURN_TICK_INTERVAL`, multiplied by the stacks.
+///
+/// The damage itself is a `DamageOverTime`, so it also follows the `DotStackingPolicy`.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct Burning {
+    pub damage: Damage,
+}
+
+impl StatusInfo for Burning {
+    const NAME: &'static str = "Torched";
+    const DESCRIPTION: &'static str = r#"
+It's okay. Ceramic objects are pretty heat resistant.
+Constant damage."#;
+}
+
+/// Sends the `DamageOverTime` for `Burning`, unless `Burning::STACKING` ignored it.
+pub fn ignite_burning(
+    mut applied_events: EventReader<StatusAppliedEvent<Burning>>,
+    mut damage_events: EventWriter<DamageEvent>,
+) {
+    for StatusAppliedEvent(ApplyStatusEvent {
+        target,
+        effect,
+        duration,
+        stacks,
+    }) in applied_events.read()
+    {
+        let damage = Damage {
+            value: effect.damage.value * *stacks as f32,
+            ..effect.damage
+        };
+        damage_events.send(DamageEvent::OverTime {
+            dot: DamageOverTime::new(damage, BURN_TICK_INTERVAL, *duration),
+            e_hit: *target,
+        });
     }
 }
 
@@ -28,7 +348,7 @@ pub struct StaggerEffect {
     pub duration: f32,
 }
 
-impl StatusEffect for StaggerEffect {
+impl StatusInfo for StaggerEffect {
     const NAME: &'static str = "Staggered";
     const DESCRIPTION: &'static str = r#"
 Dizzy...
@@ -99,64 +419,26 @@ pub fn apply_stagger(
     }
 }
 
-#[derive(Component, Default)]
-#[component(storage = "SparseSet")]
-pub struct ChillEffect {
-    pub duration: f32,
-}
-
-impl StatusEffect for ChillEffect {
-    const NAME: &'static str = "Chilly";
-    const DESCRIPTION: &'static str = r#"
-Soup should do it. Pack some soup next time.
-Everything except your combo is 0.5x slower."#;
-}
-
 #[derive(Component, Default)]
 #[component(storage = "SparseSet")]
 pub struct FreezeEffect {
     pub duration: f32,
 }
 
-impl StatusEffect for FreezeEffect {
+impl StatusInfo for FreezeEffect {
     const NAME: &'static str = "Iced";
     const DESCRIPTION: &'static str = r#"
 I guess you should just chill until it wears off.
 Temporary damage immunity. Your movement is confined to a bouncing ice cube."#;
 }
 
-#[derive(Component, Default)]
-#[component(storage = "SparseSet")]
-pub struct StunEffect {
-    pub duration: f32,
-}
-
-impl StatusEffect for StunEffect {
-    const NAME: &'static str = "Knocked";
-    const DESCRIPTION: &'static str = r#"
-Heavy stun. If someone else isn't stunned, you're pretty screwed, right now."#;
-}
-
-#[derive(Component, Default)]
-#[component(storage = "SparseSet")]
-pub struct BurnEffect {
-    pub duration: f32,
-}
-
-impl StatusEffect for BurnEffect {
-    const NAME: &'static str = "Torched";
-    const DESCRIPTION: &'static str = r#"
-It's okay. Ceramic objects are pretty heat resistant.
-Constant energy damage. 2x speed boost."#;
-}
-
 #[derive(Component, Default)]
 #[component(storage = "SparseSet")]
 pub struct NauseaEffect {
     pub duration: f32,
 }
 
-impl StatusEffect for NauseaEffect {
+impl StatusInfo for NauseaEffect {
     const NAME: &'static str = "Nauseated";
     const DESCRIPTION: &'static str = r#"
 Oh, that is... ugh... bro... why did they have to go and do that?
@@ -170,7 +452,7 @@ pub struct ShatterEffect {
     pub duration: f32,
 }
 
-impl StatusEffect for ShatterEffect {
+impl StatusInfo for ShatterEffect {
     const NAME: &'static str = "Shattered";
     const DESCRIPTION: &'static str = r#"
 Your atoms are vibrating out of their sockets.
@@ -183,7 +465,7 @@ pub struct FlashEffect {
     pub duration: f32,
 }
 
-impl StatusEffect for FlashEffect {
+impl StatusInfo for FlashEffect {
     const NAME: &'static str = "Flashed";
     const DESCRIPTION: &'static str = r#"
 Memories slip away...
@@ -196,9 +478,124 @@ pub struct OrthogonalEffect {
     pub duration: f32,
 }
 
-impl StatusEffect for OrthogonalEffect {
+impl StatusInfo for OrthogonalEffect {
     const NAME: &'static str = "Orthogonalized";
     const DESCRIPTION: &'static str = r#"
 When they hit you with that `V = {(x, y, z) ∈ R^3 | xy = z = 0}`.
 Movement is restricted to four directions."#;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Component, Clone, Debug, Default)]
+    struct MockStatus;
+
+    impl StatusInfo for MockStatus {
+        const NAME: &'static str = "Mocked";
+        const DESCRIPTION: &'static str = "";
+        const STACKING: StatusStacking = StatusStacking::Stack { max: 2 };
+    }
+
+    #[derive(Component, Clone, Debug, Default)]
+    struct MockIgnoredStatus;
+
+    impl StatusInfo for MockIgnoredStatus {
+        const NAME: &'static str = "Mocked";
+        const DESCRIPTION: &'static str = "";
+        const STACKING: StatusStacking = StatusStacking::Ignore;
+    }
+
+    fn step(app: &mut App, millis: u64) {
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_millis(millis));
+        app.update();
+    }
+
+    #[test]
+    fn lifecycle() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_plugins(StatusPlugin::<MockStatus>::default());
+
+        let e = app.world.spawn(TimeScale::default()).id();
+        for _ in 0..3 {
+            app.world
+                .send_event(ApplyStatusEvent::new(e, MockStatus, Duration::from_secs(2)));
+        }
+        step(&mut app, 0);
+        let status = app.world.get::<StatusEffect<MockStatus>>(e).unwrap();
+        assert_eq!(status.stacks, 2, "Stacked past the max.");
+
+        step(&mut app, 1000);
+        app.world
+            .send_event(ApplyStatusEvent::new(e, MockStatus, Duration::from_secs(2)));
+        step(&mut app, 1500);
+        assert!(
+            app.world.entity(e).contains::<MockStatus>(),
+            "Stacking didn't refresh the duration."
+        );
+
+        app.world.get_mut::<TimeScale>(e).unwrap().scale_by(0.5);
+        step(&mut app, 500);
+        assert!(
+            app.world.entity(e).contains::<MockStatus>(),
+            "Ignored `TimeScale`."
+        );
+        step(&mut app, 500);
+        assert!(
+            !app.world.entity(e).contains::<MockStatus>()
+                && !app.world.entity(e).contains::<StatusEffect<MockStatus>>(),
+            "Expired status wasn't removed."
+        );
+        let removed = app
+            .world
+            .resource::<Events<StatusRemovedEvent<MockStatus>>>();
+        assert_eq!(removed.len(), 1, "Didn't send `StatusRemovedEvent`.");
+    }
+
+    #[test]
+    fn paused_while_rewinding() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_plugins(StatusPlugin::<MockStatus>::default());
+
+        let e = app.world.spawn(Rewind::default()).id();
+        app.world
+            .send_event(ApplyStatusEvent::new(e, MockStatus, Duration::from_secs(1)));
+        step(&mut app, 2000);
+        assert!(
+            app.world.entity(e).contains::<MockStatus>(),
+            "Status ran out while rewinding."
+        );
+    }
+
+    #[test]
+    fn ignored_reapply() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_plugins(StatusPlugin::<MockIgnoredStatus>::default());
+
+        let e = app.world.spawn_empty().id();
+        let apply = |app: &mut App| {
+            app.world.send_event(ApplyStatusEvent::new(
+                e,
+                MockIgnoredStatus,
+                Duration::from_secs(2),
+            ));
+        };
+        apply(&mut app);
+        apply(&mut app);
+        step(&mut app, 0);
+        apply(&mut app);
+        step(&mut app, 0);
+
+        let applied = app
+            .world
+            .resource::<Events<StatusAppliedEvent<MockIgnoredStatus>>>();
+        assert_eq!(applied.len(), 1, "Sent `StatusAppliedEvent` when ignored.");
+    }
+}
@@ -0,0 +1,404 @@
+//! Areas that make damage hurt more or less.
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*};
+use bevy_rapier3d::prelude::*;
+use grin_util::query::distinguish_by_query;
+
+use crate::{
+    health::{DamageBuffer, Health},
+    hit::{credit_damage_owner, Damage, DamageVariant},
+    hitbox::Hitbox,
+    plugin::DamageSet,
+};
+
+pub struct DamageZonePlugin;
+
+impl Plugin for DamageZonePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DamageZoneLimits>().add_systems(
+            Update,
+            (
+                (init_damage_zones, track_damage_zones)
+                    .chain()
+                    .before(DamageSet::Zone),
+                apply_damage_zones.in_set(DamageSet::Zone),
+            ),
+        );
+    }
+}
+
+/// Who gets their damage scaled by a `DamageZone`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DamageZoneTarget {
+    /// Damage taken by whatever's inside.
+    #[default]
+    Received,
+    /// Damage dealt by whatever's inside, going by `Damage.source`.
+    Dealt,
+}
+
+/// Multiplies damage for everything inside of `shape`.
+///
+/// Overlapping zones multiply together, then get clamped to the `DamageZoneLimits`.
+#[derive(Component, Clone, Debug)]
+pub struct DamageZone {
+    /// `None` scales every `DamageVariant`.
+    pub variant: Option<DamageVariant>,
+    pub multiplier: f32,
+    pub shape: Collider,
+    pub target: DamageZoneTarget,
+}
+
+impl DamageZone {
+    pub fn new(variant: Option<DamageVariant>, multiplier: f32, shape: Collider) -> Self {
+        Self {
+            variant,
+            multiplier,
+            shape,
+            target: DamageZoneTarget::default(),
+        }
+    }
+
+    pub fn with_target(mut self, target: DamageZoneTarget) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn affects(&self, damage: &Damage, target: DamageZoneTarget) -> bool {
+        self.target == target && self.variant.map_or(true, |v| v == damage.ty)
+    }
+}
+
+/// The range that stacked `DamageZone` multipliers are clamped to.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct DamageZoneLimits {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Default for DamageZoneLimits {
+    fn default() -> Self {
+        Self {
+            min: 0.25,
+            max: 4.0,
+        }
+    }
+}
+
+/// The zones this entity is in, and how many of its colliders are in each one.
+///
+/// Removed once it's out of all of them.
+#[derive(Component, Debug, Default)]
+pub struct ActiveDamageZones(pub EntityHashMap<usize>);
+
+/// Adds the sensor collider for new zones.
+pub fn init_damage_zones(
+    mut commands: Commands,
+    zone_query: Query<(Entity, &DamageZone), Added<DamageZone>>,
+) {
+    for (e_zone, zone) in zone_query.iter() {
+        commands.entity(e_zone).insert((
+            zone.shape.clone(),
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            ActiveCollisionTypes::all(),
+        ));
+    }
+}
+
+/// Keeps `ActiveDamageZones` up to date with what's going in and out of zones.
+///
+/// Body parts with a `Hitbox` count as whatever they're on.
+pub fn track_damage_zones(
+    mut commands: Commands,
+    zone_query: Query<(), With<DamageZone>>,
+    hitbox_query: Query<&Hitbox>,
+    health_query: Query<(), With<Health>>,
+    mut active_query: Query<&mut ActiveDamageZones>,
+    mut collision_events: EventReader<CollisionEvent>,
+) {
+    let mut entered = EntityHashMap::<ActiveDamageZones>::default();
+
+    for collision_event in collision_events.read() {
+        let (entity_0, entity_1, started) = match collision_event {
+            CollisionEvent::Started(entity_0, entity_1, ..) => (*entity_0, *entity_1, true),
+            CollisionEvent::Stopped(entity_0, entity_1, ..) => (*entity_0, *entity_1, false),
+        };
+        let Some((e_zone, e_other)) = distinguish_by_query(&zone_query, entity_0, entity_1) else {
+            continue;
+        };
+        let entity = hitbox_query.get(e_other).map_or(e_other, |h| h.target);
+        if !health_query.contains(entity) {
+            continue;
+        }
+
+        let zones = match active_query.get_mut(entity) {
+            Ok(active) => &mut active.into_inner().0,
+            Err(..) => &mut entered.entry(entity).or_default().0,
+        };
+        let count = zones.entry(e_zone).or_default();
+        match started {
+            true => *count += 1,
+            false => *count = count.saturating_sub(1),
+        }
+        if *count == 0 {
+            zones.remove(&e_zone);
+        }
+    }
+
+    for (entity, active) in entered {
+        // might've been despawned on the way in
+        if let Some(mut e) = commands.get_entity(entity) {
+            e.insert(active);
+        }
+    }
+}
+
+/// Total multiplier for `damage` from the zones in `active`, before clamping.
+fn zone_multiplier(
+    active: Option<&ActiveDamageZones>,
+    damage: &Damage,
+    target: DamageZoneTarget,
+    zone_query: &Query<&DamageZone>,
+) -> f32 {
+    active
+        .into_iter()
+        .flat_map(|active| active.0.keys())
+        .filter_map(|e| zone_query.get(*e).ok())
+        .filter(|zone| zone.affects(damage, target))
+        .map(|zone| zone.multiplier)
+        .product()
+}
+
+/// Scales buffered damage by the zones the target and the `Damage.source` are in. Sources
+/// like items go by the zones their owner is in.
+///
+/// Zones that got despawned are forgotten here, since they might not send a `CollisionEvent`.
+pub fn apply_damage_zones(
+    mut commands: Commands,
+    limits: Res<DamageZoneLimits>,
+    zone_query: Query<&DamageZone>,
+    mut active_query: Query<(Entity, &mut ActiveDamageZones)>,
+    mut damage_query: Query<(Entity, &mut DamageBuffer), With<Health>>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
+) {
+    for (entity, mut active) in active_query.iter_mut() {
+        active.0.retain(|e, _| zone_query.contains(*e));
+        if active.0.is_empty() {
+            commands.entity(entity).remove::<ActiveDamageZones>();
+        }
+    }
+
+    let active_of = |entity: Option<Entity>| {
+        entity
+            .and_then(|e| active_query.get(e).ok())
+            .map(|(_, active)| active)
+    };
+    for (entity, mut damage_buf) in damage_query.iter_mut() {
+        for damage in damage_buf.0.iter_mut() {
+            let received = zone_multiplier(
+                active_of(Some(entity)),
+                damage,
+                DamageZoneTarget::Received,
+                &zone_query,
+            );
+            let dealt = zone_multiplier(
+                active_of(credit_damage_owner(
+                    damage.source,
+                    None,
+                    &parent_query,
+                    &owner_query,
+                )),
+                damage,
+                DamageZoneTarget::Dealt,
+                &zone_query,
+            );
+            if received != 1.0 || dealt != 1.0 {
+                damage.value *= (received * dealt).clamp(limits.min, limits.max);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEN: Damage = Damage {
+        ty: DamageVariant::Ballistic,
+        value: 10.0,
+        source: None,
+    };
+
+    fn zone_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<DamageZoneLimits>()
+            .add_event::<CollisionEvent>()
+            .add_systems(Update, (track_damage_zones, apply_damage_zones).chain());
+        app
+    }
+
+    fn damage_taken(app: &mut App, entity: Entity, damage: Damage) -> f32 {
+        app.world.get_mut::<DamageBuffer>(entity).unwrap().0 = vec![damage];
+        app.update();
+        let value = app.world.get::<DamageBuffer>(entity).unwrap().0[0].value;
+        app.world.get_mut::<DamageBuffer>(entity).unwrap().0.clear();
+        value
+    }
+
+    #[test]
+    fn enter_and_exit() {
+        let mut app = zone_app();
+        let e_zone = app
+            .world
+            .spawn(DamageZone::new(None, 0.5, Collider::ball(1.0)))
+            .id();
+        let e_body = app
+            .world
+            .spawn((Health(10.0), DamageBuffer::default()))
+            .id();
+        let parts = [0, 1].map(|_| app.world.spawn(Hitbox { target: e_body }).id());
+
+        for e_part in parts {
+            app.world.send_event(CollisionEvent::Started(
+                e_zone,
+                e_part,
+                CollisionEventFlags::SENSOR,
+            ));
+        }
+        app.update();
+        assert_eq!(
+            app.world
+                .get::<ActiveDamageZones>(e_body)
+                .and_then(|active| active.0.get(&e_zone))
+                .copied(),
+            Some(2),
+            "Didn't count both parts going in."
+        );
+
+        app.world.send_event(CollisionEvent::Stopped(
+            parts[0],
+            e_zone,
+            CollisionEventFlags::SENSOR,
+        ));
+        assert_eq!(
+            damage_taken(&mut app, e_body, TEN),
+            5.0,
+            "Left the zone while part of it was still inside."
+        );
+
+        app.world.send_event(CollisionEvent::Stopped(
+            parts[1],
+            e_zone,
+            CollisionEventFlags::SENSOR,
+        ));
+        assert_eq!(
+            damage_taken(&mut app, e_body, TEN),
+            10.0,
+            "Still scaled after leaving the zone."
+        );
+        app.update();
+        assert!(
+            app.world.get::<ActiveDamageZones>(e_body).is_none(),
+            "Kept `ActiveDamageZones` after leaving."
+        );
+    }
+
+    #[test]
+    fn stacking_clamp() {
+        let mut app = zone_app();
+        app.insert_resource(DamageZoneLimits { min: 0.5, max: 2.0 });
+        let e_body = app
+            .world
+            .spawn((Health(10.0), DamageBuffer::default()))
+            .id();
+        let zones = [
+            DamageZone::new(None, 0.5, Collider::ball(1.0)),
+            DamageZone::new(None, 0.5, Collider::ball(1.0)),
+            DamageZone::new(Some(DamageVariant::Fire), 32.0, Collider::ball(1.0)),
+        ]
+        .map(|zone| app.world.spawn(zone).id());
+        for e_zone in zones {
+            app.world.send_event(CollisionEvent::Started(
+                e_body,
+                e_zone,
+                CollisionEventFlags::SENSOR,
+            ));
+        }
+        app.update();
+
+        assert_eq!(
+            damage_taken(&mut app, e_body, TEN),
+            5.0,
+            "Stacked zones weren't clamped to the minimum."
+        );
+        assert_eq!(
+            damage_taken(
+                &mut app,
+                e_body,
+                Damage {
+                    ty: DamageVariant::Fire,
+                    ..TEN
+                }
+            ),
+            20.0,
+            "Stacked zones weren't clamped to the maximum."
+        );
+
+        app.world.despawn(zones[2]);
+        assert_eq!(
+            damage_taken(
+                &mut app,
+                e_body,
+                Damage {
+                    ty: DamageVariant::Fire,
+                    ..TEN
+                }
+            ),
+            5.0,
+            "Despawned zone still applied."
+        );
+    }
+
+    #[test]
+    fn dealt_by_owner() {
+        let mut app = zone_app();
+        let e_zone = app
+            .world
+            .spawn(
+                DamageZone::new(None, 2.0, Collider::ball(1.0))
+                    .with_target(DamageZoneTarget::Dealt),
+            )
+            .id();
+        let e_owner = app
+            .world
+            .spawn((Health(10.0), DamageBuffer::default()))
+            .id();
+        let e_item = app.world.spawn_empty().set_parent(e_owner).id();
+        let e_target = app
+            .world
+            .spawn((Health(10.0), DamageBuffer::default()))
+            .id();
+
+        app.world.send_event(CollisionEvent::Started(
+            e_zone,
+            e_owner,
+            CollisionEventFlags::SENSOR,
+        ));
+        app.update();
+
+        assert_eq!(
+            damage_taken(
+                &mut app,
+                e_target,
+                Damage {
+                    source: Some(e_item),
+                    ..TEN
+                }
+            ),
+            20.0,
+            "Item's damage didn't go by its owner's zones."
+        );
+    }
+}
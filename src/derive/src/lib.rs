@@ -87,6 +87,12 @@ fn impl_spawnable(input: DeriveInput) -> Result<proc_macro2::TokenStream, syn::E
             pub transform: Transform,
         }
 
+        impl From<Transform> for #event_ident {
+            fn from(transform: Transform) -> Self {
+                Self { transform }
+            }
+        }
+
         impl #grin_util::event::Spawnable for #ident {
             type Event = #event_ident;
         }
@@ -104,20 +110,36 @@ pub fn derive_typed_events(input: TokenStream) -> TokenStream {
 fn impl_typed_events(input: DeriveInput) -> Result<proc_macro2::TokenStream, syn::Error> {
     let ident = &input.ident;
 
+    let bevy = get_crate("bevy");
     let grin_util = get_crate("grin_util");
     let bevy_enum_filter = get_crate("bevy_enum_filter");
     let Data::Enum(data_enum) = &input.data else {
         return Err(syn::Error::new(ident.span(), "Cannot derive for non-enum."));
     };
-    let variants = data_enum.variants.iter().map(|var| &var.ident);
+    let variants = data_enum
+        .variants
+        .iter()
+        .map(|var| &var.ident)
+        .collect::<Vec<_>>();
 
     Ok(proc_macro2::TokenStream::from(quote! {
         impl #ident {
             /// Converts an `UntypedEvent` to its typed counterpart, annotated with the enum filter struct
-            /// for this variant.
-            pub fn typed_event<E: #grin_util::event::UntypedEvent>(&self, ev: &E) -> E::TypedEvent<impl Component> {
+            /// for this variant, and sends it.
+            ///
+            /// Each variant has its own typed event, so this can't just return it.
+            pub fn send_typed_event<E: #grin_util::event::UntypedEvent>(
+                &self,
+                world: &mut #bevy::prelude::World,
+                ev: &E,
+            )
+            where
+                #( E::TypedEvent<#bevy_enum_filter::Enum!(#ident::#variants)>: #bevy::prelude::Event ),*
+            {
                 match self {
-                    #( #ident::#variants => ev.typed::<#bevy_enum_filter::Enum!(#ident::#variants)>() ),*
+                    #( #ident::#variants => {
+                        world.send_event(ev.typed::<#bevy_enum_filter::Enum!(#ident::#variants)>());
+                    } ),*
                 }
             }
         }
@@ -9,6 +9,7 @@ use grin_render::{
     sketched::SketchUiImage,
     RenderLayer,
 };
+use grin_rig::humanoid::Humanoid;
 use html_parser::{Dom, Node};
 use itertools::Itertools;
 use serde::Deserialize;
@@ -27,12 +28,59 @@ impl FromWorld for DefaultTextStyle {
     }
 }
 
+/// Modifies the style of text inside a markup tag.
+pub type StyleTransform = Box<dyn Fn(&mut TextStyle) + Send + Sync>;
+
+/// Maps markup tag names in dialogue text to style changes.
+///
+/// `"I am <red>very</red> angry"` will color "very" with whatever `"red"` maps to.
+#[derive(Resource)]
+pub struct StyleTagMap(pub HashMap<String, StyleTransform>);
+
+impl Default for StyleTagMap {
+    fn default() -> Self {
+        let mut tags = Self(HashMap::new());
+        tags.insert("span", |_| {});
+        for (name, color) in [
+            ("red", Color::RED),
+            ("green", Color::GREEN),
+            ("blue", Color::BLUE),
+            ("yellow", Color::YELLOW),
+            ("cyan", Color::CYAN),
+            ("magenta", Color::FUCHSIA),
+            ("gray", Color::GRAY),
+        ] {
+            tags.insert(name, move |style| style.color = color);
+        }
+        tags.insert("big", |style| style.font_size *= 1.5);
+        tags.insert("small", |style| style.font_size *= 0.75);
+        tags
+    }
+}
+
+impl StyleTagMap {
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        transform: impl Fn(&mut TextStyle) + Send + Sync + 'static,
+    ) {
+        self.0.insert(name.into(), Box::new(transform));
+    }
+}
+
 #[derive(Resource, AssetCollection)]
 pub struct DialogueAssets {
     #[asset(key = "sfx.dialogue.eightball")]
     pub smirk_blip: Handle<AudioSource>,
     #[asset(key = "image.smirk-icon")]
     pub smirk_icon: Handle<SketchUiImage>,
+    /// For `Portrait::Live` when there's no one to look at.
+    #[asset(key = "image.portrait-placeholder")]
+    pub portrait_placeholder: Handle<Image>,
+    #[asset(key = "image.smirk-talk-0")]
+    pub smirk_talk_0: Handle<Image>,
+    #[asset(key = "image.smirk-talk-1")]
+    pub smirk_talk_1: Handle<Image>,
 }
 
 #[derive(Resource)]
@@ -77,10 +125,14 @@ impl Blip {
     }
 }
 
-#[derive(Component, Debug, Deserialize, Clone, EnumFilter, Default)]
+#[derive(Component, Debug, Deserialize, Clone, EnumFilter, Default, PartialEq, Eq, Hash)]
 pub enum Portrait {
     #[default]
     Smirk,
+    /// Rendered live from this entity's `Humanoid.head`, on the `AVATAR` layer, so whatever
+    /// the face is doing shows up. Can't go in dialogue files.
+    #[serde(skip)]
+    Live(Entity),
 }
 
 impl Portrait {
@@ -94,6 +146,16 @@ impl Portrait {
         // average rust program
         let e_portrait = match self {
             Portrait::Smirk => query_portrait::<Enum!(Portrait::Smirk)>(world)?,
+            Portrait::Live(entity) => match world.get::<Humanoid>(*entity) {
+                Some(humanoid) => humanoid.head,
+                None => {
+                    warn!("Live portrait of {:?} has no head to look at.", entity);
+                    return Ok(world
+                        .resource::<DialogueAssets>()
+                        .portrait_placeholder
+                        .clone());
+                }
+            },
         };
 
         Ok(add_gopro_world(
@@ -104,6 +166,7 @@ impl Portrait {
                     .looking_to(Vec3::Z, Vec3::Y),
                 size: UVec2::splat(240),
                 render_layers: RenderLayers::layer(RenderLayer::AVATAR as u8),
+                exclusive_layer: false,
             },
         ))
     }
@@ -114,6 +177,7 @@ impl Portrait {
 pub fn add_dialogue_assets(
     mut commands: Commands,
     default_style: Res<DefaultTextStyle>,
+    style_tags: Res<StyleTagMap>,
     dialogue_assets: Res<DialogueAssets>,
     mut dialogue_maps: ResMut<Assets<DialogueMap>>,
     mut assets: ResMut<Assets<super::Dialogue>>,
@@ -145,19 +209,28 @@ pub fn add_dialogue_assets(
                 text,
                 portrait,
                 blip,
+                blip_style,
                 cps,
                 stop_delay,
+                skippable,
+                auto_advance,
                 next,
             },
         ) in unparsed_dialogue_map.0.iter()
         {
             // AVERAGE RUST PROGRAM
             let dialogue = super::Dialogue {
-                text: parse_dialogue(text, &default_style.0),
+                text: parse_dialogue(text, &default_style.0, &style_tags),
                 portrait: portrait.clone(),
                 blip: blip.from_asset_collection(&dialogue_assets).clone(),
+                blip_style: blip_style
+                    .as_ref()
+                    .map(|style| style.parse(&dialogue_assets))
+                    .unwrap_or_default(),
                 cps: cps.unwrap_or(15.0),
                 stop_delay: stop_delay.unwrap_or(0.5),
+                skippable: skippable.unwrap_or(true),
+                auto_advance: *auto_advance,
                 next: match next {
                     DialogueNext::Continue(dialogue) => {
                         super::DialogueNext::Continue(dialogue.clone())
@@ -171,19 +244,34 @@ pub fn add_dialogue_assets(
                                          icon,
                                          text,
                                          dialogue,
+                                         requires,
                                      }| {
                                         super::DialogueOption {
                                             dialogue: dialogue.clone(),
+                                            requires: requires.clone(),
                                             icon: icon.as_ref().map(|icon| {
                                                 icon.from_asset_collection(&dialogue_assets).clone()
                                             }),
-                                            text: parse_dialogue(text, &default_style.0),
+                                            text: parse_dialogue(
+                                                text,
+                                                &default_style.0,
+                                                &style_tags,
+                                            ),
                                         }
                                     },
                                 )
                                 .collect_vec(),
                         ))
                     }
+                    DialogueNext::Branch {
+                        condition,
+                        if_true,
+                        if_false,
+                    } => super::DialogueNext::Branch {
+                        condition: condition.clone(),
+                        if_true: if_true.clone(),
+                        if_false: if_false.clone(),
+                    },
                     DialogueNext::Finish => super::DialogueNext::Finish,
                 },
             };
@@ -208,15 +296,58 @@ pub struct Dialogue {
     pub text: String,
     pub portrait: Portrait,
     pub blip: Blip,
+    pub blip_style: Option<BlipStyle>,
     pub cps: Option<f32>,
     pub stop_delay: Option<f32>,
+    pub skippable: Option<bool>,
+    pub auto_advance: Option<f32>,
     pub next: DialogueNext,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct BlipStyle {
+    #[serde(default)]
+    pub alternatives: Vec<Blip>,
+    pub selection: Option<BlipSelection>,
+    pub pitch_jitter: Option<f32>,
+    pub every: Option<usize>,
+    pub seed: Option<u64>,
+}
+
+impl BlipStyle {
+    pub fn parse(&self, assets: &DialogueAssets) -> super::BlipStyle {
+        super::BlipStyle {
+            alternatives: self
+                .alternatives
+                .iter()
+                .map(|blip| blip.from_asset_collection(assets).clone())
+                .collect_vec(),
+            selection: match self.selection {
+                Some(BlipSelection::RoundRobin) | None => super::BlipSelection::RoundRobin,
+                Some(BlipSelection::Random) => super::BlipSelection::Random,
+            },
+            pitch_jitter: self.pitch_jitter.unwrap_or(0.0),
+            every: self.every.unwrap_or(1),
+            seed: self.seed,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+pub enum BlipSelection {
+    RoundRobin,
+    Random,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub enum DialogueNext {
     Continue(String),
     Respond(DialogueOptions),
+    Branch {
+        condition: String,
+        if_true: String,
+        if_false: String,
+    },
     Finish,
 }
 
@@ -228,13 +359,14 @@ pub struct DialogueOption {
     pub icon: Option<Icon>,
     pub text: String,
     pub dialogue: String,
+    pub requires: Option<String>,
 }
 
-pub fn parse_dialogue(text: &str, default_style: &TextStyle) -> Text {
+pub fn parse_dialogue(text: &str, default_style: &TextStyle, tags: &StyleTagMap) -> Text {
     let mut sections = Vec::new();
     let dom = Dom::parse(text).expect("malformed html dialogue");
     for n in dom.children.iter() {
-        sections.append(&mut parse_section(n, default_style.clone()));
+        sections.append(&mut parse_section(n, default_style.clone(), tags));
     }
     Text::from_sections(sections)
 }
@@ -252,10 +384,23 @@ pub fn parse_color(color: Option<&str>) -> Result<Color, String> {
     Ok(Color::rgba(r?, g?, b?, a?))
 }
 
-pub fn parse_section(node: &Node, mut style: TextStyle) -> Vec<TextSection> {
+pub fn parse_section(node: &Node, mut style: TextStyle, tags: &StyleTagMap) -> Vec<TextSection> {
     match node {
         Node::Text(t) => vec![TextSection::new(t, style)],
         Node::Element(e) => {
+            let Some(transform) = tags.0.get(&e.name) else {
+                // pass it through as if it were regular text
+                warn!("Unrecognized markup tag in dialogue: {}", e.name);
+                let mut sections = vec![TextSection::new(format!("<{}>", e.name), style.clone())];
+                for n in e.children.iter() {
+                    sections.append(&mut parse_section(n, style.clone(), tags));
+                }
+                sections.push(TextSection::new(format!("</{}>", e.name), style));
+                return sections;
+            };
+
+            transform(&mut style);
+
             for (attr, v) in e.attributes.iter() {
                 match attr.as_str() {
                     "color" => style.color = parse_color(v.as_deref()).unwrap(),
@@ -268,7 +413,7 @@ pub fn parse_section(node: &Node, mut style: TextStyle) -> Vec<TextSection> {
 
             let mut sections = Vec::new();
             for n in e.children.iter() {
-                sections.append(&mut parse_section(n, style.clone()));
+                sections.append(&mut parse_section(n, style.clone(), tags));
             }
             sections
         }
@@ -289,6 +434,7 @@ mod tests {
                 :(
             "#,
             &TextStyle::default(),
+            &StyleTagMap::default(),
         ));
         assert_eq!(text.sections.len(), 5);
         assert_eq!(text.sections[0].style.color, Color::WHITE);
@@ -300,4 +446,72 @@ mod tests {
         assert_eq!(text.sections[2].style.font_size, 24.0);
         assert_eq!(text.sections[3].style.font_size, 12.0);
     }
+
+    #[test]
+    fn deserialize_branch() {
+        let map: DialogueMap = ron::from_str(
+            r#"
+            #![enable(implicit_some)]
+            DialogueMap({
+                "a": Dialogue(
+                    text: "a",
+                    portrait: Smirk,
+                    blip: Smirk,
+                    next: Branch(condition: "kills > 2", if_true: "b", if_false: "c"),
+                ),
+                "b": Dialogue(
+                    text: "b",
+                    portrait: Smirk,
+                    blip: Smirk,
+                    next: Respond(DialogueOptions([
+                        DialogueOption(text: "yes", dialogue: "c", requires: "killed_dummy"),
+                        DialogueOption(text: "no", dialogue: "c"),
+                    ])),
+                ),
+            })
+            "#,
+        )
+        .unwrap();
+
+        let DialogueNext::Branch {
+            condition,
+            if_true,
+            if_false,
+        } = &map.0["a"].next
+        else {
+            panic!("expected a branch");
+        };
+        assert_eq!(condition, "kills > 2");
+        assert_eq!(if_true, "b");
+        assert_eq!(if_false, "c");
+
+        let DialogueNext::Respond(DialogueOptions(options)) = &map.0["b"].next else {
+            panic!("expected a response");
+        };
+        assert_eq!(options[0].requires.as_deref(), Some("killed_dummy"));
+        assert_eq!(options[1].requires, None);
+    }
+
+    #[test]
+    fn parse_tags() {
+        let text = parse_dialogue(
+            "I am <red>very</red> angry... <shake>seriously</shake>",
+            &TextStyle::default(),
+            &StyleTagMap::default(),
+        );
+        assert_eq!(text.sections.len(), 6);
+        assert_eq!(text.sections[0].value.trim(), "I am");
+        assert_eq!(text.sections[0].style.color, Color::WHITE);
+        assert_eq!(text.sections[1].value.trim(), "very");
+        assert_eq!(text.sections[1].style.color, Color::RED);
+        assert_eq!(text.sections[2].value.trim(), "angry...");
+        assert_eq!(text.sections[2].style.color, Color::WHITE);
+        // unknown tags are literal text
+        assert_eq!(text.sections[3].value, "<shake>");
+        assert_eq!(text.sections[4].value.trim(), "seriously");
+        assert_eq!(text.sections[5].value, "</shake>");
+        assert!(text.sections[3..]
+            .iter()
+            .all(|s| s.style.color == Color::WHITE));
+    }
 }
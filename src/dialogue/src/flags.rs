@@ -0,0 +1,113 @@
+//! Game state that dialogue can branch on.
+
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum DialogueFlag {
+    Bool(bool),
+    Int(i32),
+}
+
+impl DialogueFlag {
+    pub fn as_int(&self) -> i32 {
+        match self {
+            Self::Bool(b) => *b as i32,
+            Self::Int(i) => *i,
+        }
+    }
+}
+
+impl From<bool> for DialogueFlag {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i32> for DialogueFlag {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+
+/// Named flags that `DialogueNext::Branch` and `DialogueOption::requires` check against.
+///
+/// Conditions are written as one of:
+/// - `flag`: the flag is `true` or nonzero.
+/// - `!flag`: the flag is `false`, zero or unset.
+/// - `flag <op> <int>`, where `op` is one of `==`, `!=`, `<`, `<=`, `>`, `>=`.
+///
+/// Unset flags are treated as `false`/`0`.
+#[derive(Resource, Default, Debug)]
+pub struct DialogueFlags(pub HashMap<String, DialogueFlag>);
+
+impl DialogueFlags {
+    pub fn set(&mut self, name: impl Into<String>, value: impl Into<DialogueFlag>) {
+        self.0.insert(name.into(), value.into());
+    }
+
+    pub fn get(&self, name: &str) -> i32 {
+        self.0.get(name).map(DialogueFlag::as_int).unwrap_or(0)
+    }
+
+    /// Evaluates a condition. Malformed conditions log an error and evaluate to `false`.
+    pub fn eval(&self, condition: &str) -> bool {
+        let condition = condition.trim();
+
+        if let Some(name) = condition.strip_prefix('!') {
+            return self.get(name.trim()) == 0;
+        }
+
+        // two character operators first so `<=` doesn't get read as `<`
+        for op in ["==", "!=", "<=", ">=", "<", ">"] {
+            let Some((name, value)) = condition.split_once(op) else {
+                continue;
+            };
+            let Ok(value) = value.trim().parse::<i32>() else {
+                error!("Malformed dialogue condition: {}", condition);
+                return false;
+            };
+            let flag = self.get(name.trim());
+            return match op {
+                "==" => flag == value,
+                "!=" => flag != value,
+                "<=" => flag <= value,
+                ">=" => flag >= value,
+                "<" => flag < value,
+                ">" => flag > value,
+                _ => unreachable!(),
+            };
+        }
+
+        self.get(condition) != 0
+    }
+
+    /// Evaluates an optional condition, where `None` is always met.
+    pub fn meets(&self, condition: Option<&str>) -> bool {
+        condition.map_or(true, |c| self.eval(c))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval() {
+        let mut flags = DialogueFlags::default();
+        flags.set("killed_dummy", true);
+        flags.set("kills", 3);
+
+        assert!(flags.eval("killed_dummy"));
+        assert!(!flags.eval("!killed_dummy"));
+        assert!(!flags.eval("picked_grin"));
+        assert!(flags.eval("!picked_grin"));
+        assert!(flags.eval("kills == 3"));
+        assert!(flags.eval("kills >= 3"));
+        assert!(flags.eval("kills<=3"));
+        assert!(!flags.eval("kills > 3"));
+        assert!(flags.eval("kills != 2"));
+        assert!(!flags.eval("kills < three"));
+        assert!(flags.meets(None));
+    }
+}
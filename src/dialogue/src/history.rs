@@ -0,0 +1,205 @@
+//! Scrollback for dialogue blocks that have already been spoken.
+
+use std::collections::VecDeque;
+
+use bevy::{input::mouse::MouseWheel, prelude::*, ui::FocusPolicy};
+
+use crate::{DefaultTextStyle, DialogueOption, Portrait};
+
+/// Key that toggles the history window.
+pub const DIALOGUE_HISTORY_KEY: KeyCode = KeyCode::Tab;
+
+/// Pixels scrolled per line of mouse wheel movement.
+pub const DIALOGUE_HISTORY_SCROLL_SPEED: f32 = 24.0;
+
+/// A single spoken block of dialogue.
+#[derive(Clone)]
+pub struct DialogueHistoryEntry {
+    /// Who said it.
+    pub portrait: Portrait,
+    /// What they said.
+    pub text: Text,
+    /// The option the player picked afterwards, if it was a `DialogueNext::Respond` block.
+    pub response: Option<DialogueOption>,
+}
+
+/// Every block of dialogue spoken so far, oldest first.
+///
+/// Persists after `DialogueEvent::Finish`. Cleared by `DialogueEvent::Reset`.
+#[derive(Resource)]
+pub struct DialogueHistory {
+    /// Maximum number of entries. The oldest entries are forgotten past this.
+    pub capacity: usize,
+    entries: VecDeque<DialogueHistoryEntry>,
+}
+
+impl Default for DialogueHistory {
+    fn default() -> Self {
+        Self::new(100)
+    }
+}
+
+impl DialogueHistory {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    pub fn push(&mut self, entry: DialogueHistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        while self.entries.len() >= self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Records the player's response to the most recent entry.
+    pub fn respond(&mut self, option: DialogueOption) {
+        if let Some(entry) = self.entries.back_mut() {
+            entry.response = Some(option);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &DialogueHistoryEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Component)]
+pub struct DialogueHistoryWindow;
+
+/// Contains the history entries. Moves up and down when scrolling.
+#[derive(Component, Default)]
+pub struct DialogueHistoryList {
+    pub scroll: f32,
+}
+
+pub fn init_dialogue_history(mut commands: Commands) {
+    commands
+        .spawn((
+            DialogueHistoryWindow,
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    top: Val::Percent(10.0),
+                    left: Val::Percent(10.0),
+                    width: Val::Percent(80.0),
+                    height: Val::Percent(60.0),
+                    padding: UiRect::all(Val::Px(16.0)),
+                    overflow: Overflow::clip_y(),
+                    ..Default::default()
+                },
+                background_color: BackgroundColor(Color::BLACK.with_a(0.75)),
+                focus_policy: FocusPolicy::Block,
+                z_index: ZIndex::Global(1001),
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                DialogueHistoryList::default(),
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Relative,
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(8.0),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+            ));
+        });
+}
+
+pub fn toggle_dialogue_history(
+    input: Res<ButtonInput<KeyCode>>,
+    mut window_query: Query<&mut Style, With<DialogueHistoryWindow>>,
+) {
+    if input.just_pressed(DIALOGUE_HISTORY_KEY) {
+        let mut style = window_query.single_mut();
+        style.display = match style.display {
+            Display::None => Display::Flex,
+            _ => Display::None,
+        };
+    }
+}
+
+pub fn display_dialogue_history(
+    mut commands: Commands,
+    history: Res<DialogueHistory>,
+    default_style: Res<DefaultTextStyle>,
+    list_query: Query<Entity, With<DialogueHistoryList>>,
+) {
+    if !history.is_changed() {
+        return;
+    }
+
+    let e_list = list_query.single();
+    commands
+        .entity(e_list)
+        .despawn_descendants()
+        .with_children(|parent| {
+            for DialogueHistoryEntry {
+                portrait,
+                text,
+                response,
+            } in history.entries()
+            {
+                let mut sections = vec![TextSection::new(
+                    format!("{:?}: ", portrait),
+                    default_style.0.clone(),
+                )];
+                sections.extend(text.sections.iter().cloned());
+                parent.spawn(TextBundle::from_sections(sections));
+
+                if let Some(DialogueOption { text, .. }) = response {
+                    let mut sections = vec![TextSection::new("> ", default_style.0.clone())];
+                    sections.extend(text.sections.iter().cloned());
+                    parent.spawn(TextBundle::from_sections(sections));
+                }
+            }
+        });
+}
+
+pub fn scroll_dialogue_history(
+    mut scroll_events: EventReader<MouseWheel>,
+    window_query: Query<
+        (&Style, &Node),
+        (With<DialogueHistoryWindow>, Without<DialogueHistoryList>),
+    >,
+    mut list_query: Query<(&mut Style, &Node, &mut DialogueHistoryList)>,
+) {
+    let Ok((window_style, window_node)) = window_query.get_single() else {
+        return;
+    };
+
+    if window_style.display == Display::None {
+        scroll_events.clear();
+        return;
+    }
+
+    let (mut style, node, mut list) = list_query.single_mut();
+    let max_scroll = (node.size().y - window_node.size().y).max(0.0);
+    for MouseWheel { y, .. } in scroll_events.read() {
+        list.scroll = (list.scroll - y * DIALOGUE_HISTORY_SCROLL_SPEED).clamp(0.0, max_scroll);
+    }
+    style.top = Val::Px(-list.scroll);
+}
@@ -0,0 +1,75 @@
+//! Dialogue window layout. Loaded from `assets/dialogue/default.window.ron`.
+
+use bevy::prelude::*;
+use serde::Deserialize;
+
+pub const DIALOGUE_WINDOW_CONFIG_PATH: &str = "dialogue/default.window.ron";
+
+#[derive(Asset, Resource, Reflect, Deserialize, Clone, Debug)]
+#[reflect(Resource)]
+pub struct DialogueWindowConfig {
+    /// Window height, in pixels.
+    pub height: f32,
+    /// Which edge of the screen the window sticks to.
+    pub anchor: WindowAnchor,
+    /// Padding around (and between) the window contents, in pixels.
+    pub padding: f32,
+    pub background: Color,
+    /// Where the portrait goes.
+    pub portrait: PortraitSide,
+    /// Maximum width of the text box, in pixels.
+    pub max_text_width: Option<f32>,
+}
+
+impl Default for DialogueWindowConfig {
+    fn default() -> Self {
+        Self {
+            height: 200.0,
+            anchor: WindowAnchor::Bottom,
+            padding: 8.0,
+            background: Color::BLACK.with_a(0.5),
+            portrait: PortraitSide::Left,
+            max_text_width: None,
+        }
+    }
+}
+
+#[derive(Reflect, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowAnchor {
+    Top,
+    #[default]
+    Bottom,
+}
+
+#[derive(Reflect, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PortraitSide {
+    #[default]
+    Left,
+    Right,
+    Hidden,
+}
+
+#[derive(Resource)]
+pub struct DialogueWindowConfigHandle(pub Handle<DialogueWindowConfig>);
+
+pub fn load_dialogue_window_config(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(DialogueWindowConfigHandle(
+        asset_server.load(DIALOGUE_WINDOW_CONFIG_PATH),
+    ));
+}
+
+/// Copies the config asset into the `DialogueWindowConfig` resource when it (re)loads.
+pub fn apply_dialogue_window_config(
+    mut config: ResMut<DialogueWindowConfig>,
+    handle: Res<DialogueWindowConfigHandle>,
+    configs: Res<Assets<DialogueWindowConfig>>,
+    mut events: EventReader<AssetEvent<DialogueWindowConfig>>,
+) {
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event {
+            if *id == handle.0.id() {
+                *config = configs.get(*id).unwrap().clone();
+            }
+        }
+    }
+}
@@ -1,13 +1,20 @@
 //! !!! SPAGHETTI WARNING !!!
 //!
 //! This module manages what you think it does.
-//! Also it only supports ASCII right now. Didn't think that far ahead. Whoops.
+//! Text is spoken one grapheme cluster at a time, so combining marks and CJK should behave.
 //!
 //! Did I mention I hate UI?
 
 pub mod asset_gen;
+pub mod flags;
+pub mod history;
+pub mod layout;
+pub mod portrait;
+
+use std::collections::VecDeque;
 
 use bevy::{
+    ecs::event::ManualEventReader,
     prelude::*,
     reflect::TypePath,
     ui::FocusPolicy,
@@ -16,11 +23,24 @@ use bevy::{
 use bevy_asset_loader::prelude::*;
 use bevy_common_assets::ron::RonAssetPlugin;
 use grin_asset::AssetLoadState;
+use grin_input::action::InputAction;
 use grin_render::sketched::SketchUiImage;
-use grin_util::keys::{InputExt, KeyCodeExt};
+use grin_time::scaling::TimeScaleImmune;
+use grin_util::{
+    keys::InputExt,
+    rng::{GrinRng, RNG_BLIP},
+};
 use itertools::Itertools;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use unicode_segmentation::UnicodeSegmentation;
 
-pub use self::asset_gen::{DefaultTextStyle, DialogueAssetLoadState, Portrait};
+pub use self::{
+    asset_gen::{DefaultTextStyle, DialogueAssetLoadState, Portrait, StyleTagMap},
+    flags::{DialogueFlag, DialogueFlags},
+    history::{DialogueHistory, DialogueHistoryEntry},
+    layout::{DialogueWindowConfig, PortraitSide, WindowAnchor},
+    portrait::{LivePortrait, PortraitAnimation, PortraitAnimations, PortraitAnimator},
+};
 
 /// Maps `Dialogue` string ID's (defined in assets file) to `Dialogue` handles.
 // the strings are like, handles... for handles.
@@ -34,26 +54,56 @@ pub struct DialoguePlugin;
 impl Plugin for DialoguePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<DefaultTextStyle>()
+            .init_resource::<GrinRng>()
+            .init_resource::<StyleTagMap>()
             .init_resource::<StopChars>()
+            .init_resource::<DialogueLineMeasure>()
+            .init_resource::<DialogueHistory>()
+            .init_resource::<DialogueFlags>()
+            .init_resource::<DialogueSelectConfig>()
+            .init_resource::<ActiveDialogue>()
+            .init_resource::<DialogueWindowConfig>()
+            .register_type::<DialogueWindowConfig>()
             .init_state::<DialogueAssetLoadState>()
             .configure_loading_state(
                 LoadingStateConfig::new(AssetLoadState::Loading)
                     .load_collection::<asset_gen::DialogueAssets>(),
             )
-            .add_plugins(RonAssetPlugin::<asset_gen::DialogueMap>::new(&[
-                "dialogue.ron",
-            ]))
+            .add_plugins((
+                RonAssetPlugin::<asset_gen::DialogueMap>::new(&["dialogue.ron"]),
+                RonAssetPlugin::<DialogueWindowConfig>::new(&["window.ron"]),
+                portrait::PortraitPlugin,
+            ))
             .add_event::<DialogueEvent>()
             .add_event::<SelectedDialogueOptionEvent>()
             .add_event::<DialoguePortraitEvent>()
+            .add_event::<DialogueConfirmEvent>()
+            .add_event::<DialogueStartedEvent>()
+            .add_event::<DialogueFinishedEvent>()
             .init_asset::<Dialogue>()
-            .add_systems(Startup, init_dialogue_box)
+            .add_systems(
+                Startup,
+                (
+                    init_dialogue_box,
+                    history::init_dialogue_history,
+                    layout::load_dialogue_window_config,
+                ),
+            )
+            .add_systems(
+                Update,
+                (
+                    layout::apply_dialogue_window_config,
+                    rebuild_dialogue_window,
+                )
+                    .chain(),
+            )
             .add_systems(Update, bevy_enum_filter::watch_for_enum::<Portrait>)
             .add_systems(
                 Update,
                 (
                     prepare_dialogue_block,
                     speak_dialogue,
+                    click_dialogue_options,
                     continue_dialogue,
                     apply_deferred,
                     select_dialogue_options,
@@ -61,10 +111,23 @@ impl Plugin for DialoguePlugin {
                     render_dialogue_portrait,
                     apply_deferred,
                     highlight_selected_dialogue,
+                    portrait::fall_back_from_despawned_portraits,
+                    portrait::animate_dialogue_portrait,
                 )
                     .chain()
                     .run_if(in_state(DialogueAssetLoadState::Success)),
             )
+            .add_systems(
+                Update,
+                (
+                    history::toggle_dialogue_history,
+                    history::display_dialogue_history,
+                    history::scroll_dialogue_history,
+                )
+                    .chain()
+                    .after(continue_dialogue)
+                    .run_if(in_state(DialogueAssetLoadState::Success)),
+            )
             .add_systems(
                 Update,
                 asset_gen::add_dialogue_assets
@@ -74,10 +137,11 @@ impl Plugin for DialoguePlugin {
     }
 }
 
-/// Procedurally iterates the characters in a block of dialogue.
+/// Procedurally iterates the graphemes in a block of dialogue.
 ///
 /// Characters in the `StopChars` resource will momentarily pause dialogue,
-/// but *only* if followed by whitespace.
+/// but *only* if followed by whitespace. Fullwidth stop characters pause regardless,
+/// because CJK text doesn't put spaces after punctuation.
 #[derive(Component)]
 pub struct TextMotor {
     /// Characters per second.
@@ -86,16 +150,161 @@ pub struct TextMotor {
     pub stop_delay: f32,
     /// Iterates `TextSection`s for the active block of dialogue.
     pub sections: Box<dyn Iterator<Item = TextSection> + Send + Sync + 'static>,
-    /// Iterates characters of the current `TextSection`.
-    pub chars: Box<dyn Iterator<Item = char> + Send + Sync + 'static>,
+    /// Iterates grapheme clusters of the current `TextSection`.
+    pub graphemes: Box<dyn Iterator<Item = String> + Send + Sync + 'static>,
     /// Sound blip when iterating a character.
-    pub blip: Handle<AudioSource>,
-    /// Accumulated time without writing a character.
+    pub blip: BlipPlayer,
+    /// Accumulated time without writing a grapheme.
     pub acc: f32,
-    /// Most recently pushed character.
-    pub latest_char: char,
+    /// Most recently pushed grapheme.
+    pub latest_grapheme: String,
     /// Whether this will skip on the next frame.
     pub skip: bool,
+    /// Whether the player is allowed to skip this block.
+    pub skippable: bool,
+    /// Lookahead buffer holding the rest of the current word, and the whitespace after it.
+    pub word: VecDeque<String>,
+    /// Approximate width of the line being written, in pixels.
+    pub line_width: f32,
+    /// Approximate width of a halfwidth character in the current `TextSection`, in pixels.
+    pub char_width: f32,
+}
+
+impl TextMotor {
+    /// Pulls the next grapheme, and whether a line break needs to go before it.
+    ///
+    /// Bevy re-wraps the text every time a grapheme is pushed, so without this words
+    /// jump to the next line halfway through being spoken.
+    pub fn next_grapheme(&mut self, max_width: f32) -> Option<(String, bool)> {
+        let mut wrap = false;
+
+        if self.word.is_empty() {
+            for g in self.graphemes.by_ref() {
+                // CJK doesn't use spaces, so every character is its own word
+                let end = is_whitespace(&g) || is_wide(&g);
+                self.word.push_back(g);
+                if end {
+                    break;
+                }
+            }
+            let word_width = self
+                .word
+                .iter()
+                .take_while(|g| !is_whitespace(g))
+                .map(|g| grapheme_width(g, self.char_width))
+                .sum::<f32>();
+            // if it doesn't fit on its own line either, it gets broken up below instead
+            if self.line_width > 0.0
+                && self.line_width + word_width > max_width
+                && word_width <= max_width
+            {
+                wrap = true;
+                self.line_width = 0.0;
+            }
+        }
+
+        let g = self.word.pop_front()?;
+        if g.contains('\n') {
+            self.line_width = 0.0;
+            return Some((g, wrap));
+        }
+
+        let width = grapheme_width(&g, self.char_width);
+        if !is_whitespace(&g) && self.line_width > 0.0 && self.line_width + width > max_width {
+            // long unbroken strings fall back to wrapping anywhere
+            wrap = true;
+            self.line_width = 0.0;
+        }
+        self.line_width += width;
+
+        Some((g, wrap))
+    }
+}
+
+/// How a `TextMotor` varies its blips.
+#[derive(Clone)]
+pub struct BlipStyle {
+    /// Blips to use alongside `Dialogue::blip`.
+    pub alternatives: Vec<Handle<AudioSource>>,
+    /// How to choose between blips.
+    pub selection: BlipSelection,
+    /// Playback speed is randomly offset within `[-pitch_jitter, pitch_jitter]`.
+    pub pitch_jitter: f32,
+    /// Only blip on every Nth audible grapheme.
+    pub every: usize,
+    /// Seeds the RNG, for the same blips every time. Otherwise it comes from `RNG_BLIP`.
+    pub seed: Option<u64>,
+}
+
+impl Default for BlipStyle {
+    fn default() -> Self {
+        Self {
+            alternatives: Vec::new(),
+            selection: BlipSelection::default(),
+            pitch_jitter: 0.0,
+            every: 1,
+            seed: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum BlipSelection {
+    #[default]
+    RoundRobin,
+    Random,
+}
+
+/// Picks sounds and pitches for a `TextMotor` according to a `BlipStyle`.
+pub struct BlipPlayer {
+    pub blip: Handle<AudioSource>,
+    pub style: BlipStyle,
+    rng: StdRng,
+    /// Audible graphemes so far.
+    count: usize,
+    /// Blips played so far.
+    played: usize,
+}
+
+impl BlipPlayer {
+    /// Styles without a `seed` get one from `rng`.
+    pub fn new(blip: Handle<AudioSource>, style: BlipStyle, rng: &mut impl Rng) -> Self {
+        Self {
+            blip,
+            rng: StdRng::seed_from_u64(style.seed.unwrap_or_else(|| rng.gen())),
+            style,
+            count: 0,
+            played: 0,
+        }
+    }
+
+    /// Registers an audible grapheme. Returns whether it should blip.
+    pub fn count_grapheme(&mut self) -> bool {
+        let blip = self.count % self.style.every.max(1) == 0;
+        self.count += 1;
+        blip
+    }
+
+    /// Returns the next blip and its playback speed.
+    pub fn next_blip(&mut self) -> (Handle<AudioSource>, f32) {
+        let n = self.style.alternatives.len() + 1;
+        let i = match self.style.selection {
+            BlipSelection::RoundRobin => self.played % n,
+            BlipSelection::Random => self.rng.gen_range(0..n),
+        };
+        self.played += 1;
+
+        let speed = match self.style.pitch_jitter {
+            j if j > 0.0 => 1.0 + self.rng.gen_range(-j..=j),
+            _ => 1.0,
+        };
+
+        let blip = match i {
+            0 => self.blip.clone(),
+            i => self.style.alternatives[i - 1].clone(),
+        };
+        (blip, speed)
+    }
 }
 
 /// Which characters followed by whitespace indicate a "pause" in dialogue.
@@ -104,10 +313,136 @@ pub struct StopChars(pub HashSet<char>);
 
 impl Default for StopChars {
     fn default() -> Self {
-        Self(HashSet::from_iter(['.', ',', ';', ':', '!', '?']))
+        Self(HashSet::from_iter([
+            '.', ',', ';', ':', '!', '?', '。', '、', '！', '？', '，', '：', '；',
+        ]))
     }
 }
 
+impl StopChars {
+    /// Whether dialogue should pause between `prev` and `next`.
+    pub fn is_stop(&self, prev: &str, next: &str) -> bool {
+        let Some(stop) = prev
+            .chars()
+            .filter(|c| !is_silent(*c))
+            .last()
+            .filter(|c| self.0.contains(c))
+        else {
+            return false;
+        };
+        is_fullwidth(stop)
+            || next
+                .chars()
+                .all(|c| c.is_whitespace() || is_closing_quote(c))
+    }
+}
+
+/// Approximate character widths for word wrapping, as a fraction of the font size.
+///
+/// There's no easy way to get glyph advances before the text is laid out, so this has to do.
+/// Fullwidth characters are assumed to be twice as wide.
+#[derive(Resource)]
+pub struct DialogueLineMeasure {
+    /// Halfwidth character width for fonts not in `fonts`.
+    pub char_width: f32,
+    /// Halfwidth character width for specific fonts.
+    pub fonts: HashMap<AssetId<Font>, f32>,
+}
+
+impl Default for DialogueLineMeasure {
+    fn default() -> Self {
+        Self {
+            char_width: 0.5,
+            fonts: HashMap::default(),
+        }
+    }
+}
+
+impl DialogueLineMeasure {
+    /// Halfwidth character width for this style, in pixels.
+    pub fn char_width(&self, style: &TextStyle) -> f32 {
+        let width = self
+            .fonts
+            .get(&style.font.id())
+            .copied()
+            .unwrap_or(self.char_width);
+        width * style.font_size
+    }
+}
+
+/// Approximate width of a grapheme cluster, in pixels.
+pub fn grapheme_width(grapheme: &str, char_width: f32) -> f32 {
+    match grapheme.chars().find(|c| !is_silent(*c)) {
+        Some(c) if is_wide_char(c) => char_width * 2.0,
+        Some(_) => char_width,
+        None if is_whitespace(grapheme) => char_width,
+        None => 0.0,
+    }
+}
+
+fn is_whitespace(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+fn is_wide(grapheme: &str) -> bool {
+    grapheme.chars().next().is_some_and(is_wide_char)
+}
+
+/// East Asian wide characters, more or less.
+fn is_wide_char(c: char) -> bool {
+    is_fullwidth(c)
+        || matches!(
+            c,
+            // hangul jamo
+            '\u{1100}'..='\u{115F}'
+                // CJK radicals through yi
+                | '\u{2E80}'..='\u{A4CF}'
+                // hangul syllables
+                | '\u{AC00}'..='\u{D7A3}'
+                // CJK compatibility ideographs
+                | '\u{F900}'..='\u{FAFF}'
+                // emoji
+                | '\u{1F300}'..='\u{1F64F}'
+                | '\u{1F900}'..='\u{1F9FF}'
+                // CJK extensions
+                | '\u{20000}'..='\u{3FFFD}'
+        )
+}
+
+/// Characters in the CJK punctuation and halfwidth/fullwidth forms blocks.
+pub fn is_fullwidth(c: char) -> bool {
+    matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FFEF}')
+}
+
+fn is_closing_quote(c: char) -> bool {
+    matches!(c, '"' | '\'' | '”' | '’' | '」' | '』')
+}
+
+/// Characters that take up no space on their own and shouldn't make a sound.
+pub fn is_silent(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            // combining diacritical marks (+ extended, supplement, for symbols, half marks)
+            '\u{0300}'..='\u{036F}'
+                | '\u{1AB0}'..='\u{1AFF}'
+                | '\u{1DC0}'..='\u{1DFF}'
+                | '\u{20D0}'..='\u{20FF}'
+                | '\u{FE20}'..='\u{FE2F}'
+                // zero width space, non-joiner, joiner
+                | '\u{200B}'..='\u{200D}'
+                // variation selectors
+                | '\u{FE00}'..='\u{FE0F}'
+                // BOM
+                | '\u{FEFF}'
+        )
+}
+
+/// Whether a grapheme cluster should play a blip when spoken.
+pub fn is_audible(grapheme: &str) -> bool {
+    !grapheme.chars().all(is_silent)
+}
+
 #[derive(Component)]
 pub struct DialogueWindow;
 
@@ -123,9 +458,109 @@ pub struct DialogueSelector;
 #[derive(Component)]
 pub struct IconContainer;
 
-pub fn init_dialogue_box(mut commands: Commands) {
+#[derive(Resource, Default)]
+pub struct DialogueSelectConfig {
+    /// Whether selection wraps from the last option to the first and vice versa.
+    pub wrap: bool,
+}
+
+/// Repeats option selection while up/down is held.
+///
+/// Moves once on the initial press, then once every `interval` after `delay`.
+#[derive(Component)]
+pub struct SelectRepeat {
+    /// Seconds before repeating.
+    pub delay: f32,
+    /// Seconds between repeats.
+    pub interval: f32,
+    held: Option<HeldDirection>,
+}
+
+struct HeldDirection {
+    direction: isize,
+    elapsed: f32,
+    repeats: usize,
+}
+
+impl Default for SelectRepeat {
+    fn default() -> Self {
+        Self {
+            delay: 0.35,
+            interval: 0.1,
+            held: None,
+        }
+    }
+}
+
+impl SelectRepeat {
+    /// Returns how many options to move this frame, in the held `direction` (-1, 0 or 1).
+    pub fn tick(&mut self, direction: isize, delta: f32) -> usize {
+        if direction == 0 {
+            self.held = None;
+            return 0;
+        }
+
+        match &mut self.held {
+            Some(held) if held.direction == direction => {
+                held.elapsed += delta;
+                let total = match held.elapsed >= self.delay {
+                    true => ((held.elapsed - self.delay) / self.interval).floor() as usize + 1,
+                    false => 0,
+                };
+                let steps = total - held.repeats;
+                held.repeats = total;
+                steps
+            }
+            _ => {
+                self.held = Some(HeldDirection {
+                    direction,
+                    elapsed: 0.0,
+                    repeats: 0,
+                });
+                1
+            }
+        }
+    }
+}
+
+pub fn init_dialogue_box(mut commands: Commands, config: Res<DialogueWindowConfig>) {
+    spawn_dialogue_window(&mut commands, &config);
+}
+
+/// Respawns the dialogue window when `DialogueWindowConfig` changes.
+///
+/// Whatever was being said is lost, so don't do this mid-conversation.
+pub fn rebuild_dialogue_window(
+    mut commands: Commands,
+    config: Res<DialogueWindowConfig>,
+    window_query: Query<(Entity, &Style), With<DialogueWindow>>,
+) {
+    if !config.is_changed() || config.is_added() {
+        return;
+    }
+
+    let Ok((e_window, style)) = window_query.get_single() else {
+        return;
+    };
+    let display = style.display;
+    commands.entity(e_window).despawn_recursive();
+
+    let e_window = spawn_dialogue_window(&mut commands, &config);
+    commands.add(move |world: &mut World| {
+        if let Some(mut style) = world.get_mut::<Style>(e_window) {
+            style.display = display;
+        }
+    });
+}
+
+pub fn spawn_dialogue_window(commands: &mut Commands, config: &DialogueWindowConfig) -> Entity {
     // mfw I spend multiple days figuring out why html/css is giving me different layouts ;)))
     // https://github.com/bevyengine/bevy/issues/1490
+    let (top, bottom) = match config.anchor {
+        WindowAnchor::Top => (Val::Percent(0.0), Val::Auto),
+        WindowAnchor::Bottom => (Val::Auto, Val::Percent(0.0)),
+    };
+
     commands
         .spawn((
             DialogueWindow,
@@ -134,42 +569,36 @@ pub fn init_dialogue_box(mut commands: Commands) {
                     position_type: PositionType::Absolute,
                     direction: Direction::LeftToRight,
                     flex_direction: FlexDirection::Row,
-                    bottom: Val::Percent(0.0),
+                    top,
+                    bottom,
                     right: Val::Percent(0.0),
-                    padding: UiRect::all(Val::Px(8.0)),
+                    padding: UiRect::all(Val::Px(config.padding)),
                     width: Val::Percent(100.0),
-                    height: Val::Px(200.0),
-                    column_gap: Val::Px(8.0),
+                    height: Val::Px(config.height),
+                    column_gap: Val::Px(config.padding),
                     ..Default::default()
                 },
-                background_color: BackgroundColor(Color::BLACK.with_a(0.5)),
+                background_color: BackgroundColor(config.background),
                 focus_policy: FocusPolicy::Block,
                 z_index: ZIndex::Global(1000),
                 ..Default::default()
             },
         ))
         .with_children(|parent| {
-            parent.spawn((
-                DialoguePortrait,
-                ImageBundle {
-                    style: Style {
-                        aspect_ratio: Some(1.0),
-                        height: Val::Percent(100.0),
-                        ..Default::default()
-                    },
-                    background_color: BackgroundColor(Color::WHITE),
-                    ..Default::default()
-                },
-            ));
+            if config.portrait != PortraitSide::Right {
+                spawn_dialogue_portrait(parent, config.portrait);
+            }
+
             parent
                 .spawn(NodeBundle {
                     style: Style {
                         flex_grow: 1.0,
                         flex_basis: Val::Px(0.0),
-                        padding: UiRect::all(Val::Px(16.0)),
+                        max_width: config.max_text_width.map_or(Val::Auto, Val::Px),
+                        padding: UiRect::all(Val::Px(config.padding * 2.0)),
                         ..Default::default()
                     },
-                    background_color: BackgroundColor(Color::BLACK.with_a(0.5)),
+                    background_color: BackgroundColor(config.background),
                     ..Default::default()
                 })
                 .with_children(|parent| {
@@ -177,6 +606,8 @@ pub fn init_dialogue_box(mut commands: Commands) {
                         DialogueText,
                         TextBundle {
                             style: Style {
+                                // `TextMotor` measures lines against this
+                                width: Val::Percent(100.0),
                                 height: Val::Percent(100.0),
                                 ..Default::default()
                             },
@@ -187,6 +618,7 @@ pub fn init_dialogue_box(mut commands: Commands) {
 
             parent.spawn((
                 DialogueSelector,
+                SelectRepeat::default(),
                 NodeBundle {
                     style: Style {
                         flex_grow: 1.0,
@@ -194,29 +626,86 @@ pub fn init_dialogue_box(mut commands: Commands) {
                         flex_direction: FlexDirection::Column,
                         ..Default::default()
                     },
-                    background_color: BackgroundColor(Color::BLACK.with_a(0.5)),
+                    background_color: BackgroundColor(config.background),
                     ..Default::default()
                 },
             ));
-        });
+
+            if config.portrait == PortraitSide::Right {
+                spawn_dialogue_portrait(parent, config.portrait);
+            }
+        })
+        .id()
 }
 
-#[derive(Asset, Default, Clone, TypePath)]
+fn spawn_dialogue_portrait(parent: &mut ChildBuilder, side: PortraitSide) {
+    parent.spawn((
+        DialoguePortrait,
+        ImageBundle {
+            style: Style {
+                display: match side {
+                    PortraitSide::Hidden => Display::None,
+                    _ => Display::Flex,
+                },
+                aspect_ratio: Some(1.0),
+                height: Val::Percent(100.0),
+                ..Default::default()
+            },
+            background_color: BackgroundColor(Color::WHITE),
+            ..Default::default()
+        },
+    ));
+}
+
+#[derive(Asset, Clone, TypePath)]
 pub struct Dialogue {
     pub text: Text,
     pub portrait: Portrait,
     pub blip: Handle<AudioSource>,
+    pub blip_style: BlipStyle,
     pub cps: f32,
     pub stop_delay: f32,
+    /// Whether the player can skip the text motor.
+    pub skippable: bool,
+    /// Continues automatically this many seconds after the text motor finishes.
+    pub auto_advance: Option<f32>,
     pub next: DialogueNext,
 }
 
+impl Default for Dialogue {
+    fn default() -> Self {
+        Self {
+            text: Text::default(),
+            portrait: Portrait::default(),
+            blip: Handle::default(),
+            blip_style: BlipStyle::default(),
+            cps: 0.0,
+            stop_delay: 0.0,
+            skippable: true,
+            auto_advance: None,
+            next: DialogueNext::default(),
+        }
+    }
+}
+
+/// Continues dialogue when the timer finishes, as if the player pressed enter.
+///
+/// Only ticks once the `TextMotor` is done.
+#[derive(Component)]
+pub struct AutoAdvance(pub Timer);
+
 #[derive(Component, Default, Clone)]
 pub enum DialogueNext {
     /// Another block of text.
     Continue(String),
     /// Let the player respond.
     Respond(DialogueOptions),
+    /// Another block of text, depending on a `DialogueFlags` condition.
+    Branch {
+        condition: String,
+        if_true: String,
+        if_false: String,
+    },
     #[default]
     Finish,
 }
@@ -225,6 +714,10 @@ pub enum DialogueNext {
 pub enum DialogueEvent {
     Say(Handle<Dialogue>),
     Finish,
+    /// Clears the `DialogueHistory`.
+    Reset,
+    /// Stops the active block of dialogue and hides the window.
+    Interrupt,
 }
 
 #[derive(Component, Clone)]
@@ -240,8 +733,29 @@ impl DialogueOptions {
             selected: 0,
         }
     }
+
+    /// Changes the selected option, returning the event if it actually changed.
+    pub fn select(&mut self, index: usize) -> Option<SelectedDialogueOptionEvent> {
+        let deselected = self.selected;
+        (index != deselected && index < self.options.len()).then(|| {
+            self.selected = index;
+            SelectedDialogueOptionEvent {
+                option: self.options[index].clone(),
+                selected: index,
+                deselected: Some(deselected),
+            }
+        })
+    }
 }
 
+/// The index of the option this UI node represents.
+#[derive(Component)]
+pub struct DialogueOptionButton(pub usize);
+
+/// Continues dialogue as if the player pressed enter.
+#[derive(Event)]
+pub struct DialogueConfirmEvent;
+
 #[derive(Clone)]
 pub struct DialogueOption {
     /// The icon when selecting this option.
@@ -250,77 +764,204 @@ pub struct DialogueOption {
     pub text: Text,
     /// Dialogue after selecting this option.
     pub dialogue: String,
+    /// `DialogueFlags` condition for this option to show up.
+    pub requires: Option<String>,
 }
 
 #[derive(Component)]
 pub struct DialogueOptionIcon;
 
+/// Sent when a conversation starts, i.e. the first `DialogueEvent::Say` since the last one ended.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct DialogueStartedEvent {
+    /// ID of the first block of dialogue.
+    pub id: String,
+}
+
+/// Sent when a conversation reaches `DialogueNext::Finish`.
+#[derive(Event, Debug, Clone, PartialEq, Eq)]
+pub struct DialogueFinishedEvent {
+    /// ID of the first block of dialogue.
+    pub id: String,
+    /// Index of each option picked along the way, in order.
+    pub choices: Vec<usize>,
+}
+
+/// The conversation in progress, if there is one.
+#[derive(Resource, Default, Debug)]
+pub struct ActiveDialogue {
+    pub id: Option<String>,
+    pub choices: Vec<usize>,
+}
+
 #[derive(Event)]
 pub struct DialoguePortraitEvent {
     pub portrait: Portrait,
+    /// Shows this instead of the talking animation.
+    pub emote: Option<Handle<Image>>,
 }
 
 pub fn prepare_dialogue_block(
     mut commands: Commands,
     dialogue_assets: Res<Assets<Dialogue>>,
+    dialogue_map: Res<DialogueMap>,
     mut text_query: Query<(Entity, &mut Text), With<DialogueText>>,
+    selector_query: Query<Entity, With<DialogueSelector>>,
     mut window_query: Query<&mut Style, With<DialogueWindow>>,
+    mut history: ResMut<DialogueHistory>,
+    mut active: ResMut<ActiveDialogue>,
     mut events: EventReader<DialogueEvent>,
     mut portrait_events: EventWriter<DialoguePortraitEvent>,
+    mut started_events: EventWriter<DialogueStartedEvent>,
+    mut rng: ResMut<GrinRng>,
 ) {
     let (e_text, mut text) = text_query.single_mut();
+    let e_select = selector_query.single();
     for event in events.read() {
-        text.sections.clear();
         match event {
             DialogueEvent::Say(h_dialogue) => {
+                // don't let anything from an unfinished block leak into this one
+                interrupt_dialogue(&mut commands, e_text, e_select);
+                text.sections.clear();
+                window_query.single_mut().display = Display::Flex;
+
+                if active.id.is_none() {
+                    // handles don't know their own ID's so look it up backwards
+                    let id = dialogue_map
+                        .0
+                        .iter()
+                        .find_map(|(id, h)| (h == h_dialogue).then(|| id.clone()))
+                        .unwrap_or_default();
+                    *active = ActiveDialogue {
+                        id: Some(id.clone()),
+                        choices: Vec::new(),
+                    };
+                    started_events.send(DialogueStartedEvent { id });
+                }
+
                 let Dialogue {
                     text,
                     portrait,
                     blip,
+                    blip_style,
                     cps,
                     stop_delay,
+                    skippable,
+                    auto_advance,
                     next,
                 } = dialogue_assets.get(h_dialogue).unwrap().clone();
 
+                history.push(DialogueHistoryEntry {
+                    portrait: portrait.clone(),
+                    text: text.clone(),
+                    response: None,
+                });
+
                 commands.entity(e_text).insert((
                     TextMotor {
                         cps,
                         stop_delay,
                         sections: Box::new(text.sections.into_iter()),
                         // will be filled on the first iteration
-                        chars: Box::new(std::iter::empty()),
-                        blip: blip.clone(),
+                        graphemes: Box::new(std::iter::empty()),
+                        blip: BlipPlayer::new(blip, blip_style, rng.fork(RNG_BLIP)),
                         acc: 0.0,
-                        latest_char: '\n', // placeholder
+                        latest_grapheme: String::new(),
                         skip: false,
+                        skippable,
+                        word: VecDeque::new(),
+                        line_width: 0.0,
+                        char_width: 0.0,
                     },
                     next,
                 ));
 
+                match auto_advance {
+                    Some(secs) => {
+                        commands
+                            .entity(e_text)
+                            .insert(AutoAdvance(Timer::from_seconds(secs, TimerMode::Once)));
+                    }
+                    None => {
+                        commands.entity(e_text).remove::<AutoAdvance>();
+                    }
+                }
+
                 // this is separated into an exclusive access system
                 // (see `render_dialogue_portrait`)
                 portrait_events.send(DialoguePortraitEvent {
                     portrait: portrait.clone(),
+                    emote: None,
                 });
             }
             DialogueEvent::Finish => {
+                text.sections.clear();
                 let mut style = window_query.single_mut();
                 style.display = Display::None;
+                *active = ActiveDialogue::default();
+            }
+            // the window's left alone, this is just the scrollback
+            DialogueEvent::Reset => {
+                history.clear();
+            }
+            DialogueEvent::Interrupt => {
+                interrupt_dialogue(&mut commands, e_text, e_select);
+                text.sections.clear();
+                window_query.single_mut().display = Display::None;
+                *active = ActiveDialogue::default();
             }
         }
     }
 }
 
-pub fn render_dialogue_portrait(world: &mut World) {
+/// Removes the state of the active block of dialogue.
+fn interrupt_dialogue(commands: &mut Commands, e_text: Entity, e_select: Entity) {
+    commands
+        .entity(e_text)
+        .remove::<(TextMotor, AutoAdvance, DialogueNext)>();
+    commands
+        .entity(e_select)
+        .remove::<DialogueOptions>()
+        .despawn_descendants();
+}
+
+pub fn render_dialogue_portrait(
+    world: &mut World,
+    mut reader: Local<ManualEventReader<DialoguePortraitEvent>>,
+) {
     let e_portrait = world
         .query_filtered::<Entity, With<DialoguePortrait>>()
         .single(world);
 
     world.resource_scope::<Events<DialoguePortraitEvent>, _>(|world, events| {
-        for DialoguePortraitEvent { portrait } in events.get_reader().read(&events) {
-            match portrait.render_target(world) {
+        for DialoguePortraitEvent { portrait, emote } in reader.read(&events) {
+            // same face, same camera
+            let reused = match portrait {
+                Portrait::Live(entity) => world
+                    .get::<LivePortrait>(e_portrait)
+                    .filter(|live| live.entity == *entity)
+                    .map(|live| live.image.clone()),
+                _ => None,
+            };
+            match reused.map_or_else(|| portrait.render_target(world), Ok) {
                 Ok(image) => {
-                    world.entity_mut(e_portrait).insert(UiImage::new(image));
+                    let animation = world
+                        .resource::<PortraitAnimations>()
+                        .0
+                        .get(portrait)
+                        .cloned()
+                        .unwrap_or_default();
+                    let mut animator = PortraitAnimator::new(image.clone(), animation);
+                    animator.emote = emote.clone();
+                    let mut e = world.entity_mut(e_portrait);
+                    e.insert((UiImage::new(image.clone()), animator));
+                    match portrait {
+                        Portrait::Live(entity) => e.insert(LivePortrait {
+                            entity: *entity,
+                            image,
+                        }),
+                        _ => e.remove::<LivePortrait>(),
+                    };
                 }
                 Err(e) => error!(
                     "Attempted to get a portrait for nonexistent component: {}",
@@ -335,38 +976,57 @@ pub fn speak_dialogue(
     mut commands: Commands,
     time: Res<Time>,
     stop_chars: Res<StopChars>,
+    line_measure: Res<DialogueLineMeasure>,
     sink_query: Query<&AudioSink>,
-    mut text_query: Query<(Entity, &mut Text, &mut TextMotor), With<DialogueText>>,
+    mut text_query: Query<(Entity, &mut Text, &mut TextMotor, &Node), With<DialogueText>>,
 ) {
-    let Ok((e_text, mut text, mut motor)) = text_query.get_single_mut() else {
+    let Ok((e_text, mut text, mut motor, node)) = text_query.get_single_mut() else {
         return;
     };
 
+    // layout hasn't happened yet
+    let max_width = match node.size().x {
+        w if w > 0.0 => w,
+        _ => f32::INFINITY,
+    };
+
     motor.acc += time.delta_seconds();
-    let mut spoke = false;
+    let mut blip = false;
 
     while motor.skip || motor.acc >= 1.0 / motor.cps {
-        match motor.chars.next() {
-            Some(c) => {
-                text.sections.last_mut().unwrap().value.push(c);
+        match motor.next_grapheme(max_width) {
+            Some((g, wrap)) => {
+                let section = text.sections.last_mut().unwrap();
+                // not a real character, so it doesn't take time or count as the latest grapheme
+                if wrap {
+                    section.value.push('\n');
+                }
+                section.value.push_str(&g);
 
-                if !spoke && !c.is_whitespace() {
-                    spoke = true;
+                if is_audible(&g) && motor.blip.count_grapheme() {
+                    blip = true;
                 }
 
                 // apply extra delay for punctuation
-                if (c.is_whitespace() || c == '"') && stop_chars.0.contains(&motor.latest_char) {
+                if stop_chars.is_stop(&motor.latest_grapheme, &g) {
                     motor.acc -= motor.stop_delay;
                 } else {
                     motor.acc -= 1.0 / motor.cps;
                 }
 
-                motor.latest_char = c;
+                motor.latest_grapheme = g;
             }
             None => match motor.sections.next() {
                 Some(s) => {
                     // copy the style, but put the text in the motor
-                    motor.chars = Box::new(s.value.chars().collect_vec().into_iter());
+                    motor.graphemes = Box::new(
+                        s.value
+                            .graphemes(true)
+                            .map(String::from)
+                            .collect_vec()
+                            .into_iter(),
+                    );
+                    motor.char_width = line_measure.char_width(&s.style);
                     text.sections.push(TextSection::from_style(s.style));
                 }
                 None => {
@@ -378,15 +1038,21 @@ pub fn speak_dialogue(
         }
     }
 
-    if spoke {
+    if blip {
         // terminate the current blip since it's not looking for overlaps
         if let Ok(blip) = sink_query.get(e_text) {
             blip.stop();
+            // otherwise the new source doesn't get played
+            commands.entity(e_text).remove::<AudioSink>();
         }
-        commands.entity(e_text).insert(AudioBundle {
-            source: motor.blip.clone(),
-            ..Default::default()
-        });
+        let (source, speed) = motor.blip.next_blip();
+        commands.entity(e_text).insert((
+            AudioBundle {
+                source,
+                settings: PlaybackSettings::ONCE.with_speed(speed),
+            },
+            TimeScaleImmune,
+        ));
     }
 }
 
@@ -399,16 +1065,20 @@ pub fn display_dialogue_options(
     };
 
     commands.entity(e_options).with_children(|parent| {
-        for DialogueOption { text, .. } in options.options.iter() {
+        for (i, DialogueOption { text, .. }) in options.options.iter().enumerate() {
             parent
-                .spawn(NodeBundle {
-                    style: Style {
-                        min_height: Val::Px(40.0),
-                        padding: UiRect::all(Val::Px(4.0)),
+                .spawn((
+                    DialogueOptionButton(i),
+                    Interaction::default(),
+                    NodeBundle {
+                        style: Style {
+                            min_height: Val::Px(40.0),
+                            padding: UiRect::all(Val::Px(4.0)),
+                            ..Default::default()
+                        },
                         ..Default::default()
                     },
-                    ..Default::default()
-                })
+                ))
                 .with_children(|parent| {
                     parent.spawn((
                         IconContainer,
@@ -440,25 +1110,46 @@ pub struct SelectedDialogueOptionEvent {
 
 pub fn select_dialogue_options(
     mut commands: Commands,
+    time: Res<Time>,
     input: Res<ButtonInput<KeyCode>>,
+    actions: Res<ButtonInput<InputAction>>,
+    config: Res<DialogueSelectConfig>,
     dialogue_map: Res<DialogueMap>,
     dialogue_assets: Res<Assets<Dialogue>>,
-    mut options_query: Query<(Entity, &mut DialogueOptions), With<DialogueSelector>>,
+    mut options_query: Query<
+        (Entity, &mut DialogueOptions, &mut SelectRepeat),
+        With<DialogueSelector>,
+    >,
     mut events: EventWriter<SelectedDialogueOptionEvent>,
 ) {
-    let Ok((e_options, mut options)) = options_query.get_single_mut() else {
+    let Ok((e_options, mut options, mut repeat)) = options_query.get_single_mut() else {
         return;
     };
 
     let mut changed = false;
     let pre_selected = options.selected;
 
-    if input.any_pressed(KeyCode::ANY_UP) && options.selected > 0 {
-        options.selected -= 1;
-        changed = true;
-    } else if input.any_pressed(KeyCode::ANY_DOWN) && options.selected < options.options.len() - 1 {
-        options.selected += 1;
-        changed = true;
+    let direction = match (
+        actions.pressed(InputAction::Up),
+        actions.pressed(InputAction::Down),
+    ) {
+        (true, false) => -1,
+        (false, true) => 1,
+        _ => 0,
+    };
+    let steps = repeat.tick(direction, time.delta_seconds());
+    if steps > 0 {
+        let n = options.options.len();
+        let selected = match (config.wrap, direction) {
+            (true, _) => (options.selected as isize + direction * steps as isize)
+                .rem_euclid(n as isize) as usize,
+            (false, -1) => options.selected.saturating_sub(steps),
+            (false, _) => (options.selected + steps).min(n - 1),
+        };
+        if selected != options.selected {
+            options.selected = selected;
+            changed = true;
+        }
     }
 
     if let Some(index) = input.just_released_number().map(|i| i - 1) {
@@ -472,10 +1163,13 @@ pub fn select_dialogue_options(
     if changed {
         let handle = &dialogue_map.0[&option.dialogue].clone();
         let dialogue = dialogue_assets.get(handle).unwrap();
-        commands.entity(e_options).insert(AudioBundle {
-            source: dialogue.blip.clone(),
-            ..Default::default()
-        });
+        commands.entity(e_options).insert((
+            AudioBundle {
+                source: dialogue.blip.clone(),
+                ..Default::default()
+            },
+            TimeScaleImmune,
+        ));
     }
 
     if options.selected != pre_selected {
@@ -487,6 +1181,32 @@ pub fn select_dialogue_options(
     }
 }
 
+/// Hovering over an option selects it. Clicking it confirms.
+pub fn click_dialogue_options(
+    mut options_query: Query<&mut DialogueOptions, With<DialogueSelector>>,
+    button_query: Query<(&Interaction, &DialogueOptionButton), Changed<Interaction>>,
+    mut select_events: EventWriter<SelectedDialogueOptionEvent>,
+    mut confirm_events: EventWriter<DialogueConfirmEvent>,
+) {
+    let Ok(mut options) = options_query.get_single_mut() else {
+        return;
+    };
+
+    for (interaction, DialogueOptionButton(i)) in button_query.iter() {
+        if *interaction == Interaction::None {
+            continue;
+        }
+
+        if let Some(event) = options.select(*i) {
+            select_events.send(event);
+        }
+
+        if *interaction == Interaction::Pressed {
+            confirm_events.send(DialogueConfirmEvent);
+        }
+    }
+}
+
 pub fn highlight_selected_dialogue(
     mut commands: Commands,
     selector_query: Query<&Children, With<DialogueSelector>>,
@@ -527,24 +1247,47 @@ pub fn highlight_selected_dialogue(
 // yucky. this is a yucky system.
 pub fn continue_dialogue(
     mut commands: Commands,
-    input: Res<ButtonInput<KeyCode>>,
+    time: Res<Time>,
+    actions: Res<ButtonInput<InputAction>>,
     dialogue_map: Res<DialogueMap>,
     text_query: Query<(Entity, &DialogueNext), With<DialogueText>>,
     selector_query: Query<Entity, With<DialogueSelector>>,
     portrait_query: Query<Entity, With<DialoguePortrait>>,
     mut motor_query: Query<&mut TextMotor, With<DialogueText>>,
+    mut auto_query: Query<&mut AutoAdvance, (With<DialogueText>, Without<TextMotor>)>,
+    mut confirm_events: EventReader<DialogueConfirmEvent>,
     opts_query: Query<&DialogueOptions, With<DialogueSelector>>,
-    mut events: EventWriter<DialogueEvent>,
-    mut opt_events: EventWriter<SelectedDialogueOptionEvent>,
+    flags: Res<DialogueFlags>,
+    mut history: ResMut<DialogueHistory>,
+    mut active: ResMut<ActiveDialogue>,
+    (mut events, mut opt_events, mut finished_events): (
+        EventWriter<DialogueEvent>,
+        EventWriter<SelectedDialogueOptionEvent>,
+        EventWriter<DialogueFinishedEvent>,
+    ),
 ) {
-    if input.just_released(KeyCode::Enter) {
-        let (e_text, next) = text_query.single();
+    let auto_advanced = auto_query
+        .get_single_mut()
+        .is_ok_and(|mut auto| auto.0.tick(time.delta()).just_finished());
+
+    let confirmed = confirm_events.read().count() > 0;
+
+    if actions.just_released(InputAction::Confirm) || confirmed || auto_advanced {
+        let Ok((e_text, next)) = text_query.get_single() else {
+            // nothing to continue
+            return;
+        };
         let e_select = selector_query.single();
         let e_portrait = portrait_query.single();
         if let Ok(mut motor) = motor_query.get_mut(e_text) {
-            motor.skip = true;
+            if motor.skippable {
+                motor.skip = true;
+            }
         } else if let Ok(opts) = opts_query.get(e_select) {
-            let handle = dialogue_map.0[&opts.options[opts.selected].dialogue].clone();
+            let option = &opts.options[opts.selected];
+            history.respond(option.clone());
+            active.choices.push(opts.selected);
+            let handle = dialogue_map.0[&option.dialogue].clone();
             events.send(DialogueEvent::Say(handle));
             commands
                 .entity(e_select)
@@ -558,19 +1301,654 @@ pub fn continue_dialogue(
                     events.send(DialogueEvent::Say(handle));
                 }
                 DialogueNext::Respond(opts) => {
-                    commands.entity(e_select).insert(opts.clone());
+                    // unmet options are hidden entirely
+                    let opts = DialogueOptions::new(
+                        opts.options
+                            .iter()
+                            .filter(|opt| flags.meets(opt.requires.as_deref()))
+                            .cloned(),
+                    );
+                    let Some(option) = opts.options.first().cloned() else {
+                        warn!("No dialogue options available; finishing");
+                        finish_dialogue(&mut active, &mut events, &mut finished_events);
+                        return;
+                    };
+                    commands.entity(e_select).insert(opts);
                     opt_events.send(SelectedDialogueOptionEvent {
-                        option: opts.options[0].clone(),
+                        option,
                         selected: 0,
                         deselected: None,
                     });
                 }
+                DialogueNext::Branch {
+                    condition,
+                    if_true,
+                    if_false,
+                } => {
+                    let dialogue = match flags.eval(condition) {
+                        true => if_true,
+                        false => if_false,
+                    };
+                    let handle = dialogue_map.0[dialogue].clone();
+                    events.send(DialogueEvent::Say(handle));
+                }
                 DialogueNext::Finish => {
-                    events.send(DialogueEvent::Finish);
+                    finish_dialogue(&mut active, &mut events, &mut finished_events);
                 }
             }
         }
     }
 }
 
-// TODO: unit test this thing. maybe. someday.
+fn finish_dialogue(
+    active: &mut ActiveDialogue,
+    events: &mut EventWriter<DialogueEvent>,
+    finished_events: &mut EventWriter<DialogueFinishedEvent>,
+) {
+    events.send(DialogueEvent::Finish);
+    if let Some(id) = active.id.take() {
+        finished_events.send(DialogueFinishedEvent {
+            id,
+            choices: std::mem::take(&mut active.choices),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn graphemes() {
+        let text = "Zoe\u{0308} said こんにちは。元気？";
+        let graphemes = text.graphemes(true).collect_vec();
+        assert_eq!(graphemes[2], "e\u{0308}");
+        // one blip per visible glyph
+        assert_eq!(
+            graphemes.iter().filter(|g| is_audible(g)).count(),
+            3 + 4 + 5 + 1 + 2 + 1
+        );
+        assert!(!is_audible("\u{200D}"));
+        assert!(!is_audible("\u{0308}"));
+    }
+
+    #[test]
+    fn blips() {
+        let style = BlipStyle {
+            alternatives: vec![Handle::weak_from_u128(1), Handle::weak_from_u128(2)],
+            pitch_jitter: 0.25,
+            every: 2,
+            seed: Some(42),
+            ..Default::default()
+        };
+
+        let mut player = BlipPlayer::new(
+            Handle::weak_from_u128(0),
+            style.clone(),
+            GrinRng::new(0).fork(RNG_BLIP),
+        );
+        let counted = (0..6).map(|_| player.count_grapheme()).collect_vec();
+        assert_eq!(counted, [true, false, true, false, true, false]);
+
+        let mut rng = StdRng::seed_from_u64(42);
+        for i in 0..6 {
+            let (blip, speed) = player.next_blip();
+            assert_eq!(blip, Handle::weak_from_u128(i % 3));
+            assert_eq!(speed, 1.0 + rng.gen_range(-0.25..=0.25));
+        }
+
+        // same seed, same blips
+        let mut a = BlipPlayer::new(
+            Handle::default(),
+            BlipStyle {
+                selection: BlipSelection::Random,
+                ..style.clone()
+            },
+            GrinRng::new(0).fork(RNG_BLIP),
+        );
+        let mut b = BlipPlayer::new(
+            Handle::default(),
+            BlipStyle {
+                selection: BlipSelection::Random,
+                ..style
+            },
+            GrinRng::new(1).fork(RNG_BLIP),
+        );
+        for _ in 0..16 {
+            assert_eq!(a.next_blip(), b.next_blip());
+        }
+    }
+
+    #[test]
+    fn select_repeat() {
+        let mut app = mock_app();
+        app.add_systems(Update, select_dialogue_options);
+
+        let dialogue = app
+            .world
+            .resource_mut::<Assets<Dialogue>>()
+            .add(mock_dialogue("a", DialogueNext::Finish));
+        app.insert_resource(DialogueMap(HashMap::from([("a".into(), dialogue)])));
+
+        let e_select = app
+            .world
+            .spawn((
+                DialogueSelector,
+                SelectRepeat {
+                    delay: 0.5,
+                    interval: 0.25,
+                    ..Default::default()
+                },
+                DialogueOptions::new((0..10).map(|_| DialogueOption {
+                    icon: None,
+                    text: Text::default(),
+                    dialogue: "a".into(),
+                    requires: None,
+                })),
+            ))
+            .id();
+
+        let step = |app: &mut App| {
+            app.world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(125));
+            app.update();
+            app.world.get::<DialogueOptions>(e_select).unwrap().selected
+        };
+
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::ArrowDown);
+        let held = (0..9).map(|_| step(&mut app)).collect_vec();
+        // initial press, then after 0.5s every 0.25s
+        assert_eq!(held, [1, 1, 1, 1, 2, 2, 3, 3, 4]);
+
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .release(KeyCode::ArrowDown);
+        assert_eq!(step(&mut app), 4);
+
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::ArrowUp);
+        assert_eq!(step(&mut app), 3);
+        assert_eq!(step(&mut app), 3);
+
+        // number keys are instant
+        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        input.release(KeyCode::ArrowUp);
+        input.press(KeyCode::Digit8);
+        input.release(KeyCode::Digit8);
+        assert_eq!(step(&mut app), 7);
+    }
+
+    #[test]
+    fn select_wrap() {
+        let mut options = DialogueOptions::new((0..3).map(|_| DialogueOption {
+            icon: None,
+            text: Text::default(),
+            dialogue: "a".into(),
+            requires: None,
+        }));
+        assert!(options.select(0).is_none());
+        assert!(options.select(3).is_none());
+        let event = options.select(2).unwrap();
+        assert_eq!(event.selected, 2);
+        assert_eq!(event.deselected, Some(0));
+
+        let mut app = mock_app();
+        app.add_systems(Update, select_dialogue_options);
+        app.world.resource_mut::<DialogueSelectConfig>().wrap = true;
+
+        let dialogue = app
+            .world
+            .resource_mut::<Assets<Dialogue>>()
+            .add(mock_dialogue("a", DialogueNext::Finish));
+        app.insert_resource(DialogueMap(HashMap::from([("a".into(), dialogue)])));
+
+        let e_select = app
+            .world
+            .spawn((DialogueSelector, SelectRepeat::default(), options))
+            .id();
+
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::ArrowDown);
+        app.update();
+
+        assert_eq!(
+            app.world.get::<DialogueOptions>(e_select).unwrap().selected,
+            0,
+            "Selection did not wrap around."
+        );
+    }
+
+    #[test]
+    fn portrait_animation() {
+        let idle = Handle::weak_from_u128(0);
+        let frames = vec![Handle::weak_from_u128(1), Handle::weak_from_u128(2)];
+        let mut animator = PortraitAnimator::new(
+            idle.clone(),
+            PortraitAnimation {
+                talking_frames: frames.clone(),
+                frame_time: 0.25,
+            },
+        );
+
+        let talking = (0..5)
+            .map(|_| animator.tick(true, 0.125).clone())
+            .collect_vec();
+        let [a, b] = [frames[0].clone(), frames[1].clone()];
+        assert_eq!(talking, [a.clone(), a, b.clone(), b, frames[0].clone()]);
+        assert_eq!(*animator.tick(false, 0.125), idle);
+
+        let emote = Handle::weak_from_u128(3);
+        animator.emote = Some(emote.clone());
+        assert_eq!(*animator.tick(true, 0.125), emote);
+    }
+
+    #[test]
+    fn rebuild_window() {
+        let mut app = mock_app();
+        app.add_systems(Update, rebuild_dialogue_window);
+        app.update();
+
+        app.world.resource_mut::<DialogueWindowConfig>().portrait = PortraitSide::Right;
+        app.update();
+
+        let mut window_query = app
+            .world
+            .query_filtered::<&Children, With<DialogueWindow>>();
+        let children = window_query
+            .single(&app.world)
+            .iter()
+            .copied()
+            .collect_vec();
+        assert_eq!(children.len(), 3);
+        assert!(app.world.get::<DialoguePortrait>(children[2]).is_some());
+        assert!(app.world.get::<DialogueSelector>(children[1]).is_some());
+
+        let mut text_query = app.world.query_filtered::<Entity, With<DialogueText>>();
+        assert_eq!(text_query.iter(&app.world).count(), 1);
+    }
+
+    #[test]
+    fn stop_chars() {
+        let stop_chars = StopChars::default();
+        assert!(stop_chars.is_stop(".", " "));
+        assert!(stop_chars.is_stop("!", "\""));
+        assert!(!stop_chars.is_stop(".", "5"));
+        assert!(stop_chars.is_stop("。", "元"));
+        assert!(stop_chars.is_stop("？", "」"));
+        assert!(!stop_chars.is_stop("a", " "));
+        assert!(!stop_chars.is_stop("", " "));
+    }
+
+    /// Runs the text through a `TextMotor` with halfwidth characters 1px wide.
+    fn wrap_text(text: &str, max_width: f32) -> String {
+        let mut motor = TextMotor {
+            cps: 1.0,
+            stop_delay: 0.0,
+            sections: Box::new(std::iter::empty()),
+            graphemes: Box::new(
+                text.graphemes(true)
+                    .map(String::from)
+                    .collect_vec()
+                    .into_iter(),
+            ),
+            blip: BlipPlayer::new(
+                Handle::default(),
+                BlipStyle::default(),
+                GrinRng::new(0).fork(RNG_BLIP),
+            ),
+            acc: 0.0,
+            latest_grapheme: String::new(),
+            skip: false,
+            skippable: true,
+            word: VecDeque::new(),
+            line_width: 0.0,
+            char_width: 1.0,
+        };
+        let mut out = String::new();
+        while let Some((g, wrap)) = motor.next_grapheme(max_width) {
+            if wrap {
+                out.push('\n');
+            }
+            out.push_str(&g);
+        }
+        out
+    }
+
+    #[test]
+    fn word_wrap() {
+        assert_eq!(wrap_text("aaa bbbb cc", 8.0), "aaa bbbb \ncc");
+        assert_eq!(wrap_text("aaa bbbb cc", f32::INFINITY), "aaa bbbb cc");
+        assert_eq!(wrap_text("aa\nbbbbbb cc", 8.0), "aa\nbbbbbb \ncc");
+        // too long for any line
+        assert_eq!(wrap_text("a abcdefghij", 4.0), "a ab\ncdef\nghij");
+        // CJK breaks between characters
+        assert_eq!(wrap_text("こんにちは", 4.0), "こん\nにち\nは");
+    }
+
+    fn mock_dialogue(text: &str, next: DialogueNext) -> Dialogue {
+        Dialogue {
+            text: Text::from_section(text, TextStyle::default()),
+            cps: 1000.0,
+            next,
+            ..Default::default()
+        }
+    }
+
+    fn press_enter(app: &mut App) {
+        app.world
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Enter);
+        app.update();
+        let mut input = app.world.resource_mut::<ButtonInput<KeyCode>>();
+        input.clear();
+        input.release(KeyCode::Enter);
+        app.update();
+        app.world.resource_mut::<ButtonInput<KeyCode>>().clear();
+        app.update();
+    }
+
+    fn mock_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<Time>()
+            .init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<StyleTagMap>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<ButtonInput<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<Gamepads>()
+            .init_resource::<ButtonInput<InputAction>>()
+            .init_resource::<grin_input::map::InputMap>()
+            .init_resource::<StopChars>()
+            .init_resource::<DialogueLineMeasure>()
+            .init_resource::<DialogueHistory>()
+            .init_resource::<DialogueFlags>()
+            .init_resource::<Assets<Dialogue>>()
+            .add_event::<DialogueEvent>()
+            .add_event::<SelectedDialogueOptionEvent>()
+            .add_event::<DialoguePortraitEvent>()
+            .add_event::<DialogueConfirmEvent>()
+            .add_event::<DialogueStartedEvent>()
+            .add_event::<DialogueFinishedEvent>()
+            .init_resource::<DialogueMap>()
+            .init_resource::<ActiveDialogue>()
+            .init_resource::<DialogueSelectConfig>()
+            .init_resource::<DialogueWindowConfig>()
+            .insert_resource(GrinRng::new(0))
+            .add_systems(Startup, init_dialogue_box)
+            .add_systems(PreUpdate, grin_input::action::update_input_actions)
+            .add_systems(
+                Update,
+                (prepare_dialogue_block, speak_dialogue, continue_dialogue).chain(),
+            );
+        app
+    }
+
+    /// a -> b -> (pick option 0) -> c -> finish. Returns `a`.
+    fn mock_conversation(app: &mut App) -> Handle<Dialogue> {
+        let mut assets = app.world.resource_mut::<Assets<Dialogue>>();
+        let a = assets.add(mock_dialogue("a", DialogueNext::Continue("b".into())));
+        let b = assets.add(mock_dialogue(
+            "b",
+            DialogueNext::Respond(DialogueOptions::new([DialogueOption {
+                icon: None,
+                text: Text::default(),
+                dialogue: "c".into(),
+                requires: None,
+            }])),
+        ));
+        let c = assets.add(mock_dialogue("c", DialogueNext::Finish));
+        app.insert_resource(DialogueMap(HashMap::from([
+            ("a".into(), a.clone()),
+            ("b".into(), b),
+            ("c".into(), c),
+        ])));
+        a
+    }
+
+    fn play_conversation(app: &mut App) {
+        let a = mock_conversation(app);
+        app.world.send_event(DialogueEvent::Say(a));
+        app.update();
+
+        // a: skip, continue
+        press_enter(app);
+        press_enter(app);
+        // b: skip, show options, pick
+        press_enter(app);
+        press_enter(app);
+        press_enter(app);
+        // c: skip, finish
+        press_enter(app);
+        press_enter(app);
+    }
+
+    #[test]
+    fn history() {
+        let mut app = mock_app();
+        play_conversation(&mut app);
+
+        let history = app.world.resource::<DialogueHistory>();
+        let entries = history.entries().collect_vec();
+        assert_eq!(entries.len(), 3);
+        for (entry, value) in entries.iter().zip(["a", "b", "c"]) {
+            assert_eq!(entry.text.sections[0].value, value);
+        }
+        assert!(entries[0].response.is_none());
+        assert_eq!(entries[1].response.as_ref().unwrap().dialogue, "c");
+        assert!(entries[2].response.is_none());
+
+        let mut text = app
+            .world
+            .query_filtered::<&mut Text, With<DialogueText>>()
+            .single_mut(&mut app.world);
+        text.sections = vec![TextSection::from("still here")];
+
+        app.world.send_event(DialogueEvent::Reset);
+        app.update();
+
+        assert!(app.world.resource::<DialogueHistory>().is_empty());
+        let text = app
+            .world
+            .query_filtered::<&Text, With<DialogueText>>()
+            .single(&app.world);
+        assert_eq!(
+            text.sections.len(),
+            1,
+            "Clearing the history cleared the window."
+        );
+    }
+
+    #[derive(Resource, Default)]
+    struct LifecycleEvents {
+        started: Vec<DialogueStartedEvent>,
+        finished: Vec<DialogueFinishedEvent>,
+    }
+
+    fn collect_lifecycle_events(
+        mut collected: ResMut<LifecycleEvents>,
+        mut started: EventReader<DialogueStartedEvent>,
+        mut finished: EventReader<DialogueFinishedEvent>,
+    ) {
+        collected.started.extend(started.read().cloned());
+        collected.finished.extend(finished.read().cloned());
+    }
+
+    #[test]
+    fn lifecycle_events() {
+        let mut app = mock_app();
+        app.init_resource::<LifecycleEvents>()
+            .add_systems(Last, collect_lifecycle_events);
+        play_conversation(&mut app);
+
+        let events = app.world.resource::<LifecycleEvents>();
+        assert_eq!(
+            events.started,
+            [DialogueStartedEvent { id: "a".into() }],
+            "should start exactly once"
+        );
+        assert_eq!(
+            events.finished,
+            [DialogueFinishedEvent {
+                id: "a".into(),
+                choices: vec![0],
+            }],
+            "should finish exactly once"
+        );
+        assert!(app.world.resource::<ActiveDialogue>().id.is_none());
+    }
+
+    #[test]
+    fn unskippable() {
+        let mut app = mock_app();
+
+        let a = app.world.resource_mut::<Assets<Dialogue>>().add(Dialogue {
+            skippable: false,
+            ..mock_dialogue("a", DialogueNext::Finish)
+        });
+        app.insert_resource(DialogueMap::default());
+
+        app.world.send_event(DialogueEvent::Say(a));
+        app.update();
+        press_enter(&mut app);
+
+        let mut motor_query = app.world.query::<&TextMotor>();
+        assert!(
+            motor_query.get_single(&app.world).is_ok(),
+            "Unskippable dialogue was skipped."
+        );
+    }
+
+    #[test]
+    fn auto_advance() {
+        let mut app = mock_app();
+
+        let mut assets = app.world.resource_mut::<Assets<Dialogue>>();
+        let a = assets.add(Dialogue {
+            auto_advance: Some(0.5),
+            ..mock_dialogue("a", DialogueNext::Continue("b".into()))
+        });
+        let b = assets.add(mock_dialogue("b", DialogueNext::Finish));
+        app.insert_resource(DialogueMap(HashMap::from([
+            ("a".into(), a.clone()),
+            ("b".into(), b),
+        ])));
+
+        let step = |app: &mut App, millis: u64| {
+            app.world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(millis));
+            app.update();
+        };
+        let said = |app: &App| app.world.resource::<DialogueHistory>().entries().count();
+
+        app.world.send_event(DialogueEvent::Say(a));
+        step(&mut app, 0);
+
+        // the timer doesn't start until the motor's done
+        for _ in 0..100 {
+            let mut motor_query = app.world.query::<&TextMotor>();
+            if motor_query.get_single(&app.world).is_err() {
+                break;
+            }
+            step(&mut app, 10);
+        }
+
+        step(&mut app, 400);
+        step(&mut app, 0);
+        assert_eq!(said(&app), 1, "Auto advanced too early.");
+
+        step(&mut app, 200);
+        step(&mut app, 0);
+        assert_eq!(said(&app), 2, "Didn't auto advance.");
+    }
+
+    #[test]
+    fn interrupt() {
+        let mut app = mock_app();
+
+        let mut assets = app.world.resource_mut::<Assets<Dialogue>>();
+        let a = assets.add(Dialogue {
+            skippable: false,
+            ..mock_dialogue("a", DialogueNext::Finish)
+        });
+        let b = assets.add(Dialogue {
+            skippable: false,
+            ..mock_dialogue("b", DialogueNext::Finish)
+        });
+        app.insert_resource(DialogueMap::default());
+
+        app.world.send_event(DialogueEvent::Say(a));
+        app.update();
+        app.world.send_event(DialogueEvent::Say(b));
+        app.update();
+
+        // the old sections iterator should be gone
+        let mut motor_query = app.world.query::<&mut TextMotor>();
+        let mut motor = motor_query.single_mut(&mut app.world);
+        assert_eq!(motor.sections.next().unwrap().value, "b");
+        assert!(motor.sections.next().is_none());
+
+        app.world.send_event(DialogueEvent::Interrupt);
+        app.update();
+
+        let mut motor_query = app.world.query::<&TextMotor>();
+        assert!(motor_query.get_single(&app.world).is_err());
+        let mut window_query = app.world.query_filtered::<&Style, With<DialogueWindow>>();
+        assert_eq!(
+            window_query.single(&app.world).display,
+            Display::None,
+            "Dialogue window was not hidden."
+        );
+
+        // shouldn't panic without an active block
+        press_enter(&mut app);
+    }
+
+    fn branch_path(killed_dummy: bool) -> Vec<String> {
+        let mut app = mock_app();
+
+        let mut assets = app.world.resource_mut::<Assets<Dialogue>>();
+        let a = assets.add(mock_dialogue(
+            "a",
+            DialogueNext::Branch {
+                condition: "killed_dummy".into(),
+                if_true: "b".into(),
+                if_false: "c".into(),
+            },
+        ));
+        let b = assets.add(mock_dialogue("b", DialogueNext::Finish));
+        let c = assets.add(mock_dialogue("c", DialogueNext::Finish));
+        app.insert_resource(DialogueMap(HashMap::from([
+            ("a".into(), a.clone()),
+            ("b".into(), b),
+            ("c".into(), c),
+        ])));
+        app.world
+            .resource_mut::<DialogueFlags>()
+            .set("killed_dummy", killed_dummy);
+
+        app.world.send_event(DialogueEvent::Say(a));
+        app.update();
+        press_enter(&mut app);
+        press_enter(&mut app);
+
+        app.world
+            .resource::<DialogueHistory>()
+            .entries()
+            .map(|entry| entry.text.sections[0].value.clone())
+            .collect_vec()
+    }
+
+    #[test]
+    fn branch() {
+        assert_eq!(branch_path(true), ["a", "b"]);
+        assert_eq!(branch_path(false), ["a", "c"]);
+    }
+}
@@ -0,0 +1,247 @@
+//! Talking heads.
+
+use bevy::{prelude::*, utils::HashMap};
+use grin_rig::humanoid::Humanoid;
+
+use crate::{
+    asset_gen::{DialogueAssetLoadState, DialogueAssets},
+    DialoguePortrait, DialogueText, Portrait, TextMotor,
+};
+
+/// Fills in the `PortraitAnimations` once the dialogue assets are in.
+///
+/// Needs `DialogueAssetLoadState`.
+pub struct PortraitPlugin;
+
+impl Plugin for PortraitPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PortraitAnimations>().add_systems(
+            OnEnter(DialogueAssetLoadState::Success),
+            init_portrait_animations,
+        );
+    }
+}
+
+/// Seconds per talking frame, for the portraits that ship with the game.
+pub const TALKING_FRAME_TIME: f32 = 0.15;
+
+/// How a `Portrait` animates while its dialogue is being spoken.
+#[derive(Clone, Default)]
+pub struct PortraitAnimation {
+    /// Cycled through while the `TextMotor` is running.
+    pub talking_frames: Vec<Handle<Image>>,
+    /// Seconds per talking frame.
+    pub frame_time: f32,
+}
+
+/// Portraits without an entry here just show the idle image.
+#[derive(Resource, Default)]
+pub struct PortraitAnimations(pub HashMap<Portrait, PortraitAnimation>);
+
+/// Swaps the `UiImage` of the `DialoguePortrait` depending on whether dialogue is being spoken.
+#[derive(Component)]
+pub struct PortraitAnimator {
+    /// Shown when not talking.
+    pub idle: Handle<Image>,
+    pub animation: PortraitAnimation,
+    /// Overrides everything else while set.
+    pub emote: Option<Handle<Image>>,
+    /// Time spent talking.
+    acc: f32,
+}
+
+impl PortraitAnimator {
+    pub fn new(idle: Handle<Image>, animation: PortraitAnimation) -> Self {
+        Self {
+            idle,
+            animation,
+            emote: None,
+            acc: 0.0,
+        }
+    }
+
+    /// Returns the image to show this frame.
+    pub fn tick(&mut self, talking: bool, delta: f32) -> &Handle<Image> {
+        if let Some(emote) = &self.emote {
+            return emote;
+        }
+
+        let frames = &self.animation.talking_frames;
+        if !talking || frames.is_empty() || self.animation.frame_time <= 0.0 {
+            self.acc = 0.0;
+            return &self.idle;
+        }
+
+        let frame = (self.acc / self.animation.frame_time) as usize % frames.len();
+        self.acc += delta;
+        &frames[frame]
+    }
+}
+
+pub fn init_portrait_animations(
+    assets: Res<DialogueAssets>,
+    mut animations: ResMut<PortraitAnimations>,
+) {
+    animations.0.insert(
+        Portrait::Smirk,
+        PortraitAnimation {
+            talking_frames: vec![assets.smirk_talk_0.clone(), assets.smirk_talk_1.clone()],
+            frame_time: TALKING_FRAME_TIME,
+        },
+    );
+}
+
+pub fn animate_dialogue_portrait(
+    time: Res<Time>,
+    text_query: Query<Has<TextMotor>, With<DialogueText>>,
+    mut portrait_query: Query<(&mut PortraitAnimator, &mut UiImage), With<DialoguePortrait>>,
+) {
+    let talking = text_query.get_single().unwrap_or(false);
+    for (mut animator, mut image) in portrait_query.iter_mut() {
+        let texture = animator.tick(talking, time.delta_seconds());
+        if image.texture != *texture {
+            image.texture = texture.clone();
+        }
+    }
+}
+
+/// On the `DialoguePortrait` while it's showing a `Portrait::Live`.
+///
+/// The `GoPro` sticks around while the portrait stays on the same entity, and gets paused with
+/// the rest of the window when the dialogue finishes. It goes away once `image` is dropped.
+#[derive(Component)]
+pub struct LivePortrait {
+    pub entity: Entity,
+    pub image: Handle<Image>,
+}
+
+/// Switches to the placeholder when whoever's in a `LivePortrait` despawns mid-conversation.
+pub fn fall_back_from_despawned_portraits(
+    mut commands: Commands,
+    assets: Res<DialogueAssets>,
+    mut portrait_query: Query<(Entity, &LivePortrait, &mut PortraitAnimator)>,
+    humanoid_query: Query<&Humanoid>,
+    entity_query: Query<()>,
+) {
+    for (e_portrait, live, mut animator) in portrait_query.iter_mut() {
+        let alive = humanoid_query
+            .get(live.entity)
+            .is_ok_and(|humanoid| entity_query.contains(humanoid.head));
+        if !alive {
+            *animator = PortraitAnimator::new(
+                assets.portrait_placeholder.clone(),
+                PortraitAnimation::default(),
+            );
+            commands.entity(e_portrait).remove::<LivePortrait>();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grin_rig::humanoid::HumanoidDominantHand;
+
+    use super::*;
+
+    #[test]
+    fn despawned_live_portrait() {
+        let mut app = App::new();
+        let placeholder = Handle::weak_from_u128(0);
+        let live = Handle::weak_from_u128(1);
+        app.insert_resource(DialogueAssets {
+            smirk_blip: Handle::default(),
+            smirk_icon: Handle::default(),
+            portrait_placeholder: placeholder.clone(),
+            smirk_talk_0: Handle::default(),
+            smirk_talk_1: Handle::default(),
+        })
+        .add_systems(Update, fall_back_from_despawned_portraits);
+
+        let e_head = app.world.spawn_empty().id();
+        let e_speaker = app
+            .world
+            .spawn(Humanoid {
+                body: e_head,
+                head: e_head,
+                lhand: e_head,
+                rhand: e_head,
+                armature: e_head,
+                dominant_hand_type: HumanoidDominantHand::Right,
+            })
+            .id();
+        let e_portrait = app
+            .world
+            .spawn((
+                LivePortrait {
+                    entity: e_speaker,
+                    image: live.clone(),
+                },
+                PortraitAnimator::new(live.clone(), PortraitAnimation::default()),
+            ))
+            .id();
+
+        app.update();
+        assert_eq!(
+            app.world.get::<PortraitAnimator>(e_portrait).unwrap().idle,
+            live,
+            "Fell back while the speaker was still around."
+        );
+
+        app.world.despawn(e_speaker);
+        app.update();
+        assert_eq!(
+            app.world.get::<PortraitAnimator>(e_portrait).unwrap().idle,
+            placeholder,
+            "Didn't fall back after the speaker despawned."
+        );
+        assert!(
+            app.world.get::<LivePortrait>(e_portrait).is_none(),
+            "Kept `LivePortrait` after the speaker despawned."
+        );
+    }
+
+    #[test]
+    fn animations_from_assets() {
+        let mut app = App::new();
+        let frames = [2, 3].map(Handle::weak_from_u128);
+        app.init_state::<DialogueAssetLoadState>()
+            .add_plugins(PortraitPlugin)
+            .insert_resource(DialogueAssets {
+                smirk_blip: Handle::default(),
+                smirk_icon: Handle::default(),
+                portrait_placeholder: Handle::default(),
+                smirk_talk_0: frames[0].clone(),
+                smirk_talk_1: frames[1].clone(),
+            });
+
+        app.update();
+        assert!(
+            app.world.resource::<PortraitAnimations>().0.is_empty(),
+            "Made animations before the dialogue assets loaded."
+        );
+
+        app.world
+            .resource_mut::<NextState<DialogueAssetLoadState>>()
+            .set(DialogueAssetLoadState::Success);
+        app.update();
+
+        let animation = app
+            .world
+            .resource::<PortraitAnimations>()
+            .0
+            .get(&Portrait::Smirk)
+            .cloned()
+            .expect("Didn't add an animation for `Portrait::Smirk`.");
+        let mut animator = PortraitAnimator::new(Handle::weak_from_u128(1), animation);
+        assert_eq!(
+            animator.tick(true, TALKING_FRAME_TIME),
+            &frames[0],
+            "Didn't start on the first talking frame."
+        );
+        assert_eq!(
+            animator.tick(true, TALKING_FRAME_TIME),
+            &frames[1],
+            "Didn't move on to the second talking frame."
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Device-agnostic buttons.
+//!
+//! Keyboard/mouse and gamepad are merged into a single `ButtonInput<InputAction>`,
+//! so systems don't have to care which one the player is holding. What's bound to what is in
+//! the `InputMap`.
+
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::map::{InputMap, RawInput};
+
+/// How far the stick needs to be pushed to count as a button press.
+pub const STICK_THRESHOLD: f32 = 0.5;
+
+pub struct InputActionPlugin;
+
+impl Plugin for InputActionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ButtonInput<InputAction>>()
+            .init_resource::<InputMap>()
+            .add_systems(PreUpdate, update_input_actions.after(InputSystem));
+    }
+}
+
+/// Default bindings are in `InputMap::default`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum InputAction {
+    /// W, up arrow, D-pad up, left stick up.
+    Up,
+    /// S, down arrow, D-pad down, left stick down.
+    Down,
+    /// Enter, south button.
+    Confirm,
+    /// LMB, right trigger.
+    Primary,
+    /// RMB, right bumper.
+    Secondary,
+    /// R, west button.
+    Reload,
+    /// E, north button.
+    Interact,
+    /// G, D-pad down. Drops the item in the dominant hand.
+    Drop,
+    /// Escape, start button.
+    Pause,
+    /// Q, left bumper.
+    Ultimate,
+    /// W, left stick up.
+    MoveForward,
+    /// S, left stick down.
+    MoveBack,
+    /// A, left stick left.
+    MoveLeft,
+    /// D, left stick right.
+    MoveRight,
+    /// Space, south button. Also swims up.
+    Jump,
+    /// Left control, east button. Swims down.
+    Crouch,
+    /// Left shift, left trigger.
+    Dash,
+    /// V, right stick press. Switches `CameraAlignment`s.
+    ToggleCamera,
+}
+
+impl InputAction {
+    pub const ALL: [Self; 18] = [
+        Self::Up,
+        Self::Down,
+        Self::Confirm,
+        Self::Primary,
+        Self::Secondary,
+        Self::Reload,
+        Self::Interact,
+        Self::Drop,
+        Self::Pause,
+        Self::Ultimate,
+        Self::MoveForward,
+        Self::MoveBack,
+        Self::MoveLeft,
+        Self::MoveRight,
+        Self::Jump,
+        Self::Crouch,
+        Self::Dash,
+        Self::ToggleCamera,
+    ];
+
+    /// Used for menus and dialogue, rather than moving around.
+    pub fn is_menu(&self) -> bool {
+        matches!(self, Self::Up | Self::Down | Self::Confirm)
+    }
+
+    /// Whether both can be in use at once, so they shouldn't share a binding.
+    pub fn overlaps(&self, other: Self) -> bool {
+        *self == Self::Pause || other == Self::Pause || self.is_menu() == other.is_menu()
+    }
+}
+
+pub fn update_input_actions(
+    raw_input: RawInput,
+    input_map: Res<InputMap>,
+    mut actions: ResMut<ButtonInput<InputAction>>,
+) {
+    actions.clear();
+    for action in InputAction::ALL {
+        let pressed = input_map
+            .bindings(action)
+            .iter()
+            .any(|binding| raw_input.pressed(*binding));
+
+        if pressed {
+            actions.press(action);
+        } else {
+            actions.release(action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<ButtonInput<MouseButton>>()
+            .init_resource::<ButtonInput<GamepadButton>>()
+            .init_resource::<Axis<GamepadAxis>>()
+            .init_resource::<Gamepads>()
+            .init_resource::<ButtonInput<InputAction>>()
+            .init_resource::<InputMap>()
+            .add_systems(Update, update_input_actions);
+        app
+    }
+
+    #[test]
+    fn merge_devices() {
+        let mut app = mock_app();
+        let trigger = GamepadButton::new(Gamepad::new(0), GamepadButtonType::RightTrigger2);
+
+        app.world
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .press(MouseButton::Left);
+        app.update();
+
+        let actions = app.world.resource::<ButtonInput<InputAction>>();
+        assert!(actions.just_pressed(InputAction::Primary));
+
+        // holding both shouldn't retrigger
+        app.world
+            .resource_mut::<ButtonInput<GamepadButton>>()
+            .press(trigger);
+        app.update();
+
+        let actions = app.world.resource::<ButtonInput<InputAction>>();
+        assert!(actions.pressed(InputAction::Primary));
+        assert!(!actions.just_pressed(InputAction::Primary));
+
+        // still held on the gamepad
+        app.world
+            .resource_mut::<ButtonInput<MouseButton>>()
+            .release(MouseButton::Left);
+        app.update();
+
+        let actions = app.world.resource::<ButtonInput<InputAction>>();
+        assert!(actions.pressed(InputAction::Primary));
+
+        app.world
+            .resource_mut::<ButtonInput<GamepadButton>>()
+            .release(trigger);
+        app.update();
+
+        let actions = app.world.resource::<ButtonInput<InputAction>>();
+        assert!(actions.just_released(InputAction::Primary));
+    }
+}
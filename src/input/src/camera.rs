@@ -1,10 +1,38 @@
-use std::{marker::PhantomData, ops::Range};
+use std::{
+    marker::PhantomData,
+    ops::{Range, RangeInclusive},
+    time::Duration,
+};
 
 use bevy::{
-    ecs::event::ManualEventReader, input::mouse::MouseMotion, prelude::*, window::CursorGrabMode,
+    ecs::event::ManualEventReader, input::mouse::MouseMotion, prelude::*, utils::HashMap,
+    window::CursorGrabMode,
 };
 use bevy_rapier3d::{na::clamp, prelude::*};
+use bevy_tweening::{component_animator_system, Animator, EaseFunction, Lens, Tween};
 use grin_physics::{CollisionGroupExt, CollisionGroupsExt};
+use grin_render::{
+    feedback::ScreenShake, sketched::SketchMaterial, TweenAppExt, TweenCompletedEvent,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::action::InputAction;
+
+/// `CameraCollision` only eases back out once there's this much more room than it's using,
+/// so it doesn't jitter when it's right up against something.
+pub const CAMERA_COLLISION_HYSTERESIS: f32 = 0.25;
+
+/// Seconds it takes to switch between `CameraAlignment`s.
+pub const CAMERA_TRANSITION_SECS: f32 = 0.4;
+
+/// Pixels across.
+pub const CROSSHAIR_SIZE: f32 = 4.0;
+
+/// Seconds that a map piece needs to be out of the way before `CameraOcclusion` puts it back.
+pub const OCCLUSION_CLEAR_SECS: f32 = 0.25;
+
+/// `CameraOcclusion` checks up to this far above the target's feet.
+pub const OCCLUSION_TARGET_HEIGHT: f32 = 1.0;
 
 pub struct PlayerCameraPlugin<T: Component> {
     phantom_data: PhantomData<T>,
@@ -22,9 +50,29 @@ impl<T: Component> Plugin for PlayerCameraPlugin<T> {
     fn build(&self, app: &mut App) {
         app.init_resource::<LookInfo>()
             .init_resource::<MouseOpts>()
+            .init_resource::<CameraSettings>()
+            .register_type::<CameraSettings>()
+            .init_resource::<CameraRecoil>()
+            .init_resource::<FadedMaterials>()
+            .add_event::<SetCameraAlignmentEvent>()
+            .add_event::<CameraAlignmentChangedEvent>()
+            .add_tween_completion_event::<AlignmentTransitionCompletedEvent>()
+            .add_systems(Startup, spawn_crosshair)
             .add_systems(
                 Update,
-                (handle_mouse, cam_update, spawn_camera::<T>).chain(),
+                (
+                    handle_mouse,
+                    set_camera_alignment,
+                    component_animator_system::<AlignmentTransition>,
+                    cam_update,
+                    collide_camera,
+                    fade_occluders.run_if(resource_exists::<Assets<SketchMaterial>>),
+                    spawn_camera::<T>,
+                    apply_camera_fov,
+                    sync_cursor_to_alignment,
+                    finish_alignment_transitions,
+                )
+                    .chain(),
             );
     }
 }
@@ -36,6 +84,55 @@ pub struct PlayerCamera {
     pub alignment: CameraAlignment,
 }
 
+/// Keeps a `CameraAlignment::Shooter` camera out of the map. Pulls in right away when
+/// something's in the way, and eases back out when it's clear.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CameraCollision {
+    /// Radius of the ball that's cast from the target to the camera.
+    pub radius: f32,
+    /// Space left between the camera and whatever it hit.
+    pub margin: f32,
+    /// How quickly it eases back out. Higher is faster.
+    pub ease_out: f32,
+    /// Distance from the target right now. `None` if it hasn't been worked out yet.
+    pub distance: Option<f32>,
+}
+
+impl Default for CameraCollision {
+    fn default() -> Self {
+        Self {
+            radius: 0.3,
+            margin: 0.2,
+            ease_out: 4.0,
+            distance: None,
+        }
+    }
+}
+
+/// Fades out map pieces between a `CameraAlignment::FortyFive` camera and its target.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct CameraOcclusion {
+    pub alpha: f32,
+}
+
+impl Default for CameraOcclusion {
+    fn default() -> Self {
+        Self { alpha: 0.25 }
+    }
+}
+
+/// Map piece that `CameraOcclusion` faded out.
+#[derive(Component, Debug)]
+pub struct Occluding {
+    pub original: Handle<SketchMaterial>,
+    /// Seconds that it's been out of the way.
+    pub clear: f32,
+}
+
+/// Faded versions of map materials, so each one is only copied once.
+#[derive(Resource, Default)]
+pub struct FadedMaterials(pub HashMap<AssetId<SketchMaterial>, Handle<SketchMaterial>>);
+
 #[derive(Copy, Clone, Debug, Default)]
 pub enum CameraAlignment {
     /// Angled downwards.
@@ -47,6 +144,71 @@ pub enum CameraAlignment {
     },
 }
 
+impl CameraAlignment {
+    /// Over the shoulder, what the player starts with.
+    pub const SHOOTER: Self = Self::Shooter {
+        offset: Vec3::new(0.0, 4.0, 0.0),
+        angle_scale: 12.0,
+    };
+
+    /// `FortyFive` aims with the cursor, so it can't be locked.
+    pub fn cursor_grab_mode(&self) -> CursorGrabMode {
+        match self {
+            Self::FortyFive => CursorGrabMode::Confined,
+            Self::Shooter { .. } => CursorGrabMode::Locked,
+        }
+    }
+
+    /// `Shooter` has the crosshair instead.
+    pub fn cursor_visible(&self) -> bool {
+        matches!(self, Self::FortyFive)
+    }
+}
+
+/// Switches the `PlayerCamera` to another `CameraAlignment`.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct SetCameraAlignmentEvent(pub CameraAlignment);
+
+/// Sent once the `PlayerCamera` is done switching alignments.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct CameraAlignmentChangedEvent {
+    pub camera: Entity,
+    pub alignment: CameraAlignment,
+}
+
+/// Eases the `PlayerCamera` from where it was over to its new `CameraAlignment`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AlignmentTransition {
+    pub from: Transform,
+    /// `0.0` is `from`, `1.0` is the new alignment.
+    pub blend: f32,
+}
+
+pub struct AlignmentBlendLens;
+
+impl Lens<AlignmentTransition> for AlignmentBlendLens {
+    fn lerp(&mut self, target: &mut AlignmentTransition, ratio: f32) {
+        target.blend = ratio;
+    }
+}
+
+#[derive(Event)]
+pub struct AlignmentTransitionCompletedEvent(pub Entity);
+
+impl From<Entity> for AlignmentTransitionCompletedEvent {
+    fn from(value: Entity) -> Self {
+        Self(value)
+    }
+}
+
+impl TweenCompletedEvent for AlignmentTransitionCompletedEvent {
+    const EVENT_ID: u64 = 80413527;
+}
+
+/// Shown in the middle of the screen for `CameraAlignment::Shooter`.
+#[derive(Component)]
+pub struct CameraCrosshair;
+
 #[derive(Resource, Default)]
 pub struct LookInfo {
     pub reader_motion: ManualEventReader<MouseMotion>,
@@ -95,13 +257,14 @@ impl LookInfo {
     }
 }
 
-/// Mouse settings.
+/// Extra `(yaw, pitch)` in radians added on top of `LookInfo`, for weapon kick. Doesn't touch
+/// where the mouse is actually looking, so it goes away when this goes back to zero.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct CameraRecoil(pub Vec2);
+
+/// Mouse settings that aren't up to the player. The rest are in `CameraSettings`.
 #[derive(Resource)]
 pub struct MouseOpts {
-    /// Mouse X sensitivity in degrees/px.
-    pub sens_x: f32,
-    /// Mouse Y sensitivity in degrees/px.
-    pub sens_y: f32,
     /// Constraints for pitch angle.
     pub pitch_bounds: Option<Range<f32>>,
     /// Maximum mouse target distance.
@@ -111,19 +274,84 @@ pub struct MouseOpts {
 impl Default for MouseOpts {
     fn default() -> Self {
         Self {
-            // I *think* this is what CS:GO uses?
-            sens_x: 0.022,
-            sens_y: 0.022,
             pitch_bounds: Some(-20.0_f32.to_radians()..70.0_f32.to_radians()),
             target_distance_cap: 128.0,
         }
     }
 }
 
+/// Camera settings that are up to the player. Saved with the rest of the `Settings`.
+///
+/// Anything out of range is clamped when it's used, so it can be edited freely.
+#[derive(Resource, Reflect, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+pub struct CameraSettings {
+    /// Degrees/px.
+    pub sensitivity_x: f32,
+    /// Degrees/px.
+    pub sensitivity_y: f32,
+    pub invert_y: bool,
+    /// Vertical.
+    pub fov_degrees: f32,
+    /// Added to the `CameraAlignment::Shooter` offset, e.g. to look over a shoulder.
+    pub shooter_shoulder_offset: Vec3,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            // I *think* this is what CS:GO uses?
+            sensitivity_x: 0.022,
+            sensitivity_y: 0.022,
+            invert_y: false,
+            fov_degrees: 45.0,
+            shooter_shoulder_offset: Vec3::ZERO,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub const SENSITIVITY_RANGE: RangeInclusive<f32> = 0.001..=1.0;
+    pub const FOV_RANGE: RangeInclusive<f32> = 30.0..=120.0;
+    /// Any further and the camera ends up somewhere else entirely.
+    pub const MAX_SHOULDER_OFFSET: f32 = 4.0;
+
+    /// Settings that can't break anything. Anything that isn't even a number goes to default.
+    pub fn clamped(&self) -> Self {
+        let default = Self::default();
+        let clamp = |value: f32, range: RangeInclusive<f32>, default: f32| match value.is_finite() {
+            true => value.clamp(*range.start(), *range.end()),
+            false => default,
+        };
+        Self {
+            sensitivity_x: clamp(
+                self.sensitivity_x,
+                Self::SENSITIVITY_RANGE,
+                default.sensitivity_x,
+            ),
+            sensitivity_y: clamp(
+                self.sensitivity_y,
+                Self::SENSITIVITY_RANGE,
+                default.sensitivity_y,
+            ),
+            invert_y: self.invert_y,
+            fov_degrees: clamp(self.fov_degrees, Self::FOV_RANGE, default.fov_degrees),
+            shooter_shoulder_offset: match self.shooter_shoulder_offset.is_finite() {
+                true => self
+                    .shooter_shoulder_offset
+                    .clamp_length_max(Self::MAX_SHOULDER_OFFSET),
+                false => default.shooter_shoulder_offset,
+            },
+        }
+    }
+}
+
 /// Writes to the `LookInfo` resource based on mouse input.
 pub fn handle_mouse(
     mut mouse_info: ResMut<LookInfo>,
     mouse_opts: Res<MouseOpts>,
+    settings: Res<CameraSettings>,
     motion: Res<Events<MouseMotion>>,
     camera_query: Query<(&Camera, &GlobalTransform), With<PlayerCamera>>,
     window_query: Query<&Window>,
@@ -135,10 +363,15 @@ pub fn handle_mouse(
 
     let window = window_query.single();
 
+    let settings = settings.clamped();
+    let invert = match settings.invert_y {
+        true => -1.0,
+        false => 1.0,
+    };
     let look_info = mouse_info.as_mut();
     for event in look_info.reader_motion.read(&motion) {
-        look_info.yaw -= (event.delta.x * mouse_opts.sens_x).to_radians();
-        look_info.pitch -= (event.delta.y * mouse_opts.sens_y).to_radians();
+        look_info.yaw -= (event.delta.x * settings.sensitivity_x).to_radians();
+        look_info.pitch -= (event.delta.y * settings.sensitivity_y * invert).to_radians();
     }
     if let Some(pitch_bounds) = &mouse_opts.pitch_bounds {
         look_info.pitch = clamp(look_info.pitch, pitch_bounds.start, pitch_bounds.end);
@@ -175,56 +408,358 @@ pub fn spawn_camera<T: Component>(mut commands: Commands, query: Query<Entity, A
     commands.spawn((
         PlayerCamera {
             target: e_plr,
-            alignment: CameraAlignment::Shooter {
-                offset: Vec3::new(0.0, 4.0, 0.0),
-                angle_scale: 12.0,
-            },
+            alignment: CameraAlignment::SHOOTER,
         },
         Camera3dBundle {
             transform: Transform::from_xyz(0.0, 32.0, 0.0).looking_to(Vec3::NEG_Z, Vec3::Y),
             ..Default::default()
         },
+        CameraCollision::default(),
+        CameraOcclusion::default(),
+        ScreenShake::default(),
     ));
 }
 
+pub fn spawn_crosshair(mut commands: Commands) {
+    commands
+        .spawn((
+            CameraCrosshair,
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(CROSSHAIR_SIZE),
+                    height: Val::Px(CROSSHAIR_SIZE),
+                    ..Default::default()
+                },
+                background_color: Color::WHITE.into(),
+                ..Default::default()
+            });
+        });
+}
+
 pub fn cam_update(
-    mut query: Query<(&mut Transform, &PlayerCamera)>,
+    mut query: Query<(&mut Transform, &PlayerCamera, Option<&AlignmentTransition>)>,
     transform_query: Query<&GlobalTransform, Without<PlayerCamera>>,
     look_info: Res<LookInfo>,
+    recoil: Res<CameraRecoil>,
+    settings: Res<CameraSettings>,
     mut window_query: Query<&mut Window>,
 ) {
-    let Ok((mut transform, PlayerCamera { target, alignment })) = query.get_single_mut() else {
+    let Ok((mut transform, PlayerCamera { target, alignment }, transition)) =
+        query.get_single_mut()
+    else {
         return;
     };
 
-    let g_target_transform = transform_query.get(*target).unwrap();
+    // the target might be getting respawned
+    let Ok(g_target_transform) = transform_query.get(*target) else {
+        return;
+    };
 
-    match alignment {
+    let rig = match alignment {
         CameraAlignment::FortyFive => {
             let target = g_target_transform.translation();
             let offset = Vec3::new(0.0, 24.0, 24.0);
             let origin = target + offset;
             let look = (-offset).normalize();
-            *transform =
-                Transform::from_translation(origin).looking_to(look, look.cross(Vec3::NEG_X));
+            Transform::from_translation(origin).looking_to(look, look.cross(Vec3::NEG_X))
         }
         CameraAlignment::Shooter {
             offset,
             angle_scale,
         } => {
-            let Ok(mut window) = window_query.get_single_mut() else {
-                return;
-            };
+            if let Ok(mut window) = window_query.get_single_mut() {
+                let pos = Vec2::new(window.width() / 2.0, window.height() / 2.0);
+                window.set_cursor_position(Some(pos));
+            }
 
-            transform.rotation =
-                Quat::from_euler(EulerRot::YXZ, look_info.yaw, look_info.pitch, 0.0);
-            transform.translation = g_target_transform.transform_point(
+            let yaw = look_info.yaw + recoil.0.x;
+            let pitch = look_info.pitch + recoil.0.y;
+            Transform::from_translation(g_target_transform.transform_point(
                 *offset
-                    + Vec3::new(0.0, -look_info.pitch.sin(), look_info.pitch.cos()) * *angle_scale,
-            );
-            let pos = Vec2::new(window.width() / 2.0, window.height() / 2.0);
-            window.set_cursor_position(Some(pos));
-            window.cursor.grab_mode = CursorGrabMode::Locked;
+                    + settings.clamped().shooter_shoulder_offset
+                    + Vec3::new(0.0, -pitch.sin(), pitch.cos()) * *angle_scale,
+            ))
+            .with_rotation(Quat::from_euler(EulerRot::YXZ, yaw, pitch, 0.0))
+        }
+    };
+
+    *transform = match transition {
+        Some(transition) => Transform {
+            translation: transition
+                .from
+                .translation
+                .lerp(rig.translation, transition.blend),
+            rotation: transition
+                .from
+                .rotation
+                .slerp(rig.rotation, transition.blend),
+            scale: rig.scale,
+        },
+        None => rig,
+    };
+}
+
+/// Switches the `PlayerCamera` over to another `CameraAlignment`, easing between the two.
+///
+/// Ignored while it's still in the middle of switching.
+pub fn set_camera_alignment(
+    mut commands: Commands,
+    mut camera_query: Query<(
+        Entity,
+        &mut PlayerCamera,
+        &Transform,
+        Has<AlignmentTransition>,
+    )>,
+    mut alignment_events: EventReader<SetCameraAlignmentEvent>,
+) {
+    let Ok((e_camera, mut camera, transform, transitioning)) = camera_query.get_single_mut() else {
+        alignment_events.clear();
+        return;
+    };
+    // only the last one counts if there's a few
+    let Some(SetCameraAlignmentEvent(alignment)) = alignment_events.read().last() else {
+        return;
+    };
+    if transitioning {
+        return;
+    }
+
+    camera.alignment = *alignment;
+    commands.entity(e_camera).insert((
+        AlignmentTransition {
+            from: *transform,
+            blend: 0.0,
+        },
+        Animator::new(
+            Tween::new(
+                EaseFunction::QuadraticInOut,
+                Duration::from_secs_f32(CAMERA_TRANSITION_SECS),
+                AlignmentBlendLens,
+            )
+            .with_completed_event(AlignmentTransitionCompletedEvent::EVENT_ID),
+        ),
+    ));
+}
+
+pub fn finish_alignment_transitions(
+    mut commands: Commands,
+    camera_query: Query<&PlayerCamera>,
+    mut completed_events: EventReader<AlignmentTransitionCompletedEvent>,
+    mut changed_events: EventWriter<CameraAlignmentChangedEvent>,
+) {
+    for AlignmentTransitionCompletedEvent(e_camera) in completed_events.read() {
+        let Ok(camera) = camera_query.get(*e_camera) else {
+            continue;
+        };
+        commands
+            .entity(*e_camera)
+            .remove::<(AlignmentTransition, Animator<AlignmentTransition>)>();
+        changed_events.send(CameraAlignmentChangedEvent {
+            camera: *e_camera,
+            alignment: camera.alignment,
+        });
+    }
+}
+
+/// Toggles between `CameraAlignment::FortyFive` and `CameraAlignment::SHOOTER`.
+pub fn input_toggle_camera_alignment(
+    camera_query: Query<&PlayerCamera>,
+    actions: Res<ButtonInput<InputAction>>,
+    mut alignment_events: EventWriter<SetCameraAlignmentEvent>,
+) {
+    if !actions.just_pressed(InputAction::ToggleCamera) {
+        return;
+    }
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    alignment_events.send(SetCameraAlignmentEvent(match camera.alignment {
+        CameraAlignment::FortyFive => CameraAlignment::SHOOTER,
+        CameraAlignment::Shooter { .. } => CameraAlignment::FortyFive,
+    }));
+}
+
+/// Grabs the cursor and shows the crosshair to match the `CameraAlignment`.
+pub fn sync_cursor_to_alignment(
+    camera_query: Query<&PlayerCamera, Changed<PlayerCamera>>,
+    mut window_query: Query<&mut Window>,
+    mut crosshair_query: Query<&mut Style, With<CameraCrosshair>>,
+) {
+    let Ok(camera) = camera_query.get_single() else {
+        return;
+    };
+    for mut window in window_query.iter_mut() {
+        window.cursor.grab_mode = camera.alignment.cursor_grab_mode();
+        window.cursor.visible = camera.alignment.cursor_visible();
+    }
+    let display = match camera.alignment {
+        CameraAlignment::FortyFive => Display::None,
+        CameraAlignment::Shooter { .. } => Display::Flex,
+    };
+    for mut style in crosshair_query.iter_mut() {
+        style.display = display;
+    }
+}
+
+/// Keeps the `PlayerCamera`'s FOV in line with `CameraSettings`.
+pub fn apply_camera_fov(
+    settings: Res<CameraSettings>,
+    mut camera_query: Query<&mut Projection, With<PlayerCamera>>,
+) {
+    let fov = settings.clamped().fov_degrees.to_radians();
+    for mut projection in camera_query.iter_mut() {
+        if !settings.is_changed() && !projection.is_added() {
+            continue;
+        }
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov;
+        }
+    }
+}
+
+/// Moves the camera in front of whatever's between it and its target. Runs after `cam_update`.
+pub fn collide_camera(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut camera_query: Query<(&mut Transform, &PlayerCamera, &mut CameraCollision)>,
+    transform_query: Query<&GlobalTransform, Without<PlayerCamera>>,
+) {
+    let Ok((mut transform, camera, mut collision)) = camera_query.get_single_mut() else {
+        return;
+    };
+    let CameraAlignment::Shooter { offset, .. } = camera.alignment else {
+        collision.distance = None;
+        return;
+    };
+    let Ok(g_target_transform) = transform_query.get(camera.target) else {
+        return;
+    };
+
+    let pivot = g_target_transform.transform_point(offset);
+    let to_camera = transform.translation - pivot;
+    let desired = to_camera.length();
+    let Some(direction) = to_camera.try_normalize() else {
+        return;
+    };
+
+    let allowed = rapier_context
+        .cast_shape(
+            pivot,
+            Quat::IDENTITY,
+            direction,
+            &Collider::ball(collision.radius),
+            ShapeCastOptions::with_max_time_of_impact(desired),
+            QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+        )
+        .map_or(desired, |(_, hit)| {
+            (hit.time_of_impact - collision.margin).max(0.0)
+        });
+
+    let current = collision.distance.unwrap_or(desired).min(desired);
+    let distance = if allowed < current {
+        allowed
+    } else if allowed >= desired || allowed > current + CAMERA_COLLISION_HYSTERESIS {
+        let t = 1.0 - (-collision.ease_out * time.delta_seconds()).exp();
+        current + (allowed - current) * t
+    } else {
+        current
+    };
+
+    collision.distance = Some(distance);
+    transform.translation = pivot + direction * distance;
+}
+
+/// Swaps map pieces between the camera and its target for faded copies, and back once they've
+/// been out of the way for `OCCLUSION_CLEAR_SECS`.
+pub fn fade_occluders(
+    mut commands: Commands,
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut materials: ResMut<Assets<SketchMaterial>>,
+    mut faded_materials: ResMut<FadedMaterials>,
+    camera_query: Query<(&GlobalTransform, &PlayerCamera, &CameraOcclusion)>,
+    transform_query: Query<&GlobalTransform, Without<PlayerCamera>>,
+    mut material_query: Query<(Entity, &mut Handle<SketchMaterial>, Option<&mut Occluding>)>,
+) {
+    let mut occluders = Vec::new();
+    if let Ok((g_camera_transform, camera, occlusion)) = camera_query.get_single() {
+        if let (CameraAlignment::FortyFive, Ok(g_target_transform)) =
+            (camera.alignment, transform_query.get(camera.target))
+        {
+            let origin = g_camera_transform.translation();
+            let to_target =
+                g_target_transform.translation() + Vec3::Y * OCCLUSION_TARGET_HEIGHT - origin;
+            if let Some(direction) = to_target.try_normalize() {
+                rapier_context.intersections_with_ray(
+                    origin,
+                    direction,
+                    to_target.length(),
+                    true,
+                    QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+                    |e_hit, _| {
+                        occluders.push(e_hit);
+                        true
+                    },
+                );
+            }
+
+            for &e_occluder in occluders.iter() {
+                let Ok((_, mut material, occluding)) = material_query.get_mut(e_occluder) else {
+                    continue;
+                };
+                if let Some(mut occluding) = occluding {
+                    occluding.clear = 0.0;
+                    continue;
+                }
+
+                let faded = match faded_materials.0.get(&material.id()) {
+                    Some(faded) => faded.clone(),
+                    None => {
+                        let Some(mut faded) = materials.get(material.id()).cloned() else {
+                            continue;
+                        };
+                        let color = faded.base.base_color;
+                        faded.base.base_color = color.with_a(color.a() * occlusion.alpha);
+                        faded.base.alpha_mode = AlphaMode::Blend;
+                        let faded = materials.add(faded);
+                        faded_materials.0.insert(material.id(), faded.clone());
+                        faded
+                    }
+                };
+                commands.entity(e_occluder).insert(Occluding {
+                    original: std::mem::replace(&mut *material, faded),
+                    clear: 0.0,
+                });
+            }
+        }
+    }
+
+    for (e_mesh, mut material, occluding) in material_query.iter_mut() {
+        let Some(mut occluding) = occluding else {
+            continue;
+        };
+        if occluders.contains(&e_mesh) {
+            continue;
+        }
+
+        occluding.clear += time.delta_seconds();
+        if occluding.clear >= OCCLUSION_CLEAR_SECS {
+            *material = occluding.original.clone();
+            commands.entity(e_mesh).remove::<Occluding>();
         }
     }
 }
@@ -268,3 +803,222 @@ impl Plugin for DebugMouseTargetPlugin {
             .add_systems(Update, update_debug_mouse_marker);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        render::mesh::MeshPlugin,
+        scene::ScenePlugin,
+        time::{TimePlugin, TimeUpdateStrategy},
+    };
+    use bevy_tweening::TweenCompleted;
+
+    use super::*;
+
+    #[test]
+    fn camera_settings() {
+        let mut app = App::new();
+        app.init_resource::<CameraSettings>()
+            .add_systems(Update, apply_camera_fov);
+
+        let e_camera = app
+            .world
+            .spawn((
+                PlayerCamera {
+                    target: Entity::PLACEHOLDER,
+                    alignment: CameraAlignment::default(),
+                },
+                Projection::default(),
+            ))
+            .id();
+        let fov = |app: &mut App| match app.world.get::<Projection>(e_camera).unwrap() {
+            Projection::Perspective(perspective) => perspective.fov.to_degrees(),
+            _ => panic!("Camera isn't perspective."),
+        };
+
+        app.world.resource_mut::<CameraSettings>().fov_degrees = 90.0;
+        app.update();
+        assert!((fov(&mut app) - 90.0).abs() < 1e-3, "FOV wasn't applied.");
+
+        app.world.resource_mut::<CameraSettings>().fov_degrees = 1000.0;
+        app.update();
+        assert!(
+            (fov(&mut app) - *CameraSettings::FOV_RANGE.end()).abs() < 1e-3,
+            "FOV wasn't clamped."
+        );
+
+        let settings = CameraSettings {
+            sensitivity_x: f32::NAN,
+            sensitivity_y: -5.0,
+            shooter_shoulder_offset: Vec3::X * 100.0,
+            ..Default::default()
+        }
+        .clamped();
+        assert_eq!(
+            settings.sensitivity_x,
+            CameraSettings::default().sensitivity_x,
+            "NaN sensitivity wasn't reset."
+        );
+        assert!(settings.sensitivity_y > 0.0, "Sensitivity wasn't clamped.");
+        assert!(
+            settings.shooter_shoulder_offset.length() <= CameraSettings::MAX_SHOULDER_OFFSET,
+            "Shoulder offset wasn't clamped."
+        );
+    }
+
+    #[test]
+    fn camera_alignment_transition() {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .add_event::<TweenCompleted>()
+            .add_event::<SetCameraAlignmentEvent>()
+            .add_event::<CameraAlignmentChangedEvent>()
+            .add_tween_completion_event::<AlignmentTransitionCompletedEvent>()
+            .add_systems(
+                Update,
+                (
+                    set_camera_alignment,
+                    component_animator_system::<AlignmentTransition>,
+                    finish_alignment_transitions,
+                )
+                    .chain(),
+            );
+
+        let e_camera = app
+            .world
+            .spawn((
+                PlayerCamera {
+                    target: Entity::PLACEHOLDER,
+                    alignment: CameraAlignment::SHOOTER,
+                },
+                Transform::default(),
+            ))
+            .id();
+        let alignment = |app: &App| app.world.get::<PlayerCamera>(e_camera).unwrap().alignment;
+
+        app.world
+            .send_event(SetCameraAlignmentEvent(CameraAlignment::FortyFive));
+        app.update();
+        assert!(
+            matches!(alignment(&app), CameraAlignment::FortyFive),
+            "Alignment wasn't switched."
+        );
+        assert!(
+            app.world.get::<AlignmentTransition>(e_camera).is_some(),
+            "Transition didn't start."
+        );
+
+        app.world
+            .send_event(SetCameraAlignmentEvent(CameraAlignment::SHOOTER));
+        app.update();
+        assert!(
+            matches!(alignment(&app), CameraAlignment::FortyFive),
+            "Switched again in the middle of the transition."
+        );
+
+        let mut changed = Vec::new();
+        for _ in 0..10 {
+            app.update();
+            changed.extend(
+                app.world
+                    .resource_mut::<Events<CameraAlignmentChangedEvent>>()
+                    .drain(),
+            );
+        }
+        assert!(
+            app.world.get::<AlignmentTransition>(e_camera).is_none(),
+            "Transition didn't finish."
+        );
+        assert_eq!(changed.len(), 1, "Alignment change wasn't sent once.");
+        assert!(
+            matches!(changed[0].alignment, CameraAlignment::FortyFive),
+            "Sent the wrong alignment."
+        );
+    }
+
+    #[test]
+    fn camera_collision() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .add_systems(Update, collide_camera);
+
+        let e_target = app.world.spawn(TransformBundle::default()).id();
+        let e_camera = app
+            .world
+            .spawn((
+                PlayerCamera {
+                    target: e_target,
+                    alignment: CameraAlignment::Shooter {
+                        offset: Vec3::ZERO,
+                        angle_scale: 8.0,
+                    },
+                },
+                CameraCollision::default(),
+                TransformBundle::default(),
+            ))
+            .id();
+        // front is at z=3.5, so the camera should end up at 3.5 - radius - margin
+        let e_wall = app
+            .world
+            .spawn((
+                Collider::cuboid(2.0, 2.0, 0.5),
+                CollisionGroups::new(Group::MAP, Group::all()),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, 4.0)),
+            ))
+            .id();
+
+        // stand in for `cam_update`, which puts the camera where it wants to be every frame
+        let update = |app: &mut App| {
+            app.world
+                .get_mut::<Transform>(e_camera)
+                .unwrap()
+                .translation = Vec3::Z * 8.0;
+            app.update();
+            app.world.get::<Transform>(e_camera).unwrap().translation.z
+        };
+
+        // colliders get added to the physics world here
+        update(&mut app);
+        assert!(
+            (update(&mut app) - 3.0).abs() < 1e-3,
+            "Camera didn't pull in."
+        );
+
+        app.world
+            .get_mut::<Transform>(e_wall)
+            .unwrap()
+            .translation
+            .z = 4.1;
+        update(&mut app);
+        assert!(
+            (update(&mut app) - 3.0).abs() < 1e-3,
+            "Camera moved within the hysteresis."
+        );
+
+        app.world.despawn(e_wall);
+        update(&mut app);
+        let distance = update(&mut app);
+        assert!(
+            distance > 3.0 && distance < 7.0,
+            "Camera didn't ease back out. ({})",
+            distance
+        );
+
+        for _ in 0..50 {
+            update(&mut app);
+        }
+        assert!(
+            (update(&mut app) - 8.0).abs() < 1e-2,
+            "Camera didn't go all the way back out."
+        );
+    }
+}
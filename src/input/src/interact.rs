@@ -0,0 +1,312 @@
+//! "Press E to ..." for things in the world.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{action::InputAction, camera::PlayerCamera};
+
+/// Nothing further than this can be interacted with, whatever its `Interactable::radius` says.
+pub const INTERACT_SEARCH_RADIUS: f32 = 4.0;
+
+/// How much facing something counts for vs. being close to it.
+pub const INTERACT_FACING_WEIGHT: f32 = 0.5;
+
+/// The prompt floats this far above whatever it's for.
+pub const INTERACT_PROMPT_HEIGHT: f32 = 1.5;
+
+pub struct InteractPlugin;
+
+impl Plugin for InteractPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InteractionCandidate>()
+            .init_resource::<InteractionsBlocked>()
+            .add_event::<InteractEvent>()
+            .add_systems(Startup, spawn_interaction_prompt)
+            .add_systems(
+                Update,
+                (
+                    find_interaction_candidate,
+                    input_interact,
+                    update_interaction_prompt,
+                )
+                    .chain()
+                    .in_set(InteractSet),
+            );
+    }
+}
+
+#[derive(SystemSet, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InteractSet;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum InteractionKind {
+    /// Says the dialogue with this ID.
+    Dialogue(String),
+    /// Picks up a dropped item.
+    Pickup,
+}
+
+/// Something that can be interacted with.
+///
+/// Found through its colliders, or the colliders of any of its descendants, so it needs at
+/// least one of those.
+#[derive(Component, Clone, Debug)]
+pub struct Interactable {
+    /// Shows up as "[E] `prompt`".
+    pub prompt: String,
+    pub radius: f32,
+    pub kind: InteractionKind,
+}
+
+/// Something that can interact with `Interactable`s, i.e. the player.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Interactor;
+
+/// Sent when `interactor` interacts with `target`.
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InteractEvent {
+    pub target: Entity,
+    pub interactor: Entity,
+}
+
+/// The `Interactable` that would be interacted with right now.
+#[derive(Resource, Debug, Default)]
+pub struct InteractionCandidate {
+    pub target: Option<Entity>,
+    pub interactor: Option<Entity>,
+}
+
+/// Hides the prompt and ignores input, like while there's dialogue on screen.
+#[derive(Resource, Debug, Default)]
+pub struct InteractionsBlocked(pub bool);
+
+#[derive(Component)]
+pub struct InteractionPrompt;
+
+pub fn spawn_interaction_prompt(mut commands: Commands) {
+    commands.spawn((
+        InteractionPrompt,
+        TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                ..Default::default()
+            },
+            ..TextBundle::from_section(
+                "",
+                TextStyle {
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                    ..Default::default()
+                },
+            )
+        },
+    ));
+}
+
+/// Finds the best `Interactable` for the `Interactor`, by distance and by whether it's
+/// facing it.
+pub fn find_interaction_candidate(
+    rapier_context: Res<RapierContext>,
+    interactor_query: Query<(Entity, &GlobalTransform), With<Interactor>>,
+    interactable_query: Query<(&Interactable, &GlobalTransform)>,
+    parent_query: Query<&Parent>,
+    mut candidate: ResMut<InteractionCandidate>,
+) {
+    let Ok((e_interactor, g_transform)) = interactor_query.get_single() else {
+        *candidate = InteractionCandidate::default();
+        return;
+    };
+
+    let origin = g_transform.translation();
+    let forward = *g_transform.forward();
+
+    let interactable_of = |e: Entity| {
+        std::iter::once(e)
+            .chain(parent_query.iter_ancestors(e))
+            .find(|e| interactable_query.contains(*e))
+    };
+
+    let mut best = None::<(Entity, f32)>;
+    rapier_context.intersections_with_shape(
+        origin,
+        Quat::IDENTITY,
+        &Collider::ball(INTERACT_SEARCH_RADIUS),
+        QueryFilter::new().exclude_collider(e_interactor),
+        |e_hit| {
+            let Some(e_target) = interactable_of(e_hit) else {
+                return true;
+            };
+            if e_target == e_interactor {
+                return true;
+            }
+
+            let (interactable, g_target_transform) = interactable_query.get(e_target).unwrap();
+            let offset = g_target_transform.translation() - origin;
+            let distance = offset.length();
+            if distance > interactable.radius {
+                return true;
+            }
+
+            let facing = forward.dot(offset.normalize_or_zero());
+            let score = distance / interactable.radius - facing * INTERACT_FACING_WEIGHT;
+            if best.map_or(true, |(_, s)| score < s) {
+                best = Some((e_target, score));
+            }
+            true
+        },
+    );
+
+    let target = best.map(|(e, _)| e);
+    if candidate.target != target || candidate.interactor != Some(e_interactor) {
+        *candidate = InteractionCandidate {
+            target,
+            interactor: Some(e_interactor),
+        };
+    }
+}
+
+pub fn input_interact(
+    actions: Res<ButtonInput<InputAction>>,
+    candidate: Res<InteractionCandidate>,
+    blocked: Res<InteractionsBlocked>,
+    mut interact_events: EventWriter<InteractEvent>,
+) {
+    if blocked.0 || !actions.just_pressed(InputAction::Interact) {
+        return;
+    }
+
+    if let InteractionCandidate {
+        target: Some(target),
+        interactor: Some(interactor),
+    } = *candidate
+    {
+        interact_events.send(InteractEvent { target, interactor });
+    }
+}
+
+/// Shows the prompt over the `InteractionCandidate`.
+pub fn update_interaction_prompt(
+    candidate: Res<InteractionCandidate>,
+    blocked: Res<InteractionsBlocked>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<PlayerCamera>>,
+    interactable_query: Query<(&Interactable, &GlobalTransform)>,
+    mut prompt_query: Query<(&mut Style, &mut Text), With<InteractionPrompt>>,
+) {
+    let Ok((mut style, mut text)) = prompt_query.get_single_mut() else {
+        return;
+    };
+
+    let position = (!blocked.0)
+        .then_some(candidate.target)
+        .flatten()
+        .and_then(|e| interactable_query.get(e).ok())
+        .and_then(|(interactable, g_transform)| {
+            let (camera, g_camera_transform) = camera_query.get_single().ok()?;
+            let anchor = g_transform.translation() + Vec3::Y * INTERACT_PROMPT_HEIGHT;
+            camera
+                .world_to_viewport(g_camera_transform, anchor)
+                .map(|position| (interactable, position))
+        });
+
+    let Some((interactable, position)) = position else {
+        if style.display != Display::None {
+            style.display = Display::None;
+        }
+        return;
+    };
+
+    style.display = Display::Flex;
+    style.left = Val::Px(position.x);
+    style.top = Val::Px(position.y);
+    let prompt = format!("[E] {}", interactable.prompt);
+    if text.sections[0].value != prompt {
+        text.sections[0].value = prompt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::MeshPlugin, scene::ScenePlugin, time::TimePlugin};
+
+    use super::*;
+
+    fn interactable(prompt: &str) -> Interactable {
+        Interactable {
+            prompt: prompt.into(),
+            radius: 2.0,
+            kind: InteractionKind::Pickup,
+        }
+    }
+
+    #[test]
+    fn interaction_candidate() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .init_resource::<ButtonInput<InputAction>>()
+            .add_plugins(InteractPlugin);
+
+        // facing -Z
+        let e_interactor = app
+            .world
+            .spawn((Interactor, Collider::ball(0.5), TransformBundle::default()))
+            .id();
+        app.world.spawn((
+            interactable("behind"),
+            Collider::ball(0.2),
+            TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, 1.0)),
+        ));
+        // collider is on a child
+        let e_ahead = app
+            .world
+            .spawn((
+                interactable("ahead"),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, -1.2)),
+            ))
+            .with_children(|parent| {
+                parent.spawn((Collider::ball(0.2), TransformBundle::default()));
+            })
+            .id();
+        app.world.spawn((
+            interactable("too far"),
+            Collider::ball(0.2),
+            TransformBundle::from_transform(Transform::from_xyz(0.0, 0.0, -3.0)),
+        ));
+
+        // colliders get added to the physics world here
+        app.update();
+        app.update();
+        assert_eq!(
+            app.world.resource::<InteractionCandidate>().target,
+            Some(e_ahead),
+            "Didn't pick the interactable in front."
+        );
+
+        app.world
+            .resource_mut::<ButtonInput<InputAction>>()
+            .press(InputAction::Interact);
+        app.update();
+        let events = app
+            .world
+            .resource_mut::<Events<InteractEvent>>()
+            .drain()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            events,
+            vec![InteractEvent {
+                target: e_ahead,
+                interactor: e_interactor,
+            }],
+            "Didn't interact."
+        );
+
+        // still `just_pressed`, since nothing's clearing it
+        app.world.resource_mut::<InteractionsBlocked>().0 = true;
+        app.update();
+        assert!(
+            app.world.resource::<Events<InteractEvent>>().is_empty(),
+            "Interacted while blocked."
+        );
+    }
+}
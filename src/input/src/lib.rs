@@ -1,4 +1,8 @@
+pub mod action;
 pub mod camera;
+pub mod interact;
+pub mod map;
+pub mod settings;
 // this would have been in `grin_character` but it causes dep issues
 // and unnecessary recompiles.
 // honestly don't know if I'll add anything else to this crate though :P
@@ -0,0 +1,301 @@
+//! Rebindable controls. `update_input_actions` goes through the `InputMap` to decide what's pressed.
+//!
+//! It's saved with the rest of the `Settings`.
+
+use std::collections::BTreeMap;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use grin_util::keys::GamepadInputExt;
+use serde::{Deserialize, Serialize};
+
+use crate::action::{InputAction, STICK_THRESHOLD};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputBinding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButtonType),
+    /// A stick pushed past `STICK_THRESHOLD`, in the positive or negative direction.
+    Stick {
+        axis: GamepadAxisType,
+        positive: bool,
+    },
+}
+
+/// Which bindings `InputMap::rebind` replaces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputDevice {
+    KeyboardMouse,
+    Gamepad,
+}
+
+impl InputBinding {
+    pub fn device(&self) -> InputDevice {
+        match self {
+            Self::Key(_) | Self::Mouse(_) => InputDevice::KeyboardMouse,
+            Self::Gamepad(_) | Self::Stick { .. } => InputDevice::Gamepad,
+        }
+    }
+}
+
+/// Two actions that are active at the same time with the same binding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InputConflict {
+    pub binding: InputBinding,
+    pub actions: (InputAction, InputAction),
+}
+
+/// Bindings for each `InputAction`. Any of them presses it.
+#[derive(Resource, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct InputMap(pub BTreeMap<InputAction, Vec<InputBinding>>);
+
+impl Default for InputMap {
+    fn default() -> Self {
+        use InputAction::*;
+        use InputBinding::*;
+
+        let stick = |axis, positive| Stick { axis, positive };
+        Self(BTreeMap::from([
+            (
+                Up,
+                vec![
+                    Key(KeyCode::KeyW),
+                    Key(KeyCode::ArrowUp),
+                    Gamepad(GamepadButtonType::DPadUp),
+                    stick(GamepadAxisType::LeftStickY, true),
+                ],
+            ),
+            (
+                Down,
+                vec![
+                    Key(KeyCode::KeyS),
+                    Key(KeyCode::ArrowDown),
+                    Gamepad(GamepadButtonType::DPadDown),
+                    stick(GamepadAxisType::LeftStickY, false),
+                ],
+            ),
+            (
+                Confirm,
+                vec![Key(KeyCode::Enter), Gamepad(GamepadButtonType::South)],
+            ),
+            (
+                Pause,
+                vec![Key(KeyCode::Escape), Gamepad(GamepadButtonType::Start)],
+            ),
+            (
+                Primary,
+                vec![
+                    Mouse(MouseButton::Left),
+                    Gamepad(GamepadButtonType::RightTrigger2),
+                ],
+            ),
+            (
+                Secondary,
+                vec![
+                    Mouse(MouseButton::Right),
+                    Gamepad(GamepadButtonType::RightTrigger),
+                ],
+            ),
+            (
+                Reload,
+                vec![Key(KeyCode::KeyR), Gamepad(GamepadButtonType::West)],
+            ),
+            (
+                Interact,
+                vec![Key(KeyCode::KeyE), Gamepad(GamepadButtonType::North)],
+            ),
+            (
+                Drop,
+                vec![Key(KeyCode::KeyG), Gamepad(GamepadButtonType::DPadDown)],
+            ),
+            (
+                Ultimate,
+                vec![Key(KeyCode::KeyQ), Gamepad(GamepadButtonType::LeftTrigger)],
+            ),
+            (
+                MoveForward,
+                vec![Key(KeyCode::KeyW), stick(GamepadAxisType::LeftStickY, true)],
+            ),
+            (
+                MoveBack,
+                vec![
+                    Key(KeyCode::KeyS),
+                    stick(GamepadAxisType::LeftStickY, false),
+                ],
+            ),
+            (
+                MoveLeft,
+                vec![
+                    Key(KeyCode::KeyA),
+                    stick(GamepadAxisType::LeftStickX, false),
+                ],
+            ),
+            (
+                MoveRight,
+                vec![Key(KeyCode::KeyD), stick(GamepadAxisType::LeftStickX, true)],
+            ),
+            (
+                Jump,
+                vec![Key(KeyCode::Space), Gamepad(GamepadButtonType::South)],
+            ),
+            (
+                Crouch,
+                vec![Key(KeyCode::ControlLeft), Gamepad(GamepadButtonType::East)],
+            ),
+            (
+                Dash,
+                vec![
+                    Key(KeyCode::ShiftLeft),
+                    Gamepad(GamepadButtonType::LeftTrigger2),
+                ],
+            ),
+            (
+                ToggleCamera,
+                vec![Key(KeyCode::KeyV), Gamepad(GamepadButtonType::RightThumb)],
+            ),
+        ]))
+    }
+}
+
+impl InputMap {
+    pub fn bindings(&self, action: InputAction) -> &[InputBinding] {
+        self.0.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Replaces `action`'s bindings on the same device as `binding`, so rebinding a key leaves
+    /// the gamepad alone.
+    ///
+    /// Returns anything else that's now bound to the same thing. They're left as is.
+    pub fn rebind(&mut self, action: InputAction, binding: InputBinding) -> Vec<InputAction> {
+        let bindings = self.0.entry(action).or_default();
+        bindings.retain(|b| b.device() != binding.device());
+        bindings.push(binding);
+
+        let conflicts = self
+            .0
+            .iter()
+            .filter(|(other, bindings)| {
+                **other != action && other.overlaps(action) && bindings.contains(&binding)
+            })
+            .map(|(other, _)| *other)
+            .collect::<Vec<_>>();
+        for other in conflicts.iter() {
+            warn!(
+                "{:?} is bound to both {:?} and {:?}.",
+                binding, action, other
+            );
+        }
+        conflicts
+    }
+
+    /// Bindings shared by actions that can be used at the same time.
+    pub fn conflicts(&self) -> Vec<InputConflict> {
+        let mut conflicts = Vec::new();
+        for (i, (a, a_bindings)) in self.0.iter().enumerate() {
+            for (b, b_bindings) in self.0.iter().skip(i + 1) {
+                if !a.overlaps(*b) {
+                    continue;
+                }
+                conflicts.extend(
+                    a_bindings
+                        .iter()
+                        .filter(|binding| b_bindings.contains(binding))
+                        .map(|binding| InputConflict {
+                            binding: *binding,
+                            actions: (*a, *b),
+                        }),
+                );
+            }
+        }
+        conflicts
+    }
+
+    /// Fills in actions that are missing, e.g. ones added after the file was saved.
+    pub fn or_defaults(mut self) -> Self {
+        for (action, bindings) in Self::default().0 {
+            self.0.entry(action).or_insert(bindings);
+        }
+        self
+    }
+}
+
+/// Everything an `InputBinding` can be bound to.
+#[derive(SystemParam)]
+pub struct RawInput<'w> {
+    pub keys: Res<'w, ButtonInput<KeyCode>>,
+    pub mouse_buttons: Res<'w, ButtonInput<MouseButton>>,
+    pub gamepad_buttons: Res<'w, ButtonInput<GamepadButton>>,
+    pub gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+    pub gamepads: Res<'w, Gamepads>,
+}
+
+impl RawInput<'_> {
+    /// On any gamepad.
+    pub fn pressed(&self, binding: InputBinding) -> bool {
+        match binding {
+            InputBinding::Key(key) => self.keys.pressed(key),
+            InputBinding::Mouse(button) => self.mouse_buttons.pressed(button),
+            InputBinding::Gamepad(button) => self.gamepad_buttons.any_gamepad_pressed(button),
+            InputBinding::Stick { axis, positive } => self
+                .gamepads
+                .iter()
+                .filter_map(|gamepad| self.gamepad_axes.get(GamepadAxis::new(gamepad, axis)))
+                .any(|value| match positive {
+                    true => value > STICK_THRESHOLD,
+                    false => value < -STICK_THRESHOLD,
+                }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings() {
+        let input_map = InputMap::default();
+        assert!(
+            input_map.conflicts().is_empty(),
+            "Default bindings conflict: {:?}",
+            input_map.conflicts()
+        );
+        for action in InputAction::ALL {
+            assert!(
+                !input_map.bindings(action).is_empty(),
+                "{:?} isn't bound.",
+                action
+            );
+        }
+    }
+
+    #[test]
+    fn rebind() {
+        let mut input_map = InputMap::default();
+        let space = InputBinding::Key(KeyCode::Space);
+
+        assert_eq!(
+            input_map.rebind(InputAction::Dash, space),
+            vec![InputAction::Jump],
+            "Conflict wasn't found."
+        );
+        assert_eq!(
+            input_map.bindings(InputAction::Dash),
+            &[
+                InputBinding::Gamepad(GamepadButtonType::LeftTrigger2),
+                space
+            ],
+            "Rebinding the keyboard replaced the wrong bindings."
+        );
+        assert_eq!(input_map.conflicts().len(), 1, "Conflict wasn't found.");
+
+        // menus and gameplay can share
+        assert!(
+            input_map
+                .rebind(InputAction::Confirm, InputBinding::Key(KeyCode::KeyE))
+                .is_empty(),
+            "Menu binding conflicts with gameplay."
+        );
+    }
+}
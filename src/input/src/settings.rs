@@ -0,0 +1,147 @@
+//! Player settings, saved as RON in the user config directory whenever they change, and loaded
+//! back on startup.
+
+use std::{fs, path::PathBuf};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::CameraSettings,
+    map::{InputConflict, InputMap},
+};
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InputMap>()
+            .init_resource::<CameraSettings>()
+            .init_resource::<SettingsFile>()
+            .add_systems(Startup, load_settings)
+            .add_systems(Last, save_settings);
+    }
+}
+
+/// Everything that goes in the file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub input: InputMap,
+    pub camera: CameraSettings,
+}
+
+/// Where the `Settings` are saved. `None` keeps them off the disk.
+#[derive(Resource, Clone, Debug)]
+pub struct SettingsFile(pub Option<PathBuf>);
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        Self(dirs::config_dir().map(|dir| dir.join("grin").join("settings.ron")))
+    }
+}
+
+/// Keeps the defaults if there's no file yet, or it's broken.
+pub fn load_settings(
+    file: Res<SettingsFile>,
+    mut input_map: ResMut<InputMap>,
+    mut camera_settings: ResMut<CameraSettings>,
+) {
+    if let Some(path) = file.0.as_ref().filter(|path| path.exists()) {
+        match fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| ron::from_str::<Settings>(&text).map_err(|e| e.to_string()))
+        {
+            Ok(settings) => {
+                *input_map = settings.input.or_defaults();
+                *camera_settings = settings.camera;
+            }
+            Err(e) => warn!("Couldn't load settings from {}: {}", path.display(), e),
+        }
+    }
+
+    for InputConflict { binding, actions } in input_map.conflicts() {
+        warn!(
+            "{:?} is bound to both {:?} and {:?}.",
+            binding, actions.0, actions.1
+        );
+    }
+}
+
+pub fn save_settings(
+    file: Res<SettingsFile>,
+    input_map: Res<InputMap>,
+    camera_settings: Res<CameraSettings>,
+) {
+    // the first change is just them being inserted, or loaded
+    if input_map.is_added() || !(input_map.is_changed() || camera_settings.is_changed()) {
+        return;
+    }
+    let Some(path) = file.0.as_ref() else {
+        return;
+    };
+
+    let settings = Settings {
+        input: input_map.clone(),
+        camera: camera_settings.clone(),
+    };
+    let result = ron::ser::to_string_pretty(&settings, ron::ser::PrettyConfig::default())
+        .map_err(|e| e.to_string())
+        .and_then(|text| {
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+            }
+            fs::write(path, text).map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        warn!("Couldn't save settings to {}: {}", path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{action::InputAction, map::InputBinding};
+
+    use super::*;
+
+    #[test]
+    fn persist_settings() {
+        let path = std::env::temp_dir().join(format!("grin_settings_{}.ron", std::process::id()));
+        let _ = fs::remove_file(&path);
+
+        let app = |path: &PathBuf| {
+            let mut app = App::new();
+            app.add_plugins(SettingsPlugin)
+                .insert_resource(SettingsFile(Some(path.clone())));
+            app
+        };
+
+        let mut first = app(&path);
+        first.update();
+        first.update();
+        assert!(!path.exists(), "Saved without changing anything.");
+
+        first
+            .world
+            .resource_mut::<InputMap>()
+            .rebind(InputAction::Reload, InputBinding::Key(KeyCode::KeyT));
+        first.update();
+        assert!(path.exists(), "Didn't save after rebinding.");
+
+        first.world.resource_mut::<CameraSettings>().invert_y = true;
+        first.update();
+
+        let mut second = app(&path);
+        second.update();
+        let _ = fs::remove_file(&path);
+        assert_eq!(
+            second.world.resource::<InputMap>(),
+            first.world.resource::<InputMap>(),
+            "Didn't load the saved bindings."
+        );
+        assert!(
+            second.world.resource::<CameraSettings>().invert_y,
+            "Didn't load the saved camera settings."
+        );
+    }
+}
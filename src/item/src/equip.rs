@@ -1,8 +1,12 @@
 use std::marker::PhantomData;
 
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{gltf::GltfNode, prelude::*, utils::HashMap};
 use grin_damage::hitbox::GltfHitboxAutoGenTarget;
-use grin_rig::humanoid::{Humanoid, HumanoidDominantHand};
+use grin_render::sketched::SketchUiImage;
+use grin_rig::{
+    humanoid::{Humanoid, HumanoidDominantHand},
+    socket::{AttachmentSockets, SOCKET_GRIP_MAIN, SOCKET_GRIP_OFF, SOCKET_MUZZLE},
+};
 use grin_util::event::UntypedEvent;
 
 use crate::{library::plugin::ItemIdentifier, plugin::ItemSet};
@@ -78,6 +82,50 @@ pub struct Equipped {
     pub right: Entity,
 }
 
+impl Equipped {
+    /// Both hands empty.
+    pub fn empty(commands: &mut Commands) -> Self {
+        Self {
+            left: commands.spawn(EmptyHand).id(),
+            right: commands.spawn(EmptyHand).id(),
+        }
+    }
+
+    /// The item in the other hand, if `e_item` is only in one of them.
+    pub fn other_hand(&self, e_item: Entity) -> Option<Entity> {
+        match (self.left == e_item, self.right == e_item) {
+            (true, false) => Some(self.right),
+            (false, true) => Some(self.left),
+            _ => None,
+        }
+    }
+
+    /// `(dominant, off)` hand items.
+    pub fn by_dominance(&self, dominant: HumanoidDominantHand) -> (Entity, Entity) {
+        match dominant {
+            HumanoidDominantHand::Left => (self.left, self.right),
+            HumanoidDominantHand::Right => (self.right, self.left),
+        }
+    }
+}
+
+/// Shows up in the HUD while equipped.
+#[derive(Component, Clone, Debug)]
+pub struct ItemIcon(pub Handle<SketchUiImage>);
+
+/// Placeholder item for a hand that isn't holding anything. Despawned when something's equipped
+/// over it.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct EmptyHand;
+
+/// The item is in the dominant hand. Double-handed items count too.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct MainHand;
+
+/// The item is in the off hand, i.e. dual wielding.
+#[derive(Component, Clone, Copy, Debug, Default)]
+pub struct OffHand;
+
 #[derive(Component, Clone, Debug)]
 pub struct EquippedTo {
     pub target: Entity,
@@ -91,6 +139,29 @@ pub enum SlotAlignment {
     Double,
 }
 
+impl SlotAlignment {
+    pub fn dominant(dominant: HumanoidDominantHand) -> Self {
+        match dominant {
+            HumanoidDominantHand::Left => Self::Left,
+            HumanoidDominantHand::Right => Self::Right,
+        }
+    }
+
+    pub fn off(dominant: HumanoidDominantHand) -> Self {
+        match dominant {
+            HumanoidDominantHand::Left => Self::Right,
+            HumanoidDominantHand::Right => Self::Left,
+        }
+    }
+
+    pub fn is_off_hand(&self, dominant: HumanoidDominantHand) -> bool {
+        matches!(
+            (self, dominant),
+            (Self::Left, HumanoidDominantHand::Right) | (Self::Right, HumanoidDominantHand::Left)
+        )
+    }
+}
+
 /// Location for item to be parented on a rig. If it's a single-handed item,
 /// the model loader defaults to `Hand` and `Offhand` is ignored.
 #[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
@@ -140,6 +211,79 @@ macro_rules! models {
     }
 }
 
+/// What held items are parented to. `Socket.GripMain` if the rig has one, otherwise the
+/// dominant hand.
+pub fn grip(humanoid: &Humanoid, sockets: Option<&AttachmentSockets>) -> Entity {
+    sockets
+        .and_then(|s| s.get(SOCKET_GRIP_MAIN))
+        .unwrap_or(humanoid.dominant_hand())
+}
+
+/// Parent and transform for a newly spawned item. Sits right on `Socket.GripMain`, or at
+/// `fallback` on the dominant hand if the rig doesn't have one.
+pub fn grip_attachment(
+    humanoid: &Humanoid,
+    sockets: Option<&AttachmentSockets>,
+    fallback: Transform,
+) -> (Entity, Transform) {
+    hand_attachment(humanoid, sockets, false, fallback)
+}
+
+/// `grip_attachment`, but can go in the off hand with `Socket.GripOff`.
+pub fn hand_attachment(
+    humanoid: &Humanoid,
+    sockets: Option<&AttachmentSockets>,
+    off_hand: bool,
+    fallback: Transform,
+) -> (Entity, Transform) {
+    let (socket, hand) = match off_hand {
+        false => (SOCKET_GRIP_MAIN, humanoid.dominant_hand()),
+        true => (SOCKET_GRIP_OFF, humanoid.off_hand()),
+    };
+    match sockets.and_then(|s| s.get(socket)) {
+        Some(e_socket) => (e_socket, Transform::IDENTITY),
+        None => {
+            warn!("Rig is missing `{}`, using a hardcoded grip.", socket);
+            (hand, fallback)
+        }
+    }
+}
+
+/// Slot for a new one-handed `T`. Goes in the off hand if the dominant hand is already holding
+/// a `T` and the off hand isn't, for dual wielding.
+pub fn dual_wield_slot<T: Component>(
+    humanoid: &Humanoid,
+    equipped: Option<&Equipped>,
+    item_query: &Query<(), With<T>>,
+) -> SlotAlignment {
+    let dominant = humanoid.dominant_hand_type;
+    match equipped.map(|e| e.by_dominance(dominant)) {
+        Some((e_main, e_off)) if item_query.contains(e_main) && !item_query.contains(e_off) => {
+            SlotAlignment::off(dominant)
+        }
+        _ => SlotAlignment::dominant(dominant),
+    }
+}
+
+/// Muzzle transform relative to the item, from its `Socket.Muzzle` GLTF node. Uses `fallback` if
+/// the model doesn't have one.
+pub fn muzzle_attachment(
+    node: Option<&Handle<GltfNode>>,
+    nodes: &Assets<GltfNode>,
+    fallback: Transform,
+) -> Transform {
+    match node.and_then(|h| nodes.get(h)) {
+        Some(node) => node.transform,
+        None => {
+            warn!(
+                "Model is missing `{}`, using a hardcoded muzzle.",
+                SOCKET_MUZZLE
+            );
+            fallback
+        }
+    }
+}
+
 // I don't know if this is a good idea TBH, but whatever. the exclusive world access seems
 // necessary due to the sheer number of event writers that exist. the alternative is
 // to make separate systems for each item which all read the event queue, but I *assume*
@@ -153,12 +297,8 @@ pub fn convert_untyped_events(world: &mut World) {
         // TODO?: figure out if I can batch send? it's difficult cause of borrowing rules.
         let mut reader = events.get_reader();
         for ev in reader.read(&events) {
-            world.send_event(
-                world
-                    .get::<ItemIdentifier>(ev.item_entity)
-                    .unwrap()
-                    .typed_event(ev),
-            );
+            let identifier = *world.get::<ItemIdentifier>(ev.item_entity).unwrap();
+            identifier.send_typed_event(world, ev);
         }
     });
 }
@@ -169,6 +309,7 @@ pub fn equip_items(
     mut events: EventReader<UntypedItemEquipEvent>,
     mut humanoid_query: Query<(&Humanoid, &mut Equipped)>,
     item_query: Query<(&ItemIdentifier, &Models, &Handedness)>,
+    empty_query: Query<(), With<EmptyHand>>,
 ) {
     for UntypedItemEquipEvent {
         parent_entity,
@@ -190,13 +331,13 @@ pub fn equip_items(
             ItemEquipEventSlot::Manual { alignment } => *alignment,
             ItemEquipEventSlot::Auto => match handedness {
                 Handedness::Double => SlotAlignment::Double,
-                Handedness::Single => match humanoid.dominant_hand_type {
-                    HumanoidDominantHand::Left => SlotAlignment::Left,
-                    HumanoidDominantHand::Right => SlotAlignment::Right,
-                },
+                Handedness::Single => SlotAlignment::dominant(humanoid.dominant_hand_type),
             },
         };
 
+        let mut replaced = vec![equipped.left, equipped.right];
+        replaced.dedup();
+
         if let Some(&e_model) = models.targets.get(&Grip::Head) {
             commands.entity(e_model).set_parent(humanoid.head);
         }
@@ -232,12 +373,26 @@ pub fn equip_items(
             }
         }
 
-        commands.entity(*item_entity).insert((
+        for e_replaced in replaced {
+            if e_replaced != equipped.left
+                && e_replaced != equipped.right
+                && empty_query.contains(e_replaced)
+            {
+                commands.entity(e_replaced).despawn();
+            }
+        }
+
+        let mut e_item_commands = commands.entity(*item_entity);
+        e_item_commands.insert((
             EquippedTo {
                 target: *parent_entity,
             },
             slot,
         ));
+        match slot.is_off_hand(humanoid.dominant_hand_type) {
+            false => e_item_commands.insert(MainHand).remove::<OffHand>(),
+            true => e_item_commands.insert(OffHand).remove::<MainHand>(),
+        };
 
         info!(
             "Equipped {:?} ({:?}) to {:?}.",
@@ -0,0 +1,253 @@
+//! Carrying more than what's in hand.
+
+use bevy::prelude::*;
+use grin_rig::{humanoid::Humanoid, socket::AttachmentSockets};
+
+use crate::{
+    equip::{equip_items, grip, ItemEquipEventSlot, Models, UntypedItemEquipEvent},
+    mechanics::{
+        animation::Aiming,
+        firing::Active,
+        util::{InputHandler, SuppressedInput},
+    },
+    plugin::ItemSet,
+};
+
+pub struct InventoryPlugin;
+
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SwitchItemEvent>()
+            .add_systems(Update, (prune_inventories, switch_items).chain())
+            .add_systems(
+                PostUpdate,
+                stash_equipped_items
+                    .after(equip_items)
+                    .in_set(ItemSet::Equip),
+            );
+    }
+}
+
+/// Every item a humanoid is carrying. Only `selected` is equipped, the rest are hidden.
+///
+/// Equipping something new adds it and selects it.
+#[derive(Component, Debug, Clone, Default)]
+pub struct Inventory {
+    pub items: Vec<Entity>,
+    pub selected: usize,
+}
+
+impl Inventory {
+    pub fn selected_item(&self) -> Option<Entity> {
+        self.items.get(self.selected).copied()
+    }
+
+    /// Takes `e_item` out, keeping the same item selected. If `e_item` was selected, the next
+    /// item gets selected and returned so it can be drawn.
+    pub fn remove(&mut self, e_item: Entity) -> Option<Entity> {
+        let i = self.items.iter().position(|&e| e == e_item)?;
+        self.items.remove(i);
+        if i < self.selected {
+            self.selected -= 1;
+        } else if i == self.selected {
+            if self.items.is_empty() {
+                self.selected = 0;
+            } else {
+                self.selected = i % self.items.len();
+                return self.selected_item();
+            }
+        }
+        None
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwitchTarget {
+    Next,
+    Previous,
+    Slot(usize),
+}
+
+/// Switches the selected item in an `Inventory`.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SwitchItemEvent {
+    /// Entity with the `Inventory`.
+    pub entity: Entity,
+    pub target: SwitchTarget,
+}
+
+/// Puts an item away. It stays parented to the humanoid, but can't be seen or used.
+pub(crate) fn holster(commands: &mut Commands, e_item: Entity, models: Option<&Models>) {
+    commands
+        .entity(e_item)
+        .remove::<(InputHandler, SuppressedInput, Active, Aiming)>()
+        .insert(Visibility::Hidden);
+    for &e_model in models.into_iter().flat_map(|m| m.targets.values()) {
+        commands.entity(e_model).insert(Visibility::Hidden);
+    }
+}
+
+/// Takes an item back out. The models are re-parented by `equip_items`.
+pub(crate) fn draw(
+    commands: &mut Commands,
+    e_owner: Entity,
+    e_item: Entity,
+    humanoid: &Humanoid,
+    sockets: Option<&AttachmentSockets>,
+    models: Option<&Models>,
+    input: bool,
+    equip_events: &mut EventWriter<UntypedItemEquipEvent>,
+) {
+    commands
+        .entity(e_item)
+        .insert(Visibility::Inherited)
+        .set_parent(grip(humanoid, sockets));
+    if input {
+        commands.entity(e_item).insert(InputHandler);
+    }
+    for &e_model in models.into_iter().flat_map(|m| m.targets.values()) {
+        commands.entity(e_model).insert(Visibility::Inherited);
+    }
+    equip_events.send(UntypedItemEquipEvent {
+        parent_entity: e_owner,
+        item_entity: e_item,
+        slot: ItemEquipEventSlot::Auto,
+    });
+}
+
+pub fn switch_items(
+    mut commands: Commands,
+    mut inventory_query: Query<(&mut Inventory, &Humanoid, Option<&AttachmentSockets>)>,
+    item_query: Query<(Option<&Models>, Has<InputHandler>, Has<SuppressedInput>)>,
+    mut switch_events: EventReader<SwitchItemEvent>,
+    mut equip_events: EventWriter<UntypedItemEquipEvent>,
+) {
+    for &SwitchItemEvent { entity, target } in switch_events.read() {
+        let Ok((mut inventory, humanoid, sockets)) = inventory_query.get_mut(entity) else {
+            continue;
+        };
+
+        let len = inventory.items.len();
+        let selected = match target {
+            _ if len == 0 => continue,
+            SwitchTarget::Next => (inventory.selected + 1) % len,
+            SwitchTarget::Previous => (inventory.selected + len - 1) % len,
+            SwitchTarget::Slot(i) if i < len => i,
+            SwitchTarget::Slot(..) => continue,
+        };
+        if selected == inventory.selected {
+            continue;
+        }
+
+        let (e_out, e_in) = (
+            inventory.items[inventory.selected],
+            inventory.items[selected],
+        );
+        inventory.selected = selected;
+
+        let Ok((models_out, input, suppressed)) = item_query.get(e_out) else {
+            continue;
+        };
+        holster(&mut commands, e_out, models_out);
+
+        let Ok((models_in, ..)) = item_query.get(e_in) else {
+            continue;
+        };
+        draw(
+            &mut commands,
+            entity,
+            e_in,
+            humanoid,
+            sockets,
+            models_in,
+            // `suppress_stunned_input` takes it away again if the stun isn't over
+            input || suppressed,
+            &mut equip_events,
+        );
+    }
+}
+
+/// Drops despawned items. If the selected item is gone, the next one gets drawn.
+pub fn prune_inventories(
+    mut commands: Commands,
+    mut inventory_query: Query<(
+        Entity,
+        &mut Inventory,
+        &Humanoid,
+        Option<&AttachmentSockets>,
+    )>,
+    item_query: Query<Option<&Models>>,
+    mut equip_events: EventWriter<UntypedItemEquipEvent>,
+) {
+    for (e_owner, mut inventory, humanoid, sockets) in inventory_query.iter_mut() {
+        if inventory.items.iter().all(|&e| item_query.contains(e)) {
+            continue;
+        }
+
+        let e_selected = inventory.selected_item();
+        let before = inventory.items[..inventory.selected.min(inventory.items.len())]
+            .iter()
+            .filter(|&&e| item_query.contains(e))
+            .count();
+        inventory.items.retain(|&e| item_query.contains(e));
+
+        if inventory.items.is_empty() {
+            inventory.selected = 0;
+            continue;
+        }
+
+        if let Some(i) = e_selected.and_then(|e| inventory.items.iter().position(|&x| x == e)) {
+            inventory.selected = i;
+            continue;
+        }
+
+        // whatever was after the dead item moved into its slot
+        inventory.selected = before % inventory.items.len();
+        let e_item = inventory.items[inventory.selected];
+        draw(
+            &mut commands,
+            e_owner,
+            e_item,
+            humanoid,
+            sockets,
+            item_query.get(e_item).ok().flatten(),
+            false,
+            &mut equip_events,
+        );
+    }
+}
+
+/// Adds newly equipped items to the `Inventory` and selects them.
+///
+/// Off hand items are left out, they're dual wielded with whatever's selected.
+pub fn stash_equipped_items(
+    mut commands: Commands,
+    mut inventory_query: Query<(&mut Inventory, &Humanoid)>,
+    models_query: Query<&Models>,
+    mut equip_events: EventReader<UntypedItemEquipEvent>,
+) {
+    for UntypedItemEquipEvent {
+        parent_entity,
+        item_entity,
+        slot,
+    } in equip_events.read()
+    {
+        let Ok((mut inventory, humanoid)) = inventory_query.get_mut(*parent_entity) else {
+            continue;
+        };
+        if let ItemEquipEventSlot::Manual { alignment } = slot {
+            if alignment.is_off_hand(humanoid.dominant_hand_type) {
+                continue;
+            }
+        }
+        if inventory.items.contains(item_entity) {
+            continue;
+        }
+
+        if let Some(e_out) = inventory.selected_item() {
+            holster(&mut commands, e_out, models_query.get(e_out).ok());
+        }
+        inventory.items.push(*item_entity);
+        inventory.selected = inventory.items.len() - 1;
+    }
+}
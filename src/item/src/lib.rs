@@ -1,5 +1,8 @@
 pub mod equip;
+pub mod inventory;
 pub mod library;
 pub mod mechanics;
+pub mod pickup;
 pub mod plugin;
 pub mod spawn;
+pub mod stats;
@@ -23,13 +23,14 @@ use crate::{
     mechanics::{
         animation::{AnimatorSystemParams, ReadOnlyAnimatorSystemParams},
         combo::{ComboPlugin, ComboStack},
-        firing::{Active, FireRate, FiringBehavior, FiringPlugin, ShotFired},
+        firing::{Active, FiringBehavior, FiringPlugin, ShotFired},
         fx::on_hit_spawn,
         util::insert_on_lmb,
     },
     models,
     plugin::{ItemPlugin, ItemSet, WeaponBundle},
     spawn::item_spawner,
+    stats::ItemStats,
 };
 
 pub use super::plugin::item_identifier_filters::Fist;
@@ -37,6 +38,12 @@ use super::plugin::ItemIdentifier;
 
 pub struct FistPlugin;
 
+pub const FIST_STATS: ItemStats = ItemStats {
+    fire_rate: 0.8,
+    damage: 0.001,
+    ..ItemStats::DEFAULT
+};
+
 #[derive(Resource, AssetCollection)]
 pub struct FistAssets {
     // there's nothing visual here; just hitboxes
@@ -102,7 +109,6 @@ impl Plugin for FistPlugin {
                             (Grip::Offhand, assets.offhand.clone()),
                         ],
                         handedness: Handedness::Double,
-                        fire_rate: FireRate(Duration::from_millis(800)),
                         ..Default::default()
                     }
                 })
@@ -148,11 +154,12 @@ pub fn punch(
         &Models,
         &EquippedTo,
         &SlotAlignment,
+        &ItemStats,
     )>,
     humanoid_query: Query<&Humanoid>,
 ) {
     for ShotFired { entity: e_item, .. } in shot_events.read() {
-        let (mut combo, hitboxes, models, EquippedTo { target: e_user }, alignment) =
+        let (mut combo, hitboxes, models, EquippedTo { target: e_user }, alignment, stats) =
             item_query.get_mut(*e_item).unwrap();
         let dominant = humanoid_query.get(*e_user).unwrap().dominant_hand_type;
         let mut animator = animator_params.get_mut(*e_item).unwrap();
@@ -210,7 +217,7 @@ pub fn punch(
                 .entity(e_collider)
                 .insert(Damage {
                     ty: DamageVariant::Ballistic,
-                    value: 0.001,
+                    value: stats.damage,
                     source: Some(*e_item),
                 })
                 .remove::<ColliderDisabled>();
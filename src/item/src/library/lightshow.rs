@@ -11,7 +11,11 @@ use grin_render::{
     duoquad::{render_duoquads, DuoQuad, DuoQuadBundle, DuoQuadRadiusLens},
     sketched::{NoOutline, SketchMaterial},
 };
-use grin_util::{color::rand_spectrum, tween::TweenCompletedEvent};
+use grin_util::{
+    color::rand_spectrum,
+    rng::{GrinRng, RNG_FX, RNG_SPREAD},
+    tween::TweenCompletedEvent,
+};
 
 use super::{
     aim_single,
@@ -133,10 +137,16 @@ pub fn spawn_bullet(
     projectile_assets: Res<ProjectileAssets>,
     mut materials: ResMut<Assets<SketchMaterial>>,
     fallback: Res<FallbackImage>,
+    mut rng: ResMut<GrinRng>,
 ) {
     for ShotFired { entity, .. } in shot_events.read() {
         let (target, accuracy, children, plr) = weapon_query.get(*entity).unwrap();
-        let muzzle_g_transform = muzzle_query.get(*children.first().unwrap()).unwrap();
+        let Some((e_muzzle, muzzle_g_transform)) = children
+            .iter()
+            .find_map(|&e| muzzle_query.get(e).ok().map(|t| (e, t)))
+        else {
+            continue;
+        };
 
         let origin = muzzle_g_transform.translation();
         let target = target.transform.translation;
@@ -145,20 +155,20 @@ pub fn spawn_bullet(
             None => Group::ENEMY_PROJECTILE,
         };
 
-        let mut rng = rand::thread_rng();
         let distr = Uniform::new_inclusive(
             (-0.5 / accuracy.0).to_radians(),
             (0.5 / accuracy.0).to_radians(),
         );
         let dir = Quat::from_euler(
             EulerRot::XYZ,
-            rand::thread_rng().sample(distr),
-            rand::thread_rng().sample(distr),
+            rng.fork(RNG_SPREAD).sample(distr),
+            rng.fork(RNG_SPREAD).sample(distr),
             0.0,
         )
         .mul_vec3(target - origin);
 
         commands.spawn((
+            Laser { muzzle: e_muzzle },
             DuoQuadBundle {
                 duoquad: DuoQuad {
                     origin,
@@ -166,7 +176,7 @@ pub fn spawn_bullet(
                     radius: 0.2,
                 },
                 material: materials.add(SketchMaterial {
-                    base_color: rand_spectrum(&mut rng),
+                    base_color: rand_spectrum(rng.fork(RNG_FX)),
                     base_color_texture: Some(fallback.texture.clone()),
                     unlit: true,
                     double_sided: true,
@@ -191,14 +201,20 @@ pub fn spawn_bullet(
     }
 }
 
+/// Keeps a laser attached to the `Muzzle` it came from.
+#[derive(Component)]
+pub struct Laser {
+    pub muzzle: Entity,
+}
+
 pub fn align_lasers(
     muzzle_query: Query<&GlobalTransform, With<Muzzle>>,
-    mut duoquad_query: Query<&mut DuoQuad>,
+    mut laser_query: Query<(&Laser, &mut DuoQuad)>,
 ) {
-    let Ok(transform) = muzzle_query.get_single() else {
-        return;
-    };
-    for mut duoquad in duoquad_query.iter_mut() {
+    for (laser, mut duoquad) in laser_query.iter_mut() {
+        let Ok(transform) = muzzle_query.get(laser.muzzle) else {
+            continue;
+        };
         duoquad.origin = transform.translation();
     }
 }
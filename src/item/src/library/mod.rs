@@ -1,2 +1,4 @@
 pub mod fist;
 pub mod plugin;
+pub mod sledge;
+pub mod smg;
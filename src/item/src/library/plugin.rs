@@ -1,19 +1,66 @@
 use bevy::{app::PluginGroupBuilder, prelude::*};
 use bevy_enum_filter::EnumFilter;
 use grin_derive::TypedEvents;
+use serde::{Deserialize, Serialize};
 
-use super::fist::FistPlugin;
+use crate::stats::ItemStats;
+
+use super::{
+    fist::{FistPlugin, FIST_STATS},
+    sledge::{SledgePlugin, SLEDGE_STATS},
+    smg::{SmgPlugin, SMG_STATS},
+};
 
 pub struct ItemLibrary;
 
 impl PluginGroup for ItemLibrary {
     fn build(self) -> PluginGroupBuilder {
-        PluginGroupBuilder::start::<Self>().add(FistPlugin)
+        PluginGroupBuilder::start::<Self>()
+            .add(FistPlugin)
+            .add(SmgPlugin)
+            .add(SledgePlugin)
     }
 }
 
-#[derive(Component, Copy, Clone, Debug, EnumFilter, TypedEvents, Default)]
+#[derive(
+    Component,
+    Copy,
+    Clone,
+    Debug,
+    PartialEq,
+    Eq,
+    Hash,
+    EnumFilter,
+    TypedEvents,
+    Default,
+    Serialize,
+    Deserialize,
+)]
 pub enum ItemIdentifier {
     #[default]
     Fist,
+    Smg,
+    Sledge,
+}
+
+impl ItemIdentifier {
+    pub const ALL: [Self; 3] = [Self::Fist, Self::Smg, Self::Sledge];
+
+    /// Lowercase, for typing in.
+    pub fn name(&self) -> String {
+        format!("{:?}", self).to_lowercase()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|item| item.name() == name)
+    }
+
+    /// For when `items.stats.ron` doesn't have it.
+    pub fn default_stats(&self) -> ItemStats {
+        match self {
+            Self::Fist => FIST_STATS,
+            Self::Smg => SMG_STATS,
+            Self::Sledge => SLEDGE_STATS,
+        }
+    }
 }
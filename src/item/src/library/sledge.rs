@@ -1,26 +1,56 @@
+//! Big hammer. Hold to wind up, let go to swing. Longer charges hit harder and swing faster.
+
 use std::time::Duration;
 
 use bevy::prelude::*;
-use bevy_asset_loader::prelude::{AssetCollection, LoadingStateAppExt};
+use bevy_asset_loader::prelude::*;
 use bevy_rapier3d::prelude::*;
 use grin_asset::AssetLoadState;
-use grin_damage::{impact::Impact, ContactDamage, Damage, DamageVariant};
-use grin_physics::{collider, CollisionGroupExt, CollisionGroupsExt};
-use grin_render::sketched::SketchMaterial;
-use grin_rig::humanoid::Humanoid;
-use grin_util::event::Spawnable;
+use grin_damage::{
+    hit::{ContactDamage, Damage, DamageVariant},
+    impact::Impact,
+    knockback::{Knockback, KnockbackMode},
+};
+use grin_physics::collider;
+use grin_render::{
+    feedback::{AddTraumaEvent, HitStopEvent},
+    sketched::SketchMaterial,
+};
+use grin_rig::{
+    anim_event::{AnimEventKind, AnimationEvent, AnimationEventMarker, AnimationEvents},
+    humanoid::Humanoid,
+    socket::AttachmentSockets,
+};
 
 use crate::{
-    find_item_owner,
-    firing::{self, FireRate, FiringPlugin, FiringType, SemiFireBundle, ShotFired},
-    insert_on_lmb,
-    melee::{update_hammer_winds, Charging, Swinging, Wind, Winding},
-    on_hit_render_impact, Active, Equipped, Item, ItemEquipEvent,
-    ItemPlugin, ItemSet, ItemSpawnEvent, WeaponBundle,
+    equip::{grip_attachment, Equipped, ItemEquipEventSlot, UntypedItemEquipEvent},
+    mechanics::{
+        melee::{
+            release_charges, spawn_melee_impacts, sweep_melee_swings, ChargeCancelledEvent,
+            ChargeLevel, ChargeReleasedEvent, Charging, FullyCharged, MeleeHitEvent, MeleeSwing,
+            Swinging, Winding,
+        },
+        util::{find_item_owner, insert_on_lmb},
+    },
+    plugin::{ItemPlugin, ItemSet, WeaponBundle},
+    spawn::ItemSpawnEvent,
+    stats::{stamp_item_stats, ItemStats},
 };
 
+pub use super::plugin::item_identifier_filters::Sledge;
+use super::plugin::ItemIdentifier;
+
 pub struct SledgePlugin;
 
+/// `damage` is at no charge, and gets scaled by `ChargeLevel::multiplier`.
+pub const SLEDGE_STATS: ItemStats = ItemStats {
+    damage: 10.0,
+    swing_arc: 120.0,
+    swing_speed: 4.0,
+    swing_window: 0.2..0.8,
+    ..ItemStats::DEFAULT
+};
+
 #[derive(Resource, AssetCollection)]
 pub struct SledgeAssets {
     #[asset(key = "mesh.sledge")]
@@ -37,134 +67,171 @@ pub struct SledgeAssets {
     pub wind_animation: Handle<AnimationClip>,
 }
 
-#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
-pub enum SledgeSystemSet {
-    Input,
-    Fire,
-    Effects,
-}
-
 impl Plugin for SledgePlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((
-            ItemPlugin::<Sledge>::default(),
-            FiringPlugin::<Sledge>::from(FiringType::SemiAutomatic),
-        ))
-        .add_collection_to_loading_state::<_, SledgeAssets>(AssetLoadState::Loading)
-        .configure_sets(
-            Update,
-            (
-                SledgeSystemSet::Input
-                    .run_if(in_state(AssetLoadState::Success))
-                    .before(firing::semi_fire::<Sledge>),
-                SledgeSystemSet::Fire
-                    .run_if(in_state(AssetLoadState::Success))
-                    .after(firing::semi_fire::<Sledge>),
-                SledgeSystemSet::Effects.run_if(in_state(AssetLoadState::Success)),
+        app.add_plugins(ItemPlugin::<Sledge>::default())
+            .configure_loading_state(
+                LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<SledgeAssets>(),
             )
-                .chain(),
-        )
-        .add_systems(
-            Update,
-            (
-                spawn.in_set(ItemSet::Spawn),
+            .add_systems(
+                PreUpdate,
+                insert_on_lmb::<Sledge, Charging>.in_set(ItemSet::Input),
+            )
+            .add_systems(
+                Update,
                 (
-                    insert_on_lmb::<Sledge, Active>,
-                    apply_deferred,
-                    wind,
-                    charge,
-                    swing_or_cancel,
-                    unswing,
-                    update_hammer_winds,
-                )
-                    .chain()
-                    .in_set(SledgeSystemSet::Input),
-                (|| Impact::from_burst_radius(2.0))
-                    .pipe(on_hit_render_impact::<Sledge>)
-                    .in_set(SledgeSystemSet::Effects),
-            ),
-        );
+                    spawn.in_set(ItemSet::Spawn),
+                    mark_swing_window
+                        .after(stamp_item_stats)
+                        .run_if(in_state(AssetLoadState::Success)),
+                    (wind, charge, swing, cancel, unswing)
+                        .chain()
+                        .after(release_charges)
+                        .in_set(ItemSet::Fire)
+                        .run_if(in_state(AssetLoadState::Success)),
+                    toggle_swing_hitbox
+                        .in_set(ItemSet::Fire)
+                        .before(sweep_melee_swings),
+                    (|| Impact::from_burst_radius(2.0))
+                        .pipe(spawn_melee_impacts::<Sledge>)
+                        .in_set(ItemSet::Effects),
+                    hit_feedback.in_set(ItemSet::Effects),
+                ),
+            );
     }
 }
 
-#[derive(Component, Clone, Default)]
-pub struct Sledge;
-
-impl Spawnable for Sledge {
-    type Event = ItemSpawnEvent<Sledge>;
-}
-
-impl Item for Sledge {
-    type SpawnEvent = ItemSpawnEvent<Sledge>;
-    type EquipEvent = ItemEquipEvent<Sledge>;
-}
-
 pub fn spawn(
     mut commands: Commands,
     assets: Res<SledgeAssets>,
     meshes: Res<Assets<Mesh>>,
-    humanoid_query: Query<&Humanoid>,
+    humanoid_query: Query<(&Humanoid, Option<&AttachmentSockets>)>,
     mut spawn_events: EventReader<ItemSpawnEvent<Sledge>>,
-    mut equip_events: EventWriter<ItemEquipEvent<Sledge>>,
+    mut equip_events: EventWriter<UntypedItemEquipEvent>,
 ) {
-    for ItemSpawnEvent { parent_entity, .. } in spawn_events.read() {
-        let humanoid = humanoid_query.get(*parent_entity).unwrap();
-
-        let item_entity = commands
+    for ItemSpawnEvent {
+        parent_entity,
+        transform,
+        ..
+    } in spawn_events.read()
+    {
+        let e_item = commands
             .spawn((
-                Sledge::default(),
-                Wind::new(1.0),
-                WeaponBundle::default(),
+                WeaponBundle::<()> {
+                    identifier: ItemIdentifier::Sledge,
+                    ..Default::default()
+                },
+                ChargeLevel::new(1.0, 2.0),
                 MaterialMeshBundle {
                     mesh: assets.sledge.clone(),
                     material: assets.sledge_material.clone(),
-                    ..Default::default()
-                },
-                SemiFireBundle {
-                    fire_rate: FireRate(2.0),
+                    transform: *transform,
                     ..Default::default()
                 },
                 RigidBody::Dynamic,
                 collider!(meshes, &assets.sledge),
-                CollisionGroups::from_group_default(Group::PLAYER_PROJECTILE),
                 Ccd::enabled(),
                 ActiveEvents::COLLISION_EVENTS,
                 ActiveHooks::FILTER_CONTACT_PAIRS,
                 ColliderMassProperties::default(),
                 GravityScale(0.0),
+                // filled in by `mark_swing_window`
+                AnimationEvents::default(),
             ))
-            .set_parent(humanoid.dominant_hand())
             .id();
+        info!("Spawning item {:?}", e_item);
+
+        let Some(e_parent) = *parent_entity else {
+            continue;
+        };
+        let Ok((humanoid, sockets)) = humanoid_query.get(e_parent) else {
+            error!("Only `Humanoid`s can hold a sledge.");
+            continue;
+        };
+
+        let (e_grip, grip_transform) = grip_attachment(humanoid, sockets, Transform::default());
+        commands
+            .entity(e_item)
+            .insert(grip_transform)
+            .set_parent(e_grip);
+        equip_events.send(UntypedItemEquipEvent {
+            parent_entity: e_parent,
+            item_entity: e_item,
+            slot: ItemEquipEventSlot::Auto,
+        });
+    }
+}
 
-        equip_events.send(ItemEquipEvent::new(*parent_entity, item_entity));
+/// Screen shake from landing a hit.
+const HIT_TRAUMA: f32 = 0.4;
+
+/// Hit-stop from landing a hit.
+const HIT_STOP: HitStopEvent = HitStopEvent {
+    duration: 0.1,
+    scale: 0.1,
+};
+
+/// Puts the swing animation's `EnableHitbox` and `DisableHitbox` markers on
+/// `ItemStats.swing_window`, whenever the stats get stamped.
+pub fn mark_swing_window(
+    assets: Res<SledgeAssets>,
+    clips: Res<Assets<AnimationClip>>,
+    mut item_query: Query<(&ItemIdentifier, &ItemStats, &mut AnimationEvents), Changed<ItemStats>>,
+) {
+    let Some(swing_clip) = clips.get(&assets.swing_animation) else {
+        return;
+    };
+
+    // the `Sledge` marker might not be in yet, right after it spawns
+    for (_, stats, mut anim_events) in item_query
+        .iter_mut()
+        .filter(|(identifier, ..)| **identifier == ItemIdentifier::Sledge)
+    {
+        anim_events.markers = [
+            (stats.swing_window.start, AnimEventKind::EnableHitbox),
+            (stats.swing_window.end, AnimEventKind::DisableHitbox),
+        ]
+        .into_iter()
+        .map(|(fraction, event)| AnimationEventMarker {
+            clip: assets.swing_animation.clone(),
+            time: swing_clip.duration() * fraction,
+            event,
+        })
+        .collect();
     }
 }
 
-/// Pulls the hammer back.
+/// Pulls the hammer back while `Charging`.
 pub fn wind(
     mut commands: Commands,
     sledge_assets: Res<SledgeAssets>,
     clips: Res<Assets<AnimationClip>>,
-    item_query: Query<&Wind, With<Sledge>>,
-    mut shot_events: EventReader<ShotFired<Sledge>>,
+    item_query: Query<
+        (Entity, &ChargeLevel),
+        (
+            With<Sledge>,
+            With<Charging>,
+            Without<Winding>,
+            Without<Swinging>,
+        ),
+    >,
     parent_query: Query<&Parent>,
     mut animator_query: Query<&mut AnimationPlayer>,
 ) {
-    for ShotFired { entity: e_item, .. } in shot_events.read() {
-        for e_animator in parent_query.iter_ancestors(*e_item) {
+    for (e_item, charge) in item_query.iter() {
+        for e_animator in parent_query.iter_ancestors(e_item) {
             let Ok(mut animator) = animator_query.get_mut(e_animator) else {
                 continue;
             };
 
-            let wind = item_query.get(*e_item).unwrap();
             let wind_clip = clips.get(&sledge_assets.wind_animation).unwrap();
             animator
                 .start_with_transition(
                     sledge_assets.wind_animation.clone(),
                     Duration::from_secs_f32(0.1),
                 )
-                .set_speed(wind_clip.duration() / wind.max);
-            commands.entity(*e_item).insert(Winding {
+                .set_speed(wind_clip.duration() / charge.charge_time);
+            commands.entity(e_item).insert(Winding {
                 duration: wind_clip.duration(),
             });
         }
@@ -175,12 +242,12 @@ pub fn wind(
 pub fn charge(
     mut commands: Commands,
     sledge_assets: Res<SledgeAssets>,
-    item_query: Query<(Entity, &Wind), (With<Sledge>, With<Active>, Without<Charging>)>,
+    item_query: Query<(Entity, &ChargeLevel), (With<Sledge>, With<Winding>, Without<FullyCharged>)>,
     parent_query: Query<&Parent>,
     mut animator_query: Query<&mut AnimationPlayer>,
 ) {
-    for (e_item, wind) in item_query.iter() {
-        if wind.progress() >= 1.0 {
+    for (e_item, charge) in item_query.iter() {
+        if charge.progress() >= 1.0 {
             for e_animator in parent_query.iter_ancestors(e_item) {
                 let Ok(mut animator) = animator_query.get_mut(e_animator) else {
                     continue;
@@ -189,56 +256,133 @@ pub fn charge(
                 animator
                     .start(sledge_assets.charge_animation.clone())
                     .repeat();
-                commands.entity(e_item).insert(Charging);
+                commands.entity(e_item).insert(FullyCharged);
             }
         }
     }
 }
 
-/// If `Wind.progress() >= 1.0`, begins swinging.
-/// Otherwise if a `Wind` is in progress, stops it.
-pub fn swing_or_cancel(
+/// Turns a released charge into a swing. More charge hits harder and swings faster.
+///
+/// No charge swings at half of `ItemStats.swing_speed`.
+pub fn swing(
     mut commands: Commands,
     sledge_assets: Res<SledgeAssets>,
     clips: Res<Assets<AnimationClip>>,
-    item_query: Query<(Entity, &Wind, &Winding), (With<Sledge>, Without<Active>)>,
+    item_query: Query<&ItemStats, (With<Sledge>, With<Winding>)>,
     parent_query: Query<&Parent, Without<Equipped>>,
     parent_query_eq: Query<&Parent, With<Equipped>>,
     mut animator_query: Query<&mut AnimationPlayer>,
+    mut released_events: EventReader<ChargeReleasedEvent>,
 ) {
-    for (e_item, wind, winding) in item_query.iter() {
+    for &ChargeReleasedEvent {
+        entity: e_item,
+        progress,
+        multiplier,
+    } in released_events.read()
+    {
+        let Ok(stats) = item_query.get(e_item) else {
+            continue;
+        };
+
         for e_animator in parent_query.iter_ancestors(e_item) {
             let Ok(mut animator) = animator_query.get_mut(e_animator) else {
                 continue;
             };
 
-            commands.entity(e_item).remove::<(Winding, Charging)>();
+            commands.entity(e_item).remove::<(Winding, ContactDamage)>();
             let swing_clip = clips.get(&sledge_assets.swing_animation).unwrap();
-            if wind.progress() >= 1.0 {
-                animator
-                    .start(sledge_assets.swing_animation.clone())
-                    .set_speed(4.0);
-                commands.entity(e_item).insert((
-                    ContactDamage::Once,
+            let speed = stats.swing_speed * (0.5 + 0.5 * progress);
+            animator
+                .start(sledge_assets.swing_animation.clone())
+                .set_speed(speed);
+            let duration = swing_clip.duration() / speed;
+            let window = &stats.swing_window;
+            commands.entity(e_item).insert((
+                // the animated collider is unreliable, so the hitbox is done by hand
+                MeleeSwing::new(
+                    stats.swing_arc,
+                    3.0,
+                    duration,
+                    duration * window.start..duration * window.end,
                     Damage {
                         ty: DamageVariant::Ballistic,
-                        value: 20.0,
+                        value: stats.damage * multiplier,
                         source: find_item_owner(e_item, &parent_query_eq),
                     },
-                    Swinging {
-                        duration: swing_clip.duration() / 4.0,
-                    },
-                ));
-            } else if wind.progress() > 0.0 {
-                let elapsed = animator.elapsed();
-                animator
-                    // I almost made an issue about this, then I found a fix in this PR.
-                    // which might be stale? I dunno. I'll see about taking over when I'm not lazy.
-                    // https://github.com/bevyengine/bevy/pull/5912
-                    // update: this got fixed in 0.12. I'll keep it as a piece of history.
-                    .seek_to(-winding.duration + elapsed)
-                    .set_speed(-4.0);
-            }
+                )
+                .gated(),
+                Swinging { duration },
+                Knockback {
+                    impulse: 16.0,
+                    mode: KnockbackMode::Radial,
+                },
+            ));
+        }
+    }
+}
+
+/// Opens and closes the swing's hitbox on the swing animation's active frames.
+pub fn toggle_swing_hitbox(
+    mut item_query: Query<&mut MeleeSwing, With<Sledge>>,
+    mut animation_events: EventReader<AnimationEvent>,
+) {
+    for &AnimationEvent { entity, event } in animation_events.read() {
+        let Ok(mut swing) = item_query.get_mut(entity) else {
+            continue;
+        };
+        match event {
+            AnimEventKind::EnableHitbox => swing.open_window(),
+            AnimEventKind::DisableHitbox => swing.close_window(),
+            _ => (),
+        }
+    }
+}
+
+/// Landing a hit shakes the screen and stops time for a moment. It's a big hammer.
+pub fn hit_feedback(
+    item_query: Query<(), With<Sledge>>,
+    mut hit_events: EventReader<MeleeHitEvent>,
+    mut trauma_events: EventWriter<AddTraumaEvent>,
+    mut hit_stop_events: EventWriter<HitStopEvent>,
+) {
+    // one sledge hitting a crowd is still one hit
+    let hit = hit_events
+        .read()
+        .any(|MeleeHitEvent { e_item, .. }| item_query.contains(*e_item));
+    if hit {
+        trauma_events.send(AddTraumaEvent(HIT_TRAUMA));
+        hit_stop_events.send(HIT_STOP);
+    }
+}
+
+/// Puts the hammer back down if the charge gets interrupted.
+pub fn cancel(
+    mut commands: Commands,
+    item_query: Query<&Winding, With<Sledge>>,
+    parent_query: Query<&Parent>,
+    mut animator_query: Query<&mut AnimationPlayer>,
+    mut cancelled_events: EventReader<ChargeCancelledEvent>,
+) {
+    for &ChargeCancelledEvent { entity: e_item } in cancelled_events.read() {
+        let Ok(winding) = item_query.get(e_item) else {
+            continue;
+        };
+
+        for e_animator in parent_query.iter_ancestors(e_item) {
+            let Ok(mut animator) = animator_query.get_mut(e_animator) else {
+                continue;
+            };
+
+            commands.entity(e_item).remove::<Winding>();
+            let elapsed = animator.elapsed();
+            animator
+                // I almost made an issue about this, then I found a fix in this PR.
+                // which might be stale? I dunno. I'll see about taking over when I'm not lazy.
+                // https://github.com/bevyengine/bevy/pull/5912
+                // update: this got fixed in 0.12. I'll keep it as a piece of history.
+                .seek_to(-winding.duration + elapsed)
+                .set_speed(-4.0);
         }
     }
 }
@@ -1,178 +1,242 @@
-use bevy::prelude::*;
+//! Automatic pistol. It's one-handed, so picking up a second one dual wields them.
+
+use bevy::{gltf::GltfNode, prelude::*, utils::HashSet};
 use bevy_rapier3d::prelude::*;
 use grin_asset::AssetLoadState;
 use grin_damage::{
-    impact::Impact,
-    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor},
-    Damage, DamageVariant,
+    hit::{Damage, DamageVariant},
+    knockback::{Knockback, KnockbackMode},
+    projectiles::{BulletProjectile, ProjectileBundle, ProjectileColor, ProjectilePool},
 };
-use grin_rig::humanoid::Humanoid;
-use grin_util::event::Spawnable;
+use grin_input::action::InputAction;
+use grin_rig::{humanoid::Humanoid, socket::AttachmentSockets};
+use grin_util::rng::{GrinRng, RNG_SPREAD};
 use rand::{distributions::Uniform, Rng};
 
 use crate::{
-    aim_on_active, find_item_owner,
-    firing::{self, AutoFireBundle, FireRate, FiringPlugin, FiringType, ItemSfx, ShotFired},
-    insert_on_lmb, on_hit_render_impact, set_local_mouse_target,
-    unaim_on_unactive, Accuracy, Active, AimType, DamageCollisionGroups, Equipped, IdleType, Item,
-    ItemEquipEvent, ItemPlugin, ItemSet, ItemSpawnEvent, Muzzle, MuzzleBundle, ProjectileAssets,
-    Sfx, Target, WeaponBundle,
+    equip::{
+        dual_wield_slot, hand_attachment, muzzle_attachment, Equipped, EquippedTo,
+        ItemEquipEventSlot, UntypedItemEquipEvent,
+    },
+    mechanics::{
+        animation::{aim_on_active, unaim_on_unactive, AimType, IdleType},
+        firing::{
+            Accuracy, Active, Ammo, FiringBehavior, FiringMode, FiringPlugin, FiringSet, ItemSfx,
+            Recoil, Reload, ShotFired, Target,
+        },
+        fx::{Muzzle, MuzzleBundle, ProjectileAssets, Sfx},
+        util::{insert_on_hand_buttons, insert_on_key, set_local_mouse_target},
+    },
+    plugin::{ItemPlugin, ItemSet, WeaponBundle},
+    spawn::ItemSpawnEvent,
+    stats::ItemStats,
 };
 
-pub struct SMGPlugin;
+pub use super::plugin::item_identifier_filters::Smg;
+use super::plugin::ItemIdentifier;
 
-#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
-pub enum SMGSystemSet {
-    Input,
-    Fire,
-    Effects,
-}
+pub struct SmgPlugin;
+
+pub const SMG_STATS: ItemStats = ItemStats {
+    fire_rate: 0.1,
+    damage: 5.0,
+    projectile_speed: 64.0,
+    spread: 8.0,
+    clip_size: Some(30),
+    ..ItemStats::DEFAULT
+};
+
+/// Rounds on top of the clip that it spawns with.
+const SMG_RESERVE: u32 = 180;
 
-impl Plugin for SMGPlugin {
+impl Plugin for SmgPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((
-            ItemPlugin::<SMG>::default(),
-            FiringPlugin::<SMG>::from(FiringType::Automatic),
+            ItemPlugin::<Smg>::default(),
+            FiringPlugin::<Smg>::from(HashSet::from([FiringBehavior::Automatic])),
         ))
-        .configure_sets(
-            Update,
+        .add_systems(
+            PreUpdate,
             (
-                SMGSystemSet::Input
-                    .run_if(in_state(AssetLoadState::Success))
-                    .before(firing::auto_fire::<SMG>),
-                SMGSystemSet::Fire
-                    .run_if(in_state(AssetLoadState::Success))
-                    .after(firing::auto_fire::<SMG>),
-                SMGSystemSet::Effects.run_if(in_state(AssetLoadState::Success)),
+                // LMB for the dominant hand, RMB for the off hand when dual wielding
+                insert_on_hand_buttons::<Smg, Active>,
+                insert_on_key::<Smg, Reload>(InputAction::Reload),
             )
-                .chain(),
+                .in_set(ItemSet::Input),
         )
         .add_systems(
             Update,
             (
                 spawn.in_set(ItemSet::Spawn),
-                (insert_on_lmb::<SMG, Active>, set_local_mouse_target::<SMG>)
-                    .chain()
-                    .in_set(SMGSystemSet::Input),
-                (spawn_bullet, aim_on_active::<SMG>, unaim_on_unactive::<SMG>)
-                    .in_set(SMGSystemSet::Fire),
-                (|| Impact::from_burst_radius(1.0))
-                    .pipe(on_hit_render_impact::<SMG>)
-                    .in_set(SMGSystemSet::Effects),
+                set_local_mouse_target::<Smg>.in_set(ItemSet::Input),
+                (spawn_bullet, aim_on_active::<Smg>, unaim_on_unactive::<Smg>)
+                    .in_set(ItemSet::Fire)
+                    .after(FiringSet::Fire)
+                    .run_if(in_state(AssetLoadState::Success)),
             ),
         );
     }
 }
 
-/// To add a firing target, insert a `item::Target` component.
-///
-/// To fire, set the `item::Active(true)` component.
-#[derive(Component, Clone, Default)]
-pub struct SMG;
-
-impl Spawnable for SMG {
-    type Event = ItemSpawnEvent<SMG>;
-}
-
-impl Item for SMG {
-    type SpawnEvent = ItemSpawnEvent<SMG>;
-    type EquipEvent = ItemEquipEvent<SMG>;
-}
-
+/// Goes in the off hand if the owner is already holding one.
 pub fn spawn(
     mut commands: Commands,
     assets: Res<ProjectileAssets>,
     sfx: Res<Sfx>,
-    humanoid_query: Query<&Humanoid>,
-    mut spawn_events: EventReader<ItemSpawnEvent<SMG>>,
-    mut equip_events: EventWriter<ItemEquipEvent<SMG>>,
+    nodes: Res<Assets<GltfNode>>,
+    humanoid_query: Query<(&Humanoid, Option<&AttachmentSockets>, Option<&Equipped>)>,
+    smg_query: Query<(), With<Smg>>,
+    mut spawn_events: EventReader<ItemSpawnEvent<Smg>>,
+    mut equip_events: EventWriter<UntypedItemEquipEvent>,
 ) {
-    for ItemSpawnEvent { parent_entity, .. } in spawn_events.read() {
-        let Ok(humanoid) = humanoid_query.get(*parent_entity) else {
-            println!("The parent entity did not have a `Humanoid`. Only `Humanoid`s are supported for `SMG`.");
-            continue;
-        };
-
-        let item_entity = commands
+    for ItemSpawnEvent {
+        parent_entity,
+        transform,
+        ..
+    } in spawn_events.read()
+    {
+        let muzzle_transform = muzzle_attachment(
+            assets.gun_muzzle.as_ref(),
+            &nodes,
+            Transform::from_xyz(0.0, 0.0, -0.15),
+        );
+        let e_item = commands
             .spawn((
-                SMG::default(),
-                WeaponBundle::default(),
+                WeaponBundle::<()> {
+                    identifier: ItemIdentifier::Smg,
+                    firing_mode: FiringMode::Auto { firing: false },
+                    ammo: Ammo::new(30, Some(SMG_RESERVE)),
+                    ..Default::default()
+                },
                 MaterialMeshBundle {
                     mesh: assets.gun.clone(),
                     material: assets.gun_material.clone(),
-                    transform: Transform::from_xyz(0.0, 0.0, -0.15),
-                    ..Default::default()
-                },
-                AutoFireBundle {
-                    fire_rate: FireRate(0.1),
+                    transform: *transform,
                     ..Default::default()
                 },
                 ItemSfx {
                     on_fire: sfx.uzi.clone(),
                 },
+                Recoil::new(
+                    Vec2::new(0.4_f32.to_radians(), 0.8_f32.to_radians()),
+                    6.0,
+                    Vec2::new(2.0_f32.to_radians(), 6.0_f32.to_radians()),
+                ),
                 IdleType::Idle,
                 AimType::RangedSingle,
             ))
             .with_children(|parent| {
                 parent.spawn(MuzzleBundle {
-                    transform: Transform::from_xyz(0.0, 0.0, -0.15),
+                    transform: muzzle_transform,
                     ..Default::default()
                 });
             })
-            .set_parent(humanoid.dominant_hand())
             .id();
+        info!("Spawning item {:?}", e_item);
+
+        let Some(e_parent) = *parent_entity else {
+            continue;
+        };
+        let Ok((humanoid, sockets, equipped)) = humanoid_query.get(e_parent) else {
+            error!("Only `Humanoid`s can hold an SMG.");
+            continue;
+        };
+
+        let slot = dual_wield_slot(humanoid, equipped, &smg_query);
+        let dual = slot.is_off_hand(humanoid.dominant_hand_type);
+        let (e_grip, grip_transform) = hand_attachment(
+            humanoid,
+            sockets,
+            dual,
+            Transform::from_xyz(0.0, 0.0, -0.15),
+        );
+        commands
+            .entity(e_item)
+            .insert(grip_transform)
+            .set_parent(e_grip);
+
+        if dual {
+            let (e_main, _) = equipped.unwrap().by_dominance(humanoid.dominant_hand_type);
+            commands.entity(e_main).insert(AimType::RangedDual);
+            commands.entity(e_item).insert(AimType::RangedDual);
+        }
 
-        equip_events.send(ItemEquipEvent::new(*parent_entity, item_entity));
+        equip_events.send(UntypedItemEquipEvent {
+            parent_entity: e_parent,
+            item_entity: e_item,
+            slot: ItemEquipEventSlot::Manual { alignment: slot },
+        });
     }
 }
 
-#[derive(Component)]
-pub struct SMGShot;
-
+/// Shoots a bullet from the `Muzzle` at the `Target`. `ItemStats.spread` only goes sideways,
+/// since the bullets stay level anyways.
 pub fn spawn_bullet(
     mut commands: Commands,
-    item_query: Query<(&Target, &Accuracy, &DamageCollisionGroups, &Children), With<SMG>>,
-    parent_query: Query<&Parent, With<Equipped>>,
+    mut pool: ResMut<ProjectilePool>,
+    item_query: Query<
+        (
+            &Target,
+            &Accuracy,
+            &ItemStats,
+            &CollisionGroups,
+            &GlobalTransform,
+            Option<&Children>,
+            Option<&EquippedTo>,
+            Option<&Recoil>,
+        ),
+        With<Smg>,
+    >,
     muzzle_query: Query<&GlobalTransform, With<Muzzle>>,
-    mut shot_events: EventReader<ShotFired<SMG>>,
+    mut shot_events: EventReader<ShotFired<Smg>>,
+    mut rng: ResMut<GrinRng>,
 ) {
-    for ShotFired { entity: e_item, .. } in shot_events.read() {
-        let (target, accuracy, damage_collision_groups, children) =
-            item_query.get(*e_item).unwrap();
-        let muzzle_g_transform = muzzle_query.get(*children.first().unwrap()).unwrap();
-
-        let origin = muzzle_g_transform.translation();
-        let target = target.transform.translation;
-        let distr = Uniform::new_inclusive(
-            (-8.0 / accuracy.0).to_radians(),
-            (8.0 / accuracy.0).to_radians(),
-        );
+    for &ShotFired { entity: e_item, .. } in shot_events.read() {
+        let Ok((target, accuracy, stats, groups, g_transform, children, equipped_to, recoil)) =
+            item_query.get(e_item)
+        else {
+            continue;
+        };
 
-        let fwd = (target - origin).normalize();
+        let origin = children
+            .into_iter()
+            .flatten()
+            .find_map(|&e| muzzle_query.get(e).ok())
+            .unwrap_or(g_transform)
+            .translation();
+        let fwd = (target.transform.translation - origin).normalize_or_zero();
+        if fwd == Vec3::ZERO {
+            continue;
+        }
+
+        let accuracy = accuracy.with_recoil(recoil);
+        let max_yaw = (stats.spread / accuracy.0.max(f32::EPSILON)).to_radians();
+        let yaw = rng
+            .fork(RNG_SPREAD)
+            .sample(Uniform::new_inclusive(-max_yaw, max_yaw));
         let mut bullet_transform =
             Transform::from_translation(origin).looking_to(fwd, fwd.any_orthogonal_vector());
-        bullet_transform.rotate(Quat::from_euler(
-            EulerRot::YXZ,
-            rand::thread_rng().sample(distr),
-            0.0,
-            0.0,
-        ));
-        commands.spawn((
-            SMGShot,
+        bullet_transform.rotate(Quat::from_rotation_y(yaw));
+
+        pool.acquire(&mut commands).insert((
             BulletProjectile,
             ProjectileBundle {
                 color: ProjectileColor::Orange,
                 damage: Damage {
                     ty: DamageVariant::Ballistic,
-                    value: 5.0,
-                    source: find_item_owner(*e_item, &parent_query),
+                    value: stats.damage,
+                    source: Some(equipped_to.map_or(e_item, |e| e.target)),
                 },
                 transform: bullet_transform.with_scale(Vec3::splat(0.15)),
-                velocity: Velocity::linear(bullet_transform.forward() * 64.0),
+                velocity: Velocity::linear(bullet_transform.forward() * stats.projectile_speed),
                 ccd: Ccd::enabled(),
-                collision_groups: damage_collision_groups.into(),
+                collision_groups: *groups,
                 ..Default::default()
             },
+            Knockback {
+                impulse: 0.5,
+                mode: KnockbackMode::Directional,
+            },
         ));
     }
 }
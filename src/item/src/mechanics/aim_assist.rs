@@ -0,0 +1,276 @@
+//! Bending the aim towards nearby enemies.
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy_rapier3d::prelude::*;
+use grin_damage::{
+    health::{Dead, Health},
+    hitbox::Hitbox,
+};
+use grin_input::camera::CameraAlignment;
+use grin_physics::CollisionGroupExt;
+
+/// Candidates that aren't the current `AimAssistLock` need to be this much closer
+/// to the aim ray to take over, so it doesn't flip-flop between two enemies.
+pub const AIM_ASSIST_STICKINESS: f32 = 0.5;
+
+pub struct AimAssistPlugin;
+
+impl Plugin for AimAssistPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AimAssist>()
+            .init_resource::<AimAssistLock>();
+    }
+}
+
+/// Settings for aim assist. Used by `set_local_mouse_target`.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct AimAssist {
+    pub enabled: bool,
+    /// Half-angle of the cone around the aim ray, in degrees.
+    pub cone_degrees: f32,
+    /// `cone_degrees` for `CameraAlignment::FortyFive`. Aiming with the cursor from up there is
+    /// a lot rougher.
+    pub forty_five_cone_degrees: f32,
+    /// `0.0` doesn't do anything, `1.0` aims right at the target.
+    pub strength: f32,
+    pub max_range: f32,
+}
+
+impl Default for AimAssist {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            cone_degrees: 8.0,
+            forty_five_cone_degrees: 15.0,
+            strength: 0.5,
+            max_range: 32.0,
+        }
+    }
+}
+
+impl AimAssist {
+    pub const OFF: Self = Self {
+        enabled: false,
+        cone_degrees: 0.0,
+        forty_five_cone_degrees: 0.0,
+        strength: 0.0,
+        max_range: 0.0,
+    };
+
+    /// Settings with the right `cone_degrees` for `alignment`.
+    pub fn for_alignment(&self, alignment: CameraAlignment) -> Self {
+        match alignment {
+            CameraAlignment::FortyFive => Self {
+                cone_degrees: self.forty_five_cone_degrees,
+                ..*self
+            },
+            CameraAlignment::Shooter { .. } => *self,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.enabled && self.strength > 0.0 && self.cone_degrees > 0.0 && self.max_range > 0.0
+    }
+}
+
+/// What aim assist locked onto last. It gets preference, to keep things stable.
+#[derive(Resource, Debug, Default)]
+pub struct AimAssistLock(pub Option<Entity>);
+
+#[derive(SystemParam)]
+pub struct AimAssistParams<'w, 's> {
+    pub settings: Res<'w, AimAssist>,
+    pub lock: ResMut<'w, AimAssistLock>,
+    pub rapier_context: Res<'w, RapierContext>,
+    pub hitbox_query: Query<'w, 's, &'static Hitbox>,
+    pub health_query: Query<'w, 's, Has<Dead>, With<Health>>,
+    pub parent_query: Query<'w, 's, &'static Parent>,
+    pub transform_query: Query<'w, 's, &'static GlobalTransform>,
+}
+
+impl<'w, 's> AimAssistParams<'w, 's> {
+    /// Bends `target` from `origin` towards the best living thing that `groups` could hit,
+    /// and updates the `AimAssistLock`. Returns `target` as is if there's nothing.
+    pub fn assist(
+        &mut self,
+        groups: CollisionGroups,
+        alignment: CameraAlignment,
+        origin: Vec3,
+        target: Vec3,
+    ) -> Vec3 {
+        let AimAssistParams {
+            settings,
+            lock,
+            rapier_context,
+            hitbox_query,
+            health_query,
+            parent_query,
+            transform_query,
+        } = self;
+        let settings = settings.for_alignment(alignment);
+
+        let Some(aim) = (target - origin).try_normalize() else {
+            return target;
+        };
+        if !settings.is_active() {
+            lock.0 = None;
+            return target;
+        }
+
+        let cone = settings.cone_degrees.to_radians();
+        let mut best = None::<(Entity, Vec3, f32)>;
+        rapier_context.intersections_with_shape(
+            origin,
+            Quat::IDENTITY,
+            &Collider::ball(settings.max_range),
+            QueryFilter::new().groups(groups),
+            |e_hit| {
+                // only living things
+                let e_hit_target = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+                let Some(e_owner) = std::iter::once(e_hit_target)
+                    .chain(parent_query.iter_ancestors(e_hit_target))
+                    .find(|&e| health_query.contains(e))
+                else {
+                    return true;
+                };
+                if health_query.get(e_owner).unwrap_or(true) {
+                    return true;
+                }
+
+                let Ok(g_transform) = transform_query.get(e_hit) else {
+                    return true;
+                };
+                let point = g_transform.translation();
+                let offset = point - origin;
+                let distance = offset.length();
+                if distance > settings.max_range || distance <= 0.0 {
+                    return true;
+                }
+
+                let mut angle = aim.angle_between(offset);
+                if angle > cone {
+                    return true;
+                }
+                if lock.0 == Some(e_owner) {
+                    angle *= AIM_ASSIST_STICKINESS;
+                }
+                if best.is_some_and(|(_, _, a)| a <= angle) {
+                    return true;
+                }
+
+                // can't aim through walls
+                let blocked = rapier_context
+                    .cast_ray(
+                        origin,
+                        offset / distance,
+                        distance,
+                        true,
+                        QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+                    )
+                    .is_some();
+                if !blocked {
+                    best = Some((e_owner, point, angle));
+                }
+                true
+            },
+        );
+
+        lock.0 = best.map(|(e, _, _)| e);
+        match best {
+            Some((_, point, _)) => target.lerp(point, settings.strength.clamp(0.0, 1.0)),
+            None => target,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::system::RunSystemOnce, render::mesh::MeshPlugin, scene::ScenePlugin, time::TimePlugin,
+    };
+    use grin_physics::CollisionGroupsExt;
+
+    use super::*;
+
+    fn enemy(app: &mut App, translation: Vec3) -> Entity {
+        app.world
+            .spawn((
+                Health(10.0),
+                Collider::ball(0.5),
+                CollisionGroups::from_group_default(Group::ENEMY),
+                TransformBundle::from_transform(Transform::from_translation(translation)),
+            ))
+            .id()
+    }
+
+    fn assist(app: &mut App, settings: AimAssist) -> Vec3 {
+        app.world.insert_resource(settings);
+        app.world.run_system_once(|mut params: AimAssistParams| {
+            params.assist(
+                CollisionGroups::from_group_default(Group::PLAYER_PROJECTILE),
+                CameraAlignment::SHOOTER,
+                Vec3::ZERO,
+                Vec3::NEG_Z * 10.0,
+            )
+        })
+    }
+
+    #[test]
+    fn aim_assist() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .add_plugins(AimAssistPlugin);
+
+        let e_left = enemy(&mut app, Vec3::new(-0.6, 0.0, -10.0));
+        let e_right = enemy(&mut app, Vec3::new(0.5, 0.0, -10.0));
+        // outside of the cone
+        enemy(&mut app, Vec3::new(10.0, 0.0, -10.0));
+
+        // colliders get added to the physics world here
+        app.update();
+
+        let hard_lock = AimAssist {
+            strength: 1.0,
+            ..Default::default()
+        };
+        assert_eq!(
+            assist(&mut app, hard_lock),
+            Vec3::new(0.5, 0.0, -10.0),
+            "Didn't lock onto the closest enemy."
+        );
+
+        // the right one's still ahead, just barely
+        app.world
+            .get_mut::<Transform>(e_left)
+            .unwrap()
+            .translation
+            .x = -0.45;
+        app.update();
+        assert_eq!(
+            app.world.resource::<AimAssistLock>().0,
+            Some(e_right),
+            "Lock changed without aim assist running."
+        );
+        assist(&mut app, hard_lock);
+        assert_eq!(
+            app.world.resource::<AimAssistLock>().0,
+            Some(e_right),
+            "Lock wasn't sticky."
+        );
+
+        app.world.entity_mut(e_right).insert(Dead);
+        assist(&mut app, hard_lock);
+        assert_eq!(
+            app.world.resource::<AimAssistLock>().0,
+            Some(e_left),
+            "Locked onto a dead enemy."
+        );
+
+        assert_eq!(
+            assist(&mut app, AimAssist::OFF),
+            Vec3::NEG_Z * 10.0,
+            "Aim assist wasn't disabled."
+        );
+    }
+}
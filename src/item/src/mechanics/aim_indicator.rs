@@ -0,0 +1,210 @@
+//! Showing where a weapon is pointed.
+
+use bevy::{pbr::NotShadowCaster, prelude::*, render::view::RenderLayers};
+use bevy_rapier3d::prelude::*;
+use grin_physics::CollisionGroupExt;
+use grin_render::{
+    beam::{render_beams, Beam, BeamBundle},
+    RenderLayer,
+};
+
+use super::{
+    firing::{Reloading, Target},
+    fx::Muzzle,
+    util::InputHandler,
+};
+
+pub struct AimIndicatorPlugin;
+
+impl Plugin for AimIndicatorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (init_aim_indicators, despawn_aim_indicators).before(render_beams),
+        );
+    }
+}
+
+/// Radius of `AimIndicatorStyle::Laser` beams.
+const LASER_RADIUS: f32 = 0.01;
+
+/// Radius of `AimIndicatorStyle::GroundReticle` circles.
+const RETICLE_RADIUS: f32 = 0.15;
+
+/// Lifts the reticle off whatever it's on, so it doesn't z-fight.
+const RETICLE_OFFSET: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AimIndicatorStyle {
+    /// A beam out of the muzzle.
+    #[default]
+    Laser,
+    /// A circle where the shot is going to land.
+    GroundReticle,
+}
+
+/// Shows where a weapon is pointed. Hidden while `Reloading` or without `InputHandler`.
+///
+/// Needs `update_aim_indicators::<T>`, which `FiringPlugin<T>` adds.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct AimIndicator {
+    pub style: AimIndicatorStyle,
+    pub color: Color,
+    /// Furthest the indicator reaches, if it doesn't hit anything first.
+    pub max_len: f32,
+}
+
+impl Default for AimIndicator {
+    fn default() -> Self {
+        Self {
+            style: AimIndicatorStyle::default(),
+            color: Color::RED,
+            max_len: 32.0,
+        }
+    }
+}
+
+/// What's drawn for an `AimIndicator`. Lives in world space, not under the item.
+#[derive(Component, Debug)]
+pub struct AimIndicatorVisual {
+    pub item: Entity,
+}
+
+pub fn init_aim_indicators(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    item_query: Query<(Entity, &AimIndicator), Added<AimIndicator>>,
+) {
+    for (e_item, indicator) in item_query.iter() {
+        let material = materials.add(StandardMaterial {
+            base_color: indicator.color,
+            unlit: true,
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        });
+        let mut e_visual_commands = commands.spawn((
+            AimIndicatorVisual { item: e_item },
+            NotShadowCaster,
+            // keeps it out of the avatar viewport
+            RenderLayers::layer(RenderLayer::STANDARD as u8),
+        ));
+        match indicator.style {
+            AimIndicatorStyle::Laser => {
+                e_visual_commands.insert(BeamBundle {
+                    beam: Beam::new(Vec3::ZERO, Vec3::NEG_Z, LASER_RADIUS),
+                    material,
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                });
+            }
+            AimIndicatorStyle::GroundReticle => {
+                e_visual_commands.insert(PbrBundle {
+                    mesh: meshes.add(Circle::new(RETICLE_RADIUS)),
+                    material,
+                    visibility: Visibility::Hidden,
+                    ..Default::default()
+                });
+            }
+        }
+    }
+}
+
+/// Moves `AimIndicator`s to this frame's `Target`. Runs after `set_local_mouse_target`.
+pub fn update_aim_indicators<T: Component>(
+    rapier_context: Res<RapierContext>,
+    item_query: Query<
+        (
+            &AimIndicator,
+            &Target,
+            &GlobalTransform,
+            Option<&Children>,
+            Has<InputHandler>,
+            Has<Reloading>,
+        ),
+        With<T>,
+    >,
+    muzzle_query: Query<&GlobalTransform, With<Muzzle>>,
+    mut visual_query: Query<(
+        &AimIndicatorVisual,
+        &mut Visibility,
+        &mut Transform,
+        Option<&mut Beam>,
+    )>,
+) {
+    for (visual, mut visibility, mut transform, beam) in visual_query.iter_mut() {
+        let Ok((indicator, target, g_transform, children, input, reloading)) =
+            item_query.get(visual.item)
+        else {
+            continue;
+        };
+
+        if !input || reloading {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        let origin = children
+            .into_iter()
+            .flatten()
+            .find_map(|&e| muzzle_query.get(e).ok())
+            .unwrap_or(g_transform)
+            .translation();
+        // the laser shows where the gun is actually pointing, the reticle shows where it's aimed
+        let (dir, max_len) = match indicator.style {
+            AimIndicatorStyle::Laser => (g_transform.forward(), indicator.max_len),
+            AimIndicatorStyle::GroundReticle => {
+                let offset = target.transform.translation - origin;
+                (
+                    offset.normalize_or_zero(),
+                    offset.length().min(indicator.max_len),
+                )
+            }
+        };
+        if dir == Vec3::ZERO {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+
+        let hit = rapier_context.cast_ray_and_get_normal(
+            origin,
+            dir,
+            max_len,
+            true,
+            QueryFilter::new().groups(CollisionGroups::new(
+                Group::all(),
+                Group::MAP | Group::ENEMY,
+            )),
+        );
+        let (end, normal) = match hit {
+            Some((_, intersection)) => (intersection.point, intersection.normal),
+            None => (origin + dir * max_len, Vec3::Y),
+        };
+
+        *visibility = Visibility::Inherited;
+        match beam {
+            Some(mut beam) => {
+                beam.origin = origin;
+                beam.target = end;
+            }
+            None => {
+                // `Circle` faces +Z
+                *transform = Transform::from_translation(end + normal * RETICLE_OFFSET)
+                    .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal.normalize_or_zero()));
+            }
+        }
+    }
+}
+
+/// Cleans up visuals once `AimIndicator` is gone.
+pub fn despawn_aim_indicators(
+    mut commands: Commands,
+    item_query: Query<(), With<AimIndicator>>,
+    visual_query: Query<(Entity, &AimIndicatorVisual)>,
+) {
+    for (e_visual, visual) in visual_query.iter() {
+        if !item_query.contains(visual.item) {
+            commands.entity(e_visual).despawn_recursive();
+        }
+    }
+}
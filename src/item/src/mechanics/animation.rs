@@ -1,23 +1,18 @@
-use std::time::Duration;
-
 use bevy::{ecs::system::SystemParam, prelude::*};
-use bevy_asset_loader::prelude::*;
-use grin_asset::AssetLoadState;
-use grin_rig::humanoid::{Humanoid, HumanoidDominantHand};
+use grin_asset::animation::{AnimationGraphAsset, IDLE_STATE};
+use grin_input::camera::CameraAlignmentChangedEvent;
+use grin_rig::{
+    humanoid::{Humanoid, HumanoidDominantHand},
+    AnimationGraph, PlayAnimationState,
+};
 
-use crate::equip::EquippedTo;
+use crate::equip::{Equipped, EquippedTo};
 
 use super::firing::Active;
 
-pub struct ItemAnimationPlugin;
-
-impl Plugin for ItemAnimationPlugin {
-    fn build(&self, app: &mut App) {
-        app.configure_loading_state(
-            LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<AimAssets>(),
-        );
-    }
-}
+pub const AIM_RANGED_SINGLE_LEFT_STATE: &str = "aim_ranged_single_l";
+pub const AIM_RANGED_SINGLE_RIGHT_STATE: &str = "aim_ranged_single_r";
+pub const AIM_RANGED_DUAL_STATE: &str = "aim_ranged_dual";
 
 /// Whether the item's aiming animation is playing.
 #[derive(Component, Debug, Copy, Clone, Eq, PartialEq, Default)]
@@ -31,81 +26,121 @@ pub enum IdleType {
     Idle,
 }
 
+impl IdleType {
+    pub fn state(&self) -> &'static str {
+        match self {
+            Self::Idle => IDLE_STATE,
+        }
+    }
+}
+
 /// Aim animation.
 #[derive(Component, Default)]
 pub enum AimType {
     #[default]
     RangedSingle,
+    /// Two one-handed guns. Falls back to `RangedSingle` if the graph doesn't have it.
+    RangedDual,
 }
 
-#[derive(Resource, AssetCollection)]
-pub struct AimAssets {
-    #[asset(key = "anim.idle")]
-    pub idle: Handle<AnimationClip>,
-    #[asset(key = "anim.pistol.right")]
-    pub ranged_single_rt: Handle<AnimationClip>,
-    #[asset(key = "anim.pistol.left")]
-    pub ranged_single_lt: Handle<AnimationClip>,
+impl AimType {
+    pub fn state(
+        &self,
+        dominant: HumanoidDominantHand,
+        graph: Option<&AnimationGraphAsset>,
+    ) -> &'static str {
+        let single = match dominant {
+            HumanoidDominantHand::Left => AIM_RANGED_SINGLE_LEFT_STATE,
+            HumanoidDominantHand::Right => AIM_RANGED_SINGLE_RIGHT_STATE,
+        };
+        match self {
+            Self::RangedSingle => single,
+            Self::RangedDual => {
+                match graph.is_some_and(|g| g.states.contains_key(AIM_RANGED_DUAL_STATE)) {
+                    true => AIM_RANGED_DUAL_STATE,
+                    false => single,
+                }
+            }
+        }
+    }
 }
 
 /// Plays the aim animations on `item::Active`.
+///
+/// With `AimType::RangedDual`, the animation is shared by both hands, so it only starts once.
 pub fn aim_on_active<T: Component>(
     mut commands: Commands,
-    assets: Res<AimAssets>,
+    graphs: Res<Assets<AnimationGraphAsset>>,
     item_query: Query<(Entity, &AimType), (With<T>, With<Active>, Without<Aiming>)>,
-    humanoid_query: Query<&HumanoidDominantHand>,
-    mut animator_query: Query<&mut AnimationPlayer>,
+    aiming_query: Query<(), With<Aiming>>,
+    humanoid_query: Query<(&Humanoid, Option<&AnimationGraph>, Option<&Equipped>)>,
     parent_query: Query<&Parent>,
 ) {
     for (e_item, aim_type) in item_query.iter() {
-        let dominant = parent_query
+        let Some((e_humanoid, (humanoid, graph, equipped))) = parent_query
             .iter_ancestors(e_item)
-            .find_map(|e| humanoid_query.get(e).ok())
-            .unwrap();
-
-        for e_parent in parent_query.iter_ancestors(e_item) {
-            let Ok(mut animator) = animator_query.get_mut(e_parent) else {
-                continue;
-            };
-
-            let clip = match aim_type {
-                AimType::RangedSingle => match dominant {
-                    HumanoidDominantHand::Left => &assets.ranged_single_lt,
-                    HumanoidDominantHand::Right => &assets.ranged_single_rt,
-                },
-            };
-
-            animator.play_with_transition(clip.clone(), Duration::from_secs_f32(0.1));
-            commands.entity(e_item).insert(Aiming);
-
-            break;
+            .find_map(|e| humanoid_query.get(e).ok().map(|q| (e, q)))
+        else {
+            continue;
+        };
+
+        let other_aiming = equipped
+            .and_then(|e| e.other_hand(e_item))
+            .is_some_and(|e| aiming_query.contains(e));
+        if !(matches!(aim_type, AimType::RangedDual) && other_aiming) {
+            let graph = graph.and_then(|g| graphs.get(&g.graph));
+            commands.entity(e_humanoid).insert(PlayAnimationState(
+                aim_type.state(humanoid.dominant_hand_type, graph),
+            ));
         }
+        commands.entity(e_item).insert(Aiming);
     }
 }
 
 /// Plays the un-aim animations on un-`item::Active`.
+///
+/// Waits for the other hand if it's still `item::Active`, since the aim animation is shared.
 pub fn unaim_on_unactive<T: Component>(
     mut commands: Commands,
-    assets: Res<AimAssets>,
     item_query: Query<(Entity, &IdleType), (With<T>, Without<Active>, With<Aiming>)>,
-    mut animator_query: Query<&mut AnimationPlayer>,
+    active_query: Query<(), With<Active>>,
+    humanoid_query: Query<Option<&Equipped>, With<Humanoid>>,
     parent_query: Query<&Parent>,
 ) {
     for (e_item, idle_type) in item_query.iter() {
-        for e_parent in parent_query.iter_ancestors(e_item) {
-            let Ok(mut animator) = animator_query.get_mut(e_parent) else {
-                continue;
-            };
-
-            let clip = match idle_type {
-                IdleType::Idle => &assets.idle,
-            };
+        let Some((e_humanoid, equipped)) = parent_query
+            .iter_ancestors(e_item)
+            .find_map(|e| humanoid_query.get(e).ok().map(|q| (e, q)))
+        else {
+            continue;
+        };
+
+        let other_active = equipped
+            .and_then(|e| e.other_hand(e_item))
+            .is_some_and(|e| active_query.contains(e));
+        if other_active {
+            continue;
+        }
 
-            animator.play_with_transition(clip.clone(), Duration::from_secs_f32(0.1));
-            commands.entity(e_item).remove::<Aiming>();
+        commands
+            .entity(e_humanoid)
+            .insert(PlayAnimationState(idle_type.state()));
+        commands.entity(e_item).remove::<Aiming>();
+    }
+}
 
-            break;
-        }
+/// The aim animations depend on the camera, so items that are mid-aim get to start over after it
+/// switches alignment. `aim_on_active` picks them back up.
+pub fn reaim_on_camera_alignment_change(
+    mut commands: Commands,
+    item_query: Query<Entity, (With<Active>, With<Aiming>)>,
+    mut alignment_events: EventReader<CameraAlignmentChangedEvent>,
+) {
+    if alignment_events.read().count() == 0 {
+        return;
+    }
+    for e_item in item_query.iter() {
+        commands.entity(e_item).remove::<Aiming>();
     }
 }
 
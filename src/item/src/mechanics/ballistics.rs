@@ -0,0 +1,164 @@
+//! Aiming lobbed projectiles.
+
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use grin_damage::projectiles::{launch_pitch, BallisticProfile};
+use grin_render::{
+    duoquad::{DuoQuad, DuoQuadBundle},
+    sketched::SketchMaterial,
+};
+
+use super::firing::Target;
+use crate::plugin::ItemSet;
+
+pub struct BallisticsPlugin;
+
+impl Plugin for BallisticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                aim_ballistic.after(ItemSet::Input).before(ItemSet::Fire),
+                (init_trajectories, draw_trajectories, despawn_trajectories).chain(),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Pitch to fire at when the `Target` is out of range. Goes as far as possible.
+const FALLBACK_PITCH: f32 = FRAC_PI_4;
+
+/// Number of `DuoQuad`s in a `ShowTrajectory` arc.
+const TRAJECTORY_SEGMENTS: usize = 16;
+
+/// Shortest arc to draw in seconds. `DuoQuad`s can't have zero length.
+const MIN_TRAJECTORY_DURATION: f32 = 0.1;
+
+/// Aims a lobbed projectile to land on the item's `Target`. `pitch` is updated by
+/// `aim_ballistic`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct BallisticAim {
+    /// Launch speed.
+    pub speed: f32,
+    pub profile: BallisticProfile,
+    /// Launch pitch in radians.
+    pub pitch: f32,
+}
+
+impl BallisticAim {
+    pub fn new(speed: f32, profile: BallisticProfile) -> Self {
+        Self {
+            speed,
+            profile,
+            pitch: FALLBACK_PITCH,
+        }
+    }
+
+    /// Launch velocity from `origin` toward `target`, at `pitch`.
+    pub fn launch_velocity(&self, origin: Vec3, target: Vec3) -> Vec3 {
+        let dir = (target - origin).xz().normalize_or_zero();
+        let (sin, cos) = self.pitch.sin_cos();
+        Vec3::new(dir.x * cos, sin, dir.y * cos) * self.speed
+    }
+
+    /// Position `t` seconds after launching from `origin` toward `target`. Ignores drag.
+    pub fn position_at(&self, origin: Vec3, target: Vec3, gravity: f32, t: f32) -> Vec3 {
+        origin + self.launch_velocity(origin, target) * t
+            - Vec3::Y * gravity * self.profile.gravity_scale * t * t / 2.0
+    }
+}
+
+/// Draws the predicted arc of a `BallisticAim`.
+#[derive(Component, Clone, Debug)]
+pub struct ShowTrajectory {
+    pub radius: f32,
+    pub material: Handle<SketchMaterial>,
+}
+
+/// One piece of a `ShowTrajectory` arc.
+#[derive(Component, Debug)]
+pub struct TrajectorySegment {
+    pub item: Entity,
+    pub index: usize,
+}
+
+/// Updates `BallisticAim.pitch` to land on `Target`, after it's set by e.g.
+/// `set_local_mouse_target`. Falls back to 45 degrees when out of range.
+pub fn aim_ballistic(
+    rapier_config: Res<RapierConfiguration>,
+    mut item_query: Query<(&mut BallisticAim, &Target, &GlobalTransform)>,
+) {
+    let gravity = rapier_config.gravity.length();
+    for (mut aim, target, g_transform) in item_query.iter_mut() {
+        let offset = target.transform.translation - g_transform.translation();
+        aim.pitch = launch_pitch(offset, aim.speed, gravity * aim.profile.gravity_scale)
+            .unwrap_or(FALLBACK_PITCH);
+    }
+}
+
+pub fn init_trajectories(
+    mut commands: Commands,
+    item_query: Query<(Entity, &ShowTrajectory), Added<ShowTrajectory>>,
+) {
+    for (e_item, show) in item_query.iter() {
+        for index in 0..TRAJECTORY_SEGMENTS {
+            commands.spawn((
+                TrajectorySegment {
+                    item: e_item,
+                    index,
+                },
+                DuoQuadBundle {
+                    duoquad: DuoQuad {
+                        radius: show.radius,
+                        ..Default::default()
+                    },
+                    material: show.material.clone(),
+                    ..Default::default()
+                },
+            ));
+        }
+    }
+}
+
+pub fn draw_trajectories(
+    rapier_config: Res<RapierConfiguration>,
+    item_query: Query<(&BallisticAim, &Target, &GlobalTransform), With<ShowTrajectory>>,
+    mut segment_query: Query<(&TrajectorySegment, &mut DuoQuad)>,
+) {
+    let gravity = rapier_config.gravity.length();
+    for (segment, mut duoquad) in segment_query.iter_mut() {
+        let Ok((aim, target, g_transform)) = item_query.get(segment.item) else {
+            continue;
+        };
+
+        let origin = g_transform.translation();
+        let target = target.transform.translation;
+        // time to cover the horizontal distance
+        let horizontal_speed = aim.speed * aim.pitch.cos();
+        let duration = match horizontal_speed > f32::EPSILON {
+            true => (target - origin).xz().length() / horizontal_speed,
+            false => 0.0,
+        }
+        .max(MIN_TRAJECTORY_DURATION);
+        let dt = duration / TRAJECTORY_SEGMENTS as f32;
+        let t = dt * segment.index as f32;
+        duoquad.origin = aim.position_at(origin, target, gravity, t);
+        duoquad.target = aim.position_at(origin, target, gravity, t + dt);
+    }
+}
+
+/// Cleans up arcs once `ShowTrajectory` is gone.
+pub fn despawn_trajectories(
+    mut commands: Commands,
+    item_query: Query<(), With<ShowTrajectory>>,
+    segment_query: Query<(Entity, &TrajectorySegment)>,
+) {
+    for (e_segment, segment) in segment_query.iter() {
+        if !item_query.contains(segment.item) {
+            commands.entity(e_segment).despawn_recursive();
+        }
+    }
+}
@@ -1,12 +1,28 @@
 use std::{marker::PhantomData, time::Duration};
 
-use bevy::{prelude::*, utils::HashSet};
+use bevy::{ecs::entity::EntityHashSet, prelude::*, utils::HashSet};
 use bevy_enum_filter::{Enum, EnumFilter};
-use grin_input::camera::{CameraAlignment, LookInfo};
+use bevy_rapier3d::prelude::*;
+use grin_damage::{
+    faction::{Faction, FriendlyFirePolicy},
+    health::{DamageBuffer, Health},
+    hit::{credit_damage_owner, Damage},
+    hitbox::Hitbox,
+};
+use grin_input::camera::{CameraAlignment, CameraRecoil, LookInfo};
 use grin_physics::PhysicsTime;
+use grin_render::beam::{render_beams, HitscanBeamEvent};
 use grin_time::scaling::TimeScale;
+use grin_util::rng::{GrinRng, RNG_RECOIL, RNG_SPREAD};
+use rand::{distributions::Uniform, Rng};
 
-use super::fx::MuzzleFlashEvent;
+use crate::stats::ItemStats;
+
+use super::{
+    aim_indicator::update_aim_indicators,
+    fx::{Muzzle, MuzzleFlashEvent},
+    util::{set_local_mouse_target, InputHandler},
+};
 
 /// Commonly used for AI or weapon targetting.
 #[derive(Component, Debug, Copy, Clone)]
@@ -49,6 +65,96 @@ impl Target {
 #[component(storage = "SparseSet")]
 pub struct Active;
 
+/// Ammo accounting. Each shot uses one round from `clip`.
+///
+/// The default clip is `u32::MAX`, for weapons that don't really use ammo.
+#[derive(Component, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Ammo {
+    /// Rounds left before reloading.
+    pub clip: u32,
+    pub clip_size: u32,
+    /// Rounds left to reload with. `None` is infinite.
+    pub reserve: Option<u32>,
+}
+
+impl Default for Ammo {
+    fn default() -> Self {
+        Self::new(u32::MAX, None)
+    }
+}
+
+impl Ammo {
+    /// Starts with a full clip.
+    pub fn new(clip_size: u32, reserve: Option<u32>) -> Self {
+        Self {
+            clip: clip_size,
+            clip_size,
+            reserve,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clip == 0
+    }
+
+    /// Whether reloading would do anything.
+    pub fn can_reload(&self) -> bool {
+        self.clip < self.clip_size && self.reserve != Some(0)
+    }
+
+    /// Uses a round. Returns `false` if the clip is empty.
+    pub fn consume(&mut self) -> bool {
+        match self.clip.checked_sub(1) {
+            Some(clip) => {
+                self.clip = clip;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Fills the clip from the reserve.
+    pub fn reload(&mut self) {
+        let missing = self.clip_size - self.clip;
+        let refill = match self.reserve {
+            Some(ref mut reserve) => {
+                let refill = missing.min(*reserve);
+                *reserve -= refill;
+                refill
+            }
+            None => missing,
+        };
+        self.clip += refill;
+    }
+}
+
+/// Seconds to reload.
+#[derive(Component, Debug, Copy, Clone)]
+pub struct ReloadTime(pub Duration);
+
+impl Default for ReloadTime {
+    fn default() -> Self {
+        Self(Duration::from_millis(1500))
+    }
+}
+
+/// Requests a reload. Players get this from `insert_on_key::<T, Reload>`.
+#[derive(Component, Debug, Copy, Clone, Default)]
+#[component(storage = "SparseSet")]
+pub struct Reload;
+
+/// The item is reloading and can't be used. Removed when the timer finishes.
+#[derive(Component, Debug, Clone)]
+#[component(storage = "SparseSet")]
+pub struct Reloading(pub Timer);
+
+/// Sent whenever `Ammo` changes. For the HUD.
+#[derive(Event, Debug, Copy, Clone)]
+pub struct AmmoChangedEvent {
+    pub entity: Entity,
+    pub ammo: Ammo,
+}
+
 /// For most items, affects the accuracy of projectiles in different ways. A higher number is better.
 ///
 /// `1.0` is the default. Can't go below zero.
@@ -67,6 +173,85 @@ impl From<f32> for Accuracy {
     }
 }
 
+impl Accuracy {
+    /// Rotates `dir` randomly, up to `max_degrees / accuracy` on each axis.
+    pub fn spread(&self, dir: Vec3, max_degrees: f32, rng: &mut impl Rng) -> Vec3 {
+        let max = (max_degrees / self.0.max(f32::EPSILON)).to_radians();
+        let distr = Uniform::new_inclusive(-max, max);
+        Quat::from_euler(EulerRot::XYZ, rng.sample(distr), rng.sample(distr), 0.0).mul_vec3(dir)
+    }
+
+    /// What it is right now, with the `Recoil` that's built up.
+    pub fn with_recoil(&self, recoil: Option<&Recoil>) -> Self {
+        Self(self.0 * recoil.map_or(1.0, Recoil::accuracy_multiplier))
+    }
+}
+
+/// Spread of a `FireMode::Hitscan` shot at `Accuracy(1.0)`, in degrees, for items without `ItemStats`.
+const HITSCAN_SPREAD_DEGREES: f32 = 1.0;
+
+/// What a shot actually does. `FiringMode` is how the trigger works, this is what comes out.
+#[derive(Component, Debug, Copy, Clone, Default, PartialEq)]
+pub enum FireMode {
+    /// The item spawns its own projectiles on `ShotFired`.
+    #[default]
+    Projectile,
+    /// Instant ray from the `Muzzle` to the `Target`. Damage comes from the item's `Damage`.
+    Hitscan {
+        max_range: f32,
+        /// Keeps going through everything that can take damage. Still stops at the map.
+        pierce: bool,
+    },
+}
+
+/// `Accuracy` is multiplied by this much at max `Recoil`.
+const RECOIL_ACCURACY_PENALTY: f32 = 0.5;
+
+/// Kick from firing. `x` is yaw and `y` is pitch, in radians.
+///
+/// Each shot pushes `accumulated` up to `max_accumulation`, and it eases back to zero over time.
+/// The more that's built up, the worse the `Accuracy` at fire time (`Accuracy::with_recoil`).
+/// Items with an `InputHandler` also kick the camera.
+#[derive(Component, Debug, Copy, Clone, PartialEq)]
+pub struct Recoil {
+    /// Pitch always goes up. Yaw is random, anywhere in `-x..=x`.
+    pub per_shot_kick: Vec2,
+    /// How fast `accumulated` decays, per second.
+    pub recovery_rate: f32,
+    pub max_accumulation: Vec2,
+    pub accumulated: Vec2,
+}
+
+impl Recoil {
+    pub fn new(per_shot_kick: Vec2, recovery_rate: f32, max_accumulation: Vec2) -> Self {
+        Self {
+            per_shot_kick,
+            recovery_rate,
+            max_accumulation,
+            accumulated: Vec2::ZERO,
+        }
+    }
+
+    pub fn kick(&mut self, rng: &mut impl Rng) {
+        let yaw = rng.gen_range(-self.per_shot_kick.x..=self.per_shot_kick.x);
+        self.accumulated = (self.accumulated + Vec2::new(yaw, self.per_shot_kick.y))
+            .clamp(-self.max_accumulation, self.max_accumulation);
+    }
+
+    pub fn recover(&mut self, dt: f32) {
+        self.accumulated *= (-self.recovery_rate * dt).exp();
+    }
+
+    /// Multiplier for `Accuracy`, from `1.0` with no recoil down to `1.0 - RECOIL_ACCURACY_PENALTY`.
+    pub fn accuracy_multiplier(&self) -> f32 {
+        let saturation = (self.accumulated.abs()
+            / self.max_accumulation.max(Vec2::splat(f32::EPSILON)))
+        .max_element()
+        .min(1.0);
+        1.0 - RECOIL_ACCURACY_PENALTY * saturation
+    }
+}
+
 #[derive(Component, Debug, Default, Hash, Eq, PartialEq)]
 pub enum FiringBehavior {
     #[default]
@@ -80,6 +265,35 @@ pub enum FiringSet {
     Effects,
 }
 
+/// Reloading for everything with `Ammo`.
+pub struct AmmoPlugin;
+
+impl Plugin for AmmoPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AmmoChangedEvent>().add_systems(
+            Update,
+            (start_reloads, strip_active_while_reloading, tick_reloads)
+                .chain()
+                .before(FiringSet::Fire),
+        );
+    }
+}
+
+/// Recovery and side effects for everything with `Recoil`. Kicks come from `FiringPlugin`.
+pub struct RecoilPlugin;
+
+impl Plugin for RecoilPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GrinRng>().add_systems(
+            Update,
+            (
+                recover_recoil.before(FiringSet::Fire),
+                apply_recoil.after(FiringSet::Fire),
+            ),
+        );
+    }
+}
+
 #[derive(Default)]
 pub struct FiringPlugin<T: Component> {
     pub supported_modes: HashSet<FiringBehavior>,
@@ -89,7 +303,19 @@ pub struct FiringPlugin<T: Component> {
 impl<T: Component> Plugin for FiringPlugin<T> {
     fn build(&self, app: &mut App) {
         app.add_event::<ShotFired<T>>()
-            .add_systems(Update, (send_muzzle_flash::<T>, step_cooldowns::<T>));
+            .init_resource::<GrinRng>()
+            .add_systems(
+                Update,
+                (
+                    send_muzzle_flash::<T>,
+                    step_cooldowns::<T>,
+                    kick_recoil::<T>.after(FiringSet::Fire).before(apply_recoil),
+                    fire_hitscan::<T>.after(FiringSet::Fire),
+                    update_aim_indicators::<T>
+                        .after(set_local_mouse_target::<T>)
+                        .before(render_beams),
+                ),
+            );
 
         for ty in &self.supported_modes {
             match ty {
@@ -200,13 +426,19 @@ pub fn step_cooldowns<T: Component>(
 
 pub fn semi_fire<T: Component>(
     mut query: Query<
-        (Entity, &mut ShotCooldown, &FireRate),
-        (With<T>, With<Enum!(FiringMode::SemiAuto)>, Added<Active>),
+        (Entity, &mut ShotCooldown, &FireRate, Option<&mut Ammo>),
+        (
+            With<T>,
+            With<Enum!(FiringMode::SemiAuto)>,
+            Added<Active>,
+            Without<Reloading>,
+        ),
     >,
     mut shot_events: EventWriter<ShotFired<T>>,
+    mut ammo_events: EventWriter<AmmoChangedEvent>,
 ) {
-    for (entity, mut cooldown, fire_rate) in query.iter_mut() {
-        if cooldown.ready() {
+    for (entity, mut cooldown, fire_rate, ammo) in query.iter_mut() {
+        if cooldown.ready() && try_consume_ammo(entity, ammo, &mut ammo_events) {
             shot_events.send(ShotFired {
                 entity,
                 phantom_data: PhantomData::default(),
@@ -224,18 +456,26 @@ pub fn auto_fire<T: Component>(
             &FireRate,
             Option<&Active>,
             &mut FiringMode,
+            Option<&mut Ammo>,
+            Has<Reloading>,
         ),
         (With<T>, With<Enum!(FiringMode::Auto)>),
     >,
     mut shot_events: EventWriter<ShotFired<T>>,
     mut shots_began: EventWriter<ShotsBegan<T>>,
     mut shots_ended: EventWriter<ShotsEnded<T>>,
+    mut ammo_events: EventWriter<AmmoChangedEvent>,
 ) {
-    for (entity, mut cooldown, fire_rate, active, mut firing_mode) in query.iter_mut() {
+    for (entity, mut cooldown, fire_rate, active, mut firing_mode, ammo, reloading) in
+        query.iter_mut()
+    {
         let FiringMode::Auto { ref mut firing } = *firing_mode else {
             continue;
         };
 
+        let empty = ammo.as_ref().is_some_and(|ammo| ammo.is_empty());
+        let active = active.filter(|_| !reloading && !empty);
+
         if active.is_some() && !*firing && cooldown.ready() {
             *firing = true;
             shots_began.send(ShotsBegan {
@@ -250,7 +490,7 @@ pub fn auto_fire<T: Component>(
             });
         }
 
-        if *firing && cooldown.ready() {
+        if *firing && cooldown.ready() && try_consume_ammo(entity, ammo, &mut ammo_events) {
             shot_events.send(ShotFired {
                 entity,
                 phantom_data: PhantomData::default(),
@@ -260,6 +500,240 @@ pub fn auto_fire<T: Component>(
     }
 }
 
+/// Uses a round if there's `Ammo`. Returns `false` if the clip is empty.
+fn try_consume_ammo(
+    entity: Entity,
+    ammo: Option<Mut<Ammo>>,
+    ammo_events: &mut EventWriter<AmmoChangedEvent>,
+) -> bool {
+    let Some(mut ammo) = ammo else {
+        return true;
+    };
+    if !ammo.consume() {
+        return false;
+    }
+    ammo_events.send(AmmoChangedEvent {
+        entity,
+        ammo: *ammo,
+    });
+    true
+}
+
+/// Starts reloading on `Reload` or an empty clip.
+pub fn start_reloads(
+    mut commands: Commands,
+    item_query: Query<(Entity, &Ammo, Option<&ReloadTime>, Has<Reload>), Without<Reloading>>,
+) {
+    for (entity, ammo, reload_time, reload) in item_query.iter() {
+        if !(reload || ammo.is_empty()) || !ammo.can_reload() {
+            continue;
+        }
+
+        let duration = reload_time.copied().unwrap_or_default().0;
+        commands
+            .entity(entity)
+            .insert(Reloading(Timer::new(duration, TimerMode::Once)))
+            .remove::<Reload>();
+    }
+}
+
+/// `insert_on_lmb` keeps putting `Active` back, so it's taken off every frame.
+pub fn strip_active_while_reloading(
+    mut commands: Commands,
+    item_query: Query<Entity, (With<Reloading>, With<Active>)>,
+) {
+    for entity in item_query.iter() {
+        commands.entity(entity).remove::<Active>();
+    }
+}
+
+pub fn tick_reloads(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut item_query: Query<(Entity, &mut Reloading, &mut Ammo, Option<&TimeScale>)>,
+    mut ammo_events: EventWriter<AmmoChangedEvent>,
+) {
+    for (entity, mut reloading, mut ammo, time_scale) in item_query.iter_mut() {
+        let dt = time.0.delta().mul_f32(time_scale.map_or(1.0, f32::from));
+        if reloading.0.tick(dt).finished() {
+            ammo.reload();
+            commands.entity(entity).remove::<(Reloading, Reload)>();
+            ammo_events.send(AmmoChangedEvent {
+                entity,
+                ammo: *ammo,
+            });
+        }
+    }
+}
+
+/// Resolves `FireMode::Hitscan` shots. Damage goes straight into the `DamageBuffer`.
+pub fn fire_hitscan<T: Component>(
+    rapier_context: Res<RapierContext>,
+    policy: Res<FriendlyFirePolicy>,
+    item_query: Query<
+        (
+            &FireMode,
+            &Target,
+            &Accuracy,
+            &Damage,
+            &GlobalTransform,
+            Option<&CollisionGroups>,
+            Option<&Children>,
+            Option<&ItemStats>,
+            Option<&Recoil>,
+        ),
+        With<T>,
+    >,
+    muzzle_query: Query<&GlobalTransform, With<Muzzle>>,
+    hitbox_query: Query<&Hitbox>,
+    mut hit_query: Query<&mut DamageBuffer>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
+    faction_query: Query<&Faction>,
+    mut shots_fired: EventReader<ShotFired<T>>,
+    mut beam_events: EventWriter<HitscanBeamEvent>,
+    mut rng: ResMut<GrinRng>,
+) {
+    for &ShotFired { entity: e_item, .. } in shots_fired.read() {
+        let Ok((
+            &FireMode::Hitscan { max_range, pierce },
+            target,
+            accuracy,
+            &damage,
+            g_transform,
+            groups,
+            children,
+            stats,
+            recoil,
+        )) = item_query.get(e_item)
+        else {
+            continue;
+        };
+
+        let origin = children
+            .into_iter()
+            .flatten()
+            .find_map(|&e| muzzle_query.get(e).ok())
+            .unwrap_or(g_transform)
+            .translation();
+        let dir = (target.transform.translation - origin).normalize_or_zero();
+        if dir == Vec3::ZERO {
+            continue;
+        }
+        let spread = stats.map_or(HITSCAN_SPREAD_DEGREES, |stats| stats.spread);
+        let dir = accuracy
+            .with_recoil(recoil)
+            .spread(dir, spread, rng.fork(RNG_SPREAD));
+        let filter = QueryFilter::new().groups(groups.copied().unwrap_or_default());
+
+        let mut hits = Vec::new();
+        match pierce {
+            true => rapier_context.intersections_with_ray(
+                origin,
+                dir,
+                max_range,
+                true,
+                filter,
+                |e_hit, intersection| {
+                    hits.push((e_hit, intersection.toi));
+                    true
+                },
+            ),
+            false => hits.extend(
+                rapier_context
+                    .cast_ray_and_get_normal(origin, dir, max_range, true, filter)
+                    .map(|(e_hit, intersection)| (e_hit, intersection.toi)),
+            ),
+        }
+        hits.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+        let damage = Damage {
+            source: credit_damage_owner(damage.source, Some(e_item), &parent_query, &owner_query),
+            ..damage
+        };
+
+        // each target only gets hit once, even if the ray goes through a few of its hitboxes
+        let mut damaged = EntityHashSet::default();
+        let mut distance = max_range;
+        for (e_hit, toi) in hits {
+            let e_target = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+            let Ok(mut damage_buf) = hit_query.get_mut(e_target) else {
+                // probably the map
+                distance = toi;
+                break;
+            };
+            if !pierce {
+                distance = toi;
+            }
+            if !damaged.insert(e_target) {
+                continue;
+            }
+
+            let Some(multiplier) = policy.multiplier(damage.source, e_target, &faction_query)
+            else {
+                continue;
+            };
+            let damage = Damage {
+                value: damage.value * multiplier,
+                ..damage
+            };
+            damage_buf.0.push(damage);
+            info!(
+                msg="Pushed hitscan damage.",
+                dealer=?e_item,
+                receiver=?e_hit,
+                dmg=?damage,
+            );
+        }
+
+        beam_events.send(HitscanBeamEvent {
+            from: origin,
+            to: origin + dir * distance,
+        });
+    }
+}
+
+pub fn kick_recoil<T: Component>(
+    mut rng: ResMut<GrinRng>,
+    mut item_query: Query<&mut Recoil, With<T>>,
+    mut shots_fired: EventReader<ShotFired<T>>,
+) {
+    for ShotFired { entity, .. } in shots_fired.read() {
+        if let Ok(mut recoil) = item_query.get_mut(*entity) {
+            recoil.kick(rng.fork(RNG_RECOIL));
+        }
+    }
+}
+
+pub fn recover_recoil(
+    time: Res<PhysicsTime>,
+    mut item_query: Query<(&mut Recoil, Option<&TimeScale>)>,
+) {
+    for (mut recoil, time_scale) in item_query.iter_mut() {
+        let dt = time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        if dt > 0.0 && recoil.accumulated != Vec2::ZERO {
+            recoil.recover(dt);
+        }
+    }
+}
+
+/// Kicks the camera by the built up `Recoil` of player items.
+pub fn apply_recoil(
+    item_query: Query<(&Recoil, Has<InputHandler>)>,
+    camera_recoil: Option<ResMut<CameraRecoil>>,
+) {
+    let mut camera_offset = Vec2::ZERO;
+    for (recoil, player) in item_query.iter() {
+        if player {
+            camera_offset += recoil.accumulated;
+        }
+    }
+
+    if let Some(mut camera_recoil) = camera_recoil {
+        camera_recoil.0 = camera_offset;
+    }
+}
+
 pub fn send_muzzle_flash<T: Component>(
     mut shots_fired: EventReader<ShotFired<T>>,
     mut muzzle_flash_events: EventWriter<MuzzleFlashEvent>,
@@ -320,3 +794,233 @@ pub fn play_sfx_continuous<T: Component>(
         commands.get_or_spawn(*entity).remove::<AudioBundle>();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy_enum_filter::prelude::AddEnumFilter;
+
+    use super::*;
+
+    #[derive(Component)]
+    struct Gun;
+
+    fn ammo_app() -> App {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_enum_filter::<FiringMode>()
+            .add_event::<ShotFired<Gun>>()
+            .add_plugins(AmmoPlugin)
+            .add_systems(Update, semi_fire::<Gun>.in_set(FiringSet::Fire));
+        app
+    }
+
+    fn spawn_gun(app: &mut App, ammo: Ammo) -> Entity {
+        app.world
+            .spawn((
+                Gun,
+                ammo,
+                ReloadTime(Duration::from_secs(1)),
+                ShotCooldown::default(),
+                FireRate::default(),
+                FiringMode::SemiAuto,
+            ))
+            .id()
+    }
+
+    #[test]
+    fn empty_clip() {
+        let mut app = ammo_app();
+        let e_gun = spawn_gun(
+            &mut app,
+            Ammo {
+                clip: 0,
+                clip_size: 10,
+                reserve: Some(0),
+            },
+        );
+        app.world.entity_mut(e_gun).insert(Active);
+        app.update();
+
+        assert!(
+            app.world.resource::<Events<ShotFired<Gun>>>().is_empty(),
+            "Fired with an empty clip."
+        );
+        assert!(
+            app.world.get::<Reloading>(e_gun).is_none(),
+            "Reloaded with an empty reserve."
+        );
+    }
+
+    #[test]
+    fn reload() {
+        let mut app = ammo_app();
+        let e_gun = spawn_gun(
+            &mut app,
+            Ammo {
+                clip: 0,
+                clip_size: 10,
+                reserve: Some(15),
+            },
+        );
+        app.update();
+        assert!(
+            app.world.get::<Reloading>(e_gun).is_some(),
+            "Didn't reload on an empty clip."
+        );
+
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_secs(1));
+        app.update();
+
+        assert_eq!(
+            app.world.get::<Ammo>(e_gun).copied(),
+            Some(Ammo {
+                clip: 10,
+                clip_size: 10,
+                reserve: Some(5),
+            }),
+            "Reload didn't refill the clip from the reserve."
+        );
+        assert!(
+            app.world.get::<Reloading>(e_gun).is_none(),
+            "Still reloading."
+        );
+    }
+
+    fn hitscan_damage(pierce: bool) -> [f32; 2] {
+        let mut app = App::new();
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1.0,
+                substeps: 1,
+            },
+            ..Default::default()
+        })
+        .add_plugins((
+            bevy::time::TimePlugin,
+            AssetPlugin::default(),
+            bevy::render::mesh::MeshPlugin,
+            bevy::scene::ScenePlugin,
+        ))
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .init_resource::<FriendlyFirePolicy>()
+        .insert_resource(GrinRng::new(0))
+        .add_event::<ShotFired<Gun>>()
+        .add_event::<HitscanBeamEvent>()
+        .add_systems(Update, fire_hitscan::<Gun>);
+
+        let e_gun = app
+            .world
+            .spawn((
+                Gun,
+                FireMode::Hitscan {
+                    max_range: 20.0,
+                    pierce,
+                },
+                Target::from_pair(Vec3::ZERO, Vec3::new(10.0, 0.0, 0.0)),
+                // no spread
+                Accuracy(f32::MAX),
+                Damage {
+                    value: 10.0,
+                    ..Default::default()
+                },
+                TransformBundle::default(),
+            ))
+            .id();
+        let dummies = [4.0, 8.0].map(|x| {
+            let transform = Transform::from_xyz(x, 0.0, 0.0);
+            app.world
+                .spawn((
+                    Collider::ball(0.5),
+                    TransformBundle {
+                        local: transform,
+                        global: transform.into(),
+                    },
+                    DamageBuffer::default(),
+                ))
+                .id()
+        });
+
+        // colliders get added to the physics world here
+        app.update();
+        app.world.send_event(ShotFired::<Gun> {
+            entity: e_gun,
+            phantom_data: PhantomData,
+        });
+        app.update();
+
+        dummies.map(|e| {
+            app.world
+                .get::<DamageBuffer>(e)
+                .unwrap()
+                .0
+                .iter()
+                .map(|d| d.value)
+                .sum()
+        })
+    }
+
+    #[test]
+    fn hitscan() {
+        assert_eq!(
+            hitscan_damage(false),
+            [10.0, 0.0],
+            "Hitscan didn't stop at the first target."
+        );
+        assert_eq!(hitscan_damage(true), [10.0, 10.0], "Hitscan didn't pierce.");
+    }
+
+    #[test]
+    fn recoil() {
+        const SEED: u64 = 7;
+        const SHOTS: usize = 10;
+
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .init_resource::<CameraRecoil>()
+            .insert_resource(GrinRng::new(SEED))
+            .add_event::<ShotFired<Gun>>()
+            .add_plugins(RecoilPlugin)
+            .add_systems(Update, kick_recoil::<Gun>.before(apply_recoil));
+
+        let recoil = Recoil::new(Vec2::new(0.01, 0.02), 4.0, Vec2::new(0.05, 0.15));
+        let e_gun = app.world.spawn((Gun, recoil, Accuracy(2.0))).id();
+        for _ in 0..SHOTS {
+            app.world.send_event(ShotFired::<Gun> {
+                entity: e_gun,
+                phantom_data: PhantomData,
+            });
+            app.update();
+        }
+
+        let mut rng = GrinRng::new(SEED);
+        let yaw = (0..SHOTS).fold(0.0_f32, |yaw, _| {
+            (yaw + rng.fork(RNG_RECOIL).gen_range(-0.01..=0.01)).clamp(-0.05, 0.05)
+        });
+        let recoil = app.world.get::<Recoil>(e_gun).unwrap();
+        assert_eq!(
+            recoil.accumulated,
+            Vec2::new(yaw, 0.15),
+            "Recoil didn't build up to the cap."
+        );
+        assert_eq!(recoil.accuracy_multiplier(), 0.5);
+        let accuracy = app.world.get::<Accuracy>(e_gun).unwrap();
+        assert_eq!(
+            *accuracy,
+            Accuracy(2.0),
+            "Recoil overwrote the item's accuracy."
+        );
+        assert_eq!(
+            accuracy.with_recoil(Some(recoil)),
+            Accuracy(1.0),
+            "Accuracy wasn't penalized by recoil."
+        );
+        assert_eq!(
+            app.world.resource::<CameraRecoil>().0,
+            Vec2::ZERO,
+            "AI weapon kicked the camera."
+        );
+    }
+}
@@ -1,26 +1,33 @@
 use bevy::{
-    ecs::system::SystemParam, pbr::CubemapVisibleEntities, prelude::*,
+    ecs::system::SystemParam, gltf::GltfNode, pbr::CubemapVisibleEntities, prelude::*,
     render::primitives::CubemapFrusta,
 };
 use bevy_asset_loader::prelude::*;
 use bevy_rapier3d::plugin::RapierContext;
 use grin_asset::AssetLoadState;
-use grin_damage::hit::DamageEvent;
+use grin_damage::hit::{try_find_deepest_contact_point, DamageEvent};
 use grin_render::sketched::SketchMaterial;
 
-use super::util::try_find_deepest_contact_point;
-
 pub struct ItemFxPlugin;
 
 impl Plugin for ItemFxPlugin {
     fn build(&self, app: &mut App) {
         app.add_event::<MuzzleFlashEvent>()
+            .init_resource::<MuzzleFlashSettings>()
             .configure_loading_state(
                 LoadingStateConfig::new(AssetLoadState::Loading)
                     .load_collection::<Sfx>()
                     .load_collection::<ProjectileAssets>(),
             )
-            .add_systems(Update, (fade_muzzle_flashes, ignite_muzzle_flashes).chain());
+            .add_systems(
+                Update,
+                (
+                    fade_muzzle_flashes,
+                    ignite_muzzle_flashes,
+                    snuff_extra_muzzle_flashes,
+                )
+                    .chain(),
+            );
     }
 }
 
@@ -28,6 +35,9 @@ impl Plugin for ItemFxPlugin {
 pub struct ProjectileAssets {
     #[asset(key = "mesh.gun")]
     pub gun: Handle<Mesh>,
+    /// `Socket.Muzzle` on the gun model.
+    #[asset(key = "node.gun.muzzle", optional)]
+    pub gun_muzzle: Option<Handle<GltfNode>>,
     #[asset(key = "mesh.bullet_5cm")]
     pub bullet_5cm: Handle<Mesh>,
     #[asset(key = "mesh.bullet_8cm")]
@@ -51,11 +61,26 @@ pub struct Sfx {
 #[derive(Component, Default)]
 pub struct Muzzle;
 
+/// Lots of guns firing at once means lots of point lights, so this caps how many can be lit.
+#[derive(Resource)]
+pub struct MuzzleFlashSettings {
+    /// Max number of lit flashes. The oldest ones get snuffed first.
+    pub max_lit: usize,
+}
+
+impl Default for MuzzleFlashSettings {
+    fn default() -> Self {
+        Self { max_lit: 8 }
+    }
+}
+
 #[derive(Component)]
 pub struct MuzzleFlash {
     pub color: Color,
     pub intensity: f32,
     pub fade_time: f32,
+    /// Whether the light casts shadows. Expensive.
+    pub shadows: bool,
 }
 
 impl Default for MuzzleFlash {
@@ -64,11 +89,20 @@ impl Default for MuzzleFlash {
             color: Color::ORANGE,
             intensity: 800.0,
             fade_time: 0.08,
+            shadows: false,
         }
     }
 }
 
-#[derive(Bundle, Default)]
+/// A `MuzzleFlash` that's currently lit. Only these get faded.
+#[derive(Component, Debug)]
+#[component(storage = "SparseSet")]
+pub struct FlashActive {
+    /// `Time.elapsed_seconds` when it was lit.
+    pub lit_at: f32,
+}
+
+#[derive(Bundle)]
 pub struct MuzzleFlashBundle {
     pub flash: MuzzleFlash,
     pub point_light: PointLight,
@@ -76,6 +110,22 @@ pub struct MuzzleFlashBundle {
     pub cubemap_frusta: CubemapFrusta,
 }
 
+impl Default for MuzzleFlashBundle {
+    fn default() -> Self {
+        Self {
+            flash: MuzzleFlash::default(),
+            // off until it's fired
+            point_light: PointLight {
+                intensity: 0.0,
+                shadows_enabled: false,
+                ..Default::default()
+            },
+            cubemap_visible_entities: CubemapVisibleEntities::default(),
+            cubemap_frusta: CubemapFrusta::default(),
+        }
+    }
+}
+
 #[derive(Bundle, Default)]
 pub struct MuzzleBundle {
     pub muzzle: Muzzle,
@@ -91,26 +141,63 @@ pub struct MuzzleBundle {
 pub struct MuzzleFlashEvent(pub Entity);
 
 pub fn fade_muzzle_flashes(
-    mut flash_query: Query<(&MuzzleFlash, &mut PointLight)>,
+    mut commands: Commands,
+    mut flash_query: Query<(Entity, &MuzzleFlash, &mut PointLight), With<FlashActive>>,
     time: Res<Time>,
 ) {
-    for (flash, mut point_light) in flash_query.iter_mut() {
+    for (entity, flash, mut point_light) in flash_query.iter_mut() {
         point_light.intensity = (point_light.intensity
             - (flash.intensity / flash.fade_time) * time.delta_seconds())
         .max(0.0);
+        if point_light.intensity <= 0.0 {
+            commands.entity(entity).remove::<FlashActive>();
+        }
     }
 }
 
+/// Lights up the `MuzzleFlash` on the event's entity, or on its children if it's an item.
 pub fn ignite_muzzle_flashes(
+    mut commands: Commands,
     mut flash_query: Query<(&MuzzleFlash, &mut PointLight)>,
+    children_query: Query<&Children>,
     mut events: EventReader<MuzzleFlashEvent>,
+    time: Res<Time>,
 ) {
-    for MuzzleFlashEvent(entity) in events.read() {
-        let Ok((flash, mut point_light)) = flash_query.get_mut(*entity) else {
-            return;
+    for &MuzzleFlashEvent(e_source) in events.read() {
+        let Some(entity) = std::iter::once(e_source)
+            .chain(children_query.get(e_source).into_iter().flatten().copied())
+            .find(|&e| flash_query.contains(e))
+        else {
+            continue;
+        };
+        let Ok((flash, mut point_light)) = flash_query.get_mut(entity) else {
+            continue;
         };
         point_light.color = flash.color;
         point_light.intensity = flash.intensity;
+        point_light.shadows_enabled = flash.shadows;
+        commands.entity(entity).insert(FlashActive {
+            lit_at: time.elapsed_seconds(),
+        });
+    }
+}
+
+/// Puts out the oldest flashes past `MuzzleFlashSettings.max_lit`.
+pub fn snuff_extra_muzzle_flashes(
+    mut commands: Commands,
+    settings: Res<MuzzleFlashSettings>,
+    mut flash_query: Query<(Entity, &FlashActive, &mut PointLight)>,
+) {
+    let count = flash_query.iter().len();
+    if count <= settings.max_lit {
+        return;
+    }
+
+    let mut flashes = flash_query.iter_mut().collect::<Vec<_>>();
+    flashes.sort_by(|(_, a, _), (_, b, _)| a.lit_at.total_cmp(&b.lit_at));
+    for (entity, _, mut point_light) in flashes.into_iter().take(count - settings.max_lit) {
+        point_light.intensity = 0.0;
+        commands.entity(entity).remove::<FlashActive>();
     }
 }
 
@@ -1,4 +1,40 @@
-use bevy::prelude::*;
+use std::ops::Range;
+
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use bevy_rapier3d::prelude::*;
+use grin_damage::{
+    debug::{hitbox_debug_color, hitbox_debug_enabled, HITBOX_DEBUG_ALPHA},
+    health::{Health, Invulnerable},
+    hit::{find_damage_owner, ContactDamage, Damage, DamageEvent},
+    hitbox::Hitbox,
+    impact::Impact,
+    plugin::DamageSet,
+    status::Stagger,
+};
+use grin_physics::PhysicsTime;
+use grin_rig::humanoid::Dash;
+use grin_time::scaling::TimeScale;
+
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<MeleeHitEvent>()
+            .add_event::<ChargeChangedEvent>()
+            .add_event::<ChargeReleasedEvent>()
+            .add_event::<ChargeCancelledEvent>()
+            .add_systems(
+                Update,
+                (
+                    (cancel_charges, update_charge_levels, release_charges).chain(),
+                    (init_melee_swings, sweep_melee_swings)
+                        .chain()
+                        .before(DamageSet::Add),
+                    draw_melee_swings.run_if(hitbox_debug_enabled),
+                ),
+            );
+    }
+}
 
 #[derive(Component)]
 pub struct SingleGrip {
@@ -51,10 +87,74 @@ pub struct Winding {
     pub duration: f32,
 }
 
+/// Charges up the adjacent `ChargeLevel` while it's here. Removing it releases the charge.
+///
+/// Players get this from `insert_on_lmb`. AI can insert and remove it directly.
 #[derive(Component, Default)]
 #[component(storage = "SparseSet")]
 pub struct Charging;
 
+/// Marks a melee weapon that's been charged all the way.
+#[derive(Component, Default)]
+#[component(storage = "SparseSet")]
+pub struct FullyCharged;
+
+/// How charged up a melee attack is.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ChargeLevel {
+    /// Seconds charged, up to `charge_time`.
+    pub charge: f32,
+    /// Seconds to reach full charge.
+    pub charge_time: f32,
+    /// Damage multiplier at full charge. No charge is `1.0`.
+    pub max_multiplier: f32,
+}
+
+impl ChargeLevel {
+    pub fn new(charge_time: f32, max_multiplier: f32) -> Self {
+        Self {
+            charge: 0.0,
+            charge_time,
+            max_multiplier,
+        }
+    }
+
+    pub fn progress(&self) -> f32 {
+        match self.charge_time > 0.0 {
+            true => (self.charge / self.charge_time).clamp(0.0, 1.0),
+            false => 1.0,
+        }
+    }
+
+    pub fn multiplier(&self) -> f32 {
+        1.0 + (self.max_multiplier - 1.0) * self.progress()
+    }
+}
+
+/// Sent whenever a `ChargeLevel` changes. For the HUD.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ChargeChangedEvent {
+    pub entity: Entity,
+    /// `ChargeLevel::progress`.
+    pub progress: f32,
+}
+
+/// Sent when `Charging` is removed from something with charge.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ChargeReleasedEvent {
+    pub entity: Entity,
+    /// `ChargeLevel::progress` at release.
+    pub progress: f32,
+    /// `ChargeLevel::multiplier` at release.
+    pub multiplier: f32,
+}
+
+/// Sent when a charge gets interrupted.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct ChargeCancelledEvent {
+    pub entity: Entity,
+}
+
 #[derive(Component, Default)]
 #[component(storage = "SparseSet")]
 pub struct Swinging {
@@ -70,3 +170,346 @@ pub fn update_hammer_winds(time: Res<Time>, mut wind_query: Query<(&mut Wind, Op
         }
     }
 }
+
+/// Drops the charge when the owner dashes or gets staggered. The charge is lost.
+pub fn cancel_charges(
+    mut commands: Commands,
+    mut charge_query: Query<(Entity, &mut ChargeLevel), With<Charging>>,
+    parent_query: Query<&Parent>,
+    interrupt_query: Query<(), Or<(With<Dash>, With<Stagger>)>>,
+    mut changed_events: EventWriter<ChargeChangedEvent>,
+    mut cancelled_events: EventWriter<ChargeCancelledEvent>,
+) {
+    for (entity, mut charge) in charge_query.iter_mut() {
+        let interrupted = parent_query
+            .iter_ancestors(entity)
+            .any(|e| interrupt_query.contains(e));
+        // if the button is still held it gets reinserted, so only cancel once
+        if !interrupted || charge.charge <= 0.0 {
+            continue;
+        }
+
+        charge.charge = 0.0;
+        commands.entity(entity).remove::<(Charging, FullyCharged)>();
+        changed_events.send(ChargeChangedEvent {
+            entity,
+            progress: 0.0,
+        });
+        cancelled_events.send(ChargeCancelledEvent { entity });
+    }
+}
+
+pub fn update_charge_levels(
+    time: Res<PhysicsTime>,
+    mut charge_query: Query<
+        (Entity, &mut ChargeLevel, Option<&TimeScale>),
+        (With<Charging>, Without<Swinging>),
+    >,
+    parent_query: Query<&Parent>,
+    interrupt_query: Query<(), Or<(With<Dash>, With<Stagger>)>>,
+    mut changed_events: EventWriter<ChargeChangedEvent>,
+) {
+    for (entity, mut charge, time_scale) in charge_query.iter_mut() {
+        if parent_query
+            .iter_ancestors(entity)
+            .any(|e| interrupt_query.contains(e))
+        {
+            continue;
+        }
+
+        let prev = charge.charge;
+        charge.charge = (charge.charge
+            + time.0.delta_seconds() * time_scale.map_or(1.0, f32::from))
+        .min(charge.charge_time);
+        if charge.charge != prev {
+            changed_events.send(ChargeChangedEvent {
+                entity,
+                progress: charge.progress(),
+            });
+        }
+    }
+}
+
+/// Sends `ChargeReleasedEvent` and resets the charge once `Charging` is gone.
+pub fn release_charges(
+    mut commands: Commands,
+    mut charge_query: Query<(Entity, &mut ChargeLevel), Without<Charging>>,
+    mut changed_events: EventWriter<ChargeChangedEvent>,
+    mut released_events: EventWriter<ChargeReleasedEvent>,
+) {
+    for (entity, mut charge) in charge_query.iter_mut() {
+        if charge.charge <= 0.0 {
+            continue;
+        }
+
+        released_events.send(ChargeReleasedEvent {
+            entity,
+            progress: charge.progress(),
+            multiplier: charge.multiplier(),
+        });
+        charge.charge = 0.0;
+        commands.entity(entity).remove::<FullyCharged>();
+        changed_events.send(ChargeChangedEvent {
+            entity,
+            progress: 0.0,
+        });
+    }
+}
+
+/// Degrees between each shape sampled along a `MeleeSwing` arc.
+const SWEEP_STEP_DEGREES: f32 = 10.0;
+
+/// A melee attack that doesn't depend on animated colliders. While `active_window` is running,
+/// this sweeps out an arc in front of the item's owner, right to left.
+///
+/// Hits go out as `DamageEvent::Contact` from the item, so `damage` gets inserted onto the item
+/// as a `Damage`. Both are removed after `duration`.
+#[derive(Component, Debug, Clone)]
+pub struct MeleeSwing {
+    /// Centered on the owner's forward direction.
+    pub arc_degrees: f32,
+    /// Reach from the owner.
+    pub radius: f32,
+    /// Seconds.
+    pub duration: f32,
+    /// Seconds into the swing where it can hit things.
+    pub active_window: Range<f32>,
+    pub damage: Damage,
+    /// Seconds since the swing started.
+    pub elapsed: f32,
+    /// Everything already hit by this swing. Targets are `Hitbox` targets, so each thing gets
+    /// hit once.
+    pub hits: EntityHashSet,
+    /// Waits for `open_window` instead of starting `active_window` on time.
+    pub gated: bool,
+}
+
+impl MeleeSwing {
+    pub fn new(
+        arc_degrees: f32,
+        radius: f32,
+        duration: f32,
+        active_window: Range<f32>,
+        damage: Damage,
+    ) -> Self {
+        Self {
+            arc_degrees,
+            radius,
+            duration,
+            active_window,
+            damage,
+            elapsed: 0.0,
+            hits: EntityHashSet::default(),
+            gated: false,
+        }
+    }
+
+    /// For swings timed by the animation's `EnableHitbox` and `DisableHitbox` markers.
+    /// `active_window` is only used for its length until then.
+    pub fn gated(mut self) -> Self {
+        self.gated = true;
+        self
+    }
+
+    /// Starts the active window now. It's as long as it was before.
+    pub fn open_window(&mut self) {
+        let Range { start, end } = self.active_window;
+        self.active_window = self.elapsed..self.elapsed + (end - start);
+        self.gated = false;
+    }
+
+    /// Ends the active window now.
+    pub fn close_window(&mut self) {
+        self.active_window.end = self.active_window.end.min(self.elapsed);
+    }
+}
+
+/// Sent when a `MeleeSwing` hits something.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct MeleeHitEvent {
+    pub e_item: Entity,
+    pub e_hit: Entity,
+    /// Roughly where the swing touched it.
+    pub point: Vec3,
+}
+
+pub fn init_melee_swings(
+    mut commands: Commands,
+    swing_query: Query<(Entity, &MeleeSwing), Added<MeleeSwing>>,
+) {
+    for (e_item, swing) in swing_query.iter() {
+        commands.entity(e_item).insert(swing.damage);
+    }
+}
+
+/// Checks the part of the `MeleeSwing` arc covered this frame for hits.
+///
+/// The arc is approximated with balls reaching from the owner to `radius`, every
+/// `SWEEP_STEP_DEGREES`, filtered by the item's `CollisionGroups`.
+pub fn sweep_melee_swings(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    rapier_context: Res<RapierContext>,
+    mut swing_query: Query<(
+        Entity,
+        &mut MeleeSwing,
+        Option<&CollisionGroups>,
+        Option<&TimeScale>,
+    )>,
+    transform_query: Query<&GlobalTransform>,
+    hitbox_query: Query<&Hitbox>,
+    invulnerable_query: Query<&Invulnerable>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut hit_events: EventWriter<MeleeHitEvent>,
+) {
+    for (e_item, mut swing, groups, time_scale) in swing_query.iter_mut() {
+        let t0 = swing.elapsed;
+        swing.elapsed += time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        let t1 = swing.elapsed;
+        if t1 >= swing.duration {
+            commands.entity(e_item).remove::<(MeleeSwing, Damage)>();
+        }
+        if swing.gated {
+            continue;
+        }
+
+        // the part of this frame inside the active window
+        let Range { start, end } = swing.active_window;
+        let (t0, t1) = (t0.max(start), t1.min(end));
+        if t0 > t1 {
+            continue;
+        }
+
+        let e_owner = find_damage_owner(e_item, &parent_query, &owner_query).unwrap_or(e_item);
+        let Ok(g_owner_transform) = transform_query.get(e_owner) else {
+            continue;
+        };
+        let origin = g_owner_transform.translation();
+        let forward = g_owner_transform.forward();
+        let up = g_owner_transform.up();
+
+        // right to left
+        let arc = swing.arc_degrees.to_radians();
+        let angle = |t: f32| match end > start {
+            true => arc * ((t - start) / (end - start) - 0.5),
+            false => 0.0,
+        };
+        let (a0, a1) = (angle(t0), angle(t1));
+        let steps = ((a1 - a0) / SWEEP_STEP_DEGREES.to_radians())
+            .ceil()
+            .max(1.0) as usize;
+
+        let ball_radius = swing.radius / 2.0;
+        let shape = Collider::ball(ball_radius);
+        let filter = QueryFilter::new()
+            .groups(groups.copied().unwrap_or_default())
+            .exclude_collider(e_item)
+            .exclude_rigid_body(e_owner);
+
+        let mut hits = Vec::new();
+        for i in 0..=steps {
+            let a = a0 + (a1 - a0) * i as f32 / steps as f32;
+            let center = origin + Quat::from_axis_angle(up, a) * forward * ball_radius;
+            rapier_context.intersections_with_shape(
+                center,
+                Quat::IDENTITY,
+                &shape,
+                filter,
+                |e_hit| {
+                    let e_target = hitbox_query.get(e_hit).map_or(e_hit, |h| h.target);
+                    if e_target != e_owner && swing.hits.insert(e_target) {
+                        hits.push((e_hit, e_target, center));
+                    }
+                    true
+                },
+            );
+        }
+
+        for (e_hit, e_target, center) in hits {
+            let invulnerable = invulnerable_query.get(e_target).ok();
+            if invulnerable.is_some_and(|i| !i.send_absorbed) {
+                continue;
+            }
+
+            // closest point on the ball to whatever got hit
+            let point = transform_query.get(e_hit).map_or(center, |g| {
+                center + (g.translation() - center).clamp_length_max(ball_radius)
+            });
+
+            debug!(
+                msg="Melee swing hit.",
+                dealer=?e_item,
+                receiver=?e_hit,
+            );
+            damage_events.send(DamageEvent::Contact {
+                kind: ContactDamage::FollowThrough,
+                e_damage: e_item,
+                e_hit,
+                absorbed: invulnerable.is_some(),
+            });
+            hit_events.send(MeleeHitEvent {
+                e_item,
+                e_hit,
+                point,
+            });
+        }
+    }
+}
+
+/// Draws the whole `MeleeSwing` arc while it can hit things, and a line where it is right now.
+pub fn draw_melee_swings(
+    mut gizmos: Gizmos,
+    swing_query: Query<(Entity, &MeleeSwing, Option<&CollisionGroups>)>,
+    transform_query: Query<&GlobalTransform>,
+    parent_query: Query<&Parent>,
+    owner_query: Query<(), With<Health>>,
+) {
+    for (e_item, swing, groups) in swing_query.iter() {
+        if swing.gated || !swing.active_window.contains(&swing.elapsed) {
+            continue;
+        }
+
+        let e_owner = find_damage_owner(e_item, &parent_query, &owner_query).unwrap_or(e_item);
+        let Ok(g_owner_transform) = transform_query.get(e_owner) else {
+            continue;
+        };
+        let origin = g_owner_transform.translation();
+        let forward = g_owner_transform.forward();
+        let up = g_owner_transform.up();
+        let color = hitbox_debug_color(groups, false);
+
+        let arc = swing.arc_degrees.to_radians();
+        let point = |a: f32| origin + Quat::from_axis_angle(up, a) * forward * swing.radius;
+        let steps = (swing.arc_degrees / SWEEP_STEP_DEGREES).ceil().max(1.0) as usize;
+        let edge = (0..=steps).map(|i| point(arc * (i as f32 / steps as f32 - 0.5)));
+        gizmos.linestrip(
+            std::iter::once(origin)
+                .chain(edge)
+                .chain(std::iter::once(origin)),
+            color.with_a(HITBOX_DEBUG_ALPHA),
+        );
+
+        let Range { start, end } = swing.active_window;
+        let progress = (swing.elapsed - start) / (end - start);
+        gizmos.line(origin, point(arc * (progress - 0.5)), color);
+    }
+}
+
+/// Spawns the piped `Impact` wherever a `MeleeSwing` from a `T` lands.
+pub fn spawn_melee_impacts<T: Component>(
+    In(impact): In<Impact>,
+    mut commands: Commands,
+    item_query: Query<(), With<T>>,
+    mut hit_events: EventReader<MeleeHitEvent>,
+) {
+    for MeleeHitEvent { e_item, point, .. } in hit_events.read() {
+        if item_query.contains(*e_item) {
+            commands.spawn((
+                impact.clone(),
+                TransformBundle::from_transform(Transform::from_translation(*point)),
+            ));
+        }
+    }
+}
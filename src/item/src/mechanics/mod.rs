@@ -1,4 +1,7 @@
+pub mod aim_assist;
+pub mod aim_indicator;
 pub mod animation;
+pub mod ballistics;
 pub mod combo;
 pub mod firing;
 pub mod fx;
@@ -1,11 +1,20 @@
-use bevy::{ecs::query::QueryEntityError, prelude::*};
+use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
-use grin_damage::hit::DamageEvent;
-use grin_input::camera::{LookInfo, PlayerCamera};
+use grin_damage::status::Stunned;
+use grin_input::{
+    action::InputAction,
+    camera::{LookInfo, PlayerCamera},
+};
+use grin_physics::{CollisionGroupExt, CollisionGroupsExt};
+use grin_time::GlobalRewind;
 
-use crate::equip::{Equipped, SlotAlignment};
+use crate::equip::{Equipped, MainHand, OffHand, SlotAlignment};
 
-use super::firing::Target;
+use super::{
+    aim_assist::AimAssistParams,
+    animation::Aiming,
+    firing::{Active, Target},
+};
 
 /// Returns the first ancestor with an `Equipped` component.
 pub fn find_item_owner(
@@ -16,22 +25,38 @@ pub fn find_item_owner(
 }
 
 /// On `(With<InputHandler>, With<T>)`,
-/// sets the `Target` component to the user's mouse position.
+/// sets the `Target` component to the user's mouse position, bent by `AimAssist`.
 pub fn set_local_mouse_target<T: Component>(
     camera_query: Query<&PlayerCamera>,
-    mut item_query: Query<(&mut Target, &GlobalTransform), (With<InputHandler>, With<T>)>,
+    mut item_query: Query<
+        (&mut Target, &GlobalTransform, Option<&CollisionGroups>),
+        (With<InputHandler>, With<T>),
+    >,
     look_info: Res<LookInfo>,
+    mut aim_assist: AimAssistParams,
 ) {
     let Ok(camera) = camera_query.get_single() else {
         return;
     };
 
-    for (mut target, g_transform) in item_query.iter_mut() {
-        *target = Target::from_camera(
+    for (mut target, g_transform, groups) in item_query.iter_mut() {
+        let origin = g_transform.translation();
+        let raw_target = Target::from_camera(
             &g_transform.compute_transform(),
             camera.alignment,
             &look_info,
         );
+        // only things that the item could actually hit
+        let groups = groups
+            .copied()
+            .unwrap_or_else(|| CollisionGroups::from_group_default(Group::PLAYER_PROJECTILE));
+        let assisted = aim_assist.assist(
+            groups,
+            camera.alignment,
+            origin,
+            raw_target.transform.translation,
+        );
+        *target = Target::from_pair(origin, assisted);
     }
 }
 
@@ -40,16 +65,50 @@ pub fn set_local_mouse_target<T: Component>(
 #[component(storage = "SparseSet")]
 pub struct InputHandler;
 
+/// `InputHandler` taken away while the owner is `Stunned`, or while a `GlobalRewindEvent` is going.
+/// It comes back when that's over. Whatever it was doing (`Active`, `Aiming`) gets dropped.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct SuppressedInput;
+
+pub fn suppress_stunned_input(
+    mut commands: Commands,
+    global_rewind: Option<Res<GlobalRewind>>,
+    owner_query: Query<(&Equipped, Has<Stunned>)>,
+    item_query: Query<(Has<InputHandler>, Has<SuppressedInput>)>,
+) {
+    let rewinding = global_rewind.is_some_and(|r| r.is_active());
+    for (equipped, stunned) in owner_query.iter() {
+        let stunned = stunned || rewinding;
+        for e_item in [equipped.left, equipped.right] {
+            let Ok((input, suppressed)) = item_query.get(e_item) else {
+                continue;
+            };
+            if stunned && input {
+                commands
+                    .entity(e_item)
+                    .remove::<(InputHandler, Active, Aiming)>()
+                    .insert(SuppressedInput);
+            } else if !stunned && suppressed {
+                commands
+                    .entity(e_item)
+                    .remove::<SuppressedInput>()
+                    .insert(InputHandler);
+            }
+        }
+    }
+}
+
 /// On `(With<InputHandler>, With<T>)`,
-/// - If `mouse_button` is pressed, inserts `C`.
-/// - If `mouse_button` is not pressed, removes `C`.
-pub fn insert_on_mouse_button<C: Component + Default>(
+/// - If `action` is pressed, inserts `C`.
+/// - If `action` is not pressed, removes `C`.
+pub fn insert_on_action<C: Component + Default>(
     commands: &mut Commands,
     entities: impl Iterator<Item = Entity>,
-    mouse_buttons: &ButtonInput<MouseButton>,
-    mouse_button: MouseButton,
+    actions: &ButtonInput<InputAction>,
+    action: InputAction,
 ) {
-    if mouse_buttons.pressed(mouse_button) {
+    if actions.pressed(action) {
         for entity in entities {
             commands.entity(entity).insert(C::default());
         }
@@ -61,34 +120,65 @@ pub fn insert_on_mouse_button<C: Component + Default>(
 }
 
 /// On `(With<InputHandler>, With<T>)`,
-/// - If LMB is pressed, inserts `C`.
-/// - If LMB is not pressed, removes `C`.
+/// - If LMB (or right trigger) is pressed, inserts `C`.
+/// - If LMB (or right trigger) is not pressed, removes `C`.
 pub fn insert_on_lmb<T: Component, C: Component + Default>(
     mut commands: Commands,
     query: Query<Entity, (With<T>, With<InputHandler>)>,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    actions: Res<ButtonInput<InputAction>>,
 ) {
-    insert_on_mouse_button::<C>(
-        &mut commands,
-        query.iter(),
-        &mouse_buttons,
-        MouseButton::Left,
-    );
+    insert_on_action::<C>(&mut commands, query.iter(), &actions, InputAction::Primary);
+}
+
+/// On `(With<InputHandler>, With<T>)`,
+/// - If `action` is pressed, inserts `C`.
+/// - If `action` is not pressed, removes `C`.
+///
+/// For anything other than LMB/RMB, e.g. `insert_on_key::<T, Reload>(InputAction::Reload)`.
+pub fn insert_on_key<T: Component, C: Component + Default>(
+    action: InputAction,
+) -> impl FnMut(Commands, Query<Entity, (With<T>, With<InputHandler>)>, Res<ButtonInput<InputAction>>)
+{
+    move |mut commands, query, actions| {
+        insert_on_action::<C>(&mut commands, query.iter(), &actions, action);
+    }
 }
 
 /// On `(With<InputHandler>, With<T>)`,
-/// - If RMB is pressed, inserts `C`.
-/// - If RMB is not pressed, removes `C`.
+/// - If RMB (or right bumper) is pressed, inserts `C`.
+/// - If RMB (or right bumper) is not pressed, removes `C`.
 pub fn insert_on_rmb<T: Component, C: Component + Default>(
     mut commands: Commands,
     query: Query<Entity, (With<T>, With<InputHandler>)>,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    actions: Res<ButtonInput<InputAction>>,
 ) {
-    insert_on_mouse_button::<C>(
+    insert_on_action::<C>(
         &mut commands,
         query.iter(),
-        &mouse_buttons,
-        MouseButton::Right,
+        &actions,
+        InputAction::Secondary,
+    );
+}
+
+/// On `(With<InputHandler>, With<T>)`, `insert_on_lmb` for the `MainHand` item and
+/// `insert_on_rmb` for the `OffHand` one. For dual wielding.
+pub fn insert_on_hand_buttons<T: Component, C: Component + Default>(
+    mut commands: Commands,
+    main_query: Query<Entity, (With<T>, With<MainHand>, With<InputHandler>)>,
+    off_query: Query<Entity, (With<T>, With<OffHand>, With<InputHandler>)>,
+    actions: Res<ButtonInput<InputAction>>,
+) {
+    insert_on_action::<C>(
+        &mut commands,
+        main_query.iter(),
+        &actions,
+        InputAction::Primary,
+    );
+    insert_on_action::<C>(
+        &mut commands,
+        off_query.iter(),
+        &actions,
+        InputAction::Secondary,
     );
 }
 
@@ -101,9 +191,9 @@ pub fn insert_on_rmb<T: Component, C: Component + Default>(
 pub fn insert_on_hmb<T: Component, C: Component + Default>(
     mut commands: Commands,
     query: Query<(Entity, &SlotAlignment), (With<T>, With<InputHandler>)>,
-    mouse_buttons: Res<ButtonInput<MouseButton>>,
+    actions: Res<ButtonInput<InputAction>>,
 ) {
-    insert_on_mouse_button::<C>(
+    insert_on_action::<C>(
         &mut commands,
         query
             .iter()
@@ -127,48 +217,15 @@ pub fn insert_on_hmb<T: Component, C: Component + Default>(
             .filter_map(|(e, h)| {
                 matches!(h, SlotAlignment::Left | SlotAlignment::Double).then_some(e)
             }),
-        &mouse_buttons,
-        MouseButton::Left,
+        &actions,
+        InputAction::Primary,
     );
-    insert_on_mouse_button::<C>(
+    insert_on_action::<C>(
         &mut commands,
         query.iter().filter_map(|(e, h)| {
             matches!(h, SlotAlignment::Right | SlotAlignment::Double).then_some(e)
         }),
-        &mouse_buttons,
-        MouseButton::Right,
+        &actions,
+        InputAction::Secondary,
     );
 }
-
-#[derive(Debug)]
-pub enum DamageContactError {
-    EventMismatch(DamageEvent),
-    ItemQueryMismatch(QueryEntityError),
-    NoContactPair(Entity, Entity),
-    NoContact(Entity, Entity),
-}
-
-/// Helper function for finding a collision point.
-pub fn try_find_deepest_contact_point(
-    damage_event: &DamageEvent,
-    rapier_context: &RapierContext,
-    item_query: &Query<&GlobalTransform>,
-) -> Result<Vec3, DamageContactError> {
-    let &DamageEvent::Contact {
-        e_damage, e_hit, ..
-    } = damage_event
-    else {
-        return Err(DamageContactError::EventMismatch(damage_event.clone()));
-    };
-    let g_item_transform = item_query
-        .get(e_damage)
-        .map_err(|e| DamageContactError::ItemQueryMismatch(e))?;
-    let contact_pair = rapier_context
-        .contact_pair(e_hit, e_damage)
-        .ok_or(DamageContactError::NoContactPair(e_damage, e_hit))?;
-    let contact = contact_pair
-        .find_deepest_contact()
-        .ok_or(DamageContactError::NoContact(e_damage, e_hit))?;
-    let contact_point = g_item_transform.transform_point(contact.1.local_p1());
-    Ok(contact_point)
-}
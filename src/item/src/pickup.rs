@@ -0,0 +1,315 @@
+//! Dropping items on the ground and picking them back up.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use grin_input::{
+    action::InputAction,
+    interact::{InteractEvent, Interactable, InteractionKind},
+};
+use grin_physics::{CollisionGroupExt, CollisionGroupsExt};
+use grin_rig::{humanoid::Humanoid, socket::AttachmentSockets};
+use grin_time::{CommandsExt, TimeParent};
+
+use crate::{
+    equip::{
+        grip, EmptyHand, Equipped, EquippedTo, ItemEquipEventSlot, MainHand, Models, SlotAlignment,
+        UntypedItemEquipEvent,
+    },
+    inventory::{draw, Inventory},
+    mechanics::{
+        animation::Aiming,
+        firing::Active,
+        util::{InputHandler, SuppressedInput},
+    },
+};
+
+pub struct PickupPlugin;
+
+impl Plugin for PickupPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ItemDropEvent>()
+            .add_event::<ItemPickupEvent>()
+            // in case `InteractPlugin` isn't around
+            .add_event::<InteractEvent>()
+            .add_systems(
+                Update,
+                (
+                    drop_on_input,
+                    drop_items,
+                    interact_pick_up_items,
+                    pick_up_items,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// How close something needs to be to pick up a dropped item.
+pub const PICKUP_RADIUS: f32 = 1.5;
+
+/// Half extents of the collider for dropped items that don't have one.
+const DROPPED_ITEM_HALF_EXTENTS: Vec3 = Vec3::new(0.2, 0.1, 0.4);
+
+/// Drops an item on the ground.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ItemDropEvent {
+    pub item: Entity,
+}
+
+/// Picks up a dropped item.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct ItemPickupEvent {
+    pub item: Entity,
+    pub new_owner: Entity,
+}
+
+/// An item on the ground. Remembers what the item looked like when it was held, so picking it up
+/// can put it back.
+#[derive(Component, Debug)]
+pub struct Dropped {
+    /// Entity with the `PickupSensor`.
+    pub sensor: Entity,
+    pub transform: Transform,
+    pub rigid_body: Option<RigidBody>,
+    pub collision_groups: Option<CollisionGroups>,
+    /// Whether the collider was added on drop.
+    pub added_collider: bool,
+}
+
+/// Sensor around a dropped item. Anything in range can pick it up.
+#[derive(Component, Debug)]
+pub struct PickupSensor {
+    pub item: Entity,
+}
+
+/// Returns the closest dropped item in pickup range of `point`.
+pub fn find_pickup(
+    rapier_context: &RapierContext,
+    point: Vec3,
+    sensor_query: &Query<(&PickupSensor, &GlobalTransform)>,
+) -> Option<Entity> {
+    let mut closest = None::<(Entity, f32)>;
+    rapier_context.intersections_with_point(
+        point,
+        QueryFilter::new().predicate(&|e| sensor_query.contains(e)),
+        |e_sensor| {
+            let (sensor, g_transform) = sensor_query.get(e_sensor).unwrap();
+            let distance = g_transform.translation().distance_squared(point);
+            if closest.map_or(true, |(_, d)| distance < d) {
+                closest = Some((sensor.item, distance));
+            }
+            true
+        },
+    );
+    closest.map(|(e_item, _)| e_item)
+}
+
+/// Drops the player's dominant hand item on `InputAction::Drop`.
+pub fn drop_on_input(
+    actions: Res<ButtonInput<InputAction>>,
+    item_query: Query<Entity, (With<InputHandler>, With<MainHand>)>,
+    mut drop_events: EventWriter<ItemDropEvent>,
+) {
+    if !actions.just_pressed(InputAction::Drop) {
+        return;
+    }
+    for e_item in item_query.iter() {
+        drop_events.send(ItemDropEvent { item: e_item });
+    }
+}
+
+pub fn drop_items(
+    mut commands: Commands,
+    item_query: Query<(
+        &EquippedTo,
+        &Transform,
+        Option<&Models>,
+        Option<&RigidBody>,
+        Option<&CollisionGroups>,
+        Has<Collider>,
+        Has<InputHandler>,
+        Has<SuppressedInput>,
+        Has<TimeParent>,
+    )>,
+    mut owner_query: Query<(
+        &Humanoid,
+        Option<&AttachmentSockets>,
+        Option<&mut Equipped>,
+        Option<&mut Inventory>,
+    )>,
+    mut drop_events: EventReader<ItemDropEvent>,
+    mut equip_events: EventWriter<UntypedItemEquipEvent>,
+) {
+    for &ItemDropEvent { item: e_item } in drop_events.read() {
+        let Ok((
+            &EquippedTo { target: e_owner },
+            &transform,
+            models,
+            rigid_body,
+            collision_groups,
+            has_collider,
+            input,
+            suppressed,
+            has_time_parent,
+        )) = item_query.get(e_item)
+        else {
+            warn!("Dropped an item that wasn't equipped.");
+            continue;
+        };
+
+        if let Ok((humanoid, sockets, equipped, inventory)) = owner_query.get_mut(e_owner) {
+            if let Some(mut equipped) = equipped {
+                // same as a fresh character
+                if equipped.left == e_item {
+                    equipped.left = commands.spawn(EmptyHand).id();
+                }
+                if equipped.right == e_item {
+                    equipped.right = commands.spawn(EmptyHand).id();
+                }
+            }
+
+            if let Some(e_next) = inventory.and_then(|mut inv| inv.remove(e_item)) {
+                let next_models = item_query.get(e_next).ok().and_then(|(_, _, m, ..)| m);
+                draw(
+                    &mut commands,
+                    e_owner,
+                    e_next,
+                    humanoid,
+                    sockets,
+                    next_models,
+                    input || suppressed,
+                    &mut equip_events,
+                );
+            }
+        }
+
+        // the models are on the hands, not the item
+        for &e_model in models.into_iter().flat_map(|m| m.targets.values()) {
+            commands
+                .entity(e_model)
+                .set_parent_in_place(e_item)
+                .insert(Visibility::Inherited);
+        }
+
+        let sensor = commands
+            .spawn((
+                PickupSensor { item: e_item },
+                Collider::ball(PICKUP_RADIUS),
+                Sensor,
+                ColliderMassProperties::Density(0.0),
+                CollisionGroups::new(Group::DEBRIS, Group::NONE),
+                TransformBundle::default(),
+            ))
+            .set_parent(e_item)
+            .id();
+
+        let mut e_item_commands = commands.entity(e_item);
+        e_item_commands
+            .remove_parent_in_place()
+            .remove::<(
+                InputHandler,
+                SuppressedInput,
+                Active,
+                Aiming,
+                EquippedTo,
+                SlotAlignment,
+            )>()
+            .insert((
+                Dropped {
+                    sensor,
+                    transform,
+                    rigid_body: rigid_body.copied(),
+                    collision_groups: collision_groups.copied(),
+                    added_collider: !has_collider,
+                },
+                RigidBody::Dynamic,
+                CollisionGroups::from_group_default(Group::DEBRIS),
+                Visibility::Inherited,
+                Interactable {
+                    prompt: "Pick up".into(),
+                    radius: PICKUP_RADIUS,
+                    kind: InteractionKind::Pickup,
+                },
+            ));
+        if !has_collider {
+            e_item_commands.insert(Collider::cuboid(
+                DROPPED_ITEM_HALF_EXTENTS.x,
+                DROPPED_ITEM_HALF_EXTENTS.y,
+                DROPPED_ITEM_HALF_EXTENTS.z,
+            ));
+        }
+        if !has_time_parent {
+            e_item_commands.set_time_parent(e_owner);
+        }
+
+        info!("Dropped {:?} from {:?}.", e_item, e_owner);
+    }
+}
+
+/// Sends `ItemPickupEvent`s for `InteractionKind::Pickup`.
+pub fn interact_pick_up_items(
+    interactable_query: Query<&Interactable, With<Dropped>>,
+    mut interact_events: EventReader<InteractEvent>,
+    mut pickup_events: EventWriter<ItemPickupEvent>,
+) {
+    for &InteractEvent { target, interactor } in interact_events.read() {
+        if interactable_query
+            .get(target)
+            .is_ok_and(|i| i.kind == InteractionKind::Pickup)
+        {
+            pickup_events.send(ItemPickupEvent {
+                item: target,
+                new_owner: interactor,
+            });
+        }
+    }
+}
+
+pub fn pick_up_items(
+    mut commands: Commands,
+    item_query: Query<&Dropped>,
+    humanoid_query: Query<(&Humanoid, Option<&AttachmentSockets>)>,
+    mut pickup_events: EventReader<ItemPickupEvent>,
+    mut equip_events: EventWriter<UntypedItemEquipEvent>,
+) {
+    for &ItemPickupEvent {
+        item: e_item,
+        new_owner: e_owner,
+    } in pickup_events.read()
+    {
+        let Ok(dropped) = item_query.get(e_item) else {
+            continue;
+        };
+        let Ok((humanoid, sockets)) = humanoid_query.get(e_owner) else {
+            error!("Picked up item with non-humanoid.");
+            continue;
+        };
+
+        commands.entity(dropped.sensor).despawn_recursive();
+
+        let mut e_item_commands = commands.entity(e_item);
+        e_item_commands
+            .remove::<(Dropped, RigidBody, CollisionGroups, Interactable)>()
+            .set_parent(grip(humanoid, sockets))
+            .insert(dropped.transform);
+        if let Some(rigid_body) = dropped.rigid_body {
+            e_item_commands.insert(rigid_body);
+        }
+        if let Some(collision_groups) = dropped.collision_groups {
+            e_item_commands.insert(collision_groups);
+        }
+        if dropped.added_collider {
+            e_item_commands.remove::<Collider>();
+        }
+
+        // `equip_items` takes the models back to the hands and `convert_untyped_events` sends
+        // the typed `ItemEquipEvent`
+        equip_events.send(UntypedItemEquipEvent {
+            parent_entity: e_owner,
+            item_entity: e_item,
+            slot: ItemEquipEventSlot::Auto,
+        });
+
+        info!("Picked up {:?} with {:?}.", e_item, e_owner);
+    }
+}
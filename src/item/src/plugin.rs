@@ -8,17 +8,31 @@ use grin_damage::{
     hit::ContactDamage,
     hitbox::{HitboxManager, Hitboxes},
 };
+use grin_input::camera::CameraAlignmentChangedEvent;
 use grin_physics::{CollisionGroupExt, CollisionGroupsExt};
+use grin_util::event::SpawnRegistry;
 
 use crate::{
     equip::{EquipPlugin, GltfHitboxAutoGen, Handedness, ItemEquipEvent, Models},
+    inventory::InventoryPlugin,
     library::plugin::ItemIdentifier,
     mechanics::{
+        aim_assist::AimAssistPlugin,
+        aim_indicator::AimIndicatorPlugin,
+        animation::reaim_on_camera_alignment_change,
+        ballistics::BallisticsPlugin,
         combo::ComboStack,
-        firing::{Accuracy, FireRate, FiringMode, ShotCooldown, Target},
+        firing::{
+            Accuracy, Ammo, AmmoPlugin, FireMode, FireRate, FiringMode, RecoilPlugin, ReloadTime,
+            ShotCooldown, Target,
+        },
         fx::ItemFxPlugin,
+        melee::MeleePlugin,
+        util::suppress_stunned_input,
     },
-    spawn::ItemSpawnEvent,
+    pickup::PickupPlugin,
+    spawn::{convert_untyped_spawn_events, ItemSpawnEvent, UntypedItemSpawnEvent},
+    stats::{ItemStats, ItemStatsPlugin},
 };
 
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
@@ -44,7 +58,33 @@ impl Plugin for MasterItemPlugin {
             .configure_sets(
                 PostUpdate,
                 ItemSet::Equip.run_if(in_state(AssetLoadState::Success)),
+            )
+            .add_event::<CameraAlignmentChangedEvent>()
+            .add_event::<UntypedItemSpawnEvent>()
+            .add_systems(PreUpdate, suppress_stunned_input)
+            .add_systems(
+                Update,
+                (
+                    reaim_on_camera_alignment_change,
+                    convert_untyped_spawn_events.before(ItemSet::Spawn),
+                ),
             );
+
+        // items spawned by name are pickups
+        let mut registry = app
+            .world
+            .get_resource_or_insert_with(SpawnRegistry::default);
+        for identifier in ItemIdentifier::ALL {
+            registry.insert(identifier.name(), move |commands, transform| {
+                commands.add(move |world: &mut World| {
+                    world.send_event(UntypedItemSpawnEvent {
+                        identifier,
+                        parent_entity: None,
+                        transform,
+                    });
+                });
+            });
+        }
     }
 }
 
@@ -74,7 +114,16 @@ impl PluginGroup for ItemPlugins {
         PluginGroupBuilder::start::<Self>()
             .add(MasterItemPlugin)
             .add(EquipPlugin)
+            .add(InventoryPlugin)
+            .add(PickupPlugin)
             .add(ItemFxPlugin)
+            .add(MeleePlugin)
+            .add(AmmoPlugin)
+            .add(RecoilPlugin)
+            .add(BallisticsPlugin)
+            .add(AimAssistPlugin)
+            .add(AimIndicatorPlugin)
+            .add(ItemStatsPlugin)
     }
 }
 
@@ -111,6 +160,14 @@ pub struct WeaponBundle<C: Send + Sync + 'static> {
     pub cooldown: ShotCooldown,
     /// Weapon firing mode (default: `SemiAuto`).
     pub firing_mode: FiringMode,
+    /// What comes out when it fires (default: `Projectile`).
+    pub fire_mode: FireMode,
+    /// Weapon ammo (default: bottomless clip).
+    pub ammo: Ammo,
+    /// Weapon reload time.
+    pub reload_time: ReloadTime,
+    /// Weapon stats (stamped at spawn from the `ItemStatsMap`).
+    pub stats: ItemStats,
 }
 
 // I love rust it really is my favourite language :) :) <333
@@ -131,6 +188,10 @@ impl<C: Send + Sync + 'static> Default for WeaponBundle<C> {
             fire_rate: FireRate::default(),
             cooldown: ShotCooldown::default(),
             firing_mode: FiringMode::default(),
+            fire_mode: FireMode::default(),
+            ammo: Ammo::default(),
+            reload_time: ReloadTime::default(),
+            stats: ItemStats::default(),
         }
     }
 }
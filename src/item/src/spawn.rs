@@ -1,8 +1,15 @@
 use std::marker::PhantomData;
 
-use bevy::{ecs::system::SystemParam, prelude::*};
+use bevy::{
+    ecs::{event::ManualEventReader, system::SystemParam},
+    prelude::*,
+};
+use grin_util::event::UntypedEvent;
 
-use crate::equip::{ItemEquipEventSlot, UntypedItemEquipEvent};
+use crate::{
+    equip::{ItemEquipEventSlot, UntypedItemEquipEvent},
+    library::plugin::ItemIdentifier,
+};
 
 #[derive(Event, Clone)]
 pub struct ItemSpawnEvent<I: Component> {
@@ -24,6 +31,37 @@ impl<I: Component> Default for ItemSpawnEvent<I> {
     }
 }
 
+/// `ItemSpawnEvent` for when the item is only known by its `ItemIdentifier`.
+#[derive(Event, Clone)]
+pub struct UntypedItemSpawnEvent {
+    pub identifier: ItemIdentifier,
+    pub parent_entity: Option<Entity>,
+    pub transform: Transform,
+}
+
+impl UntypedEvent for UntypedItemSpawnEvent {
+    type TypedEvent<I> = ItemSpawnEvent<I>;
+
+    fn typed<I>(&self) -> Self::TypedEvent<I> {
+        ItemSpawnEvent {
+            parent_entity: self.parent_entity,
+            transform: self.transform,
+            phantom_data: PhantomData,
+        }
+    }
+}
+
+pub fn convert_untyped_spawn_events(
+    world: &mut World,
+    mut reader: Local<ManualEventReader<UntypedItemSpawnEvent>>,
+) {
+    world.resource_scope::<Events<UntypedItemSpawnEvent>, _>(|world, events| {
+        for ev in reader.read(&events) {
+            ev.identifier.send_typed_event(world, ev);
+        }
+    });
+}
+
 #[derive(SystemParam)]
 pub struct ItemSpawnerParams<'w, 's> {
     pub commands: Commands<'w, 's>,
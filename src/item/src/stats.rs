@@ -0,0 +1,248 @@
+//! Item numbers, loaded from `items.stats.ron` so they can be tuned without recompiling.
+
+use std::{ops::Range, time::Duration};
+
+use bevy::{prelude::*, reflect::TypePath, utils::HashMap};
+use bevy_asset_loader::prelude::*;
+use bevy_common_assets::ron::RonAssetPlugin;
+use grin_asset::AssetLoadState;
+use grin_damage::hit::Damage;
+use serde::Deserialize;
+
+use crate::{
+    library::plugin::ItemIdentifier,
+    mechanics::firing::{Accuracy, Ammo, FireRate, ReloadTime},
+    plugin::ItemSet,
+};
+
+pub struct ItemStatsPlugin;
+
+impl Plugin for ItemStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RonAssetPlugin::<ItemStatsFile>::new(&["stats.ron"]))
+            .init_resource::<ItemStatsMap>()
+            .configure_loading_state(
+                LoadingStateConfig::new(AssetLoadState::Loading)
+                    .load_collection::<ItemStatsAssets>(),
+            )
+            .add_systems(
+                Update,
+                (
+                    load_item_stats,
+                    stamp_item_stats.after(ItemSet::Spawn).before(ItemSet::Fire),
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// What an item does, in numbers. Items read whatever they use out of this at fire time, and
+/// the generic stuff gets stamped into `FireRate`, `Accuracy`, `Ammo`, `ReloadTime` and `Damage`.
+///
+/// Anything left out of the file is `ItemStats::DEFAULT`, not the item's compiled stats.
+#[derive(Component, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct ItemStats {
+    /// Seconds between shots.
+    pub fire_rate: f32,
+    pub damage: f32,
+    /// Meters per second.
+    pub projectile_speed: f32,
+    pub accuracy: f32,
+    /// Degrees, at `Accuracy(1.0)`.
+    pub spread: f32,
+    /// `None` is a bottomless clip.
+    pub clip_size: Option<u32>,
+    /// Seconds.
+    pub reload_time: f32,
+    /// Degrees, for melee.
+    pub swing_arc: f32,
+    /// Swing animation speed, for melee.
+    pub swing_speed: f32,
+    /// The part of the swing animation that can hit things, as a fraction of the clip.
+    pub swing_window: Range<f32>,
+}
+
+impl ItemStats {
+    pub const DEFAULT: Self = Self {
+        fire_rate: 1.0,
+        damage: 0.0,
+        projectile_speed: 0.0,
+        accuracy: 1.0,
+        spread: 1.0,
+        clip_size: None,
+        reload_time: 1.5,
+        swing_arc: 90.0,
+        swing_speed: 1.0,
+        swing_window: 0.0..1.0,
+    };
+}
+
+impl Default for ItemStats {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// `ItemStats` keyed by `ItemIdentifier::name`.
+#[derive(Asset, TypePath, Deserialize, Clone, Debug, Default)]
+pub struct ItemStatsFile(pub HashMap<String, ItemStats>);
+
+#[derive(Resource, AssetCollection)]
+pub struct ItemStatsAssets {
+    #[asset(key = "stats.items")]
+    pub stats: Handle<ItemStatsFile>,
+}
+
+/// The latest `ItemStatsFile`.
+#[derive(Resource, Default)]
+pub struct ItemStatsMap(pub HashMap<String, ItemStats>);
+
+impl ItemStatsMap {
+    /// Falls back to `ItemIdentifier::default_stats` if the file doesn't have it.
+    pub fn get(&self, identifier: ItemIdentifier) -> ItemStats {
+        self.0.get(&identifier.name()).cloned().unwrap_or_else(|| {
+            warn!(
+                "No stats for `{}`, using the compiled ones.",
+                identifier.name()
+            );
+            identifier.default_stats()
+        })
+    }
+}
+
+/// Copies the stats file into the `ItemStatsMap` when it (re)loads.
+pub fn load_item_stats(
+    mut stats_map: ResMut<ItemStatsMap>,
+    assets: Option<Res<ItemStatsAssets>>,
+    files: Res<Assets<ItemStatsFile>>,
+    mut events: EventReader<AssetEvent<ItemStatsFile>>,
+) {
+    let Some(assets) = assets else {
+        return;
+    };
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event {
+            if *id == assets.stats.id() {
+                stats_map.0 = files.get(*id).unwrap().0.clone();
+            }
+        }
+    }
+}
+
+/// Stamps `ItemStats` onto new items, and onto every item when the `ItemStatsMap` changes.
+pub fn stamp_item_stats(
+    stats_map: Res<ItemStatsMap>,
+    mut item_query: Query<(
+        Ref<ItemIdentifier>,
+        &mut ItemStats,
+        Option<&mut FireRate>,
+        Option<&mut Accuracy>,
+        Option<&mut Ammo>,
+        Option<&mut ReloadTime>,
+        Option<&mut Damage>,
+    )>,
+) {
+    for (identifier, mut stats, fire_rate, accuracy, ammo, reload_time, damage) in
+        item_query.iter_mut()
+    {
+        let spawned = identifier.is_added();
+        if !spawned && !stats_map.is_changed() {
+            continue;
+        }
+
+        *stats = stats_map.get(*identifier);
+        if let Some(mut fire_rate) = fire_rate {
+            fire_rate.0 = Duration::from_secs_f32(stats.fire_rate);
+        }
+        if let Some(mut accuracy) = accuracy {
+            accuracy.0 = stats.accuracy;
+        }
+        if let Some(mut ammo) = ammo {
+            let clip_size = stats.clip_size.unwrap_or(u32::MAX);
+            *ammo = match spawned {
+                true => Ammo::new(clip_size, ammo.reserve),
+                false => Ammo {
+                    clip: ammo.clip.min(clip_size),
+                    clip_size,
+                    reserve: ammo.reserve,
+                },
+            };
+        }
+        if let Some(mut reload_time) = reload_time {
+            reload_time.0 = Duration::from_secs_f32(stats.reload_time);
+        }
+        if let Some(mut damage) = damage {
+            damage.value = stats.damage;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restamp_on_reload() {
+        let mut app = App::new();
+        app.init_resource::<ItemStatsMap>()
+            .add_systems(Update, stamp_item_stats);
+
+        app.world.resource_mut::<ItemStatsMap>().0.insert(
+            "fist".into(),
+            ItemStats {
+                fire_rate: 0.5,
+                clip_size: Some(10),
+                ..Default::default()
+            },
+        );
+        let e_item = app
+            .world
+            .spawn((
+                ItemIdentifier::Fist,
+                ItemStats::default(),
+                FireRate::default(),
+                Ammo::default(),
+            ))
+            .id();
+        app.update();
+
+        assert_eq!(
+            app.world.get::<FireRate>(e_item).unwrap().0,
+            Duration::from_millis(500),
+            "Didn't stamp the fire rate at spawn."
+        );
+        assert_eq!(
+            app.world.get::<Ammo>(e_item).unwrap().clip,
+            10,
+            "Didn't start with a full clip."
+        );
+
+        app.world
+            .resource_mut::<ItemStatsMap>()
+            .0
+            .get_mut("fist")
+            .unwrap()
+            .clip_size = Some(4);
+        app.update();
+
+        assert_eq!(
+            *app.world.get::<Ammo>(e_item).unwrap(),
+            Ammo {
+                clip: 4,
+                clip_size: 4,
+                reserve: None,
+            },
+            "Didn't restamp the clip after a reload."
+        );
+
+        app.world.resource_mut::<ItemStatsMap>().0.clear();
+        app.update();
+
+        assert_eq!(
+            *app.world.get::<ItemStats>(e_item).unwrap(),
+            ItemIdentifier::Fist.default_stats(),
+            "Didn't fall back to the compiled stats."
+        );
+    }
+}
@@ -8,11 +8,17 @@ use bevy::{
     window::CursorGrabMode,
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
-use grin_ai::{spawn::EnemySpawn, AiPlugins};
+use grin_ai::AiSet;
+use grin_ai::{encounter::EncounterDirector, AiPlugins};
 use grin_asset::{texture_array, AssetLoadState, DynamicAssetPlugin};
-use grin_character::{CharacterPlugins, CharacterSet};
-use grin_damage::plugin::DamagePlugins;
+use grin_character::{menu::RespawnPlayerEvent, CharacterPlugins, CharacterSet};
+use grin_console::ConsolePlugin;
+use grin_damage::{
+    plugin::{DamagePlugins, DamageSet},
+    status::{Burning, Disoriented, RewindStatusPlugin, Slowed, Stunned},
+};
 use grin_dialogue::{DialogueEvent, DialogueMap};
+use grin_input::{action::InputActionPlugin, settings::SettingsPlugin};
 use grin_item::{
     library::plugin::ItemLibrary,
     plugin::{ItemPlugins, ItemSet},
@@ -20,11 +26,17 @@ use grin_item::{
 use grin_map::{Map, MapLoadState, MapPlugin};
 use grin_physics::GrinPhysicsPlugin;
 use grin_render::RenderFXPlugins;
-use grin_rig::{humanoid::HumanoidPlugin, GrinAnimationPlugin};
-use grin_time::{scaling::TimeScalePlugin, RewindComponentPlugin, RewindPlugin};
+use grin_rig::{footstep::FootstepPlugin, humanoid::HumanoidPlugin, GrinAnimationPlugin};
+use grin_save::SavePlugin;
+use grin_time::{
+    compression::TransformCompression, scaling::TimeScalePlugin, zones::TimeScaleZonePlugin,
+    GlobalRewindEvent, RewindComponentPlugin, RewindPlugin,
+};
 use grin_util::{
-    event::{DefaultSpawnable, TweenEventPlugin},
+    event::{DefaultSpawnable, SpawnRegistryPlugin, TweenEventPlugin},
+    rng::{seed_from_args, GrinRng},
     spatial::SpatialPlugin,
+    state::GameState,
 };
 
 fn main() -> Result<(), io::Error> {
@@ -74,10 +86,17 @@ fn main() -> Result<(), io::Error> {
 
     app.add_plugins(default_plugins);
 
+    // otherwise `GrinRng` checks the environment, then picks one at random
+    if let Some(seed) = seed_from_args(env::args()) {
+        app.insert_resource(GrinRng::new(seed));
+    }
+
     app.init_resource::<Msaa>()
         .init_resource::<AmbientLight>()
         .add_plugins((
             DynamicAssetPlugin,
+            InputActionPlugin,
+            SettingsPlugin,
             LogDiagnosticsPlugin::default(),
             WorldInspectorPlugin::new(),
             TweenEventPlugin,
@@ -99,23 +118,66 @@ fn main() -> Result<(), io::Error> {
         ))
         .add_plugins((
             TimeScalePlugin,
+            TimeScaleZonePlugin,
             RewindPlugin::default(),
-            RewindComponentPlugin::<Transform>::default(),
+            RewindComponentPlugin::<Transform>::default()
+                .with_compression(TransformCompression::default()),
+            RewindStatusPlugin::<Slowed>::default(),
+            RewindStatusPlugin::<Stunned>::default(),
+            RewindStatusPlugin::<Burning>::default(),
+            RewindStatusPlugin::<Disoriented>::default(),
             SpatialPlugin,
             GrinAnimationPlugin,
+            FootstepPlugin,
+            SavePlugin,
+            SpawnRegistryPlugin,
+            ConsolePlugin,
         ))
         .add_systems(OnEnter(AssetLoadState::Success), load_scene)
         .add_systems(
             OnEnter(AssetLoadState::Success),
             grin_character::kit::smirk::Smirk::spawn_default().before(CharacterSet::Spawn),
         )
+        .add_systems(
+            Update,
+            grin_character::kit::smirk::Smirk::spawn_default()
+                .run_if(on_event::<RespawnPlayerEvent>())
+                .before(CharacterSet::Spawn),
+        )
+        .configure_sets(
+            Update,
+            (
+                CharacterSet::Spawn,
+                CharacterSet::Load,
+                ItemSet::Spawn,
+                ItemSet::Input,
+                ItemSet::Fire,
+                ItemSet::Effects,
+                AiSet::RunTrees,
+                AiSet::Spawn,
+                DamageSet::Add,
+                DamageSet::Propagate,
+                DamageSet::Resist,
+                DamageSet::Zone,
+                DamageSet::Clear,
+                DamageSet::Kill,
+            )
+                .run_if(not(in_state(GameState::Paused))),
+        )
+        .configure_sets(
+            Update,
+            DamageSet::Zone
+                .after(DamageSet::Resist)
+                .before(DamageSet::Clear),
+        )
+        .configure_sets(
+            PostUpdate,
+            (CharacterSet::Init, ItemSet::Equip).run_if(not(in_state(GameState::Paused))),
+        )
         .add_systems(
             OnEnter(MapLoadState::Success),
-            |mut events: EventWriter<EnemySpawn<grin_ai::Dummy>>| {
-                events.send(EnemySpawn {
-                    transform: Transform::from_xyz(0.0, 1E-2, -10.0),
-                    ..Default::default()
-                });
+            |asset_server: Res<AssetServer>, mut director: ResMut<EncounterDirector>| {
+                director.start(vec![asset_server.load("waves/test.wave.ron")]);
             },
         )
         //.add_systems(OnEnter(DialogueAssetLoadState::Success), test_dialogue)
@@ -126,7 +188,7 @@ fn main() -> Result<(), io::Error> {
                 apply_deferred
                     .after(CharacterSet::Spawn)
                     .before(ItemSet::Spawn),
-                bevy::window::close_on_esc,
+                debug_global_rewind,
             ),
         );
 
@@ -164,6 +226,16 @@ fn load_scene(
     ));
 }
 
+// TODO: this should be an ability or something
+fn debug_global_rewind(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut events: EventWriter<GlobalRewindEvent>,
+) {
+    if keys.just_pressed(KeyCode::KeyT) {
+        events.send(GlobalRewindEvent { seconds: 3.0 });
+    }
+}
+
 fn test_dialogue(mut events: EventWriter<DialogueEvent>, dialogue_map: Res<DialogueMap>) {
     events.send(DialogueEvent::Say(dialogue_map.0["test_1"].clone()));
 }
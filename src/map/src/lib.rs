@@ -1,6 +1,10 @@
+pub mod marker;
+pub mod obstacle;
+pub mod prop;
+
 use std::sync::Arc;
 
-use bevy::{prelude::*, render::mesh::VertexAttributeValues};
+use bevy::{gltf::GltfExtras, prelude::*, render::mesh::VertexAttributeValues};
 use bevy_landmass::{prelude::*, ValidationError};
 use bevy_mod_outline::OutlineMode;
 use geo::{
@@ -9,9 +13,13 @@ use geo::{
 use geo_offset::Offset;
 use grin_physics::collider;
 use grin_render::sketched::NoOutline;
-use grin_rig::humanoid::HUMANOID_RADIUS;
+use grin_rig::{footstep::SurfaceKind, humanoid::HUMANOID_RADIUS};
 use grin_util::vectors::Vec3Ext;
 use itertools::Itertools;
+use marker::{MapMarkerPlugin, SpawnPoint, TriggerVolume};
+use obstacle::NavObstaclePlugin;
+use prop::{Destructible, DestructiblePlugin};
+use serde::Deserialize;
 use spade::{ConstrainedDelaunayTriangulation, Point2, Triangulation};
 
 /// How much to offset navmesh from obstacles.
@@ -27,18 +35,20 @@ pub struct MapPlugin {
 
 impl Plugin for MapPlugin {
     fn build(&self, app: &mut App) {
-        app.init_state::<MapLoadState>().add_systems(
-            Update,
-            (
-                check_map_existence.run_if(in_state(MapLoadState::NotLoaded)),
-                setup_map_navigation
-                    .pipe(finish_navmesh_generation)
-                    .run_if(in_state(MapLoadState::Loading)),
-            ),
-        );
+        app.init_state::<MapLoadState>()
+            .add_plugins((DestructiblePlugin, MapMarkerPlugin, NavObstaclePlugin))
+            .add_systems(
+                Update,
+                (
+                    check_map_existence.run_if(in_state(MapLoadState::NotLoaded)),
+                    setup_map_navigation
+                        .pipe(finish_navmesh_generation)
+                        .run_if(in_state(MapLoadState::Loading)),
+                ),
+            );
 
         if let Some(color) = self.navmesh_debugging {
-            app.add_systems(
+            app.insert_resource(NavMeshDebugging(color)).add_systems(
                 Update,
                 draw_navmesh_system_with_color(color).run_if(in_state(MapLoadState::Success)),
             );
@@ -46,6 +56,10 @@ impl Plugin for MapPlugin {
     }
 }
 
+/// Exists when `MapPlugin::navmesh_debugging` is on, for other debug gizmos to go along with it.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct NavMeshDebugging(pub Color);
+
 #[derive(Component)]
 pub struct Map;
 
@@ -84,11 +98,15 @@ pub fn check_map_existence(
 // I need to check the logs on a more complex map
 pub fn setup_map_navigation(
     mut commands: Commands,
+    asset_server: Res<AssetServer>,
     meshes: Res<Assets<Mesh>>,
     mut navmeshes: ResMut<Assets<NavMesh>>,
     map_query: Query<Entity, (With<Map>, With<Children>)>,
     mesh_query: Query<(&GlobalTransform, &Handle<Mesh>, &Name)>,
+    extras_query: Query<&GltfExtras>,
+    marker_query: Query<(), Or<(With<SpawnPoint>, With<TriggerVolume>)>>,
     children_query: Query<&Children>,
+    parent_query: Query<&Parent>,
 ) -> Result<NavMeshStatistics, NavMeshGenerationError> {
     let e_map = map_query
         .get_single()
@@ -101,6 +119,14 @@ pub fn setup_map_navigation(
             continue;
         };
 
+        // markers aren't part of the level
+        if std::iter::once(e_node)
+            .chain(parent_query.iter_ancestors(e_node))
+            .any(|e| marker_query.contains(e))
+        {
+            continue;
+        }
+
         // polygons within "restricted" hulls are excluded
         // I don't want this to include the larger, all-encompassing floor
         // which will define the navmesh boundary
@@ -109,14 +135,29 @@ pub fn setup_map_navigation(
             _ => true,
         };
 
-        map_meshes.push((restricted, *g_transform, mesh.clone()));
-
         commands.entity(e_node).insert(collider!(&meshes, mesh));
         if !restricted {
             commands.entity(e_node).insert(OutlineMode::RealVertex);
         } else {
             commands.entity(e_node).insert(NoOutline);
         }
+
+        if let Some(surface) = extras_query
+            .get(e_node)
+            .ok()
+            .and_then(|extras| serde_json::from_str::<MapNodeExtras>(&extras.value).ok())
+            .and_then(|extras| extras.surface)
+        {
+            commands.entity(e_node).insert(surface);
+        }
+
+        match Destructible::from_name(name.as_str(), &asset_server) {
+            // it can break, so it's a `NavObstacle` instead of a hole in the navmesh
+            Some(destructible) => {
+                commands.entity(e_node).insert(destructible);
+            }
+            None => map_meshes.push((restricted, *g_transform, mesh.clone())),
+        }
     }
 
     // https://skatgame.net/mburo/ps/thesis_demyen_2006.pdf
@@ -267,6 +308,14 @@ pub fn setup_map_navigation(
     })
 }
 
+/// Custom properties on map nodes.
+#[derive(Deserialize)]
+struct MapNodeExtras {
+    /// Footstep surface, like `"metal"`.
+    #[serde(default)]
+    surface: Option<SurfaceKind>,
+}
+
 #[derive(Resource)]
 pub struct MapData {
     pub archipelago: Entity,
@@ -297,6 +346,16 @@ pub fn finish_navmesh_generation(
 #[derive(Resource)]
 pub struct NavMeshGeometry(pub NavigationMesh);
 
+impl NavMeshGeometry {
+    /// Middle of each polygon. Good enough for picking spots to walk to.
+    pub fn polygon_centers(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.0
+            .polygons
+            .iter()
+            .map(|poly| poly.iter().map(|&i| self.0.vertices[i]).sum::<Vec3>() / poly.len() as f32)
+    }
+}
+
 fn draw_navmesh_system_with_color(color: Color) -> impl Fn(Gizmos, Res<NavMeshGeometry>) {
     move |mut gizmos: Gizmos, navmesh: Res<NavMeshGeometry>| {
         for poly in navmesh.0.polygons.iter() {
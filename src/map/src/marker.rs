@@ -0,0 +1,499 @@
+//! Spawn points and triggers, placed in the map as named nodes.
+//!
+//! - `Spawn.Player`
+//! - `Spawn.Enemy.<Type>`, like `Spawn.Enemy.Dummy.01`. The type is looked up lowercase.
+//! - `Trigger.<Id>`, like `Trigger.Encounter1`. The volume is the box around its meshes.
+//! - `DamageZone.<Variant>.<Percent>%`, like `DamageZone.Fire.150%` or `DamageZone.All.50%`.
+//!   Damage taken inside gets scaled. The volume works like a trigger's.
+//!
+//! Anything after the name can be a number, to tell nodes of the same kind apart.
+
+use bevy::{ecs::entity::EntityHashMap, prelude::*, render::primitives::Aabb, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+use grin_damage::{hit::DamageVariant, hitbox::Hitbox, zone::DamageZone};
+use grin_physics::CollisionGroupExt;
+
+use crate::{Map, MapLoadState};
+
+pub struct MapMarkerPlugin;
+
+impl Plugin for MapMarkerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TriggerEnteredEvent>()
+            .add_event::<TriggerExitedEvent>()
+            .add_systems(OnEnter(MapLoadState::Loading), scan_map_markers)
+            .add_systems(Update, (init_trigger_volumes, fire_trigger_volumes).chain());
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SpawnPointKind {
+    Player,
+    /// Name in the `EnemyRegistry`.
+    Enemy(String),
+}
+
+/// Where something spawns. The node stays where it is, but it's hidden.
+#[derive(Component, Clone, Debug, PartialEq)]
+pub struct SpawnPoint {
+    pub kind: SpawnPointKind,
+    /// In world space.
+    pub transform: Transform,
+}
+
+impl SpawnPoint {
+    /// Where the player goes, if the map says.
+    pub fn player<'a>(points: impl IntoIterator<Item = &'a SpawnPoint>) -> Option<Transform> {
+        points
+            .into_iter()
+            .find(|p| p.kind == SpawnPointKind::Player)
+            .map(|p| p.transform)
+    }
+
+    /// Every point for `enemy_type`, in order of their names.
+    pub fn enemies<'a>(
+        points: impl IntoIterator<Item = (&'a Name, &'a SpawnPoint)>,
+        enemy_type: &str,
+    ) -> Vec<Transform> {
+        let mut points = points
+            .into_iter()
+            .filter(|(_, p)| matches!(&p.kind, SpawnPointKind::Enemy(t) if t == enemy_type))
+            .collect::<Vec<_>>();
+        points.sort_by_key(|(name, _)| name.as_str());
+        points.into_iter().map(|(_, p)| p.transform).collect()
+    }
+}
+
+/// Sends `TriggerEnteredEvent` and `TriggerExitedEvent` when things go in and out of `collider`.
+///
+/// Body parts with a `Hitbox` count as whatever they're on.
+#[derive(Component, Clone, Debug)]
+pub struct TriggerVolume {
+    pub id: String,
+    /// Relative to the volume.
+    pub collider: Collider,
+}
+
+/// How many colliders each thing has inside of a `TriggerVolume`.
+#[derive(Component, Debug, Default)]
+pub struct TriggerOccupants(pub EntityHashMap<usize>);
+
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct TriggerEnteredEvent {
+    pub id: String,
+    pub entity: Entity,
+}
+
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct TriggerExitedEvent {
+    pub id: String,
+    pub entity: Entity,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MapMarker {
+    Spawn(SpawnPointKind),
+    Trigger(String),
+    DamageZone {
+        /// `None` is `All`.
+        variant: Option<DamageVariant>,
+        percent: u32,
+    },
+}
+
+impl MapMarker {
+    /// `Ok(None)` if it's not a marker at all, `Err` if it looks like one but it's wrong.
+    pub fn from_name(name: &str) -> Result<Option<Self>, String> {
+        let mut parts = name.split('.').collect::<Vec<_>>();
+        // numbers on the end are just for telling them apart
+        if parts.len() > 2 && parts.last().unwrap().chars().all(|c| c.is_ascii_digit()) {
+            parts.pop();
+        }
+        match parts.as_slice() {
+            ["Spawn", "Player"] => Ok(Some(Self::Spawn(SpawnPointKind::Player))),
+            ["Spawn", "Enemy", enemy_type] if !enemy_type.is_empty() => Ok(Some(Self::Spawn(
+                SpawnPointKind::Enemy(enemy_type.to_lowercase()),
+            ))),
+            ["Trigger", id] if !id.is_empty() => Ok(Some(Self::Trigger(id.to_string()))),
+            ["DamageZone", variant, percent] => {
+                let variant = match *variant {
+                    "All" => None,
+                    "Ballistic" => Some(DamageVariant::Ballistic),
+                    "Fire" => Some(DamageVariant::Fire),
+                    "Toxin" => Some(DamageVariant::Toxin),
+                    _ => return Err(format!("`{}` isn't a damage type", variant)),
+                };
+                let Some(Ok(percent)) = percent.strip_suffix('%').map(str::parse) else {
+                    return Err(format!("`{}` isn't a percentage", percent));
+                };
+                Ok(Some(Self::DamageZone { variant, percent }))
+            }
+            ["Spawn", ..] => Err("expected `Spawn.Player` or `Spawn.Enemy.<Type>`".into()),
+            ["Trigger", ..] => Err("expected `Trigger.<Id>`".into()),
+            ["DamageZone", ..] => Err("expected `DamageZone.<Variant>.<Percent>%`".into()),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Box around `e_node`'s meshes and its children's, relative to it.
+fn trigger_collider(
+    e_node: Entity,
+    meshes: &Assets<Mesh>,
+    mesh_query: &Query<(&Handle<Mesh>, &Transform)>,
+    children_query: &Query<&Children>,
+) -> Option<Collider> {
+    // the node's own mesh is already relative to it
+    let (min, max) = std::iter::once((e_node, Transform::IDENTITY))
+        .chain(
+            children_query
+                .get(e_node)
+                .into_iter()
+                .flatten()
+                .filter_map(|&e| mesh_query.get(e).ok().map(|(_, t)| (e, *t))),
+        )
+        .filter_map(|(e, transform)| {
+            let Aabb {
+                center,
+                half_extents,
+            } = meshes.get(mesh_query.get(e).ok()?.0)?.compute_aabb()?;
+            let corners = [-1.0, 1.0].into_iter().flat_map(|x| {
+                [-1.0, 1.0].into_iter().flat_map(move |y| {
+                    [-1.0, 1.0]
+                        .into_iter()
+                        .map(move |z| Vec3::from(center + half_extents * Vec3A::new(x, y, z)))
+                })
+            });
+            Some(
+                corners
+                    .map(|corner| transform.transform_point(corner))
+                    .fold((Vec3::MAX, Vec3::MIN), |(min, max), p| {
+                        (min.min(p), max.max(p))
+                    }),
+            )
+        })
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))?;
+
+    let half_extents = (max - min) / 2.0;
+    Some(Collider::compound(vec![(
+        (min + max) / 2.0,
+        Quat::IDENTITY,
+        Collider::cuboid(half_extents.x, half_extents.y, half_extents.z),
+    )]))
+}
+
+/// Turns marker nodes into `SpawnPoint`s, `TriggerVolume`s and `DamageZone`s, warning about
+/// anything that's missing or doubled up.
+pub fn scan_map_markers(
+    mut commands: Commands,
+    meshes: Res<Assets<Mesh>>,
+    map_query: Query<Entity, With<Map>>,
+    node_query: Query<(&Name, &GlobalTransform)>,
+    mesh_query: Query<(&Handle<Mesh>, &Transform)>,
+    children_query: Query<&Children>,
+) {
+    let Ok(e_map) = map_query.get_single() else {
+        return;
+    };
+
+    let mut names = HashMap::<&str, usize>::new();
+    let mut triggers = HashMap::<String, usize>::new();
+    let mut players = 0;
+    for e_node in children_query.iter_descendants(e_map) {
+        let Ok((name, g_transform)) = node_query.get(e_node) else {
+            continue;
+        };
+        let marker = match MapMarker::from_name(name.as_str()) {
+            Ok(Some(marker)) => marker,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!("Map node `{}` isn't a proper marker: {}.", name, e);
+                continue;
+            }
+        };
+        *names.entry(name.as_str()).or_default() += 1;
+
+        match marker {
+            MapMarker::Spawn(kind) => {
+                if kind == SpawnPointKind::Player {
+                    players += 1;
+                }
+                commands.entity(e_node).insert((
+                    SpawnPoint {
+                        kind,
+                        transform: g_transform.compute_transform(),
+                    },
+                    Visibility::Hidden,
+                ));
+            }
+            MapMarker::Trigger(id) => {
+                let Some(collider) =
+                    trigger_collider(e_node, &meshes, &mesh_query, &children_query)
+                else {
+                    warn!(
+                        "Trigger `{}` doesn't have a mesh to make a volume from.",
+                        id
+                    );
+                    continue;
+                };
+                *triggers.entry(id.clone()).or_default() += 1;
+                commands
+                    .entity(e_node)
+                    .insert((TriggerVolume { id, collider }, Visibility::Hidden));
+            }
+            MapMarker::DamageZone { variant, percent } => {
+                let Some(collider) =
+                    trigger_collider(e_node, &meshes, &mesh_query, &children_query)
+                else {
+                    warn!(
+                        "Damage zone `{}` doesn't have a mesh to make a volume from.",
+                        name
+                    );
+                    continue;
+                };
+                commands.entity(e_node).insert((
+                    DamageZone::new(variant, percent as f32 / 100.0, collider),
+                    Visibility::Hidden,
+                ));
+            }
+        }
+    }
+
+    if players == 0 {
+        warn!("Map doesn't have a `Spawn.Player`.");
+    } else if players > 1 {
+        warn!(
+            "Map has {} player spawn points. Only one gets used.",
+            players
+        );
+    }
+    for (name, count) in names.into_iter().filter(|(_, count)| *count > 1) {
+        warn!("Map has {} nodes named `{}`.", count, name);
+    }
+    for (id, count) in triggers.into_iter().filter(|(_, count)| *count > 1) {
+        warn!("Map has {} triggers with the id `{}`.", count, id);
+    }
+}
+
+pub fn init_trigger_volumes(
+    mut commands: Commands,
+    volume_query: Query<(Entity, &TriggerVolume), Added<TriggerVolume>>,
+) {
+    for (e_volume, volume) in volume_query.iter() {
+        commands.entity(e_volume).insert((
+            volume.collider.clone(),
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC,
+            CollisionGroups::new(Group::MAP, Group::PLAYER | Group::ENEMY),
+            TriggerOccupants::default(),
+        ));
+    }
+}
+
+pub fn fire_trigger_volumes(
+    mut volume_query: Query<(&TriggerVolume, &mut TriggerOccupants)>,
+    hitbox_query: Query<&Hitbox>,
+    mut collision_events: EventReader<CollisionEvent>,
+    mut entered_events: EventWriter<TriggerEnteredEvent>,
+    mut exited_events: EventWriter<TriggerExitedEvent>,
+) {
+    for event in collision_events.read() {
+        let (e1, e2, started) = match *event {
+            CollisionEvent::Started(e1, e2, _) => (e1, e2, true),
+            CollisionEvent::Stopped(e1, e2, _) => (e1, e2, false),
+        };
+        let (e_volume, e_other) = match volume_query.contains(e1) {
+            true => (e1, e2),
+            false => (e2, e1),
+        };
+        let Ok((volume, mut occupants)) = volume_query.get_mut(e_volume) else {
+            continue;
+        };
+        let entity = hitbox_query.get(e_other).map_or(e_other, |h| h.target);
+
+        let count = occupants.0.entry(entity).or_default();
+        match started {
+            true => {
+                *count += 1;
+                if *count == 1 {
+                    entered_events.send(TriggerEnteredEvent {
+                        id: volume.id.clone(),
+                        entity,
+                    });
+                }
+            }
+            // never saw it go in
+            false if *count == 0 => {
+                occupants.0.remove(&entity);
+            }
+            false => {
+                *count -= 1;
+                if *count == 0 {
+                    occupants.0.remove(&entity);
+                    exited_events.send(TriggerExitedEvent {
+                        id: volume.id.clone(),
+                        entity,
+                    });
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::MeshPlugin;
+
+    use super::*;
+
+    #[test]
+    fn marker_names() {
+        assert_eq!(
+            MapMarker::from_name("Spawn.Player"),
+            Ok(Some(MapMarker::Spawn(SpawnPointKind::Player)))
+        );
+        assert_eq!(
+            MapMarker::from_name("Spawn.Enemy.Dummy.01"),
+            Ok(Some(MapMarker::Spawn(SpawnPointKind::Enemy(
+                "dummy".into()
+            ))))
+        );
+        assert_eq!(
+            MapMarker::from_name("Trigger.Encounter1"),
+            Ok(Some(MapMarker::Trigger("Encounter1".into())))
+        );
+        assert_eq!(
+            MapMarker::from_name("DamageZone.Fire.150%.02"),
+            Ok(Some(MapMarker::DamageZone {
+                variant: Some(DamageVariant::Fire),
+                percent: 150,
+            }))
+        );
+        assert_eq!(MapMarker::from_name("Plane"), Ok(None));
+        assert!(
+            MapMarker::from_name("Spawn.Boss").is_err(),
+            "Bad spawn point was let through."
+        );
+        assert!(
+            MapMarker::from_name("Trigger").is_err(),
+            "Trigger without an id was let through."
+        );
+        assert!(
+            MapMarker::from_name("DamageZone.Fire.150").is_err(),
+            "Damage zone without a percentage was let through."
+        );
+    }
+
+    fn node(app: &mut App, e_parent: Entity, name: &str, translation: Vec3) -> Entity {
+        let transform = Transform::from_translation(translation);
+        app.world
+            .spawn((
+                Name::new(name.to_string()),
+                TransformBundle {
+                    local: transform,
+                    global: transform.into(),
+                },
+            ))
+            .set_parent(e_parent)
+            .id()
+    }
+
+    #[test]
+    fn map_markers() {
+        let mut app = App::new();
+        app.add_plugins((AssetPlugin::default(), MeshPlugin))
+            .add_event::<CollisionEvent>()
+            .add_event::<TriggerEnteredEvent>()
+            .add_event::<TriggerExitedEvent>()
+            .add_systems(Startup, scan_map_markers)
+            .add_systems(Update, (init_trigger_volumes, fire_trigger_volumes).chain());
+
+        let e_map = app.world.spawn((Map, TransformBundle::default())).id();
+        let e_player = node(&mut app, e_map, "Spawn.Player", Vec3::X);
+        node(&mut app, e_map, "Spawn.Enemy.Dummy.02", Vec3::Z * 2.0);
+        node(&mut app, e_map, "Spawn.Enemy.Dummy.01", Vec3::Z);
+        let e_trigger = node(&mut app, e_map, "Trigger.Encounter1", Vec3::ZERO);
+        let mesh = app
+            .world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::new(2.0, 2.0, 2.0));
+        app.world
+            .spawn((mesh, TransformBundle::default()))
+            .set_parent(e_trigger);
+        app.update();
+
+        let mut points = app.world.query::<(&Name, &SpawnPoint)>();
+        assert_eq!(
+            SpawnPoint::player(points.iter(&app.world).map(|(_, p)| p)),
+            Some(Transform::from_translation(Vec3::X)),
+            "Player spawn point wasn't found."
+        );
+        assert_eq!(
+            SpawnPoint::enemies(points.iter(&app.world), "dummy"),
+            vec![
+                Transform::from_translation(Vec3::Z),
+                Transform::from_translation(Vec3::Z * 2.0),
+            ],
+            "Enemy spawn points weren't found in order."
+        );
+        assert_eq!(
+            app.world.get::<Visibility>(e_player),
+            Some(&Visibility::Hidden),
+            "Spawn point isn't hidden."
+        );
+        assert!(
+            app.world.get::<Sensor>(e_trigger).is_some(),
+            "Trigger didn't get a sensor."
+        );
+
+        // two body parts going in only counts once
+        let e_body = app.world.spawn_empty().id();
+        let parts = [0, 1].map(|_| app.world.spawn(Hitbox { target: e_body }).id());
+        for e_part in parts {
+            app.world.send_event(CollisionEvent::Started(
+                e_trigger,
+                e_part,
+                CollisionEventFlags::SENSOR,
+            ));
+        }
+        app.update();
+        let entered = app
+            .world
+            .resource_mut::<Events<TriggerEnteredEvent>>()
+            .drain()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entered,
+            vec![TriggerEnteredEvent {
+                id: "Encounter1".into(),
+                entity: e_body,
+            }],
+            "Entering wasn't sent once for the whole body."
+        );
+
+        app.world.send_event(CollisionEvent::Stopped(
+            parts[0],
+            e_trigger,
+            CollisionEventFlags::SENSOR,
+        ));
+        app.update();
+        assert!(
+            app.world
+                .resource::<Events<TriggerExitedEvent>>()
+                .is_empty(),
+            "Left while part of it was still inside."
+        );
+        app.world.send_event(CollisionEvent::Stopped(
+            parts[1],
+            e_trigger,
+            CollisionEventFlags::SENSOR,
+        ));
+        app.update();
+        assert_eq!(
+            app.world.resource::<Events<TriggerExitedEvent>>().len(),
+            1,
+            "Leaving wasn't sent."
+        );
+    }
+}
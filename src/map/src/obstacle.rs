@@ -0,0 +1,93 @@
+//! Things that get in the way after the navmesh is built.
+//!
+//! Landmass can't patch an island once it's built, so these don't touch the navmesh.
+//! Agents steer around them locally instead.
+
+use bevy::{ecs::entity::EntityHashSet, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+pub struct NavObstaclePlugin;
+
+impl Plugin for NavObstaclePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<NavObstacles>().add_systems(
+            PreUpdate,
+            (unregister_nav_obstacles, register_nav_obstacles).chain(),
+        );
+    }
+}
+
+/// Something agents should walk around. `shape` is positioned by the entity's `GlobalTransform`.
+#[derive(Component, Clone)]
+pub struct NavObstacle {
+    pub shape: Collider,
+    /// How much agents would rather not walk through it. `None` is impassable.
+    ///
+    /// Costs of `1.0` or less are ignored.
+    pub cost: Option<f32>,
+}
+
+impl NavObstacle {
+    pub fn solid(shape: Collider) -> Self {
+        Self { shape, cost: None }
+    }
+
+    /// How hard to steer away, from `0.0` to `1.0`.
+    pub fn avoidance(&self) -> f32 {
+        match self.cost {
+            Some(cost) => 1.0 - 1.0 / cost.max(1.0),
+            None => 1.0,
+        }
+    }
+}
+
+/// Every `NavObstacle` that's currently around.
+#[derive(Resource, Debug, Default)]
+pub struct NavObstacles(pub EntityHashSet);
+
+pub fn register_nav_obstacles(
+    mut obstacles: ResMut<NavObstacles>,
+    obstacle_query: Query<Entity, Added<NavObstacle>>,
+) {
+    for e_obstacle in obstacle_query.iter() {
+        obstacles.0.insert(e_obstacle);
+    }
+}
+
+/// Also catches despawns.
+pub fn unregister_nav_obstacles(
+    mut obstacles: ResMut<NavObstacles>,
+    mut removed: RemovedComponents<NavObstacle>,
+) {
+    for e_obstacle in removed.read() {
+        obstacles.0.remove(&e_obstacle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn obstacle_registry() {
+        let mut app = App::new();
+        app.add_plugins(NavObstaclePlugin);
+
+        let e_obstacle = app
+            .world
+            .spawn(NavObstacle::solid(Collider::ball(1.0)))
+            .id();
+        app.update();
+        assert!(
+            app.world.resource::<NavObstacles>().0.contains(&e_obstacle),
+            "Obstacle wasn't registered."
+        );
+
+        app.world.despawn(e_obstacle);
+        app.update();
+        assert!(
+            app.world.resource::<NavObstacles>().0.is_empty(),
+            "Obstacle wasn't unregistered."
+        );
+    }
+}
@@ -0,0 +1,279 @@
+//! Map props that break.
+
+use std::time::Duration;
+
+use bevy::{prelude::*, transform::commands::BuildChildrenTransformExt};
+use bevy_rapier3d::prelude::*;
+use bevy_tweening::{lens::TransformScaleLens, Animator, Delay, EaseFunction, Tween};
+use grin_damage::health::{Dead, Health, HealthBundle};
+use grin_physics::{collider, CollisionGroupExt, CollisionGroupsExt};
+use grin_rig::humanoid::Shattered;
+use grin_time::CommandsExt;
+use grin_util::{
+    event::TweenCompletedEvent,
+    rng::{GrinRng, RNG_DEBRIS},
+};
+use rand::{distributions::Uniform, Rng};
+
+use crate::obstacle::NavObstacle;
+
+/// Map nodes named like `Destructible_crate` become `Destructible`s.
+///
+/// Fragments are at `meshes/crate_shatter.glb`. Blender's `.001` suffixes are ignored.
+pub const DESTRUCTIBLE_PREFIX: &str = "Destructible_";
+
+/// `Destructible::health` for props from the map.
+pub const PROP_HEALTH: f32 = 30.0;
+
+/// Seconds before fragments start shrinking.
+pub const FRAGMENT_DECAY: f32 = 5.0;
+
+/// Seconds that fragments take to shrink away.
+pub const FRAGMENT_SHRINK: f32 = 0.5;
+
+pub struct DestructiblePlugin;
+
+impl Plugin for DestructiblePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GrinRng>().add_systems(
+            Update,
+            (
+                init_destructibles,
+                shatter_destructibles,
+                // scenes spawn in preupdate, so this waits a frame anyways
+                init_prop_fragments,
+            ),
+        );
+    }
+}
+
+/// Breaks into `fragments` when `Dead`. Agents walk around its `Collider` until then.
+#[derive(Component, Debug)]
+pub struct Destructible {
+    /// Starting health.
+    pub health: Health,
+    pub fragments: Handle<Scene>,
+    /// Fragments fly outwards at a random speed within this range.
+    pub impulse_range: (f32, f32),
+    /// Seconds before fragments start shrinking away.
+    pub decay: f32,
+}
+
+impl Destructible {
+    pub fn new(health: f32, fragments: Handle<Scene>) -> Self {
+        Self {
+            health: Health(health),
+            fragments,
+            impulse_range: (2.0, 6.0),
+            decay: FRAGMENT_DECAY,
+        }
+    }
+
+    /// Creates a `Destructible` for a map node named with `DESTRUCTIBLE_PREFIX`.
+    pub fn from_name(name: &str, asset_server: &AssetServer) -> Option<Self> {
+        let key = name.strip_prefix(DESTRUCTIBLE_PREFIX)?;
+        let key = key.split('.').next().unwrap_or(key);
+        Some(Self::new(
+            PROP_HEALTH,
+            asset_server.load(format!("meshes/{key}_shatter.glb#Scene0")),
+        ))
+    }
+}
+
+/// Fragment scene that hasn't been set up yet.
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct PropShatter {
+    pub prop: Entity,
+    pub speed: Uniform<f32>,
+    pub decay: f32,
+}
+
+pub fn init_destructibles(
+    mut commands: Commands,
+    prop_query: Query<(Entity, &Destructible, Option<&Collider>), Added<Destructible>>,
+) {
+    for (e_prop, destructible, collider) in prop_query.iter() {
+        let mut e_prop_commands = commands.entity(e_prop);
+        e_prop_commands.insert(HealthBundle {
+            health: Health(destructible.health.0),
+            ..Default::default()
+        });
+        if let Some(collider) = collider {
+            e_prop_commands.insert(NavObstacle::solid(collider.clone()));
+        }
+    }
+}
+
+/// Swaps the mesh of `Dead` props for their fragments.
+pub fn shatter_destructibles(
+    mut commands: Commands,
+    prop_query: Query<(Entity, &Destructible, &GlobalTransform), (With<Dead>, Without<Shattered>)>,
+) {
+    for (e_prop, destructible, g_transform) in prop_query.iter() {
+        let (min, max) = destructible.impulse_range;
+        commands
+            .entity(e_prop)
+            .insert(Shattered)
+            .remove::<(Handle<Mesh>, Collider, NavObstacle)>();
+        commands
+            .spawn((
+                PropShatter {
+                    prop: e_prop,
+                    speed: Uniform::new_inclusive(min, max.max(min)),
+                    decay: destructible.decay,
+                },
+                SceneBundle {
+                    scene: destructible.fragments.clone(),
+                    transform: g_transform.compute_transform(),
+                    ..Default::default()
+                },
+            ))
+            .set_time_parent(e_prop);
+    }
+}
+
+/// Turns each mesh in a `PropShatter` scene into debris, then throws out the scene.
+///
+/// Fragments are taken out of the scene so they're in global space, and shrink away after
+/// `PropShatter::decay`.
+pub fn init_prop_fragments(
+    mut commands: Commands,
+    scene_query: Query<(Entity, &PropShatter, &GlobalTransform), With<Children>>,
+    children_query: Query<&Children>,
+    mesh_query: Query<(&Handle<Mesh>, &GlobalTransform)>,
+    meshes: Res<Assets<Mesh>>,
+    mut rng: ResMut<GrinRng>,
+) {
+    for (e_scene, shatter, g_scene_transform) in scene_query.iter() {
+        let center = g_scene_transform.translation();
+        for e_fragment in children_query.iter_descendants(e_scene) {
+            let Ok((mesh, g_transform)) = mesh_query.get(e_fragment) else {
+                continue;
+            };
+
+            let (scale, _, _) = g_transform.to_scale_rotation_translation();
+            let direction = (g_transform.translation() - center)
+                .try_normalize()
+                .unwrap_or(Vec3::Y);
+            let shrink = Delay::new(Duration::from_secs_f32(shatter.decay)).then(
+                Tween::new(
+                    EaseFunction::QuadraticIn,
+                    Duration::from_secs_f32(FRAGMENT_SHRINK),
+                    TransformScaleLens {
+                        start: scale,
+                        end: Vec3::ZERO,
+                    },
+                )
+                .with_completed_event(TweenCompletedEvent::Despawn as u64),
+            );
+
+            commands
+                .entity(e_fragment)
+                .remove_parent_in_place()
+                .insert((
+                    RigidBody::Dynamic,
+                    CollisionGroups::from_group_default(Group::DEBRIS),
+                    collider!(meshes, mesh),
+                    Velocity::linear(direction * rng.fork(RNG_DEBRIS).sample(shatter.speed)),
+                    Animator::new(shrink),
+                ))
+                .set_time_parent(shatter.prop);
+        }
+        commands.entity(e_scene).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{render::mesh::MeshPlugin, scene::ScenePlugin};
+
+    use super::*;
+
+    #[test]
+    fn destructible() {
+        let mut app = App::new();
+        app.add_plugins((AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins(DestructiblePlugin);
+
+        let mesh = app
+            .world
+            .resource_mut::<Assets<Mesh>>()
+            .add(Cuboid::new(1.0, 1.0, 1.0));
+        let e_prop = app
+            .world
+            .spawn((
+                Destructible::new(10.0, Handle::default()),
+                mesh.clone(),
+                Collider::cuboid(1.0, 1.0, 1.0),
+                TransformBundle::default(),
+            ))
+            .id();
+
+        app.update();
+        assert_eq!(
+            app.world.get::<Health>(e_prop).map(|h| h.0),
+            Some(10.0),
+            "Prop didn't get health."
+        );
+        assert!(
+            app.world.get::<NavObstacle>(e_prop).is_some(),
+            "Prop isn't a `NavObstacle`."
+        );
+
+        app.world.entity_mut(e_prop).insert(Dead);
+        app.update();
+        assert!(
+            app.world.get::<Collider>(e_prop).is_none()
+                && app.world.get::<Handle<Mesh>>(e_prop).is_none(),
+            "Prop wasn't swapped out."
+        );
+        assert!(
+            app.world.get::<NavObstacle>(e_prop).is_none(),
+            "Broken prop is still in the way."
+        );
+
+        // stand in for the fragment scene, since it's not actually getting loaded
+        let e_scene = app
+            .world
+            .query_filtered::<Entity, With<PropShatter>>()
+            .single(&app.world);
+        let fragments = [Vec3::X, Vec3::NEG_X].map(|translation| {
+            let transform = Transform::from_translation(translation);
+            app.world
+                .spawn((
+                    mesh.clone(),
+                    TransformBundle {
+                        local: transform,
+                        global: transform.into(),
+                    },
+                ))
+                .set_parent(e_scene)
+                .id()
+        });
+        app.update();
+
+        assert!(
+            app.world.get_entity(e_scene).is_none(),
+            "Fragment scene wasn't cleaned up."
+        );
+        for (e_fragment, direction) in fragments.into_iter().zip([Vec3::X, Vec3::NEG_X]) {
+            let fragment = app.world.entity(e_fragment);
+            assert!(
+                fragment.get::<Parent>().is_none(),
+                "Fragment wasn't moved to global space."
+            );
+            assert_eq!(
+                fragment.get::<CollisionGroups>().map(|g| g.memberships),
+                Some(Group::DEBRIS),
+                "Fragment isn't debris."
+            );
+            assert!(
+                fragment
+                    .get::<Velocity>()
+                    .is_some_and(|v| v.linvel.dot(direction) > 0.0),
+                "Fragment didn't fly outwards."
+            );
+        }
+    }
+}
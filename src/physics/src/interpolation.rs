@@ -0,0 +1,219 @@
+//! Smooths out kinematic bodies when physics and rendering run at different rates.
+
+use bevy::{prelude::*, transform::TransformSystem};
+use bevy_rapier3d::prelude::*;
+use grin_time::Rewind;
+
+pub struct KinematicInterpolationPlugin;
+
+impl Plugin for KinematicInterpolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PhysicsInterpolation>().add_systems(
+            PostUpdate,
+            (
+                update_physics_interpolation,
+                record_physics_transforms,
+                interpolate_render_transforms,
+            )
+                .chain()
+                .after(PhysicsSet::Writeback)
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+/// Where rendering is between the last two physics steps.
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct PhysicsInterpolation {
+    /// Whether physics stepped this frame.
+    pub stepped: bool,
+    /// `0.0` is the previous step, `1.0` is the latest one.
+    pub alpha: f32,
+}
+
+impl Default for PhysicsInterpolation {
+    fn default() -> Self {
+        Self {
+            stepped: true,
+            alpha: 1.0,
+        }
+    }
+}
+
+/// Renders a kinematic body between physics steps.
+///
+/// Rapier's `TransformInterpolation` is the equivalent for dynamic bodies.
+///
+/// The body's `Transform` is left alone so none of this feeds back into physics. Instead, `render`
+/// (a child of the body, like the armature) is offset to where the body should appear. Anything
+/// else that moves `render` should move `render_base` instead.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct KinematicInterpolation {
+    pub render: Entity,
+    /// `render`'s local transform when there's no offset.
+    pub render_base: Transform,
+    /// The body at the step before `current`.
+    pub previous: Option<Transform>,
+    /// The body at the latest physics step.
+    pub current: Option<Transform>,
+}
+
+impl KinematicInterpolation {
+    pub fn new(render: Entity, render_base: Transform) -> Self {
+        Self {
+            render,
+            render_base,
+            previous: None,
+            current: None,
+        }
+    }
+
+    /// Where the body should appear at `alpha`.
+    pub fn lerp(&self, alpha: f32) -> Option<Transform> {
+        let current = self.current?;
+        let previous = self.previous.unwrap_or(current);
+        Some(Transform {
+            translation: previous.translation.lerp(current.translation, alpha),
+            rotation: previous.rotation.slerp(current.rotation, alpha),
+            scale: previous.scale.lerp(current.scale, alpha),
+        })
+    }
+}
+
+/// Updates `PhysicsInterpolation`.
+///
+/// Rapier steps while `SimulationToRenderTime` is positive, and leaves it in `(-dt, 0.0]`. That's
+/// how far rendering is behind the latest step. The other timestep modes step every frame.
+pub fn update_physics_interpolation(
+    mut last_diff: Local<f32>,
+    time: Res<Time>,
+    rapier_config: Res<RapierConfiguration>,
+    sim_to_render_time: Res<SimulationToRenderTime>,
+    mut interpolation: ResMut<PhysicsInterpolation>,
+) {
+    *interpolation = match rapier_config.timestep_mode {
+        TimestepMode::Interpolated { dt, .. } => PhysicsInterpolation {
+            stepped: *last_diff + time.delta_seconds() > 0.0,
+            alpha: ((dt + sim_to_render_time.diff) / dt).clamp(0.0, 1.0),
+        },
+        _ => PhysicsInterpolation::default(),
+    };
+    *last_diff = sim_to_render_time.diff;
+}
+
+/// Saves body transforms on frames that physics stepped.
+pub fn record_physics_transforms(
+    interpolation: Res<PhysicsInterpolation>,
+    mut body_query: Query<(&mut KinematicInterpolation, &Transform), Without<Rewind>>,
+) {
+    if !interpolation.stepped {
+        return;
+    }
+
+    for (mut body, transform) in body_query.iter_mut() {
+        body.previous = body.current.or(Some(*transform));
+        body.current = Some(*transform);
+    }
+}
+
+/// Offsets `KinematicInterpolation::render` to the interpolated transform.
+///
+/// Rewinds snap back to the body, since the recorded steps don't mean anything anymore.
+pub fn interpolate_render_transforms(
+    interpolation: Res<PhysicsInterpolation>,
+    mut body_query: Query<(&mut KinematicInterpolation, &Transform, Has<Rewind>)>,
+    mut render_query: Query<&mut Transform, Without<KinematicInterpolation>>,
+) {
+    for (mut body, transform, rewinding) in body_query.iter_mut() {
+        let Ok(mut render_transform) = render_query.get_mut(body.render) else {
+            continue;
+        };
+
+        if rewinding {
+            body.previous = None;
+            body.current = None;
+        }
+
+        *render_transform = match body.lerp(interpolation.alpha) {
+            Some(interpolated) => {
+                // local to the body's actual transform
+                Transform::from_matrix(
+                    transform.compute_matrix().inverse() * interpolated.compute_matrix(),
+                ) * body.render_base
+            }
+            None => body.render_base,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use crate::new_physics_app;
+
+    use super::*;
+
+    #[test]
+    fn kinematic_interpolation() {
+        const PHYSICS_DT: f32 = 1.0 / 30.0;
+        const RENDER_DT: f32 = 1.0 / 144.0;
+        const SPEED: f32 = 1.0;
+
+        let mut app = new_physics_app();
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Interpolated {
+                dt: PHYSICS_DT,
+                time_scale: 1.0,
+                substeps: 1,
+            },
+            ..Default::default()
+        })
+        .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+            RENDER_DT,
+        )))
+        .add_plugins(KinematicInterpolationPlugin);
+
+        let e_render = app.world.spawn(TransformBundle::default()).id();
+        let e_body = app
+            .world
+            .spawn((
+                RigidBody::KinematicVelocityBased,
+                Velocity::linear(Vec3::X * SPEED),
+                TransformBundle::default(),
+                KinematicInterpolation::new(e_render, Transform::default()),
+            ))
+            .add_child(e_render)
+            .id();
+
+        let mut rendered = Vec::new();
+        for _ in 0..60 {
+            app.update();
+
+            let body = *app.world.get::<Transform>(e_body).unwrap();
+            let render = *app.world.get::<Transform>(e_render).unwrap();
+            let steps = body.translation.x / (SPEED * PHYSICS_DT);
+            assert!(
+                (steps - steps.round()).abs() < 1E-3,
+                "Interpolation got fed back into the body. ({})",
+                body.translation.x,
+            );
+            rendered.push((body * render).translation.x);
+        }
+
+        // skip the first couple of steps while it gets going
+        let deltas = rendered[16..]
+            .windows(2)
+            .map(|w| w[1] - w[0])
+            .collect::<Vec<_>>();
+        assert!(
+            deltas
+                .iter()
+                .all(|dx| (dx - SPEED * RENDER_DT).abs() < 1E-4),
+            "Stuttered. {:?}",
+            deltas,
+        );
+    }
+}
@@ -1,8 +1,20 @@
+pub mod interpolation;
+pub mod platform;
+pub mod volume;
+
 use std::time::Duration;
 
-use bevy::{ecs::system::SystemParam, prelude::*, time::TimeSystem};
+use bevy::{
+    ecs::system::{EntityCommand, EntityCommands, SystemParam},
+    prelude::*,
+    time::TimeSystem,
+    transform::commands::BuildChildrenTransformExt,
+};
 use bevy_rapier3d::prelude::*;
 use grin_time::scaling::TimeScale;
+use interpolation::KinematicInterpolationPlugin;
+use platform::{DropThrough, MovingPlatformPlugin, OneWayPlatform, OneWayPlatformPlugin};
+use volume::MovementVolumePlugin;
 
 #[derive(Default)]
 pub struct GrinPhysicsPlugin {
@@ -19,6 +31,10 @@ impl Plugin for GrinPhysicsPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<PhysicsTime>()
             .add_plugins((
+                KinematicInterpolationPlugin,
+                OneWayPlatformPlugin,
+                MovingPlatformPlugin,
+                MovementVolumePlugin,
                 RapierPhysicsPlugin::<GrinPhysicsHooks>::default(),
                 RapierDebugRenderPlugin {
                     enabled: self.debug_enabled,
@@ -26,7 +42,6 @@ impl Plugin for GrinPhysicsPlugin {
                     mode: self.debug_mode,
                 },
             ))
-            .init_resource::<GrinPhysicsHooks>()
             .insert_resource(RapierConfiguration {
                 // gravity is scaled by human height / humanoid height.
                 // it's a magic number. I don't want to import `grin_character`.
@@ -42,12 +57,20 @@ impl Plugin for GrinPhysicsPlugin {
             .add_systems(
                 Update,
                 (
-                    assign_collider_ref_transforms,
-                    assign_collider_ref_collision_groups,
-                ),
+                    resolve_dangling_collider_refs,
+                    (
+                        assign_collider_ref_transforms,
+                        assign_collider_ref_collision_groups,
+                    ),
+                )
+                    .chain(),
             )
             .add_systems(First, write_physics_time.after(TimeSystem))
-            .add_systems(Last, (update_force_timers, kill_timed_forces).chain());
+            .add_systems(Last, (update_force_timers, kill_timed_forces).chain())
+            .add_systems(
+                PostUpdate,
+                update_timed_force_stacks.before(PhysicsSet::SyncBackend),
+            );
     }
 }
 
@@ -55,12 +78,47 @@ impl Plugin for GrinPhysicsPlugin {
 ///
 /// This is important for computing contact points for melee weapons, without the constraints
 /// solver interfering.
-#[derive(Resource, SystemParam, Default)]
-pub struct GrinPhysicsHooks;
+///
+/// `OneWayPlatform`s are also solved here.
+#[derive(SystemParam)]
+pub struct GrinPhysicsHooks<'w, 's> {
+    platform_query: Query<'w, 's, &'static OneWayPlatform>,
+    drop_query: Query<'w, 's, (), With<DropThrough>>,
+    parent_query: Query<'w, 's, &'static Parent>,
+}
+
+impl BevyPhysicsHooks for GrinPhysicsHooks<'_, '_> {
+    fn filter_contact_pair(&self, context: PairFilterContextView) -> Option<SolverFlags> {
+        let colliders = [
+            (context.collider1(), context.raw.collider1),
+            (context.collider2(), context.raw.collider2),
+        ];
+        let Some(i) = colliders
+            .iter()
+            .position(|(e, _)| self.platform_query.contains(*e))
+        else {
+            // melee weapons
+            return Some(SolverFlags::empty());
+        };
+
+        let platform = self.platform_query.get(colliders[i].0).unwrap();
+        let (e_other, h_other) = colliders[1 - i];
+        if self.drop_query.contains(e_other)
+            || self
+                .parent_query
+                .iter_ancestors(e_other)
+                .any(|e| self.drop_query.contains(e))
+        {
+            return None;
+        }
 
-impl BevyPhysicsHooks for GrinPhysicsHooks {
-    fn filter_contact_pair(&self, _context: PairFilterContextView) -> Option<SolverFlags> {
-        Some(SolverFlags::empty())
+        let raw_colliders = context.raw.colliders;
+        platform
+            .blocks(
+                &raw_colliders[colliders[i].1].compute_aabb(),
+                &raw_colliders[h_other].compute_aabb(),
+            )
+            .then_some(SolverFlags::COMPUTE_IMPULSES)
     }
 }
 
@@ -71,6 +129,7 @@ pub trait CollisionGroupExt {
     const ENEMY_PROJECTILE: Group;
     const DEBRIS: Group;
     const MAP: Group;
+    const ONE_WAY: Group;
     const PROJECTILE: Group;
 }
 
@@ -81,6 +140,7 @@ impl CollisionGroupExt for Group {
     const ENEMY_PROJECTILE: Group = Self::GROUP_4;
     const DEBRIS: Group = Self::GROUP_5;
     const MAP: Group = Self::GROUP_32;
+    const ONE_WAY: Group = Self::GROUP_31;
     // well this is weird... thanks rust
     // https://github.com/bitflags/bitflags/issues/180
     const PROJECTILE: Group = Self::PLAYER_PROJECTILE.union(Self::ENEMY_PROJECTILE);
@@ -107,6 +167,7 @@ impl CollisionGroupsExt for CollisionGroups {
             ),
             Group::DEBRIS => CollisionGroups::new(Group::DEBRIS, Group::MAP),
             Group::MAP => CollisionGroups::new(Group::MAP, Group::all()),
+            Group::ONE_WAY => CollisionGroups::new(Group::ONE_WAY, Group::all()),
             _ => CollisionGroups::default(),
         }
     }
@@ -186,6 +247,8 @@ pub fn write_physics_time(
 ) {
     // just found out about `SimulationToRenderTime`. hm. whoops.
     let dt = match rapier_config.timestep_mode {
+        // paused
+        _ if !rapier_config.physics_pipeline_active => 0.0,
         TimestepMode::Fixed { dt, .. } => dt,
         // as far as I'm concerned here, they're the same.
         TimestepMode::Variable {
@@ -206,36 +269,88 @@ pub fn write_physics_time(
         .update_with_instant(last_update + Duration::from_secs_f32(dt));
 }
 
+/// How a timed force fades out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ForceEasing {
+    Linear,
+    /// Drops off fast, then trails.
+    CubicOut,
+    /// Full force for the first `hold` fraction of the duration, then fades linearly.
+    /// `1.0` cuts off all at once at the end.
+    HoldThenDrop {
+        hold: f32,
+    },
+}
+
+impl Default for ForceEasing {
+    fn default() -> Self {
+        Self::HoldThenDrop { hold: 1.0 }
+    }
+}
+
+impl ForceEasing {
+    /// Force multiplier, `fraction` of the way through.
+    pub fn sample(&self, fraction: f32) -> f32 {
+        let fraction = fraction.clamp(0.0, 1.0);
+        match *self {
+            Self::Linear => 1.0 - fraction,
+            Self::CubicOut => (1.0 - fraction).powi(3),
+            Self::HoldThenDrop { hold } => match fraction < hold {
+                true => 1.0,
+                false if hold >= 1.0 => 0.0,
+                false => 1.0 - (fraction - hold) / (1.0 - hold),
+            },
+        }
+    }
+}
+
 /// Add to an `ExternalForce`. At the end of this component's duration,
 /// the `ExternalForce` is stopped and this component is removed.
+///
+/// The force is scaled down over time with `easing`, from whatever it was on the first tick.
 #[derive(Component)]
 pub struct ForceTimer {
     pub timer: Timer,
+    pub easing: ForceEasing,
+    initial: Option<ExternalForce>,
 }
 
 impl ForceTimer {
     pub fn new(duration: Duration) -> Self {
         Self {
             timer: Timer::new(duration, TimerMode::Once),
+            easing: ForceEasing::default(),
+            initial: None,
         }
     }
 
     pub fn from_seconds(duration: f32) -> Self {
-        Self {
-            timer: Timer::from_seconds(duration, TimerMode::Once),
-        }
+        Self::new(Duration::from_secs_f32(duration))
+    }
+
+    pub fn with_easing(mut self, easing: ForceEasing) -> Self {
+        self.easing = easing;
+        self
     }
 }
 
-/// Updates `ForceTimer` durations.
+/// Updates `ForceTimer` durations and eases their `ExternalForce`s.
 pub fn update_force_timers(
     time: Res<PhysicsTime>,
-    mut timer_query: Query<(&mut ForceTimer, &TimeScale)>,
+    mut timer_query: Query<(&mut ForceTimer, &TimeScale, Option<&mut ExternalForce>)>,
 ) {
-    for (mut timer, time_scale) in timer_query.iter_mut() {
+    for (mut timer, time_scale, force) in timer_query.iter_mut() {
         timer
             .timer
             .tick(time.0.delta().mul_f32(f32::from(time_scale)));
+
+        let Some(mut force) = force else {
+            continue;
+        };
+        let initial = *timer.initial.get_or_insert(*force);
+        let scale = timer.easing.sample(timer.timer.fraction());
+        force.force = initial.force * scale;
+        force.torque = initial.torque * scale;
     }
 }
 
@@ -253,6 +368,82 @@ pub fn kill_timed_forces(
     }
 }
 
+/// One force in a `TimedForceStack`.
+#[derive(Clone, Debug)]
+pub struct TimedForce {
+    pub force: Vec3,
+    pub torque: Vec3,
+    pub timer: Timer,
+    pub easing: ForceEasing,
+}
+
+impl TimedForce {
+    pub fn new(force: Vec3, duration: Duration, easing: ForceEasing) -> Self {
+        Self {
+            force,
+            torque: Vec3::ZERO,
+            timer: Timer::new(duration, TimerMode::Once),
+            easing,
+        }
+    }
+
+    pub fn with_torque(mut self, torque: Vec3) -> Self {
+        self.torque = torque;
+        self
+    }
+
+    /// The eased force and torque right now.
+    pub fn current(&self) -> ExternalForce {
+        let scale = self.easing.sample(self.timer.fraction());
+        ExternalForce {
+            force: self.force * scale,
+            torque: self.torque * scale,
+        }
+    }
+}
+
+/// Multiple `ForceTimer`s, basically. Their sum is written to the entity's `ExternalForce`,
+/// so overlapping knockbacks don't clobber each other. Without an `ExternalForce`, whatever
+/// added it reads `sum` itself.
+///
+/// Don't use a `ForceTimer` on the same entity.
+#[derive(Component, Clone, Debug, Default)]
+pub struct TimedForceStack(pub Vec<TimedForce>);
+
+impl TimedForceStack {
+    pub fn push(&mut self, force: TimedForce) {
+        self.0.push(force);
+    }
+
+    pub fn sum(&self) -> ExternalForce {
+        self.0
+            .iter()
+            .map(TimedForce::current)
+            .fold(ExternalForce::default(), |acc, f| ExternalForce {
+                force: acc.force + f.force,
+                torque: acc.torque + f.torque,
+            })
+    }
+}
+
+/// Ticks `TimedForceStack`s, forgets the finished ones, and writes the rest to `ExternalForce`.
+pub fn update_timed_force_stacks(
+    time: Res<PhysicsTime>,
+    mut stack_query: Query<(
+        &mut TimedForceStack,
+        Option<&mut ExternalForce>,
+        Option<&TimeScale>,
+    )>,
+) {
+    for (mut stack, force, time_scale) in stack_query.iter_mut() {
+        let delta = time.0.delta().mul_f32(time_scale.map_or(1.0, f32::from));
+        stack.0.retain_mut(|f| !f.timer.tick(delta).finished());
+        if let Some(mut force) = force {
+            *force = stack.sum();
+        }
+    }
+}
+
 // NOTE: it turns out that this was unnecessary
 // but hell, I wrote it, and might need it in my belt later, so it'll sit for now
 
@@ -261,7 +452,55 @@ pub fn kill_timed_forces(
 /// A jank solution to combined colliders of a rigid-body
 /// needing to have direct children as colliders.
 #[derive(Component)]
-pub struct ColliderRef(pub Entity);
+pub struct ColliderRef {
+    pub source: Entity,
+    /// What happens when `source` is despawned.
+    pub policy: ColliderRefPolicy,
+}
+
+impl ColliderRef {
+    pub fn new(source: Entity) -> Self {
+        Self {
+            source,
+            policy: ColliderRefPolicy::default(),
+        }
+    }
+}
+
+/// What a `ColliderRef` does after its source is despawned.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColliderRefPolicy {
+    /// Despawns the ref along with its children.
+    #[default]
+    Despawn,
+    /// Detaches the ref and leaves it where it was, as a regular collider.
+    Freeze,
+}
+
+/// Turns a `ColliderRef` back into a regular collider. It stays where it is in world space.
+pub struct RemoveColliderRef;
+
+impl EntityCommand for RemoveColliderRef {
+    fn apply(self, entity: Entity, world: &mut World) {
+        let Some(mut e) = world.get_entity_mut(entity) else {
+            return;
+        };
+        if e.take::<ColliderRef>().is_some() {
+            e.remove_parent_in_place();
+        }
+    }
+}
+
+pub trait ColliderRefCommandsExt {
+    fn remove_collider_ref(&mut self);
+}
+
+impl<'a> ColliderRefCommandsExt for EntityCommands<'a> {
+    /// Detaches the `ColliderRef` from its source.
+    fn remove_collider_ref(&mut self) {
+        self.add(RemoveColliderRef);
+    }
+}
 
 #[derive(Bundle)]
 pub struct ColliderRefBundle {
@@ -272,17 +511,46 @@ pub struct ColliderRefBundle {
     pub global_transform: Transform,
 }
 
+/// Applies `ColliderRefPolicy` to `ColliderRef`s whose source is gone.
+///
+/// The ref is dealt with here, so this only warns once per ref.
+pub fn resolve_dangling_collider_refs(
+    mut commands: Commands,
+    collider_ref_query: Query<(Entity, &ColliderRef)>,
+    source_query: Query<(), With<GlobalTransform>>,
+) {
+    for (e_ref, collider_ref) in collider_ref_query.iter() {
+        if source_query.contains(collider_ref.source) {
+            continue;
+        }
+
+        warn!(
+            msg="`ColliderRef` source is gone.",
+            e_ref=?e_ref,
+            source=?collider_ref.source,
+            policy=?collider_ref.policy,
+        );
+        match collider_ref.policy {
+            ColliderRefPolicy::Despawn => commands.entity(e_ref).despawn_recursive(),
+            ColliderRefPolicy::Freeze => commands.entity(e_ref).remove_collider_ref(),
+        }
+    }
+}
+
 /// Updates the `Transform`s of `ColliderRef`s to match the source entity.
 pub fn assign_collider_ref_transforms(
     mut collider_ref_query: Query<(&ColliderRef, Option<&Parent>, &mut Transform)>,
     g_transform_query: Query<&GlobalTransform>,
 ) {
-    for (ColliderRef(e_collider), parent, mut transform) in collider_ref_query.iter_mut() {
-        let g_parent_transform = match parent {
-            Some(parent) => *g_transform_query.get(parent.get()).unwrap(),
-            None => GlobalTransform::default(),
+    for (collider_ref, parent, mut transform) in collider_ref_query.iter_mut() {
+        // dangling refs are handled by `resolve_dangling_collider_refs`
+        let Ok(g_collider_transform) = g_transform_query.get(collider_ref.source) else {
+            continue;
         };
-        let g_collider_transform = g_transform_query.get(*e_collider).unwrap();
+        let g_parent_transform = parent
+            .and_then(|p| g_transform_query.get(p.get()).ok())
+            .copied()
+            .unwrap_or_default();
         *transform = g_collider_transform.reparented_to(&g_parent_transform);
     }
 }
@@ -292,8 +560,8 @@ pub fn assign_collider_ref_collision_groups(
     mut collider_ref_query: Query<(&ColliderRef, &mut CollisionGroups)>,
     collision_groups_query: Query<&CollisionGroups, Without<ColliderRef>>,
 ) {
-    for (ColliderRef(e_collider), mut collision_groups) in collider_ref_query.iter_mut() {
-        if let Ok(new_collision_groups) = collision_groups_query.get(*e_collider) {
+    for (collider_ref, mut collision_groups) in collider_ref_query.iter_mut() {
+        if let Ok(new_collision_groups) = collision_groups_query.get(collider_ref.source) {
             *collision_groups = *new_collision_groups;
         };
     }
@@ -382,4 +650,149 @@ mod tests {
             "`ExternalForce.torque` wasn't reset.",
         );
     }
+
+    #[test]
+    fn timed_force_stack() {
+        let mut app = App::new();
+        app.init_resource::<PhysicsTime>()
+            .add_systems(Update, update_timed_force_stacks);
+
+        let e = app
+            .world
+            .spawn((
+                ExternalForce::default(),
+                TimedForceStack(vec![TimedForce::new(
+                    Vec3::X * 10.0,
+                    Duration::from_secs(2),
+                    ForceEasing::Linear,
+                )]),
+            ))
+            .id();
+
+        let step = |app: &mut App| {
+            app.world
+                .resource_mut::<PhysicsTime>()
+                .0
+                .advance_by(Duration::from_secs_f32(0.5));
+            app.update();
+            let force = app.world.get::<ExternalForce>(e).unwrap().force.x;
+            let len = app.world.get::<TimedForceStack>(e).unwrap().0.len();
+            (force, len)
+        };
+
+        let (force, _) = step(&mut app);
+        assert!((force - 7.5).abs() < 1E-4, "Wrong fade. ({})", force);
+
+        // second one starts halfway through the first
+        app.world
+            .get_mut::<TimedForceStack>(e)
+            .unwrap()
+            .push(TimedForce::new(
+                Vec3::X * 6.0,
+                Duration::from_secs(1),
+                ForceEasing::Linear,
+            ));
+
+        let (force, len) = step(&mut app);
+        assert!(
+            (force - 8.0).abs() < 1E-4,
+            "Forces didn't add up. ({})",
+            force
+        );
+        assert_eq!(len, 2);
+
+        let (force, len) = step(&mut app);
+        assert!(
+            (force - 2.5).abs() < 1E-4,
+            "Forces didn't add up. ({})",
+            force
+        );
+        assert_eq!(len, 1, "Finished force wasn't removed.");
+
+        let (force, len) = step(&mut app);
+        assert!(force.abs() < 1E-4, "Force didn't fade out. ({})", force);
+        assert_eq!(len, 0, "Finished force wasn't removed.");
+    }
+
+    fn collider_ref_app(policy: ColliderRefPolicy) -> (App, Entity, Entity) {
+        let mut app = App::new();
+        app.add_plugins(TransformPlugin).add_systems(
+            Update,
+            (
+                resolve_dangling_collider_refs,
+                assign_collider_ref_transforms,
+            )
+                .chain(),
+        );
+
+        let e_source = app
+            .world
+            .spawn(TransformBundle::from_transform(Transform::from_xyz(
+                1.0, 2.0, 3.0,
+            )))
+            .id();
+        let e_parent = app
+            .world
+            .spawn(TransformBundle::from_transform(Transform::from_xyz(
+                -1.0, 0.0, 0.0,
+            )))
+            .id();
+        let e_ref = app
+            .world
+            .spawn((
+                ColliderRef {
+                    source: e_source,
+                    policy,
+                },
+                TransformBundle::default(),
+            ))
+            .set_parent(e_parent)
+            .id();
+
+        app.update();
+        app.update();
+        (app, e_source, e_ref)
+    }
+
+    #[test]
+    fn collider_ref_despawn() {
+        let (mut app, e_source, e_ref) = collider_ref_app(ColliderRefPolicy::Despawn);
+        app.world.despawn(e_source);
+        app.update();
+        app.update();
+
+        assert!(
+            app.world.get_entity(e_ref).is_none(),
+            "Dangling `ColliderRef` wasn't despawned."
+        );
+    }
+
+    #[test]
+    fn collider_ref_freeze() {
+        let (mut app, e_source, e_ref) = collider_ref_app(ColliderRefPolicy::Freeze);
+        assert_eq!(
+            app.world
+                .get::<GlobalTransform>(e_ref)
+                .unwrap()
+                .translation(),
+            Vec3::new(1.0, 2.0, 3.0),
+            "`ColliderRef` didn't follow the source.",
+        );
+
+        app.world.despawn(e_source);
+        app.update();
+        app.update();
+
+        let e = app.world.entity(e_ref);
+        assert!(
+            !e.contains::<ColliderRef>(),
+            "Dangling `ColliderRef` wasn't removed."
+        );
+        assert!(!e.contains::<Parent>(), "Frozen collider wasn't detached.");
+        assert_eq!(
+            e.get::<Transform>().unwrap().translation,
+            Vec3::new(1.0, 2.0, 3.0),
+            "Frozen collider moved.",
+        );
+    }
 }
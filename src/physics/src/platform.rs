@@ -0,0 +1,451 @@
+//! Platforms that can be jumped onto from below and dropped through, and platforms that move.
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_rapier3d::{prelude::*, rapier::geometry::Aabb};
+use grin_time::{scaling::TimeScale, Rewind};
+
+use crate::{CollisionGroupExt, CollisionGroupsExt, PhysicsTime};
+
+/// How far into a platform something can sink and still be standing on it.
+pub const ONE_WAY_TOLERANCE: f32 = 0.1;
+
+/// How far off the edge of a platform a character counts as being over it.
+pub const ONE_WAY_MARGIN: f32 = 0.5;
+
+/// Seconds that `DropThrough` lasts.
+pub const DROP_THROUGH_SECS: f32 = 0.3;
+
+/// How far below its feet a `Carriable` looks for a `MovingPlatform`.
+pub const CARRY_DISTANCE: f32 = 0.2;
+
+pub struct OneWayPlatformPlugin;
+
+impl Plugin for OneWayPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                init_one_way_platforms,
+                (update_drop_throughs, filter_one_way_platforms).chain(),
+            ),
+        );
+    }
+}
+
+/// Only blocks things coming from the `normal` side. `normal` is in world space.
+///
+/// These are `Group::ONE_WAY` rather than `Group::MAP`, so the character controller can be told
+/// to ignore them. That also means they don't block line of sight.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct OneWayPlatform {
+    pub normal: Vec3,
+}
+
+impl Default for OneWayPlatform {
+    fn default() -> Self {
+        Self { normal: Vec3::Y }
+    }
+}
+
+/// Lowest and highest points of `aabb` along `normal`.
+fn extent(aabb: &Aabb, normal: Vec3) -> (f32, f32) {
+    aabb.vertices()
+        .iter()
+        .map(|p| Vec3::new(p.x, p.y, p.z).dot(normal))
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), d| {
+            (lo.min(d), hi.max(d))
+        })
+}
+
+impl OneWayPlatform {
+    /// Whether something in `other` is on the `normal` side of the platform, in `platform`.
+    pub fn blocks(&self, platform: &Aabb, other: &Aabb) -> bool {
+        extent(other, self.normal).0 >= extent(platform, self.normal).1 - ONE_WAY_TOLERANCE
+    }
+
+    /// Whether `point` is on the `normal` side of the platform.
+    pub fn blocks_point(&self, platform: &Aabb, point: Vec3) -> bool {
+        point.dot(self.normal) >= extent(platform, self.normal).1 - ONE_WAY_TOLERANCE
+    }
+
+    /// Whether `point` is above or below the platform, give or take `ONE_WAY_MARGIN`.
+    pub fn is_over(&self, platform: &Aabb, point: Vec3) -> bool {
+        // slide it onto the top of the platform and see if it's in the box
+        let top = extent(platform, self.normal).1;
+        let point = point + self.normal * (top - point.dot(self.normal));
+        let mins = Vec3::new(platform.mins.x, platform.mins.y, platform.mins.z);
+        let maxs = Vec3::new(platform.maxs.x, platform.maxs.y, platform.maxs.z);
+        point.cmpge(mins - ONE_WAY_MARGIN).all() && point.cmple(maxs + ONE_WAY_MARGIN).all()
+    }
+}
+
+/// Falls through `OneWayPlatform`s while this is on.
+#[derive(Component, Debug, Clone)]
+pub struct DropThrough {
+    pub timer: Timer,
+}
+
+impl Default for DropThrough {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(DROP_THROUGH_SECS, TimerMode::Once),
+        }
+    }
+}
+
+/// Sets up colliders for new `OneWayPlatform`s so `GrinPhysicsHooks` sees them.
+pub fn init_one_way_platforms(
+    mut commands: Commands,
+    platform_query: Query<(Entity, Option<&ActiveHooks>), Added<OneWayPlatform>>,
+) {
+    for (e_platform, hooks) in platform_query.iter() {
+        commands.entity(e_platform).insert((
+            hooks.copied().unwrap_or(ActiveHooks::empty()) | ActiveHooks::FILTER_CONTACT_PAIRS,
+            CollisionGroups::from_group_default(Group::ONE_WAY),
+        ));
+    }
+}
+
+pub fn update_drop_throughs(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut drop_query: Query<(Entity, &mut DropThrough, Option<&TimeScale>)>,
+) {
+    for (entity, mut drop_through, time_scale) in drop_query.iter_mut() {
+        let delta = time.0.delta().mul_f32(time_scale.map_or(1.0, f32::from));
+        if drop_through.timer.tick(delta).finished() {
+            commands.entity(entity).remove::<DropThrough>();
+        }
+    }
+}
+
+/// The character controller doesn't go through `GrinPhysicsHooks`, so this does the same thing
+/// with its filter groups. `Group::ONE_WAY` is filtered out while it's dropping through, or
+/// below a platform that it's under.
+///
+/// Character feet are assumed to be at their origin.
+pub fn filter_one_way_platforms(
+    rapier_context: Res<RapierContext>,
+    platform_query: Query<(Entity, &OneWayPlatform)>,
+    mut controller_query: Query<(
+        &mut KinematicCharacterController,
+        &GlobalTransform,
+        Has<DropThrough>,
+    )>,
+) {
+    for (mut controller, g_transform, dropping) in controller_query.iter_mut() {
+        let Some(groups) = controller.filter_groups else {
+            continue;
+        };
+
+        let feet = g_transform.translation();
+        let passing = dropping
+            || platform_query.iter().any(|(e_platform, platform)| {
+                let Some(aabb) = rapier_context
+                    .entity2collider()
+                    .get(&e_platform)
+                    .and_then(|handle| rapier_context.colliders.get(*handle))
+                    .map(|collider| collider.compute_aabb())
+                else {
+                    return false;
+                };
+                platform.is_over(&aabb, feet) && !platform.blocks_point(&aabb, feet)
+            });
+
+        if groups.filters.contains(Group::ONE_WAY) == passing {
+            let mut groups = groups;
+            groups.filters.set(Group::ONE_WAY, !passing);
+            controller.filter_groups = Some(groups);
+        }
+    }
+}
+
+pub struct MovingPlatformPlugin;
+
+impl Plugin for MovingPlatformPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, move_platforms).add_systems(
+            PostUpdate,
+            carry_on_platforms.before(PhysicsSet::SyncBackend),
+        );
+    }
+}
+
+/// How a `MovingPlatform` moves.
+#[derive(Clone, Debug)]
+pub enum PlatformMotion {
+    /// Goes to each waypoint in order at `speed`, then loops back to the first one.
+    /// Going back and forth is just two waypoints.
+    Path {
+        waypoints: Vec<Vec3>,
+        speed: f32,
+        /// Index of the waypoint it's going toward.
+        next: usize,
+    },
+    /// Something else is moving its `Transform`.
+    External,
+}
+
+/// Carries `Carriable`s standing on it.
+///
+/// Carrying works off of `Transform`, so these shouldn't have a parent.
+#[derive(Component, Clone, Debug)]
+pub struct MovingPlatform {
+    pub motion: PlatformMotion,
+    /// Where it was when things were last carried.
+    previous: Option<Transform>,
+}
+
+impl MovingPlatform {
+    pub fn path(waypoints: Vec<Vec3>, speed: f32) -> Self {
+        Self {
+            motion: PlatformMotion::Path {
+                waypoints,
+                speed,
+                next: 0,
+            },
+            previous: None,
+        }
+    }
+
+    pub fn external() -> Self {
+        Self {
+            motion: PlatformMotion::External,
+            previous: None,
+        }
+    }
+}
+
+/// Gets moved along with `MovingPlatform`s it's standing on. Feet are assumed to be at the origin.
+///
+/// This goes through the `KinematicCharacterController` if there is one, otherwise it moves the
+/// `Transform`. `Dash`es and walking are added on top, so they're relative to the platform.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct Carriable;
+
+/// Moves `PlatformMotion::Path` platforms.
+pub fn move_platforms(
+    time: Res<PhysicsTime>,
+    mut platform_query: Query<
+        (&mut MovingPlatform, &mut Transform, Option<&TimeScale>),
+        Without<Rewind>,
+    >,
+) {
+    for (mut platform, mut transform, time_scale) in platform_query.iter_mut() {
+        let PlatformMotion::Path {
+            waypoints,
+            speed,
+            next,
+        } = &mut platform.motion
+        else {
+            continue;
+        };
+
+        let mut remaining = *speed * time.0.delta_seconds() * time_scale.map_or(1.0, f32::from);
+        // it can pass more than one waypoint in a frame, but not loop around forever
+        for _ in 0..waypoints.len() {
+            let Some(target) = waypoints.get(*next).copied() else {
+                break;
+            };
+            let offset = target - transform.translation;
+            let distance = offset.length();
+            if distance > remaining {
+                transform.translation += offset / distance * remaining;
+                break;
+            }
+            transform.translation = target;
+            remaining -= distance;
+            *next = (*next + 1) % waypoints.len();
+        }
+    }
+}
+
+/// Moves `Carriable`s by however much the platform under them moved since last frame.
+pub fn carry_on_platforms(
+    rapier_context: Res<RapierContext>,
+    mut platform_query: Query<(Entity, &mut MovingPlatform, &Transform, Has<Rewind>)>,
+    mut carried_query: Query<
+        (
+            &GlobalTransform,
+            &mut Transform,
+            Option<&mut KinematicCharacterController>,
+        ),
+        (With<Carriable>, Without<MovingPlatform>, Without<Rewind>),
+    >,
+) {
+    let mut deltas = HashMap::new();
+    for (e_platform, mut platform, transform, rewinding) in platform_query.iter_mut() {
+        if let Some(previous) = platform.previous.replace(*transform) {
+            // being rewound isn't really moving
+            if !rewinding {
+                deltas.insert(e_platform, (previous, *transform));
+            }
+        }
+    }
+    if deltas.is_empty() {
+        return;
+    }
+
+    for (g_transform, mut transform, char_controller) in carried_query.iter_mut() {
+        let feet = g_transform.translation();
+        let Some((e_platform, _)) = rapier_context.cast_ray(
+            feet + Vec3::Y * CARRY_DISTANCE / 2.0,
+            Vec3::NEG_Y,
+            CARRY_DISTANCE,
+            true,
+            QueryFilter::new().predicate(&|e| deltas.contains_key(&e)),
+        ) else {
+            continue;
+        };
+        let (previous, current) = deltas[&e_platform];
+
+        let rotation = current.rotation * previous.rotation.inverse();
+        let carried = current.translation + rotation * (feet - previous.translation);
+        let offset = carried - feet;
+        match char_controller {
+            Some(mut char_controller) => {
+                char_controller.translation =
+                    Some(char_controller.translation.unwrap_or_default() + offset);
+            }
+            None => transform.translation += offset,
+        }
+
+        let (yaw, ..) = rotation.to_euler(EulerRot::YXZ);
+        transform.rotate_y(yaw);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{render::mesh::MeshPlugin, scene::ScenePlugin, time::TimePlugin};
+
+    use crate::{new_physics_app, GrinPhysicsHooks};
+
+    use super::*;
+
+    fn height(app: &App, entity: Entity) -> f32 {
+        app.world.get::<Transform>(entity).unwrap().translation.y
+    }
+
+    #[test]
+    fn one_way_platform() {
+        let mut app = App::new();
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::Fixed {
+                dt: 1.0 / 60.0,
+                substeps: 1,
+            },
+            ..Default::default()
+        })
+        .add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+        .add_plugins((
+            RapierPhysicsPlugin::<GrinPhysicsHooks>::default(),
+            OneWayPlatformPlugin,
+        ))
+        .init_resource::<PhysicsTime>();
+
+        app.world.spawn((
+            OneWayPlatform::default(),
+            Collider::cuboid(2.0, 0.1, 2.0),
+            TransformBundle::default(),
+        ));
+        // coming from below
+        let e_capsule = app
+            .world
+            .spawn((
+                RigidBody::Dynamic,
+                Collider::capsule_y(0.5, 0.25),
+                Velocity::linear(Vec3::Y * 10.0),
+                TransformBundle::from_transform(Transform::from_xyz(0.0, -2.0, 0.0)),
+            ))
+            .id();
+
+        for _ in 0..180 {
+            app.update();
+        }
+        assert!(
+            (height(&app, e_capsule) - 0.85).abs() < ONE_WAY_TOLERANCE,
+            "Didn't pass through from below and land on top. ({})",
+            height(&app, e_capsule),
+        );
+
+        app.world
+            .entity_mut(e_capsule)
+            .insert(DropThrough::default());
+        for _ in 0..60 {
+            app.update();
+        }
+        assert!(
+            height(&app, e_capsule) < -1.0,
+            "Didn't drop through. ({})",
+            height(&app, e_capsule),
+        );
+    }
+
+    #[test]
+    fn moving_platform() {
+        let mut app = new_physics_app();
+        app.add_plugins(TransformPlugin)
+            .init_resource::<PhysicsTime>()
+            .add_plugins(MovingPlatformPlugin);
+
+        let e_platform = app
+            .world
+            .spawn((
+                MovingPlatform::path(vec![Vec3::ZERO, Vec3::X * 4.0], 1.0),
+                Collider::cuboid(2.0, 0.1, 2.0),
+                TransformBundle::default(),
+            ))
+            .id();
+        let e_rider = app
+            .world
+            .spawn((
+                Carriable,
+                TransformBundle::from_transform(Transform::from_xyz(0.0, 0.1, 0.0)),
+            ))
+            .id();
+
+        // colliders get added to the physics world here
+        app.update();
+        app.update();
+
+        let x = |app: &App, e| app.world.get::<Transform>(e).unwrap().translation.x;
+        let step = |app: &mut App| {
+            app.world
+                .resource_mut::<PhysicsTime>()
+                .0
+                .advance_by(Duration::from_secs_f32(0.1));
+            app.update();
+        };
+
+        // there and halfway back
+        for i in 0..60 {
+            step(&mut app);
+            assert!(
+                (x(&app, e_rider) - x(&app, e_platform)).abs() < 1E-4,
+                "Wasn't carried on frame {}. ({} vs {})",
+                i,
+                x(&app, e_rider),
+                x(&app, e_platform),
+            );
+        }
+        assert!(
+            (x(&app, e_platform) - 2.0).abs() < 1E-4,
+            "Platform didn't turn around. ({})",
+            x(&app, e_platform),
+        );
+
+        // step off
+        app.world
+            .get_mut::<Transform>(e_rider)
+            .unwrap()
+            .translation
+            .x = 10.0;
+        for _ in 0..10 {
+            step(&mut app);
+        }
+        assert_eq!(x(&app, e_rider), 10.0, "Got carried after stepping off.");
+    }
+}
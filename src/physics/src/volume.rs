@@ -0,0 +1,270 @@
+//! Water and ladders, which change how characters move.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use grin_time::scaling::TimeScale;
+
+use crate::{CollisionGroupsExt, PhysicsTime};
+
+/// How far above the feet to check for volumes. Roughly waist height, so shallow water doesn't count.
+pub const MOVEMENT_VOLUME_PROBE_HEIGHT: f32 = 0.5;
+
+/// Seconds after jumping off of a ladder before it can be grabbed again.
+pub const LADDER_DISMOUNT_SECS: f32 = 0.3;
+
+pub struct MovementVolumePlugin;
+
+impl Plugin for MovementVolumePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                init_movement_volumes,
+                (update_ladder_dismounts, update_movement_modes).chain(),
+            ),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MovementVolumeKind {
+    /// `drag` is the fraction of speed lost, `buoyancy` is the fraction of gravity cancelled out.
+    Water { drag: f32, buoyancy: f32 },
+    /// Climbs along `axis`, in world space.
+    Ladder { axis: Vec3 },
+}
+
+/// Changes the `MovementMode` of anything inside of its collider.
+///
+/// These don't collide with anything, and don't show up in raycasts unless asked for.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct MovementVolume {
+    pub kind: MovementVolumeKind,
+}
+
+/// How a character is getting around. Updated by `update_movement_modes`.
+///
+/// Ladders win out over water, and the draggiest water wins out over other water.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq)]
+pub enum MovementMode {
+    #[default]
+    Walking,
+    Swimming {
+        drag: f32,
+        buoyancy: f32,
+    },
+    Climbing {
+        axis: Vec3,
+    },
+}
+
+impl MovementMode {
+    /// How much gravity should apply. Nothing's pulling characters down right now,
+    /// but whatever does should go through this.
+    pub fn gravity_scale(&self) -> f32 {
+        match *self {
+            Self::Walking => 1.0,
+            Self::Swimming { buoyancy, .. } => (1.0 - buoyancy).max(0.0),
+            Self::Climbing { .. } => 0.0,
+        }
+    }
+
+    /// Speed multiplier.
+    pub fn speed_scale(&self) -> f32 {
+        match *self {
+            Self::Swimming { drag, .. } => (1.0 - drag).max(0.0),
+            _ => 1.0,
+        }
+    }
+}
+
+/// Ignores ladders for a bit after jumping off of one.
+#[derive(Component, Debug, Clone)]
+pub struct LadderDismount {
+    pub timer: Timer,
+}
+
+impl Default for LadderDismount {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(LADDER_DISMOUNT_SECS, TimerMode::Once),
+        }
+    }
+}
+
+pub fn init_movement_volumes(
+    mut commands: Commands,
+    volume_query: Query<Entity, Added<MovementVolume>>,
+) {
+    for e_volume in volume_query.iter() {
+        commands
+            .entity(e_volume)
+            .insert((Sensor, CollisionGroups::from_group_default(Group::NONE)));
+    }
+}
+
+pub fn update_ladder_dismounts(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut dismount_query: Query<(Entity, &mut LadderDismount, Option<&TimeScale>)>,
+) {
+    for (entity, mut dismount, time_scale) in dismount_query.iter_mut() {
+        let delta = time.0.delta().mul_f32(time_scale.map_or(1.0, f32::from));
+        if dismount.timer.tick(delta).finished() {
+            commands.entity(entity).remove::<LadderDismount>();
+        }
+    }
+}
+
+/// Every `MovementVolume` containing `point`.
+pub fn volumes_at<'a>(
+    rapier_context: &RapierContext,
+    volume_query: &'a Query<&MovementVolume>,
+    point: Vec3,
+) -> Vec<&'a MovementVolume> {
+    let mut volumes = Vec::new();
+    rapier_context.intersections_with_point(
+        point,
+        QueryFilter::new().predicate(&|e| volume_query.contains(e)),
+        |e| {
+            volumes.push(volume_query.get(e).unwrap());
+            true
+        },
+    );
+    volumes
+}
+
+/// Picks `MovementMode`s from whatever volumes characters are in. This is done from scratch
+/// every frame, so overlapping or despawned volumes don't need any bookkeeping.
+///
+/// Character feet are assumed to be at their origin.
+pub fn update_movement_modes(
+    rapier_context: Res<RapierContext>,
+    volume_query: Query<&MovementVolume>,
+    mut mode_query: Query<(&mut MovementMode, &GlobalTransform, Has<LadderDismount>)>,
+) {
+    for (mut mode, g_transform, dismounted) in mode_query.iter_mut() {
+        let point = g_transform.translation() + Vec3::Y * MOVEMENT_VOLUME_PROBE_HEIGHT;
+
+        let mut new_mode = MovementMode::Walking;
+        for volume in volumes_at(&rapier_context, &volume_query, point) {
+            new_mode = match (new_mode, volume.kind) {
+                (_, MovementVolumeKind::Ladder { axis }) if !dismounted => MovementMode::Climbing {
+                    axis: axis.normalize_or_zero(),
+                },
+                (MovementMode::Walking, MovementVolumeKind::Water { drag, buoyancy }) => {
+                    MovementMode::Swimming { drag, buoyancy }
+                }
+                (
+                    MovementMode::Swimming { drag: old_drag, .. },
+                    MovementVolumeKind::Water { drag, buoyancy },
+                ) if drag > old_drag => MovementMode::Swimming { drag, buoyancy },
+                (mode, _) => mode,
+            };
+        }
+
+        if *mode != new_mode {
+            *mode = new_mode;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::new_physics_app;
+
+    use super::*;
+
+    fn transform_bundle(transform: Transform) -> TransformBundle {
+        TransformBundle {
+            local: transform,
+            global: transform.into(),
+        }
+    }
+
+    #[test]
+    fn movement_modes() {
+        let mut app = new_physics_app();
+        app.add_plugins(TransformPlugin)
+            .init_resource::<PhysicsTime>()
+            .add_plugins(MovementVolumePlugin);
+
+        let e_water = app
+            .world
+            .spawn((
+                MovementVolume {
+                    kind: MovementVolumeKind::Water {
+                        drag: 0.5,
+                        buoyancy: 0.8,
+                    },
+                },
+                Collider::cuboid(2.0, 2.0, 2.0),
+                transform_bundle(Transform::default()),
+            ))
+            .id();
+        // sticking out of the water
+        let e_ladder = app
+            .world
+            .spawn((
+                MovementVolume {
+                    kind: MovementVolumeKind::Ladder { axis: Vec3::Y },
+                },
+                Collider::cuboid(0.5, 4.0, 0.5),
+                transform_bundle(Transform::from_xyz(2.0, 0.0, 0.0)),
+            ))
+            .id();
+        let e_character = app
+            .world
+            .spawn((
+                MovementMode::default(),
+                transform_bundle(Transform::from_xyz(10.0, 0.0, 0.0)),
+            ))
+            .id();
+
+        let mut move_to = |app: &mut App, translation: Vec3| {
+            app.world
+                .entity_mut(e_character)
+                .insert(transform_bundle(Transform::from_translation(translation)));
+            // colliders get added to the physics world here
+            app.update();
+            app.update();
+            *app.world.get::<MovementMode>(e_character).unwrap()
+        };
+
+        assert_eq!(
+            move_to(&mut app, Vec3::new(10.0, 0.0, 0.0)),
+            MovementMode::Walking,
+            "Not walking on land."
+        );
+        assert_eq!(
+            move_to(&mut app, Vec3::new(0.0, -1.0, 0.0)),
+            MovementMode::Swimming {
+                drag: 0.5,
+                buoyancy: 0.8,
+            },
+            "Not swimming in water."
+        );
+        assert_eq!(
+            move_to(&mut app, Vec3::new(2.0, -1.0, 0.0)),
+            MovementMode::Climbing { axis: Vec3::Y },
+            "Ladder didn't take priority over water."
+        );
+
+        app.world.despawn(e_ladder);
+        assert_eq!(
+            move_to(&mut app, Vec3::new(2.0, -1.0, 0.0)),
+            MovementMode::Swimming {
+                drag: 0.5,
+                buoyancy: 0.8,
+            },
+            "Still climbing a despawned ladder."
+        );
+
+        app.world.despawn(e_water);
+        assert_eq!(
+            move_to(&mut app, Vec3::new(2.0, -1.0, 0.0)),
+            MovementMode::Walking,
+            "Still swimming in despawned water."
+        );
+    }
+}
@@ -1,23 +1,32 @@
 // TODO: see if this old thing actually works :P
 
-use bevy::prelude::*;
+use bevy::{pbr::NotShadowCaster, prelude::*};
 use bevy_tweening::{component_animator_system, Lens};
 
 pub struct BeamPlugin;
 
 impl Plugin for BeamPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            (
-                render_beams,
-                set_beam_radius,
-                component_animator_system::<Beam>,
-            ),
-        );
+        app.add_event::<HitscanBeamEvent>()
+            .init_resource::<TracerMaterial>()
+            .add_systems(
+                Update,
+                (
+                    (spawn_tracers, fade_tracers).chain().before(render_beams),
+                    render_beams,
+                    set_beam_radius,
+                    component_animator_system::<Beam>,
+                ),
+            );
     }
 }
 
+/// Seconds for a hitscan tracer to fade out.
+pub const TRACER_FADE_TIME: f32 = 0.1;
+
+/// Starting radius of a hitscan tracer.
+pub const TRACER_RADIUS: f32 = 0.04;
+
 /// Cylindrical (capsule-ical?) beam.
 ///
 /// This is less efficient than `render::DuoQuad`. However, it looks better with large volumes.
@@ -60,6 +69,31 @@ pub struct BeamBundle<M: Material> {
     pub global_transform: GlobalTransform,
 }
 
+/// Draws a tracer from `from` to `to`, for hitscan shots.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct HitscanBeamEvent {
+    pub from: Vec3,
+    pub to: Vec3,
+}
+
+/// A `Beam` that shrinks away and despawns when the timer finishes.
+#[derive(Component, Debug)]
+pub struct Tracer(pub Timer);
+
+#[derive(Resource)]
+pub struct TracerMaterial(pub Handle<StandardMaterial>);
+
+impl FromWorld for TracerMaterial {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+        Self(materials.add(StandardMaterial {
+            base_color: Color::rgb(1.0, 0.9, 0.6),
+            unlit: true,
+            ..Default::default()
+        }))
+    }
+}
+
 #[derive(Component, Default)]
 pub struct BeamRadiusLens {
     pub start: f32,
@@ -106,6 +140,43 @@ pub fn render_beams(
     }
 }
 
+pub fn spawn_tracers(
+    mut commands: Commands,
+    material: Res<TracerMaterial>,
+    mut beam_events: EventReader<HitscanBeamEvent>,
+) {
+    for &HitscanBeamEvent { from, to } in beam_events.read() {
+        // zero length beams can't be oriented
+        if from.distance_squared(to) <= f32::EPSILON {
+            continue;
+        }
+
+        commands.spawn((
+            BeamBundle {
+                beam: Beam::new(from, to, TRACER_RADIUS),
+                material: material.0.clone(),
+                ..Default::default()
+            },
+            Tracer(Timer::from_seconds(TRACER_FADE_TIME, TimerMode::Once)),
+            NotShadowCaster,
+        ));
+    }
+}
+
+pub fn fade_tracers(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut tracer_query: Query<(Entity, &mut Tracer, &mut Beam)>,
+) {
+    for (e_tracer, mut tracer, mut beam) in tracer_query.iter_mut() {
+        if tracer.0.tick(time.delta()).finished() {
+            commands.entity(e_tracer).despawn_recursive();
+            continue;
+        }
+        beam.radius = beam.initial_radius * tracer.0.fraction_remaining();
+    }
+}
+
 // sane person: `Transform.scale` is bad practice :(((
 // me (gangsta): we do a little trolling B)
 pub fn set_beam_radius(mut beam_query: Query<(&Beam, &mut Transform), Changed<Beam>>) {
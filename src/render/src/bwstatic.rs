@@ -34,12 +34,15 @@ use bevy::{
         Extract, Render, RenderApp, RenderSet,
     },
 };
+use grin_util::rng::{GrinRng, RNG_FX};
+use rand::Rng;
 
 pub struct BWStaticPlugin;
 
 impl Plugin for BWStaticPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(UniformComponentPlugin::<BWStaticUniform>::default())
+            .add_systems(Update, roll_bw_static)
             .get_sub_app_mut(RenderApp)
             .expect("Failed to get render app.")
             .init_resource::<SpecializedMeshPipelines<BWStaticPipeline>>()
@@ -62,6 +65,10 @@ impl Plugin for BWStaticPlugin {
 pub struct BWStaticEffect {
     pub rate: f32,
     pub enabled: bool,
+    /// Rerolled every `rate` seconds.
+    pub seed: f32,
+    /// When `seed` was last rolled, in multiples of `rate`.
+    pub last_t: f32,
 }
 
 impl Default for BWStaticEffect {
@@ -69,6 +76,26 @@ impl Default for BWStaticEffect {
         Self {
             rate: 1.0 / 16.0,
             enabled: true,
+            seed: 0.0,
+            last_t: 0.0,
+        }
+    }
+}
+
+/// Rolls `BWStaticEffect::seed` from `GrinRng`. The render world can't get at it.
+pub fn roll_bw_static(
+    time: Res<Time>,
+    mut rng: ResMut<GrinRng>,
+    mut effect_query: Query<&mut BWStaticEffect>,
+) {
+    for mut effect in effect_query.iter_mut() {
+        if !effect.enabled {
+            continue;
+        }
+        let t = (time.elapsed_seconds_wrapped() / effect.rate).trunc();
+        if t != effect.last_t {
+            effect.last_t = t;
+            effect.seed = rng.fork(RNG_FX).gen::<f32>() * 128.0;
         }
     }
 }
@@ -189,22 +216,14 @@ struct BWStaticUniform {
 fn extract_bw_static_uniforms(
     mut commands: Commands,
     query: Extract<Query<(Entity, &BWStaticEffect)>>,
-    time: Res<Time>,
-    mut last_t: Local<f32>,
-    mut seed: Local<f32>,
 ) {
     for (entity, effect) in query.iter() {
         if !effect.enabled {
             continue;
         }
-        let t = (time.elapsed_seconds_wrapped() / effect.rate).trunc();
-        if t != *last_t {
-            *last_t = t;
-            *seed = rand::random::<f32>() * 128.0;
-        }
         commands
             .get_or_spawn(entity)
-            .insert(BWStaticUniform { t: *seed });
+            .insert(BWStaticUniform { t: effect.seed });
     }
 }
 
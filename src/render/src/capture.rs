@@ -0,0 +1,390 @@
+//! Screenshots, and the kill cam.
+//!
+//! The kill cam is a `GoPro` that goes up where the killer was, watching the victim for a bit.
+//! It renders into a ring of images, one at a time, so that the UI can flash them afterwards.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use bevy::{
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        view::{screenshot::ScreenshotManager, Layer, RenderLayers},
+    },
+    window::PrimaryWindow,
+};
+
+use crate::{
+    gopro::{add_gopro, create_image_target, register_gopros, GoPro, GoProSettings},
+    RenderLayer,
+};
+
+pub struct CapturePlugin;
+
+impl Plugin for CapturePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KillCamSettings>()
+            .init_resource::<KillCamReplay>()
+            .add_event::<CaptureScreenshotEvent>()
+            .add_event::<KillCamEvent>()
+            .add_event::<KillCamFinishedEvent>()
+            .add_systems(
+                Update,
+                (
+                    capture_screenshots.run_if(resource_exists::<ScreenshotManager>),
+                    // the `GoPro` has to be registered with its own image before it's moved
+                    // onto the ring, or it takes a frame with it when it's freed
+                    start_kill_cams.before(register_gopros),
+                    record_kill_cams.after(register_gopros),
+                ),
+            );
+    }
+}
+
+/// Saves the primary window to `path`, which can be a file or a directory.
+/// `None` goes to `default_screenshot_dir`. Files in a directory are named after the time.
+#[derive(Event, Clone, Debug, Default)]
+pub struct CaptureScreenshotEvent {
+    pub path: Option<PathBuf>,
+}
+
+pub fn default_screenshot_dir() -> PathBuf {
+    dirs::picture_dir()
+        .unwrap_or_default()
+        .join("grin")
+        .join("screenshots")
+}
+
+/// Where a screenshot goes. Anything without an extension is a directory.
+/// Creates the directory if it's not there.
+pub fn screenshot_path(path: Option<&Path>, timestamp_millis: u128) -> io::Result<PathBuf> {
+    let path = match path {
+        Some(path) if path.extension().is_some() => path.to_path_buf(),
+        Some(dir) => dir.join(format!("grin_{}.png", timestamp_millis)),
+        None => default_screenshot_dir().join(format!("grin_{}.png", timestamp_millis)),
+    };
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)?;
+    }
+    Ok(path)
+}
+
+pub fn capture_screenshots(
+    mut screenshot_manager: ResMut<ScreenshotManager>,
+    window_query: Query<Entity, With<PrimaryWindow>>,
+    mut screenshot_events: EventReader<CaptureScreenshotEvent>,
+) {
+    let Ok(e_window) = window_query.get_single() else {
+        screenshot_events.clear();
+        return;
+    };
+    for CaptureScreenshotEvent { path } in screenshot_events.read() {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let path = match screenshot_path(path.as_deref(), timestamp) {
+            Ok(path) => path,
+            Err(e) => {
+                warn!("Couldn't make a place for the screenshot: {}", e);
+                continue;
+            }
+        };
+        match screenshot_manager.save_screenshot_to_disk(e_window, &path) {
+            Ok(()) => info!("Saved screenshot to {}.", path.display()),
+            // one per frame
+            Err(e) => warn!("Couldn't take a screenshot: {}", e),
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct KillCamSettings {
+    /// Off by default. `KillCamEvent`s are ignored while it's off.
+    pub enabled: bool,
+    /// Seconds of recording.
+    pub duration: f32,
+    /// How many images are in the ring.
+    pub frames: usize,
+    pub size: UVec2,
+    pub render_layers: RenderLayers,
+}
+
+impl Default for KillCamSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration: 1.5,
+            frames: 12,
+            size: UVec2::new(320, 180),
+            render_layers: RenderLayers::layer(RenderLayer::STANDARD as Layer),
+        }
+    }
+}
+
+/// Starts the kill cam on `victim`, from `from`. Ignored if it's already recording.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct KillCamEvent {
+    pub victim: Entity,
+    pub from: Vec3,
+}
+
+/// Sent once the kill cam is done recording. `KillCamReplay` has the frames.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct KillCamFinishedEvent {
+    pub victim: Entity,
+}
+
+/// The ring of images the kill cam records into. They're kept around for the next one,
+/// so the images don't pile up.
+#[derive(Resource, Debug, Default)]
+pub struct KillCamReplay {
+    ring: Vec<Handle<Image>>,
+    /// Oldest first.
+    recorded: Vec<usize>,
+}
+
+impl KillCamReplay {
+    /// What was recorded last, oldest first. Empty while it's still recording.
+    pub fn frames(&self) -> impl Iterator<Item = &Handle<Image>> {
+        self.recorded.iter().map(|i| &self.ring[*i])
+    }
+
+    /// The ring, resized to the settings.
+    fn prepare(&mut self, images: &mut Assets<Image>, settings: &KillCamSettings) {
+        self.recorded.clear();
+        if self.ring.len() != settings.frames
+            || self
+                .ring
+                .first()
+                .and_then(|h| images.get(h))
+                .is_some_and(|image| image.size() != settings.size)
+        {
+            for h_frame in self.ring.drain(..) {
+                images.remove(&h_frame);
+            }
+            self.ring = (0..settings.frames)
+                .map(|_| create_image_target(images, settings.size))
+                .collect();
+        }
+    }
+}
+
+/// What the kill cam hangs off of. Despawning it takes the `GoPro` and its image with it.
+#[derive(Component, Debug)]
+pub struct KillCam {
+    pub victim: Entity,
+    /// Seconds it's been recording.
+    pub elapsed: f32,
+    /// Where the next frame goes in the ring.
+    pub cursor: usize,
+    /// Keeps the `GoPro` alive until it's done.
+    pub h_target: Handle<Image>,
+}
+
+pub fn start_kill_cams(
+    mut commands: Commands,
+    settings: Res<KillCamSettings>,
+    mut replay: ResMut<KillCamReplay>,
+    mut images: ResMut<Assets<Image>>,
+    kill_cam_query: Query<(), With<KillCam>>,
+    transform_query: Query<&GlobalTransform>,
+    mut kill_cam_events: EventReader<KillCamEvent>,
+) {
+    let Some(&KillCamEvent { victim, from }) = kill_cam_events.read().last() else {
+        return;
+    };
+    if !settings.enabled || settings.frames == 0 || !kill_cam_query.is_empty() {
+        return;
+    }
+    let Ok(g_victim_transform) = transform_query.get(victim) else {
+        return;
+    };
+
+    replay.prepare(&mut images, &settings);
+
+    let e_anchor = commands
+        .spawn(TransformBundle::from_transform(
+            Transform::from_translation(from).looking_at(g_victim_transform.translation(), Vec3::Y),
+        ))
+        .id();
+    let h_target = add_gopro(
+        &mut commands,
+        &mut images,
+        GoProSettings {
+            entity: e_anchor,
+            transform: Transform::IDENTITY,
+            size: settings.size,
+            render_layers: settings.render_layers.clone(),
+            exclusive_layer: false,
+        },
+    );
+    commands.entity(e_anchor).insert(KillCam {
+        victim,
+        elapsed: 0.0,
+        cursor: 0,
+        h_target,
+    });
+}
+
+/// Points the kill cam at the next image in the ring every so often, keeping it on the victim.
+pub fn record_kill_cams(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<KillCamSettings>,
+    mut replay: ResMut<KillCamReplay>,
+    mut kill_cam_query: Query<(Entity, &mut KillCam, &mut Transform, &Children)>,
+    mut gopro_query: Query<&mut Camera, With<GoPro>>,
+    transform_query: Query<&GlobalTransform>,
+    mut finished_events: EventWriter<KillCamFinishedEvent>,
+) {
+    let frame_secs = settings.duration / settings.frames.max(1) as f32;
+    for (e_kill_cam, mut kill_cam, mut transform, children) in kill_cam_query.iter_mut() {
+        if kill_cam.elapsed >= settings.duration || replay.ring.is_empty() {
+            // the next one up is the oldest, once it's gone all the way around
+            let len = replay.ring.len();
+            let start = match kill_cam.cursor >= len {
+                true => kill_cam.cursor % len.max(1),
+                false => 0,
+            };
+            replay.recorded = (0..kill_cam.cursor.min(len))
+                .map(|i| (start + i) % len)
+                .collect();
+            commands.entity(e_kill_cam).despawn_recursive();
+            finished_events.send(KillCamFinishedEvent {
+                victim: kill_cam.victim,
+            });
+            continue;
+        }
+
+        if let Ok(g_victim_transform) = transform_query.get(kill_cam.victim) {
+            transform.look_at(g_victim_transform.translation(), Vec3::Y);
+        }
+
+        // how many frames should be done by now
+        let due = (kill_cam.elapsed / frame_secs) as usize + 1;
+        if due > kill_cam.cursor {
+            let h_frame = replay.ring[kill_cam.cursor % replay.ring.len()].clone_weak();
+            for &e_child in children.iter() {
+                if let Ok(mut camera) = gopro_query.get_mut(e_child) {
+                    camera.target = RenderTarget::Image(h_frame.clone());
+                }
+            }
+            kill_cam.cursor += 1;
+        }
+        kill_cam.elapsed += time.delta_seconds();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use crate::gopro::{GoProPlugin, GoPros};
+
+    use super::*;
+
+    #[test]
+    fn screenshot_dir() {
+        let dir = std::env::temp_dir()
+            .join(format!("grin_screenshots_{}", std::process::id()))
+            .join("nested");
+        let _ = fs::remove_dir_all(&dir);
+
+        let path = screenshot_path(Some(&dir), 1234).unwrap();
+        assert_eq!(
+            path,
+            dir.join("grin_1234.png"),
+            "Screenshot wasn't named after the time."
+        );
+        assert!(dir.is_dir(), "Directory wasn't created.");
+
+        let file = dir.join("other").join("shot.png");
+        assert_eq!(
+            screenshot_path(Some(&file), 1234).unwrap(),
+            file,
+            "File name wasn't kept."
+        );
+        let _ = fs::remove_dir_all(dir.parent().unwrap());
+    }
+
+    #[test]
+    fn kill_cam() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .insert_resource(KillCamSettings {
+                enabled: true,
+                duration: 1.0,
+                frames: 4,
+                size: UVec2::splat(8),
+                ..Default::default()
+            })
+            .add_plugins((GoProPlugin, CapturePlugin));
+
+        let e_victim = app.world.spawn(TransformBundle::default()).id();
+        let kill = |app: &mut App| {
+            app.world.send_event(KillCamEvent {
+                victim: e_victim,
+                from: Vec3::new(0.0, 2.0, 8.0),
+            });
+        };
+
+        kill(&mut app);
+        app.update();
+        kill(&mut app);
+        app.update();
+        let mut kill_cams = app.world.query::<&KillCam>();
+        assert_eq!(
+            kill_cams.iter(&app.world).count(),
+            1,
+            "Started another kill cam while one was recording."
+        );
+
+        let mut finished = Vec::new();
+        for _ in 0..15 {
+            app.update();
+            finished.extend(
+                app.world
+                    .resource_mut::<Events<KillCamFinishedEvent>>()
+                    .drain()
+                    .map(|ev| ev.victim),
+            );
+        }
+        assert_eq!(finished, vec![e_victim], "Kill cam didn't finish once.");
+        assert_eq!(
+            app.world.resource::<KillCamReplay>().frames().count(),
+            4,
+            "Frames weren't all recorded."
+        );
+
+        app.update();
+        assert!(
+            app.world.resource::<GoPros>().targets.is_empty(),
+            "Kill cam GoPro wasn't cleaned up."
+        );
+        assert_eq!(
+            app.world.resource::<Assets<Image>>().len(),
+            4,
+            "Kill cam image leaked, or the frames were freed."
+        );
+
+        kill(&mut app);
+        for _ in 0..15 {
+            app.update();
+        }
+        assert_eq!(
+            app.world.resource::<Assets<Image>>().len(),
+            4,
+            "Kill cam image leaked after the second time."
+        );
+    }
+}
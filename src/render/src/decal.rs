@@ -0,0 +1,290 @@
+//! Marks left behind on surfaces, like bullet holes.
+
+use std::collections::VecDeque;
+
+use bevy::{pbr::NotShadowCaster, prelude::*};
+use bevy_asset_loader::prelude::*;
+
+use crate::sketched::{NoOutline, SketchMaterial};
+
+/// Decals get pushed out this far along the normal, so they don't z-fight with the surface.
+pub const DECAL_OFFSET: f32 = 0.01;
+
+/// Default for `DecalSettings::max_decals`.
+pub const MAX_DECALS: usize = 128;
+
+pub struct DecalPlugin;
+
+impl Plugin for DecalPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SpawnDecalEvent>()
+            .init_resource::<DecalSettings>()
+            .init_resource::<Decals>()
+            .add_systems(PostUpdate, (spawn_decals, prune_decals).chain());
+    }
+}
+
+/// Loaded by whoever has the loading state. Decals don't show up until it's there.
+#[derive(Resource, AssetCollection)]
+pub struct DecalAssets {
+    #[asset(key = "mat.decal.bullet_hole")]
+    pub bullet_hole: Handle<SketchMaterial>,
+    #[asset(key = "mat.decal.scorch")]
+    pub scorch: Handle<SketchMaterial>,
+}
+
+impl DecalAssets {
+    pub fn material(&self, kind: DecalKind) -> &Handle<SketchMaterial> {
+        match kind {
+            DecalKind::BulletHole => &self.bullet_hole,
+            DecalKind::Scorch => &self.scorch,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecalKind {
+    BulletHole,
+    Scorch,
+}
+
+/// Puts a `size` by `size` decal flat on the surface at `position`, facing `normal`.
+///
+/// If there's a `target`, the decal gets parented to it, so it follows it around and goes away
+/// with it. It's dropped if `target` is already gone.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct SpawnDecalEvent {
+    pub position: Vec3,
+    pub normal: Vec3,
+    pub kind: DecalKind,
+    pub size: f32,
+    pub target: Option<Entity>,
+}
+
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct DecalSettings {
+    /// When there's more than this, the oldest ones go first.
+    pub max_decals: usize,
+}
+
+impl Default for DecalSettings {
+    fn default() -> Self {
+        Self {
+            max_decals: MAX_DECALS,
+        }
+    }
+}
+
+/// Every decal that's out right now, oldest first.
+#[derive(Resource, Debug, Default)]
+pub struct Decals(pub VecDeque<Entity>);
+
+#[derive(Component, Debug)]
+pub struct Decal {
+    pub kind: DecalKind,
+}
+
+pub fn spawn_decals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut mesh: Local<Option<Handle<Mesh>>>,
+    assets: Option<Res<DecalAssets>>,
+    settings: Res<DecalSettings>,
+    mut decals: ResMut<Decals>,
+    transform_query: Query<&GlobalTransform>,
+    mut decal_events: EventReader<SpawnDecalEvent>,
+) {
+    let Some(assets) = assets else {
+        decal_events.clear();
+        return;
+    };
+    let mesh = mesh
+        .get_or_insert_with(|| meshes.add(Rectangle::new(1.0, 1.0)))
+        .clone();
+
+    for SpawnDecalEvent {
+        position,
+        normal,
+        kind,
+        size,
+        target,
+    } in decal_events.read()
+    {
+        let Some(normal) = normal.try_normalize() else {
+            continue;
+        };
+        // the quad faces +Z
+        let transform = Transform::from_translation(*position + normal * DECAL_OFFSET)
+            .with_rotation(Quat::from_rotation_arc(Vec3::Z, normal))
+            .with_scale(Vec3::splat(*size));
+
+        let (transform, parent) = match target {
+            Some(e_target) => {
+                let Ok(g_target_transform) = transform_query.get(*e_target) else {
+                    continue;
+                };
+                (
+                    GlobalTransform::from(transform).reparented_to(g_target_transform),
+                    Some(*e_target),
+                )
+            }
+            None => (transform, None),
+        };
+
+        let mut decal = commands.spawn((
+            Decal { kind: *kind },
+            MaterialMeshBundle {
+                mesh: mesh.clone(),
+                material: assets.material(*kind).clone(),
+                transform,
+                ..Default::default()
+            },
+            NoOutline,
+            NotShadowCaster,
+        ));
+        if let Some(parent) = parent {
+            decal.set_parent(parent);
+        }
+        decals.0.push_back(decal.id());
+    }
+
+    while decals.0.len() > settings.max_decals {
+        let e_decal = decals.0.pop_front().unwrap();
+        if let Some(decal) = commands.get_entity(e_decal) {
+            decal.despawn_recursive();
+        }
+    }
+}
+
+/// Throws out decals that were despawned, and ones whose parent was despawned without them.
+pub fn prune_decals(
+    mut commands: Commands,
+    mut decals: ResMut<Decals>,
+    decal_query: Query<Option<&Parent>, With<Decal>>,
+    entity_query: Query<()>,
+) {
+    decals.0.retain(|&e_decal| match decal_query.get(e_decal) {
+        Ok(Some(parent)) if !entity_query.contains(parent.get()) => {
+            commands.entity(e_decal).despawn_recursive();
+            false
+        }
+        Ok(..) => true,
+        Err(..) => false,
+    });
+}
+
+/// Clears decals off of anything that gets a `T`, like when it shatters.
+pub fn clear_decals_on<T: Component>(
+    mut commands: Commands,
+    cleared_query: Query<Entity, Added<T>>,
+    children_query: Query<&Children>,
+    decal_query: Query<(), With<Decal>>,
+) {
+    for e_cleared in cleared_query.iter() {
+        for e_decal in children_query
+            .iter_descendants(e_cleared)
+            .filter(|e| decal_query.contains(*e))
+        {
+            commands.entity(e_decal).despawn_recursive();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::MeshPlugin;
+
+    use super::*;
+
+    #[derive(Component)]
+    struct Shattered;
+
+    fn decal_at(app: &mut App, position: Vec3, target: Option<Entity>) {
+        app.world.send_event(SpawnDecalEvent {
+            position,
+            normal: Vec3::Y,
+            kind: DecalKind::BulletHole,
+            size: 0.1,
+            target,
+        });
+    }
+
+    #[test]
+    fn decals() {
+        let mut app = App::new();
+        app.add_plugins((AssetPlugin::default(), MeshPlugin))
+            .init_asset::<SketchMaterial>()
+            .insert_resource(DecalSettings { max_decals: 2 })
+            .insert_resource(DecalAssets {
+                bullet_hole: Handle::default(),
+                scorch: Handle::default(),
+            })
+            .add_plugins(DecalPlugin)
+            .add_systems(Update, clear_decals_on::<Shattered>);
+
+        // there's no transform propagation here
+        let wall_transform = Transform::from_xyz(0.0, 1.0, 0.0);
+        let e_wall = app
+            .world
+            .spawn((wall_transform, GlobalTransform::from(wall_transform)))
+            .id();
+
+        decal_at(&mut app, Vec3::ZERO, None);
+        decal_at(&mut app, Vec3::new(0.0, 2.0, 0.0), Some(e_wall));
+        app.update();
+        let e_first = app.world.resource::<Decals>().0[0];
+        let e_on_wall = app.world.resource::<Decals>().0[1];
+        assert_eq!(
+            app.world.get::<Parent>(e_on_wall).map(Parent::get),
+            Some(e_wall),
+            "Decal wasn't parented to what it hit."
+        );
+        let transform = app.world.get::<Transform>(e_on_wall).unwrap();
+        assert!(
+            transform
+                .translation
+                .abs_diff_eq(Vec3::new(0.0, 1.0 + DECAL_OFFSET, 0.0), 1e-5),
+            "Decal isn't offset in the parent's space."
+        );
+        assert!(
+            (transform.rotation * Vec3::Z).abs_diff_eq(Vec3::Y, 1e-5),
+            "Decal doesn't face the normal."
+        );
+
+        decal_at(&mut app, Vec3::ZERO, None);
+        app.update();
+        assert_eq!(
+            app.world.resource::<Decals>().0.len(),
+            2,
+            "Decals went over the cap."
+        );
+        assert!(
+            app.world.get_entity(e_first).is_none(),
+            "Oldest decal wasn't despawned."
+        );
+
+        app.world.entity_mut(e_wall).insert(Shattered);
+        app.update();
+        assert!(
+            app.world.get_entity(e_on_wall).is_none(),
+            "Decal wasn't cleared on shatter."
+        );
+
+        let e_crate = app.world.spawn(TransformBundle::default()).id();
+        decal_at(&mut app, Vec3::ZERO, Some(e_crate));
+        app.update();
+        let e_on_crate = *app.world.resource::<Decals>().0.back().unwrap();
+        // like `time_despawn`, which doesn't take the children
+        app.world.despawn(e_crate);
+        app.update();
+        assert!(
+            app.world.get_entity(e_on_crate).is_none(),
+            "Orphaned decal wasn't despawned."
+        );
+        assert_eq!(
+            app.world.resource::<Decals>().0.len(),
+            1,
+            "Despawned decals are still in the buffer."
+        );
+    }
+}
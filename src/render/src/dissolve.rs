@@ -0,0 +1,305 @@
+//! Burning things away, instead of having them pop out of existence.
+
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::CollisionGroups;
+use grin_physics::CollisionGroupExt;
+use grin_time::CommandsExt;
+
+use crate::{
+    sketched::{MaterialMutationResource, SketchMaterial},
+    EffectFlags,
+};
+
+/// Seconds after spawning that debris starts dissolving.
+pub const DEBRIS_DISSOLVE_DELAY: f32 = 8.0;
+
+/// Seconds that debris takes to dissolve.
+pub const DEBRIS_DISSOLVE_SECS: f32 = 1.0;
+
+pub struct DissolvePlugin;
+
+impl Plugin for DissolvePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebrisDissolve>().add_systems(
+            Update,
+            (
+                mark_debris,
+                dissolve_after_delay,
+                update_dissolves,
+                cancel_dissolves,
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Dissolves this entity's materials, and its descendants', over `duration` seconds.
+///
+/// With `EffectFlags::DESPAWN`, the entity gets `time_despawn`ed once it's gone.
+/// Otherwise the component comes off, and with `EffectFlags::REZERO` the materials go back
+/// to normal. Taking the component off early counts as cancelling, which also `REZERO`s.
+#[derive(Component, Clone, Debug)]
+pub struct Dissolve {
+    pub duration: f32,
+    /// Glow at the edge of the dissolve.
+    pub edge_color: Color,
+    pub flags: EffectFlags,
+    /// Seconds since it started.
+    pub elapsed: f32,
+}
+
+impl Dissolve {
+    pub fn new(duration: f32, edge_color: Color) -> Self {
+        Self {
+            duration,
+            edge_color,
+            flags: EffectFlags::default(),
+            elapsed: 0.0,
+        }
+    }
+
+    /// How much is dissolved, from `0.0` to `1.0`.
+    pub fn t(&self) -> f32 {
+        match self.duration > 0.0 {
+            true => (self.elapsed / self.duration).clamp(0.0, 1.0),
+            false => 1.0,
+        }
+    }
+}
+
+/// Materials that go back to normal when `Dissolve` comes off.
+#[derive(Component, Debug)]
+pub struct DissolveRezero;
+
+/// Inserts the `DebrisDissolve` once the timer's up.
+#[derive(Component, Debug)]
+pub struct DissolveAfter(pub Timer);
+
+/// Things in `Group::DEBRIS` dissolve this long after they show up.
+#[derive(Resource, Clone, Debug)]
+pub struct DebrisDissolve {
+    pub enabled: bool,
+    pub delay: f32,
+    pub dissolve: Dissolve,
+}
+
+impl Default for DebrisDissolve {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            delay: DEBRIS_DISSOLVE_DELAY,
+            dissolve: Dissolve::new(DEBRIS_DISSOLVE_SECS, Color::rgb(1.0, 0.5, 0.1)),
+        }
+    }
+}
+
+pub fn mark_debris(
+    mut commands: Commands,
+    settings: Res<DebrisDissolve>,
+    debris_query: Query<
+        (Entity, &CollisionGroups),
+        (
+            Added<CollisionGroups>,
+            Without<DissolveAfter>,
+            Without<Dissolve>,
+        ),
+    >,
+) {
+    if !settings.enabled {
+        return;
+    }
+
+    for (e_debris, groups) in debris_query.iter() {
+        if groups.memberships.contains(Group::DEBRIS) {
+            commands
+                .entity(e_debris)
+                .insert(DissolveAfter(Timer::from_seconds(
+                    settings.delay,
+                    TimerMode::Once,
+                )));
+        }
+    }
+}
+
+pub fn dissolve_after_delay(
+    mut commands: Commands,
+    time: Res<Time>,
+    settings: Res<DebrisDissolve>,
+    mut delay_query: Query<(Entity, &mut DissolveAfter)>,
+) {
+    for (entity, mut delay) in delay_query.iter_mut() {
+        if delay.0.tick(time.delta()).finished() {
+            commands
+                .entity(entity)
+                .remove::<DissolveAfter>()
+                .insert(settings.dissolve.clone());
+        }
+    }
+}
+
+pub fn update_dissolves(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut materials: ResMut<Assets<SketchMaterial>>,
+    mut material_mutation: ResMut<MaterialMutationResource>,
+    mut dissolve_query: Query<(Entity, &mut Dissolve, Has<DissolveRezero>)>,
+    children_query: Query<&Children>,
+    mut material_query: Query<&mut Handle<SketchMaterial>>,
+) {
+    for (entity, mut dissolve, rezero) in dissolve_query.iter_mut() {
+        if dissolve.flags.intersects(EffectFlags::REZERO) && !rezero {
+            commands.entity(entity).insert(DissolveRezero);
+        }
+
+        dissolve.elapsed += time.delta_seconds();
+        let t = dissolve.t();
+        let edge_color = Vec4::from(dissolve.edge_color.as_linear_rgba_f32());
+
+        for e_material in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            let Ok(mut h_material) = material_query.get_mut(e_material) else {
+                continue;
+            };
+
+            // each entity gets its own copy, so nothing else sharing the material dissolves
+            if let Some(h_mod_material) =
+                material_mutation.modify(&mut materials, &h_material, |mat| {
+                    mat.extension.dissolve_enabled = true;
+                    mat.extension.dissolve = t;
+                    mat.extension.dissolve_edge_color = edge_color;
+                })
+            {
+                *h_material = h_mod_material;
+            }
+        }
+
+        if t >= 1.0 {
+            if dissolve.flags.intersects(EffectFlags::DESPAWN) {
+                commands.entity(entity).time_despawn();
+            } else {
+                commands.entity(entity).remove::<Dissolve>();
+            }
+        }
+    }
+}
+
+/// Puts the materials back for `DissolveRezero` entities that lost their `Dissolve`.
+pub fn cancel_dissolves(
+    mut commands: Commands,
+    mut material_mutation: ResMut<MaterialMutationResource>,
+    mut removed: RemovedComponents<Dissolve>,
+    rezero_query: Query<(), (With<DissolveRezero>, Without<Dissolve>)>,
+    children_query: Query<&Children>,
+    mut material_query: Query<&mut Handle<SketchMaterial>>,
+) {
+    for entity in removed.read() {
+        if !rezero_query.contains(entity) {
+            continue;
+        }
+
+        for e_material in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            let Ok(mut h_material) = material_query.get_mut(e_material) else {
+                continue;
+            };
+
+            if let Ok(h_base_material) = material_mutation.pop_base(&h_material.id()) {
+                *h_material = h_base_material;
+            }
+        }
+        commands.entity(entity).remove::<DissolveRezero>();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+    use grin_physics::CollisionGroupsExt;
+
+    use crate::sketched::SketchMaterialInfo;
+
+    use super::*;
+
+    fn dissolved(app: &App, entity: Entity) -> Option<f32> {
+        let handle = app.world.get::<Handle<SketchMaterial>>(entity)?;
+        let material = app.world.resource::<Assets<SketchMaterial>>().get(handle)?;
+        material
+            .extension
+            .dissolve_enabled
+            .then_some(material.extension.dissolve)
+    }
+
+    #[test]
+    fn dissolve() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default()))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                500,
+            )))
+            .init_asset::<SketchMaterial>()
+            .init_resource::<MaterialMutationResource>()
+            .insert_resource(DebrisDissolve {
+                delay: 0.5,
+                ..Default::default()
+            })
+            .add_plugins(DissolvePlugin);
+
+        let h_shared = app
+            .world
+            .resource_mut::<Assets<SketchMaterial>>()
+            .add(SketchMaterial {
+                base: StandardMaterial::default(),
+                extension: SketchMaterialInfo::default(),
+            });
+
+        let e_still = app.world.spawn(h_shared.clone()).id();
+        let e_debris = app
+            .world
+            .spawn((
+                h_shared.clone(),
+                CollisionGroups::from_group_default(Group::DEBRIS),
+            ))
+            .id();
+        let e_cancelled = app.world.spawn(TransformBundle::default()).id();
+        let e_cancelled_mesh = app.world.spawn(h_shared.clone()).id();
+        app.world
+            .entity_mut(e_cancelled)
+            .add_child(e_cancelled_mesh)
+            .insert(Dissolve {
+                flags: EffectFlags::REZERO,
+                ..Dissolve::new(2.0, Color::WHITE)
+            });
+
+        // the first update has no delta
+        app.update();
+        // debris timer goes off, and it starts dissolving right away
+        app.update();
+        assert_eq!(
+            dissolved(&app, e_debris),
+            Some(0.5),
+            "Debris didn't start dissolving."
+        );
+        assert_eq!(
+            dissolved(&app, e_cancelled_mesh),
+            Some(0.25),
+            "Child material didn't dissolve."
+        );
+        assert_eq!(
+            dissolved(&app, e_still),
+            None,
+            "Shared material was dissolved."
+        );
+
+        app.world.entity_mut(e_cancelled).remove::<Dissolve>();
+        app.update();
+        assert_eq!(
+            app.world.get::<Handle<SketchMaterial>>(e_cancelled_mesh),
+            Some(&h_shared),
+            "Cancelled dissolve didn't rezero."
+        );
+        assert!(
+            app.world.get_entity(e_debris).is_none(),
+            "Debris wasn't despawned."
+        );
+    }
+}
@@ -0,0 +1,410 @@
+//! Screen shake, wobble and hit-stop.
+
+use bevy::{ecs::entity::EntityHashSet, prelude::*, transform::TransformSystem};
+use grin_time::scaling::{scale_animations, scale_velocities, TimeScale, TimeScaleSet};
+
+/// Trauma doesn't go over this, no matter how much gets added.
+pub const MAX_TRAUMA: f32 = 1.0;
+
+/// Trauma lost per second.
+pub const TRAUMA_DECAY: f32 = 1.25;
+
+/// Roll at full wobble, in radians.
+pub const WOBBLE_ANGLE: f32 = 0.12;
+
+/// Wobbles per second.
+pub const WOBBLE_FREQUENCY: f32 = 0.75;
+
+/// How fast the wobble fades in and out, per second.
+pub const WOBBLE_FADE: f32 = 2.0;
+
+/// Hit-stops don't last longer than this, even when they're extended.
+pub const HIT_STOP_MAX_SECS: f32 = 0.25;
+
+/// Hit-stops don't slow time more than this.
+pub const HIT_STOP_MIN_SCALE: f32 = 0.05;
+
+pub struct FeedbackPlugin;
+
+impl Plugin for FeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FeedbackSettings>()
+            .init_resource::<Trauma>()
+            .init_resource::<Wobble>()
+            .init_resource::<HitStop>()
+            .add_event::<AddTraumaEvent>()
+            .add_event::<HitStopEvent>()
+            .add_systems(First, reset_screen_shake)
+            .add_systems(
+                PostUpdate,
+                (
+                    (add_trauma, apply_screen_shake, apply_wobble)
+                        .chain()
+                        .before(TransformSystem::TransformPropagate),
+                    (start_hit_stops, apply_hit_stop)
+                        .chain()
+                        .in_set(TimeScaleSet::Scale)
+                        .before(scale_animations)
+                        .before(scale_velocities),
+                ),
+            );
+    }
+}
+
+#[derive(Resource, Debug, Clone, Copy)]
+pub struct FeedbackSettings {
+    pub screen_shake: bool,
+    pub hit_stop: bool,
+}
+
+impl Default for FeedbackSettings {
+    fn default() -> Self {
+        Self {
+            screen_shake: true,
+            hit_stop: true,
+        }
+    }
+}
+
+/// Adds trauma to the screen shake. `1.0` is as bad as it gets.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct AddTraumaEvent(pub f32);
+
+/// Slows everything with a `TimeScale` down to `scale` for `duration` seconds.
+///
+/// A hit-stop that comes in during another one doesn't stack on top of it. It keeps the lower
+/// scale and the longer time left, capped at `HIT_STOP_MAX_SECS`.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct HitStopEvent {
+    pub duration: f32,
+    pub scale: f32,
+}
+
+/// Screen shake is `value` squared, so small hits barely do anything and big ones go crazy.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct Trauma {
+    pub value: f32,
+    /// Seconds of shaking, for the noise.
+    pub elapsed: f32,
+}
+
+impl Trauma {
+    pub fn add(&mut self, amount: f32) {
+        self.value = (self.value + amount.max(0.0)).min(MAX_TRAUMA);
+    }
+
+    pub fn decay(&mut self, delta_seconds: f32) {
+        self.value = (self.value - TRAUMA_DECAY * delta_seconds).max(0.0);
+    }
+
+    pub fn shake(&self) -> f32 {
+        self.value * self.value
+    }
+}
+
+/// Shakes this camera with the `Trauma`. The shake comes off again in `First`, so whatever
+/// moves the camera doesn't need to know about it.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct ScreenShake {
+    /// Yaw, pitch and roll at full shake, in radians.
+    pub max_angles: Vec3,
+    /// How fast it jitters around.
+    pub frequency: f32,
+    /// Rotation that's on the camera right now.
+    pub applied: Quat,
+}
+
+impl Default for ScreenShake {
+    fn default() -> Self {
+        Self {
+            max_angles: Vec3::new(0.06, 0.06, 0.1),
+            frequency: 16.0,
+            applied: Quat::IDENTITY,
+        }
+    }
+}
+
+/// Slow seasick sway on every `ScreenShake` camera, on top of the shake. It fades towards
+/// `target`, so whatever wants it should keep that up to date.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct Wobble {
+    /// `1.0` is full wobble.
+    pub target: f32,
+    pub value: f32,
+    pub elapsed: f32,
+}
+
+impl Wobble {
+    pub fn fade(&mut self, delta_seconds: f32) {
+        let step = WOBBLE_FADE * delta_seconds;
+        self.value += (self.target - self.value).clamp(-step, step);
+    }
+
+    /// Roll right now.
+    pub fn roll(&self) -> f32 {
+        WOBBLE_ANGLE * self.value * (self.elapsed * WOBBLE_FREQUENCY * std::f32::consts::TAU).sin()
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+pub struct HitStop {
+    pub timer: Timer,
+    /// What the scale should be right now, if there's a hit-stop.
+    pub scale: Option<f32>,
+    /// What the scale is right now.
+    pub applied: Option<f32>,
+    /// Entities that have `applied` in their `TimeScale`.
+    pub scaled: EntityHashSet,
+}
+
+/// Random gradients, smoothed. Somewhere in `[-1.0, 1.0]`.
+fn perlin(x: f32, seed: u32) -> f32 {
+    let gradient = |i: i32| {
+        let mut h = (i as u32).wrapping_mul(0x27d4_eb2d) ^ seed.wrapping_mul(0x9e37_79b9);
+        h ^= h >> 15;
+        h = h.wrapping_mul(0x85eb_ca6b);
+        h ^= h >> 13;
+        h as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+
+    let i = x.floor();
+    let f = x - i;
+    let u = f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+    let a = gradient(i as i32) * f;
+    let b = gradient(i as i32 + 1) * (f - 1.0);
+    (a + (b - a) * u) * 2.0
+}
+
+pub fn reset_screen_shake(mut shake_query: Query<(&mut Transform, &mut ScreenShake)>) {
+    for (mut transform, mut shake) in shake_query.iter_mut() {
+        if shake.applied != Quat::IDENTITY {
+            transform.rotation *= shake.applied.inverse();
+            shake.applied = Quat::IDENTITY;
+        }
+    }
+}
+
+pub fn add_trauma(
+    settings: Res<FeedbackSettings>,
+    mut trauma: ResMut<Trauma>,
+    mut trauma_events: EventReader<AddTraumaEvent>,
+) {
+    for AddTraumaEvent(amount) in trauma_events.read() {
+        if settings.screen_shake {
+            trauma.add(*amount);
+        }
+    }
+}
+
+pub fn apply_screen_shake(
+    time: Res<Time>,
+    settings: Res<FeedbackSettings>,
+    mut trauma: ResMut<Trauma>,
+    mut shake_query: Query<(&mut Transform, &mut ScreenShake)>,
+) {
+    if !settings.screen_shake {
+        *trauma = Trauma::default();
+        return;
+    }
+    if trauma.value <= 0.0 {
+        return;
+    }
+
+    trauma.elapsed += time.delta_seconds();
+    let amount = trauma.shake();
+    for (mut transform, mut shake) in shake_query.iter_mut() {
+        let t = trauma.elapsed * shake.frequency;
+        let angles =
+            shake.max_angles * amount * Vec3::new(perlin(t, 0), perlin(t, 1), perlin(t, 2));
+        shake.applied = Quat::from_euler(EulerRot::YXZ, angles.x, angles.y, angles.z);
+        transform.rotation *= shake.applied;
+    }
+    trauma.decay(time.delta_seconds());
+}
+
+/// Goes after `apply_screen_shake`, and puts itself in `ScreenShake::applied` so it comes off
+/// with the shake.
+pub fn apply_wobble(
+    time: Res<Time>,
+    settings: Res<FeedbackSettings>,
+    mut wobble: ResMut<Wobble>,
+    mut shake_query: Query<(&mut Transform, &mut ScreenShake)>,
+) {
+    if !settings.screen_shake {
+        *wobble = Wobble::default();
+        return;
+    }
+
+    wobble.fade(time.delta_seconds());
+    if wobble.value <= 0.0 {
+        wobble.elapsed = 0.0;
+        return;
+    }
+
+    wobble.elapsed += time.delta_seconds();
+    let rotation = Quat::from_rotation_z(wobble.roll());
+    for (mut transform, mut shake) in shake_query.iter_mut() {
+        shake.applied *= rotation;
+        transform.rotation *= rotation;
+    }
+}
+
+pub fn start_hit_stops(
+    settings: Res<FeedbackSettings>,
+    mut hit_stop: ResMut<HitStop>,
+    mut hit_stop_events: EventReader<HitStopEvent>,
+) {
+    for HitStopEvent { duration, scale } in hit_stop_events.read() {
+        if !settings.hit_stop {
+            continue;
+        }
+
+        let remaining = match hit_stop.scale {
+            Some(_) => hit_stop.timer.remaining_secs(),
+            None => 0.0,
+        };
+        let duration = remaining.max(*duration).clamp(0.0, HIT_STOP_MAX_SECS);
+        let scale = scale.clamp(HIT_STOP_MIN_SCALE, 1.0);
+        hit_stop.timer = Timer::from_seconds(duration, TimerMode::Once);
+        hit_stop.scale = Some(hit_stop.scale.map_or(scale, |s| s.min(scale)));
+    }
+}
+
+/// Puts the hit-stop scale on every `TimeScale`, and takes it back off when it's over.
+pub fn apply_hit_stop(
+    time: Res<Time>,
+    settings: Res<FeedbackSettings>,
+    mut hit_stop: ResMut<HitStop>,
+    mut scale_query: Query<(Entity, &mut TimeScale)>,
+) {
+    let hit_stop = &mut *hit_stop;
+
+    if hit_stop.scale.is_some() {
+        hit_stop.timer.tick(time.delta());
+        if hit_stop.timer.finished() || !settings.hit_stop {
+            hit_stop.scale = None;
+        }
+    }
+
+    if hit_stop.applied != hit_stop.scale {
+        if let Some(applied) = hit_stop.applied.take() {
+            for e_scaled in hit_stop.scaled.drain() {
+                let Ok((_, mut time_scale)) = scale_query.get_mut(e_scaled) else {
+                    continue;
+                };
+                if let Err(err) = time_scale.unscale_by(applied) {
+                    error!("{}", err);
+                }
+            }
+        }
+        hit_stop.applied = hit_stop.scale;
+    }
+
+    // new things can show up mid-stop
+    if let Some(scale) = hit_stop.applied {
+        for (entity, mut time_scale) in scale_query.iter_mut() {
+            if hit_stop.scaled.insert(entity) {
+                time_scale.scale_by(scale);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use super::*;
+
+    #[test]
+    fn trauma_decay() {
+        let mut trauma = Trauma::default();
+        trauma.add(0.5);
+        trauma.add(0.25);
+        assert_eq!(trauma.value, 0.75, "Trauma didn't add up.");
+        assert_eq!(trauma.shake(), 0.5625, "Shake isn't trauma squared.");
+
+        trauma.add(2.0);
+        assert_eq!(trauma.value, MAX_TRAUMA, "Trauma went over the cap.");
+
+        trauma.decay(0.4);
+        assert_eq!(
+            trauma.value,
+            MAX_TRAUMA - TRAUMA_DECAY * 0.4,
+            "Trauma didn't decay linearly."
+        );
+
+        trauma.decay(10.0);
+        assert_eq!(trauma.value, 0.0, "Trauma decayed below zero.");
+
+        for x in (0..1000).map(|i| i as f32 * 0.037) {
+            let noise = perlin(x, 1);
+            assert!((-1.0..=1.0).contains(&noise), "Noise out of range.");
+        }
+    }
+
+    #[test]
+    fn wobble_fade() {
+        let mut wobble = Wobble {
+            target: 1.0,
+            ..Default::default()
+        };
+        wobble.fade(0.25);
+        assert_eq!(wobble.value, 0.5, "Wobble didn't fade in.");
+        wobble.fade(10.0);
+        assert_eq!(wobble.value, 1.0, "Wobble went past the target.");
+
+        wobble.target = 0.0;
+        wobble.fade(10.0);
+        assert_eq!(wobble.value, 0.0, "Wobble didn't fade out.");
+    }
+
+    #[test]
+    fn hit_stop() {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                50,
+            )))
+            .add_plugins(FeedbackPlugin);
+
+        let e_scaled = app.world.spawn(TimeScale::default()).id();
+        let scale = |app: &App| f32::from(app.world.get::<TimeScale>(e_scaled).unwrap());
+
+        // the first update has no delta
+        app.update();
+
+        app.world.send_event(HitStopEvent {
+            duration: 0.1,
+            scale: 0.5,
+        });
+        app.update();
+        assert_eq!(scale(&app), 0.5, "Hit-stop didn't slow things down.");
+
+        app.world.send_event(HitStopEvent {
+            duration: 0.1,
+            scale: 0.25,
+        });
+        app.update();
+        assert_eq!(scale(&app), 0.25, "Hit-stops stacked.");
+
+        for _ in 0..(HIT_STOP_MAX_SECS / 0.05) as usize + 1 {
+            app.update();
+        }
+        assert_eq!(scale(&app), 1.0, "Time didn't go back to normal.");
+        assert!(
+            app.world.resource::<HitStop>().scaled.is_empty(),
+            "Hit-stop didn't let go."
+        );
+
+        app.world.resource_mut::<FeedbackSettings>().hit_stop = false;
+        app.world.send_event(HitStopEvent {
+            duration: 0.1,
+            scale: 0.5,
+        });
+        app.update();
+        assert_eq!(scale(&app), 1.0, "Hit-stop wasn't disabled.");
+    }
+}
@@ -0,0 +1,373 @@
+//! Flipping through the layers of a `SketchMaterial`, like for blinking faces.
+
+use bevy::{
+    prelude::*,
+    render::render_resource::{TextureViewDescriptor, TextureViewDimension},
+    utils::{HashMap, HashSet},
+};
+
+use crate::sketched::SketchMaterial;
+
+pub struct FlipbookPlugin;
+
+impl Plugin for FlipbookPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<LayeredTextures>().add_systems(
+            Update,
+            (relayer_reloaded_images, animate_material_layers).chain(),
+        );
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LayerAnimationMode {
+    #[default]
+    Loop,
+    /// Stops on the last layer.
+    Once,
+    /// Goes back and forth.
+    PingPong,
+}
+
+/// Steps through `layers` of this entity's `SketchMaterial`.
+///
+/// The material gets cloned for this entity the first time, and again whenever the handle gets
+/// swapped out, so other entities with the same material aren't affected.
+/// Overrides `SketchAnimation`.
+#[derive(Component, Clone, Debug)]
+pub struct MaterialLayerAnimator {
+    pub layers: Vec<u32>,
+    pub fps: f32,
+    pub mode: LayerAnimationMode,
+    /// Seconds since it started.
+    pub elapsed: f32,
+}
+
+impl MaterialLayerAnimator {
+    pub fn new(layers: impl Into<Vec<u32>>, fps: f32, mode: LayerAnimationMode) -> Self {
+        Self {
+            layers: layers.into(),
+            fps,
+            mode,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Index into `layers` for right now. `None` if there aren't any.
+    pub fn frame(&self) -> Option<usize> {
+        let n = self.layers.len();
+        if n == 0 {
+            return None;
+        }
+
+        let step = (self.elapsed * self.fps).max(0.0) as usize;
+        Some(match self.mode {
+            LayerAnimationMode::Loop => step % n,
+            LayerAnimationMode::Once => step.min(n - 1),
+            LayerAnimationMode::PingPong if n == 1 => 0,
+            LayerAnimationMode::PingPong => {
+                let period = 2 * (n - 1);
+                let i = step % period;
+                match i < n {
+                    true => i,
+                    false => period - i,
+                }
+            }
+        })
+    }
+
+    /// The material layer for right now.
+    pub fn layer(&self) -> Option<u32> {
+        self.frame().map(|i| self.layers[i])
+    }
+
+    /// Starts over from the first layer.
+    pub fn restart(&mut self) {
+        self.elapsed = 0.0;
+    }
+}
+
+/// The material that `MaterialLayerAnimator` cloned for this entity.
+#[derive(Component, Debug)]
+pub struct LayerMaterialOwner(pub AssetId<SketchMaterial>);
+
+/// How many layers each layered texture was loaded with.
+/// Needed to split them up again when they're hot-reloaded.
+#[derive(Resource, Debug, Default)]
+pub struct LayeredTextures(pub HashMap<AssetId<Image>, u32>);
+
+fn has_layered_view(image: &Image) -> bool {
+    image
+        .texture_view_descriptor
+        .as_ref()
+        .is_some_and(|d| d.dimension == Some(TextureViewDimension::D2Array))
+}
+
+/// Gives `image` a `D2Array` view, which is what `SketchMaterialInfo::base_color_texture` needs.
+///
+/// Single images are split into `layers` stacked top to bottom. If they don't split evenly,
+/// it's just one layer. Returns the number of layers it ended up with.
+pub fn set_layered_view(image: &mut Image, layers: u32) -> u32 {
+    if image.texture_descriptor.size.depth_or_array_layers == 1 {
+        let layers = match layers > 0 && image.height() % layers == 0 {
+            true => layers,
+            false => {
+                warn!(
+                    msg = "Texture doesn't split into its layers.",
+                    height = image.height(),
+                    layers,
+                );
+                1
+            }
+        };
+        image.reinterpret_stacked_2d_as_array(layers);
+    }
+
+    let layers = image.texture_descriptor.array_layer_count();
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        label: Some("D2Array Texture View"),
+        dimension: Some(TextureViewDimension::D2Array),
+        format: Some(image.texture_descriptor.format),
+        array_layer_count: Some(layers),
+        ..Default::default()
+    });
+    layers
+}
+
+/// Reloaded images come back as plain 2D textures, which doesn't fit the material binding.
+/// This splits them up again and pokes the materials using them, so they get rebuilt.
+pub fn relayer_reloaded_images(
+    layered: Res<LayeredTextures>,
+    mut textures: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<SketchMaterial>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+) {
+    let mut reloaded = HashSet::new();
+    for event in image_events.read() {
+        let AssetEvent::Modified { id } = event else {
+            continue;
+        };
+        let Some(&layers) = layered.0.get(id) else {
+            continue;
+        };
+        // this also catches the `Modified` from fixing it
+        if textures.get(*id).map_or(true, has_layered_view) {
+            continue;
+        }
+
+        set_layered_view(textures.get_mut(*id).unwrap(), layers);
+        reloaded.insert(*id);
+    }
+
+    if reloaded.is_empty() {
+        return;
+    }
+
+    let stale = materials
+        .iter()
+        .filter(|(_, material)| {
+            material
+                .extension
+                .base_color_texture
+                .as_ref()
+                .is_some_and(|h| reloaded.contains(&h.id()))
+        })
+        .map(|(id, _)| id)
+        .collect::<Vec<_>>();
+    for id in stale {
+        materials.get_mut(id);
+    }
+}
+
+pub fn animate_material_layers(
+    mut commands: Commands,
+    time: Res<Time>,
+    textures: Res<Assets<Image>>,
+    mut materials: ResMut<Assets<SketchMaterial>>,
+    mut animator_query: Query<(
+        Entity,
+        &mut MaterialLayerAnimator,
+        &mut Handle<SketchMaterial>,
+        Option<&LayerMaterialOwner>,
+    )>,
+) {
+    for (entity, mut animator, mut material_handle, owner) in animator_query.iter_mut() {
+        animator.elapsed += time.delta_seconds();
+
+        if owner.map(|o| o.0) != Some(material_handle.id()) {
+            let Some(material) = materials.get(&*material_handle).cloned() else {
+                continue;
+            };
+            *material_handle = materials.add(material);
+            commands
+                .entity(entity)
+                .insert(LayerMaterialOwner(material_handle.id()));
+        }
+
+        let Some(layer) = animator.layer() else {
+            continue;
+        };
+        let Some(material) = materials.get(&*material_handle) else {
+            continue;
+        };
+        // the texture might have been reloaded with fewer layers
+        let max_layers = material
+            .extension
+            .base_color_texture_layers(&textures)
+            .unwrap_or(1);
+        let layer = layer.min(max_layers.saturating_sub(1));
+
+        // touching the material rebuilds its bind group, so only do it when it changes
+        if material.extension.layer != layer {
+            materials
+                .get_mut(&*material_handle)
+                .unwrap()
+                .extension
+                .layer = layer;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        render::{
+            render_asset::RenderAssetUsages,
+            render_resource::{Extent3d, TextureDimension, TextureFormat},
+        },
+        time::{TimePlugin, TimeUpdateStrategy},
+    };
+
+    use crate::sketched::SketchMaterialInfo;
+
+    use super::*;
+
+    fn stacked_image(height: u32) -> Image {
+        Image::new_fill(
+            Extent3d {
+                width: 1,
+                height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255, 255, 255, 255],
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    #[test]
+    fn layer_frames() {
+        let frames = |mode| {
+            let mut animator = MaterialLayerAnimator::new([7, 8, 9], 1.0, mode);
+            (0..6)
+                .map(|i| {
+                    animator.elapsed = i as f32;
+                    animator.layer().unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            frames(LayerAnimationMode::Loop),
+            vec![7, 8, 9, 7, 8, 9],
+            "Bad looping."
+        );
+        assert_eq!(
+            frames(LayerAnimationMode::Once),
+            vec![7, 8, 9, 9, 9, 9],
+            "Didn't stop on the last layer."
+        );
+        assert_eq!(
+            frames(LayerAnimationMode::PingPong),
+            vec![7, 8, 9, 8, 7, 8],
+            "Bad ping-ponging."
+        );
+        assert_eq!(
+            MaterialLayerAnimator::new([], 1.0, LayerAnimationMode::Loop).layer(),
+            None,
+            "Empty animator has a layer."
+        );
+    }
+
+    #[test]
+    fn material_layer_animator() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default()))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs(1)))
+            .init_asset::<Image>()
+            .init_asset::<SketchMaterial>()
+            .add_plugins(FlipbookPlugin);
+
+        let h_image = {
+            let mut image = stacked_image(4);
+            set_layered_view(&mut image, 4);
+            app.world.resource_mut::<Assets<Image>>().add(image)
+        };
+        app.world
+            .resource_mut::<LayeredTextures>()
+            .0
+            .insert(h_image.id(), 4);
+        let h_shared = app
+            .world
+            .resource_mut::<Assets<SketchMaterial>>()
+            .add(SketchMaterial {
+                base: StandardMaterial::default(),
+                extension: SketchMaterialInfo {
+                    base_color_texture: Some(h_image.clone()),
+                    ..Default::default()
+                },
+            });
+
+        let e_still = app.world.spawn(h_shared.clone()).id();
+        let e_animated = app
+            .world
+            .spawn((
+                h_shared.clone(),
+                MaterialLayerAnimator::new([0, 3], 1.0, LayerAnimationMode::Loop),
+            ))
+            .id();
+
+        let layer = |app: &App, entity| {
+            let handle = app.world.get::<Handle<SketchMaterial>>(entity).unwrap();
+            app.world
+                .resource::<Assets<SketchMaterial>>()
+                .get(handle)
+                .unwrap()
+                .extension
+                .layer
+        };
+
+        // the first update has no delta
+        app.update();
+        app.update();
+        assert_ne!(
+            app.world.get::<Handle<SketchMaterial>>(e_animated),
+            Some(&h_shared),
+            "Shared material wasn't cloned."
+        );
+        assert_eq!(layer(&app, e_animated), 3, "Layer didn't change.");
+        assert_eq!(layer(&app, e_still), 0, "Shared material was changed.");
+
+        // reloads as one stacked image, with half the layers
+        app.world
+            .resource_mut::<LayeredTextures>()
+            .0
+            .insert(h_image.id(), 2);
+        app.world
+            .resource_mut::<Assets<Image>>()
+            .insert(h_image.id(), stacked_image(2));
+        app.update();
+        app.update();
+        let image = app.world.resource::<Assets<Image>>().get(&h_image).unwrap();
+        assert!(has_layered_view(image), "Reloaded image isn't D2Array.");
+        assert_eq!(
+            image.texture_descriptor.array_layer_count(),
+            2,
+            "Reloaded image has the wrong layers."
+        );
+        assert_eq!(layer(&app, e_animated), 1, "Layer wasn't clamped.");
+    }
+}
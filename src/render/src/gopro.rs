@@ -1,26 +1,53 @@
 use bevy::{
-    asset::LoadState,
+    ecs::entity::EntityHashMap,
     prelude::*,
     render::{
         camera::RenderTarget,
         render_resource::{
             Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
         },
-        view::RenderLayers,
+        view::{Layer, RenderLayers},
     },
+    utils::HashMap,
 };
 
+use crate::RenderLayer;
+
+/// Layers that GoPros can get to themselves with `GoProSettings::exclusive_layer`.
+/// The ones before this are for `RenderLayer`.
+pub const GOPRO_LAYERS_START: Layer = RenderLayer::MINIMAP as Layer + 1;
+
 pub struct GoProPlugin;
 
 impl Plugin for GoProPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, unload_gopros);
+        app.init_resource::<GoPros>().add_systems(
+            Update,
+            (
+                unload_gopros,
+                free_gopros,
+                register_gopros,
+                apply_gopro_settings,
+                pause_hidden_gopros,
+            )
+                .chain(),
+        );
     }
 }
 
-#[derive(Component)]
-pub struct GoPro;
+/// A camera that renders to an image, which usually ends up in a `UiImage` somewhere.
+///
+/// `settings` is what the camera goes off of, so change those instead of the camera.
+/// The image is freed when this is despawned.
+#[derive(Component, Debug)]
+pub struct GoPro {
+    pub settings: GoProSettings,
+    /// The layer it got from `GoProSettings::exclusive_layer`. Stuff that should only show up
+    /// on this GoPro goes on this layer. Handed out the frame after it's spawned.
+    pub layer: Option<Layer>,
+}
 
+#[derive(Clone, Debug)]
 pub struct GoProSettings {
     /// Parent entity of the camera.
     pub entity: Entity,
@@ -30,9 +57,34 @@ pub struct GoProSettings {
     pub size: UVec2,
     /// `RenderLayers` of the camera.
     pub render_layers: RenderLayers,
+    /// Gets a layer that no other GoPro has, on top of `render_layers`. See `GoPro::layer`.
+    pub exclusive_layer: bool,
 }
 
-pub fn create_image_target(images: &mut Assets<Image>, size: UVec2) -> Handle<Image> {
+/// Keeps track of `GoPro`s so they can be cleaned up after they're despawned.
+#[derive(Resource, Debug, Default)]
+pub struct GoPros {
+    /// The image each one renders to.
+    pub targets: EntityHashMap<AssetId<Image>>,
+    /// Who has which exclusive layer.
+    pub layers: HashMap<Layer, Entity>,
+}
+
+impl GoPros {
+    fn allocate_layer(&mut self, e_gopro: Entity) -> Option<Layer> {
+        let layer = (GOPRO_LAYERS_START..RenderLayers::TOTAL_LAYERS as Layer)
+            .find(|layer| !self.layers.contains_key(layer))?;
+        self.layers.insert(layer, e_gopro);
+        Some(layer)
+    }
+
+    fn free(&mut self, e_gopro: Entity) -> Option<AssetId<Image>> {
+        self.layers.retain(|_, e| *e != e_gopro);
+        self.targets.remove(&e_gopro)
+    }
+}
+
+fn image_target(size: UVec2) -> Image {
     let size = Extent3d {
         width: size.x,
         height: size.y,
@@ -54,18 +106,17 @@ pub fn create_image_target(images: &mut Assets<Image>, size: UVec2) -> Handle<Im
         ..Default::default()
     };
     target.resize(size);
-    images.add(target)
+    target
 }
 
-pub fn create_gopro(
-    target: Handle<Image>,
-    transform: Transform,
-    render_layers: RenderLayers,
-) -> impl Bundle {
+pub fn create_image_target(images: &mut Assets<Image>, size: UVec2) -> Handle<Image> {
+    images.add(image_target(size))
+}
+
+pub fn create_gopro(target: Handle<Image>, settings: GoProSettings) -> impl Bundle {
     (
-        GoPro,
         Camera3dBundle {
-            transform,
+            transform: settings.transform,
             camera: Camera {
                 order: -1,
                 // by looking at the internal camera system for like 2 seconds
@@ -76,7 +127,11 @@ pub fn create_gopro(
             },
             ..Default::default()
         },
-        render_layers.clone(),
+        settings.render_layers.clone(),
+        GoPro {
+            settings,
+            layer: None,
+        },
     )
 }
 
@@ -88,21 +143,11 @@ pub fn add_gopro(
     images: &mut Assets<Image>,
     settings: GoProSettings,
 ) -> Handle<Image> {
-    let GoProSettings {
-        entity,
-        transform,
-        size,
-        render_layers,
-    } = settings;
-
-    let h_target = create_image_target(images, size);
+    let entity = settings.entity;
+    let h_target = create_image_target(images, settings.size);
 
     commands
-        .spawn(create_gopro(
-            h_target.clone_weak(),
-            transform,
-            render_layers,
-        ))
+        .spawn(create_gopro(h_target.clone_weak(), settings))
         .set_parent(entity);
 
     h_target
@@ -112,39 +157,253 @@ pub fn add_gopro(
 ///
 /// The camera is removed when this handle is dropped.
 pub fn add_gopro_world(world: &mut World, settings: GoProSettings) -> Handle<Image> {
-    let GoProSettings {
-        entity,
-        transform,
-        size,
-        render_layers,
-    } = settings;
+    let entity = settings.entity;
     let mut images = world.resource_mut::<Assets<Image>>();
 
-    let h_target = create_image_target(&mut images, size);
+    let h_target = create_image_target(&mut images, settings.size);
 
     world
-        .spawn(create_gopro(
-            h_target.clone_weak(),
-            transform,
-            render_layers,
-        ))
+        .spawn(create_gopro(h_target.clone_weak(), settings))
         .set_parent(entity);
 
     h_target
 }
 
+fn image_target_id(camera: &Camera) -> Option<AssetId<Image>> {
+    match &camera.target {
+        RenderTarget::Image(target) => Some(target.id()),
+        _ => None,
+    }
+}
+
 /// Remove `GoPro`s with dropped target handles.
 pub fn unload_gopros(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    images: Res<Assets<Image>>,
     query: Query<(Entity, &Camera), With<GoPro>>,
 ) {
     for (entity, camera) in query.iter() {
-        if let RenderTarget::Image(target) = &camera.target {
-            // TODO: changed in bevy 0.12. is this supposed to be `NotLoaded` or `None`?
-            if let Some(LoadState::NotLoaded) = asset_server.get_load_state(target) {
-                commands.entity(entity).despawn();
+        if image_target_id(camera).is_some_and(|id| !images.contains(id)) {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Frees the image and layer of `GoPro`s that were despawned.
+pub fn free_gopros(
+    mut gopros: ResMut<GoPros>,
+    mut images: ResMut<Assets<Image>>,
+    mut removed: RemovedComponents<GoPro>,
+) {
+    for e_gopro in removed.read() {
+        if let Some(id) = gopros.free(e_gopro) {
+            images.remove(id);
+        }
+    }
+}
+
+pub fn register_gopros(
+    mut gopros: ResMut<GoPros>,
+    mut gopro_query: Query<(Entity, &mut GoPro, &Camera, &mut RenderLayers), Added<GoPro>>,
+) {
+    for (e_gopro, mut gopro, camera, mut render_layers) in gopro_query.iter_mut() {
+        if let Some(id) = image_target_id(camera) {
+            gopros.targets.insert(e_gopro, id);
+        }
+
+        if gopro.settings.exclusive_layer {
+            match gopros.allocate_layer(e_gopro) {
+                Some(layer) => {
+                    gopro.layer = Some(layer);
+                    *render_layers = render_layers.with(layer);
+                }
+                None => warn!(msg = "Ran out of layers for GoPros.", gopro = ?e_gopro),
             }
         }
     }
 }
+
+/// Moves, resizes and re-parents `GoPro`s to match their settings.
+///
+/// Resizing swaps in a new image under the same handle, so anything showing it
+/// (like a `UiImage`) keeps up without having to be told.
+pub fn apply_gopro_settings(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    mut gopro_query: Query<
+        (Entity, &GoPro, &Camera, &mut Transform, Option<&Parent>),
+        Changed<GoPro>,
+    >,
+    entity_query: Query<()>,
+) {
+    for (e_gopro, gopro, camera, mut transform, parent) in gopro_query.iter_mut() {
+        let settings = &gopro.settings;
+
+        if *transform != settings.transform {
+            *transform = settings.transform;
+        }
+
+        if parent.map(Parent::get) != Some(settings.entity) {
+            match entity_query.contains(settings.entity) {
+                true => {
+                    commands.entity(e_gopro).set_parent(settings.entity);
+                }
+                false => warn!(msg = "GoPro parent doesn't exist.", gopro = ?e_gopro),
+            }
+        }
+
+        let Some(id) = image_target_id(camera) else {
+            continue;
+        };
+        if images
+            .get(id)
+            .is_some_and(|image| image.size() != settings.size)
+        {
+            images.insert(id, image_target(settings.size));
+        }
+    }
+}
+
+/// Turns off `GoPro`s when every `UiImage` showing them is hidden, since no one's looking.
+/// `GoPro`s that aren't shown by a `UiImage` are left alone.
+pub fn pause_hidden_gopros(
+    mut gopro_query: Query<&mut Camera, With<GoPro>>,
+    ui_image_query: Query<(Entity, &UiImage, Option<&InheritedVisibility>)>,
+    style_query: Query<&Style>,
+    parent_query: Query<&Parent>,
+) {
+    for mut camera in gopro_query.iter_mut() {
+        let Some(id) = image_target_id(&camera) else {
+            continue;
+        };
+
+        let mut users = ui_image_query
+            .iter()
+            .filter(|(_, ui_image, _)| ui_image.texture.id() == id)
+            .peekable();
+        if users.peek().is_none() {
+            continue;
+        }
+
+        let shown = users.any(|(e_user, _, visibility)| {
+            let displayed = std::iter::once(e_user)
+                .chain(parent_query.iter_ancestors(e_user))
+                .filter_map(|e| style_query.get(e).ok())
+                .all(|style| style.display != Display::None);
+            displayed && visibility.map_or(true, |v| v.get())
+        });
+        if camera.is_active != shown {
+            camera.is_active = shown;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(entity: Entity) -> GoProSettings {
+        GoProSettings {
+            entity,
+            transform: Transform::default(),
+            size: UVec2::splat(16),
+            render_layers: RenderLayers::layer(1),
+            exclusive_layer: true,
+        }
+    }
+
+    fn size(app: &App, handle: &Handle<Image>) -> Option<UVec2> {
+        app.world
+            .resource::<Assets<Image>>()
+            .get(handle)
+            .map(Image::size)
+    }
+
+    #[test]
+    fn gopros() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<Image>()
+            .add_plugins(GoProPlugin);
+
+        let e_avatar = app.world.spawn(TransformBundle::default()).id();
+        let e_enemy = app.world.spawn(TransformBundle::default()).id();
+
+        let h_avatar = add_gopro_world(&mut app.world, settings(e_avatar));
+        let h_killcam = add_gopro_world(&mut app.world, settings(e_enemy));
+        let h_security = add_gopro_world(&mut app.world, settings(e_enemy));
+        app.update();
+
+        let mut cameras = app
+            .world
+            .query::<(Entity, &GoPro, &Camera, &RenderLayers)>();
+        let mut find = |app: &mut App, handle: &Handle<Image>| {
+            cameras
+                .iter(&app.world)
+                .find(|(_, _, camera, _)| image_target_id(camera) == Some(handle.id()))
+                .map(|(entity, gopro, _, render_layers)| {
+                    (entity, gopro.layer.unwrap(), render_layers.clone())
+                })
+                .unwrap()
+        };
+        let (e_gopro, avatar_layer, avatar_layers) = find(&mut app, &h_avatar);
+        let (_, killcam_layer, _) = find(&mut app, &h_killcam);
+        let (_, security_layer, _) = find(&mut app, &h_security);
+        assert!(
+            avatar_layer != killcam_layer
+                && killcam_layer != security_layer
+                && avatar_layer != security_layer,
+            "GoPros share a layer."
+        );
+        assert!(
+            avatar_layers.intersects(&RenderLayers::layer(1))
+                && avatar_layers.intersects(&RenderLayers::layer(avatar_layer)),
+            "GoPro didn't get its layers."
+        );
+
+        app.world.get_mut::<GoPro>(e_gopro).unwrap().settings.size = UVec2::new(32, 8);
+        app.world.get_mut::<GoPro>(e_gopro).unwrap().settings.entity = e_enemy;
+        app.update();
+        assert_eq!(
+            size(&app, &h_avatar),
+            Some(UVec2::new(32, 8)),
+            "Image wasn't resized."
+        );
+        assert_eq!(
+            app.world.get::<Parent>(e_gopro).map(Parent::get),
+            Some(e_enemy),
+            "GoPro wasn't re-parented."
+        );
+
+        let is_active = |app: &App| app.world.get::<Camera>(e_gopro).unwrap().is_active;
+        let e_viewport = app
+            .world
+            .spawn((UiImage::new(h_avatar.clone()), Style::default()))
+            .id();
+        app.update();
+        assert!(is_active(&app), "Shown GoPro was paused.");
+        app.world.get_mut::<Style>(e_viewport).unwrap().display = Display::None;
+        app.update();
+        assert!(!is_active(&app), "Hidden GoPro wasn't paused.");
+
+        app.world.despawn(e_gopro);
+        app.update();
+        assert_eq!(size(&app, &h_avatar), None, "Image wasn't freed.");
+        assert!(
+            !app.world
+                .resource::<GoPros>()
+                .layers
+                .contains_key(&avatar_layer),
+            "Layer wasn't freed."
+        );
+
+        drop(h_security);
+        app.update();
+        app.update();
+        assert_eq!(
+            app.world.resource::<GoPros>().targets.len(),
+            1,
+            "GoPro wasn't unloaded with its image."
+        );
+    }
+}
@@ -1,10 +1,17 @@
 pub mod beam;
 pub mod blaze;
 pub mod bwstatic;
+pub mod capture;
+pub mod decal;
+pub mod dissolve;
 pub mod duoquad;
+pub mod feedback;
 pub mod fill;
+pub mod flipbook;
 pub mod gopro;
+pub mod outline;
 pub mod particles;
+pub mod rewind;
 pub mod sketched;
 pub mod tint;
 
@@ -19,9 +26,17 @@ use tint::TintPlugin;
 use self::{
     beam::BeamPlugin,
     blaze::BlazePlugin,
+    capture::CapturePlugin,
     //bwstatic::BWStaticPlugin,
+    decal::DecalPlugin,
+    dissolve::DissolvePlugin,
     duoquad::DuoQuadPlugin,
+    feedback::FeedbackPlugin,
+    flipbook::FlipbookPlugin,
     gopro::GoProPlugin,
+    outline::OutlineOverridePlugin,
+    particles::DustPlugin,
+    rewind::RewindFilterPlugin,
     sketched::{GlobalMeshOutline, SketchEffectPlugin},
 };
 
@@ -29,6 +44,7 @@ use self::{
 pub enum RenderLayer {
     STANDARD,
     AVATAR,
+    MINIMAP,
 }
 
 pub struct RenderFXPlugins;
@@ -65,9 +81,17 @@ impl PluginGroup for RenderFXPlugins {
             .add(FillPlugin)
             .add(TintPlugin)
             .add(GoProPlugin)
+            .add(CapturePlugin)
             .add(DuoQuadPlugin)
             .add(BeamPlugin)
             .add(BlazePlugin)
+            .add(DustPlugin)
+            .add(RewindFilterPlugin)
+            .add(FeedbackPlugin)
+            .add(FlipbookPlugin)
+            .add(DissolvePlugin)
+            .add(DecalPlugin)
+            .add(OutlineOverridePlugin)
     }
 }
 
@@ -0,0 +1,285 @@
+//! Outlines that change with what's going on, like flashing when something gets hit.
+
+use bevy::{ecs::system::EntityCommands, prelude::*, utils::HashMap};
+use bevy_mod_outline::OutlineVolume;
+use grin_time::Rewind;
+
+/// Priorities for the built-in overrides. Highest wins.
+pub const DAMAGE_FLASH_PRIORITY: i32 = 40;
+pub const AIM_HIGHLIGHT_PRIORITY: i32 = 30;
+pub const TELEGRAPH_PRIORITY: i32 = 20;
+pub const REWIND_PRIORITY: i32 = 10;
+
+/// Outline colour for anything that's being rewound. Kinda ghostly.
+pub const REWIND_OUTLINE_COLOR: Color = Color::rgb(0.5, 0.6, 0.75);
+
+pub struct OutlineOverridePlugin;
+
+impl Plugin for OutlineOverridePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, ghost_rewinding_outlines)
+            .add_systems(PostUpdate, apply_outline_overrides);
+    }
+}
+
+/// Replaces the outline of an entity and its descendants.
+///
+/// `None` leaves that part alone. Colour and width are resolved separately,
+/// so a pulsing width still goes through a higher priority colour.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutlineOverride {
+    pub color: Option<Color>,
+    pub width: Option<f32>,
+    pub priority: i32,
+}
+
+impl OutlineOverride {
+    pub fn color(color: Color, priority: i32) -> Self {
+        Self {
+            color: Some(color),
+            width: None,
+            priority,
+        }
+    }
+
+    pub fn width(width: f32, priority: i32) -> Self {
+        Self {
+            color: None,
+            width: Some(width),
+            priority,
+        }
+    }
+}
+
+/// Every `OutlineOverride` on this entity, by whoever asked for it.
+/// Use `OutlineOverrideCommandsExt` instead of touching this directly.
+#[derive(Component, Debug, Default)]
+pub struct OutlineOverrides(pub HashMap<&'static str, OutlineOverride>);
+
+/// What the outline was before it got overridden.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct OutlineBase {
+    pub colour: Color,
+    pub width: f32,
+}
+
+pub trait OutlineOverrideCommandsExt {
+    fn set_outline_override(&mut self, key: &'static str, outline: OutlineOverride);
+    fn clear_outline_override(&mut self, key: &'static str);
+}
+
+impl<'a> OutlineOverrideCommandsExt for EntityCommands<'a> {
+    /// Sets the override for `key`. Does nothing if the entity is gone.
+    fn set_outline_override(&mut self, key: &'static str, outline: OutlineOverride) {
+        self.add(move |entity: Entity, world: &mut World| {
+            let Some(mut e) = world.get_entity_mut(entity) else {
+                return;
+            };
+            match e.get_mut::<OutlineOverrides>() {
+                Some(mut overrides) => {
+                    overrides.0.insert(key, outline);
+                }
+                None => {
+                    e.insert(OutlineOverrides(HashMap::from([(key, outline)])));
+                }
+            }
+        });
+    }
+
+    /// Takes off the override for `key`. Does nothing if the entity is gone.
+    fn clear_outline_override(&mut self, key: &'static str) {
+        self.add(move |entity: Entity, world: &mut World| {
+            let Some(mut e) = world.get_entity_mut(entity) else {
+                return;
+            };
+            let Some(mut overrides) = e.get_mut::<OutlineOverrides>() else {
+                return;
+            };
+            overrides.0.remove(key);
+            if overrides.0.is_empty() {
+                e.remove::<OutlineOverrides>();
+            }
+        });
+    }
+}
+
+#[derive(Default, Clone, Copy)]
+struct Resolved {
+    color: Option<(i32, Color)>,
+    width: Option<(i32, f32)>,
+}
+
+impl Resolved {
+    fn add(&mut self, outline: &OutlineOverride) {
+        if let Some(color) = outline.color {
+            if self.color.map_or(true, |(p, _)| outline.priority > p) {
+                self.color = Some((outline.priority, color));
+            }
+        }
+        if let Some(width) = outline.width {
+            if self.width.map_or(true, |(p, _)| outline.priority > p) {
+                self.width = Some((outline.priority, width));
+            }
+        }
+    }
+}
+
+/// Writes `OutlineOverrides` onto the `OutlineVolume`s under them, and puts back the ones
+/// that aren't overridden anymore.
+///
+/// Nothing is kept on the overridden entity itself, so it can be despawned whenever.
+pub fn apply_outline_overrides(
+    mut commands: Commands,
+    override_query: Query<(Entity, &OutlineOverrides)>,
+    children_query: Query<&Children>,
+    mut outline_query: Query<(Entity, &mut OutlineVolume, Option<&OutlineBase>)>,
+) {
+    let mut resolved = HashMap::<Entity, Resolved>::new();
+    for (entity, overrides) in override_query.iter() {
+        // meshes can get their outlines late, so this is done every frame
+        for e_mesh in std::iter::once(entity).chain(children_query.iter_descendants(entity)) {
+            if outline_query.contains(e_mesh) {
+                let res = resolved.entry(e_mesh).or_default();
+                for outline in overrides.0.values() {
+                    res.add(outline);
+                }
+            }
+        }
+    }
+
+    for (e_mesh, mut volume, base) in outline_query.iter_mut() {
+        let Some(res) = resolved.get(&e_mesh) else {
+            // not overridden anymore, or whatever was overriding it is gone
+            if let Some(base) = base {
+                volume.colour = base.colour;
+                volume.width = base.width;
+                commands.entity(e_mesh).remove::<OutlineBase>();
+            }
+            continue;
+        };
+        let base = match base {
+            Some(base) => *base,
+            None => {
+                let base = OutlineBase {
+                    colour: volume.colour,
+                    width: volume.width,
+                };
+                commands.entity(e_mesh).insert(base);
+                base
+            }
+        };
+
+        let colour = res.color.map_or(base.colour, |(_, c)| c);
+        let width = res.width.map_or(base.width, |(_, w)| w);
+        if volume.colour != colour {
+            volume.colour = colour;
+        }
+        if volume.width != width {
+            volume.width = width;
+        }
+    }
+}
+
+/// Greys out the outline of anything with `Rewind`.
+pub fn ghost_rewinding_outlines(
+    mut commands: Commands,
+    rewind_query: Query<Entity, Added<Rewind>>,
+    mut removed: RemovedComponents<Rewind>,
+) {
+    for entity in rewind_query.iter() {
+        commands.entity(entity).set_outline_override(
+            "rewind",
+            OutlineOverride::color(REWIND_OUTLINE_COLOR, REWIND_PRIORITY),
+        );
+    }
+    for entity in removed.read() {
+        if let Some(mut e) = commands.get_entity(entity) {
+            e.clear_outline_override("rewind");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::CommandQueue;
+
+    use super::*;
+
+    fn with_commands(app: &mut App, entity: Entity, f: impl FnOnce(&mut EntityCommands)) {
+        let mut queue = CommandQueue::default();
+        f(&mut Commands::new(&mut queue, &app.world).entity(entity));
+        queue.apply(&mut app.world);
+    }
+
+    fn outline(app: &App, entity: Entity) -> (Color, f32) {
+        let volume = app.world.get::<OutlineVolume>(entity).unwrap();
+        (volume.colour, volume.width)
+    }
+
+    #[test]
+    fn outline_overrides() {
+        let mut app = App::new();
+        app.add_plugins(OutlineOverridePlugin);
+
+        let e_parent = app.world.spawn(TransformBundle::default()).id();
+        let e_mesh = app
+            .world
+            .spawn(OutlineVolume {
+                colour: Color::BLACK,
+                width: 6.0,
+                visible: true,
+            })
+            .id();
+        app.world.entity_mut(e_parent).add_child(e_mesh);
+
+        app.world.entity_mut(e_parent).insert(Rewind::default());
+        with_commands(&mut app, e_parent, |e| {
+            e.set_outline_override("test", OutlineOverride::color(Color::RED, 100))
+        });
+        app.update();
+        assert_eq!(
+            outline(&app, e_mesh),
+            (Color::RED, 6.0),
+            "Highest priority colour didn't win."
+        );
+
+        with_commands(&mut app, e_parent, |e| {
+            e.set_outline_override("pulse", OutlineOverride::width(9.0, 0));
+            e.clear_outline_override("test");
+        });
+        app.update();
+        assert_eq!(
+            outline(&app, e_mesh),
+            (REWIND_OUTLINE_COLOR, 9.0),
+            "Width and colour weren't resolved separately."
+        );
+
+        app.world.entity_mut(e_parent).remove::<Rewind>();
+        with_commands(&mut app, e_parent, |e| e.clear_outline_override("pulse"));
+        app.update();
+        assert_eq!(
+            outline(&app, e_mesh),
+            (Color::BLACK, 6.0),
+            "Outline wasn't restored."
+        );
+        assert!(
+            app.world.get::<OutlineOverrides>(e_parent).is_none(),
+            "Empty overrides weren't removed."
+        );
+
+        app.world.entity_mut(e_parent).insert(Rewind::default());
+        app.update();
+        // like `time_despawn`, which doesn't take the children
+        app.world.despawn(e_parent);
+        app.update();
+        assert_eq!(
+            outline(&app, e_mesh),
+            (Color::BLACK, 6.0),
+            "Outline wasn't restored after despawning."
+        );
+        assert!(
+            app.world.get::<OutlineBase>(e_mesh).is_none(),
+            "Base outline wasn't cleaned up."
+        );
+    }
+}
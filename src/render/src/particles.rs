@@ -5,6 +5,7 @@ use std::{
 
 use bevy::prelude::*;
 use bevy_hanabi::prelude::*;
+use grin_util::distr;
 
 fn calc_func_id<T: Hash>(value: &T) -> u64 {
     let mut hasher = DefaultHasher::default();
@@ -58,3 +59,116 @@ impl Modifier for SetVelocityModifier {
         Ok(())
     }
 }
+
+/// Seconds before a `DustPuff` gets cleaned up.
+pub const DUST_PUFF_LIFETIME: f32 = 1.0;
+
+pub struct DustPlugin;
+
+impl Plugin for DustPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, create_dust_effect_assets)
+            .add_systems(Update, despawn_dust_puffs);
+    }
+}
+
+#[derive(Resource)]
+pub struct DustParticles {
+    pub puff: Handle<EffectAsset>,
+}
+
+/// A one-off cloud of dust, like from a footstep.
+#[derive(Component)]
+pub struct DustPuff {
+    pub timer: Timer,
+}
+
+impl Default for DustPuff {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(DUST_PUFF_LIFETIME, TimerMode::Once),
+        }
+    }
+}
+
+pub fn create_dust_effect_assets(mut commands: Commands, mut assets: ResMut<Assets<EffectAsset>>) {
+    let puff = {
+        let w = ExprWriter::new();
+
+        let pos = SetPositionCircleModifier {
+            center: w.lit(Vec3::ZERO).expr(),
+            axis: w.lit(Vec3::Y).expr(),
+            radius: w.lit(0.2).expr(),
+            dimension: ShapeDimension::Volume,
+        };
+
+        let vel = SetVelocitySphereModifier {
+            center: w.lit(Vec3::NEG_Y).expr(),
+            speed: w.lit(0.5).uniform(w.lit(1.0)).expr(),
+        };
+
+        let lifetime =
+            SetAttributeModifier::new(Attribute::LIFETIME, w.lit(0.3).uniform(w.lit(0.5)).expr());
+
+        let size = SizeOverLifetimeModifier {
+            gradient: distr::f32_map(10, &distr::linear, &distr::quad).fold(
+                Gradient::new(),
+                |mut grad, (i, t)| {
+                    grad.add_key(i, Vec2::splat(0.1 + t * 0.1));
+                    grad
+                },
+            ),
+            screen_space_size: false,
+        };
+
+        let color = ColorOverLifetimeModifier {
+            gradient: distr::f32_map(10, &distr::linear, &distr::quad).fold(
+                Gradient::new(),
+                |mut grad, (i, t)| {
+                    grad.add_key(i, Vec4::new(0.6, 0.6, 0.6, 1.0 - t));
+                    grad
+                },
+            ),
+        };
+
+        let drag = LinearDragModifier::new(w.lit(4.0).expr());
+
+        let effect = EffectAsset::new(vec![64], Spawner::once(12.0.into(), true), w.finish())
+            .with_name("dust_puff_particle")
+            .init(pos)
+            .init(vel)
+            .init(lifetime)
+            .update(drag)
+            .render(color)
+            .render(size)
+            .render(OrientModifier::default());
+
+        assets.add(effect)
+    };
+
+    commands.insert_resource(DustParticles { puff });
+}
+
+/// Spawns a `DustPuff` at `translation`.
+pub fn spawn_dust_puff(commands: &mut Commands, particles: &DustParticles, translation: Vec3) {
+    commands.spawn((
+        DustPuff::default(),
+        ParticleEffectBundle {
+            effect: ParticleEffect::new(particles.puff.clone()),
+            transform: Transform::from_translation(translation),
+            ..Default::default()
+        },
+    ));
+}
+
+pub fn despawn_dust_puffs(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut puff_query: Query<(Entity, &mut DustPuff)>,
+) {
+    for (entity, mut puff) in puff_query.iter_mut() {
+        if puff.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,46 @@
+//! Makes it obvious when the whole world is going backwards.
+
+use bevy::{prelude::*, render::view::ColorGrading};
+use grin_time::GlobalRewind;
+
+pub struct RewindFilterPlugin;
+
+impl Plugin for RewindFilterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, filter_global_rewinds);
+    }
+}
+
+/// Saturation of the camera during a `GlobalRewindEvent`.
+pub const REWIND_SATURATION: f32 = 0.0;
+
+/// Slightly darker, too.
+pub const REWIND_EXPOSURE: f32 = -0.5;
+
+/// The camera's `ColorGrading` from before the rewind started.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct RewindFiltered(pub ColorGrading);
+
+/// Desaturates every camera while a `GlobalRewindEvent` is going.
+pub fn filter_global_rewinds(
+    mut commands: Commands,
+    global_rewind: Option<Res<GlobalRewind>>,
+    mut camera_query: Query<(Entity, &mut ColorGrading, Option<&RewindFiltered>), With<Camera>>,
+) {
+    let active = global_rewind.is_some_and(|r| r.is_active());
+    for (e_camera, mut grading, filtered) in camera_query.iter_mut() {
+        match (active, filtered) {
+            (true, None) => {
+                commands.entity(e_camera).insert(RewindFiltered(*grading));
+                grading.pre_saturation = REWIND_SATURATION;
+                grading.post_saturation = REWIND_SATURATION;
+                grading.exposure += REWIND_EXPOSURE;
+            }
+            (false, Some(RewindFiltered(original))) => {
+                *grading = *original;
+                commands.entity(e_camera).remove::<RewindFiltered>();
+            }
+            _ => (),
+        }
+    }
+}
@@ -15,6 +15,8 @@ use bevy::{
 };
 pub(crate) use bevy_mod_outline::*;
 
+use crate::flipbook::MaterialLayerAnimator;
+
 pub type SketchMaterial = ExtendedMaterial<StandardMaterial, SketchMaterialInfo>;
 
 pub struct SketchEffectPlugin {
@@ -118,7 +120,10 @@ pub fn animate_sketched_materials(
     textures: Res<Assets<Image>>,
     mut materials: ResMut<Assets<SketchMaterial>>,
     time: Res<Time>,
-    material_handle_query: Query<(&Handle<SketchMaterial>, &SketchAnimation)>,
+    material_handle_query: Query<
+        (&Handle<SketchMaterial>, &SketchAnimation),
+        Without<MaterialLayerAnimator>,
+    >,
 ) {
     for (material_handle, sketch) in material_handle_query.iter() {
         if let Some(material) = materials.get_mut(material_handle) {
@@ -326,6 +331,14 @@ pub struct SketchMaterialInfo {
     /// `y_cutoff`.
     #[uniform(104)]
     pub y_cutoff: f32,
+    /// Dissolve effect enabled? See `Dissolve`.
+    pub dissolve_enabled: bool,
+    /// Proportion dissolved.
+    #[uniform(105)]
+    pub dissolve: f32,
+    /// Glow at the edge of the dissolve, in linear RGBA.
+    #[uniform(106)]
+    pub dissolve_edge_color: Vec4,
 }
 
 impl Default for SketchMaterialInfo {
@@ -336,6 +349,9 @@ impl Default for SketchMaterialInfo {
             base_color_texture: None,
             fill_enabled: true,
             y_cutoff: f32::MAX,
+            dissolve_enabled: false,
+            dissolve: 0.0,
+            dissolve_edge_color: Vec4::ONE,
         }
     }
 }
@@ -356,6 +372,7 @@ impl SketchMaterialInfo {
 pub struct SketchMaterialKey {
     pub sketch_enabled: bool,
     pub y_cutoff_enabled: bool,
+    pub dissolve_enabled: bool,
 }
 
 impl From<&SketchMaterialInfo> for SketchMaterialKey {
@@ -363,6 +380,7 @@ impl From<&SketchMaterialInfo> for SketchMaterialKey {
         Self {
             sketch_enabled: value.sketch_enabled,
             y_cutoff_enabled: value.fill_enabled,
+            dissolve_enabled: value.dissolve_enabled,
         }
     }
 }
@@ -394,6 +412,9 @@ impl MaterialExtension for SketchMaterialInfo {
                 descriptor.vertex.shader_defs.push("FILL".into());
                 fragment.shader_defs.push("FILL".into());
             }
+            if key.bind_group_data.dissolve_enabled {
+                fragment.shader_defs.push("DISSOLVE".into());
+            }
         }
 
         debug!(
@@ -0,0 +1,225 @@
+//! Extra meshes on humanoids, like hats and shades.
+
+use bevy::{
+    gltf::{GltfMesh, GltfNode},
+    prelude::*,
+    render::view::RenderLayers,
+};
+use grin_render::sketched::SketchMaterial;
+
+use crate::humanoid::{Humanoid, HumanoidPartType};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AccessorySpec {
+    /// What it's stuck to.
+    pub part: HumanoidPartType,
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<SketchMaterial>,
+    /// Relative to `part`.
+    pub transform: Transform,
+}
+
+impl AccessorySpec {
+    /// Takes the mesh and offset from a GLTF node, like the accessories on the premade rigs.
+    ///
+    /// `None` if the node or its mesh isn't loaded.
+    pub fn from_gltf_node(
+        part: HumanoidPartType,
+        node: &Handle<GltfNode>,
+        material: Handle<SketchMaterial>,
+        nodes: &Assets<GltfNode>,
+        gltf_meshes: &Assets<GltfMesh>,
+    ) -> Option<Self> {
+        let node = nodes.get(node)?;
+        let gltf_mesh = gltf_meshes.get(node.mesh.as_ref()?)?;
+        Some(Self {
+            part,
+            mesh: gltf_mesh.primitives.first()?.mesh.clone(),
+            material,
+            transform: node.transform,
+        })
+    }
+}
+
+/// Accessories for a humanoid. Gets spawned when the humanoid is loaded, and respawned
+/// whenever it changes.
+#[derive(Component, Clone, Debug, Default)]
+pub struct HumanoidAccessories(pub Vec<AccessorySpec>);
+
+/// An accessory spawned from a `HumanoidAccessories`.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Accessory {
+    pub humanoid: Entity,
+}
+
+/// What's been spawned for each `HumanoidAccessories` entry.
+#[derive(Component, Debug, Default)]
+pub struct SpawnedAccessories(pub Vec<(AccessorySpec, Entity)>);
+
+/// Spawns accessories that were added, and despawns ones that were taken off.
+pub fn attach_accessories(
+    mut commands: Commands,
+    mut humanoid_query: Query<(
+        Entity,
+        &Humanoid,
+        Ref<HumanoidAccessories>,
+        Option<&mut SpawnedAccessories>,
+    )>,
+    removed_query: Query<(Entity, &SpawnedAccessories), Without<HumanoidAccessories>>,
+) {
+    for (e_humanoid, humanoid, accessories, spawned) in humanoid_query.iter_mut() {
+        let mut spawned = match spawned {
+            Some(_) if !accessories.is_changed() => continue,
+            Some(spawned) => std::mem::take(&mut spawned.into_inner().0),
+            None => Vec::new(),
+        };
+
+        spawned.retain(|(spec, e_accessory)| {
+            let keep = accessories.0.contains(spec);
+            if !keep {
+                if let Some(e) = commands.get_entity(*e_accessory) {
+                    e.despawn_recursive();
+                }
+            }
+            keep
+        });
+        for spec in accessories.0.iter() {
+            if spawned.iter().any(|(s, _)| s == spec) {
+                continue;
+            }
+            let e_accessory = commands
+                .spawn((
+                    Accessory {
+                        humanoid: e_humanoid,
+                    },
+                    MaterialMeshBundle {
+                        mesh: spec.mesh.clone(),
+                        material: spec.material.clone(),
+                        transform: spec.transform,
+                        ..Default::default()
+                    },
+                ))
+                .set_parent(humanoid.part(spec.part))
+                .id();
+            spawned.push((spec.clone(), e_accessory));
+        }
+        commands
+            .entity(e_humanoid)
+            .insert(SpawnedAccessories(spawned));
+    }
+
+    for (e_humanoid, spawned) in removed_query.iter() {
+        for (_, e_accessory) in spawned.0.iter() {
+            if let Some(e) = commands.get_entity(*e_accessory) {
+                e.despawn_recursive();
+            }
+        }
+        commands.entity(e_humanoid).remove::<SpawnedAccessories>();
+    }
+}
+
+/// Accessories go on the same `RenderLayers` as the mesh of the part they're on.
+pub fn sync_accessory_render_layers(
+    mut commands: Commands,
+    accessory_query: Query<(Entity, &Parent, Option<&RenderLayers>), With<Accessory>>,
+    children_query: Query<&Children>,
+    layers_query: Query<&RenderLayers, Without<Accessory>>,
+) {
+    for (e_accessory, parent, layers) in accessory_query.iter() {
+        // the part's own mesh is its first child
+        let part_layers = children_query
+            .get(parent.get())
+            .ok()
+            .and_then(|children| layers_query.get(children[0]).ok());
+        match (part_layers, layers) {
+            (Some(part_layers), Some(layers)) if part_layers == layers => (),
+            (Some(part_layers), _) => {
+                commands.entity(e_accessory).insert(*part_layers);
+            }
+            (None, Some(_)) => {
+                commands.entity(e_accessory).remove::<RenderLayers>();
+            }
+            (None, None) => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::humanoid::HumanoidDominantHand;
+
+    use super::*;
+
+    fn spec(part: HumanoidPartType, mesh: u128) -> AccessorySpec {
+        AccessorySpec {
+            part,
+            mesh: Handle::weak_from_u128(mesh),
+            material: Handle::default(),
+            transform: Transform::default(),
+        }
+    }
+
+    fn accessories(app: &mut App) -> Vec<(Entity, Entity)> {
+        let mut query = app.world.query::<(Entity, &Parent, &Accessory)>();
+        query
+            .iter(&app.world)
+            .map(|(e_accessory, parent, _)| (e_accessory, parent.get()))
+            .collect()
+    }
+
+    #[test]
+    fn humanoid_accessories() {
+        let mut app = App::new();
+        app.add_systems(Update, attach_accessories);
+
+        let e_head = app.world.spawn(SpatialBundle::default()).id();
+        let e_part = app.world.spawn(SpatialBundle::default()).id();
+        let e_humanoid = app
+            .world
+            .spawn((
+                Humanoid {
+                    body: e_part,
+                    head: e_head,
+                    lhand: e_part,
+                    rhand: e_part,
+                    armature: e_part,
+                    dominant_hand_type: HumanoidDominantHand::Right,
+                },
+                HumanoidAccessories(vec![
+                    spec(HumanoidPartType::Head, 1),
+                    spec(HumanoidPartType::Body, 2),
+                ]),
+            ))
+            .id();
+
+        app.update();
+        let spawned = accessories(&mut app);
+        assert_eq!(spawned.len(), 2, "Accessories weren't spawned.");
+        let e_shades = spawned
+            .iter()
+            .find(|(_, e_parent)| *e_parent == e_head)
+            .expect("Accessory isn't on the right part.")
+            .0;
+
+        app.world
+            .get_mut::<HumanoidAccessories>(e_humanoid)
+            .unwrap()
+            .0
+            .pop();
+        app.update();
+        assert_eq!(
+            accessories(&mut app),
+            vec![(e_shades, e_head)],
+            "Removed accessory wasn't despawned, or the other one was respawned."
+        );
+
+        app.world
+            .entity_mut(e_humanoid)
+            .remove::<HumanoidAccessories>();
+        app.update();
+        assert!(
+            accessories(&mut app).is_empty(),
+            "Accessories weren't despawned with `HumanoidAccessories`."
+        );
+    }
+}
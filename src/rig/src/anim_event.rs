@@ -0,0 +1,269 @@
+//! Gameplay events at timestamps in animation clips, like when a swing can actually hit.
+
+use bevy::prelude::*;
+
+use crate::humanoid::Humanoid;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimEventKind {
+    EnableHitbox,
+    DisableHitbox,
+    Footstep,
+    /// Where the flash goes.
+    MuzzleFlash(Entity),
+    /// For anything that doesn't deserve its own kind.
+    Custom(u32),
+}
+
+#[derive(Clone, Debug)]
+pub struct AnimationEventMarker {
+    pub clip: Handle<AnimationClip>,
+    /// Seconds into the clip, unaffected by playback speed.
+    pub time: f32,
+    pub event: AnimEventKind,
+}
+
+/// Where the animator was last frame.
+#[derive(Clone, Debug)]
+struct AnimationCursor {
+    clip: Handle<AnimationClip>,
+    seek_time: f32,
+    elapsed: f32,
+    completions: u32,
+    finished: bool,
+}
+
+/// Sends an `AnimationEvent` for each marker whenever the animator passes it, once per pass
+/// through the clip.
+///
+/// Goes on the animator itself, a `Humanoid` (for its armature), or anything under an animator,
+/// like an item. Events only start once it's found its animator, so a clip that was already
+/// playing doesn't fire the markers it's behind.
+#[derive(Component, Clone, Debug, Default)]
+pub struct AnimationEvents {
+    pub markers: Vec<AnimationEventMarker>,
+    cursor: Option<AnimationCursor>,
+}
+
+impl AnimationEvents {
+    pub fn with(mut self, clip: Handle<AnimationClip>, time: f32, event: AnimEventKind) -> Self {
+        self.markers
+            .push(AnimationEventMarker { clip, time, event });
+        self
+    }
+
+    /// Markers for `clip` after `start` and up to `end`, in order.
+    fn markers_in(&self, clip: &Handle<AnimationClip>, start: f32, end: f32) -> Vec<AnimEventKind> {
+        let mut markers = self
+            .markers
+            .iter()
+            .filter(|m| &m.clip == clip && m.time > start && m.time <= end)
+            .collect::<Vec<_>>();
+        markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+        markers.into_iter().map(|m| m.event).collect()
+    }
+}
+
+/// So that markers at 0 are included from the start of a pass.
+const START: f32 = f32::NEG_INFINITY;
+
+/// Sent when an animator passes an `AnimationEventMarker`.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnimationEvent {
+    /// Whatever has the `AnimationEvents`.
+    pub entity: Entity,
+    pub event: AnimEventKind,
+}
+
+/// The animator that drives `entity`'s `AnimationEvents`.
+fn find_animator(
+    entity: Entity,
+    humanoid: Option<&Humanoid>,
+    parent_query: &Query<&Parent>,
+    animator_query: &Query<&AnimationPlayer>,
+) -> Option<Entity> {
+    std::iter::once(entity)
+        .chain(humanoid.map(|h| h.armature))
+        .chain(parent_query.iter_ancestors(entity))
+        .find(|e| animator_query.contains(*e))
+}
+
+/// Should run right after the animators are advanced, so nothing in between can restart them.
+///
+/// Everything skipped over since last frame fires, even several loops of it. Reversed playback
+/// doesn't fire anything.
+pub fn emit_animation_events(
+    clips: Res<Assets<AnimationClip>>,
+    mut events_query: Query<(Entity, &mut AnimationEvents, Option<&Humanoid>)>,
+    parent_query: Query<&Parent>,
+    animator_query: Query<&AnimationPlayer>,
+    mut animation_events: EventWriter<AnimationEvent>,
+) {
+    for (entity, mut anim_events, humanoid) in events_query.iter_mut() {
+        let Some(animator) = find_animator(entity, humanoid, &parent_query, &animator_query)
+            .and_then(|e| animator_query.get(e).ok())
+        else {
+            anim_events.cursor = None;
+            continue;
+        };
+
+        let clip = animator.animation_clip();
+        let cursor = AnimationCursor {
+            clip: clip.clone(),
+            seek_time: animator.seek_time(),
+            elapsed: animator.elapsed(),
+            completions: animator.completions(),
+            finished: animator.is_finished(),
+        };
+        let Some(prev) = anim_events.cursor.replace(cursor.clone()) else {
+            continue;
+        };
+        let mut fired = Vec::new();
+        if &prev.clip != clip {
+            // whatever got cut off shouldn't stay hitting things
+            if !prev.finished {
+                fired.extend(
+                    anim_events
+                        .markers_in(&prev.clip, prev.seek_time, f32::INFINITY)
+                        .into_iter()
+                        .filter(|ev| *ev == AnimEventKind::DisableHitbox),
+                );
+            }
+            fired.extend(anim_events.markers_in(clip, START, cursor.seek_time));
+        } else if cursor.elapsed < prev.elapsed
+            || cursor.completions < prev.completions
+            || (cursor.completions == prev.completions && cursor.seek_time < prev.seek_time)
+        {
+            // restarted, or went backwards
+            if animator.speed() > 0.0 && cursor.completions == 0 {
+                fired.extend(anim_events.markers_in(clip, START, cursor.seek_time));
+            }
+        } else if animator.speed() > 0.0 && !prev.finished {
+            let loops = cursor.completions - prev.completions;
+            if loops == 0 {
+                fired.extend(anim_events.markers_in(clip, prev.seek_time, cursor.seek_time));
+            } else if let Some(duration) = clips.get(clip).map(AnimationClip::duration) {
+                fired.extend(anim_events.markers_in(clip, prev.seek_time, duration));
+                for _ in 1..loops {
+                    fired.extend(anim_events.markers_in(clip, START, duration));
+                }
+                // the last loop doesn't wrap around once it's finished
+                if !cursor.finished {
+                    fired.extend(anim_events.markers_in(clip, START, cursor.seek_time));
+                }
+            }
+        }
+
+        animation_events.send_batch(
+            fired
+                .into_iter()
+                .map(|event| AnimationEvent { entity, event }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::animation::{EntityPath, Interpolation, Keyframes, VariableCurve};
+    use grin_asset::animation::AnimationGraphAsset;
+
+    use crate::GrinAnimationPlugin;
+
+    use super::*;
+
+    fn clip(duration: f32) -> AnimationClip {
+        let mut clip = AnimationClip::default();
+        clip.add_curve_to_path(
+            EntityPath {
+                parts: vec![Name::new("bone")],
+            },
+            VariableCurve {
+                keyframe_timestamps: vec![0.0, duration],
+                keyframes: Keyframes::Translation(vec![Vec3::ZERO, Vec3::ONE]),
+                interpolation: Interpolation::Linear,
+            },
+        );
+        clip
+    }
+
+    fn step(app: &mut App, secs: f32) -> Vec<AnimEventKind> {
+        app.world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs_f32(secs));
+        app.update();
+        app.world
+            .resource_mut::<Events<AnimationEvent>>()
+            .drain()
+            .map(|ev| ev.event)
+            .collect()
+    }
+
+    #[test]
+    fn animation_events() {
+        let mut app = App::new();
+        app.add_plugins((AssetPlugin::default(), AnimationPlugin))
+            .init_resource::<Time>()
+            .init_asset::<AnimationGraphAsset>()
+            .add_plugins(GrinAnimationPlugin);
+
+        let mut clips = app.world.resource_mut::<Assets<AnimationClip>>();
+        let h_swing = clips.add(clip(1.0));
+        let h_other = clips.add(clip(1.0));
+        let e_armature = app
+            .world
+            .spawn((
+                AnimationPlayer::default(),
+                AnimationEvents::default()
+                    .with(h_swing.clone(), 0.9, AnimEventKind::DisableHitbox)
+                    .with(h_swing.clone(), 0.0, AnimEventKind::Custom(0))
+                    .with(h_swing.clone(), 0.5, AnimEventKind::EnableHitbox),
+            ))
+            .id();
+        step(&mut app, 0.1);
+
+        let mut animator = app.world.get_mut::<AnimationPlayer>(e_armature).unwrap();
+        animator.start(h_swing.clone()).repeat();
+        assert_eq!(
+            step(&mut app, 0.1),
+            vec![AnimEventKind::Custom(0)],
+            "Marker at 0 didn't fire."
+        );
+
+        assert_eq!(
+            step(&mut app, 1.0),
+            vec![
+                AnimEventKind::EnableHitbox,
+                AnimEventKind::DisableHitbox,
+                AnimEventKind::Custom(0),
+            ],
+            "Skipped markers didn't fire in order after looping."
+        );
+
+        let mut animator = app.world.get_mut::<AnimationPlayer>(e_armature).unwrap();
+        animator
+            .start_with_transition(h_swing.clone(), Duration::from_secs_f32(0.1))
+            .repeat();
+        assert_eq!(
+            step(&mut app, 0.1),
+            vec![AnimEventKind::Custom(0)],
+            "Restarting the clip didn't start a new pass."
+        );
+
+        assert_eq!(
+            step(&mut app, 0.5),
+            vec![AnimEventKind::EnableHitbox],
+            "Marker didn't fire."
+        );
+        assert_eq!(step(&mut app, 0.0), vec![], "Marker fired twice.");
+
+        let mut animator = app.world.get_mut::<AnimationPlayer>(e_armature).unwrap();
+        animator.start(h_other);
+        assert_eq!(
+            step(&mut app, 0.1),
+            vec![AnimEventKind::DisableHitbox],
+            "Hitbox wasn't disabled when the clip got cut off."
+        );
+    }
+}
@@ -0,0 +1,357 @@
+//! Footstep sounds and dust.
+
+use bevy::{audio::Volume, prelude::*};
+use bevy_asset_loader::prelude::*;
+use bevy_rapier3d::prelude::*;
+use grin_asset::AssetLoadState;
+use grin_damage::health::Dead;
+use grin_physics::{CollisionGroupExt, PhysicsTime};
+use grin_render::particles::{spawn_dust_puff, DustParticles};
+use grin_time::{global_rewind_active, Rewind};
+use serde::Deserialize;
+
+use crate::anim_event::{AnimEventKind, AnimationEvent};
+
+/// How far above and below a foot to look for the ground.
+pub const FOOTSTEP_RAY_LENGTH: f32 = 0.25;
+
+/// Footsteps at this speed or faster play at full volume.
+pub const FOOTSTEP_FULL_VOLUME_SPEED: f32 = 8.0;
+
+/// Footsteps are never quieter than this.
+pub const FOOTSTEP_MIN_VOLUME: f32 = 0.1;
+
+pub struct FootstepPlugin;
+
+impl Plugin for FootstepPlugin {
+    fn build(&self, app: &mut App) {
+        app.configure_loading_state(
+            LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<FootstepAssets>(),
+        )
+        .add_event::<FootstepEvent>()
+        .add_systems(
+            Update,
+            (
+                (emit_stride_footsteps, emit_animated_footsteps).run_if(not(global_rewind_active)),
+                play_footsteps.run_if(in_state(AssetLoadState::Success)),
+            )
+                .chain(),
+        );
+    }
+}
+
+#[derive(Resource, AssetCollection)]
+pub struct FootstepAssets {
+    #[asset(key = "sfx.footstep")]
+    pub footstep: Handle<AudioSource>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Foot {
+    Left,
+    Right,
+}
+
+impl Foot {
+    pub fn other(&self) -> Self {
+        match self {
+            Self::Left => Self::Right,
+            Self::Right => Self::Left,
+        }
+    }
+}
+
+/// What the ground is made of. Goes on map colliders.
+#[derive(Component, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum SurfaceKind {
+    #[default]
+    Stone,
+    Dirt,
+    Wood,
+    Metal,
+}
+
+impl SurfaceKind {
+    /// Playback speed of the footstep sound.
+    pub fn pitch(&self) -> f32 {
+        match self {
+            Self::Stone => 1.0,
+            Self::Dirt => 0.8,
+            Self::Wood => 1.2,
+            Self::Metal => 1.5,
+        }
+    }
+
+    /// Whether stepping on it kicks up dust.
+    pub fn dusty(&self) -> bool {
+        matches!(self, Self::Stone | Self::Dirt)
+    }
+}
+
+/// Sent when a foot hits the ground.
+#[derive(Event, Clone, Copy, Debug)]
+pub struct FootstepEvent {
+    pub entity: Entity,
+    pub foot: Foot,
+    /// Where the foot landed.
+    pub translation: Vec3,
+    /// How fast `entity` was going.
+    pub speed: f32,
+    /// `None` if the ground doesn't have a `SurfaceKind`, or there isn't any ground.
+    pub surface: Option<SurfaceKind>,
+}
+
+/// Replaces the usual footstep sound.
+#[derive(Component, Clone, Debug)]
+pub struct FootstepSound(pub Handle<AudioSource>);
+
+/// Sends `FootstepEvent`s every `stride` units that a `KinematicCharacterController` moves.
+///
+/// For characters whose legs are animated rather than procedural, so there's no real foot
+/// to go off of. Their feet are assumed to be at their origin.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct StrideFootsteps {
+    pub stride: f32,
+    /// Distance since the last footstep.
+    pub traveled: f32,
+    pub next: Foot,
+}
+
+impl Default for StrideFootsteps {
+    fn default() -> Self {
+        Self {
+            stride: 1.5,
+            traveled: 0.0,
+            next: Foot::Left,
+        }
+    }
+}
+
+/// The `SurfaceKind` under `point`.
+pub fn surface_at(
+    rapier_context: &RapierContext,
+    surface_query: &Query<&SurfaceKind>,
+    point: Vec3,
+) -> Option<SurfaceKind> {
+    rapier_context
+        .cast_ray(
+            point + Vec3::Y * FOOTSTEP_RAY_LENGTH,
+            Vec3::NEG_Y,
+            FOOTSTEP_RAY_LENGTH * 2.0,
+            true,
+            QueryFilter::new().groups(CollisionGroups::new(Group::all(), Group::MAP)),
+        )
+        .and_then(|(e_ground, _)| surface_query.get(e_ground).ok().copied())
+}
+
+pub fn emit_stride_footsteps(
+    time: Res<PhysicsTime>,
+    rapier_context: Res<RapierContext>,
+    mut character_query: Query<
+        (
+            Entity,
+            &mut StrideFootsteps,
+            &KinematicCharacterControllerOutput,
+            &GlobalTransform,
+        ),
+        (Without<Dead>, Without<Rewind>),
+    >,
+    surface_query: Query<&SurfaceKind>,
+    mut footstep_events: EventWriter<FootstepEvent>,
+) {
+    let dt = time.0.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+
+    for (entity, mut strides, output, g_transform) in character_query.iter_mut() {
+        let distance = output.effective_translation.xz().length();
+        if distance <= 0.0 {
+            continue;
+        }
+
+        strides.traveled += distance;
+        if strides.traveled < strides.stride {
+            continue;
+        }
+        strides.traveled %= strides.stride;
+
+        let translation = g_transform.translation();
+        footstep_events.send(FootstepEvent {
+            entity,
+            foot: strides.next,
+            translation,
+            speed: distance / dt,
+            surface: surface_at(&rapier_context, &surface_query, translation),
+        });
+        strides.next = strides.next.other();
+    }
+}
+
+/// Sends `FootstepEvent`s for `AnimEventKind::Footstep` markers.
+///
+/// Feet alternate with `StrideFootsteps::next` if there is one, otherwise it's always the left.
+pub fn emit_animated_footsteps(
+    time: Res<PhysicsTime>,
+    rapier_context: Res<RapierContext>,
+    mut character_query: Query<
+        (
+            &GlobalTransform,
+            Option<&KinematicCharacterControllerOutput>,
+            Option<&mut StrideFootsteps>,
+        ),
+        (Without<Dead>, Without<Rewind>),
+    >,
+    surface_query: Query<&SurfaceKind>,
+    mut animation_events: EventReader<AnimationEvent>,
+    mut footstep_events: EventWriter<FootstepEvent>,
+) {
+    let dt = time.0.delta_seconds();
+    for &AnimationEvent { entity, event } in animation_events.read() {
+        if event != AnimEventKind::Footstep {
+            continue;
+        }
+        let Ok((g_transform, output, strides)) = character_query.get_mut(entity) else {
+            continue;
+        };
+
+        let foot = match strides {
+            Some(mut strides) => {
+                let foot = strides.next;
+                strides.next = foot.other();
+                foot
+            }
+            None => Foot::Left,
+        };
+        let speed = match (output, dt > 0.0) {
+            (Some(output), true) => output.effective_translation.xz().length() / dt,
+            _ => FOOTSTEP_FULL_VOLUME_SPEED,
+        };
+        let translation = g_transform.translation();
+        footstep_events.send(FootstepEvent {
+            entity,
+            foot,
+            translation,
+            speed,
+            surface: surface_at(&rapier_context, &surface_query, translation),
+        });
+    }
+}
+
+/// Plays sounds and kicks up dust for `FootstepEvent`s.
+pub fn play_footsteps(
+    mut commands: Commands,
+    assets: Res<FootstepAssets>,
+    dust: Option<Res<DustParticles>>,
+    mut footstep_events: EventReader<FootstepEvent>,
+    sound_query: Query<&FootstepSound>,
+    suppressed_query: Query<(), Or<(With<Dead>, With<Rewind>)>>,
+) {
+    for event in footstep_events.read() {
+        if suppressed_query.contains(event.entity) {
+            continue;
+        }
+
+        let source = sound_query
+            .get(event.entity)
+            .map_or_else(|_| assets.footstep.clone(), |s| s.0.clone());
+        let volume = (event.speed / FOOTSTEP_FULL_VOLUME_SPEED).clamp(FOOTSTEP_MIN_VOLUME, 1.0);
+        commands.spawn((
+            AudioBundle {
+                source,
+                settings: PlaybackSettings::DESPAWN
+                    .with_spatial(true)
+                    .with_volume(Volume::new(volume))
+                    .with_speed(event.surface.unwrap_or_default().pitch()),
+            },
+            TransformBundle::from_transform(Transform::from_translation(event.translation)),
+        ));
+
+        if let Some(dust) = dust.as_ref() {
+            if event.surface.unwrap_or_default().dusty() {
+                spawn_dust_puff(&mut commands, dust, event.translation);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{render::mesh::MeshPlugin, scene::ScenePlugin, time::TimePlugin};
+
+    use super::*;
+
+    #[test]
+    fn stride_footsteps() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .init_resource::<PhysicsTime>()
+            .add_event::<FootstepEvent>()
+            .add_systems(Update, emit_stride_footsteps);
+
+        app.world.spawn((
+            Collider::cuboid(4.0, 0.1, 4.0),
+            CollisionGroups::new(Group::MAP, Group::all()),
+            SurfaceKind::Metal,
+            TransformBundle::from_transform(Transform::from_xyz(0.0, -0.1, 0.0)),
+        ));
+        let e_walker = app
+            .world
+            .spawn((
+                StrideFootsteps {
+                    stride: 1.0,
+                    ..Default::default()
+                },
+                KinematicCharacterControllerOutput {
+                    effective_translation: Vec3::X * 0.5,
+                    ..Default::default()
+                },
+                TransformBundle::default(),
+            ))
+            .id();
+
+        // colliders get added to the physics world here
+        app.update();
+        app.world.resource_mut::<Events<FootstepEvent>>().clear();
+
+        let mut footsteps = Vec::new();
+        for _ in 0..5 {
+            app.world
+                .resource_mut::<PhysicsTime>()
+                .0
+                .advance_by(Duration::from_secs_f32(0.1));
+            app.update();
+            footsteps.extend(
+                app.world
+                    .resource_mut::<Events<FootstepEvent>>()
+                    .drain()
+                    .map(|ev| (ev.foot, ev.surface)),
+            );
+        }
+        assert_eq!(
+            footsteps,
+            vec![
+                (Foot::Left, Some(SurfaceKind::Metal)),
+                (Foot::Right, Some(SurfaceKind::Metal)),
+            ],
+            "Wrong footsteps after walking 2 strides."
+        );
+
+        app.world.entity_mut(e_walker).insert(Dead);
+        app.world
+            .resource_mut::<PhysicsTime>()
+            .0
+            .advance_by(Duration::from_secs_f32(0.1));
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(
+            app.world.resource::<Events<FootstepEvent>>().is_empty(),
+            "Dead character is still walking."
+        );
+    }
+}
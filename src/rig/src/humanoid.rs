@@ -1,16 +1,29 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashSet};
 use bevy_asset_loader::prelude::*;
 use bevy_rapier3d::prelude::*;
-use grin_asset::AssetLoadState;
-use grin_damage::health::Dead;
-use grin_physics::{collider, CollisionGroupExt, CollisionGroupsExt};
-use grin_render::sketched::SketchMaterial;
-use grin_time::{scaling::RawVelocity, CommandsExt};
+use grin_asset::{animation::AnimationGraphAsset, AssetLoadState};
+use grin_damage::health::{Dead, PartDamageScale};
+use grin_physics::{
+    collider, interpolation::KinematicInterpolation, CollisionGroupExt, CollisionGroupsExt,
+    PhysicsTime,
+};
+use grin_render::{decal::clear_decals_on, sketched::SketchMaterial};
+use grin_time::{scaling::RawVelocity, CommandsExt, RewindableDespawn, TimeChildren};
+use grin_util::rng::{GrinRng, RNG_DEBRIS, RNG_HUMANOID};
 use rand::{distributions::Uniform, Rng};
 
+use crate::{
+    accessory::{attach_accessories, sync_accessory_render_layers, Accessory},
+    socket::AttachmentSockets,
+    AnimationGraph,
+};
+
 pub const HUMANOID_HEIGHT: f32 = 2.625;
 pub const HUMANOID_RADIUS: f32 = 0.5;
 
+/// Seconds before a ragdoll gets cleaned up.
+pub const RAGDOLL_LIFETIME: f32 = 10.0;
+
 pub struct HumanoidPlugin;
 
 impl Plugin for HumanoidPlugin {
@@ -18,6 +31,7 @@ impl Plugin for HumanoidPlugin {
         app.configure_loading_state(
             LoadingStateConfig::new(AssetLoadState::Loading).load_collection::<HumanoidAssets>(),
         )
+        .init_resource::<GrinRng>()
         .add_systems(Update, dash.before(PhysicsSet::StepSimulation))
         .add_systems(
             Update,
@@ -26,12 +40,22 @@ impl Plugin for HumanoidPlugin {
                 // since scenes render in preupdate this'll actually have to wait a frame
                 // so ordering doesn't really matter
                 init_shattered_fragments.run_if(in_state(AssetLoadState::Success)),
+                ragdoll_on_death,
+                // props use `Shattered` too
+                clear_decals_on::<Shattered>,
+                start_death_timers,
+                despawn_timed_out_humanoids,
             ),
         )
         .add_systems(
             Update,
             (
-                process_skeletons.run_if(in_state(AssetLoadState::Success)),
+                (
+                    process_skeletons.run_if(in_state(AssetLoadState::Success)),
+                    attach_accessories,
+                )
+                    .chain(),
+                sync_accessory_render_layers,
                 morph_moving_humanoids,
             ),
         );
@@ -56,6 +80,108 @@ pub struct HumanoidAssets {
     pub body_gray: Handle<SketchMaterial>,
     #[asset(key = "mat.skin")]
     pub skin: Handle<SketchMaterial>,
+    #[asset(key = "animgraph.humanoid")]
+    pub animation_graph: Handle<AnimationGraphAsset>,
+    // square versions don't all exist yet. see `MissingSquareAssets`
+    #[asset(key = "mesh.square_body", optional)]
+    pub square_body: Option<Handle<Mesh>>,
+    #[asset(key = "model.square_body_shatter", optional)]
+    pub square_body_shatter: Option<Handle<Scene>>,
+    #[asset(key = "mesh.square_head", optional)]
+    pub square_head: Option<Handle<Mesh>>,
+    #[asset(key = "model.square_head_shatter", optional)]
+    pub square_head_shatter: Option<Handle<Scene>>,
+    #[asset(key = "mesh.square_hand", optional)]
+    pub square_hand: Option<Handle<Mesh>>,
+}
+
+impl HumanoidAssets {
+    pub fn body(
+        &self,
+        race: HumanoidRace,
+        build: HumanoidBuild,
+        missing: &mut MissingSquareAssets,
+    ) -> Handle<Mesh> {
+        let round = match build {
+            HumanoidBuild::Male => &self.mbody,
+            HumanoidBuild::Female => &self.fbody,
+        };
+        match race {
+            HumanoidRace::Round => round.clone(),
+            HumanoidRace::Square => missing.pick("mesh.square_body", &self.square_body, round),
+        }
+    }
+
+    pub fn body_shatter(
+        &self,
+        race: HumanoidRace,
+        missing: &mut MissingSquareAssets,
+    ) -> Handle<Scene> {
+        match race {
+            HumanoidRace::Round => self.mbody_shatter.clone(),
+            HumanoidRace::Square => missing.pick(
+                "model.square_body_shatter",
+                &self.square_body_shatter,
+                &self.mbody_shatter,
+            ),
+        }
+    }
+
+    pub fn head(&self, race: HumanoidRace, missing: &mut MissingSquareAssets) -> Handle<Mesh> {
+        match race {
+            HumanoidRace::Round => self.head.clone(),
+            HumanoidRace::Square => missing.pick("mesh.square_head", &self.square_head, &self.head),
+        }
+    }
+
+    pub fn head_shatter(
+        &self,
+        race: HumanoidRace,
+        missing: &mut MissingSquareAssets,
+    ) -> Handle<Scene> {
+        match race {
+            HumanoidRace::Round => self.head_shatter.clone(),
+            HumanoidRace::Square => missing.pick(
+                "model.square_head_shatter",
+                &self.square_head_shatter,
+                &self.head_shatter,
+            ),
+        }
+    }
+
+    pub fn hand(&self, race: HumanoidRace, missing: &mut MissingSquareAssets) -> Handle<Mesh> {
+        match race {
+            HumanoidRace::Round => self.hand.clone(),
+            HumanoidRace::Square => missing.pick("mesh.square_hand", &self.square_hand, &self.hand),
+        }
+    }
+}
+
+/// Keys of the square humanoid assets that aren't in the asset file, which get the round ones
+/// instead. Each one is only warned about once.
+#[derive(Default)]
+pub struct MissingSquareAssets(HashSet<&'static str>);
+
+impl MissingSquareAssets {
+    fn pick<A: Asset>(
+        &mut self,
+        key: &'static str,
+        square: &Option<Handle<A>>,
+        round: &Handle<A>,
+    ) -> Handle<A> {
+        match square {
+            Some(square) => square.clone(),
+            None => {
+                if self.0.insert(key) {
+                    warn!(
+                        "Square humanoid asset `{}` doesn't exist yet. Using the round one.",
+                        key
+                    );
+                }
+                round.clone()
+            }
+        }
+    }
 }
 
 /// Root object for humanoid rigs.
@@ -107,6 +233,34 @@ impl Humanoid {
     }
 }
 
+/// Damage multipliers for the `HumanoidPartType::HITBOX` parts. Goes on the root.
+///
+/// These get turned into a `PartDamageScale` on each part.
+#[derive(Component, Debug, Clone, Copy)]
+pub struct HumanoidDamageScales {
+    pub head: f32,
+    pub body: f32,
+}
+
+impl Default for HumanoidDamageScales {
+    fn default() -> Self {
+        Self {
+            head: 2.0,
+            body: 1.0,
+        }
+    }
+}
+
+impl HumanoidDamageScales {
+    pub fn get(&self, part: &HumanoidPartType) -> PartDamageScale {
+        PartDamageScale(match part {
+            HumanoidPartType::Head => self.head,
+            HumanoidPartType::Body => self.body,
+            _ => 1.0,
+        })
+    }
+}
+
 #[derive(Component, Default)]
 pub struct Head;
 
@@ -119,14 +273,29 @@ pub struct Hand;
 #[derive(Component)]
 pub struct DominantHand;
 
-#[derive(Component, Default, Clone, Copy, Eq, PartialEq)]
+#[derive(Component, Debug, Default, Clone, Copy, Eq, PartialEq)]
 pub enum HumanoidRace {
     #[default]
     Round,
     Square,
 }
 
-#[derive(Component, Clone, Copy, Eq, PartialEq)]
+impl HumanoidRace {
+    /// Shape for character controllers. Centered, so it should be offset by half of
+    /// `HUMANOID_HEIGHT`.
+    pub fn controller_shape(&self) -> Collider {
+        match self {
+            HumanoidRace::Round => {
+                Collider::capsule_y(HUMANOID_HEIGHT / 2.0 - HUMANOID_RADIUS, HUMANOID_RADIUS)
+            }
+            HumanoidRace::Square => {
+                Collider::cuboid(HUMANOID_RADIUS, HUMANOID_HEIGHT / 2.0, HUMANOID_RADIUS)
+            }
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Copy, Eq, PartialEq)]
 pub enum HumanoidBuild {
     Male,
     Female,
@@ -139,11 +308,10 @@ impl HumanoidBuild {
             false => HumanoidBuild::Female,
         }
     }
-}
 
-impl Default for HumanoidBuild {
-    fn default() -> Self {
-        Self::random(&mut rand::thread_rng())
+    /// `random`, but from `GrinRng`, so it's the same for the same seed.
+    pub fn seeded(rng: &mut GrinRng) -> Self {
+        Self::random(rng.fork(RNG_HUMANOID))
     }
 }
 
@@ -162,11 +330,10 @@ impl HumanoidDominantHand {
             false => HumanoidDominantHand::Right,
         }
     }
-}
 
-impl Default for HumanoidDominantHand {
-    fn default() -> Self {
-        Self::random(&mut rand::thread_rng())
+    /// `random`, but from `GrinRng`, so it's the same for the same seed.
+    pub fn seeded(rng: &mut GrinRng) -> Self {
+        Self::random(rng.fork(RNG_HUMANOID))
     }
 }
 
@@ -200,6 +367,7 @@ pub struct HumanoidBundle {
     pub spatial: SpatialBundle,
 }
 
+/// A right handed male. See `random` for anything else.
 impl Default for HumanoidBundle {
     fn default() -> Self {
         Self {
@@ -208,13 +376,24 @@ impl Default for HumanoidBundle {
             face: HumanoidFace::default(),
             clothing: HumanoidClothing::default(),
             race: HumanoidRace::default(),
-            build: HumanoidBuild::default(),
-            dominant_hand: HumanoidDominantHand::default(),
+            build: HumanoidBuild::Male,
+            dominant_hand: HumanoidDominantHand::Right,
             spatial: SpatialBundle::default(),
         }
     }
 }
 
+impl HumanoidBundle {
+    /// Seeded build and dominant hand.
+    pub fn random(rng: &mut GrinRng) -> Self {
+        Self {
+            build: HumanoidBuild::seeded(rng),
+            dominant_hand: HumanoidDominantHand::seeded(rng),
+            ..Default::default()
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Dash {
     pub velocity: Vec3,
@@ -242,10 +421,30 @@ pub fn dash(
     }
 }
 
+/// What happens to a `Humanoid` when it's `Dead`. `Shatter` if there isn't one.
+#[derive(Component, Debug, Default, Clone, Copy, PartialEq)]
+pub enum DeathBehavior {
+    /// See `shatter_on_death`.
+    #[default]
+    Shatter,
+    /// See `ragdoll_on_death`. Cleaned up after `RAGDOLL_LIFETIME` seconds.
+    Ragdoll,
+    /// Just disappears after `delay` seconds.
+    Despawn { delay: f32 },
+}
+
+/// Despawns a dead `Humanoid` and its `TimeChildren` when finished.
+#[derive(Component, Debug)]
+pub struct DeathTimer(pub Timer);
+
 #[derive(Component)]
 #[component(storage = "SparseSet")]
 pub struct Shattered;
 
+#[derive(Component)]
+#[component(storage = "SparseSet")]
+pub struct Ragdolled;
+
 #[derive(Component)]
 #[component(storage = "SparseSet")]
 pub struct Shatter {
@@ -256,7 +455,7 @@ pub struct Shatter {
 
 /// For any `Humanoid` with `Dead`, this will
 /// - Shatter `Humanoid` and `Humanoid.head` into fragments.
-/// - Drop any other descendants with colliders on the ground.
+/// - Drop any other descendants with colliders on the ground, and any `Accessory`s.
 /// - Remove the `Handle<Mesh>`, `Handle<Material>` and `Collider`
 /// from the humanoid and all descendants.
 ///
@@ -265,14 +464,30 @@ pub struct Shatter {
 pub fn shatter_on_death(
     mut commands: Commands,
     assets: Res<HumanoidAssets>,
-    humanoid_query: Query<(Entity, &Humanoid, &RawVelocity), (With<Dead>, Without<Shattered>)>,
+    mut missing: Local<MissingSquareAssets>,
+    humanoid_query: Query<
+        (
+            Entity,
+            &Humanoid,
+            &RawVelocity,
+            Option<&HumanoidRace>,
+            Option<&DeathBehavior>,
+        ),
+        (With<Dead>, Without<Shattered>),
+    >,
     shatter_query: Query<(&GlobalTransform, &Handle<SketchMaterial>)>,
     child_query: Query<(&GlobalTransform, &Collider)>,
     mesh_query: Query<(Entity, &Handle<Mesh>, &Handle<SketchMaterial>)>,
     children_query: Query<&Children>,
+    accessory_query: Query<&GlobalTransform, With<Accessory>>,
+    meshes: Res<Assets<Mesh>>,
 ) {
-    for (e_humanoid, humanoid, velocity) in humanoid_query.iter() {
+    for (e_humanoid, humanoid, velocity, race, behavior) in humanoid_query.iter() {
+        if behavior.is_some_and(|b| *b != DeathBehavior::Shatter) {
+            continue;
+        }
         commands.entity(e_humanoid).insert(Shattered);
+        let race = race.copied().unwrap_or_default();
 
         // cause the head to explode and the body to crumble
         // there's a little bit of speed on the body
@@ -280,12 +495,12 @@ pub fn shatter_on_death(
         for (e_fragment, scene, speed) in [
             (
                 children_query.get(humanoid.body).unwrap()[0],
-                &assets.mbody_shatter,
+                assets.body_shatter(race, &mut missing),
                 Uniform::new_inclusive(0.0, 2.0),
             ),
             (
                 children_query.get(humanoid.head).unwrap()[0],
-                &assets.head_shatter,
+                assets.head_shatter(race, &mut missing),
                 Uniform::new_inclusive(64.0, 86.0),
             ),
         ] {
@@ -298,7 +513,7 @@ pub fn shatter_on_death(
                         speed,
                     },
                     SceneBundle {
-                        scene: scene.clone(),
+                        scene,
                         transform: g_transform.compute_transform(),
                         ..Default::default()
                     },
@@ -317,7 +532,30 @@ pub fn shatter_on_death(
                 continue;
             }
 
-            if let Ok((g_transform, collider)) = child_query.get(e_child) {
+            // accessories don't have colliders while they're on, so they get a box
+            let child = child_query
+                .get(e_child)
+                .map(|(g_transform, collider)| (g_transform, collider.clone()))
+                .or_else(|_| {
+                    let g_transform = accessory_query.get(e_child)?;
+                    let (_, mesh, _) = mesh_query.get(e_child)?;
+                    Ok(match meshes.get(mesh).and_then(Mesh::compute_aabb) {
+                        Some(aabb) => (
+                            g_transform,
+                            Collider::compound(vec![(
+                                aabb.center.into(),
+                                Quat::IDENTITY,
+                                Collider::cuboid(
+                                    aabb.half_extents.x,
+                                    aabb.half_extents.y,
+                                    aabb.half_extents.z,
+                                ),
+                            )]),
+                        ),
+                        None => (g_transform, Collider::ball(0.1)),
+                    })
+                });
+            if let Ok((g_transform, collider)) = child {
                 // if the mesh is on the collider, use this entity
                 // if the mesh is a child, use that entity
                 if let Ok((e_mesh, mesh, material)) = mesh_query.get(e_child).or_else(|_| {
@@ -338,7 +576,7 @@ pub fn shatter_on_death(
                                 ..Default::default()
                             },
                             RigidBody::Dynamic,
-                            collider.clone(),
+                            collider,
                             CollisionGroups::from_group_default(Group::DEBRIS),
                             velocity.0.clone(),
                         ))
@@ -356,6 +594,7 @@ pub fn init_shattered_fragments(
     transform_query: Query<&GlobalTransform>,
     mesh_query: Query<(Entity, &Handle<Mesh>)>,
     meshes: Res<Assets<Mesh>>,
+    mut rng: ResMut<GrinRng>,
 ) {
     // this looks more complicated than it should be
     // cause `Velocity` is always in global space
@@ -387,7 +626,7 @@ pub fn init_shattered_fragments(
                 Velocity {
                     linvel: *inherited_velocity
                         + (g_transform1.translation() - g_transform0.translation()).normalize()
-                            * rand::thread_rng().sample(speed),
+                            * rng.fork(RNG_DEBRIS).sample(speed),
                     ..Default::default()
                 },
             ));
@@ -395,6 +634,131 @@ pub fn init_shattered_fragments(
     }
 }
 
+/// For any `Humanoid` with `Dead` and `DeathBehavior::Ragdoll`, this will
+/// - Take the parts out of the hierarchy, so they're in global space.
+/// - Make each part with a collider a `RigidBody::Dynamic` in the `DEBRIS` group.
+/// - Attach the head and hands to the body with joints.
+/// Parts without colliders get stuck to the body instead.
+///
+/// The root entity stops being a rigidbody. The parts are time children of it.
+pub fn ragdoll_on_death(
+    mut commands: Commands,
+    humanoid_query: Query<
+        (Entity, &Humanoid, &RawVelocity, &DeathBehavior),
+        (With<Dead>, Without<Ragdolled>),
+    >,
+    collider_query: Query<(), With<Collider>>,
+    transform_query: Query<&GlobalTransform>,
+    children_query: Query<&Children>,
+) {
+    for (e_humanoid, humanoid, velocity, behavior) in humanoid_query.iter() {
+        if *behavior != DeathBehavior::Ragdoll {
+            continue;
+        }
+        commands
+            .entity(e_humanoid)
+            .insert(Ragdolled)
+            .remove::<RigidBody>();
+
+        let Ok(g_body_transform) = transform_query.get(humanoid.body) else {
+            continue;
+        };
+        let body_inverse = g_body_transform.affine().inverse();
+
+        for e_part in humanoid.parts(HumanoidPartType::ALL) {
+            // if the collider isn't on the part, it's on the mesh
+            let e_collider = std::iter::once(e_part)
+                .chain(children_query.get(e_part).into_iter().flatten().copied())
+                .find(|e| collider_query.contains(*e));
+
+            let Some(e_collider) = e_collider else {
+                if e_part != humanoid.body {
+                    commands.entity(e_part).set_parent_in_place(humanoid.body);
+                }
+                continue;
+            };
+
+            commands
+                .entity(e_collider)
+                .insert(CollisionGroups::from_group_default(Group::DEBRIS));
+            commands
+                .entity(e_part)
+                .remove_parent_in_place()
+                .insert((RigidBody::Dynamic, velocity.0.clone()))
+                .set_time_parent(e_humanoid);
+
+            if e_part == humanoid.body {
+                continue;
+            }
+
+            // pivot around wherever the part is attached to the body right now,
+            // which is close enough to a neck or shoulder
+            let Ok(g_part_transform) = transform_query.get(e_part) else {
+                continue;
+            };
+            let joint = SphericalJointBuilder::new()
+                .local_anchor1(body_inverse.transform_point3(g_part_transform.translation()))
+                .local_anchor2(Vec3::ZERO)
+                .contacts_enabled(false);
+            commands
+                .entity(e_part)
+                .insert(ImpulseJoint::new(humanoid.body, joint));
+        }
+    }
+}
+
+/// Inserts `DeathTimer` for `DeathBehavior`s that need cleaning up.
+pub fn start_death_timers(
+    mut commands: Commands,
+    humanoid_query: Query<
+        (Entity, &DeathBehavior),
+        (With<Humanoid>, With<Dead>, Without<DeathTimer>),
+    >,
+) {
+    for (e_humanoid, behavior) in humanoid_query.iter() {
+        let seconds = match behavior {
+            DeathBehavior::Shatter => continue,
+            DeathBehavior::Ragdoll => RAGDOLL_LIFETIME,
+            DeathBehavior::Despawn { delay } => *delay,
+        };
+        commands
+            .entity(e_humanoid)
+            .insert(DeathTimer(Timer::from_seconds(seconds, TimerMode::Once)));
+    }
+}
+
+pub fn despawn_timed_out_humanoids(
+    mut commands: Commands,
+    time: Res<PhysicsTime>,
+    mut humanoid_query: Query<(
+        Entity,
+        &mut DeathTimer,
+        Option<&TimeChildren>,
+        Has<RewindableDespawn>,
+    )>,
+) {
+    for (e_humanoid, mut timer, time_children, rewindable) in humanoid_query.iter_mut() {
+        if !timer.0.tick(time.0.delta()).finished() {
+            continue;
+        }
+        // parks the `TimeChildren` too. the timer starts over if it comes back still dead
+        if rewindable {
+            commands
+                .entity(e_humanoid)
+                .remove::<DeathTimer>()
+                .time_despawn();
+            continue;
+        }
+        // ragdoll parts aren't in the regular hierarchy anymore
+        for e_child in time_children.into_iter().flat_map(|c| c.0.iter()) {
+            if let Some(e_child) = commands.get_entity(*e_child) {
+                e_child.despawn_recursive();
+            }
+        }
+        commands.entity(e_humanoid).despawn_recursive();
+    }
+}
+
 #[derive(Component, Default, Clone, Copy)]
 pub struct Skeleton;
 
@@ -433,6 +797,7 @@ impl HumanoidBuilder {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HumanoidPartType {
     Body,
     Head,
@@ -493,9 +858,12 @@ impl std::fmt::Display for HumanoidLoadError {
 /// - Inserts `Humanoid`.
 /// - Updates meshes and textures to be in line with cosmetic components.
 /// - Assigns dominant hand.
+/// - Inserts `AttachmentSockets`.
+/// - Interpolates the armature between physics steps with `KinematicInterpolation`.
 pub fn process_skeletons(
     mut commands: Commands,
     assets: Res<HumanoidAssets>,
+    mut missing: Local<MissingSquareAssets>,
     skeleton_query: Query<
         (
             Entity,
@@ -504,22 +872,22 @@ pub fn process_skeletons(
             &HumanoidDominantHand,
             &HumanoidFace,
             &HumanoidClothing,
+            Has<AnimationGraph>,
         ),
         (With<Skeleton>, With<Children>, Without<Humanoid>),
     >,
     children_query: Query<&Children>,
     name_query: Query<&Name>,
+    transform_query: Query<&Transform>,
 ) {
-    for (e_skeleton, race, build, dominant_hand, face, clothing) in skeleton_query.iter() {
+    for (e_skeleton, race, build, dominant_hand, face, clothing, has_graph) in skeleton_query.iter()
+    {
         let mut builder = HumanoidBuilder::default();
         builder.dominant_hand_type = Some(dominant_hand.clone());
 
         let face = face.0.clone().unwrap_or(assets.skin.clone());
         let clothing = clothing.0.clone().unwrap_or(assets.body_gray.clone());
-        let hand_mesh = match race {
-            HumanoidRace::Round => assets.hand.clone(),
-            HumanoidRace::Square => todo!(),
-        };
+        let hand_mesh = assets.hand(*race, &mut missing);
 
         // technically don't need to clone stuff in here
         // but I'm not feeling like writing unsafe today
@@ -538,29 +906,18 @@ pub fn process_skeletons(
                     commands.entity(e_node).insert((Head, Velocity::default()));
 
                     let e_mesh = children_query.get(e_node).unwrap()[0];
-                    commands.entity(e_mesh).insert((
-                        face.clone(),
-                        match race {
-                            HumanoidRace::Round => assets.head.clone(),
-                            HumanoidRace::Square => todo!(),
-                        },
-                    ));
+                    commands
+                        .entity(e_mesh)
+                        .insert((face.clone(), assets.head(*race, &mut missing)));
                 }
                 HumanoidPartType::Body => {
                     builder.body = Some(e_node);
                     commands.entity(e_node).insert(Body);
 
                     let e_mesh = children_query.get(e_node).unwrap()[0];
-                    commands.entity(e_mesh).insert((
-                        clothing.clone(),
-                        match race {
-                            HumanoidRace::Round => match build {
-                                HumanoidBuild::Male => assets.mbody.clone(),
-                                HumanoidBuild::Female => assets.fbody.clone(),
-                            },
-                            HumanoidRace::Square => todo!(),
-                        },
-                    ));
+                    commands
+                        .entity(e_mesh)
+                        .insert((clothing.clone(), assets.body(*race, *build, &mut missing)));
                 }
                 // TODO:
                 // hand colliders do not follow animations right now. not a huge deal,
@@ -596,7 +953,22 @@ pub fn process_skeletons(
 
         match builder.build() {
             Ok(humanoid) => {
-                commands.entity(e_skeleton).insert(humanoid);
+                let sockets = AttachmentSockets::collect(e_skeleton, &children_query, &name_query);
+                let interpolation = KinematicInterpolation::new(
+                    humanoid.armature,
+                    transform_query
+                        .get(humanoid.armature)
+                        .copied()
+                        .unwrap_or_default(),
+                );
+                commands
+                    .entity(e_skeleton)
+                    .insert((humanoid, sockets, interpolation));
+                if !has_graph {
+                    commands
+                        .entity(e_skeleton)
+                        .insert(AnimationGraph::new(assets.animation_graph.clone()));
+                }
             }
             Err(e) => error!("{}", e),
         }
@@ -631,11 +1003,16 @@ pub const MORPH_EASE_CONSTANT: f32 = 4.0;
 /// in intensity based on their speed. The slant is based on `Velocity.linvel`.
 pub fn morph_moving_humanoids(
     time: Res<Time>,
-    humanoid_query: Query<(&Humanoid, &Transform, &Velocity)>,
+    mut humanoid_query: Query<(
+        &Humanoid,
+        &Transform,
+        &Velocity,
+        Option<&mut KinematicInterpolation>,
+    )>,
     mut transform_query: Query<&mut Transform, Without<Humanoid>>,
     mut morph_query: Query<&mut MorphWeights>,
 ) {
-    for (humanoid, transform, velocity) in humanoid_query.iter() {
+    for (humanoid, transform, velocity, interpolation) in humanoid_query.iter_mut() {
         let speed = velocity.linvel.length();
         let translation_norm = velocity.linvel.normalize_or_zero();
 
@@ -679,7 +1056,7 @@ pub fn morph_moving_humanoids(
         }
 
         // right/left weights
-        if weight_z > 0.0 {
+        if weight_x > 0.0 {
             weights[HumanoidMorph::Right as usize] = weight_x;
             weights[HumanoidMorph::Left as usize] = 0.0;
         } else {
@@ -687,8 +1064,164 @@ pub fn morph_moving_humanoids(
             weights[HumanoidMorph::Left as usize] = -weight_x;
         }
 
-        let mut armature_transform = transform_query.get_mut(humanoid.armature).unwrap();
-        armature_transform.translation =
-            Vec3::new(weight_x, armature_transform.translation.y, weight_z);
+        let lean = |armature_transform: &mut Transform| {
+            armature_transform.translation =
+                Vec3::new(weight_x, armature_transform.translation.y, weight_z);
+        };
+        match interpolation {
+            // it resets the armature to `render_base` every frame
+            Some(mut interpolation) if interpolation.render == humanoid.armature => {
+                lean(&mut interpolation.render_base)
+            }
+            _ => lean(&mut transform_query.get_mut(humanoid.armature).unwrap()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_humanoids() {
+        let humanoids = |seed| {
+            let mut app = App::new();
+            app.insert_resource(GrinRng::new(seed)).add_systems(
+                Update,
+                |mut commands: Commands, mut rng: ResMut<GrinRng>| {
+                    for _ in 0..16 {
+                        commands.spawn(HumanoidBundle::random(&mut rng));
+                    }
+                },
+            );
+            app.update();
+            let mut humanoids = app
+                .world
+                .query::<(Entity, &HumanoidBuild, &HumanoidDominantHand)>()
+                .iter(&app.world)
+                .map(|(e, build, hand)| (e, *build, *hand))
+                .collect::<Vec<_>>();
+            humanoids.sort_by_key(|(e, ..)| *e);
+            humanoids
+                .into_iter()
+                .map(|(_, build, hand)| (build, hand))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            humanoids(7),
+            humanoids(7),
+            "Same seed gave different humanoids."
+        );
+        assert_ne!(humanoids(7), humanoids(8), "Seed didn't do anything.");
+    }
+
+    fn spawn_skeleton(app: &mut App, race: HumanoidRace) -> Entity {
+        let e_skeleton = app
+            .world
+            .spawn((
+                Skeleton,
+                race,
+                HumanoidBuild::Male,
+                HumanoidDominantHand::Right,
+                HumanoidFace::default(),
+                HumanoidClothing::default(),
+                SpatialBundle::default(),
+            ))
+            .id();
+        for part in HumanoidPartType::ALL
+            .into_iter()
+            .chain([HumanoidPartType::Armature])
+        {
+            let e_mesh = app.world.spawn(SpatialBundle::default()).id();
+            let e_node = app
+                .world
+                .spawn((
+                    Name::new(part.node_id().to_string()),
+                    SpatialBundle::default(),
+                ))
+                .add_child(e_mesh)
+                .id();
+            app.world.entity_mut(e_skeleton).add_child(e_node);
+        }
+        e_skeleton
+    }
+
+    fn mesh(app: &App, e_part: Entity) -> Handle<Mesh> {
+        let e_mesh = app.world.get::<Children>(e_part).unwrap()[0];
+        app.world.get::<Handle<Mesh>>(e_mesh).unwrap().clone()
+    }
+
+    #[test]
+    fn humanoid_races() {
+        let mut app = App::new();
+        let assets = HumanoidAssets {
+            mbody: Handle::weak_from_u128(1),
+            mbody_shatter: Handle::default(),
+            fbody: Handle::weak_from_u128(2),
+            head: Handle::weak_from_u128(3),
+            head_shatter: Handle::default(),
+            hand: Handle::weak_from_u128(4),
+            body_gray: Handle::default(),
+            skin: Handle::default(),
+            animation_graph: Handle::default(),
+            square_body: None,
+            square_body_shatter: None,
+            square_head: Some(Handle::weak_from_u128(5)),
+            square_head_shatter: None,
+            square_hand: None,
+        };
+        app.insert_resource(assets)
+            .add_systems(Update, process_skeletons);
+
+        let e_round = spawn_skeleton(&mut app, HumanoidRace::Round);
+        let e_square = spawn_skeleton(&mut app, HumanoidRace::Square);
+        app.update();
+
+        let round = app
+            .world
+            .get::<Humanoid>(e_round)
+            .expect("Round humanoid wasn't built.");
+        assert_eq!(
+            mesh(&app, round.head),
+            Handle::weak_from_u128(3),
+            "Round humanoid got the wrong head."
+        );
+        let square = app
+            .world
+            .get::<Humanoid>(e_square)
+            .expect("Square humanoid wasn't built.");
+        assert_eq!(
+            mesh(&app, square.head),
+            Handle::weak_from_u128(5),
+            "Square humanoid didn't get the square head."
+        );
+        assert_eq!(
+            mesh(&app, square.body),
+            Handle::weak_from_u128(1),
+            "Square humanoid didn't fall back to the round body."
+        );
+        assert_eq!(
+            app.world
+                .get::<KinematicInterpolation>(e_round)
+                .map(|interpolation| interpolation.render),
+            Some(round.armature),
+            "Armature isn't interpolated."
+        );
+
+        assert!(
+            HumanoidRace::Round
+                .controller_shape()
+                .as_capsule()
+                .is_some(),
+            "Round humanoid isn't a capsule."
+        );
+        assert!(
+            HumanoidRace::Square
+                .controller_shape()
+                .as_cuboid()
+                .is_some(),
+            "Square humanoid isn't a cuboid."
+        );
     }
 }
@@ -1,47 +1,200 @@
+pub mod accessory;
+pub mod anim_event;
+pub mod footstep;
 pub mod humanoid;
+pub mod socket;
 
-use bevy::{animation::RepeatAnimation, prelude::*};
+use anim_event::{emit_animation_events, AnimationEvent};
+use bevy::{
+    animation::{animation_player, RepeatAnimation},
+    prelude::*,
+};
+use grin_asset::animation::{AnimationGraphAsset, IDLE_STATE};
 use humanoid::Humanoid;
 
 pub struct GrinAnimationPlugin;
 
 impl Plugin for GrinAnimationPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, load_idle);
+        app.add_event::<AnimationEvent>()
+            .add_systems(Update, play_animation_states)
+            .add_systems(PostUpdate, emit_animation_events.after(animation_player));
     }
 }
 
-/// Makes a rig instantly assume the animation provided.
-#[derive(Component, Clone)]
-pub struct Idle {
-    pub clip: Handle<AnimationClip>,
+/// The animation states a rig has. Humanoids get the default one when they're loaded,
+/// unless they were spawned with one.
+#[derive(Component, Clone, Debug)]
+pub struct AnimationGraph {
+    pub graph: Handle<AnimationGraphAsset>,
+    /// What was played last. `None` if nothing's played yet.
+    pub state: Option<&'static str>,
 }
 
-pub fn load_idle(
+impl AnimationGraph {
+    pub fn new(graph: Handle<AnimationGraphAsset>) -> Self {
+        Self { graph, state: None }
+    }
+}
+
+/// Makes a rig go to an animation state. It stays on until it can be played.
+///
+/// If the rig's `AnimationGraph` doesn't have the state, it goes to idle instead.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayAnimationState(pub &'static str);
+
+pub fn play_animation_states(
     mut commands: Commands,
-    // TODO: apply query filters here
-    // component will probably have to be moved to the armature itself
-    // in order to track `Changed<AnimationPlayer>`,
-    // which is a bit annoying + too lazy to do RN.
-    humanoid_query: Query<(Entity, &Humanoid, Ref<Idle>)>,
+    graphs: Res<Assets<AnimationGraphAsset>>,
+    mut rig_query: Query<(
+        Entity,
+        &Humanoid,
+        &mut AnimationGraph,
+        Option<&PlayAnimationState>,
+    )>,
     mut animator_query: Query<&mut AnimationPlayer>,
 ) {
-    for (e_rig, humanoid, idle) in humanoid_query.iter() {
+    for (e_rig, humanoid, mut graph, request) in rig_query.iter_mut() {
         let Ok(mut animator) = animator_query.get_mut(humanoid.armature) else {
             error!(
-                msg="Could not load idle animation: Animator not found.",
+                msg="Could not play animation state: Animator not found.",
                 e_rig=?e_rig,
             );
             continue;
         };
+        let Some(graph_asset) = graphs.get(&graph.graph) else {
+            continue;
+        };
+
+        let requested = match request {
+            Some(PlayAnimationState(state)) => {
+                commands.entity(e_rig).remove::<PlayAnimationState>();
+                *state
+            }
+            // some things play their clips directly, so this goes back to idle after
+            None if graph.state.is_none() || graph.is_changed() || animator.is_finished() => {
+                IDLE_STATE
+            }
+            None => continue,
+        };
+
+        let (state, anim) = match graph_asset.states.get(requested) {
+            Some(anim) => (requested, anim),
+            None => {
+                warn!(
+                    "Animation state `{}` isn't in the graph. Falling back to idle.",
+                    requested
+                );
+                let Some(anim) = graph_asset.states.get(IDLE_STATE) else {
+                    error!(
+                        msg="Could not play animation state: Graph has no idle.",
+                        e_rig=?e_rig,
+                    );
+                    continue;
+                };
+                (IDLE_STATE, anim)
+            }
+        };
+
+        animator
+            .play_with_transition(anim.clip.clone(), graph_asset.blend(graph.state, state))
+            .set_repeat(match anim.repeat {
+                true => RepeatAnimation::Forever,
+                false => RepeatAnimation::Never,
+            });
+        // so that it's only changed when the graph itself is
+        graph.bypass_change_detection().state = Some(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use grin_asset::animation::AnimationGraphState;
+
+    use crate::humanoid::HumanoidDominantHand;
+
+    use super::*;
+
+    fn playing(app: &App, e_armature: Entity) -> Handle<AnimationClip> {
+        app.world
+            .get::<AnimationPlayer>(e_armature)
+            .unwrap()
+            .animation_clip()
+            .clone()
+    }
 
-        // HACK: second condition checks whether the animator was never played
-        // (there may be some edge cases where this occurs naturally; will need
-        // to figure this one out).
-        if animator.is_finished() || animator.elapsed() == 0.0 || idle.is_changed() {
-            animator
-                .set_repeat(RepeatAnimation::Forever)
-                .play(idle.clip.clone());
+    #[test]
+    fn animation_states() {
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin::default())
+            .init_asset::<AnimationClip>()
+            .init_asset::<AnimationGraphAsset>()
+            .add_plugins(GrinAnimationPlugin);
+
+        let mut clips = app.world.resource_mut::<Assets<AnimationClip>>();
+        let h_idle = clips.add(AnimationClip::default());
+        let h_aim = clips.add(AnimationClip::default());
+        let mut graph = AnimationGraphAsset::default();
+        for (state, clip) in [("idle", &h_idle), ("aim", &h_aim)] {
+            graph.states.insert(
+                state.to_string(),
+                AnimationGraphState {
+                    clip: clip.clone(),
+                    repeat: true,
+                },
+            );
         }
+        let h_graph = app
+            .world
+            .resource_mut::<Assets<AnimationGraphAsset>>()
+            .add(graph);
+
+        let e_armature = app.world.spawn(AnimationPlayer::default()).id();
+        let e_rig = app
+            .world
+            .spawn((
+                Humanoid {
+                    body: e_armature,
+                    head: e_armature,
+                    lhand: e_armature,
+                    rhand: e_armature,
+                    armature: e_armature,
+                    dominant_hand_type: HumanoidDominantHand::Right,
+                },
+                AnimationGraph::new(h_graph),
+            ))
+            .id();
+
+        app.update();
+        assert_eq!(
+            playing(&app, e_armature),
+            h_idle,
+            "Rig didn't start in idle."
+        );
+
+        app.world
+            .entity_mut(e_rig)
+            .insert(PlayAnimationState("aim"));
+        app.update();
+        assert_eq!(playing(&app, e_armature), h_aim, "State wasn't played.");
+        assert_eq!(
+            app.world.get::<AnimationGraph>(e_rig).unwrap().state,
+            Some("aim"),
+            "State wasn't tracked."
+        );
+        assert!(
+            app.world.get::<PlayAnimationState>(e_rig).is_none(),
+            "Request wasn't taken off."
+        );
+
+        app.world
+            .entity_mut(e_rig)
+            .insert(PlayAnimationState("missing"));
+        app.update();
+        assert_eq!(
+            playing(&app, e_armature),
+            h_idle,
+            "Missing state didn't fall back to idle."
+        );
     }
 }
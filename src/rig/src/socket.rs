@@ -0,0 +1,46 @@
+//! Named attachment points in GLTF models.
+//!
+//! Any empty node named `Socket.<Something>` is a socket. Stuff gets parented to it instead of
+//! being placed with magic numbers.
+
+use bevy::{prelude::*, utils::HashMap};
+
+pub const SOCKET_PREFIX: &str = "Socket.";
+
+/// Where projectiles come out of a weapon.
+pub const SOCKET_MUZZLE: &str = "Socket.Muzzle";
+/// Where the main hand holds an item.
+pub const SOCKET_GRIP_MAIN: &str = "Socket.GripMain";
+/// Where the off hand holds an item.
+pub const SOCKET_GRIP_OFF: &str = "Socket.GripOff";
+
+/// Socket name to socket entity. Built when the model is loaded.
+#[derive(Component, Debug, Clone, Default)]
+pub struct AttachmentSockets(pub HashMap<String, Entity>);
+
+impl AttachmentSockets {
+    pub fn is_socket(name: &str) -> bool {
+        name.starts_with(SOCKET_PREFIX)
+    }
+
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.0.get(name).copied()
+    }
+
+    /// Collects every socket under `e_root`.
+    pub fn collect(
+        e_root: Entity,
+        children_query: &Query<&Children>,
+        name_query: &Query<&Name>,
+    ) -> Self {
+        Self(
+            children_query
+                .iter_descendants(e_root)
+                .filter_map(|e_node| {
+                    let name = name_query.get(e_node).ok()?;
+                    Self::is_socket(name.as_str()).then(|| (name.to_string(), e_node))
+                })
+                .collect(),
+        )
+    }
+}
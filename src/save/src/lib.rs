@@ -0,0 +1,594 @@
+//! Save slots. Each one is a RON file in the `SaveDirectory` with just enough to put the level
+//! back together: the map, the player, whichever enemies are alive and the dialogue flags.
+//!
+//! Loading throws out the player and enemies, waits for the map, then spawns them again through
+//! the usual events. Transforms and health get patched on once they show up.
+
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use bevy::prelude::*;
+use grin_ai::{encounter::EnemyRegistry, EnemyIdentifier};
+use grin_asset::AssetLoadState;
+use grin_character::{menu::RespawnPlayerEvent, AvatarLoadState, PlayerCharacter, StatusViewport};
+use grin_damage::health::{Dead, Health};
+use grin_dialogue::flags::{DialogueFlag, DialogueFlags};
+use grin_input::camera::PlayerCamera;
+use grin_item::{
+    equip::Equipped, inventory::Inventory, library::plugin::ItemIdentifier,
+    spawn::UntypedItemSpawnEvent,
+};
+use grin_map::{Map, MapData, MapLoadState};
+use grin_util::state::GameState;
+use serde::{Deserialize, Serialize};
+
+/// Bump this when `SaveGame` changes in a way that old saves can't be read.
+pub const SAVE_VERSION: u32 = 1;
+
+/// Respawned enemies are matched up with their saves by where they are.
+pub const ENEMY_MATCH_DISTANCE: f32 = 1E-3;
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SaveDirectory>()
+            .init_resource::<EnemyRegistry>()
+            .init_resource::<PendingLoad>()
+            .init_resource::<PendingRestore>()
+            .add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        save_game,
+                        apply_pending_load
+                            .run_if(in_state(AssetLoadState::Success))
+                            .run_if(in_state(MapLoadState::Success)),
+                        load_game,
+                    )
+                        .chain(),
+                    (restore_player, restore_enemies, restore_items).chain(),
+                ),
+            );
+    }
+}
+
+/// Writes the game as it is to `slot`.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SaveGameEvent {
+    pub slot: u8,
+}
+
+/// Replaces the game with what's in `slot`. Nothing happens if it can't be read.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LoadGameEvent {
+    pub slot: u8,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct SaveDirectory(pub PathBuf);
+
+impl Default for SaveDirectory {
+    fn default() -> Self {
+        Self(
+            dirs::data_dir()
+                .unwrap_or_default()
+                .join("grin")
+                .join("saves"),
+        )
+    }
+}
+
+impl SaveDirectory {
+    pub fn slot_path(&self, slot: u8) -> PathBuf {
+        self.0.join(format!("slot_{}.ron", slot))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SaveHeader {
+    pub version: u32,
+}
+
+impl Default for SaveHeader {
+    fn default() -> Self {
+        Self {
+            version: SAVE_VERSION,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct PlayerSave {
+    pub transform: Transform,
+    pub health: f32,
+    pub items: Vec<ItemIdentifier>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct EnemySave {
+    /// Name in the `EnemyRegistry`.
+    pub enemy_type: String,
+    pub transform: Transform,
+    pub health: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct SaveGame {
+    pub header: SaveHeader,
+    /// Asset path of the `Map` scene.
+    pub map: Option<String>,
+    pub player: Option<PlayerSave>,
+    pub enemies: Vec<EnemySave>,
+    pub flags: BTreeMap<String, DialogueFlag>,
+}
+
+/// Only reads the header, so the version can be checked before the rest.
+#[derive(Deserialize)]
+struct VersionCheck {
+    header: SaveHeader,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(io::Error),
+    Format(String),
+    /// Saved by a different version of the game.
+    Version(u32),
+}
+
+pub fn write_save(path: &Path, game: &SaveGame) -> Result<(), SaveError> {
+    let text = ron::ser::to_string_pretty(game, ron::ser::PrettyConfig::default())
+        .map_err(|e| SaveError::Format(e.to_string()))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(SaveError::Io)?;
+    }
+    fs::write(path, text).map_err(SaveError::Io)
+}
+
+pub fn read_save(path: &Path) -> Result<SaveGame, SaveError> {
+    let text = fs::read_to_string(path).map_err(SaveError::Io)?;
+    let VersionCheck { header } =
+        ron::from_str(&text).map_err(|e| SaveError::Format(e.to_string()))?;
+    if header.version != SAVE_VERSION {
+        return Err(SaveError::Version(header.version));
+    }
+    ron::from_str(&text).map_err(|e| SaveError::Format(e.to_string()))
+}
+
+/// A `SaveGame` that's waiting on the map to load.
+#[derive(Resource, Default)]
+pub struct PendingLoad(pub Option<SaveGame>);
+
+/// Whatever's been respawned from a `SaveGame`, but hasn't shown up yet.
+#[derive(Resource, Default)]
+pub struct PendingRestore {
+    pub player: Option<PlayerSave>,
+    pub enemies: Vec<EnemySave>,
+    pub items: Vec<ItemIdentifier>,
+}
+
+fn map_path(asset_server: Option<&AssetServer>, h_scene: &Handle<Scene>) -> Option<String> {
+    asset_server?
+        .get_path(h_scene.id())
+        .map(|path| path.to_string())
+}
+
+pub fn save_game(
+    directory: Res<SaveDirectory>,
+    registry: Res<EnemyRegistry>,
+    asset_server: Option<Res<AssetServer>>,
+    flags: Option<Res<DialogueFlags>>,
+    map_query: Query<&Handle<Scene>, With<Map>>,
+    player_query: Query<
+        (&Transform, &Health, Option<&Inventory>, Option<&Equipped>),
+        With<PlayerCharacter>,
+    >,
+    item_query: Query<&ItemIdentifier>,
+    enemy_query: Query<(EntityRef, &Transform, &Health), (With<EnemyIdentifier>, Without<Dead>)>,
+    mut save_events: EventReader<SaveGameEvent>,
+) {
+    for SaveGameEvent { slot } in save_events.read() {
+        let player =
+            player_query
+                .get_single()
+                .ok()
+                .map(|(transform, health, inventory, equipped)| {
+                    let mut items = match (inventory, equipped) {
+                        (Some(inventory), _) => inventory.items.clone(),
+                        (None, Some(equipped)) => vec![equipped.left, equipped.right],
+                        (None, None) => Vec::new(),
+                    };
+                    // two-handed items are in both
+                    items.dedup();
+                    PlayerSave {
+                        transform: *transform,
+                        health: health.0,
+                        items: item_query.iter_many(items).copied().collect(),
+                    }
+                });
+
+        let enemies = enemy_query
+            .iter()
+            .filter_map(|(entity, transform, health)| {
+                Some(EnemySave {
+                    enemy_type: registry.enemy_type(&entity)?.to_owned(),
+                    transform: *transform,
+                    health: health.0,
+                })
+            })
+            .collect();
+
+        let game = SaveGame {
+            header: SaveHeader::default(),
+            map: map_query
+                .iter()
+                .next()
+                .and_then(|h_scene| map_path(asset_server.as_deref(), h_scene)),
+            player,
+            enemies,
+            flags: flags.map_or_else(BTreeMap::new, |flags| {
+                flags.0.iter().map(|(k, v)| (k.clone(), *v)).collect()
+            }),
+        };
+
+        let path = directory.slot_path(*slot);
+        match write_save(&path, &game) {
+            Ok(()) => info!("Saved slot {} to {}.", slot, path.display()),
+            Err(e) => warn!("Couldn't save slot {} to {}: {:?}", slot, path.display(), e),
+        }
+    }
+}
+
+/// Reads the save and clears out the player and enemies. The map is swapped if it's a different
+/// one. Everything comes back in `apply_pending_load`.
+pub fn load_game(
+    mut commands: Commands,
+    directory: Res<SaveDirectory>,
+    asset_server: Option<Res<AssetServer>>,
+    map_data: Option<Res<MapData>>,
+    mut pending: ResMut<PendingLoad>,
+    mut restore: ResMut<PendingRestore>,
+    mut map_state: ResMut<NextState<MapLoadState>>,
+    mut avatar_state: ResMut<NextState<AvatarLoadState>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    map_query: Query<(Entity, &Handle<Scene>), With<Map>>,
+    gameplay_query: Query<
+        Entity,
+        Or<(
+            With<PlayerCharacter>,
+            With<PlayerCamera>,
+            With<StatusViewport>,
+            With<EnemyIdentifier>,
+        )>,
+    >,
+    mut load_events: EventReader<LoadGameEvent>,
+) {
+    let Some(LoadGameEvent { slot }) = load_events.read().last() else {
+        return;
+    };
+    let path = directory.slot_path(*slot);
+    let game = match read_save(&path) {
+        Ok(game) => game,
+        Err(e) => {
+            warn!(
+                "Couldn't load slot {} from {}: {:?}",
+                slot,
+                path.display(),
+                e
+            );
+            return;
+        }
+    };
+
+    for entity in gameplay_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    *restore = PendingRestore::default();
+    avatar_state.set(AvatarLoadState::NotLoaded);
+    game_state.set(GameState::Playing);
+
+    if let (Some(map), Some(asset_server)) = (game.map.as_ref(), asset_server.as_deref()) {
+        let current = map_query
+            .iter()
+            .next()
+            .and_then(|(_, h_scene)| map_path(Some(asset_server), h_scene));
+        if current.as_ref() != Some(map) {
+            for (e_map, _) in map_query.iter() {
+                commands.entity(e_map).despawn_recursive();
+            }
+            if let Some(map_data) = map_data {
+                commands.entity(map_data.archipelago).despawn_recursive();
+            }
+            commands.spawn((
+                Map,
+                SceneBundle {
+                    scene: asset_server.load(map.clone()),
+                    ..Default::default()
+                },
+            ));
+            map_state.set(MapLoadState::NotLoaded);
+        }
+    }
+
+    pending.0 = Some(game);
+}
+
+pub fn apply_pending_load(
+    mut commands: Commands,
+    registry: Res<EnemyRegistry>,
+    mut pending: ResMut<PendingLoad>,
+    mut restore: ResMut<PendingRestore>,
+    mut respawn_events: EventWriter<RespawnPlayerEvent>,
+) {
+    let Some(game) = pending.0.take() else {
+        return;
+    };
+
+    if let Some(player) = game.player {
+        respawn_events.send(RespawnPlayerEvent);
+        restore.items = player.items.clone();
+        restore.player = Some(player);
+    }
+
+    for enemy in game.enemies {
+        match registry.spawn_fns.get(&enemy.enemy_type) {
+            Some(spawn_fn) => {
+                spawn_fn(&mut commands, enemy.transform);
+                restore.enemies.push(enemy);
+            }
+            None => warn!("Unknown enemy type `{}` in save.", enemy.enemy_type),
+        }
+    }
+
+    commands.insert_resource(DialogueFlags(game.flags.into_iter().collect()));
+    info!("Save loaded.");
+}
+
+pub fn restore_player(
+    mut restore: ResMut<PendingRestore>,
+    mut player_query: Query<(&mut Transform, &mut Health), (With<PlayerCharacter>, Added<Health>)>,
+) {
+    if restore.player.is_none() {
+        return;
+    }
+    let Ok((mut transform, mut health)) = player_query.get_single_mut() else {
+        return;
+    };
+    let Some(player) = restore.player.take() else {
+        return;
+    };
+    *transform = player.transform;
+    health.0 = player.health;
+}
+
+pub fn restore_enemies(
+    mut restore: ResMut<PendingRestore>,
+    mut enemy_query: Query<(&Transform, &mut Health), (With<EnemyIdentifier>, Added<Health>)>,
+) {
+    if restore.enemies.is_empty() {
+        return;
+    }
+    for (transform, mut health) in enemy_query.iter_mut() {
+        if let Some(i) = restore.enemies.iter().position(|enemy| {
+            enemy.transform.translation.distance(transform.translation) < ENEMY_MATCH_DISTANCE
+        }) {
+            health.0 = restore.enemies.swap_remove(i).health;
+        }
+    }
+}
+
+/// Gives back whatever the player had on top of their kit's starting item.
+pub fn restore_items(
+    mut restore: ResMut<PendingRestore>,
+    player_query: Query<(Entity, &Inventory), With<PlayerCharacter>>,
+    item_query: Query<&ItemIdentifier>,
+    mut spawn_events: EventWriter<UntypedItemSpawnEvent>,
+) {
+    if restore.items.is_empty() || restore.player.is_some() {
+        return;
+    }
+    let Ok((e_player, inventory)) = player_query.get_single() else {
+        return;
+    };
+    // the starting item goes in first
+    if inventory.items.is_empty() {
+        return;
+    }
+
+    let mut missing = std::mem::take(&mut restore.items);
+    for identifier in item_query.iter_many(inventory.items.iter()) {
+        if let Some(i) = missing.iter().position(|m| m == identifier) {
+            missing.remove(i);
+        }
+    }
+    for identifier in missing {
+        spawn_events.send(UntypedItemSpawnEvent {
+            identifier,
+            parent_entity: Some(e_player),
+            transform: Transform::default(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("grin_{}_{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_file() {
+        let dir = temp_dir("save_file");
+        let path = SaveDirectory(dir.clone()).slot_path(0);
+
+        let game = SaveGame {
+            map: Some("gltf/rooftop.glb#Scene0".into()),
+            player: Some(PlayerSave {
+                transform: Transform::from_xyz(1.0, 0.0, 2.0),
+                health: 60.0,
+                items: vec![ItemIdentifier::Fist],
+            }),
+            enemies: vec![EnemySave {
+                enemy_type: "dummy".into(),
+                transform: Transform::from_xyz(5.0, 0.0, 5.0)
+                    .with_rotation(Quat::from_rotation_y(1.0)),
+                health: 40.0,
+            }],
+            flags: BTreeMap::from([("met_dummy".into(), DialogueFlag::Bool(true))]),
+            ..Default::default()
+        };
+        write_save(&path, &game).unwrap();
+        let loaded = read_save(&path);
+
+        let old = SaveGame {
+            header: SaveHeader { version: 0 },
+            ..game.clone()
+        };
+        write_save(&path, &old).unwrap();
+        let old_loaded = read_save(&path);
+
+        fs::write(&path, "(header: (version: 1), map: 4)").unwrap();
+        let broken_loaded = read_save(&path);
+
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(loaded.unwrap(), game, "Save didn't round trip.");
+        assert!(
+            matches!(old_loaded, Err(SaveError::Version(0))),
+            "Old save wasn't caught: {:?}",
+            old_loaded
+        );
+        assert!(
+            matches!(broken_loaded, Err(SaveError::Format(_))),
+            "Broken save wasn't caught: {:?}",
+            broken_loaded
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let dir = temp_dir("save_and_load");
+
+        let mut app = App::new();
+        app.init_state::<AssetLoadState>()
+            .init_state::<MapLoadState>()
+            .init_state::<AvatarLoadState>()
+            .init_state::<GameState>()
+            .init_resource::<DialogueFlags>()
+            .add_event::<RespawnPlayerEvent>()
+            .add_event::<UntypedItemSpawnEvent>()
+            .add_plugins(SavePlugin)
+            .insert_resource(SaveDirectory(dir.clone()))
+            .add_systems(
+                Update,
+                |mut commands: Commands, mut respawn_events: EventReader<RespawnPlayerEvent>| {
+                    for _ in respawn_events.read() {
+                        commands.spawn((
+                            PlayerCharacter,
+                            Health(100.0),
+                            TransformBundle::default(),
+                        ));
+                    }
+                },
+            );
+
+        let mut registry = app.world.resource_mut::<EnemyRegistry>();
+        registry.insert("dummy", |commands, transform| {
+            commands.spawn((
+                EnemyIdentifier::Dummy,
+                Health(100.0),
+                TransformBundle::from_transform(transform),
+            ));
+        });
+        registry.identify::<EnemyIdentifier>("dummy");
+
+        app.world
+            .resource_mut::<NextState<AssetLoadState>>()
+            .set(AssetLoadState::Success);
+        app.world
+            .resource_mut::<NextState<MapLoadState>>()
+            .set(MapLoadState::Success);
+
+        let player_transform = Transform::from_xyz(1.0, 0.0, 2.0);
+        let enemy_transform = Transform::from_xyz(5.0, 0.0, 5.0);
+        let e_player = app
+            .world
+            .spawn((
+                PlayerCharacter,
+                Health(60.0),
+                TransformBundle::from_transform(player_transform),
+            ))
+            .id();
+        let e_enemy = app
+            .world
+            .spawn((
+                EnemyIdentifier::Dummy,
+                Health(40.0),
+                TransformBundle::from_transform(enemy_transform),
+            ))
+            .id();
+        app.world.spawn((
+            EnemyIdentifier::Dummy,
+            Health(0.0),
+            Dead,
+            TransformBundle::default(),
+        ));
+        app.world
+            .resource_mut::<DialogueFlags>()
+            .set("met_dummy", true);
+
+        app.world.send_event(SaveGameEvent { slot: 1 });
+        app.update();
+        let saved = SaveDirectory(dir.clone()).slot_path(1).exists();
+
+        // things change after saving
+        app.world.get_mut::<Health>(e_player).unwrap().0 = 10.0;
+        app.world.despawn(e_enemy);
+        app.world.resource_mut::<DialogueFlags>().0.clear();
+
+        // read, then respawn, then restore
+        app.world.send_event(LoadGameEvent { slot: 1 });
+        for _ in 0..5 {
+            app.update();
+        }
+        let _ = fs::remove_dir_all(&dir);
+        assert!(saved, "Didn't save.");
+
+        let players = app
+            .world
+            .query_filtered::<(&Transform, &Health), With<PlayerCharacter>>()
+            .iter(&app.world)
+            .map(|(transform, health)| (*transform, health.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            players,
+            vec![(player_transform, 60.0)],
+            "Player wasn't restored."
+        );
+
+        let enemies = app
+            .world
+            .query_filtered::<(&Transform, &Health), With<EnemyIdentifier>>()
+            .iter(&app.world)
+            .map(|(transform, health)| (*transform, health.0))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            enemies,
+            vec![(enemy_transform, 40.0)],
+            "Enemies weren't restored."
+        );
+
+        assert_eq!(
+            app.world.resource::<DialogueFlags>().get("met_dummy"),
+            1,
+            "Flags weren't restored."
+        );
+    }
+}
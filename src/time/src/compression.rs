@@ -0,0 +1,355 @@
+//! Cheaper storage for `History`s.
+//!
+//! Everything's stored as full copies by default. `RewindComponentPlugin::with_compression`
+//! swaps in something smarter, e.g. `TransformCompression`.
+
+use std::{collections::VecDeque, sync::Arc};
+
+use bevy::prelude::*;
+
+/// A delta between two components, packed into 16 bytes. It's up to the `Compression` what's in it.
+pub type PackedDelta = u128;
+
+/// How a `History` stores components.
+pub trait Compression<T>: Send + Sync + 'static {
+    /// Whether `b` is close enough to `a` that it doesn't need to be stored.
+    fn same(&self, _a: &T, _b: &T) -> bool {
+        false
+    }
+
+    /// A full copy is stored at least every this many components. `1` means no deltas.
+    fn keyframe_interval(&self) -> usize {
+        1
+    }
+
+    /// Difference between `from` and `to`. `None` stores a full copy instead.
+    fn delta(&self, _from: &T, _to: &T) -> Option<PackedDelta> {
+        None
+    }
+
+    /// Undoes `delta`. Only called with deltas from `Compression::delta`.
+    fn apply(&self, from: &T, delta: PackedDelta) -> T;
+}
+
+/// Full copy of everything.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoCompression;
+
+impl<T: Clone> Compression<T> for NoCompression {
+    fn apply(&self, from: &T, _delta: PackedDelta) -> T {
+        from.clone()
+    }
+}
+
+/// Skips components that were change detected without actually changing.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SkipEqual;
+
+impl<T: Clone + PartialEq> Compression<T> for SkipEqual {
+    fn same(&self, a: &T, b: &T) -> bool {
+        a == b
+    }
+
+    fn apply(&self, from: &T, _delta: PackedDelta) -> T {
+        from.clone()
+    }
+}
+
+/// Keyframes + quantized deltas for `Transform`.
+///
+/// Deltas are taken from the previous decoded transform, so error doesn't pile up
+/// past `precision`. Changes in scale, or big jumps, get a keyframe.
+#[derive(Debug, Clone, Copy)]
+pub struct TransformCompression {
+    pub keyframe_interval: usize,
+    /// Translation step, in meters.
+    pub precision: f32,
+}
+
+impl Default for TransformCompression {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: 16,
+            precision: 1.0 / 1024.0,
+        }
+    }
+}
+
+/// Rotation components are in `[-1.0, 1.0]`, so they use the whole `i16` range.
+const ROTATION_STEP: f32 = 1.0 / i16::MAX as f32;
+
+fn quantize(value: f32, step: f32) -> Option<i16> {
+    let q = (value / step).round();
+    (q.abs() <= i16::MAX as f32).then_some(q as i16)
+}
+
+fn pack(values: [i16; 6]) -> PackedDelta {
+    values
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (i, &v)| acc | (v as u16 as u128) << (16 * i))
+}
+
+fn unpack(delta: PackedDelta) -> [i16; 6] {
+    std::array::from_fn(|i| (delta >> (16 * i)) as u16 as i16)
+}
+
+impl Compression<Transform> for TransformCompression {
+    fn same(&self, a: &Transform, b: &Transform) -> bool {
+        a.translation
+            .abs_diff_eq(b.translation, self.precision / 2.0)
+            && a.rotation.abs_diff_eq(b.rotation, ROTATION_STEP / 2.0)
+            && a.scale == b.scale
+    }
+
+    fn keyframe_interval(&self) -> usize {
+        self.keyframe_interval
+    }
+
+    fn delta(&self, from: &Transform, to: &Transform) -> Option<PackedDelta> {
+        if from.scale != to.scale {
+            return None;
+        }
+
+        let translation = to.translation - from.translation;
+        let mut rotation = from.rotation.inverse() * to.rotation;
+        // same rotation, and `w` gets rebuilt as positive
+        if rotation.w < 0.0 {
+            rotation = -rotation;
+        }
+
+        Some(pack([
+            quantize(translation.x, self.precision)?,
+            quantize(translation.y, self.precision)?,
+            quantize(translation.z, self.precision)?,
+            quantize(rotation.x, ROTATION_STEP)?,
+            quantize(rotation.y, ROTATION_STEP)?,
+            quantize(rotation.z, ROTATION_STEP)?,
+        ]))
+    }
+
+    fn apply(&self, from: &Transform, delta: PackedDelta) -> Transform {
+        let [tx, ty, tz, rx, ry, rz] = unpack(delta);
+        let translation = Vec3::new(tx as f32, ty as f32, tz as f32) * self.precision;
+        let xyz = Vec3::new(rx as f32, ry as f32, rz as f32) * ROTATION_STEP;
+        let rotation = Quat::from_xyzw(
+            xyz.x,
+            xyz.y,
+            xyz.z,
+            (1.0 - xyz.length_squared()).max(0.0).sqrt(),
+        );
+
+        Transform {
+            translation: from.translation + translation,
+            rotation: (from.rotation * rotation).normalize(),
+            scale: from.scale,
+        }
+    }
+}
+
+/// The `Compression` for `EntityHistories<T>`.
+#[derive(Resource, Clone)]
+pub struct HistoryCompression<T>(pub Arc<dyn Compression<T>>);
+
+impl<T: Clone + 'static> Default for HistoryCompression<T> {
+    fn default() -> Self {
+        Self(Arc::new(NoCompression))
+    }
+}
+
+/// Components stored in a `History`, oldest first.
+///
+/// The oldest one is always a keyframe, so the front can be forgotten without decoding the rest.
+#[derive(Debug)]
+pub struct ComponentBuffer<T> {
+    keyframes: VecDeque<T>,
+    deltas: VecDeque<PackedDelta>,
+    /// Whether each entry is a keyframe.
+    is_keyframe: VecDeque<bool>,
+}
+
+impl<T> Default for ComponentBuffer<T> {
+    fn default() -> Self {
+        Self {
+            keyframes: VecDeque::new(),
+            deltas: VecDeque::new(),
+            is_keyframe: VecDeque::new(),
+        }
+    }
+}
+
+impl<T: Clone> ComponentBuffer<T> {
+    pub fn len(&self) -> usize {
+        self.is_keyframe.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.is_keyframe.is_empty()
+    }
+
+    /// How many full copies are stored.
+    pub fn keyframes(&self) -> usize {
+        self.keyframes.len()
+    }
+
+    /// Deltas since the latest keyframe.
+    fn trailing_deltas(&self) -> usize {
+        self.is_keyframe.iter().rev().take_while(|k| !**k).count()
+    }
+
+    /// The latest component.
+    pub fn back(&self, compression: &dyn Compression<T>) -> Option<T> {
+        let key = self.keyframes.back()?;
+        let n = self.trailing_deltas();
+        Some(
+            self.deltas
+                .range(self.deltas.len() - n..)
+                .fold(key.clone(), |t, &delta| compression.apply(&t, delta)),
+        )
+    }
+
+    pub fn push_back(&mut self, t: T, compression: &dyn Compression<T>) {
+        let delta = match self.trailing_deltas() + 1 < compression.keyframe_interval() {
+            true => self
+                .back(compression)
+                .and_then(|prev| compression.delta(&prev, &t)),
+            false => None,
+        };
+        match delta {
+            Some(delta) => self.deltas.push_back(delta),
+            None => self.keyframes.push_back(t),
+        }
+        self.is_keyframe.push_back(delta.is_none());
+    }
+
+    pub fn pop_back(&mut self, compression: &dyn Compression<T>) -> Option<T> {
+        let t = self.back(compression)?;
+        if self.is_keyframe.pop_back()? {
+            self.keyframes.pop_back();
+        } else {
+            self.deltas.pop_back();
+        }
+        Some(t)
+    }
+
+    /// Forgets the oldest component.
+    pub fn pop_front(&mut self, compression: &dyn Compression<T>) -> Option<T> {
+        self.is_keyframe.pop_front()?;
+        let t = self.keyframes.pop_front()?;
+        // the next one becomes the oldest, so it needs to be a keyframe
+        if let Some(is_keyframe) = self.is_keyframe.front_mut() {
+            if !*is_keyframe {
+                let delta = self.deltas.pop_front().unwrap();
+                self.keyframes.push_front(compression.apply(&t, delta));
+                *is_keyframe = true;
+            }
+        }
+        Some(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use crate::{EntityHistories, Rewind, RewindComponentPlugin, RewindPlugin};
+
+    use super::*;
+
+    /// Walks in a circle, turning to face the way it's going. Stands still for a bit partway.
+    fn scripted_movement(
+        mut frame: Local<usize>,
+        mut query: Query<&mut Transform, Without<Rewind>>,
+    ) {
+        for mut transform in query.iter_mut() {
+            let t = match *frame {
+                0..=29 => *frame,
+                // change detected but not actually changed
+                30..=39 => 29,
+                _ => *frame - 10,
+            } as f32
+                / 60.0;
+            *transform = Transform::from_xyz((t * TAU).cos() * 4.0, 0.0, (t * TAU).sin() * 4.0)
+                .with_rotation(Quat::from_rotation_y(-t * TAU));
+        }
+        *frame += 1;
+    }
+
+    fn mock_app(compression: Option<TransformCompression>) -> App {
+        let mut app = App::new();
+        let mut plugin = RewindComponentPlugin::<Transform> {
+            fixed_timestep: false,
+            ..Default::default()
+        };
+        if let Some(compression) = compression {
+            plugin = plugin.with_compression(compression);
+        }
+        app.add_plugins((
+            RewindPlugin {
+                fixed_timestep: false,
+            },
+            plugin,
+        ))
+        .add_systems(
+            First,
+            scripted_movement.before(crate::add_new_histories::<Transform>),
+        );
+        app
+    }
+
+    #[test]
+    fn transform_compression() {
+        const FRAMES: usize = 90;
+
+        let compression = TransformCompression::default();
+        let mut apps = [mock_app(None), mock_app(Some(compression))];
+        let entities = apps
+            .each_mut()
+            .map(|app| app.world.spawn(Transform::default()).id());
+
+        for app in apps.iter_mut() {
+            for _ in 0..FRAMES {
+                app.update();
+            }
+        }
+
+        let [plain, compressed] = [0, 1].map(|i| {
+            let histories = apps[i].world.resource::<EntityHistories<Transform>>();
+            let components = &histories.0[&entities[i]].components;
+            (components.len(), components.keyframes())
+        });
+        assert!(
+            compressed.0 < plain.0,
+            "Didn't skip unchanged components. ({} vs {})",
+            compressed.0,
+            plain.0,
+        );
+        assert!(
+            compressed.1 * 8 < plain.1,
+            "Didn't store deltas. ({} keyframes vs {})",
+            compressed.1,
+            plain.1,
+        );
+
+        for (app, &e) in apps.iter_mut().zip(entities.iter()) {
+            app.world.entity_mut(e).insert(Rewind {
+                frames: FRAMES as u32,
+                fps: 1,
+            });
+        }
+        for _ in 0..FRAMES {
+            for app in apps.iter_mut() {
+                app.update();
+            }
+            let [a, b] = [0, 1].map(|i| *apps[i].world.get::<Transform>(entities[i]).unwrap());
+            assert!(
+                a.translation
+                    .abs_diff_eq(b.translation, compression.precision * 2.0)
+                    && a.rotation.abs_diff_eq(b.rotation, 1E-3),
+                "Compressed rewind drifted. ({:?} vs {:?})",
+                a,
+                b,
+            );
+        }
+    }
+}
@@ -1,20 +1,31 @@
 //! This module manages rewind logic for the game.
-//! I expect to make compression optimizations to this in the future(?)
+//! Histories are stored however the plugin's `Compression` says; see `compression`.
 //!
 //! https://youtu.be/8dinUbg2h70
 
+pub mod compression;
 pub mod scaling;
+pub mod zones;
 
-use std::{collections::vec_deque::VecDeque, marker::PhantomData};
+use std::{collections::vec_deque::VecDeque, marker::PhantomData, sync::Arc};
 
 use bevy::{
     ecs::system::{EntityCommand, EntityCommands},
+    hierarchy::despawn_with_children_recursive,
     prelude::*,
     utils::{HashMap, HashSet},
 };
+use bevy_rapier3d::prelude::{Collider, ColliderDisabled, RigidBody, RigidBodyDisabled};
+use compression::{ComponentBuffer, Compression, HistoryCompression, NoCompression};
 
 pub const FIXED_TIMESTEP_SECS: f32 = 1.0 / 60.0;
 
+/// How many frames of history are kept. Also how long a `Parked` entity waits for a `Rewind`.
+pub const HISTORY_FRAMES: usize = 600;
+
+/// `Rewind::fps` for a `GlobalRewindEvent`. Faster than real time so it doesn't drag on.
+pub const GLOBAL_REWIND_FPS: u32 = 2;
+
 /// Dependency for `RewindComponentPlugin`.
 pub struct RewindPlugin {
     // this needs to be toggleable because the stupid unit tests don't work with fixed timesteps...
@@ -31,9 +42,23 @@ impl Default for RewindPlugin {
 
 impl Plugin for RewindPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<Frame>();
+        app.init_resource::<Frame>()
+            .init_resource::<TrackedEntities>()
+            .init_resource::<GlobalRewind>()
+            .add_event::<GlobalRewindEvent>()
+            .add_systems(
+                PreUpdate,
+                (start_global_rewinds, update_global_rewind).chain(),
+            );
 
-        let systems = (update_frame_index, update_rewind_frames, propagate_rewinds);
+        let systems = (
+            update_frame_index,
+            update_rewind_frames,
+            propagate_rewinds,
+            update_parked
+                .after(update_rewind_frames)
+                .before(update_frame_index),
+        );
 
         if self.fixed_timestep {
             app.add_systems(FixedUpdate, systems);
@@ -46,6 +71,7 @@ impl Plugin for RewindPlugin {
 /// Adding this plugin allows the component `T` to be modified by `Rewind`.
 pub struct RewindComponentPlugin<T: Component + Clone> {
     pub fixed_timestep: bool,
+    compression: Arc<dyn Compression<T>>,
     phantom_data: PhantomData<T>,
 }
 
@@ -53,16 +79,32 @@ impl<T: Component + Clone> Default for RewindComponentPlugin<T> {
     fn default() -> Self {
         Self {
             fixed_timestep: true,
+            compression: Arc::new(NoCompression),
             phantom_data: PhantomData::default(),
         }
     }
 }
 
+impl<T: Component + Clone> RewindComponentPlugin<T> {
+    /// Stores `History<T>`s with `compression` instead of full copies.
+    /// Doesn't change what gets rewound, just how much memory it takes.
+    pub fn with_compression(mut self, compression: impl Compression<T>) -> Self {
+        self.compression = Arc::new(compression);
+        self
+    }
+}
+
 impl<T: Component + Clone> Plugin for RewindComponentPlugin<T> {
     fn build(&self, app: &mut App) {
         assert!(app.is_plugin_added::<RewindPlugin>());
 
-        app.init_resource::<EntityHistories<T>>();
+        app.init_resource::<EntityHistories<T>>()
+            .init_resource::<HistoryForgetters>()
+            .insert_resource(HistoryCompression(Arc::clone(&self.compression)));
+        app.world
+            .resource_mut::<HistoryForgetters>()
+            .0
+            .push(forget_history::<T>);
 
         let systems = (
             add_new_histories::<T>,
@@ -152,21 +194,187 @@ impl EntityCommand for SetTimeParent {
 }
 
 /// This is a drop-in replacement for regular despawn. It also affects the time hierarchy.
+///
+/// `RewindableDespawn` entities get `Parked` instead.
 pub struct Despawn;
 
 impl EntityCommand for Despawn {
     fn apply(self, entity: Entity, world: &mut World) {
-        if let Some(TimeParent(e_parent)) = world.entity_mut(entity).take::<TimeParent>() {
-            let mut parent = world.entity_mut(e_parent);
-            parent.get_mut::<TimeChildren>().unwrap().0.remove(&entity);
+        let Some(e) = world.get_entity(entity) else {
+            return;
+        };
+        if e.contains::<RewindableDespawn>() {
+            park(world, entity);
+        } else {
+            despawn_in_time_hierarchy(world, entity, false);
+        }
+    }
+}
+
+/// Takes the entity out of the time hierarchy and forgets its history, without despawning it.
+/// It's like a brand new entity afterwards, so it can be reused for something else.
+pub struct Recycle;
+
+impl EntityCommand for Recycle {
+    fn apply(self, entity: Entity, world: &mut World) {
+        if world.get_entity(entity).is_none() {
+            return;
+        }
+        detach_time_parent(world, entity);
+        if let Some(TimeChildren(children)) = world.entity_mut(entity).take::<TimeChildren>() {
+            for e_child in children {
+                if let Some(mut child) = world.get_entity_mut(e_child) {
+                    child.remove::<TimeParent>();
+                }
+            }
+        }
+        world
+            .entity_mut(entity)
+            .remove::<(Rewind, RewindableDespawn, Parked)>();
+
+        let forgetters = world
+            .get_resource::<HistoryForgetters>()
+            .map(|f| f.0.clone())
+            .unwrap_or_default();
+        for forget in forgetters {
+            forget(world, entity);
+        }
+    }
+}
+
+fn detach_time_parent(world: &mut World, entity: Entity) {
+    if let Some(TimeParent(e_parent)) = world.entity_mut(entity).take::<TimeParent>() {
+        // the parent might have gone first
+        if let Some(mut children) = world.get_mut::<TimeChildren>(e_parent) {
+            children.0.remove(&entity);
+        }
+    }
+}
+
+fn despawn_in_time_hierarchy(world: &mut World, entity: Entity, recursive: bool) {
+    detach_time_parent(world, entity);
+    match recursive {
+        true => despawn_with_children_recursive(world, entity),
+        false => {
+            world.despawn(entity);
+        }
+    }
+}
+
+/// `CommandsExt::time_despawn` parks this entity instead of despawning it,
+/// so a `Rewind` can bring it back.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct RewindableDespawn;
+
+/// A `RewindableDespawn` entity that's been despawned. It's hidden, and its colliders and
+/// rigidbody are disabled. Its `TimeChildren` are parked along with it.
+///
+/// It comes back if it's rewound to before it was despawned, or gets despawned for real
+/// (along with its descendants) after `HISTORY_FRAMES`.
+///
+/// Systems that shouldn't touch dead things should filter these out.
+#[derive(Component, Debug, Clone)]
+pub struct Parked {
+    /// Frames since it was despawned, minus the ones it's been rewound.
+    pub frames: usize,
+    /// Visibility before it was parked.
+    pub visibility: Option<Visibility>,
+}
+
+fn park(world: &mut World, entity: Entity) {
+    if world.get::<Parked>(entity).is_some() {
+        return;
+    }
+
+    let visibility = world.get::<Visibility>(entity).copied();
+    if let Some(mut v) = world.get_mut::<Visibility>(entity) {
+        *v = Visibility::Hidden;
+    }
+    if world.get::<RigidBody>(entity).is_some() {
+        world.entity_mut(entity).insert(RigidBodyDisabled);
+    }
+    for e_collider in self_and_descendants(world, entity) {
+        if world.get::<Collider>(e_collider).is_some() {
+            world.entity_mut(e_collider).insert(ColliderDisabled);
+        }
+    }
+    world.entity_mut(entity).insert(Parked {
+        frames: 0,
+        visibility,
+    });
+
+    // these would be left floating around otherwise
+    let time_children = world
+        .get::<TimeChildren>(entity)
+        .map(|c| c.0.iter().copied().collect::<Vec<_>>())
+        .unwrap_or_default();
+    for e_child in time_children {
+        if world.get_entity(e_child).is_some() {
+            park(world, e_child);
+        }
+    }
+}
+
+fn unpark(world: &mut World, entity: Entity) {
+    let Some(parked) = world.entity_mut(entity).take::<Parked>() else {
+        return;
+    };
+
+    if let (Some(visibility), Some(mut v)) =
+        (parked.visibility, world.get_mut::<Visibility>(entity))
+    {
+        *v = visibility;
+    }
+    world.entity_mut(entity).remove::<RigidBodyDisabled>();
+    for e_collider in self_and_descendants(world, entity) {
+        world.entity_mut(e_collider).remove::<ColliderDisabled>();
+    }
+}
+
+fn self_and_descendants(world: &World, entity: Entity) -> Vec<Entity> {
+    let mut entities = vec![entity];
+    let mut i = 0;
+    while let Some(&e) = entities.get(i) {
+        if let Some(children) = world.get::<Children>(e) {
+            entities.extend(children.iter().copied());
+        }
+        i += 1;
+    }
+    entities
+}
+
+/// Brings back `Parked` entities once they've been rewound far enough,
+/// and gets rid of the ones that have been waiting too long.
+pub fn update_parked(
+    mut commands: Commands,
+    mut query: Query<(Entity, &mut Parked, Option<&Rewind>)>,
+) {
+    for (entity, mut parked, rewind) in query.iter_mut() {
+        match rewind {
+            Some(rewind) => {
+                parked.frames = parked.frames.saturating_sub(rewind.fps as usize);
+                if parked.frames == 0 {
+                    commands.add(move |world: &mut World| unpark(world, entity));
+                }
+            }
+            None => {
+                parked.frames += 1;
+                if parked.frames >= HISTORY_FRAMES {
+                    commands.add(move |world: &mut World| {
+                        if world.get_entity(entity).is_some() {
+                            despawn_in_time_hierarchy(world, entity, true);
+                        }
+                    });
+                }
+            }
         }
-        world.entity_mut(entity).despawn();
     }
 }
 
 pub trait CommandsExt {
     fn set_time_parent(&mut self, parent: Entity);
     fn time_despawn(&mut self);
+    fn time_recycle(&mut self);
 }
 
 impl<'w, 's, 'a> CommandsExt for EntityCommands<'a> {
@@ -177,9 +385,15 @@ impl<'w, 's, 'a> CommandsExt for EntityCommands<'a> {
 
     /// Despawns the entity and resolves the time hierarchy.
     /// **Prefer this over `despawn`.**
+    ///
+    /// `RewindableDespawn` entities get `Parked` instead.
     fn time_despawn(&mut self) {
         self.add(Despawn);
-        self.despawn();
+    }
+
+    /// Clears the entity's time hierarchy and history, for reusing it. See `Recycle`.
+    fn time_recycle(&mut self) {
+        self.add(Recycle);
     }
 }
 
@@ -240,6 +454,21 @@ pub fn update_frame_index(mut frame_index: ResMut<Frame>) {
 #[derive(Resource, Debug)]
 pub struct EntityHistories<T: Component>(HashMap<Entity, History<T>>);
 
+/// Throws out an entity's `EntityHistories<T>`, for every `T` with a `RewindComponentPlugin`.
+#[derive(Resource, Default)]
+pub struct HistoryForgetters(pub Vec<fn(&mut World, Entity)>);
+
+fn forget_history<T: Component>(world: &mut World, entity: Entity) {
+    let forgot = world
+        .resource_mut::<EntityHistories<T>>()
+        .0
+        .remove(&entity)
+        .is_some();
+    if forgot {
+        world.resource_mut::<TrackedEntities>().forget(entity);
+    }
+}
+
 impl<T: Component> Default for EntityHistories<T> {
     fn default() -> Self {
         Self(HashMap::default())
@@ -251,9 +480,9 @@ impl<T: Component> Default for EntityHistories<T> {
 pub struct History<T: Component> {
     /// `Timestamp`s.
     pub frames: VecDeque<Timestamp>,
-    /// `Component`s. Each value of a `Timestamp::Existent` refers to a state of the component from this deque,
-    /// where the component at earlier timestamps is located at the front of the deque.
-    pub components: VecDeque<T>,
+    /// `Component`s. Each value of a `Timestamp::Existent` refers to a state of the component from this buffer,
+    /// where the component at earlier timestamps is located at the front of the buffer.
+    pub components: ComponentBuffer<T>,
     /// How frames are being stored in `History.frames`. The history will begin forgetting frames
     /// after reaching `History::MAX_STORAGE_FRAMES`, and the storage state will change permanently after this point.
     pub storage_state: HistoryStorageState,
@@ -274,14 +503,14 @@ pub enum HistoryStorageState {
 
 impl<T: Component> History<T> {
     /// The maximum number of frames to store before forgetting values.
-    const MAX_STORAGE_FRAMES: usize = 600;
+    const MAX_STORAGE_FRAMES: usize = HISTORY_FRAMES;
 }
 
 impl<T: Component> Default for History<T> {
     fn default() -> Self {
         Self {
             frames: VecDeque::with_capacity(Self::MAX_STORAGE_FRAMES),
-            components: VecDeque::new(),
+            components: ComponentBuffer::default(),
             storage_state: HistoryStorageState::Growing,
             rendered_frame: 0,
         }
@@ -302,7 +531,10 @@ pub enum Timestamp {
 }
 
 /// Forgets the oldest frame in a `History` if it's out of memory space.
-pub fn retire_frame<T: Component>(mut timestamps: ResMut<EntityHistories<T>>) {
+pub fn retire_frame<T: Component + Clone>(
+    mut timestamps: ResMut<EntityHistories<T>>,
+    compression: Res<HistoryCompression<T>>,
+) {
     for history in timestamps
         .0
         .values_mut()
@@ -318,7 +550,7 @@ pub fn retire_frame<T: Component>(mut timestamps: ResMut<EntityHistories<T>>) {
                 new_frame;
             // different frame time => different component state
             if old_frame_time != *new_frame_time {
-                history.components.pop_front();
+                history.components.pop_front(&*compression.0);
             }
         }
 
@@ -329,11 +561,13 @@ pub fn retire_frame<T: Component>(mut timestamps: ResMut<EntityHistories<T>>) {
 /// Adds histories for `Entity`s containing `T`.
 pub fn add_new_histories<T: Component>(
     mut histories: ResMut<EntityHistories<T>>,
+    mut tracked: ResMut<TrackedEntities>,
     query: Query<Entity, With<T>>,
 ) {
     for entity in query.iter() {
         if histories.0.get(&entity).is_none() {
             histories.0.insert(entity, History::default());
+            *tracked.0.entry(entity).or_default() += 1;
         }
     }
 }
@@ -342,9 +576,11 @@ pub fn add_new_histories<T: Component>(
 pub fn save_frame<T: Component + Clone>(
     mut histories: ResMut<EntityHistories<T>>,
     frame_time: Res<Frame>,
+    compression: Res<HistoryCompression<T>>,
     t_query: Query<Ref<T>>,
     rewound_query: Query<(), With<Rewind>>,
 ) {
+    let compression = &*compression.0;
     for (entity, history) in histories.0.iter_mut() {
         // entities being rewound aren't included in history
         if rewound_query.get(*entity).is_ok() {
@@ -354,10 +590,17 @@ pub fn save_frame<T: Component + Clone>(
         let timestamp = match t_query.get(*entity) {
             // component exists
             Ok(t) => {
-                match t.is_changed() {
+                // modified, but not enough to be worth storing
+                let same = t.is_changed()
+                    && matches!(history.frames.back(), Some(Timestamp::Existent(..)))
+                    && history
+                        .components
+                        .back(compression)
+                        .is_some_and(|prev| compression.same(&prev, &t));
+                match t.is_changed() && !same {
                     // exists and modified; change the component
                     true => {
-                        history.components.push_back(t.clone());
+                        history.components.push_back(t.clone(), compression);
                         Timestamp::Existent(frame_time.0)
                     }
                     false => {
@@ -392,14 +635,19 @@ pub fn save_frame<T: Component + Clone>(
 /// All values have been either rewound or forgotten.
 /// A new history is guaranteed to be stored with its initial value
 /// so it won't be deleted.
-pub fn clear_unused_histories<T: Component>(
+pub fn clear_unused_histories<T: Component + Clone>(
     mut histories: ResMut<EntityHistories<T>>,
+    mut tracked: ResMut<TrackedEntities>,
     entity_query: Query<Entity>,
     t_query: Query<(), With<T>>,
 ) {
     histories.0.retain(|entity, history| {
-        entity_query.get(*entity).is_ok()
-            && (!history.components.is_empty() || t_query.get(*entity).is_ok())
+        let keep = entity_query.get(*entity).is_ok()
+            && (!history.components.is_empty() || t_query.get(*entity).is_ok());
+        if !keep {
+            tracked.forget(*entity);
+        }
+        keep
     });
 }
 
@@ -407,14 +655,18 @@ pub fn clear_unused_histories<T: Component>(
 ///
 /// This is because the latest component in storage is already on the entity
 /// and rewinding would cause the same one to be copied.
-pub fn initialize_rewinds<T: Component>(
+pub fn initialize_rewinds<T: Component + Clone>(
     query: Query<Entity, Added<Rewind>>,
     mut histories: ResMut<EntityHistories<T>>,
+    compression: Res<HistoryCompression<T>>,
 ) {
     for entity in query.iter() {
-        let history = histories.0.get_mut(&entity).unwrap();
+        // it's only being rewound for some other component
+        let Some(history) = histories.0.get_mut(&entity) else {
+            continue;
+        };
         if let Some(Timestamp::Existent(..)) = history.frames.back() {
-            history.components.pop_back();
+            history.components.pop_back(&*compression.0);
         }
     }
 }
@@ -436,9 +688,13 @@ pub fn rewind<T: Component + Clone>(
     query: Query<(Entity, &Rewind, Option<&OutOfHistory>)>,
     t_query: Query<&T, With<Rewind>>,
     mut histories: ResMut<EntityHistories<T>>,
+    compression: Res<HistoryCompression<T>>,
 ) {
+    let compression = &*compression.0;
     for (entity, rewind, out_of_history) in query.iter() {
-        let history = histories.0.get_mut(&entity).unwrap();
+        let Some(history) = histories.0.get_mut(&entity) else {
+            continue;
+        };
         // need to track this in a variable instead of query since buffers aren't updated within system
         let mut despawned = false;
         // process each frame per `fps`
@@ -453,7 +709,7 @@ pub fn rewind<T: Component + Clone>(
                         commands.entity(entity).insert(
                             history
                                 .components
-                                .pop_back()
+                                .pop_back(compression)
                                 .expect("No past components in frame queue."),
                         );
                         history.rendered_frame = frame;
@@ -500,7 +756,7 @@ pub fn rewind<T: Component + Clone>(
             // or a new one that it rewound to
             if !despawned {
                 if let Ok(t) = t_query.get(entity) {
-                    history.components.push_back(t.clone());
+                    history.components.push_back(t.clone(), compression);
                     // make sure at least one frame exists to accompany this component
                     // otherwise we get errors
                     if history.frames.is_empty() {
@@ -513,6 +769,86 @@ pub fn rewind<T: Component + Clone>(
     }
 }
 
+/// Rewinds everything by `seconds`, except for `RewindExempt` entities.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct GlobalRewindEvent {
+    pub seconds: f32,
+}
+
+/// Left alone by `GlobalRewindEvent`s, along with all of its descendants.
+/// Cameras and UI are always left alone.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct RewindExempt;
+
+/// Every entity with a `History`, and how many it has.
+#[derive(Resource, Debug, Default)]
+pub struct TrackedEntities(pub HashMap<Entity, usize>);
+
+impl TrackedEntities {
+    fn forget(&mut self, entity: Entity) {
+        if let Some(count) = self.0.get_mut(&entity) {
+            *count -= 1;
+            if *count == 0 {
+                self.0.remove(&entity);
+            }
+        }
+    }
+}
+
+/// Entities still being rewound by the last `GlobalRewindEvent`.
+#[derive(Resource, Debug, Default)]
+pub struct GlobalRewind(pub HashSet<Entity>);
+
+impl GlobalRewind {
+    pub fn is_active(&self) -> bool {
+        !self.0.is_empty()
+    }
+}
+
+/// Run condition for while a `GlobalRewindEvent` is going.
+pub fn global_rewind_active(global_rewind: Option<Res<GlobalRewind>>) -> bool {
+    global_rewind.is_some_and(|r| r.is_active())
+}
+
+pub fn start_global_rewinds(
+    mut commands: Commands,
+    mut events: EventReader<GlobalRewindEvent>,
+    tracked: Res<TrackedEntities>,
+    mut global_rewind: ResMut<GlobalRewind>,
+    exempt_query: Query<(), Or<(With<RewindExempt>, With<Camera>, With<Node>)>>,
+    parent_query: Query<&Parent>,
+) {
+    for GlobalRewindEvent { seconds } in events.read() {
+        let frames = (seconds / FIXED_TIMESTEP_SECS).round() as u32;
+        info!("Rewinding {} frames.", frames);
+
+        for &entity in tracked.0.keys() {
+            let exempt = exempt_query.contains(entity)
+                || parent_query
+                    .iter_ancestors(entity)
+                    .any(|e| exempt_query.contains(e));
+            if exempt {
+                continue;
+            }
+            if let Some(mut e) = commands.get_entity(entity) {
+                e.insert(Rewind {
+                    frames,
+                    fps: GLOBAL_REWIND_FPS,
+                });
+                global_rewind.0.insert(entity);
+            }
+        }
+    }
+}
+
+/// Lets go of entities that have finished rewinding.
+pub fn update_global_rewind(
+    mut global_rewind: ResMut<GlobalRewind>,
+    rewind_query: Query<(), With<Rewind>>,
+) {
+    global_rewind.0.retain(|&e| rewind_query.contains(e));
+}
+
 // god, this was painful
 #[cfg(test)]
 mod tests {
@@ -768,4 +1104,202 @@ mod tests {
 
         assert!(app.world.get_entity(e).is_none());
     }
+
+    #[test]
+    fn rewindable_despawn() {
+        let mut app = mock_app();
+        add_mutations(&mut app);
+
+        let e_parent = app.world.spawn(MockComponent::default()).id();
+        let e = app
+            .world
+            .spawn((
+                MockComponent::default(),
+                RewindableDespawn,
+                Visibility::Visible,
+                RigidBody::Dynamic,
+                Collider::ball(1.0),
+            ))
+            .id();
+        let e_collider = app.world.spawn(Collider::ball(1.0)).set_parent(e).id();
+        SetTimeParent { parent: e_parent }.apply(e, &mut app.world);
+
+        app.update();
+        app.update();
+
+        // shattered. or whatever
+        Despawn.apply(e, &mut app.world);
+        app.update();
+        app.update();
+
+        assert!(app.world.get_entity(e).is_some(), "Entity was despawned.");
+        assert!(
+            app.world.get::<Parked>(e).is_some(),
+            "Entity wasn't parked."
+        );
+        assert_eq!(
+            app.world.get::<Visibility>(e),
+            Some(&Visibility::Hidden),
+            "Parked entity wasn't hidden."
+        );
+        assert!(
+            app.world.get::<ColliderDisabled>(e_collider).is_some(),
+            "Parked entity's colliders weren't disabled."
+        );
+        let parked_value = app.world.get::<MockComponent>(e).unwrap().0;
+
+        // goes through the parent's `TimeChildren`
+        app.world.entity_mut(e_parent).insert(Rewind {
+            frames: 4,
+            ..Default::default()
+        });
+        for _ in 0..5 {
+            app.update();
+        }
+
+        assert!(
+            app.world.get::<Parked>(e).is_none(),
+            "Rewind didn't bring the entity back."
+        );
+        assert_eq!(
+            app.world.get::<Visibility>(e),
+            Some(&Visibility::Visible),
+            "Visibility wasn't restored."
+        );
+        assert!(
+            app.world.get::<RigidBodyDisabled>(e).is_none()
+                && app.world.get::<ColliderDisabled>(e_collider).is_none(),
+            "Physics weren't restored."
+        );
+        assert!(
+            app.world.get::<MockComponent>(e).unwrap().0 < parked_value,
+            "Components weren't rewound."
+        );
+
+        // works like normal again
+        let value = app.world.get::<MockComponent>(e).unwrap().0;
+        app.update();
+        assert_eq!(
+            app.world.get::<MockComponent>(e).unwrap().0,
+            value + 1,
+            "Entity didn't keep updating."
+        );
+
+        // nobody comes to save it this time
+        Despawn.apply(e, &mut app.world);
+        for _ in 0..=HISTORY_FRAMES {
+            app.update();
+        }
+        assert!(
+            app.world.get_entity(e).is_none() && app.world.get_entity(e_collider).is_none(),
+            "Entity wasn't despawned after its history expired."
+        );
+        assert!(
+            !app.world
+                .get::<TimeChildren>(e_parent)
+                .unwrap()
+                .0
+                .contains(&e),
+            "Entity was left in `TimeChildren`."
+        );
+    }
+
+    #[test]
+    fn recycle() {
+        let mut app = mock_app();
+
+        let e_parent = app.world.spawn(MockComponent::default()).id();
+        let e = app.world.spawn(MockComponent::default()).id();
+        let e_child = app.world.spawn_empty().id();
+        SetTimeParent { parent: e_parent }.apply(e, &mut app.world);
+        SetTimeParent { parent: e }.apply(e_child, &mut app.world);
+        app.update();
+
+        Recycle.apply(e, &mut app.world);
+        assert!(
+            app.world.get::<TimeParent>(e).is_none()
+                && !app
+                    .world
+                    .get::<TimeChildren>(e_parent)
+                    .unwrap()
+                    .0
+                    .contains(&e),
+            "Recycled entity is still a time child."
+        );
+        assert!(
+            app.world.get::<TimeParent>(e_child).is_none(),
+            "Recycled entity still has time children."
+        );
+        assert!(
+            !app.world
+                .resource::<EntityHistories<MockComponent>>()
+                .0
+                .contains_key(&e),
+            "Recycled entity kept its history."
+        );
+        assert!(
+            app.world.get::<MockComponent>(e).is_some(),
+            "Recycling took the components."
+        );
+    }
+
+    #[test]
+    fn global_rewind() {
+        let mut app = mock_app();
+        add_mutations(&mut app);
+
+        let e = app.world.spawn(MockComponent::default()).id();
+        let e_exempt = app
+            .world
+            .spawn((MockComponent::default(), RewindExempt))
+            .id();
+        let e_exempt_child = app
+            .world
+            .spawn(MockComponent::default())
+            .set_parent(e_exempt)
+            .id();
+
+        for _ in 0..4 {
+            app.update();
+        }
+        assert_eq!(
+            app.world.resource::<TrackedEntities>().0.len(),
+            3,
+            "Didn't track every entity with a history."
+        );
+
+        app.world.send_event(GlobalRewindEvent {
+            seconds: FIXED_TIMESTEP_SECS * 2.0,
+        });
+        app.update();
+
+        assert!(
+            app.world.resource::<GlobalRewind>().is_active(),
+            "Global rewind didn't start."
+        );
+        assert!(
+            app.world.get::<Rewind>(e).is_some(),
+            "Entity wasn't rewound."
+        );
+        assert!(
+            app.world.get::<Rewind>(e_exempt).is_none()
+                && app.world.get::<Rewind>(e_exempt_child).is_none(),
+            "Exempt entity was rewound."
+        );
+
+        for _ in 0..3 {
+            app.update();
+        }
+        assert!(
+            !app.world.resource::<GlobalRewind>().is_active(),
+            "Global rewind didn't end."
+        );
+
+        app.world.despawn(e);
+        app.update();
+        assert!(
+            !app.world.resource::<TrackedEntities>().0.contains_key(&e),
+            "Despawned entity is still tracked."
+        );
+    }
 }
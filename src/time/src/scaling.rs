@@ -1,10 +1,16 @@
-use bevy::prelude::*;
+use bevy::{
+    audio::{AudioPlaySet, AudioSinkPlayback, PlaybackMode},
+    ecs::entity::EntityHashSet,
+    prelude::*,
+};
 use bevy_rapier3d::{
     dynamics::RigidBody,
     prelude::{PhysicsSet, Velocity},
 };
 use grin_util::numbers::{MulStack, MulStackError};
 
+use crate::Rewind;
+
 #[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
 pub enum TimeScaleSet {
     PreScale,
@@ -34,9 +40,25 @@ impl Plugin for TimeScalePlugin {
             PostUpdate,
             apply_deferred.in_set(TimeScaleSet::PreScaleFlush),
         )
+        .init_resource::<RewindAudio>()
+        .init_resource::<GlobalTimeScale>()
+        .add_systems(
+            PostUpdate,
+            (
+                (scale_audio::<AudioSink>, scale_audio::<SpatialAudioSink>).before(AudioPlaySet),
+                scale_animations,
+                scale_velocities,
+            )
+                .in_set(TimeScaleSet::Scale),
+        )
         .add_systems(
             PostUpdate,
-            (scale_audio, scale_animations, scale_velocities).in_set(TimeScaleSet::Scale),
+            apply_global_time_scale
+                .in_set(TimeScaleSet::Scale)
+                .before(scale_audio::<AudioSink>)
+                .before(scale_audio::<SpatialAudioSink>)
+                .before(scale_animations)
+                .before(scale_velocities),
         )
         .add_systems(Last, write_time_scales);
     }
@@ -82,6 +104,59 @@ impl From<&TimeScale> for f32 {
     }
 }
 
+/// A `TimeScale` multiplier for everything at once.
+#[derive(Resource, Debug)]
+pub struct GlobalTimeScale {
+    pub scale: f32,
+    /// What the scale is right now.
+    pub applied: Option<f32>,
+    /// Entities that have `applied` in their `TimeScale`.
+    pub scaled: EntityHashSet,
+}
+
+impl Default for GlobalTimeScale {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            applied: None,
+            scaled: EntityHashSet::default(),
+        }
+    }
+}
+
+/// Puts `GlobalTimeScale` on every `TimeScale`, and takes the old one off when it changes.
+pub fn apply_global_time_scale(
+    mut global: ResMut<GlobalTimeScale>,
+    mut scale_query: Query<(Entity, &mut TimeScale)>,
+) {
+    let global = &mut *global;
+    global.scaled.retain(|e| scale_query.contains(*e));
+
+    let scale = Some(global.scale).filter(|&scale| scale != 1.0);
+    if global.applied != scale {
+        if let Some(applied) = global.applied.take() {
+            for e_scaled in global.scaled.drain() {
+                let Ok((_, mut time_scale)) = scale_query.get_mut(e_scaled) else {
+                    continue;
+                };
+                if let Err(err) = time_scale.unscale_by(applied) {
+                    error!("{}", err);
+                }
+            }
+        }
+        global.applied = scale;
+    }
+
+    // new things show up all the time
+    if let Some(scale) = global.applied {
+        for (entity, mut time_scale) in scale_query.iter_mut() {
+            if global.scaled.insert(entity) {
+                time_scale.scale_by(scale);
+            }
+        }
+    }
+}
+
 /// Records the timescale for this frame in `TimeScale.memoed`.
 pub fn write_time_scales(mut scale_query: Query<&mut TimeScale>) {
     for mut time_scale in scale_query.iter_mut() {
@@ -166,10 +241,105 @@ pub fn scale_velocities(
     }
 }
 
-/// Adjusts audio speed with `TimeScale`.
-pub fn scale_audio(audio_query: Query<(&AudioSink, &TimeScale), Changed<TimeScale>>) {
-    for (audio, time_scale) in audio_query.iter() {
-        audio.set_speed(time_scale.into());
+/// Audio that plays at normal speed no matter the `TimeScale`. For dialogue blips and UI.
+#[derive(Component, Default, Debug, Clone, Copy)]
+pub struct TimeScaleImmune;
+
+/// Audio that `scale_audio` paused. Anything else that pauses a sink gets left alone.
+#[derive(Component, Default, Debug, Clone, Copy)]
+#[component(storage = "SparseSet")]
+pub struct TimeScalePaused;
+
+/// What happens to looping audio while its entity is being `Rewind`ed.
+#[derive(Resource, Debug, Clone, Copy)]
+pub enum RewindAudio {
+    Pause,
+    /// Plays at this speed, which sounds kind of like it's going backwards if it's low enough.
+    Pitch(f32),
+}
+
+impl Default for RewindAudio {
+    fn default() -> Self {
+        Self::Pitch(0.25)
+    }
+}
+
+/// Adjusts audio speed with `TimeScale`, on top of `PlaybackSettings::speed`.
+///
+/// Sinks are scaled by their parent's `TimeScale` as well, since they're usually attached to
+/// whatever's making the noise.
+pub fn scale_audio<S: Component + AudioSinkPlayback>(
+    mut commands: Commands,
+    rewind_audio: Res<RewindAudio>,
+    audio_query: Query<
+        (
+            Entity,
+            &S,
+            Option<&PlaybackSettings>,
+            Option<&TimeScale>,
+            Option<&Parent>,
+            Has<TimeScalePaused>,
+        ),
+        Without<TimeScaleImmune>,
+    >,
+    owner_query: Query<(Option<&TimeScale>, Has<Rewind>)>,
+) {
+    for (e_audio, sink, settings, time_scale, parent, paused_here) in audio_query.iter() {
+        let (owner_scale, owner_rewinding) = parent
+            .and_then(|p| owner_query.get(p.get()).ok())
+            .map_or((1.0, false), |(scale, rewinding)| {
+                (scale.map_or(1.0, f32::from), rewinding)
+            });
+        let rewinding = owner_rewinding || owner_query.get(e_audio).is_ok_and(|(_, r)| r);
+        let looping = settings.is_some_and(|s| s.mode == PlaybackMode::Loop);
+        let base_speed = settings.map_or(1.0, |s| s.speed);
+
+        if rewinding && looping {
+            match *rewind_audio {
+                RewindAudio::Pause => pause_sink(&mut commands, e_audio, sink, paused_here),
+                RewindAudio::Pitch(speed) => {
+                    resume_sink(&mut commands, e_audio, sink, paused_here);
+                    sink.set_speed(base_speed * speed);
+                }
+            }
+            continue;
+        }
+
+        let scale = time_scale.map_or(1.0, f32::from) * owner_scale;
+        if scale > 0.0 {
+            // it'd be paused from rewinding, or `0.0`
+            resume_sink(&mut commands, e_audio, sink, paused_here);
+            sink.set_speed(base_speed * scale);
+        } else {
+            // rodio doesn't like a speed of zero
+            pause_sink(&mut commands, e_audio, sink, paused_here);
+        }
+    }
+}
+
+/// Pauses the sink unless something else already did.
+fn pause_sink(
+    commands: &mut Commands,
+    e_audio: Entity,
+    sink: &impl AudioSinkPlayback,
+    paused_here: bool,
+) {
+    if !paused_here && !sink.is_paused() {
+        sink.pause();
+        commands.entity(e_audio).insert(TimeScalePaused);
+    }
+}
+
+/// Plays the sink if `scale_audio` was the one that paused it.
+fn resume_sink(
+    commands: &mut Commands,
+    e_audio: Entity,
+    sink: &impl AudioSinkPlayback,
+    paused_here: bool,
+) {
+    if paused_here {
+        sink.play();
+        commands.entity(e_audio).remove::<TimeScalePaused>();
     }
 }
 
@@ -231,54 +401,142 @@ mod tests {
         assert!(unscale.is_err(), "Bad `TimeScale::unscale` didn't error.");
     }
 
-    // no test here. I'm not sure what's up but the audiosink doesn't load on update.
-    // my assumption is that the audio file just isn't processed yet. not sure how
-    // to block the thread until that happens.
+    /// Stands in for `AudioSink`, which needs an actual audio device.
+    #[derive(Component, Default)]
+    struct MockSink(std::sync::Mutex<(f32, bool)>);
+
+    impl AudioSinkPlayback for MockSink {
+        fn volume(&self) -> f32 {
+            1.0
+        }
+
+        fn set_volume(&self, _volume: f32) {}
+
+        fn speed(&self) -> f32 {
+            self.0.lock().unwrap().0
+        }
+
+        fn set_speed(&self, speed: f32) {
+            self.0.lock().unwrap().0 = speed;
+        }
+
+        fn play(&self) {
+            self.0.lock().unwrap().1 = false;
+        }
+
+        fn pause(&self) {
+            self.0.lock().unwrap().1 = true;
+        }
+
+        fn is_paused(&self) -> bool {
+            self.0.lock().unwrap().1
+        }
+
+        fn stop(&self) {}
+
+        fn empty(&self) -> bool {
+            false
+        }
+    }
+
     #[test]
     fn audio_scale() {
-        /*let mut app = App::new();
-        app.add_plugins((
-            TaskPoolPlugin::default(),
-            AssetPlugin::default(),
-            AudioPlugin::default(),
-            TimeScalePlugin,
-        ));
+        let mut app = App::new();
+        app.init_resource::<RewindAudio>()
+            .add_systems(Update, scale_audio::<MockSink>);
 
-        let asset_server = app.world.resource::<AssetServer>();
+        let speed = |app: &App, e| app.world.get::<MockSink>(e).unwrap().speed();
+        let paused = |app: &App, e| app.world.get::<MockSink>(e).unwrap().is_paused();
 
-        let audio = app
+        let e_owner = app.world.spawn(TimeScale::default()).id();
+        let e_audio = app
             .world
-            .spawn((
-                AudioBundle {
-                    source: asset_server.load("audio/eightball-blip.ogg"),
-                    ..Default::default()
-                },
-                TimeScale {
-                    mulstack: vec![0.5],
-                    ..Default::default()
-                },
-            ))
+            .spawn((MockSink::default(), TimeScale::default()))
+            .set_parent(e_owner)
+            .id();
+        let e_immune = app
+            .world
+            .spawn((MockSink::default(), TimeScaleImmune))
+            .set_parent(e_owner)
+            .id();
+        let e_music = app
+            .world
+            .spawn((MockSink::default(), PlaybackSettings::LOOP.with_speed(2.0)))
+            .set_parent(e_owner)
             .id();
 
+        app.world
+            .get_mut::<TimeScale>(e_owner)
+            .unwrap()
+            .scale_by(0.5);
         app.update();
+        assert_eq!(speed(&app, e_audio), 0.5, "Audio didn't scale.");
+        assert_eq!(speed(&app, e_immune), 0.0, "Immune audio was scaled.");
+        assert_eq!(
+            speed(&app, e_music),
+            1.0,
+            "Audio didn't keep its own speed."
+        );
 
+        app.world
+            .get_mut::<TimeScale>(e_audio)
+            .unwrap()
+            .scale_by(0.5);
+        app.update();
+        assert_eq!(speed(&app, e_audio), 0.25, "Scales didn't stack.");
+
+        app.world
+            .get_mut::<TimeScale>(e_audio)
+            .unwrap()
+            .scale_by(0.0);
+        app.update();
+        assert!(paused(&app, e_audio), "Stopped time didn't pause.");
+
+        app.world
+            .get_mut::<TimeScale>(e_audio)
+            .unwrap()
+            .unscale_by(0.0)
+            .unwrap();
+        app.update();
+        assert!(
+            !paused(&app, e_audio),
+            "Didn't resume after time started again."
+        );
+
+        app.world.entity_mut(e_owner).insert(Rewind::default());
+        app.update();
         assert_eq!(
-            app.world.entity(audio).get::<AudioSink>().unwrap().speed(),
+            speed(&app, e_music),
             0.5,
-            "Audio didn't scale.",
+            "Looping audio didn't pitch down while rewinding."
+        );
+        assert_eq!(
+            speed(&app, e_audio),
+            0.25,
+            "One-shot audio shouldn't care about rewinding."
         );
 
-        let mut entity = app.world.entity_mut(audio);
-        let mut time_scale = entity.get_mut::<TimeScale>().unwrap();
-        time_scale.unscale_by(0.5).unwrap();
+        app.insert_resource(RewindAudio::Pause);
+        app.update();
+        assert!(
+            paused(&app, e_music),
+            "Looping audio didn't pause while rewinding."
+        );
 
+        app.world.entity_mut(e_owner).remove::<Rewind>();
         app.update();
+        assert!(
+            !paused(&app, e_music) && speed(&app, e_music) == 1.0,
+            "Looping audio didn't go back to normal."
+        );
 
-        assert_eq!(
-            app.world.entity(audio).get::<AudioSink>().unwrap().speed(),
-            1.0,
-            "Audio didn't unscale.",
-        );*/
+        // paused by whoever played it, not by time
+        app.world.get::<MockSink>(e_music).unwrap().pause();
+        app.update();
+        assert!(
+            paused(&app, e_music),
+            "Resumed audio that something else paused."
+        );
     }
 
     #[test]
@@ -436,5 +694,30 @@ mod tests {
         );
     }
 
+    #[test]
+    fn global_time_scale() {
+        let mut app = App::new();
+        app.init_resource::<GlobalTimeScale>()
+            .add_systems(Update, apply_global_time_scale);
+
+        let scale = |app: &App, e| f32::from(app.world.get::<TimeScale>(e).unwrap());
+
+        let e_old = app.world.spawn(TimeScale::default()).id();
+        app.world.resource_mut::<GlobalTimeScale>().scale = 0.5;
+        app.update();
+        let e_new = app.world.spawn(TimeScale::default()).id();
+        app.update();
+        assert_eq!(scale(&app, e_old), 0.5, "Didn't scale.");
+        assert_eq!(scale(&app, e_new), 0.5, "Didn't scale new entities.");
+
+        app.world.resource_mut::<GlobalTimeScale>().scale = 2.0;
+        app.update();
+        assert_eq!(scale(&app, e_old), 2.0, "Old scale wasn't taken off.");
+
+        app.world.resource_mut::<GlobalTimeScale>().scale = 1.0;
+        app.update();
+        assert_eq!(scale(&app, e_new), 1.0, "Didn't go back to normal.");
+    }
+
     // TODO: audio + anim testing... but I'm tired of tests
 }
@@ -0,0 +1,288 @@
+//! Areas where time runs at a different speed.
+
+use bevy::{ecs::entity::EntityHashSet, prelude::*, utils::HashMap};
+use bevy_rapier3d::prelude::*;
+use grin_util::query::distinguish_by_query;
+
+use crate::scaling::{
+    scale_animations, scale_velocities, TimeScale, TimeScalePlugin, TimeScaleSet,
+};
+
+/// How fast `TimeScale` moves toward a zone's scale, per second.
+pub const TIME_SCALE_ZONE_BLEND: f32 = 2.0;
+
+/// Makes `TimeScaleZone`s work. Needs `TimeScalePlugin` and rapier.
+pub struct TimeScaleZonePlugin;
+
+impl Plugin for TimeScaleZonePlugin {
+    fn build(&self, app: &mut App) {
+        assert!(app.is_plugin_added::<TimeScalePlugin>());
+
+        app.add_systems(
+            PostUpdate,
+            (
+                (init_time_scale_zones, track_time_scale_zones).in_set(TimeScaleSet::PreScale),
+                blend_time_scale_zones
+                    .in_set(TimeScaleSet::Scale)
+                    .before(scale_animations)
+                    .before(scale_velocities),
+            ),
+        );
+    }
+}
+
+/// Anything with a `TimeScale` inside of `shape` gets its `TimeScale` blended toward `scale`.
+///
+/// When zones overlap, the slowest one wins.
+#[derive(Component, Clone, Debug)]
+pub struct TimeScaleZone {
+    pub scale: f32,
+    pub shape: Collider,
+}
+
+/// Scripted `TimeScale`s that zones shouldn't mess with.
+/// Zones are blended back out while this is on.
+#[derive(Component, Debug, Default, Clone, Copy)]
+pub struct TimeScalePriority;
+
+/// The zones this entity is in. Removed once it's out of all of them and back to normal speed.
+#[derive(Component, Debug, Default)]
+pub struct InTimeScaleZones {
+    pub zones: EntityHashSet,
+    /// The multiplier that's in the `TimeScale` right now.
+    pub applied: Option<f32>,
+}
+
+/// Adds the sensor collider for new zones.
+pub fn init_time_scale_zones(
+    mut commands: Commands,
+    zone_query: Query<(Entity, &TimeScaleZone), Added<TimeScaleZone>>,
+) {
+    for (e_zone, zone) in zone_query.iter() {
+        commands.entity(e_zone).insert((
+            zone.shape.clone(),
+            Sensor,
+            ActiveEvents::COLLISION_EVENTS,
+            // zones usually don't have rigidbodies, and neither does a lot of the stuff going in them
+            ActiveCollisionTypes::all(),
+        ));
+    }
+}
+
+/// Keeps `InTimeScaleZones` up to date with what's going in and out of zones.
+pub fn track_time_scale_zones(
+    mut commands: Commands,
+    zone_query: Query<(), With<TimeScaleZone>>,
+    scale_query: Query<(), With<TimeScale>>,
+    mut in_zones_query: Query<&mut InTimeScaleZones>,
+    mut collision_events: EventReader<CollisionEvent>,
+) {
+    let mut entered = HashMap::<Entity, InTimeScaleZones>::new();
+
+    for collision_event in collision_events.read() {
+        let (entity_0, entity_1, started) = match collision_event {
+            CollisionEvent::Started(entity_0, entity_1, ..) => (*entity_0, *entity_1, true),
+            CollisionEvent::Stopped(entity_0, entity_1, ..) => (*entity_0, *entity_1, false),
+        };
+        let Some((e_zone, e_other)) = distinguish_by_query(&zone_query, entity_0, entity_1) else {
+            continue;
+        };
+        if zone_query.contains(e_other) || !scale_query.contains(e_other) {
+            continue;
+        }
+
+        let zones = match in_zones_query.get_mut(e_other) {
+            Ok(in_zones) => &mut in_zones.into_inner().zones,
+            Err(..) => &mut entered.entry(e_other).or_default().zones,
+        };
+        match started {
+            true => zones.insert(e_zone),
+            false => zones.remove(&e_zone),
+        };
+    }
+
+    for (entity, in_zones) in entered {
+        // might've been despawned on the way in
+        if let Some(mut e) = commands.get_entity(entity) {
+            e.insert(in_zones);
+        }
+    }
+}
+
+/// Moves `TimeScale`s toward the slowest zone they're in.
+///
+/// Zones that got despawned are forgotten here, since they might not send a `CollisionEvent`.
+pub fn blend_time_scale_zones(
+    mut commands: Commands,
+    time: Res<Time>,
+    zone_query: Query<&TimeScaleZone>,
+    mut query: Query<(
+        Entity,
+        &mut InTimeScaleZones,
+        &mut TimeScale,
+        Has<TimeScalePriority>,
+    )>,
+) {
+    for (entity, mut in_zones, mut time_scale, priority) in query.iter_mut() {
+        in_zones.zones.retain(|e| zone_query.contains(*e));
+
+        let target = match priority {
+            true => 1.0,
+            false => in_zones
+                .zones
+                .iter()
+                .map(|e| zone_query.get(*e).unwrap().scale)
+                .reduce(f32::min)
+                .unwrap_or(1.0),
+        };
+        let from = in_zones.applied.unwrap_or(1.0);
+        let step = TIME_SCALE_ZONE_BLEND * time.delta_seconds();
+        let to = match (target - from).abs() <= step {
+            true => target,
+            false => from + step.copysign(target - from),
+        };
+
+        if let Some(applied) = in_zones.applied.take() {
+            if let Err(err) = time_scale.unscale_by(applied) {
+                error!("{}", err);
+            }
+        }
+
+        if in_zones.zones.is_empty() && to == 1.0 {
+            commands.entity(entity).remove::<InTimeScaleZones>();
+        } else {
+            time_scale.scale_by(to);
+            in_zones.applied = Some(to);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::{
+        render::mesh::MeshPlugin,
+        scene::ScenePlugin,
+        time::{TimePlugin, TimeUpdateStrategy},
+    };
+
+    use super::*;
+
+    fn transform_bundle(transform: Transform) -> TransformBundle {
+        TransformBundle {
+            local: transform,
+            global: transform.into(),
+        }
+    }
+
+    fn scale(app: &App, entity: Entity) -> f32 {
+        app.world.get::<TimeScale>(entity).unwrap().into()
+    }
+
+    #[test]
+    fn time_scale_zone() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, AssetPlugin::default(), MeshPlugin, ScenePlugin))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .add_plugins((TimeScalePlugin, TimeScaleZonePlugin));
+
+        let e_zone = app
+            .world
+            .spawn((
+                TimeScaleZone {
+                    scale: 0.5,
+                    shape: Collider::cuboid(2.0, 2.0, 2.0),
+                },
+                transform_bundle(Transform::default()),
+            ))
+            .id();
+        let e = app
+            .world
+            .spawn((
+                Collider::ball(0.5),
+                transform_bundle(Transform::from_xyz(10.0, 0.0, 0.0)),
+            ))
+            .id();
+
+        // colliders get added to the physics world here
+        app.update();
+        app.update();
+        assert_eq!(scale(&app, e), 1.0, "Slowed outside of the zone.");
+
+        app.world
+            .entity_mut(e)
+            .insert(transform_bundle(Transform::default()));
+        let mut timeline = Vec::new();
+        for _ in 0..6 {
+            app.update();
+            timeline.push(scale(&app, e));
+        }
+        assert!(
+            timeline.windows(2).all(|w| w[1] <= w[0]),
+            "Didn't blend down smoothly. {:?}",
+            timeline,
+        );
+        assert!(timeline[0] > 0.5, "Jumped straight to the zone's scale. {:?}", timeline);
+        assert_eq!(
+            *timeline.last().unwrap(),
+            0.5,
+            "Didn't reach the zone's scale. {:?}",
+            timeline,
+        );
+
+        app.world
+            .entity_mut(e)
+            .insert(transform_bundle(Transform::from_xyz(10.0, 0.0, 0.0)));
+        timeline.clear();
+        for _ in 0..6 {
+            app.update();
+            timeline.push(scale(&app, e));
+        }
+        assert!(
+            timeline.windows(2).all(|w| w[1] >= w[0]),
+            "Didn't blend back up smoothly. {:?}",
+            timeline,
+        );
+        assert_eq!(
+            *timeline.last().unwrap(),
+            1.0,
+            "Didn't restore the scale on exit. {:?}",
+            timeline,
+        );
+        assert!(
+            app.world.get::<InTimeScaleZones>(e).is_none(),
+            "Kept `InTimeScaleZones` after leaving."
+        );
+
+        // zone disappears while inside
+        app.world
+            .entity_mut(e)
+            .insert(transform_bundle(Transform::default()));
+        for _ in 0..6 {
+            app.update();
+        }
+        assert_eq!(scale(&app, e), 0.5, "Didn't re-enter the zone.");
+        app.world.despawn(e_zone);
+        for _ in 0..6 {
+            app.update();
+        }
+        assert_eq!(
+            scale(&app, e),
+            1.0,
+            "Didn't restore the scale after the zone was despawned."
+        );
+        assert!(
+            app.world
+                .get::<TimeScale>(e)
+                .unwrap()
+                .mulstack
+                .multipliers
+                .is_empty(),
+            "Left multipliers behind."
+        );
+    }
+}
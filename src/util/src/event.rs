@@ -1,5 +1,6 @@
-use bevy::{ecs::system::BoxedSystem, prelude::*};
+use bevy::{ecs::system::BoxedSystem, prelude::*, utils::HashMap};
 use bevy_tweening::TweenCompleted;
+use itertools::Itertools;
 
 pub struct TweenEventPlugin;
 
@@ -23,6 +24,105 @@ pub fn despawn_tweens(mut commands: Commands, mut events: EventReader<TweenCompl
     }
 }
 
+/// Spawns things from data, like maps and saves, through their typed spawn events.
+pub struct SpawnRegistryPlugin;
+
+impl Plugin for SpawnRegistryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnRegistry>()
+            .add_event::<SpawnByNameEvent>()
+            .add_systems(Update, spawn_by_name);
+    }
+}
+
+pub type SpawnFn = Box<dyn Fn(&mut Commands, Transform) + Send + Sync>;
+
+/// Maps names to whatever spawns them.
+#[derive(Resource, Default)]
+pub struct SpawnRegistry {
+    pub spawn_fns: HashMap<String, SpawnFn>,
+}
+
+impl SpawnRegistry {
+    pub fn insert(
+        &mut self,
+        name: impl Into<String>,
+        spawn_fn: impl Fn(&mut Commands, Transform) + Send + Sync + 'static,
+    ) {
+        self.spawn_fns.insert(name.into(), Box::new(spawn_fn));
+    }
+
+    /// Spawns by sending `E`.
+    pub fn register<E: Event + From<Transform>>(&mut self, name: impl Into<String>) {
+        self.insert(name, |commands, transform| {
+            commands.add(move |world: &mut World| {
+                world.send_event(E::from(transform));
+            });
+        });
+    }
+
+    /// Registered names, sorted.
+    pub fn names(&self) -> Vec<&str> {
+        self.spawn_fns.keys().map(String::as_str).sorted().collect()
+    }
+
+    /// Returns `false` if nothing's called `name`.
+    pub fn spawn(&self, commands: &mut Commands, name: &str, transform: Transform) -> bool {
+        match self.spawn_fns.get(name) {
+            Some(spawn_fn) => {
+                spawn_fn(commands, transform);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+pub trait SpawnAppExt {
+    /// Lets `SpawnByNameEvent` spawn things by `name` with `E`.
+    fn register_spawn<E: Event + From<Transform>>(&mut self, name: impl Into<String>) -> &mut Self;
+
+    /// Lets `SpawnByNameEvent` spawn `T` by `name`.
+    fn register_spawnable<T: Spawnable>(&mut self, name: impl Into<String>) -> &mut Self
+    where
+        T::Event: From<Transform>,
+    {
+        self.register_spawn::<T::Event>(name)
+    }
+}
+
+impl SpawnAppExt for App {
+    fn register_spawn<E: Event + From<Transform>>(&mut self, name: impl Into<String>) -> &mut Self {
+        self.world
+            .get_resource_or_insert_with(SpawnRegistry::default)
+            .register::<E>(name);
+        self
+    }
+}
+
+/// Spawns whatever's called `name` in the `SpawnRegistry`.
+#[derive(Event, Clone, Debug)]
+pub struct SpawnByNameEvent {
+    pub name: String,
+    pub transform: Transform,
+}
+
+pub fn spawn_by_name(
+    mut commands: Commands,
+    registry: Res<SpawnRegistry>,
+    mut events: EventReader<SpawnByNameEvent>,
+) {
+    for SpawnByNameEvent { name, transform } in events.read() {
+        if !registry.spawn(&mut commands, name, *transform) {
+            error!(
+                "Nothing called `{}` to spawn. Known: {}.",
+                name,
+                registry.names().join(", "),
+            );
+        }
+    }
+}
+
 pub trait Spawnable {
     type Event: Event + Clone;
 
@@ -53,3 +153,51 @@ pub trait UntypedEvent: Event {
 
     fn typed<I>(&self) -> Self::TypedEvent<I>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Event, Clone, Debug, PartialEq)]
+    struct MockSpawnEvent(Transform);
+
+    impl From<Transform> for MockSpawnEvent {
+        fn from(transform: Transform) -> Self {
+            Self(transform)
+        }
+    }
+
+    #[test]
+    fn spawn_by_name() {
+        let mut app = App::new();
+        app.add_plugins(SpawnRegistryPlugin)
+            .add_event::<MockSpawnEvent>()
+            .register_spawn::<MockSpawnEvent>("mock");
+
+        let transform = Transform::from_xyz(1.0, 2.0, 3.0);
+        for name in ["mock", "missing"] {
+            app.world.send_event(SpawnByNameEvent {
+                name: name.into(),
+                transform,
+            });
+        }
+        app.update();
+
+        let events = app.world.resource::<Events<MockSpawnEvent>>();
+        let spawned = events
+            .get_reader()
+            .read(events)
+            .cloned()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spawned,
+            vec![MockSpawnEvent(transform)],
+            "Didn't spawn by name."
+        );
+        assert_eq!(
+            app.world.resource::<SpawnRegistry>().names(),
+            vec!["mock"],
+            "Wrong names."
+        );
+    }
+}
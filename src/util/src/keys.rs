@@ -91,3 +91,27 @@ impl InputExt for ButtonInput<KeyCode> {
         None
     }
 }
+
+// same thing but for gamepads. doesn't care which gamepad it is.
+pub trait GamepadInputExt {
+    fn any_gamepad_pressed(&self, button: GamepadButtonType) -> bool;
+    fn any_gamepad_just_pressed(&self, button: GamepadButtonType) -> bool;
+    fn any_gamepad_just_released(&self, button: GamepadButtonType) -> bool;
+}
+
+impl GamepadInputExt for ButtonInput<GamepadButton> {
+    /// Whether `button` is pressed on any gamepad.
+    fn any_gamepad_pressed(&self, button: GamepadButtonType) -> bool {
+        self.get_pressed().any(|b| b.button_type == button)
+    }
+
+    /// Whether `button` was just pressed on any gamepad.
+    fn any_gamepad_just_pressed(&self, button: GamepadButtonType) -> bool {
+        self.get_just_pressed().any(|b| b.button_type == button)
+    }
+
+    /// Whether `button` was just released on any gamepad.
+    fn any_gamepad_just_released(&self, button: GamepadButtonType) -> bool {
+        self.get_just_released().any(|b| b.button_type == button)
+    }
+}
@@ -4,5 +4,7 @@ pub mod event;
 pub mod keys;
 pub mod numbers;
 pub mod query;
+pub mod rng;
 pub mod spatial;
+pub mod state;
 pub mod vectors;
@@ -0,0 +1,120 @@
+//! Seeded randomness, so a run can be played back the same way.
+//!
+//! Each subsystem draws from its own stream with `GrinRng::fork`, so rolling one more time in
+//! one of them doesn't shift the rest.
+
+use std::env;
+
+use bevy::{prelude::*, utils::HashMap};
+use rand::Rng;
+use rand_pcg::Pcg32;
+
+/// Seed to use instead of a random one.
+pub const SEED_ENV_VAR: &str = "GRIN_SEED";
+
+/// `HumanoidBuild`, `HumanoidDominantHand`.
+pub const RNG_HUMANOID: &str = "humanoid";
+/// Shots that don't go exactly where they're aimed.
+pub const RNG_SPREAD: &str = "spread";
+/// Which way guns kick.
+pub const RNG_RECOIL: &str = "recoil";
+/// Pieces flying off of things that break.
+pub const RNG_DEBRIS: &str = "debris";
+/// Drops.
+pub const RNG_LOOT: &str = "loot";
+/// Dialogue blips.
+pub const RNG_BLIP: &str = "blip";
+/// How long patrols wait around.
+pub const RNG_PATROL: &str = "patrol";
+/// Colors, particles and other looks.
+pub const RNG_FX: &str = "fx";
+
+#[derive(Resource, Debug)]
+pub struct GrinRng {
+    seed: u64,
+    streams: HashMap<String, Pcg32>,
+}
+
+/// Seeded from `SEED_ENV_VAR` if it's set.
+impl Default for GrinRng {
+    fn default() -> Self {
+        Self::new(seed_from_env().unwrap_or_else(|| rand::thread_rng().gen()))
+    }
+}
+
+impl GrinRng {
+    pub fn new(seed: u64) -> Self {
+        info!("RNG seed: {}", seed);
+        Self {
+            seed,
+            streams: HashMap::default(),
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The stream for `name`. It's the same numbers every time for the same seed.
+    pub fn fork(&mut self, name: &str) -> &mut Pcg32 {
+        let seed = self.seed;
+        self.streams
+            .entry(name.to_owned())
+            .or_insert_with(|| Pcg32::new(seed, stream_id(name)))
+    }
+}
+
+/// FNV-1a, since `Hash` isn't stable between builds.
+fn stream_id(name: &str) -> u64 {
+    name.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub fn seed_from_env() -> Option<u64> {
+    env::var(SEED_ENV_VAR).ok()?.parse().ok()
+}
+
+/// Looks for `--seed <seed>`.
+pub fn seed_from_args(args: impl IntoIterator<Item = String>) -> Option<u64> {
+    let mut args = args.into_iter();
+    args.find(|arg| arg == "--seed")?;
+    args.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn streams() {
+        let rolls =
+            |rng: &mut GrinRng, name| (0..8).map(|_| rng.fork(name).gen()).collect::<Vec<u32>>();
+
+        let mut a = GrinRng::new(7);
+        let mut b = GrinRng::new(7);
+        // extra rolls in another stream don't matter
+        rolls(&mut b, RNG_SPREAD);
+        assert_eq!(
+            rolls(&mut a, RNG_HUMANOID),
+            rolls(&mut b, RNG_HUMANOID),
+            "Same seed gave different numbers."
+        );
+        assert_ne!(
+            rolls(&mut a, RNG_HUMANOID),
+            rolls(&mut a, RNG_SPREAD),
+            "Streams are the same."
+        );
+
+        assert_eq!(
+            seed_from_args(["grin", "--seed", "42"].map(String::from)),
+            Some(42),
+            "Didn't read the seed."
+        );
+        assert_eq!(
+            seed_from_args(["grin", "--seed"].map(String::from)),
+            None,
+            "Read a seed that isn't there."
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! What the game as a whole is doing.
+
+use bevy::prelude::*;
+
+/// Gameplay systems are paused during `Paused`, but not `Dead`.
+#[derive(States, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    Playing,
+    Paused,
+    Dead,
+}